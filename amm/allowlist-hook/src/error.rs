@@ -0,0 +1,66 @@
+//! Error types
+
+use num_derive::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    msg,
+    program_error::{PrintProgramError, ProgramError},
+};
+use thiserror::Error;
+
+/// Errors that may be returned by the allowlist hook program.
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum AllowlistError {
+    // 0
+    /// The allowlist, entry or validation account is already initialized.
+    #[error("AlreadyInUse")]
+    AlreadyInUse,
+    /// The program address provided doesn't match value generated by the program.
+    #[error("InvalidProgramAddress")]
+    InvalidProgramAddress,
+    /// The mint or allowlist authority must sign.
+    #[error("InvalidSignAccount")]
+    InvalidSignAccount,
+    /// The mint is not a Token-2022 mint whose transfer hook is this program.
+    #[error("InvalidMint")]
+    InvalidMint,
+    /// The owner of a token account of the transfer is not on the allowlist.
+    #[error("Wallet is not on the allowlist")]
+    NotAllowlisted,
+
+    // 5
+    /// The hook is not invoked by a Token-2022 transfer.
+    #[error("NotTransferring")]
+    NotTransferring,
+}
+
+impl From<AllowlistError> for ProgramError {
+    fn from(e: AllowlistError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+impl<T> DecodeError<T> for AllowlistError {
+    fn type_of() -> &'static str {
+        "Allowlist Error"
+    }
+}
+
+impl PrintProgramError for AllowlistError {
+    fn print<E>(&self)
+    where
+        E: 'static
+            + std::error::Error
+            + DecodeError<E>
+            + PrintProgramError
+            + num_traits::FromPrimitive,
+    {
+        match self {
+            AllowlistError::AlreadyInUse => msg!("Error: AlreadyInUse"),
+            AllowlistError::InvalidProgramAddress => msg!("Error: InvalidProgramAddress"),
+            AllowlistError::InvalidSignAccount => msg!("Error: Invalid Sign Account"),
+            AllowlistError::InvalidMint => msg!("Error: InvalidMint"),
+            AllowlistError::NotAllowlisted => msg!("Error: Wallet is not on the allowlist"),
+            AllowlistError::NotTransferring => msg!("Error: NotTransferring"),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Instruction types
+
+use crate::state::{allowlist_address, allowlist_entry_address};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+};
+use spl_transfer_hook_interface::get_extra_account_metas_address;
+use std::convert::TryInto;
+
+/// Instructions supported by the allowlist hook program, besides the transfer hook
+/// interface's `Execute`.
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum AllowlistInstruction {
+    ///   Creates the `Allowlist` account of a mint and its ExtraAccountMetaList, which lists
+    ///   the allowlist and the entries of the source and destination owners.
+    ///
+    ///   0. `[writable, signer]` Payer Account
+    ///   1. `[writable]` Allowlist Account, derived from `find_program_address(&[ALLOWLIST_SEED, mint])`
+    ///   2. `[writable]` ExtraAccountMetaList Account, derived from `get_extra_account_metas_address`
+    ///   3. `[]` Token-2022 mint Account, whose transfer hook is this program
+    ///   4. `[signer]` Mint authority Account, becomes the allowlist authority
+    ///   5. `[]` Sys program id
+    InitializeAllowlist,
+
+    ///   Adds a wallet to the allowlist of a mint.
+    ///
+    ///   0. `[writable, signer]` Allowlist authority Account, pays the rent
+    ///   1. `[]` Allowlist Account
+    ///   2. `[writable]` AllowlistEntry Account, derived from
+    ///      `find_program_address(&[ALLOWLIST_ENTRY_SEED, mint, wallet])`
+    ///   3. `[]` Sys program id
+    AddWallet {
+        /// wallet added
+        wallet: Pubkey,
+    },
+
+    ///   Removes a wallet from the allowlist of a mint, the entry rent goes back to the authority.
+    ///
+    ///   0. `[writable, signer]` Allowlist authority Account
+    ///   1. `[]` Allowlist Account
+    ///   2. `[writable]` AllowlistEntry Account
+    RemoveWallet {
+        /// wallet removed
+        wallet: Pubkey,
+    },
+}
+
+impl AllowlistInstruction {
+    /// Unpacks a byte buffer into a [AllowlistInstruction](enum.AllowlistInstruction.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        Ok(match tag {
+            0 => Self::InitializeAllowlist,
+            1 => Self::AddWallet {
+                wallet: Self::unpack_pubkey(rest)?,
+            },
+            2 => Self::RemoveWallet {
+                wallet: Self::unpack_pubkey(rest)?,
+            },
+            _ => return Err(ProgramError::InvalidInstructionData),
+        })
+    }
+
+    fn unpack_pubkey(input: &[u8]) -> Result<Pubkey, ProgramError> {
+        input
+            .get(..32)
+            .and_then(|slice| slice.try_into().ok())
+            .map(Pubkey::new_from_array)
+            .ok_or(ProgramError::InvalidInstructionData)
+    }
+
+    /// Packs a [AllowlistInstruction](enum.AllowlistInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(33);
+        match *self {
+            Self::InitializeAllowlist => {
+                buf.push(0);
+            }
+            Self::AddWallet { wallet } => {
+                buf.push(1);
+                buf.extend_from_slice(wallet.as_ref());
+            }
+            Self::RemoveWallet { wallet } => {
+                buf.push(2);
+                buf.extend_from_slice(wallet.as_ref());
+            }
+        }
+        buf
+    }
+}
+
+/// Creates an 'initialize_allowlist' instruction.
+pub fn initialize_allowlist(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    mint_authority: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(allowlist_address(program_id, mint).0, false),
+        AccountMeta::new(get_extra_account_metas_address(mint, program_id), false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(*mint_authority, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: AllowlistInstruction::InitializeAllowlist.pack(),
+    }
+}
+
+/// Creates an 'add_wallet' instruction.
+pub fn add_wallet(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    authority: &Pubkey,
+    wallet: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(allowlist_address(program_id, mint).0, false),
+        AccountMeta::new(allowlist_entry_address(program_id, mint, wallet).0, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: AllowlistInstruction::AddWallet { wallet: *wallet }.pack(),
+    }
+}
+
+/// Creates a 'remove_wallet' instruction.
+pub fn remove_wallet(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    authority: &Pubkey,
+    wallet: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(allowlist_address(program_id, mint).0, false),
+        AccountMeta::new(allowlist_entry_address(program_id, mint, wallet).0, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: AllowlistInstruction::RemoveWallet { wallet: *wallet }.pack(),
+    }
+}
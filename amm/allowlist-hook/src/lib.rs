@@ -0,0 +1,17 @@
+//! Reference KYC allowlist Token-2022 transfer hook. The issuer of a mint keeps an
+//! allowlist of wallets, and both the owner of the source and the owner of the destination
+//! token account of a transfer must be on it. Token accounts owned by the AMM authority,
+//! such as the pool vaults, are exempt intermediaries, so a swap only checks the end user.
+
+mod entrypoint;
+pub mod error;
+pub mod instruction;
+pub mod processor;
+pub mod state;
+#[cfg(test)]
+mod tests;
+
+// Export current solana-sdk types for downstream users who may also be building with a different solana-sdk version
+pub use solana_program;
+
+solana_program::declare_id!("FR6LsTDVFePtFJ9fetKRGpz63Mi59yxWNzJ2EDU5yFQ1");
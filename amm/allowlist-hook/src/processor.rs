@@ -0,0 +1,300 @@
+//! Program state processor
+
+use crate::{
+    error::AllowlistError,
+    instruction::AllowlistInstruction,
+    state::{
+        allowlist_address, allowlist_entry_address, extra_account_metas, Allowlist, AllowlistEntry,
+        ALLOWLIST_ENTRY_SEED, ALLOWLIST_SEED,
+    },
+};
+use raydium_amm::processor::AUTHORITY_AMM;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_option::COption,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
+};
+use spl_tlv_account_resolution::state::ExtraAccountMetaList;
+use spl_token_2022::{
+    extension::{
+        transfer_hook::{self, TransferHookAccount},
+        BaseStateWithExtensions, StateWithExtensions,
+    },
+    state::{Account as Token2022Account, Mint as Token2022Mint},
+};
+use spl_transfer_hook_interface::{
+    collect_extra_account_metas_signer_seeds, get_extra_account_metas_address_and_bump_seed,
+    instruction::{ExecuteInstruction, TransferHookInstruction},
+};
+
+/// Program state handler.
+pub struct Processor {}
+impl Processor {
+    /// Create a program derived account owned by this program, also when someone sent
+    /// lamports to its address beforehand
+    fn create_pda_account<'a>(
+        program_id: &Pubkey,
+        payer_info: &AccountInfo<'a>,
+        new_account_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        data_size: usize,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        if new_account_info.owner != system_program_info.key || new_account_info.data_len() != 0 {
+            return Err(AllowlistError::AlreadyInUse.into());
+        }
+        let required_lamports = Rent::get()?
+            .minimum_balance(data_size)
+            .max(1)
+            .saturating_sub(new_account_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    payer_info.key,
+                    new_account_info.key,
+                    required_lamports,
+                ),
+                &[
+                    payer_info.clone(),
+                    new_account_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(new_account_info.key, data_size as u64),
+            &[new_account_info.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(new_account_info.key, program_id),
+            &[new_account_info.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        )
+    }
+
+    /// Owner of a Token-2022 token account
+    fn token_account_owner(account_info: &AccountInfo) -> Result<Pubkey, ProgramError> {
+        let data = account_info.try_borrow_data()?;
+        Ok(StateWithExtensions::<Token2022Account>::unpack(&data)?
+            .base
+            .owner)
+    }
+
+    /// Processes an [InitializeAllowlist](enum.AllowlistInstruction.html).
+    pub fn process_initialize_allowlist(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer_info = next_account_info(account_info_iter)?;
+        let allowlist_info = next_account_info(account_info_iter)?;
+        let validation_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let mint_authority_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !payer_info.is_signer || !mint_authority_info.is_signer {
+            return Err(AllowlistError::InvalidSignAccount.into());
+        }
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // the issuer sets up the allowlist of a mint whose transfer hook is this program
+        if *mint_info.owner != spl_token_2022::id() {
+            return Err(AllowlistError::InvalidMint.into());
+        }
+        {
+            let data = mint_info.try_borrow_data()?;
+            let mint = StateWithExtensions::<Token2022Mint>::unpack(&data)?;
+            if transfer_hook::get_program_id(&mint) != Some(*program_id) {
+                return Err(AllowlistError::InvalidMint.into());
+            }
+            if mint.base.mint_authority != COption::Some(*mint_authority_info.key) {
+                return Err(AllowlistError::InvalidSignAccount.into());
+            }
+        }
+
+        let mint = mint_info.key;
+        let (expect_allowlist, bump) = allowlist_address(program_id, mint);
+        let (expect_validation, validation_bump) =
+            get_extra_account_metas_address_and_bump_seed(mint, program_id);
+        if *allowlist_info.key != expect_allowlist || *validation_info.key != expect_validation {
+            return Err(AllowlistError::InvalidProgramAddress.into());
+        }
+
+        Self::create_pda_account(
+            program_id,
+            payer_info,
+            allowlist_info,
+            system_program_info,
+            Allowlist::LEN,
+            &[ALLOWLIST_SEED, mint.as_ref(), &[bump]],
+        )?;
+        // the AMM holds pool tokens in vaults owned by its authority, see `Processor::authority_id`
+        let (amm_authority, _) = Pubkey::find_program_address(&[AUTHORITY_AMM], &raydium_amm::id());
+        *Allowlist::load_mut_checked(allowlist_info, program_id)? = Allowlist {
+            mint: *mint,
+            authority: *mint_authority_info.key,
+            amm_authority,
+            bump: bump as u64,
+            ..Default::default()
+        };
+
+        let extra_account_metas = extra_account_metas()?;
+        Self::create_pda_account(
+            program_id,
+            payer_info,
+            validation_info,
+            system_program_info,
+            ExtraAccountMetaList::size_of(extra_account_metas.len())?,
+            &collect_extra_account_metas_signer_seeds(mint, &[validation_bump]),
+        )?;
+        ExtraAccountMetaList::init::<ExecuteInstruction>(
+            &mut validation_info.try_borrow_mut_data()?,
+            &extra_account_metas,
+        )?;
+        msg!("allowlist of {}", mint);
+        Ok(())
+    }
+
+    /// Processes an [AddWallet](enum.AllowlistInstruction.html) or a
+    /// [RemoveWallet](enum.AllowlistInstruction.html).
+    pub fn process_update_wallet(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        wallet: &Pubkey,
+        remove: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let authority_info = next_account_info(account_info_iter)?;
+        let allowlist_info = next_account_info(account_info_iter)?;
+        let entry_info = next_account_info(account_info_iter)?;
+
+        let mint = Allowlist::load_mut_checked(allowlist_info, program_id)?.mint;
+        let allowlist = *Allowlist::load_checked(allowlist_info, program_id, &mint)?;
+        if !authority_info.is_signer || *authority_info.key != allowlist.authority {
+            return Err(AllowlistError::InvalidSignAccount.into());
+        }
+        let (expect_entry, bump) = allowlist_entry_address(program_id, &mint, wallet);
+        if *entry_info.key != expect_entry {
+            return Err(AllowlistError::InvalidProgramAddress.into());
+        }
+
+        if remove {
+            AllowlistEntry::load_checked(entry_info, program_id, &mint, wallet)?;
+            *AllowlistEntry::load_mut_checked(entry_info, program_id)? = AllowlistEntry::default();
+            let lamports = entry_info.lamports();
+            **entry_info.lamports.borrow_mut() = 0;
+            **authority_info.lamports.borrow_mut() = authority_info
+                .lamports()
+                .checked_add(lamports)
+                .ok_or(ProgramError::ArithmeticOverflow)?;
+            msg!("removed {} from the allowlist of {}", wallet, mint);
+        } else {
+            let system_program_info = next_account_info(account_info_iter)?;
+            if *system_program_info.key != solana_program::system_program::id() {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            Self::create_pda_account(
+                program_id,
+                authority_info,
+                entry_info,
+                system_program_info,
+                AllowlistEntry::LEN,
+                &[
+                    ALLOWLIST_ENTRY_SEED,
+                    mint.as_ref(),
+                    wallet.as_ref(),
+                    &[bump],
+                ],
+            )?;
+            *AllowlistEntry::load_mut_checked(entry_info, program_id)? = AllowlistEntry {
+                mint,
+                wallet: *wallet,
+                bump: bump as u64,
+                ..Default::default()
+            };
+            msg!("added {} to the allowlist of {}", wallet, mint);
+        }
+        Ok(())
+    }
+
+    /// Processes the transfer hook interface's `Execute`. The owners of the source and the
+    /// destination must be on the allowlist, unless they are the AMM authority: the vaults
+    /// are intermediaries, a swap checks the user on its other side.
+    pub fn process_execute(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let source_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let _authority_info = next_account_info(account_info_iter)?;
+        let validation_info = next_account_info(account_info_iter)?;
+        let allowlist_info = next_account_info(account_info_iter)?;
+        let source_entry_info = next_account_info(account_info_iter)?;
+        let destination_entry_info = next_account_info(account_info_iter)?;
+
+        // only Token-2022 sets the transferring flag of the source during a transfer
+        {
+            let data = source_info.try_borrow_data()?;
+            let source = StateWithExtensions::<Token2022Account>::unpack(&data)?;
+            if !bool::from(source.get_extension::<TransferHookAccount>()?.transferring) {
+                return Err(AllowlistError::NotTransferring.into());
+            }
+        }
+        ExtraAccountMetaList::check_account_infos::<ExecuteInstruction>(
+            accounts,
+            &TransferHookInstruction::Execute { amount }.pack(),
+            program_id,
+            &validation_info.try_borrow_data()?,
+        )?;
+        let allowlist = *Allowlist::load_checked(allowlist_info, program_id, mint_info.key)?;
+
+        for (token_account_info, entry_info) in [
+            (source_info, source_entry_info),
+            (destination_info, destination_entry_info),
+        ] {
+            let owner = Self::token_account_owner(token_account_info)?;
+            if owner == allowlist.amm_authority {
+                continue;
+            }
+            if AllowlistEntry::load_checked(entry_info, program_id, mint_info.key, &owner).is_err()
+            {
+                msg!("{} is not on the allowlist", owner);
+                return Err(AllowlistError::NotAllowlisted.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Processes an [Instruction](enum.AllowlistInstruction.html).
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+        if let Ok(TransferHookInstruction::Execute { amount }) =
+            TransferHookInstruction::unpack(input)
+        {
+            return Self::process_execute(program_id, accounts, amount);
+        }
+        match AllowlistInstruction::unpack(input)? {
+            AllowlistInstruction::InitializeAllowlist => {
+                Self::process_initialize_allowlist(program_id, accounts)
+            }
+            AllowlistInstruction::AddWallet { wallet } => {
+                Self::process_update_wallet(program_id, accounts, &wallet, false)
+            }
+            AllowlistInstruction::RemoveWallet { wallet } => {
+                Self::process_update_wallet(program_id, accounts, &wallet, true)
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! State transition types
+
+use crate::error::AllowlistError;
+use bytemuck::{from_bytes, from_bytes_mut, Pod, Zeroable};
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, seeds::Seed};
+use std::{
+    cell::{Ref, RefMut},
+    mem::size_of,
+};
+
+/// Seed of the `Allowlist` account of a mint
+pub const ALLOWLIST_SEED: &[u8] = b"allowlist";
+/// Seed of the `AllowlistEntry` account of a wallet
+pub const ALLOWLIST_ENTRY_SEED: &[u8] = b"allowlist_entry";
+
+/// Address of the `Allowlist` account of a mint
+pub fn allowlist_address(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ALLOWLIST_SEED, mint.as_ref()], program_id)
+}
+
+/// Address of the `AllowlistEntry` account of a wallet for a mint
+pub fn allowlist_entry_address(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    wallet: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ALLOWLIST_ENTRY_SEED, mint.as_ref(), wallet.as_ref()],
+        program_id,
+    )
+}
+
+/// Extra accounts of `Execute`, in order: the `Allowlist` account of the mint and the
+/// `AllowlistEntry` accounts of the source and destination owners, which need not exist
+pub fn extra_account_metas() -> Result<[ExtraAccountMeta; 3], ProgramError> {
+    let entry_of = |account_index| {
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: ALLOWLIST_ENTRY_SEED.to_vec(),
+                },
+                Seed::AccountKey { index: 1 },
+                // owner of the token account
+                Seed::AccountData {
+                    account_index,
+                    data_index: 32,
+                    length: 32,
+                },
+            ],
+            false,
+            false,
+        )
+    };
+    Ok([
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: ALLOWLIST_SEED.to_vec(),
+                },
+                Seed::AccountKey { index: 1 },
+            ],
+            false,
+            false,
+        )?,
+        // source token account
+        entry_of(0)?,
+        // destination token account
+        entry_of(2)?,
+    ])
+}
+
+/// Allowlist of a mint, one account per mint derived from
+/// `find_program_address(&[ALLOWLIST_SEED, mint])`
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Allowlist {
+    /// mint the allowlist applies to
+    pub mint: Pubkey,
+    /// adds and removes wallets, the mint authority at initialization
+    pub authority: Pubkey,
+    /// AMM authority, its token accounts are exempt from the allowlist
+    pub amm_authority: Pubkey,
+    /// bump seed of the account address
+    pub bump: u64,
+    /// padding
+    pub padding: [u64; 4],
+}
+unsafe impl Zeroable for Allowlist {}
+unsafe impl Pod for Allowlist {}
+
+impl Allowlist {
+    pub const LEN: usize = size_of::<Self>();
+
+    /// load_mut_checked
+    #[inline]
+    pub fn load_mut_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<RefMut<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if account.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(RefMut::map(account.try_borrow_mut_data()?, |data| {
+            from_bytes_mut(data)
+        }))
+    }
+
+    /// load_checked, also rejects an account that is not the allowlist of `mint`
+    #[inline]
+    pub fn load_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+        mint: &Pubkey,
+    ) -> Result<Ref<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if account.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let allowlist: Ref<Self> = Ref::map(account.try_borrow_data()?, |data| from_bytes(data));
+        if allowlist.mint != *mint {
+            return Err(AllowlistError::InvalidMint.into());
+        }
+        // the stored bump pins the account to its seeds
+        let address = Pubkey::create_program_address(
+            &[ALLOWLIST_SEED, mint.as_ref(), &[allowlist.bump as u8]],
+            program_id,
+        )
+        .map_err(|_| AllowlistError::InvalidProgramAddress)?;
+        if address != *account.key {
+            return Err(AllowlistError::InvalidProgramAddress.into());
+        }
+        Ok(allowlist)
+    }
+}
+
+/// Wallet on the allowlist of a mint, one account per wallet derived from
+/// `find_program_address(&[ALLOWLIST_ENTRY_SEED, mint, wallet])`. Removing the wallet
+/// closes the account.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AllowlistEntry {
+    /// mint of the allowlist
+    pub mint: Pubkey,
+    /// wallet allowed to own token accounts that send or receive the mint
+    pub wallet: Pubkey,
+    /// bump seed of the account address
+    pub bump: u64,
+    /// padding
+    pub padding: [u64; 2],
+}
+unsafe impl Zeroable for AllowlistEntry {}
+unsafe impl Pod for AllowlistEntry {}
+
+impl AllowlistEntry {
+    pub const LEN: usize = size_of::<Self>();
+
+    /// load_mut_checked
+    #[inline]
+    pub fn load_mut_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<RefMut<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if account.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(RefMut::map(account.try_borrow_mut_data()?, |data| {
+            from_bytes_mut(data)
+        }))
+    }
+
+    /// load_checked, also rejects an account that is not the entry of `wallet` for `mint`
+    #[inline]
+    pub fn load_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+        mint: &Pubkey,
+        wallet: &Pubkey,
+    ) -> Result<Ref<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if account.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let entry: Ref<Self> = Ref::map(account.try_borrow_data()?, |data| from_bytes(data));
+        if entry.mint != *mint || entry.wallet != *wallet {
+            return Err(AllowlistError::NotAllowlisted.into());
+        }
+        // the stored bump pins the account to its seeds
+        let address = Pubkey::create_program_address(
+            &[
+                ALLOWLIST_ENTRY_SEED,
+                mint.as_ref(),
+                wallet.as_ref(),
+                &[entry.bump as u8],
+            ],
+            program_id,
+        )
+        .map_err(|_| AllowlistError::InvalidProgramAddress)?;
+        if address != *account.key {
+            return Err(AllowlistError::InvalidProgramAddress.into());
+        }
+        Ok(entry)
+    }
+}
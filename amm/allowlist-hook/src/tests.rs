@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use solana_program::pubkey::Pubkey;
+
+    use crate::{
+        instruction::{self, AllowlistInstruction},
+        state::{
+            allowlist_address, allowlist_entry_address, extra_account_metas, Allowlist,
+            AllowlistEntry,
+        },
+    };
+
+    /// Test instruction data round trip
+    #[test]
+    fn test_instruction_pack_unpack() {
+        let wallet = Pubkey::new_unique();
+        for instruction in [
+            AllowlistInstruction::InitializeAllowlist,
+            AllowlistInstruction::AddWallet { wallet },
+            AllowlistInstruction::RemoveWallet { wallet },
+        ] {
+            let data = instruction.pack();
+            assert_eq!(AllowlistInstruction::unpack(&data).unwrap(), instruction);
+        }
+        assert!(AllowlistInstruction::unpack(&[]).is_err());
+        assert!(AllowlistInstruction::unpack(&[1; 16]).is_err());
+        assert!(AllowlistInstruction::unpack(&[3; 33]).is_err());
+    }
+
+    /// Test the allowlist and the entries of different wallets and mints have distinct addresses
+    #[test]
+    fn test_addresses() {
+        let program_id = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let other_mint = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let other_wallet = Pubkey::new_unique();
+
+        let allowlist = allowlist_address(&program_id, &mint).0;
+        let entry = allowlist_entry_address(&program_id, &mint, &wallet).0;
+        assert_ne!(allowlist, allowlist_address(&program_id, &other_mint).0);
+        assert_ne!(entry, allowlist);
+        assert_ne!(
+            entry,
+            allowlist_entry_address(&program_id, &mint, &other_wallet).0
+        );
+        assert_ne!(
+            entry,
+            allowlist_entry_address(&program_id, &other_mint, &wallet).0
+        );
+        assert_eq!(Allowlist::LEN, 32 * 3 + 8 * 5);
+        assert_eq!(AllowlistEntry::LEN, 32 * 2 + 8 * 3);
+    }
+
+    /// Test the extra accounts are the read-only allowlist and owner entries, and the builders
+    #[test]
+    fn test_extra_account_metas() {
+        let program_id = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+
+        let metas = extra_account_metas().unwrap();
+        assert_eq!(metas.len(), 3);
+        assert!(metas
+            .iter()
+            .all(|meta| !bool::from(meta.is_signer) && !bool::from(meta.is_writable)));
+
+        let init = instruction::initialize_allowlist(&program_id, &authority, &mint, &authority);
+        assert_eq!(init.accounts.len(), 6);
+        assert_eq!(
+            init.accounts[1].pubkey,
+            allowlist_address(&program_id, &mint).0
+        );
+        assert!(init.accounts[4].is_signer);
+        let add = instruction::add_wallet(&program_id, &mint, &authority, &wallet);
+        assert_eq!(add.accounts.len(), 4);
+        assert_eq!(
+            add.accounts[2].pubkey,
+            allowlist_entry_address(&program_id, &mint, &wallet).0
+        );
+        assert!(add.accounts[0].is_signer && add.accounts[2].is_writable);
+        let remove = instruction::remove_wallet(&program_id, &mint, &authority, &wallet);
+        assert_eq!(remove.accounts.len(), 3);
+        assert_eq!(remove.accounts[2].pubkey, add.accounts[2].pubkey);
+    }
+}
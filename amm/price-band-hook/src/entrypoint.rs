@@ -0,0 +1,23 @@
+//! Program entrypoint definitions
+
+#![cfg(not(feature = "no-entrypoint"))]
+
+use crate::{error::PriceBandError, processor::Processor};
+use solana_program::{
+    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult,
+    program_error::PrintProgramError, pubkey::Pubkey,
+};
+
+entrypoint!(process_instruction);
+fn process_instruction<'a>(
+    program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    if let Err(error) = Processor::process(program_id, accounts, instruction_data) {
+        // catch the error so we can print it
+        error.print::<PriceBandError>();
+        return Err(error);
+    }
+    Ok(())
+}
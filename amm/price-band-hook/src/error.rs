@@ -0,0 +1,74 @@
+//! Error types
+
+use num_derive::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    msg,
+    program_error::{PrintProgramError, ProgramError},
+};
+use thiserror::Error;
+
+/// Errors that may be returned by the price band hook program.
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum PriceBandError {
+    // 0
+    /// The price band or validation account is already initialized.
+    #[error("AlreadyInUse")]
+    AlreadyInUse,
+    /// The program address provided doesn't match value generated by the program.
+    #[error("InvalidProgramAddress")]
+    InvalidProgramAddress,
+    /// The mint or band authority must sign.
+    #[error("InvalidSignAccount")]
+    InvalidSignAccount,
+    /// The mint is not a Token-2022 mint whose transfer hook is this program.
+    #[error("InvalidMint")]
+    InvalidMint,
+    /// The band is empty.
+    #[error("InvalidInput")]
+    InvalidInput,
+
+    // 5
+    /// The trade context is not the AMM trade context of the mint.
+    #[error("InvalidTradeContext")]
+    InvalidTradeContext,
+    /// The swap executes outside the price band.
+    #[error("Swap price is out of the band")]
+    PriceOutOfBand,
+    /// The hook is not invoked by a Token-2022 transfer.
+    #[error("NotTransferring")]
+    NotTransferring,
+}
+
+impl From<PriceBandError> for ProgramError {
+    fn from(e: PriceBandError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+impl<T> DecodeError<T> for PriceBandError {
+    fn type_of() -> &'static str {
+        "Price Band Error"
+    }
+}
+
+impl PrintProgramError for PriceBandError {
+    fn print<E>(&self)
+    where
+        E: 'static
+            + std::error::Error
+            + DecodeError<E>
+            + PrintProgramError
+            + num_traits::FromPrimitive,
+    {
+        match self {
+            PriceBandError::AlreadyInUse => msg!("Error: AlreadyInUse"),
+            PriceBandError::InvalidProgramAddress => msg!("Error: InvalidProgramAddress"),
+            PriceBandError::InvalidSignAccount => msg!("Error: Invalid Sign Account"),
+            PriceBandError::InvalidMint => msg!("Error: InvalidMint"),
+            PriceBandError::InvalidInput => msg!("Error: InvalidInput"),
+            PriceBandError::InvalidTradeContext => msg!("Error: InvalidTradeContext"),
+            PriceBandError::PriceOutOfBand => msg!("Error: Swap price is out of the band"),
+            PriceBandError::NotTransferring => msg!("Error: NotTransferring"),
+        }
+    }
+}
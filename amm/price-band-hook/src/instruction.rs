@@ -0,0 +1,151 @@
+//! Instruction types
+
+use crate::state::price_band_address;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+};
+use spl_transfer_hook_interface::get_extra_account_metas_address;
+use std::convert::TryInto;
+
+/// Instructions supported by the price band hook program, besides the transfer hook
+/// interface's `Execute`.
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum PriceBandInstruction {
+    ///   Creates the `PriceBand` account of a mint and its ExtraAccountMetaList, which lists
+    ///   the band and the AMM trade context of the mint. The trade context is created by the
+    ///   AMM's `CreateTradeContext`, transfers of the mint fail until it exists.
+    ///
+    ///   0. `[writable, signer]` Payer Account
+    ///   1. `[writable]` PriceBand Account, derived from `find_program_address(&[PRICE_BAND_SEED, mint])`
+    ///   2. `[writable]` ExtraAccountMetaList Account, derived from `get_extra_account_metas_address`
+    ///   3. `[]` Token-2022 mint Account, whose transfer hook is this program
+    ///   4. `[signer]` Mint authority Account, becomes the band authority
+    ///   5. `[]` Sys program id
+    InitializePriceBand {
+        /// lowest price of the mint, as a Q64.64
+        min_price_x64: u128,
+        /// highest price of the mint, as a Q64.64
+        max_price_x64: u128,
+    },
+
+    ///   Sets the band of a mint.
+    ///
+    ///   0. `[writable]` PriceBand Account
+    ///   1. `[signer]` Band authority Account
+    UpdatePriceBand {
+        /// lowest price of the mint, as a Q64.64
+        min_price_x64: u128,
+        /// highest price of the mint, as a Q64.64
+        max_price_x64: u128,
+    },
+}
+
+impl PriceBandInstruction {
+    /// Unpacks a byte buffer into a [PriceBandInstruction](enum.PriceBandInstruction.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let (min_price_x64, rest) = Self::unpack_u128(rest)?;
+        let (max_price_x64, _rest) = Self::unpack_u128(rest)?;
+        Ok(match tag {
+            0 => Self::InitializePriceBand {
+                min_price_x64,
+                max_price_x64,
+            },
+            1 => Self::UpdatePriceBand {
+                min_price_x64,
+                max_price_x64,
+            },
+            _ => return Err(ProgramError::InvalidInstructionData),
+        })
+    }
+
+    fn unpack_u128(input: &[u8]) -> Result<(u128, &[u8]), ProgramError> {
+        if input.len() >= 16 {
+            let (amount, rest) = input.split_at(16);
+            let amount = amount
+                .try_into()
+                .ok()
+                .map(u128::from_le_bytes)
+                .ok_or(ProgramError::InvalidInstructionData)?;
+            Ok((amount, rest))
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    /// Packs a [PriceBandInstruction](enum.PriceBandInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(33);
+        let (tag, min_price_x64, max_price_x64) = match *self {
+            Self::InitializePriceBand {
+                min_price_x64,
+                max_price_x64,
+            } => (0, min_price_x64, max_price_x64),
+            Self::UpdatePriceBand {
+                min_price_x64,
+                max_price_x64,
+            } => (1, min_price_x64, max_price_x64),
+        };
+        buf.push(tag);
+        buf.extend_from_slice(&min_price_x64.to_le_bytes());
+        buf.extend_from_slice(&max_price_x64.to_le_bytes());
+        buf
+    }
+}
+
+/// Creates an 'initialize_price_band' instruction.
+pub fn initialize_price_band(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    mint_authority: &Pubkey,
+    min_price_x64: u128,
+    max_price_x64: u128,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(price_band_address(program_id, mint).0, false),
+        AccountMeta::new(get_extra_account_metas_address(mint, program_id), false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(*mint_authority, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PriceBandInstruction::InitializePriceBand {
+            min_price_x64,
+            max_price_x64,
+        }
+        .pack(),
+    }
+}
+
+/// Creates an 'update_price_band' instruction.
+pub fn update_price_band(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    authority: &Pubkey,
+    min_price_x64: u128,
+    max_price_x64: u128,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(price_band_address(program_id, mint).0, false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PriceBandInstruction::UpdatePriceBand {
+            min_price_x64,
+            max_price_x64,
+        }
+        .pack(),
+    }
+}
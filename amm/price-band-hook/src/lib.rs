@@ -0,0 +1,17 @@
+//! Reference Token-2022 transfer hook that reads the AMM trade context of its mint.
+//! A transfer made by an AMM swap must execute within the issuer's price band,
+//! any other transfer passes. The trade context is listed in the mint's
+//! ExtraAccountMetaList, so Token-2022 forwards it to every `Execute`.
+
+mod entrypoint;
+pub mod error;
+pub mod instruction;
+pub mod processor;
+pub mod state;
+#[cfg(test)]
+mod tests;
+
+// Export current solana-sdk types for downstream users who may also be building with a different solana-sdk version
+pub use solana_program;
+
+solana_program::declare_id!("CvXt4M7WNJ4waDBhVu4jmR1hznPUwqarBfbNRBHh7QSL");
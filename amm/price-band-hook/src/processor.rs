@@ -0,0 +1,260 @@
+//! Program state processor
+
+use crate::{
+    error::PriceBandError,
+    instruction::PriceBandInstruction,
+    state::{extra_account_metas, price_band_address, PriceBand, PRICE_BAND_SEED},
+};
+use raydium_amm::state::TradeContext;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_option::COption,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
+};
+use spl_tlv_account_resolution::state::ExtraAccountMetaList;
+use spl_token_2022::{
+    extension::{
+        transfer_hook::{self, TransferHookAccount},
+        BaseStateWithExtensions, StateWithExtensions,
+    },
+    state::{Account as Token2022Account, Mint as Token2022Mint},
+};
+use spl_transfer_hook_interface::{
+    collect_extra_account_metas_signer_seeds, get_extra_account_metas_address_and_bump_seed,
+    instruction::{ExecuteInstruction, TransferHookInstruction},
+};
+
+/// Program state handler.
+pub struct Processor {}
+impl Processor {
+    /// Create a program derived account owned by this program, also when someone sent
+    /// lamports to its address beforehand
+    fn create_pda_account<'a>(
+        program_id: &Pubkey,
+        payer_info: &AccountInfo<'a>,
+        new_account_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        data_size: usize,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        if new_account_info.owner != system_program_info.key || new_account_info.data_len() != 0 {
+            return Err(PriceBandError::AlreadyInUse.into());
+        }
+        let required_lamports = Rent::get()?
+            .minimum_balance(data_size)
+            .max(1)
+            .saturating_sub(new_account_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    payer_info.key,
+                    new_account_info.key,
+                    required_lamports,
+                ),
+                &[
+                    payer_info.clone(),
+                    new_account_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(new_account_info.key, data_size as u64),
+            &[new_account_info.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(new_account_info.key, program_id),
+            &[new_account_info.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        )
+    }
+
+    /// Processes an [InitializePriceBand](enum.PriceBandInstruction.html).
+    pub fn process_initialize_price_band(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        min_price_x64: u128,
+        max_price_x64: u128,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer_info = next_account_info(account_info_iter)?;
+        let price_band_info = next_account_info(account_info_iter)?;
+        let validation_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let mint_authority_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !payer_info.is_signer || !mint_authority_info.is_signer {
+            return Err(PriceBandError::InvalidSignAccount.into());
+        }
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if min_price_x64 > max_price_x64 {
+            return Err(PriceBandError::InvalidInput.into());
+        }
+        // the issuer sets up the band of a mint whose transfer hook is this program
+        if *mint_info.owner != spl_token_2022::id() {
+            return Err(PriceBandError::InvalidMint.into());
+        }
+        {
+            let data = mint_info.try_borrow_data()?;
+            let mint = StateWithExtensions::<Token2022Mint>::unpack(&data)?;
+            if transfer_hook::get_program_id(&mint) != Some(*program_id) {
+                return Err(PriceBandError::InvalidMint.into());
+            }
+            if mint.base.mint_authority != COption::Some(*mint_authority_info.key) {
+                return Err(PriceBandError::InvalidSignAccount.into());
+            }
+        }
+
+        let mint = mint_info.key;
+        let (expect_price_band, bump) = price_band_address(program_id, mint);
+        let (expect_validation, validation_bump) =
+            get_extra_account_metas_address_and_bump_seed(mint, program_id);
+        if *price_band_info.key != expect_price_band || *validation_info.key != expect_validation {
+            return Err(PriceBandError::InvalidProgramAddress.into());
+        }
+
+        Self::create_pda_account(
+            program_id,
+            payer_info,
+            price_band_info,
+            system_program_info,
+            PriceBand::LEN,
+            &[PRICE_BAND_SEED, mint.as_ref(), &[bump]],
+        )?;
+        *PriceBand::load_mut_checked(price_band_info, program_id)? = PriceBand {
+            mint: *mint,
+            authority: *mint_authority_info.key,
+            min_price_x64,
+            max_price_x64,
+            bump: bump as u64,
+            ..Default::default()
+        };
+
+        let extra_account_metas = extra_account_metas(mint)?;
+        Self::create_pda_account(
+            program_id,
+            payer_info,
+            validation_info,
+            system_program_info,
+            ExtraAccountMetaList::size_of(extra_account_metas.len())?,
+            &collect_extra_account_metas_signer_seeds(mint, &[validation_bump]),
+        )?;
+        ExtraAccountMetaList::init::<ExecuteInstruction>(
+            &mut validation_info.try_borrow_mut_data()?,
+            &extra_account_metas,
+        )?;
+        msg!("price band of {}", mint);
+        Ok(())
+    }
+
+    /// Processes an [UpdatePriceBand](enum.PriceBandInstruction.html).
+    pub fn process_update_price_band(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        min_price_x64: u128,
+        max_price_x64: u128,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let price_band_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        if min_price_x64 > max_price_x64 {
+            return Err(PriceBandError::InvalidInput.into());
+        }
+        let mut price_band = PriceBand::load_mut_checked(price_band_info, program_id)?;
+        if !authority_info.is_signer || *authority_info.key != price_band.authority {
+            return Err(PriceBandError::InvalidSignAccount.into());
+        }
+        price_band.min_price_x64 = min_price_x64;
+        price_band.max_price_x64 = max_price_x64;
+        Ok(())
+    }
+
+    /// Processes the transfer hook interface's `Execute`. The trade context tells a swap of
+    /// the AMM from any other transfer: it is active only while the swap transfers run.
+    pub fn process_execute(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let source_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let _destination_info = next_account_info(account_info_iter)?;
+        let _authority_info = next_account_info(account_info_iter)?;
+        let validation_info = next_account_info(account_info_iter)?;
+        let price_band_info = next_account_info(account_info_iter)?;
+        let trade_context_info = next_account_info(account_info_iter)?;
+
+        // only Token-2022 sets the transferring flag of the source during a transfer
+        {
+            let data = source_info.try_borrow_data()?;
+            let source = StateWithExtensions::<Token2022Account>::unpack(&data)?;
+            if !bool::from(source.get_extension::<TransferHookAccount>()?.transferring) {
+                return Err(PriceBandError::NotTransferring.into());
+            }
+        }
+        ExtraAccountMetaList::check_account_infos::<ExecuteInstruction>(
+            accounts,
+            &TransferHookInstruction::Execute { amount }.pack(),
+            program_id,
+            &validation_info.try_borrow_data()?,
+        )?;
+        let price_band = *PriceBand::load_checked(price_band_info, program_id, mint_info.key)?;
+
+        // fail closed until the AMM has created the trade context of the mint
+        let trade_context =
+            TradeContext::load_checked(trade_context_info, &raydium_amm::id(), mint_info.key)
+                .map_err(|_| PriceBandError::InvalidTradeContext)?;
+        if !trade_context.is_active() {
+            return Ok(());
+        }
+        let (amm, price_x64) = (trade_context.amm, trade_context.price_x64);
+        if !price_band.contains(price_x64) {
+            msg!(
+                "swap of pool {} at price_x64 {} is out of the band",
+                amm,
+                price_x64
+            );
+            return Err(PriceBandError::PriceOutOfBand.into());
+        }
+        Ok(())
+    }
+
+    /// Processes an [Instruction](enum.PriceBandInstruction.html).
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+        if let Ok(TransferHookInstruction::Execute { amount }) =
+            TransferHookInstruction::unpack(input)
+        {
+            return Self::process_execute(program_id, accounts, amount);
+        }
+        match PriceBandInstruction::unpack(input)? {
+            PriceBandInstruction::InitializePriceBand {
+                min_price_x64,
+                max_price_x64,
+            } => Self::process_initialize_price_band(
+                program_id,
+                accounts,
+                min_price_x64,
+                max_price_x64,
+            ),
+            PriceBandInstruction::UpdatePriceBand {
+                min_price_x64,
+                max_price_x64,
+            } => {
+                Self::process_update_price_band(program_id, accounts, min_price_x64, max_price_x64)
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! State transition types
+
+use crate::error::PriceBandError;
+use bytemuck::{from_bytes, from_bytes_mut, Pod, Zeroable};
+use raydium_amm::processor::trade_context_address;
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, seeds::Seed};
+use std::{
+    cell::{Ref, RefMut},
+    mem::size_of,
+};
+
+/// Seed of the `PriceBand` account of a mint
+pub const PRICE_BAND_SEED: &[u8] = b"price_band";
+
+/// Address of the `PriceBand` account of a mint
+pub fn price_band_address(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PRICE_BAND_SEED, mint.as_ref()], program_id)
+}
+
+/// Extra accounts of `Execute`, in order: the `PriceBand` account of the mint and the AMM
+/// trade context of the mint, which Token-2022 passes read-only
+pub fn extra_account_metas(mint: &Pubkey) -> Result<[ExtraAccountMeta; 2], ProgramError> {
+    Ok([
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: PRICE_BAND_SEED.to_vec(),
+                },
+                // the mint of the transfer
+                Seed::AccountKey { index: 1 },
+            ],
+            false,
+            false,
+        )?,
+        ExtraAccountMeta::new_with_pubkey(
+            &trade_context_address(&raydium_amm::id(), mint).0,
+            false,
+            false,
+        )?,
+    ])
+}
+
+/// Price band of the swaps of a mint, one account per mint derived from
+/// `find_program_address(&[PRICE_BAND_SEED, mint])`
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PriceBand {
+    /// mint the band applies to
+    pub mint: Pubkey,
+    /// updates the band, the mint authority at initialization
+    pub authority: Pubkey,
+    /// lowest price of the mint in the other token of a pool, as a Q64.64
+    pub min_price_x64: u128,
+    /// highest price of the mint in the other token of a pool, as a Q64.64
+    pub max_price_x64: u128,
+    /// bump seed of the account address
+    pub bump: u64,
+    /// padding
+    pub padding: [u64; 4],
+}
+unsafe impl Zeroable for PriceBand {}
+unsafe impl Pod for PriceBand {}
+
+impl PriceBand {
+    pub const LEN: usize = size_of::<Self>();
+
+    /// load_mut_checked
+    #[inline]
+    pub fn load_mut_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<RefMut<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if account.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(RefMut::map(account.try_borrow_mut_data()?, |data| {
+            from_bytes_mut(data)
+        }))
+    }
+
+    /// load_checked, also rejects an account that is not the band of `mint`
+    #[inline]
+    pub fn load_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+        mint: &Pubkey,
+    ) -> Result<Ref<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if account.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let band: Ref<Self> = Ref::map(account.try_borrow_data()?, |data| from_bytes(data));
+        if band.mint != *mint {
+            return Err(PriceBandError::InvalidMint.into());
+        }
+        // the stored bump pins the account to its seeds
+        let address = Pubkey::create_program_address(
+            &[PRICE_BAND_SEED, mint.as_ref(), &[band.bump as u8]],
+            program_id,
+        )
+        .map_err(|_| PriceBandError::InvalidProgramAddress)?;
+        if address != *account.key {
+            return Err(PriceBandError::InvalidProgramAddress.into());
+        }
+        Ok(band)
+    }
+
+    /// the price is within the band, bounds included
+    pub fn contains(&self, price_x64: u128) -> bool {
+        let (min_price_x64, max_price_x64) = (self.min_price_x64, self.max_price_x64);
+        min_price_x64 <= price_x64 && price_x64 <= max_price_x64
+    }
+}
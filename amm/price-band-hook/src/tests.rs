@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod tests {
+    use solana_program::pubkey::Pubkey;
+
+    use crate::{
+        instruction::{self, PriceBandInstruction},
+        state::{extra_account_metas, price_band_address, PriceBand},
+    };
+
+    /// Test instruction data round trip
+    #[test]
+    fn test_instruction_pack_unpack() {
+        for instruction in [
+            PriceBandInstruction::InitializePriceBand {
+                min_price_x64: 1 << 63,
+                max_price_x64: 3 << 64,
+            },
+            PriceBandInstruction::UpdatePriceBand {
+                min_price_x64: 0,
+                max_price_x64: u128::MAX,
+            },
+        ] {
+            let data = instruction.pack();
+            assert_eq!(data.len(), 33);
+            assert_eq!(PriceBandInstruction::unpack(&data).unwrap(), instruction);
+        }
+        assert!(PriceBandInstruction::unpack(&[]).is_err());
+        assert!(PriceBandInstruction::unpack(&[0; 17]).is_err());
+        assert!(PriceBandInstruction::unpack(&[2; 33]).is_err());
+    }
+
+    /// Test the band bounds are inclusive
+    #[test]
+    fn test_price_band_contains() {
+        let band = PriceBand {
+            min_price_x64: 1 << 64,
+            max_price_x64: 2 << 64,
+            ..Default::default()
+        };
+        assert!(band.contains(1 << 64));
+        assert!(band.contains(3 << 63));
+        assert!(band.contains(2 << 64));
+        assert!(!band.contains((1 << 64) - 1));
+        assert!(!band.contains((2 << 64) + 1));
+        assert_eq!(PriceBand::LEN, 32 * 2 + 16 * 2 + 8 * 5);
+    }
+
+    /// Test the extra accounts list the band and the AMM trade context of the mint read-only
+    #[test]
+    fn test_extra_account_metas() {
+        let program_id = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+
+        let metas = extra_account_metas(&mint).unwrap();
+        let trade_context =
+            raydium_amm::processor::trade_context_address(&raydium_amm::id(), &mint).0;
+        assert_eq!(metas[1].address_config, trade_context.to_bytes());
+        assert!(metas.iter().all(|meta| !bool::from(meta.is_writable)));
+
+        let init = instruction::initialize_price_band(&program_id, &payer, &mint, &payer, 0, 1);
+        assert_eq!(init.accounts.len(), 6);
+        assert_eq!(
+            init.accounts[1].pubkey,
+            price_band_address(&program_id, &mint).0
+        );
+        assert!(init.accounts[4].is_signer);
+        let update = instruction::update_price_band(&program_id, &mint, &payer, 0, 1);
+        assert_eq!(update.accounts.len(), 2);
+        assert!(update.accounts[1].is_signer);
+    }
+}
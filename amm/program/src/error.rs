@@ -222,6 +222,67 @@ pub enum AmmError {
     /// Incompatible token programs for operation
     #[error("Incompatible token programs for operation")]
     IncompatibleTokenPrograms,
+    /// Mint extension is forbidden or needs the admin approval
+    #[error("Mint extension is forbidden or needs the admin approval")]
+    ForbiddenMintExtension,
+    /// Transfer hook exceeded the compute units registered in its hook config
+    #[error("Transfer hook exceeded its compute budget")]
+    HookComputeBudgetExceeded,
+
+    /// Transfer hook program can be upgraded by an unknown authority or was redeployed
+    #[error("Transfer hook program is upgradeable or was redeployed")]
+    UnverifiedHookProgram,
+
+    /// Pool is locked by an instruction in progress, e.g. re-entered from a transfer hook
+    #[error("Pool is locked by an instruction in progress")]
+    PoolLocked,
+
+    /// Hook registration is vetoed after or activated before its challenge period ends
+    #[error("Hook registration is not in its challenge period or not past it")]
+    HookChallengePeriod,
+
+    /// Hook dry run always fails to revert its transfers, the result is in the logs
+    #[error("Hook dry run reverted")]
+    HookDryRunReverted,
+
+    /// Escrow claim or escrow token account does not match its derivation, or the side
+    /// can not be escrowed
+    #[error("Invalid escrow account")]
+    InvalidEscrowAccount,
+
+    /// Trade context account does not match its derivation or is passed read-only
+    #[error("Invalid trade context account")]
+    InvalidTradeContext,
+
+    /// StableSwap amplification coefficient or its ramp is out of range
+    #[error("Invalid amplification coefficient")]
+    InvalidAmp,
+
+    /// Price oracle account does not match its derivation, is passed read-only or is
+    /// missing for a pool that has one
+    #[error("Invalid price oracle account")]
+    InvalidPriceOracle,
+
+    /// Price oracle has no observation as old as requested, or was never updated
+    #[error("Price oracle has no observation that old")]
+    ObservationUnavailable,
+
+    /// Referrer token account of a swap is not a token account of the input mint
+    #[error("Invalid referrer account")]
+    InvalidReferrer,
+
+    /// Transfer hook program of a pool mint changed since the pool was created, the pool
+    /// is paused by `PauseOnHookChange` and only withdrawals go on
+    #[error("Transfer hook program of a pool mint changed")]
+    HookProgramChanged,
+
+    /// Token wrapper accounts of a side do not match the wrapper of its vault mint
+    #[error("Invalid token wrapper accounts")]
+    InvalidWrapperAccounts,
+
+    /// A pool with an orderbook only trades on the constant product curve its orders quote
+    #[error("Curve is not supported by a pool with an orderbook")]
+    UnsupportedCurve,
 }
 
 impl From<AmmError> for ProgramError {
@@ -318,6 +379,21 @@ impl PrintProgramError for AmmError {
             AmmError::InvalidTransferHookAccounts => msg!("Error: Transfer hook accounts are invalid or missing"),
             AmmError::UnauthorizedTransferHook => msg!("Error: Transfer hook program is not whitelisted"),
             AmmError::IncompatibleTokenPrograms => msg!("Error: Incompatible token programs for operation"),
+            AmmError::ForbiddenMintExtension => msg!("Error: Mint extension is forbidden or needs the admin approval"),
+            AmmError::HookComputeBudgetExceeded => msg!("Error: Transfer hook exceeded its compute budget"),
+            AmmError::UnverifiedHookProgram => msg!("Error: Transfer hook program is upgradeable or was redeployed"),
+            AmmError::PoolLocked => msg!("Error: Pool is locked by an instruction in progress"),
+            AmmError::HookChallengePeriod => msg!("Error: Hook registration is not in its challenge period or not past it"),
+            AmmError::HookDryRunReverted => msg!("Error: Hook dry run reverted"),
+            AmmError::InvalidEscrowAccount => msg!("Error: Invalid escrow account"),
+            AmmError::InvalidTradeContext => msg!("Error: Invalid trade context account"),
+            AmmError::InvalidAmp => msg!("Error: Invalid amplification coefficient"),
+            AmmError::InvalidPriceOracle => msg!("Error: Invalid price oracle account"),
+            AmmError::ObservationUnavailable => msg!("Error: Price oracle has no observation that old"),
+            AmmError::InvalidReferrer => msg!("Error: Invalid referrer account"),
+            AmmError::HookProgramChanged => msg!("Error: Transfer hook program of a pool mint changed"),
+            AmmError::InvalidWrapperAccounts => msg!("Error: Invalid token wrapper accounts"),
+            AmmError::UnsupportedCurve => msg!("Error: Curve is not supported by a pool with an orderbook"),
         }
     }
 }
@@ -3,7 +3,7 @@
 #![allow(clippy::too_many_arguments)]
 #![allow(deprecated)]
 
-use crate::state::{AmmParams, Fees, LastOrderDistance, SimulateParams};
+use crate::state::{AmmParams, FeeSplit, Fees, LastOrderDistance, RampAmp, SimulateParams};
 use arrayref::array_ref;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
@@ -35,6 +35,13 @@ pub struct InitializeInstruction2 {
     pub init_pc_amount: u64,
     /// init token coin amount
     pub init_coin_amount: u64,
+    /// accept transfer hooks activated through the bonded registry, encoded as an
+    /// optional trailing byte
+    pub registry_hooks: bool,
+    /// amplification coefficient of a StableSwap pool, 0 for the constant product curve,
+    /// encoded as an optional u64 after `registry_hooks`. `InitializeNoOrderbook` only,
+    /// `Initialize2` fails a non zero amp with `UnsupportedCurve`.
+    pub amp: u64,
 }
 
 #[repr(C)]
@@ -62,6 +69,8 @@ pub struct DepositInstruction {
     pub max_pc_amount: u64,
     pub base_side: u64,
     pub other_amount_min: Option<u64>,
+    /// Packed after `other_amount_min`, zero is packed if it is not set
+    pub wrap_sides: WrapSides,
 }
 
 #[repr(C)]
@@ -72,8 +81,40 @@ pub struct WithdrawInstruction {
     pub amount: u64,
     pub min_coin_amount: Option<u64>,
     pub min_pc_amount: Option<u64>,
+    /// Sides credited to the owner's escrow claim instead of delivered, see `WITHDRAW_ESCROW_*`.
+    /// Packed after the min amounts, zero ones are packed if they are not set.
+    pub escrow_sides: u8,
+    /// Packed after `escrow_sides`
+    pub wrap_sides: WrapSides,
 }
 
+/// The sides of a `Deposit` or `Withdraw` whose user token Account holds the underlying mint
+/// of the wrapper mint in the vault, each taking `WRAP_ACCOUNT_LEN` token wrapper accounts and
+/// the transfer hook accounts of its underlying mint, see `with_wrapper`.
+/// Packed as a byte of the wrapped sides followed by both hook account counts.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WrapSides {
+    /// Transfer hook accounts of the underlying coin mint, `None` if the coin side is not wrapped
+    pub coin_hook_account_count: Option<u8>,
+    /// Transfer hook accounts of the underlying pc mint, `None` if the pc side is not wrapped
+    pub pc_hook_account_count: Option<u8>,
+}
+
+/// the coin side of a `WrapSides` is wrapped
+pub const WRAP_COIN: u8 = 1 << 0;
+/// the pc side of a `WrapSides` is wrapped
+pub const WRAP_PC: u8 = 1 << 1;
+/// token wrapper accounts of a wrapped side, before the hook accounts of its underlying mint
+pub const WRAP_ACCOUNT_LEN: usize = 6;
+
+/// the coin side of a withdrawal is escrowed
+pub const WITHDRAW_ESCROW_COIN: u8 = 1 << 0;
+/// the pc side of a withdrawal is escrowed
+pub const WITHDRAW_ESCROW_PC: u8 = 1 << 1;
+/// escrow accounts a withdrawal with `escrow_sides` set takes, see `Withdraw`
+pub const ESCROW_ACCOUNT_LEN: usize = 4;
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct SetParamsInstruction {
@@ -82,6 +123,8 @@ pub struct SetParamsInstruction {
     pub new_pubkey: Option<Pubkey>,
     pub fees: Option<Fees>,
     pub last_order_distance: Option<LastOrderDistance>,
+    pub ramp_amp: Option<RampAmp>,
+    pub fee_split: Option<FeeSplit>,
 }
 
 #[repr(C)]
@@ -108,6 +151,78 @@ pub struct SwapInstructionBaseOut {
     pub amount_out: u64,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SwapInstructionBaseInV2 {
+    // SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token to output, prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// Number of transfer hook accounts of the coin side
+    pub coin_hook_account_count: u8,
+    /// Number of transfer hook accounts of the pc side, after those of the coin side
+    pub pc_hook_account_count: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SwapInstructionBaseOutV2 {
+    // SOURCE amount to transfer, output to DESTINATION is based on the exchange rate
+    pub max_amount_in: u64,
+    /// Minimum amount of DESTINATION token to output, prevents excessive slippage
+    pub amount_out: u64,
+    /// Number of transfer hook accounts of the coin side
+    pub coin_hook_account_count: u8,
+    /// Number of transfer hook accounts of the pc side, after those of the coin side
+    pub pc_hook_account_count: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SwapRouteInstruction {
+    // SOURCE amount to transfer into the first pool
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token out of the last pool, prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// Number of pools of the route, at most `MAX_SWAP_ROUTE_HOPS`
+    pub hop_count: u8,
+}
+
+/// max pools a `SwapRoute` goes through
+pub const MAX_SWAP_ROUTE_HOPS: usize = 4;
+/// accounts of each pool of a `SwapRoute`
+pub const SWAP_ROUTE_HOP_ACCOUNT_LEN: usize = 15;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CreatePriceOracleInstruction {
+    /// Number of observations the ring buffer keeps, 1 to `MAX_PRICE_OBSERVATIONS`
+    pub observation_len: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DepositSingleInstruction {
+    /// Amount of the `side` token to transfer, part of it is swapped for the other side
+    pub amount_in: u64,
+    /// Minimum lp amount to mint, prevents excessive slippage
+    pub minimum_lp_amount: u64,
+    /// Side of the token deposited, 0 coin and 1 pc as `DepositInstruction::base_side`
+    pub side: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WithdrawSingleInstruction {
+    /// Lp amount to burn
+    pub amount: u64,
+    /// Minimum amount of the `side` token received, net of its Token-2022 transfer fee,
+    /// prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// Side of the token withdrawn, 0 coin and 1 pc
+    pub side: u8,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct SimulateInstruction {
@@ -129,6 +244,21 @@ pub struct ConfigArgs {
     pub param: u8,
     pub owner: Option<Pubkey>,
     pub create_pool_fee: Option<u64>,
+    pub value: Option<u64>,
+}
+
+/// Add or update a whitelisted transfer hook program
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HookConfigArgs {
+    /// max compute units the hook may consume per transfer
+    pub max_compute_units: u64,
+    /// the hook is safe to be called re-entrantly
+    pub is_reentrant_safe: bool,
+    /// hash of the extra accounts the hook expects
+    pub expected_accounts_hash: Option<[u8; 32]>,
+    /// upgrade authority the hook program may keep, it must be immutable otherwise
+    pub upgrade_authority: Option<Pubkey>,
 }
 
 /// Instructions supported by the AmmInfo program.
@@ -142,8 +272,9 @@ pub enum AmmInstruction {
     Initialize(InitializeInstruction),
 
     ///   Initializes a new AMM pool.
+    ///   Each side may use Spl Token or Spl Token-2022, the lp mint is created under the pc side token program.
     ///
-    ///   0. `[]` Spl Token program id
+    ///   0. `[]` Coin side token program id, Spl Token or Spl Token-2022
     ///   1. `[]` Associated Token program id
     ///   2. `[]` Sys program id
     ///   3. `[]` Rent program id
@@ -164,6 +295,10 @@ pub enum AmmInstruction {
     ///   18. `[]` User token coin Account
     ///   19. '[]` User token pc Account
     ///   20. `[writable]` User destination lp token ATA Account
+    ///   21..N. `[]` (optional) Transfer accounts of the initial liquidity, coin side first: for a Token-2022 side
+    ///          its mint followed by the transfer hook accounts of a hooked mint, see `whitelist::split_transfer_hook_accounts`
+    ///   N+1. `[signer]` (optional) The admin Account approving mint extensions that need it, see `token_utils::extension_policy`
+    ///   N+2. `[]` (optional) Pc side token program id, if the pc mint belongs to the other token program
     Initialize2(InitializeInstruction2),
 
     ///   MonitorStep. To monitor place Amm order state machine turn around step by step.
@@ -193,7 +328,7 @@ pub enum AmmInstruction {
     ///   Deposit some tokens into the pool.  The output is a "pool" token representing ownership
     ///   into the pool. Inputs are converted to the current ratio.
     ///
-    ///   0. `[]` Spl Token program id
+    ///   0. `[]` Coin side token program id
     ///   1. `[writable]` AMM Account
     ///   2. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
     ///   3. `[]` AMM open_orders Account
@@ -207,11 +342,20 @@ pub enum AmmInstruction {
     ///   11. `[writable]` User lp token. To deposit the generated tokens, user is the owner.
     ///   12. '[signer]` User wallet Account
     ///   13. `[]` Market event queue Account.
+    ///   14. `[]` (optional) Pc side token program id, required when it differs from the coin side or any Account follows
+    ///   15... `[]` (optional) Transfer accounts of each Token-2022 side, coin side first, required for that side,
+    ///       see `whitelist::split_transfer_hook_accounts`.
+    ///   N+1... `[]` (`wrap_sides` set) Token wrapper accounts of each wrapped side, coin side first,
+    ///       see `with_wrapper`: the wrapper program id, the `Wrapper`, underlying mint,
+    ///       `[writable]` wrapper mint and `[writable]` escrow Accounts, the Token-2022 program id
+    ///       and as many transfer hook accounts of the underlying mint as `wrap_sides` gives. The
+    ///       user token Account of that side holds the underlying mint, which is wrapped into the vault.
+    ///   N+2. `[writable]` (optional) Price oracle Account as the last Account, required when the pool has one, see `CreatePriceOracle`
     Deposit(DepositInstruction),
 
     ///   Withdraw the vault tokens from the pool at the current ratio.
     ///
-    ///   0. `[]` Spl Token program id
+    ///   0. `[]` Coin side token program id
     ///   1. `[writable]` AMM Account
     ///   2. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
     ///   3. `[writable]` AMM open orders Account
@@ -231,9 +375,30 @@ pub enum AmmInstruction {
     ///   17. `[writable]` Market event queue Account
     ///   18. `[writable]` Market bids Account
     ///   19. `[writable]` Market asks Account
+    ///   20. `[writable]` (optional) the referrer pc account used for settle back referrer
+    ///
+    ///   A pool with a Token-2022 side, or a withdrawal with `escrow_sides` or `wrap_sides` set,
+    ///   takes its trailing accounts at fixed positions instead:
+    ///   20. `[writable]` the referrer pc account used for settle back referrer, the default pubkey for none
+    ///   21. `[]` Pc side token program id
+    ///   22..25. (`escrow_sides` set) Escrow accounts, the user wallet is writable to pay their rent:
+    ///       `[]` System program id, `[writable]` escrow claim Account derived from
+    ///       `escrow_claim_address`, `[writable]` coin and pc escrow token Accounts derived from
+    ///       `find_program_address(&[program_id, amm, COIN_ESCROW_ASSOCIATED_SEED])` and
+    ///       `PC_ESCROW_ASSOCIATED_SEED`. The transfer hook accounts of an escrowed side are
+    ///       resolved for its escrow token Account as the destination.
+    ///   N... `[]` Transfer accounts of each Token-2022 side, coin side first, see
+    ///       `whitelist::split_transfer_hook_accounts`. The min amounts are held against the
+    ///       amounts net of Token-2022 transfer fees.
+    ///   N+1... `[]` (`wrap_sides` set) Token wrapper accounts of each wrapped side, see `Deposit`.
+    ///       The user token Account of that side holds the underlying mint, which is unwrapped
+    ///       from the vault. The min amounts are held against the amounts net of the transfer fee
+    ///       of the underlying mint.
     Withdraw(WithdrawInstruction),
 
-    ///   Migrate the associated market from Serum to OpenBook.
+    ///   Migrate the associated market from Serum to OpenBook. The new market lists the vault
+    ///   mints, the wrapper mint of a side whose vault holds the wrapper mint of a Token-2022
+    ///   mint, so LPs of that side go on depositing and withdrawing the underlying mint.
     ///
     ///   0. `[]` Spl Token program id
     ///   1. `[]` Sys program id
@@ -258,7 +423,11 @@ pub enum AmmInstruction {
     ///   20. '[]` Admin Account
     MigrateToOpenBook,
 
-    ///   Set AMM params
+    ///   Set AMM params. A pool created by `InitializeNoOrderbook` only takes `Status`, except
+    ///   the statuses that plan orders, `RampAmp`, which ramps the amplification coefficient of
+    ///   a StableSwap pool, and `FeeSplit`, and ignores accounts 3, 4 and 7..14. A pool with an
+    ///   orderbook keeps the constant product curve its orders quote and fails `RampAmp` with
+    ///   `UnsupportedCurve`.
     ///
     ///   0. `[]` Spl Token program id
     ///   1. `[writable]` AMM Account.
@@ -276,12 +445,17 @@ pub enum AmmInstruction {
     ///   13. `[writable]` Market bids Account
     ///   14. `[writable]` Market asks Account
     ///   15. `[signer]` Admin Account
-    ///   16. `[]` (optional) New AMM open orders Account to replace old AMM open orders Account
+    ///   16. `[]` (optional) For `UpdateOpenOrder`, new AMM open orders Account to replace old AMM
+    ///       open orders Account. For the status of a pool paused by a hook change, AMM coin mint
+    ///       Account, the transfer hook programs of both mints are pinned again
+    ///   17. `[]` (status of a pool paused by a hook change) AMM pc mint Account
     SetParams(SetParamsInstruction),
 
-    ///   Withdraw Pnl from pool by protocol
+    ///   Withdraw Pnl from pool by protocol, with the protocol share of the swap fees of a pool
+    ///   with a fee split. A pool created by `InitializeNoOrderbook` only has that share, and
+    ///   takes any account, such as the AMM Account, as accounts 4 and 10..16.
     ///
-    ///   0. `[]` Spl Token program id
+    ///   0. `[]` Coin side token program id
     ///   1. `[writable]` AMM Account
     ///   2. `[]` AMM config Account, derived from `find_program_address(&[&&AMM_CONFIG_SEED])`.
     ///   3. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
@@ -299,6 +473,11 @@ pub enum AmmInstruction {
     ///   15. `[writable]` Market pc vault Account
     ///   16. '[]` Market vault signer Account
     ///   17. `[]` (optional) the referrer pc account used for settle back referrer
+    ///
+    ///   A pool with a Token-2022 side takes its trailing accounts at fixed positions instead:
+    ///   17. `[]` Pc side token program id
+    ///   18... `[]` Transfer accounts of each Token-2022 side, coin side first, see
+    ///       `whitelist::split_transfer_hook_accounts`.
     WithdrawPnl,
 
     ///   Withdraw (M)SRM from the (M)SRM Account used for fee discounts by admin
@@ -313,7 +492,7 @@ pub enum AmmInstruction {
 
     /// Swap coin or pc from pool, base amount_in with a slippage of minimum_amount_out
     ///
-    ///   0. `[]` Spl Token program id
+    ///   0. `[]` Coin side token program id, Spl Token or Spl Token-2022
     ///   1. `[writable]` AMM Account
     ///   2. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
     ///   3. `[writable]` AMM open orders Account
@@ -331,6 +510,12 @@ pub enum AmmInstruction {
     ///   15. `[writable]` User source token Account.
     ///   16. `[writable]` User destination token Account.
     ///   17. `[signer]` User wallet Account
+    ///   18..N. `[]` (optional) Token-2022 transfer accounts, one group per Token-2022 transfer, source side first,
+    ///          see `whitelist::split_transfer_hook_accounts`
+    ///   N..M. `[writable]` (optional) Referrer token Account of the source mint, paid the referrer share of the swap fee
+    ///          of a pool with a fee split, then the Token-2022 transfer accounts of its transfer from the source vault
+    ///   N+1. `[]` (optional) Pc side token program id as the last Account, required when it differs from the coin side
+    ///   N+2. `[writable]` (optional) Price oracle Account as the very last Account, required when the pool has one, see `CreatePriceOracle`
     SwapBaseIn(SwapInstructionBaseIn),
 
     ///   Continue Initializes a new Amm pool because of compute units limit.
@@ -340,7 +525,7 @@ pub enum AmmInstruction {
 
     /// Swap coin or pc from pool, base amount_out with a slippage of max_amount_in
     ///
-    ///   0. `[]` Spl Token program id
+    ///   0. `[]` Coin side token program id, Spl Token or Spl Token-2022
     ///   1. `[writable]` AMM Account
     ///   2. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
     ///   3. `[writable]` AMM open orders Account
@@ -358,8 +543,34 @@ pub enum AmmInstruction {
     ///   15. `[writable]` User source token Account.
     ///   16. `[writable]` User destination token Account.
     ///   17. `[signer]` User wallet Account
+    ///   18..N. `[]` (optional) Token-2022 transfer accounts, one group per Token-2022 transfer, source side first,
+    ///          see `whitelist::split_transfer_hook_accounts`
+    ///   N..M. `[writable]` (optional) Referrer token Account of the source mint, paid the referrer share of the swap fee
+    ///          of a pool with a fee split, then the Token-2022 transfer accounts of its transfer from the source vault
+    ///   N+1. `[]` (optional) Pc side token program id as the last Account, required when it differs from the coin side
+    ///   N+2. `[writable]` (optional) Price oracle Account as the very last Account, required when the pool has one, see `CreatePriceOracle`
     SwapBaseOut(SwapInstructionBaseOut),
 
+    /// Simulate only, logs the requested data as json.
+    ///
+    /// `HookDryRunInfo` performs the hooked transfers of a swap, `amount_in` from the user and
+    /// `minimum_amount_out` from the vault, logs `HookDryRunData` and fails with
+    /// `HookDryRunReverted` so nothing is kept. A hook that rejects its transfer aborts the
+    /// simulation with its own error instead, after the `HookDryRunSide` of the rejected side is
+    /// logged. Its accounts:
+    ///
+    ///   0. `[]` Coin side token program id
+    ///   1. `[]` Pc side token program id
+    ///   2. `[]` AMM Account
+    ///   3. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   4. `[writable]` AMM coin vault Account
+    ///   5. `[writable]` AMM pc vault Account
+    ///   6. `[writable]` User source token Account.
+    ///   7. `[writable]` User destination token Account.
+    ///   8. `[signer]` User wallet Account
+    ///   9..11. `[]` AMM open orders, Market and Market event queue Accounts, only for a pool
+    ///          created by `Initialize2`, whose reserves include its orders on the orderbook
+    ///   N..M. `[]` Token-2022 transfer accounts as `SwapBaseInNoOrderbook`
     SimulateInfo(SimulateInstruction),
 
     AdminCancelOrders(AdminCancelOrdersInstruction),
@@ -369,6 +580,335 @@ pub enum AmmInstruction {
 
     /// Update amm config account by admin
     UpdateConfigAccount(ConfigArgs),
+
+    /// Add or update a whitelisted transfer hook program by admin
+    ///
+    ///   0. `[writable, signer]` Admin Account
+    ///   1. `[writable]` Hook config Account, derived from `find_program_address(&[HOOK_CONFIG_SEED, hook_program])`
+    ///   2. `[]` Transfer hook program id
+    ///   3. `[]` System program id
+    ///   4. `[]` Rent program id
+    ///   5. `[]` ProgramData Account of the hook program, derived from
+    ///      `find_program_address(&[hook_program], bpf_loader_upgradeable)`, ignored for a
+    ///      program of a non-upgradeable loader
+    AddTransferHook(HookConfigArgs),
+
+    /// Remove a whitelisted transfer hook program by admin, the rent is returned to the admin
+    ///
+    ///   0. `[writable, signer]` Admin Account
+    ///   1. `[writable]` Hook config Account
+    RemoveTransferHook,
+
+    /// Swap coin or pc from pool, base amount_in with a slippage of minimum_amount_out.
+    /// Same as `SwapBaseIn` with a fixed account layout for Token-2022 pools
+    ///
+    ///   0. `[]` Coin side token program id, Spl Token or Spl Token-2022
+    ///   1. `[]` Pc side token program id, Spl Token or Spl Token-2022
+    ///   2. `[writable]` AMM Account
+    ///   3. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   4. `[writable]` AMM open orders Account
+    ///   5. `[writable]` AMM coin vault Account to swap FROM or To.
+    ///   6. `[writable]` AMM pc vault Account to swap FROM or To.
+    ///   7. `[]` AMM coin mint Account
+    ///   8. `[]` AMM pc mint Account
+    ///   9. `[]` Market program id
+    ///   10. `[writable]` Market Account. Market program is the owner.
+    ///   11. `[writable]` Market bids Account
+    ///   12. `[writable]` Market asks Account
+    ///   13. `[writable]` Market event queue Account
+    ///   14. `[writable]` Market coin vault Account
+    ///   15. `[writable]` Market pc vault Account
+    ///   16. '[]` Market vault signer Account
+    ///   17. `[writable]` User source token Account.
+    ///   18. `[writable]` User destination token Account.
+    ///   19. `[signer]` User wallet Account
+    ///   20..20+C. `[]` `coin_hook_account_count` transfer hook accounts of a hooked Token-2022 coin mint,
+    ///          the group of `whitelist::split_transfer_hook_accounts` without its mint
+    ///   20+C..20+C+P. `[]` `pc_hook_account_count` transfer hook accounts of a hooked Token-2022 pc mint
+    ///   20+C+P..R. `[writable]` (optional) Referrer token Account and its transfer accounts, as those of `SwapBaseIn`
+    ///   R. `[writable]` (optional) Price oracle Account as the last Account, required when the pool has one, see `CreatePriceOracle`
+    SwapBaseInV2(SwapInstructionBaseInV2),
+
+    /// Swap coin or pc from pool, base amount_out with a slippage of max_amount_in.
+    /// Same as `SwapBaseOut` with the account layout of `SwapBaseInV2`
+    ///
+    ///   0. `[]` Coin side token program id, Spl Token or Spl Token-2022
+    ///   1. `[]` Pc side token program id, Spl Token or Spl Token-2022
+    ///   2. `[writable]` AMM Account
+    ///   3. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   4. `[writable]` AMM open orders Account
+    ///   5. `[writable]` AMM coin vault Account to swap FROM or To.
+    ///   6. `[writable]` AMM pc vault Account to swap FROM or To.
+    ///   7. `[]` AMM coin mint Account
+    ///   8. `[]` AMM pc mint Account
+    ///   9. `[]` Market program id
+    ///   10. `[writable]` Market Account. Market program is the owner.
+    ///   11. `[writable]` Market bids Account
+    ///   12. `[writable]` Market asks Account
+    ///   13. `[writable]` Market event queue Account
+    ///   14. `[writable]` Market coin vault Account
+    ///   15. `[writable]` Market pc vault Account
+    ///   16. '[]` Market vault signer Account
+    ///   17. `[writable]` User source token Account.
+    ///   18. `[writable]` User destination token Account.
+    ///   19. `[signer]` User wallet Account
+    ///   20..20+C. `[]` `coin_hook_account_count` transfer hook accounts of a hooked Token-2022 coin mint,
+    ///          the group of `whitelist::split_transfer_hook_accounts` without its mint
+    ///   20+C..20+C+P. `[]` `pc_hook_account_count` transfer hook accounts of a hooked Token-2022 pc mint
+    ///   20+C+P..R. `[writable]` (optional) Referrer token Account and its transfer accounts, as those of `SwapBaseIn`
+    ///   R. `[writable]` (optional) Price oracle Account as the last Account, required when the pool has one, see `CreatePriceOracle`
+    SwapBaseOutV2(SwapInstructionBaseOutV2),
+
+    ///   Initializes a new pool without an OpenBook market, for mints the market can't list
+    ///   such as hooked Token-2022 mints. The pool is never placed on the orderbook and its
+    ///   swap fees stay with the liquidity providers. A non zero `amp` creates a StableSwap pool.
+    ///
+    ///   0. `[]` Coin side token program id, Spl Token or Spl Token-2022
+    ///   1. `[]` Pc side token program id, Spl Token or Spl Token-2022
+    ///   2. `[]` Associated Token program id
+    ///   3. `[]` Sys program id
+    ///   4. `[]` Rent program id
+    ///   5. `[writable]` New AMM Account to create.
+    ///   6. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   7. `[writable]` AMM lp mint Account
+    ///   8. `[]` AMM coin mint Account
+    ///   9. `[]` AMM pc mint Account
+    ///   10. `[writable]` AMM coin vault Account. Must be non zero, owned by $authority.
+    ///   11. `[writable]` AMM pc vault Account. Must be non zero, owned by $authority.
+    ///   12. `[]` AMM config Account, derived from `find_program_address(&[&&AMM_CONFIG_SEED])`.
+    ///   13. `[writable]` AMM create pool fee destination Account
+    ///   14. `[]` Pool seed Account, any key not used by another pool, derives the pool accounts in place of the market
+    ///   15. `[writable, signer]` User wallet Account
+    ///   16. `[writable]` User token coin Account
+    ///   17. `[writable]` User token pc Account
+    ///   18. `[writable]` User destination lp token ATA Account
+    ///   19..N. `[]` (optional) Transfer accounts of the initial liquidity, coin side first: for a Token-2022 side
+    ///          its mint followed by the transfer hook accounts of a hooked mint, see `whitelist::split_transfer_hook_accounts`
+    ///   N+1. `[signer]` (optional) The admin Account if a mint has an extension that needs approval
+    InitializeNoOrderbook(InitializeInstruction2),
+
+    ///   Deposit some tokens into a pool created by `InitializeNoOrderbook`.
+    ///
+    ///   0. `[]` Coin side token program id
+    ///   1. `[]` Pc side token program id
+    ///   2. `[writable]` AMM Account
+    ///   3. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   4. `[writable]` AMM lp mint Account. Owned by $authority.
+    ///   5. `[writable]` AMM coin vault Account to deposit into.
+    ///   6. `[writable]` AMM pc vault Account to deposit into.
+    ///   7. `[writable]` User coin token Account to deposit from.
+    ///   8. `[writable]` User pc token Account to deposit from.
+    ///   9. `[writable]` User lp token. To deposit the generated tokens, user is the owner.
+    ///   10. '[signer]` User wallet Account
+    ///   11..N. `[]` Token-2022 transfer accounts, one group per Token-2022 transfer, coin side first,
+    ///          see `whitelist::split_transfer_hook_accounts`
+    ///   N+1. `[writable]` (optional) Price oracle Account as the last Account, required when the pool has one, see `CreatePriceOracle`
+    DepositNoOrderbook(DepositInstruction),
+
+    ///   Withdraw the vault tokens from a pool created by `InitializeNoOrderbook`.
+    ///
+    ///   0. `[]` Coin side token program id
+    ///   1. `[]` Pc side token program id
+    ///   2. `[writable]` AMM Account
+    ///   3. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   4. `[writable]` AMM lp mint Account. Owned by $authority.
+    ///   5. `[writable]` AMM coin vault Account to withdraw FROM,
+    ///   6. `[writable]` AMM pc vault Account to withdraw FROM,
+    ///   7. `[writable]` User lp token Account.
+    ///   8. `[writable]` User token coin Account. user Account to credit.
+    ///   9. `[writable]` User token pc Account. user Account to credit.
+    ///   10. `[signer]` User wallet Account
+    ///   11..14. (`escrow_sides` set) Escrow accounts, see `Withdraw`
+    ///   11..N. `[]` Token-2022 transfer accounts, one group per Token-2022 transfer, coin side first,
+    ///          see `whitelist::split_transfer_hook_accounts`
+    WithdrawNoOrderbook(WithdrawInstruction),
+
+    /// Swap coin or pc from a pool created by `InitializeNoOrderbook`, base amount_in with a slippage of minimum_amount_out
+    ///
+    ///   0. `[]` Coin side token program id
+    ///   1. `[]` Pc side token program id
+    ///   2. `[writable]` AMM Account
+    ///   3. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   4. `[writable]` AMM coin vault Account to swap FROM or To.
+    ///   5. `[writable]` AMM pc vault Account to swap FROM or To.
+    ///   6. `[writable]` User source token Account.
+    ///   7. `[writable]` User destination token Account.
+    ///   8. `[signer]` User wallet Account
+    ///   9..N. `[]` Token-2022 transfer accounts, one group per Token-2022 transfer, source side first,
+    ///          see `whitelist::split_transfer_hook_accounts`
+    ///   N..M. `[writable]` (optional) Referrer token Account and its transfer accounts, as those of `SwapBaseIn`
+    ///   N+1. `[writable]` (optional) Price oracle Account as the last Account, required when the pool has one, see `CreatePriceOracle`
+    SwapBaseInNoOrderbook(SwapInstructionBaseIn),
+
+    /// Swap coin or pc from a pool created by `InitializeNoOrderbook`, base amount_out with a slippage of max_amount_in
+    ///
+    ///   Accounts as `SwapBaseInNoOrderbook`
+    SwapBaseOutNoOrderbook(SwapInstructionBaseOut),
+
+    /// Propose a transfer hook program for the registry by anyone, bonding `hook_bond` lamports of
+    /// the amm config. The hook is activated by `ActivateTransferHook` once the challenge period
+    /// ends unless a reviewer vetoes it with `VetoTransferHook`. Only pools created with
+    /// `registry_hooks` accept registry approved hooks. `is_reentrant_safe` must be false, only
+    /// the admin marks a hook re-entrant safe, with `AddTransferHook`.
+    ///
+    ///   0. `[writable, signer]` Proposer Account, pays the rent and the bond
+    ///   1. `[]` AMM config Account, derived from `find_program_address(&[&&AMM_CONFIG_SEED])`.
+    ///   2. `[writable]` Hook registration Account, derived from
+    ///      `find_program_address(&[HOOK_REGISTRATION_SEED, hook_program])`
+    ///   3. `[]` Transfer hook program id
+    ///   4. `[]` ProgramData Account of the hook program, ignored for a program of a
+    ///      non-upgradeable loader
+    ///   5. `[]` System program id
+    ProposeTransferHook(HookConfigArgs),
+
+    /// Veto a proposed transfer hook by a reviewer of the amm config during the challenge
+    /// period, the registration is closed and the bond slashed to the create pool fee destination
+    ///
+    ///   0. `[signer]` Reviewer Account
+    ///   1. `[]` AMM config Account
+    ///   2. `[writable]` Hook registration Account
+    ///   3. `[writable]` AMM create pool fee destination Account
+    VetoTransferHook,
+
+    /// Activate a proposed transfer hook by anyone once the challenge period ends. The hook
+    /// config rent is paid from the bond, the rest returned to the proposer.
+    ///
+    ///   0. `[writable]` Hook registration Account
+    ///   1. `[writable]` Proposer Account
+    ///   2. `[writable]` Hook config Account, derived from `find_program_address(&[HOOK_CONFIG_SEED, hook_program])`
+    ///   3. `[]` ProgramData Account of the hook program, ignored for a program of a
+    ///      non-upgradeable loader
+    ///   4. `[]` System program id
+    ActivateTransferHook,
+
+    /// Deliver the amounts a withdrawal escrowed for the owner, retrying the transfers its
+    /// transfer hook refused. The claim Account is closed to the owner once empty.
+    ///
+    ///   0. `[]` Coin side token program id
+    ///   1. `[]` Pc side token program id
+    ///   2. `[]` AMM Account
+    ///   3. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   4. `[writable]` Escrow claim Account, derived from `escrow_claim_address`
+    ///   5. `[writable]` Coin escrow token Account
+    ///   6. `[writable]` Pc escrow token Account
+    ///   7. `[writable]` User token coin Account. user Account to credit.
+    ///   8. `[writable]` User token pc Account. user Account to credit.
+    ///   9. `[writable, signer]` User wallet Account, the owner of the claim
+    ///   10..N. `[]` Token-2022 transfer accounts, one group per Token-2022 side, coin side first,
+    ///          see `WithdrawNoOrderbook`. The hook accounts are resolved for the escrow token
+    ///          Account as the source and $authority as its owner.
+    ClaimEscrowed,
+
+    /// Create the trade context Account of a Token-2022 mint by anyone. Swaps write the pool,
+    /// direction, amounts, price and reserves into it before their transfers of the mint and
+    /// clear it after, for a transfer hook that lists it in its ExtraAccountMetaList.
+    /// It is passed writable among the hook accounts, see `with_writable_trade_context`.
+    ///
+    ///   0. `[writable, signer]` Payer Account, pays the rent
+    ///   1. `[writable]` Trade context Account, derived from `find_program_address(&[TRADE_CONTEXT_SEED, mint])`
+    ///   2. `[]` Token-2022 mint Account
+    ///   3. `[]` System program id
+    CreateTradeContext,
+
+    /// Swap through an ordered list of pools, base amount_in with one slippage check of
+    /// minimum_amount_out on what the last pool delivers. The output of each pool goes straight
+    /// from its vault into the vault of the next pool, so the user never holds the
+    /// intermediate tokens.
+    ///
+    ///   0. `[writable]` User source token Account, of the input mint of the first pool.
+    ///   1. `[writable]` User destination token Account, of the output mint of the last pool.
+    ///   2. `[signer]` User wallet Account
+    ///   Then `hop_count` groups of `SWAP_ROUTE_HOP_ACCOUNT_LEN` pool accounts, in route order:
+    ///   0. `[]` Coin side token program id, Spl Token or Spl Token-2022
+    ///   1. `[]` Pc side token program id, Spl Token or Spl Token-2022
+    ///   2. `[writable]` AMM Account
+    ///   3. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   4. `[writable]` AMM open orders Account
+    ///   5. `[writable]` AMM coin vault Account
+    ///   6. `[writable]` AMM pc vault Account
+    ///   7. `[]` Market program id
+    ///   8. `[writable]` Market Account. Market program is the owner.
+    ///   9. `[writable]` Market bids Account
+    ///   10. `[writable]` Market asks Account
+    ///   11. `[writable]` Market event queue Account
+    ///   12. `[writable]` Market coin vault Account
+    ///   13. `[writable]` Market pc vault Account
+    ///   14. '[]` Market vault signer Account
+    ///   A pool without orderbook permission takes its AMM Account in place of the open orders
+    ///   and market Accounts.
+    ///   Then the Token-2022 transfer accounts, one group per Token-2022 transfer in route order,
+    ///   the user into the first pool, each pool into the next and the last pool to the user,
+    ///   see `whitelist::split_transfer_hook_accounts`
+    ///   Then `[writable]` the price oracle Account of each pool that has one, in route order
+    SwapRoute(SwapRouteInstruction),
+
+    /// Create the price oracle Account of a pool by the admin. From then on every swap, deposit
+    /// and `WithdrawSingle` of the pool takes the oracle as its very last Account, writable, and
+    /// records the cumulative prices of the pool into it. The oracle keeps them for the last `observation_len` seconds with a
+    /// trade, see `PriceOracle::observe`. The AMM Account must have its `AmmExtension`, see
+    /// `ExtendAmm`.
+    ///
+    ///   0. `[writable, signer]` Admin Account, pays the rent
+    ///   1. `[writable]` AMM Account
+    ///   2. `[writable]` Price oracle Account, derived from `find_program_address(&[PRICE_ORACLE_SEED, amm])`
+    ///   3. `[]` System program id
+    CreatePriceOracle(CreatePriceOracleInstruction),
+
+    /// Deposit a single side into a pool created by `InitializeNoOrderbook`. The part of
+    /// amount_in that balances the deposit is swapped for the other side at the pool price,
+    /// paying the swap fee, and the rest is deposited with the swap output, minting at least
+    /// minimum_lp_amount. The swapped tokens never leave the vaults. A pool created by
+    /// `Initialize2` keeps part of its liquidity and pnl on the orderbook, so it is not supported
+    /// and fails with `InvalidStatus`.
+    ///
+    ///   0. `[]` Coin side token program id
+    ///   1. `[]` Pc side token program id
+    ///   2. `[writable]` AMM Account
+    ///   3. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   4. `[writable]` AMM lp mint Account. Owned by $authority.
+    ///   5. `[writable]` AMM coin vault Account
+    ///   6. `[writable]` AMM pc vault Account
+    ///   7. `[writable]` User token Account of the `side` mint to deposit from.
+    ///   8. `[writable]` User lp token. To deposit the generated tokens, user is the owner.
+    ///   9. `[signer]` User wallet Account
+    ///   10. `[]` (optional) Mint of the other side, required when it is a Token-2022 mint
+    ///   11..N. `[]` Token-2022 transfer accounts of the deposit, as those of `DepositNoOrderbook`
+    ///   N+1. `[writable]` (optional) Price oracle Account as the last Account, required when the pool has one, see `CreatePriceOracle`
+    DepositSingle(DepositSingleInstruction),
+
+    /// Withdraw a single side from a pool created by `InitializeNoOrderbook`. The lp amount is
+    /// burnt for its share of both sides, and the share of the other side is swapped for the
+    /// `side` token at the pool price, paying the swap fee. The user receives at least
+    /// minimum_amount_out. Like `DepositSingle` it fails with `InvalidStatus` on a pool created
+    /// by `Initialize2`.
+    ///
+    ///   0..6. Accounts as `DepositSingle`
+    ///   7. `[writable]` User lp token Account.
+    ///   8. `[writable]` User token Account of the `side` mint to credit.
+    ///   9. `[signer]` User wallet Account
+    ///   10. `[]` (optional) Mint of the other side, required when it is a Token-2022 mint
+    ///   11..N. `[]` Token-2022 transfer accounts of the withdrawal, as those of `WithdrawNoOrderbook`
+    ///   N+1. `[writable]` (optional) Price oracle Account as the last Account, required when the pool has one, see `CreatePriceOracle`
+    WithdrawSingle(WithdrawSingleInstruction),
+
+    /// Pause a pool whose transfer hook program of a mint changed since the pool was created,
+    /// by anyone. The pool moves to `AmmStatus::HookProgramChanged` and only allows
+    /// withdrawals until the admin revives it with `SetParams`. Deposits and swaps of such a
+    /// pool fail with `HookProgramChanged` instead of pausing it.
+    ///
+    ///   0. `[writable]` AMM Account
+    ///   1. `[]` Coin mint Account
+    ///   2. `[]` Pc mint Account
+    PauseOnHookChange,
+
+    /// Extend an AMM Account created before the `AmmExtension` to `AMM_EXTENDED_LEN`, by anyone.
+    /// The pool accumulates its prices from its next swap or deposit on and can get a price
+    /// oracle with `CreatePriceOracle`.
+    ///
+    ///   0. `[writable, signer]` Payer Account, pays the rent of the extension
+    ///   1. `[writable]` AMM Account
+    ///   2. `[]` System program id
+    ExtendAmm,
 }
 
 impl AmmInstruction {
@@ -383,17 +923,32 @@ impl AmmInstruction {
                 let (open_time, _reset) = Self::unpack_u64(rest)?;
                 Self::Initialize(InitializeInstruction { nonce, open_time })
             }
-            1 => {
+            1 | 20 => {
                 let (nonce, rest) = Self::unpack_u8(rest)?;
                 let (open_time, rest) = Self::unpack_u64(rest)?;
                 let (init_pc_amount, rest) = Self::unpack_u64(rest)?;
-                let (init_coin_amount, _reset) = Self::unpack_u64(rest)?;
-                Self::Initialize2(InitializeInstruction2 {
+                let (init_coin_amount, rest) = Self::unpack_u64(rest)?;
+                let (registry_hooks, rest) = match Self::unpack_u8(rest) {
+                    Ok((registry_hooks, rest)) => (registry_hooks != 0, rest),
+                    Err(_) => (false, rest),
+                };
+                let amp = match Self::unpack_u64(rest) {
+                    Ok((amp, _rest)) => amp,
+                    Err(_) => 0,
+                };
+                let init = InitializeInstruction2 {
                     nonce,
                     open_time,
                     init_pc_amount,
                     init_coin_amount,
-                })
+                    registry_hooks,
+                    amp,
+                };
+                if tag == 1 {
+                    Self::Initialize2(init)
+                } else {
+                    Self::InitializeNoOrderbook(init)
+                }
             }
             2 => {
                 let (plan_order_limit, rest) = Self::unpack_u16(rest)?;
@@ -405,37 +960,56 @@ impl AmmInstruction {
                     cancel_order_limit,
                 })
             }
-            3 => {
+            3 | 21 => {
                 let (max_coin_amount, rest) = Self::unpack_u64(rest)?;
                 let (max_pc_amount, rest) = Self::unpack_u64(rest)?;
                 let (base_side, rest) = Self::unpack_u64(rest)?;
-                let other_amount_min = if rest.len() >= 8 {
-                    let (other_amount_min, _rest) = Self::unpack_u64(rest)?;
-                    Some(other_amount_min)
+                let (other_amount_min, rest) = if rest.len() >= 8 {
+                    let (other_amount_min, rest) = Self::unpack_u64(rest)?;
+                    (Some(other_amount_min), rest)
                 } else {
-                    None
+                    (None, rest)
                 };
-                Self::Deposit(DepositInstruction {
+                let wrap_sides = Self::unpack_wrap_sides(rest)?;
+                let deposit = DepositInstruction {
                     max_coin_amount,
                     max_pc_amount,
                     base_side,
                     other_amount_min,
-                })
+                    wrap_sides,
+                };
+                if tag == 3 {
+                    Self::Deposit(deposit)
+                } else {
+                    Self::DepositNoOrderbook(deposit)
+                }
             }
-            4 => {
+            4 | 22 => {
                 let (amount, rest) = Self::unpack_u64(rest)?;
-                let (min_coin_amount, min_pc_amount) = if rest.len() >= 16 {
+                let (min_coin_amount, min_pc_amount, rest) = if rest.len() >= 16 {
                     let (min_coin_amount, rest) = Self::unpack_u64(rest)?;
-                    let (min_pc_amount, _rest) = Self::unpack_u64(rest)?;
-                    (Some(min_coin_amount), Some(min_pc_amount))
+                    let (min_pc_amount, rest) = Self::unpack_u64(rest)?;
+                    (Some(min_coin_amount), Some(min_pc_amount), rest)
                 } else {
-                    (None, None)
+                    (None, None, &[][..])
+                };
+                let (escrow_sides, rest) = match rest.split_first() {
+                    Some((&escrow_sides, rest)) => (escrow_sides, rest),
+                    None => (0, rest),
                 };
-                Self::Withdraw(WithdrawInstruction {
+                let wrap_sides = Self::unpack_wrap_sides(rest)?;
+                let withdraw = WithdrawInstruction {
                     amount,
                     min_coin_amount,
                     min_pc_amount,
-                })
+                    escrow_sides,
+                    wrap_sides,
+                };
+                if tag == 4 {
+                    Self::Withdraw(withdraw)
+                } else {
+                    Self::WithdrawNoOrderbook(withdraw)
+                }
             }
             5 => Self::MigrateToOpenBook,
             6 => {
@@ -450,6 +1024,8 @@ impl AmmInstruction {
                                 new_pubkey: Some(Pubkey::new_from_array(*new_pubkey)),
                                 fees: None,
                                 last_order_distance: None,
+                                ramp_amp: None,
+                                fee_split: None,
                             })
                         } else {
                             return Err(ProgramError::InvalidInstructionData.into());
@@ -465,6 +1041,8 @@ impl AmmInstruction {
                                 new_pubkey: None,
                                 fees: Some(fees),
                                 last_order_distance: None,
+                                ramp_amp: None,
+                                fee_split: None,
                             })
                         } else {
                             return Err(ProgramError::InvalidInstructionData.into());
@@ -483,6 +1061,44 @@ impl AmmInstruction {
                                     last_order_numerator,
                                     last_order_denominator,
                                 }),
+                                ramp_amp: None,
+                                fee_split: None,
+                            })
+                        } else {
+                            return Err(ProgramError::InvalidInstructionData.into());
+                        }
+                    }
+                    AmmParams::RampAmp => {
+                        if rest.len() >= 16 {
+                            let (target_amp, rest) = Self::unpack_u64(rest)?;
+                            let (stop_ramp_ts, _rest) = Self::unpack_u64(rest)?;
+                            Self::SetParams(SetParamsInstruction {
+                                param,
+                                value: None,
+                                new_pubkey: None,
+                                fees: None,
+                                last_order_distance: None,
+                                ramp_amp: Some(RampAmp {
+                                    target_amp,
+                                    stop_ramp_ts,
+                                }),
+                                fee_split: None,
+                            })
+                        } else {
+                            return Err(ProgramError::InvalidInstructionData.into());
+                        }
+                    }
+                    AmmParams::FeeSplit => {
+                        if rest.len() >= 8 {
+                            let (protocol_share, rest) = Self::unpack_u32(rest)?;
+                            let (referrer_share, _rest) = Self::unpack_u32(rest)?;
+                            Self::SetParams(SetParamsInstruction {
+                                param,
+                                fee_split: Some(FeeSplit {
+                                    protocol_share,
+                                    referrer_share,
+                                }),
+                                ..Default::default()
                             })
                         } else {
                             return Err(ProgramError::InvalidInstructionData.into());
@@ -497,6 +1113,8 @@ impl AmmInstruction {
                                 new_pubkey: None,
                                 fees: None,
                                 last_order_distance: None,
+                                ramp_amp: None,
+                                fee_split: None,
                             })
                         } else {
                             return Err(ProgramError::InvalidInstructionData.into());
@@ -509,25 +1127,35 @@ impl AmmInstruction {
                 let (amount, _rest) = Self::unpack_u64(rest)?;
                 Self::WithdrawSrm(WithdrawSrmInstruction { amount })
             }
-            9 => {
+            9 | 23 => {
                 let (amount_in, rest) = Self::unpack_u64(rest)?;
                 let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
-                Self::SwapBaseIn(SwapInstructionBaseIn {
+                let swap = SwapInstructionBaseIn {
                     amount_in,
                     minimum_amount_out,
-                })
+                };
+                if tag == 9 {
+                    Self::SwapBaseIn(swap)
+                } else {
+                    Self::SwapBaseInNoOrderbook(swap)
+                }
             }
             10 => {
                 let (nonce, _rest) = Self::unpack_u8(rest)?;
                 Self::PreInitialize(PreInitializeInstruction { nonce })
             }
-            11 => {
+            11 | 24 => {
                 let (max_amount_in, rest) = Self::unpack_u64(rest)?;
                 let (amount_out, _rest) = Self::unpack_u64(rest)?;
-                Self::SwapBaseOut(SwapInstructionBaseOut {
+                let swap = SwapInstructionBaseOut {
                     max_amount_in,
                     amount_out,
-                })
+                };
+                if tag == 11 {
+                    Self::SwapBaseOut(swap)
+                } else {
+                    Self::SwapBaseOutNoOrderbook(swap)
+                }
             }
             12 => {
                 let (param, rest) = Self::unpack_u8(rest)?;
@@ -539,7 +1167,7 @@ impl AmmInstruction {
                             swap_base_out_value: None,
                         })
                     }
-                    SimulateParams::SwapBaseInInfo => {
+                    SimulateParams::SwapBaseInInfo | SimulateParams::HookDryRunInfo => {
                         let (amount_in, rest) = Self::unpack_u64(rest)?;
                         let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
                         let swap_base_in = Some(SwapInstructionBaseIn {
@@ -575,12 +1203,16 @@ impl AmmInstruction {
             15 => {
                 let (param, rest) = Self::unpack_u8(rest)?;
                 match param {
-                    0 | 1 => {
+                    0 | 1 | 3 | 4 => {
+                        if rest.len() < 32 {
+                            return Err(ProgramError::InvalidInstructionData.into());
+                        }
                         let pubkey = array_ref![rest, 0, 32];
                         Self::UpdateConfigAccount(ConfigArgs {
                             param,
                             owner: Some(Pubkey::new_from_array(*pubkey)),
                             create_pool_fee: None,
+                            value: None,
                         })
                     }
                     2 => {
@@ -589,6 +1221,16 @@ impl AmmInstruction {
                             param,
                             owner: None,
                             create_pool_fee: Some(create_pool_fee),
+                            value: None,
+                        })
+                    }
+                    5 | 6 => {
+                        let (value, _rest) = Self::unpack_u64(rest)?;
+                        Self::UpdateConfigAccount(ConfigArgs {
+                            param,
+                            owner: None,
+                            create_pool_fee: None,
+                            value: Some(value),
                         })
                     }
                     _ => {
@@ -596,6 +1238,95 @@ impl AmmInstruction {
                     }
                 }
             }
+            16 | 25 => {
+                let (max_compute_units, rest) = Self::unpack_u64(rest)?;
+                let (is_reentrant_safe, rest) = Self::unpack_u8(rest)?;
+                // an all zero hash stands in for no hash when an upgrade authority follows
+                let expected_accounts_hash = if rest.len() >= 32 {
+                    Some(*array_ref![rest, 0, 32]).filter(|hash| *hash != [0u8; 32])
+                } else {
+                    None
+                };
+                let upgrade_authority = if rest.len() >= 64 {
+                    Some(Pubkey::new_from_array(*array_ref![rest, 32, 32]))
+                } else {
+                    None
+                };
+                let args = HookConfigArgs {
+                    max_compute_units,
+                    is_reentrant_safe: is_reentrant_safe != 0,
+                    expected_accounts_hash,
+                    upgrade_authority,
+                };
+                if tag == 16 {
+                    Self::AddTransferHook(args)
+                } else {
+                    Self::ProposeTransferHook(args)
+                }
+            }
+            17 => Self::RemoveTransferHook,
+            18 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (coin_hook_account_count, rest) = Self::unpack_u8(rest)?;
+                let (pc_hook_account_count, _rest) = Self::unpack_u8(rest)?;
+                Self::SwapBaseInV2(SwapInstructionBaseInV2 {
+                    amount_in,
+                    minimum_amount_out,
+                    coin_hook_account_count,
+                    pc_hook_account_count,
+                })
+            }
+            19 => {
+                let (max_amount_in, rest) = Self::unpack_u64(rest)?;
+                let (amount_out, rest) = Self::unpack_u64(rest)?;
+                let (coin_hook_account_count, rest) = Self::unpack_u8(rest)?;
+                let (pc_hook_account_count, _rest) = Self::unpack_u8(rest)?;
+                Self::SwapBaseOutV2(SwapInstructionBaseOutV2 {
+                    max_amount_in,
+                    amount_out,
+                    coin_hook_account_count,
+                    pc_hook_account_count,
+                })
+            }
+            26 => Self::VetoTransferHook,
+            27 => Self::ActivateTransferHook,
+            28 => Self::ClaimEscrowed,
+            29 => Self::CreateTradeContext,
+            30 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (hop_count, _rest) = Self::unpack_u8(rest)?;
+                Self::SwapRoute(SwapRouteInstruction {
+                    amount_in,
+                    minimum_amount_out,
+                    hop_count,
+                })
+            }
+            31 => {
+                let (observation_len, _rest) = Self::unpack_u16(rest)?;
+                Self::CreatePriceOracle(CreatePriceOracleInstruction { observation_len })
+            }
+            32 | 33 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount, rest) = Self::unpack_u64(rest)?;
+                let (side, _rest) = Self::unpack_u8(rest)?;
+                if tag == 32 {
+                    Self::DepositSingle(DepositSingleInstruction {
+                        amount_in: amount,
+                        minimum_lp_amount: minimum_amount,
+                        side,
+                    })
+                } else {
+                    Self::WithdrawSingle(WithdrawSingleInstruction {
+                        amount,
+                        minimum_amount_out: minimum_amount,
+                        side,
+                    })
+                }
+            }
+            34 => Self::PauseOnHookChange,
+            35 => Self::ExtendAmm,
             _ => return Err(ProgramError::InvalidInstructionData.into()),
         })
     }
@@ -628,6 +1359,50 @@ impl AmmInstruction {
         }
     }
 
+    fn unpack_u32(input: &[u8]) -> Result<(u32, &[u8]), ProgramError> {
+        if input.len() >= 4 {
+            let (amount, rest) = input.split_at(4);
+            let amount = amount
+                .get(..4)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u32::from_le_bytes)
+                .ok_or(ProgramError::InvalidInstructionData)?;
+            Ok((amount, rest))
+        } else {
+            Err(ProgramError::InvalidInstructionData.into())
+        }
+    }
+
+    fn unpack_wrap_sides(input: &[u8]) -> Result<WrapSides, ProgramError> {
+        if input.is_empty() {
+            return Ok(WrapSides::default());
+        }
+        let (sides, rest) = Self::unpack_u8(input)?;
+        let (coin_hook_account_count, rest) = Self::unpack_u8(rest)?;
+        let (pc_hook_account_count, _rest) = Self::unpack_u8(rest)?;
+        if sides > WRAP_COIN | WRAP_PC {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Ok(WrapSides {
+            coin_hook_account_count: Some(coin_hook_account_count)
+                .filter(|_| sides & WRAP_COIN != 0),
+            pc_hook_account_count: Some(pc_hook_account_count).filter(|_| sides & WRAP_PC != 0),
+        })
+    }
+
+    fn pack_wrap_sides(wrap_sides: &WrapSides, buf: &mut Vec<u8>) {
+        let mut sides = 0;
+        if wrap_sides.coin_hook_account_count.is_some() {
+            sides |= WRAP_COIN;
+        }
+        if wrap_sides.pc_hook_account_count.is_some() {
+            sides |= WRAP_PC;
+        }
+        buf.push(sides);
+        buf.push(wrap_sides.coin_hook_account_count.unwrap_or(0));
+        buf.push(wrap_sides.pc_hook_account_count.unwrap_or(0));
+    }
+
     fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
         if input.len() >= 8 {
             let (amount, rest) = input.split_at(8);
@@ -656,12 +1431,31 @@ impl AmmInstruction {
                 open_time,
                 init_pc_amount,
                 init_coin_amount,
+                registry_hooks,
+                amp,
+            })
+            | Self::InitializeNoOrderbook(InitializeInstruction2 {
+                nonce,
+                open_time,
+                init_pc_amount,
+                init_coin_amount,
+                registry_hooks,
+                amp,
             }) => {
-                buf.push(1);
+                buf.push(match self {
+                    Self::Initialize2(_) => 1,
+                    _ => 20,
+                });
                 buf.push(*nonce);
                 buf.extend_from_slice(&open_time.to_le_bytes());
                 buf.extend_from_slice(&init_pc_amount.to_le_bytes());
                 buf.extend_from_slice(&init_coin_amount.to_le_bytes());
+                if *registry_hooks || *amp != 0 {
+                    buf.push(*registry_hooks as u8);
+                }
+                if *amp != 0 {
+                    buf.extend_from_slice(&amp.to_le_bytes());
+                }
             }
             Self::MonitorStep(MonitorStepInstruction {
                 plan_order_limit,
@@ -678,25 +1472,62 @@ impl AmmInstruction {
                 max_pc_amount,
                 base_side,
                 other_amount_min,
-            }) => {
-                buf.push(3);
-                buf.extend_from_slice(&max_coin_amount.to_le_bytes());
+                wrap_sides,
+            })
+            | Self::DepositNoOrderbook(DepositInstruction {
+                max_coin_amount,
+                max_pc_amount,
+                base_side,
+                other_amount_min,
+                wrap_sides,
+            }) => {
+                buf.push(match self {
+                    Self::Deposit(_) => 3,
+                    _ => 21,
+                });
+                buf.extend_from_slice(&max_coin_amount.to_le_bytes());
                 buf.extend_from_slice(&max_pc_amount.to_le_bytes());
                 buf.extend_from_slice(&base_side.to_le_bytes());
                 if other_amount_min.is_some() {
                     buf.extend_from_slice(&other_amount_min.unwrap().to_le_bytes());
+                } else if *wrap_sides != WrapSides::default() {
+                    buf.extend_from_slice(&[0u8; 8]);
+                }
+                if *wrap_sides != WrapSides::default() {
+                    Self::pack_wrap_sides(wrap_sides, &mut buf);
                 }
             }
             Self::Withdraw(WithdrawInstruction {
                 amount,
                 min_coin_amount,
                 min_pc_amount,
+                escrow_sides,
+                wrap_sides,
+            })
+            | Self::WithdrawNoOrderbook(WithdrawInstruction {
+                amount,
+                min_coin_amount,
+                min_pc_amount,
+                escrow_sides,
+                wrap_sides,
             }) => {
-                buf.push(4);
+                buf.push(match self {
+                    Self::Withdraw(_) => 4,
+                    _ => 22,
+                });
                 buf.extend_from_slice(&amount.to_le_bytes());
+                let wrapped = *wrap_sides != WrapSides::default();
                 if min_coin_amount.is_some() && min_pc_amount.is_some() {
                     buf.extend_from_slice(&min_coin_amount.unwrap().to_le_bytes());
                     buf.extend_from_slice(&min_pc_amount.unwrap().to_le_bytes());
+                } else if *escrow_sides != 0 || wrapped {
+                    buf.extend_from_slice(&[0u8; 16]);
+                }
+                if *escrow_sides != 0 || wrapped {
+                    buf.push(*escrow_sides);
+                }
+                if wrapped {
+                    Self::pack_wrap_sides(wrap_sides, &mut buf);
                 }
             }
             Self::MigrateToOpenBook => {
@@ -708,6 +1539,8 @@ impl AmmInstruction {
                 new_pubkey,
                 fees,
                 last_order_distance,
+                ramp_amp,
+                fee_split,
             }) => {
                 buf.push(6);
                 buf.push(*param);
@@ -736,6 +1569,22 @@ impl AmmInstruction {
                         buf.extend_from_slice(&distance.last_order_numerator.to_le_bytes());
                         buf.extend_from_slice(&distance.last_order_denominator.to_le_bytes());
                     }
+                    AmmParams::RampAmp => {
+                        let ramp_amp = match ramp_amp {
+                            Some(a) => a,
+                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                        };
+                        buf.extend_from_slice(&ramp_amp.target_amp.to_le_bytes());
+                        buf.extend_from_slice(&ramp_amp.stop_ramp_ts.to_le_bytes());
+                    }
+                    AmmParams::FeeSplit => {
+                        let fee_split = match fee_split {
+                            Some(a) => a,
+                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                        };
+                        buf.extend_from_slice(&fee_split.protocol_share.to_le_bytes());
+                        buf.extend_from_slice(&fee_split.referrer_share.to_le_bytes());
+                    }
                     _ => {
                         let value = match value {
                             Some(a) => a,
@@ -755,8 +1604,15 @@ impl AmmInstruction {
             Self::SwapBaseIn(SwapInstructionBaseIn {
                 amount_in,
                 minimum_amount_out,
+            })
+            | Self::SwapBaseInNoOrderbook(SwapInstructionBaseIn {
+                amount_in,
+                minimum_amount_out,
             }) => {
-                buf.push(9);
+                buf.push(match self {
+                    Self::SwapBaseIn(_) => 9,
+                    _ => 23,
+                });
                 buf.extend_from_slice(&amount_in.to_le_bytes());
                 buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
             }
@@ -767,8 +1623,15 @@ impl AmmInstruction {
             Self::SwapBaseOut(SwapInstructionBaseOut {
                 max_amount_in,
                 amount_out,
+            })
+            | Self::SwapBaseOutNoOrderbook(SwapInstructionBaseOut {
+                max_amount_in,
+                amount_out,
             }) => {
-                buf.push(11);
+                buf.push(match self {
+                    Self::SwapBaseOut(_) => 11,
+                    _ => 24,
+                });
                 buf.extend_from_slice(&max_amount_in.to_le_bytes());
                 buf.extend_from_slice(&amount_out.to_le_bytes());
             }
@@ -781,7 +1644,7 @@ impl AmmInstruction {
                 buf.push(*param);
                 match SimulateParams::from_u64(*param as u64) {
                     SimulateParams::PoolInfo | SimulateParams::RunCrankInfo => {}
-                    SimulateParams::SwapBaseInInfo => {
+                    SimulateParams::SwapBaseInInfo | SimulateParams::HookDryRunInfo => {
                         let swap_base_in = match swap_base_in_value {
                             Some(a) => a,
                             None => return Err(ProgramError::InvalidInstructionData.into()),
@@ -810,11 +1673,12 @@ impl AmmInstruction {
                 param,
                 owner,
                 create_pool_fee,
+                value,
             }) => {
                 buf.push(15);
                 buf.push(*param);
                 match param {
-                    0 | 1 => {
+                    0 | 1 | 3 | 4 => {
                         let owner = match owner {
                             Some(owner) => {
                                 if *owner == Pubkey::default() {
@@ -834,9 +1698,120 @@ impl AmmInstruction {
                         };
                         buf.extend_from_slice(&create_pool_fee.to_le_bytes());
                     }
+                    5 | 6 => {
+                        let value = match value {
+                            Some(value) => value,
+                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                        };
+                        buf.extend_from_slice(&value.to_le_bytes());
+                    }
                     _ => return Err(ProgramError::InvalidInstructionData.into()),
                 }
             }
+            Self::AddTransferHook(HookConfigArgs {
+                max_compute_units,
+                is_reentrant_safe,
+                expected_accounts_hash,
+                upgrade_authority,
+            })
+            | Self::ProposeTransferHook(HookConfigArgs {
+                max_compute_units,
+                is_reentrant_safe,
+                expected_accounts_hash,
+                upgrade_authority,
+            }) => {
+                buf.push(match self {
+                    Self::AddTransferHook(_) => 16,
+                    _ => 25,
+                });
+                buf.extend_from_slice(&max_compute_units.to_le_bytes());
+                buf.push(*is_reentrant_safe as u8);
+                if let Some(upgrade_authority) = upgrade_authority {
+                    buf.extend_from_slice(&expected_accounts_hash.unwrap_or_default());
+                    buf.extend_from_slice(upgrade_authority.as_ref());
+                } else if let Some(expected_accounts_hash) = expected_accounts_hash {
+                    buf.extend_from_slice(expected_accounts_hash);
+                }
+            }
+            Self::RemoveTransferHook => {
+                buf.push(17);
+            }
+            Self::SwapBaseInV2(SwapInstructionBaseInV2 {
+                amount_in,
+                minimum_amount_out,
+                coin_hook_account_count,
+                pc_hook_account_count,
+            }) => {
+                buf.push(18);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.push(*coin_hook_account_count);
+                buf.push(*pc_hook_account_count);
+            }
+            Self::SwapBaseOutV2(SwapInstructionBaseOutV2 {
+                max_amount_in,
+                amount_out,
+                coin_hook_account_count,
+                pc_hook_account_count,
+            }) => {
+                buf.push(19);
+                buf.extend_from_slice(&max_amount_in.to_le_bytes());
+                buf.extend_from_slice(&amount_out.to_le_bytes());
+                buf.push(*coin_hook_account_count);
+                buf.push(*pc_hook_account_count);
+            }
+            Self::VetoTransferHook => {
+                buf.push(26);
+            }
+            Self::ActivateTransferHook => {
+                buf.push(27);
+            }
+            Self::ClaimEscrowed => {
+                buf.push(28);
+            }
+            Self::CreateTradeContext => {
+                buf.push(29);
+            }
+            Self::SwapRoute(SwapRouteInstruction {
+                amount_in,
+                minimum_amount_out,
+                hop_count,
+            }) => {
+                buf.push(30);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.push(*hop_count);
+            }
+            Self::CreatePriceOracle(CreatePriceOracleInstruction { observation_len }) => {
+                buf.push(31);
+                buf.extend_from_slice(&observation_len.to_le_bytes());
+            }
+            Self::DepositSingle(DepositSingleInstruction {
+                amount_in,
+                minimum_lp_amount,
+                side,
+            }) => {
+                buf.push(32);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_lp_amount.to_le_bytes());
+                buf.push(*side);
+            }
+            Self::WithdrawSingle(WithdrawSingleInstruction {
+                amount,
+                minimum_amount_out,
+                side,
+            }) => {
+                buf.push(33);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.push(*side);
+            }
+            Self::PauseOnHookChange => {
+                buf.push(34);
+            }
+            Self::ExtendAmm => {
+                buf.push(35);
+            }
         }
         Ok(buf)
     }
@@ -868,19 +1843,21 @@ pub fn initialize2(
     open_time: u64,
     init_pc_amount: u64,
     init_coin_amount: u64,
+    registry_hooks: bool,
 ) -> Result<Instruction, ProgramError> {
     let init_data = AmmInstruction::Initialize2(InitializeInstruction2 {
         nonce,
         open_time,
         init_pc_amount,
         init_coin_amount,
+        registry_hooks,
+        amp: 0,
     });
     let data = init_data.pack()?;
 
-    let accounts = vec![
+    let mut accounts = vec![
         // spl & sys  
         AccountMeta::new_readonly(*token_program_coin, false),
-        AccountMeta::new_readonly(*token_program_pc, false),
         AccountMeta::new_readonly(spl_associated_token_account::id(), false),
         AccountMeta::new_readonly(solana_program::system_program::id(), false),
         AccountMeta::new_readonly(sysvar::rent::id(), false),
@@ -905,6 +1882,10 @@ pub fn initialize2(
         AccountMeta::new(*user_token_pc, false),
         AccountMeta::new(*user_token_lp, false),
     ];
+    // pc side token program goes last when it differs from the coin side
+    if token_program_pc != token_program_coin {
+        accounts.push(AccountMeta::new_readonly(*token_program_pc, false));
+    }
 
     Ok(Instruction {
         program_id: *amm_program,
@@ -913,6 +1894,78 @@ pub fn initialize2(
     })
 }
 
+/// Creates an 'initialize2' instruction with the transfer hook accounts of the initial
+/// liquidity transfers, required when either side is a Token-2022 mint.
+/// `coin_hook_accounts` and `pc_hook_accounts` are the transfer hook accounts
+/// of a hooked Token-2022 mint, see `swap_base_in_checked`.
+pub fn initialize2_checked(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    amm_coin_mint: &Pubkey,
+    amm_pc_mint: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_target_orders: &Pubkey,
+    amm_config: &Pubkey,
+    create_fee_destination: &Pubkey,
+    market_program: &Pubkey,
+    market: &Pubkey,
+    user_wallet: &Pubkey,
+    user_token_coin: &Pubkey,
+    user_token_pc: &Pubkey,
+    user_token_lp: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    coin_hook_accounts: &[AccountMeta],
+    pc_hook_accounts: &[AccountMeta],
+    nonce: u8,
+    open_time: u64,
+    init_pc_amount: u64,
+    init_coin_amount: u64,
+    registry_hooks: bool,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = initialize2(
+        amm_program,
+        amm_pool,
+        amm_authority,
+        amm_open_orders,
+        amm_lp_mint,
+        amm_coin_mint,
+        amm_pc_mint,
+        amm_coin_vault,
+        amm_pc_vault,
+        amm_target_orders,
+        amm_config,
+        create_fee_destination,
+        market_program,
+        market,
+        user_wallet,
+        user_token_coin,
+        user_token_pc,
+        user_token_lp,
+        token_program_coin,
+        token_program_pc,
+        nonce,
+        open_time,
+        init_pc_amount,
+        init_coin_amount,
+        registry_hooks,
+    )?;
+    let mut transfer_accounts =
+        transfer_checked_accounts(amm_coin_mint, token_program_coin, coin_hook_accounts);
+    transfer_accounts.extend(transfer_checked_accounts(
+        amm_pc_mint,
+        token_program_pc,
+        pc_hook_accounts,
+    ));
+    // the transfer accounts follow the user lp account
+    instruction.accounts.splice(21..21, transfer_accounts);
+    Ok(instruction)
+}
+
 /// Creates a 'deposit' instruction.
 pub fn deposit(
     amm_program: &Pubkey,
@@ -941,6 +1994,7 @@ pub fn deposit(
         max_pc_amount,
         base_side,
         other_amount_min,
+        wrap_sides: WrapSides::default(),
     })
     .pack()?;
 
@@ -974,6 +2028,72 @@ pub fn deposit(
     })
 }
 
+/// Creates a 'deposit' instruction with the transfer accounts of each Token-2022 side,
+/// required when either side is a Token-2022 mint.
+/// `coin_hook_accounts` and `pc_hook_accounts` are the transfer hook accounts of a hooked
+/// Token-2022 mint, see `swap_base_in_checked`.
+pub fn deposit_checked(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_target_orders: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    market: &Pubkey,
+    market_event_queue: &Pubkey,
+    user_token_coin: &Pubkey,
+    user_token_pc: &Pubkey,
+    user_token_lp: &Pubkey,
+    user_owner: &Pubkey,
+    coin_mint: &Pubkey,
+    pc_mint: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    coin_hook_accounts: &[AccountMeta],
+    pc_hook_accounts: &[AccountMeta],
+    max_coin_amount: u64,
+    max_pc_amount: u64,
+    base_side: u64,
+    other_amount_min: Option<u64>,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = deposit(
+        amm_program,
+        amm_pool,
+        amm_authority,
+        amm_open_orders,
+        amm_target_orders,
+        amm_lp_mint,
+        amm_coin_vault,
+        amm_pc_vault,
+        market,
+        market_event_queue,
+        user_token_coin,
+        user_token_pc,
+        user_token_lp,
+        user_owner,
+        token_program_coin,
+        token_program_pc,
+        max_coin_amount,
+        max_pc_amount,
+        base_side,
+        other_amount_min,
+    )?;
+    // the transfer accounts of each Token-2022 side follow the pc side token program
+    instruction.accounts.extend(transfer_checked_accounts(
+        coin_mint,
+        token_program_coin,
+        coin_hook_accounts,
+    ));
+    instruction.accounts.extend(transfer_checked_accounts(
+        pc_mint,
+        token_program_pc,
+        pc_hook_accounts,
+    ));
+    Ok(instruction)
+}
+
 /// Creates a 'withdraw' instruction.
 pub fn withdraw(
     amm_program: &Pubkey,
@@ -996,6 +2116,8 @@ pub fn withdraw(
     market_event_queue: &Pubkey,
     market_bids: &Pubkey,
     market_asks: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
 
     referrer_pc_account: Option<&Pubkey>,
 
@@ -1007,12 +2129,14 @@ pub fn withdraw(
         amount,
         min_coin_amount,
         min_pc_amount,
+        escrow_sides: 0,
+        wrap_sides: WrapSides::default(),
     })
     .pack()?;
 
     let mut accounts = vec![
-        // spl token
-        AccountMeta::new_readonly(spl_token::id(), false),
+        // coin side token program
+        AccountMeta::new_readonly(*token_program_coin, false),
         // amm
         AccountMeta::new(*amm_pool, false),
         AccountMeta::new_readonly(*amm_authority, false),
@@ -1040,6 +2164,10 @@ pub fn withdraw(
     if let Some(referrer_pc_key) = referrer_pc_account {
         accounts.push(AccountMeta::new(*referrer_pc_key, false));
     }
+    // pc side token program goes last when it differs from the coin side
+    if token_program_pc != token_program_coin {
+        accounts.push(AccountMeta::new_readonly(*token_program_pc, false));
+    }
 
     Ok(Instruction {
         program_id: *amm_program,
@@ -1048,6 +2176,122 @@ pub fn withdraw(
     })
 }
 
+/// Creates a 'withdraw' instruction with the transfer accounts of each Token-2022 side,
+/// required when either side is a Token-2022 mint.
+/// `coin_hook_accounts` and `pc_hook_accounts` are the transfer hook accounts of a hooked
+/// Token-2022 mint, see `swap_base_in_checked`. The sides in `escrow_sides` are credited
+/// to the user's escrow claim, their hook accounts resolved for the escrow token account.
+pub fn withdraw_checked(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_target_orders: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    market_program: &Pubkey,
+    market: &Pubkey,
+    market_coin_vault: &Pubkey,
+    market_pc_vault: &Pubkey,
+    market_vault_signer: &Pubkey,
+    user_token_lp: &Pubkey,
+    user_token_coin: &Pubkey,
+    user_token_pc: &Pubkey,
+    user_owner: &Pubkey,
+    market_event_queue: &Pubkey,
+    market_bids: &Pubkey,
+    market_asks: &Pubkey,
+    coin_mint: &Pubkey,
+    pc_mint: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    coin_hook_accounts: &[AccountMeta],
+    pc_hook_accounts: &[AccountMeta],
+
+    referrer_pc_account: Option<&Pubkey>,
+
+    amount: u64,
+    min_coin_amount: Option<u64>,
+    min_pc_amount: Option<u64>,
+    escrow_sides: u8,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = withdraw(
+        amm_program,
+        amm_pool,
+        amm_authority,
+        amm_open_orders,
+        amm_target_orders,
+        amm_lp_mint,
+        amm_coin_vault,
+        amm_pc_vault,
+        market_program,
+        market,
+        market_coin_vault,
+        market_pc_vault,
+        market_vault_signer,
+        user_token_lp,
+        user_token_coin,
+        user_token_pc,
+        user_owner,
+        market_event_queue,
+        market_bids,
+        market_asks,
+        token_program_coin,
+        token_program_pc,
+        referrer_pc_account,
+        amount,
+        min_coin_amount,
+        min_pc_amount,
+    )?;
+    if *token_program_coin != spl_token_2022::id()
+        && *token_program_pc != spl_token_2022::id()
+        && escrow_sides == 0
+    {
+        return Ok(instruction);
+    }
+    // the referrer pc account and the pc side token program take fixed positions
+    if token_program_pc != token_program_coin {
+        instruction.accounts.pop();
+    }
+    let referrer_pc_key = referrer_pc_account.copied().unwrap_or_default();
+    instruction
+        .accounts
+        .push(AccountMeta::new(referrer_pc_key, false));
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(*token_program_pc, false));
+    if escrow_sides != 0 {
+        instruction.data = AmmInstruction::Withdraw(WithdrawInstruction {
+            amount,
+            min_coin_amount,
+            min_pc_amount,
+            escrow_sides,
+            wrap_sides: WrapSides::default(),
+        })
+        .pack()?;
+        let index = instruction.accounts.len();
+        insert_escrow_accounts(
+            &mut instruction.accounts,
+            index,
+            amm_program,
+            amm_pool,
+            user_owner,
+        );
+    }
+    instruction.accounts.extend(transfer_checked_accounts(
+        coin_mint,
+        token_program_coin,
+        coin_hook_accounts,
+    ));
+    instruction.accounts.extend(transfer_checked_accounts(
+        pc_mint,
+        token_program_pc,
+        pc_hook_accounts,
+    ));
+    Ok(instruction)
+}
+
 /// Creates a 'swap base in' instruction.
 pub fn swap_base_in(
     amm_program: &Pubkey,
@@ -1071,47 +2315,39 @@ pub fn swap_base_in(
     amount_in: u64,
     minimum_amount_out: u64,
 ) -> Result<Instruction, ProgramError> {
-    let data = AmmInstruction::SwapBaseIn(SwapInstructionBaseIn {
+    swap_base_in_with_hooks(
+        amm_program,
+        &spl_token::id(),
+        &spl_token::id(),
+        amm_pool,
+        amm_authority,
+        amm_open_orders,
+        amm_coin_vault,
+        amm_pc_vault,
+        market_program,
+        market,
+        market_bids,
+        market_asks,
+        market_event_queue,
+        market_coin_vault,
+        market_pc_vault,
+        market_vault_signer,
+        user_token_source,
+        user_token_destination,
+        user_source_owner,
+        &[],
         amount_in,
         minimum_amount_out,
-    })
-    .pack()?;
-
-    let accounts = vec![
-        // spl token
-        AccountMeta::new_readonly(spl_token::id(), false),
-        // amm
-        AccountMeta::new(*amm_pool, false),
-        AccountMeta::new_readonly(*amm_authority, false),
-        AccountMeta::new(*amm_open_orders, false),
-        // AccountMeta::new(*amm_target_orders, false),
-        AccountMeta::new(*amm_coin_vault, false),
-        AccountMeta::new(*amm_pc_vault, false),
-        // market
-        AccountMeta::new_readonly(*market_program, false),
-        AccountMeta::new(*market, false),
-        AccountMeta::new(*market_bids, false),
-        AccountMeta::new(*market_asks, false),
-        AccountMeta::new(*market_event_queue, false),
-        AccountMeta::new(*market_coin_vault, false),
-        AccountMeta::new(*market_pc_vault, false),
-        AccountMeta::new_readonly(*market_vault_signer, false),
-        // user
-        AccountMeta::new(*user_token_source, false),
-        AccountMeta::new(*user_token_destination, false),
-        AccountMeta::new_readonly(*user_source_owner, true),
-    ];
-
-    Ok(Instruction {
-        program_id: *amm_program,
-        accounts,
-        data,
-    })
+    )
 }
 
-/// Creates a 'swap base out' instruction.
-pub fn swap_base_out(
+/// Creates a 'swap base in' instruction for a pool with Token-2022 sides.
+/// `transfer_hook_accounts` follow the user accounts, and the pc side token program
+/// is appended when it differs from the coin side.
+pub fn swap_base_in_with_hooks(
     amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
     amm_pool: &Pubkey,
     amm_authority: &Pubkey,
     amm_open_orders: &Pubkey,
@@ -1128,19 +2364,20 @@ pub fn swap_base_out(
     user_token_source: &Pubkey,
     user_token_destination: &Pubkey,
     user_source_owner: &Pubkey,
+    transfer_hook_accounts: &[AccountMeta],
 
-    max_amount_in: u64,
-    amount_out: u64,
+    amount_in: u64,
+    minimum_amount_out: u64,
 ) -> Result<Instruction, ProgramError> {
-    let data = AmmInstruction::SwapBaseOut(SwapInstructionBaseOut {
-        max_amount_in,
-        amount_out,
+    let data = AmmInstruction::SwapBaseIn(SwapInstructionBaseIn {
+        amount_in,
+        minimum_amount_out,
     })
     .pack()?;
 
-    let accounts = vec![
-        // spl token
-        AccountMeta::new_readonly(spl_token::id(), false),
+    let mut accounts = vec![
+        // coin side token program, spl token or token-2022
+        AccountMeta::new_readonly(*token_program_coin, false),
         // amm
         AccountMeta::new(*amm_pool, false),
         AccountMeta::new_readonly(*amm_authority, false),
@@ -1162,6 +2399,12 @@ pub fn swap_base_out(
         AccountMeta::new(*user_token_destination, false),
         AccountMeta::new_readonly(*user_source_owner, true),
     ];
+    // token-2022 transfer hook extra accounts
+    accounts.extend_from_slice(transfer_hook_accounts);
+    // pc side token program goes last when it differs from the coin side
+    if token_program_pc != token_program_coin {
+        accounts.push(AccountMeta::new_readonly(*token_program_pc, false));
+    }
 
     Ok(Instruction {
         program_id: *amm_program,
@@ -1170,15 +2413,20 @@ pub fn swap_base_out(
     })
 }
 
-/// Creates a 'migrate_to_openbook' instruction.
-pub fn migrate_to_openbook(
+/// Creates a 'swap base in' instruction with the mint of each transfer,
+/// required when either side is a Token-2022 mint.
+/// `source_hook_accounts` and `destination_hook_accounts` are the transfer hook
+/// accounts of a hooked Token-2022 mint: the hook config Account, the extra accounts
+/// resolved from its ExtraAccountMetaList, the hook program id and the ExtraAccountMetaList Account.
+pub fn swap_base_in_checked(
     amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
     amm_pool: &Pubkey,
     amm_authority: &Pubkey,
     amm_open_orders: &Pubkey,
     amm_coin_vault: &Pubkey,
     amm_pc_vault: &Pubkey,
-    amm_target_orders: &Pubkey,
     market_program: &Pubkey,
     market: &Pubkey,
     market_bids: &Pubkey,
@@ -1187,23 +2435,327 @@ pub fn migrate_to_openbook(
     market_coin_vault: &Pubkey,
     market_pc_vault: &Pubkey,
     market_vault_signer: &Pubkey,
+    user_token_source: &Pubkey,
+    user_token_destination: &Pubkey,
+    user_source_owner: &Pubkey,
+    source_mint: &Pubkey,
+    source_token_program: &Pubkey,
+    source_hook_accounts: &[AccountMeta],
+    destination_mint: &Pubkey,
+    destination_token_program: &Pubkey,
+    destination_hook_accounts: &[AccountMeta],
 
-    new_amm_open_orders: &Pubkey,
-    new_market_program: &Pubkey,
-    new_market: &Pubkey,
-
-    admin: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
 ) -> Result<Instruction, ProgramError> {
-    let data = AmmInstruction::MigrateToOpenBook.pack()?;
+    let mut transfer_accounts =
+        transfer_checked_accounts(source_mint, source_token_program, source_hook_accounts);
+    transfer_accounts.extend(transfer_checked_accounts(
+        destination_mint,
+        destination_token_program,
+        destination_hook_accounts,
+    ));
+    swap_base_in_with_hooks(
+        amm_program,
+        token_program_coin,
+        token_program_pc,
+        amm_pool,
+        amm_authority,
+        amm_open_orders,
+        amm_coin_vault,
+        amm_pc_vault,
+        market_program,
+        market,
+        market_bids,
+        market_asks,
+        market_event_queue,
+        market_coin_vault,
+        market_pc_vault,
+        market_vault_signer,
+        user_token_source,
+        user_token_destination,
+        user_source_owner,
+        &transfer_accounts,
+        amount_in,
+        minimum_amount_out,
+    )
+}
 
-    let accounts = vec![
-        // spl token
-        AccountMeta::new_readonly(spl_token::id(), false),
-        AccountMeta::new_readonly(solana_program::system_program::id(), false),
-        AccountMeta::new_readonly(sysvar::rent::id(), false),
-        // amm
-        AccountMeta::new(*amm_pool, false),
-        AccountMeta::new_readonly(*amm_authority, false),
+/// Creates a 'swap base out' instruction.
+pub fn swap_base_out(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    market_program: &Pubkey,
+    market: &Pubkey,
+    market_bids: &Pubkey,
+    market_asks: &Pubkey,
+    market_event_queue: &Pubkey,
+    market_coin_vault: &Pubkey,
+    market_pc_vault: &Pubkey,
+    market_vault_signer: &Pubkey,
+    user_token_source: &Pubkey,
+    user_token_destination: &Pubkey,
+    user_source_owner: &Pubkey,
+
+    max_amount_in: u64,
+    amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    swap_base_out_with_hooks(
+        amm_program,
+        &spl_token::id(),
+        &spl_token::id(),
+        amm_pool,
+        amm_authority,
+        amm_open_orders,
+        amm_coin_vault,
+        amm_pc_vault,
+        market_program,
+        market,
+        market_bids,
+        market_asks,
+        market_event_queue,
+        market_coin_vault,
+        market_pc_vault,
+        market_vault_signer,
+        user_token_source,
+        user_token_destination,
+        user_source_owner,
+        &[],
+        max_amount_in,
+        amount_out,
+    )
+}
+
+/// Creates a 'swap base out' instruction for a pool with Token-2022 sides.
+/// `transfer_hook_accounts` follow the user accounts, and the pc side token program
+/// is appended when it differs from the coin side.
+pub fn swap_base_out_with_hooks(
+    amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    market_program: &Pubkey,
+    market: &Pubkey,
+    market_bids: &Pubkey,
+    market_asks: &Pubkey,
+    market_event_queue: &Pubkey,
+    market_coin_vault: &Pubkey,
+    market_pc_vault: &Pubkey,
+    market_vault_signer: &Pubkey,
+    user_token_source: &Pubkey,
+    user_token_destination: &Pubkey,
+    user_source_owner: &Pubkey,
+    transfer_hook_accounts: &[AccountMeta],
+
+    max_amount_in: u64,
+    amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::SwapBaseOut(SwapInstructionBaseOut {
+        max_amount_in,
+        amount_out,
+    })
+    .pack()?;
+
+    let mut accounts = vec![
+        // coin side token program, spl token or token-2022
+        AccountMeta::new_readonly(*token_program_coin, false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(*amm_open_orders, false),
+        // AccountMeta::new(*amm_target_orders, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        // market
+        AccountMeta::new_readonly(*market_program, false),
+        AccountMeta::new(*market, false),
+        AccountMeta::new(*market_bids, false),
+        AccountMeta::new(*market_asks, false),
+        AccountMeta::new(*market_event_queue, false),
+        AccountMeta::new(*market_coin_vault, false),
+        AccountMeta::new(*market_pc_vault, false),
+        AccountMeta::new_readonly(*market_vault_signer, false),
+        // user
+        AccountMeta::new(*user_token_source, false),
+        AccountMeta::new(*user_token_destination, false),
+        AccountMeta::new_readonly(*user_source_owner, true),
+    ];
+    // token-2022 transfer hook extra accounts
+    accounts.extend_from_slice(transfer_hook_accounts);
+    // pc side token program goes last when it differs from the coin side
+    if token_program_pc != token_program_coin {
+        accounts.push(AccountMeta::new_readonly(*token_program_pc, false));
+    }
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'swap base out' instruction with the mint of each transfer,
+/// see `swap_base_in_checked`.
+pub fn swap_base_out_checked(
+    amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    market_program: &Pubkey,
+    market: &Pubkey,
+    market_bids: &Pubkey,
+    market_asks: &Pubkey,
+    market_event_queue: &Pubkey,
+    market_coin_vault: &Pubkey,
+    market_pc_vault: &Pubkey,
+    market_vault_signer: &Pubkey,
+    user_token_source: &Pubkey,
+    user_token_destination: &Pubkey,
+    user_source_owner: &Pubkey,
+    source_mint: &Pubkey,
+    source_token_program: &Pubkey,
+    source_hook_accounts: &[AccountMeta],
+    destination_mint: &Pubkey,
+    destination_token_program: &Pubkey,
+    destination_hook_accounts: &[AccountMeta],
+
+    max_amount_in: u64,
+    amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut transfer_accounts =
+        transfer_checked_accounts(source_mint, source_token_program, source_hook_accounts);
+    transfer_accounts.extend(transfer_checked_accounts(
+        destination_mint,
+        destination_token_program,
+        destination_hook_accounts,
+    ));
+    swap_base_out_with_hooks(
+        amm_program,
+        token_program_coin,
+        token_program_pc,
+        amm_pool,
+        amm_authority,
+        amm_open_orders,
+        amm_coin_vault,
+        amm_pc_vault,
+        market_program,
+        market,
+        market_bids,
+        market_asks,
+        market_event_queue,
+        market_coin_vault,
+        market_pc_vault,
+        market_vault_signer,
+        user_token_source,
+        user_token_destination,
+        user_source_owner,
+        &transfer_accounts,
+        max_amount_in,
+        amount_out,
+    )
+}
+
+/// Accounts of one swap transfer: a Token-2022 transfer takes its mint and hook accounts
+fn transfer_checked_accounts(
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    hook_accounts: &[AccountMeta],
+) -> Vec<AccountMeta> {
+    if *token_program != spl_token_2022::id() {
+        return Vec::new();
+    }
+    let mut accounts = vec![AccountMeta::new_readonly(*mint, false)];
+    accounts.extend_from_slice(hook_accounts);
+    accounts
+}
+
+/// Insert the escrow accounts of a withdrawal at `index`, the user wallet pays their rent
+fn insert_escrow_accounts(
+    accounts: &mut Vec<AccountMeta>,
+    index: usize,
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    user_owner: &Pubkey,
+) {
+    for account in accounts.iter_mut() {
+        if account.pubkey == *user_owner {
+            account.is_writable = true;
+        }
+    }
+    accounts.splice(
+        index..index,
+        [
+            AccountMeta::new_readonly(solana_program::system_program::id(), false),
+            AccountMeta::new(
+                crate::processor::escrow_claim_address(amm_program, amm_pool, user_owner).0,
+                false,
+            ),
+            AccountMeta::new(
+                crate::processor::escrow_token_address(
+                    amm_program,
+                    amm_pool,
+                    crate::processor::COIN_ESCROW_ASSOCIATED_SEED,
+                ),
+                false,
+            ),
+            AccountMeta::new(
+                crate::processor::escrow_token_address(
+                    amm_program,
+                    amm_pool,
+                    crate::processor::PC_ESCROW_ASSOCIATED_SEED,
+                ),
+                false,
+            ),
+        ],
+    );
+}
+
+/// Creates a 'migrate_to_openbook' instruction.
+pub fn migrate_to_openbook(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_target_orders: &Pubkey,
+    market_program: &Pubkey,
+    market: &Pubkey,
+    market_bids: &Pubkey,
+    market_asks: &Pubkey,
+    market_event_queue: &Pubkey,
+    market_coin_vault: &Pubkey,
+    market_pc_vault: &Pubkey,
+    market_vault_signer: &Pubkey,
+
+    new_amm_open_orders: &Pubkey,
+    new_market_program: &Pubkey,
+    new_market: &Pubkey,
+
+    admin: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::MigrateToOpenBook.pack()?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
         AccountMeta::new(*amm_open_orders, false),
         AccountMeta::new(*amm_coin_vault, false),
         AccountMeta::new(*amm_pc_vault, false),
@@ -1251,12 +2803,14 @@ pub fn withdrawpnl(
     market_coin_vault: &Pubkey,
     market_pc_vault: &Pubkey,
     market_vault_signer: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
     let data = AmmInstruction::WithdrawPnl.pack()?;
 
-    let accounts = vec![
-        // spl token
-        AccountMeta::new_readonly(spl_token::id(), false),
+    let mut accounts = vec![
+        // coin side token program
+        AccountMeta::new_readonly(*token_program_coin, false),
         // amm
         AccountMeta::new(*amm_pool, false),
         AccountMeta::new_readonly(*amm_config, false),
@@ -1276,6 +2830,10 @@ pub fn withdrawpnl(
         AccountMeta::new(*market_pc_vault, false),
         AccountMeta::new_readonly(*market_vault_signer, false),
     ];
+    // pc side token program goes last when it differs from the coin side
+    if token_program_pc != token_program_coin {
+        accounts.push(AccountMeta::new_readonly(*token_program_pc, false));
+    }
 
     Ok(Instruction {
         program_id: *amm_program,
@@ -1284,6 +2842,78 @@ pub fn withdrawpnl(
     })
 }
 
+/// Creates a 'withdrawpnl' instruction with the transfer accounts of each Token-2022 side,
+/// required when either side is a Token-2022 mint.
+/// `coin_hook_accounts` and `pc_hook_accounts` are the transfer hook accounts of a hooked
+/// Token-2022 mint, see `swap_base_in_checked`.
+pub fn withdrawpnl_checked(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_config: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    user_token_coin: &Pubkey,
+    user_token_pc: &Pubkey,
+    user_owner: &Pubkey,
+    amm_target_orders: &Pubkey,
+    market_program: &Pubkey,
+    market: &Pubkey,
+    market_event_queue: &Pubkey,
+    market_coin_vault: &Pubkey,
+    market_pc_vault: &Pubkey,
+    market_vault_signer: &Pubkey,
+    coin_mint: &Pubkey,
+    pc_mint: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    coin_hook_accounts: &[AccountMeta],
+    pc_hook_accounts: &[AccountMeta],
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = withdrawpnl(
+        amm_program,
+        amm_pool,
+        amm_config,
+        amm_authority,
+        amm_open_orders,
+        amm_coin_vault,
+        amm_pc_vault,
+        user_token_coin,
+        user_token_pc,
+        user_owner,
+        amm_target_orders,
+        market_program,
+        market,
+        market_event_queue,
+        market_coin_vault,
+        market_pc_vault,
+        market_vault_signer,
+        token_program_coin,
+        token_program_pc,
+    )?;
+    if *token_program_coin != spl_token_2022::id() && *token_program_pc != spl_token_2022::id() {
+        return Ok(instruction);
+    }
+    // the pc side token program takes a fixed position
+    if token_program_pc == token_program_coin {
+        instruction
+            .accounts
+            .push(AccountMeta::new_readonly(*token_program_pc, false));
+    }
+    instruction.accounts.extend(transfer_checked_accounts(
+        coin_mint,
+        token_program_coin,
+        coin_hook_accounts,
+    ));
+    instruction.accounts.extend(transfer_checked_accounts(
+        pc_mint,
+        token_program_pc,
+        pc_hook_accounts,
+    ));
+    Ok(instruction)
+}
+
 /// Creates a 'SetParams' instruction.
 pub fn set_params(
     amm_program: &Pubkey,
@@ -1308,6 +2938,8 @@ pub fn set_params(
     new_amm_open_orders: Option<Pubkey>,
     fees: Option<Fees>,
     last_order_distance: Option<LastOrderDistance>,
+    ramp_amp: Option<RampAmp>,
+    fee_split: Option<FeeSplit>,
 ) -> Result<Instruction, ProgramError> {
     let data = AmmInstruction::SetParams(SetParamsInstruction {
         param,
@@ -1315,6 +2947,8 @@ pub fn set_params(
         new_pubkey,
         fees,
         last_order_distance,
+        ramp_amp,
+        fee_split,
     })
     .pack()?;
 
@@ -1758,3 +3392,1215 @@ pub fn update_config_account(
         data,
     })
 }
+
+/// Creates an 'add_transfer_hook' instruction.
+pub fn add_transfer_hook(
+    amm_program: &Pubkey,
+    admin: &Pubkey,
+    hook_config: &Pubkey,
+    hook_program: &Pubkey,
+    hook_config_args: HookConfigArgs,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::AddTransferHook(hook_config_args).pack()?;
+    let accounts = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(*hook_config, false),
+        AccountMeta::new_readonly(*hook_program, false),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(
+            crate::whitelist::hook_program_data_address(hook_program),
+            false,
+        ),
+    ];
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'propose_transfer_hook' instruction.
+pub fn propose_transfer_hook(
+    amm_program: &Pubkey,
+    proposer: &Pubkey,
+    amm_config: &Pubkey,
+    hook_program: &Pubkey,
+    hook_config_args: HookConfigArgs,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::ProposeTransferHook(hook_config_args).pack()?;
+    let accounts = vec![
+        AccountMeta::new(*proposer, true),
+        AccountMeta::new_readonly(*amm_config, false),
+        AccountMeta::new(
+            crate::whitelist::hook_registration_address(amm_program, hook_program).0,
+            false,
+        ),
+        AccountMeta::new_readonly(*hook_program, false),
+        AccountMeta::new_readonly(
+            crate::whitelist::hook_program_data_address(hook_program),
+            false,
+        ),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'veto_transfer_hook' instruction.
+pub fn veto_transfer_hook(
+    amm_program: &Pubkey,
+    reviewer: &Pubkey,
+    amm_config: &Pubkey,
+    hook_program: &Pubkey,
+    create_fee_destination: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::VetoTransferHook.pack()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*reviewer, true),
+        AccountMeta::new_readonly(*amm_config, false),
+        AccountMeta::new(
+            crate::whitelist::hook_registration_address(amm_program, hook_program).0,
+            false,
+        ),
+        AccountMeta::new(*create_fee_destination, false),
+    ];
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'activate_transfer_hook' instruction.
+pub fn activate_transfer_hook(
+    amm_program: &Pubkey,
+    proposer: &Pubkey,
+    hook_program: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::ActivateTransferHook.pack()?;
+    let accounts = vec![
+        AccountMeta::new(
+            crate::whitelist::hook_registration_address(amm_program, hook_program).0,
+            false,
+        ),
+        AccountMeta::new(*proposer, false),
+        AccountMeta::new(
+            crate::whitelist::hook_config_address(amm_program, hook_program).0,
+            false,
+        ),
+        AccountMeta::new_readonly(
+            crate::whitelist::hook_program_data_address(hook_program),
+            false,
+        ),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'claim_escrowed' instruction, see `withdraw_no_orderbook` for the transfer accounts.
+pub fn claim_escrowed(
+    amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    user_token_coin: &Pubkey,
+    user_token_pc: &Pubkey,
+    user_owner: &Pubkey,
+    coin_mint: &Pubkey,
+    coin_hook_accounts: &[AccountMeta],
+    pc_mint: &Pubkey,
+    pc_hook_accounts: &[AccountMeta],
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::ClaimEscrowed.pack()?;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*token_program_coin, false),
+        AccountMeta::new_readonly(*token_program_pc, false),
+        AccountMeta::new_readonly(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(
+            crate::processor::escrow_claim_address(amm_program, amm_pool, user_owner).0,
+            false,
+        ),
+        AccountMeta::new(
+            crate::processor::escrow_token_address(
+                amm_program,
+                amm_pool,
+                crate::processor::COIN_ESCROW_ASSOCIATED_SEED,
+            ),
+            false,
+        ),
+        AccountMeta::new(
+            crate::processor::escrow_token_address(
+                amm_program,
+                amm_pool,
+                crate::processor::PC_ESCROW_ASSOCIATED_SEED,
+            ),
+            false,
+        ),
+        AccountMeta::new(*user_token_coin, false),
+        AccountMeta::new(*user_token_pc, false),
+        AccountMeta::new(*user_owner, true),
+    ];
+    // token-2022 transfer accounts, coin side first
+    accounts.extend(transfer_checked_accounts(
+        coin_mint,
+        token_program_coin,
+        coin_hook_accounts,
+    ));
+    accounts.extend(transfer_checked_accounts(
+        pc_mint,
+        token_program_pc,
+        pc_hook_accounts,
+    ));
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'create_trade_context' instruction.
+pub fn create_trade_context(
+    amm_program: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::CreateTradeContext.pack()?;
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(
+            crate::processor::trade_context_address(amm_program, mint).0,
+            false,
+        ),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Mark the trade context of `mint` writable among the hook accounts of its transfer, as
+/// resolved from an ExtraAccountMetaList that lists it read-only.
+pub fn with_writable_trade_context(
+    amm_program: &Pubkey,
+    mint: &Pubkey,
+    hook_accounts: &[AccountMeta],
+) -> Vec<AccountMeta> {
+    let trade_context = crate::processor::trade_context_address(amm_program, mint).0;
+    hook_accounts
+        .iter()
+        .map(|account| {
+            if account.pubkey == trade_context {
+                AccountMeta::new(account.pubkey, account.is_signer)
+            } else {
+                account.clone()
+            }
+        })
+        .collect()
+}
+
+/// Creates a 'create_price_oracle' instruction.
+pub fn create_price_oracle(
+    amm_program: &Pubkey,
+    admin: &Pubkey,
+    amm_pool: &Pubkey,
+    observation_len: u16,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::CreatePriceOracle(CreatePriceOracleInstruction { observation_len })
+        .pack()?;
+    let accounts = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new(
+            crate::processor::price_oracle_address(amm_program, amm_pool).0,
+            false,
+        ),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Append the price oracle of `amm_pool` to a swap, deposit or `withdraw_single` instruction of
+/// a pool that has one. For a `swap_route`, call it for each such pool in route order.
+pub fn with_price_oracle(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    mut instruction: Instruction,
+) -> Instruction {
+    instruction.accounts.push(AccountMeta::new(
+        crate::processor::price_oracle_address(amm_program, amm_pool).0,
+        false,
+    ));
+    instruction
+}
+
+/// Append the token wrapper accounts of `underlying_mint` to a `deposit` or `withdraw`
+/// instruction of a pool whose vault of `side`, `WRAP_COIN` or `WRAP_PC`, holds its wrapper
+/// mint, and set the side in the `wrap_sides` of its data. Call it before `with_price_oracle`.
+/// The user token Account of that side then holds `underlying_mint`, and `hook_accounts` are
+/// resolved for the transfer into the wrapper escrow on deposit, out of it on withdraw.
+pub fn with_wrapper(
+    side: u8,
+    underlying_mint: &Pubkey,
+    hook_accounts: &[AccountMeta],
+    mut instruction: Instruction,
+) -> Result<Instruction, ProgramError> {
+    let mut data = AmmInstruction::unpack(&instruction.data)?;
+    let wrap_sides = match &mut data {
+        AmmInstruction::Deposit(DepositInstruction { wrap_sides, .. })
+        | AmmInstruction::Withdraw(WithdrawInstruction { wrap_sides, .. }) => wrap_sides,
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+    let hook_account_count =
+        u8::try_from(hook_accounts.len()).map_err(|_| ProgramError::InvalidArgument)?;
+    let mut index = instruction.accounts.len();
+    match side {
+        WRAP_COIN if wrap_sides.coin_hook_account_count.is_none() => {
+            // the coin side group goes before the pc side group
+            if let Some(pc_hook_account_count) = wrap_sides.pc_hook_account_count {
+                index -= WRAP_ACCOUNT_LEN + pc_hook_account_count as usize;
+            }
+            wrap_sides.coin_hook_account_count = Some(hook_account_count);
+        }
+        WRAP_PC if wrap_sides.pc_hook_account_count.is_none() => {
+            wrap_sides.pc_hook_account_count = Some(hook_account_count);
+        }
+        _ => return Err(ProgramError::InvalidArgument),
+    }
+    instruction.data = data.pack()?;
+    let wrapper_program = token_wrapper::id();
+    let mut wrap_accounts = vec![
+        AccountMeta::new_readonly(wrapper_program, false),
+        AccountMeta::new_readonly(
+            token_wrapper::state::wrapper_address(&wrapper_program, underlying_mint).0,
+            false,
+        ),
+        AccountMeta::new_readonly(*underlying_mint, false),
+        AccountMeta::new(
+            token_wrapper::state::wrapper_mint_address(&wrapper_program, underlying_mint).0,
+            false,
+        ),
+        AccountMeta::new(
+            token_wrapper::state::escrow_address(&wrapper_program, underlying_mint).0,
+            false,
+        ),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+    ];
+    wrap_accounts.extend_from_slice(hook_accounts);
+    instruction.accounts.splice(index..index, wrap_accounts);
+    Ok(instruction)
+}
+
+/// Creates an 'remove_transfer_hook' instruction.
+pub fn remove_transfer_hook(
+    amm_program: &Pubkey,
+    admin: &Pubkey,
+    hook_config: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::RemoveTransferHook.pack()?;
+    let accounts = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(*hook_config, false),
+    ];
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'swap base in v2' instruction.
+/// `coin_hook_accounts` and `pc_hook_accounts` are the transfer hook accounts of a hooked
+/// Token-2022 mint, see `SwapBaseInV2`.
+pub fn swap_base_in_v2(
+    amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_coin_mint: &Pubkey,
+    amm_pc_mint: &Pubkey,
+    market_program: &Pubkey,
+    market: &Pubkey,
+    market_bids: &Pubkey,
+    market_asks: &Pubkey,
+    market_event_queue: &Pubkey,
+    market_coin_vault: &Pubkey,
+    market_pc_vault: &Pubkey,
+    market_vault_signer: &Pubkey,
+    user_token_source: &Pubkey,
+    user_token_destination: &Pubkey,
+    user_source_owner: &Pubkey,
+    coin_hook_accounts: &[AccountMeta],
+    pc_hook_accounts: &[AccountMeta],
+
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::SwapBaseInV2(SwapInstructionBaseInV2 {
+        amount_in,
+        minimum_amount_out,
+        coin_hook_account_count: hook_account_count(coin_hook_accounts)?,
+        pc_hook_account_count: hook_account_count(pc_hook_accounts)?,
+    })
+    .pack()?;
+    let accounts = swap_v2_accounts(
+        token_program_coin,
+        token_program_pc,
+        amm_pool,
+        amm_authority,
+        amm_open_orders,
+        amm_coin_vault,
+        amm_pc_vault,
+        amm_coin_mint,
+        amm_pc_mint,
+        market_program,
+        market,
+        market_bids,
+        market_asks,
+        market_event_queue,
+        market_coin_vault,
+        market_pc_vault,
+        market_vault_signer,
+        user_token_source,
+        user_token_destination,
+        user_source_owner,
+        coin_hook_accounts,
+        pc_hook_accounts,
+    );
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'swap base out v2' instruction, see `swap_base_in_v2`.
+pub fn swap_base_out_v2(
+    amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_coin_mint: &Pubkey,
+    amm_pc_mint: &Pubkey,
+    market_program: &Pubkey,
+    market: &Pubkey,
+    market_bids: &Pubkey,
+    market_asks: &Pubkey,
+    market_event_queue: &Pubkey,
+    market_coin_vault: &Pubkey,
+    market_pc_vault: &Pubkey,
+    market_vault_signer: &Pubkey,
+    user_token_source: &Pubkey,
+    user_token_destination: &Pubkey,
+    user_source_owner: &Pubkey,
+    coin_hook_accounts: &[AccountMeta],
+    pc_hook_accounts: &[AccountMeta],
+
+    max_amount_in: u64,
+    amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::SwapBaseOutV2(SwapInstructionBaseOutV2 {
+        max_amount_in,
+        amount_out,
+        coin_hook_account_count: hook_account_count(coin_hook_accounts)?,
+        pc_hook_account_count: hook_account_count(pc_hook_accounts)?,
+    })
+    .pack()?;
+    let accounts = swap_v2_accounts(
+        token_program_coin,
+        token_program_pc,
+        amm_pool,
+        amm_authority,
+        amm_open_orders,
+        amm_coin_vault,
+        amm_pc_vault,
+        amm_coin_mint,
+        amm_pc_mint,
+        market_program,
+        market,
+        market_bids,
+        market_asks,
+        market_event_queue,
+        market_coin_vault,
+        market_pc_vault,
+        market_vault_signer,
+        user_token_source,
+        user_token_destination,
+        user_source_owner,
+        coin_hook_accounts,
+        pc_hook_accounts,
+    );
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+fn hook_account_count(hook_accounts: &[AccountMeta]) -> Result<u8, ProgramError> {
+    u8::try_from(hook_accounts.len()).map_err(|_| ProgramError::InvalidArgument)
+}
+
+/// Accounts of a `SwapBaseInV2` or `SwapBaseOutV2`
+fn swap_v2_accounts(
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_coin_mint: &Pubkey,
+    amm_pc_mint: &Pubkey,
+    market_program: &Pubkey,
+    market: &Pubkey,
+    market_bids: &Pubkey,
+    market_asks: &Pubkey,
+    market_event_queue: &Pubkey,
+    market_coin_vault: &Pubkey,
+    market_pc_vault: &Pubkey,
+    market_vault_signer: &Pubkey,
+    user_token_source: &Pubkey,
+    user_token_destination: &Pubkey,
+    user_source_owner: &Pubkey,
+    coin_hook_accounts: &[AccountMeta],
+    pc_hook_accounts: &[AccountMeta],
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        // token programs, spl token or token-2022
+        AccountMeta::new_readonly(*token_program_coin, false),
+        AccountMeta::new_readonly(*token_program_pc, false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(*amm_open_orders, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        AccountMeta::new_readonly(*amm_coin_mint, false),
+        AccountMeta::new_readonly(*amm_pc_mint, false),
+        // market
+        AccountMeta::new_readonly(*market_program, false),
+        AccountMeta::new(*market, false),
+        AccountMeta::new(*market_bids, false),
+        AccountMeta::new(*market_asks, false),
+        AccountMeta::new(*market_event_queue, false),
+        AccountMeta::new(*market_coin_vault, false),
+        AccountMeta::new(*market_pc_vault, false),
+        AccountMeta::new_readonly(*market_vault_signer, false),
+        // user
+        AccountMeta::new(*user_token_source, false),
+        AccountMeta::new(*user_token_destination, false),
+        AccountMeta::new_readonly(*user_source_owner, true),
+    ];
+    // token-2022 transfer hook accounts, coin side first
+    accounts.extend_from_slice(coin_hook_accounts);
+    accounts.extend_from_slice(pc_hook_accounts);
+    accounts
+}
+
+/// Creates an 'initialize_no_orderbook' instruction.
+/// `coin_hook_accounts` and `pc_hook_accounts` are the transfer hook accounts
+/// of a hooked Token-2022 mint, see `swap_base_in_checked`.
+pub fn initialize_no_orderbook(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    amm_coin_mint: &Pubkey,
+    amm_pc_mint: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_config: &Pubkey,
+    create_fee_destination: &Pubkey,
+    pool_seed: &Pubkey,
+    user_wallet: &Pubkey,
+    user_token_coin: &Pubkey,
+    user_token_pc: &Pubkey,
+    user_token_lp: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    coin_hook_accounts: &[AccountMeta],
+    pc_hook_accounts: &[AccountMeta],
+    nonce: u8,
+    open_time: u64,
+    init_pc_amount: u64,
+    init_coin_amount: u64,
+    registry_hooks: bool,
+    amp: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::InitializeNoOrderbook(InitializeInstruction2 {
+        nonce,
+        open_time,
+        init_pc_amount,
+        init_coin_amount,
+        registry_hooks,
+        amp,
+    })
+    .pack()?;
+
+    let mut accounts = vec![
+        // spl & sys
+        AccountMeta::new_readonly(*token_program_coin, false),
+        AccountMeta::new_readonly(*token_program_pc, false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(*amm_lp_mint, false),
+        AccountMeta::new_readonly(*amm_coin_mint, false),
+        AccountMeta::new_readonly(*amm_pc_mint, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        AccountMeta::new_readonly(*amm_config, false),
+        AccountMeta::new(*create_fee_destination, false),
+        AccountMeta::new_readonly(*pool_seed, false),
+        // user wallet
+        AccountMeta::new(*user_wallet, true),
+        AccountMeta::new(*user_token_coin, false),
+        AccountMeta::new(*user_token_pc, false),
+        AccountMeta::new(*user_token_lp, false),
+    ];
+    // mints and transfer hook accounts of the token-2022 sides
+    accounts.extend(transfer_checked_accounts(
+        amm_coin_mint,
+        token_program_coin,
+        coin_hook_accounts,
+    ));
+    accounts.extend(transfer_checked_accounts(
+        amm_pc_mint,
+        token_program_pc,
+        pc_hook_accounts,
+    ));
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'deposit_no_orderbook' instruction.
+/// `coin_hook_accounts` and `pc_hook_accounts` are the transfer hook accounts
+/// of a hooked Token-2022 mint, see `swap_base_in_checked`.
+pub fn deposit_no_orderbook(
+    amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    user_token_coin: &Pubkey,
+    user_token_pc: &Pubkey,
+    user_token_lp: &Pubkey,
+    user_owner: &Pubkey,
+    coin_mint: &Pubkey,
+    coin_hook_accounts: &[AccountMeta],
+    pc_mint: &Pubkey,
+    pc_hook_accounts: &[AccountMeta],
+    max_coin_amount: u64,
+    max_pc_amount: u64,
+    base_side: u64,
+    other_amount_min: Option<u64>,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::DepositNoOrderbook(DepositInstruction {
+        max_coin_amount,
+        max_pc_amount,
+        base_side,
+        other_amount_min,
+        wrap_sides: WrapSides::default(),
+    })
+    .pack()?;
+
+    let mut accounts = vec![
+        // token programs, spl token or token-2022
+        AccountMeta::new_readonly(*token_program_coin, false),
+        AccountMeta::new_readonly(*token_program_pc, false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(*amm_lp_mint, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        // user
+        AccountMeta::new(*user_token_coin, false),
+        AccountMeta::new(*user_token_pc, false),
+        AccountMeta::new(*user_token_lp, false),
+        AccountMeta::new_readonly(*user_owner, true),
+    ];
+    // token-2022 transfer accounts, coin side first
+    accounts.extend(transfer_checked_accounts(
+        coin_mint,
+        token_program_coin,
+        coin_hook_accounts,
+    ));
+    accounts.extend(transfer_checked_accounts(
+        pc_mint,
+        token_program_pc,
+        pc_hook_accounts,
+    ));
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'withdraw_no_orderbook' instruction, see `deposit_no_orderbook` and
+/// `withdraw_checked` for `escrow_sides`.
+pub fn withdraw_no_orderbook(
+    amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    user_token_lp: &Pubkey,
+    user_token_coin: &Pubkey,
+    user_token_pc: &Pubkey,
+    user_owner: &Pubkey,
+    coin_mint: &Pubkey,
+    coin_hook_accounts: &[AccountMeta],
+    pc_mint: &Pubkey,
+    pc_hook_accounts: &[AccountMeta],
+    amount: u64,
+    min_coin_amount: Option<u64>,
+    min_pc_amount: Option<u64>,
+    escrow_sides: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::WithdrawNoOrderbook(WithdrawInstruction {
+        amount,
+        min_coin_amount,
+        min_pc_amount,
+        escrow_sides,
+        wrap_sides: WrapSides::default(),
+    })
+    .pack()?;
+
+    let mut accounts = vec![
+        // token programs, spl token or token-2022
+        AccountMeta::new_readonly(*token_program_coin, false),
+        AccountMeta::new_readonly(*token_program_pc, false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(*amm_lp_mint, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        // user
+        AccountMeta::new(*user_token_lp, false),
+        AccountMeta::new(*user_token_coin, false),
+        AccountMeta::new(*user_token_pc, false),
+        AccountMeta::new_readonly(*user_owner, true),
+    ];
+    if escrow_sides != 0 {
+        let index = accounts.len();
+        insert_escrow_accounts(&mut accounts, index, amm_program, amm_pool, user_owner);
+    }
+    // token-2022 transfer accounts, coin side first
+    accounts.extend(transfer_checked_accounts(
+        coin_mint,
+        token_program_coin,
+        coin_hook_accounts,
+    ));
+    accounts.extend(transfer_checked_accounts(
+        pc_mint,
+        token_program_pc,
+        pc_hook_accounts,
+    ));
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'swap_base_in_no_orderbook' instruction, see `swap_base_in_checked`.
+pub fn swap_base_in_no_orderbook(
+    amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    user_token_source: &Pubkey,
+    user_token_destination: &Pubkey,
+    user_source_owner: &Pubkey,
+    source_mint: &Pubkey,
+    source_token_program: &Pubkey,
+    source_hook_accounts: &[AccountMeta],
+    destination_mint: &Pubkey,
+    destination_token_program: &Pubkey,
+    destination_hook_accounts: &[AccountMeta],
+
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::SwapBaseInNoOrderbook(SwapInstructionBaseIn {
+        amount_in,
+        minimum_amount_out,
+    })
+    .pack()?;
+
+    let mut accounts = vec![
+        // token programs, spl token or token-2022
+        AccountMeta::new_readonly(*token_program_coin, false),
+        AccountMeta::new_readonly(*token_program_pc, false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        // user
+        AccountMeta::new(*user_token_source, false),
+        AccountMeta::new(*user_token_destination, false),
+        AccountMeta::new_readonly(*user_source_owner, true),
+    ];
+    // token-2022 transfer accounts, source side first
+    accounts.extend(transfer_checked_accounts(
+        source_mint,
+        source_token_program,
+        source_hook_accounts,
+    ));
+    accounts.extend(transfer_checked_accounts(
+        destination_mint,
+        destination_token_program,
+        destination_hook_accounts,
+    ));
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'simulate_info' instruction of `SimulateParams::HookDryRunInfo` for
+/// simulateTransaction, the accounts of `swap_base_in_no_orderbook` with a read only AMM Account.
+/// `amm_orderbook` is the open orders, market and market event queue of a pool with an orderbook.
+pub fn simulate_hook_dry_run(
+    amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_orderbook: Option<(&Pubkey, &Pubkey, &Pubkey)>,
+    user_token_source: &Pubkey,
+    user_token_destination: &Pubkey,
+    user_source_owner: &Pubkey,
+    source_mint: &Pubkey,
+    source_token_program: &Pubkey,
+    source_hook_accounts: &[AccountMeta],
+    destination_mint: &Pubkey,
+    destination_token_program: &Pubkey,
+    destination_hook_accounts: &[AccountMeta],
+
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = swap_base_in_no_orderbook(
+        amm_program,
+        token_program_coin,
+        token_program_pc,
+        amm_pool,
+        amm_authority,
+        amm_coin_vault,
+        amm_pc_vault,
+        user_token_source,
+        user_token_destination,
+        user_source_owner,
+        source_mint,
+        source_token_program,
+        source_hook_accounts,
+        destination_mint,
+        destination_token_program,
+        destination_hook_accounts,
+        amount_in,
+        minimum_amount_out,
+    )?;
+    instruction.data = AmmInstruction::SimulateInfo(SimulateInstruction {
+        param: SimulateParams::HookDryRunInfo.into_u64() as u8,
+        swap_base_in_value: Some(SwapInstructionBaseIn {
+            amount_in,
+            minimum_amount_out,
+        }),
+        swap_base_out_value: None,
+    })
+    .pack()?;
+    // the pool is only read
+    instruction.accounts[2].is_writable = false;
+    if let Some((amm_open_orders, market, market_event_queue)) = amm_orderbook {
+        instruction.accounts.splice(
+            9..9,
+            [
+                AccountMeta::new_readonly(*amm_open_orders, false),
+                AccountMeta::new_readonly(*market, false),
+                AccountMeta::new_readonly(*market_event_queue, false),
+            ],
+        );
+    }
+    Ok(instruction)
+}
+
+/// Creates a 'swap_base_out_no_orderbook' instruction, see `swap_base_in_checked`.
+pub fn swap_base_out_no_orderbook(
+    amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    user_token_source: &Pubkey,
+    user_token_destination: &Pubkey,
+    user_source_owner: &Pubkey,
+    source_mint: &Pubkey,
+    source_token_program: &Pubkey,
+    source_hook_accounts: &[AccountMeta],
+    destination_mint: &Pubkey,
+    destination_token_program: &Pubkey,
+    destination_hook_accounts: &[AccountMeta],
+
+    max_amount_in: u64,
+    amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::SwapBaseOutNoOrderbook(SwapInstructionBaseOut {
+        max_amount_in,
+        amount_out,
+    })
+    .pack()?;
+
+    let mut accounts = vec![
+        // token programs, spl token or token-2022
+        AccountMeta::new_readonly(*token_program_coin, false),
+        AccountMeta::new_readonly(*token_program_pc, false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        // user
+        AccountMeta::new(*user_token_source, false),
+        AccountMeta::new(*user_token_destination, false),
+        AccountMeta::new_readonly(*user_source_owner, true),
+    ];
+    // token-2022 transfer accounts, source side first
+    accounts.extend(transfer_checked_accounts(
+        source_mint,
+        source_token_program,
+        source_hook_accounts,
+    ));
+    accounts.extend(transfer_checked_accounts(
+        destination_mint,
+        destination_token_program,
+        destination_hook_accounts,
+    ));
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Pool accounts of one hop of a `SwapRoute`. A pool without orderbook permission
+/// takes its AMM Account as `amm_open_orders` and the market keys.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SwapRouteHop {
+    pub token_program_coin: Pubkey,
+    pub token_program_pc: Pubkey,
+    pub amm_pool: Pubkey,
+    pub amm_authority: Pubkey,
+    pub amm_open_orders: Pubkey,
+    pub amm_coin_vault: Pubkey,
+    pub amm_pc_vault: Pubkey,
+    pub market_program: Pubkey,
+    pub market: Pubkey,
+    pub market_bids: Pubkey,
+    pub market_asks: Pubkey,
+    pub market_event_queue: Pubkey,
+    pub market_coin_vault: Pubkey,
+    pub market_pc_vault: Pubkey,
+    pub market_vault_signer: Pubkey,
+}
+
+/// Creates a 'swap_route' instruction.
+/// `transfers` are the mint, token program and transfer hook accounts of each transfer in
+/// route order, `hops.len() + 1` of them: the user into the first pool, each pool into the
+/// next and the last pool to the user.
+pub fn swap_route(
+    amm_program: &Pubkey,
+    hops: &[SwapRouteHop],
+    user_token_source: &Pubkey,
+    user_token_destination: &Pubkey,
+    user_source_owner: &Pubkey,
+    transfers: &[(Pubkey, Pubkey, &[AccountMeta])],
+
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    if hops.is_empty() || hops.len() > MAX_SWAP_ROUTE_HOPS || transfers.len() != hops.len() + 1 {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = AmmInstruction::SwapRoute(SwapRouteInstruction {
+        amount_in,
+        minimum_amount_out,
+        hop_count: hops.len() as u8,
+    })
+    .pack()?;
+
+    let mut accounts = vec![
+        // user
+        AccountMeta::new(*user_token_source, false),
+        AccountMeta::new(*user_token_destination, false),
+        AccountMeta::new_readonly(*user_source_owner, true),
+    ];
+    for hop in hops {
+        accounts.extend([
+            // token programs, spl token or token-2022
+            AccountMeta::new_readonly(hop.token_program_coin, false),
+            AccountMeta::new_readonly(hop.token_program_pc, false),
+            // amm
+            AccountMeta::new(hop.amm_pool, false),
+            AccountMeta::new_readonly(hop.amm_authority, false),
+            AccountMeta::new(hop.amm_open_orders, false),
+            AccountMeta::new(hop.amm_coin_vault, false),
+            AccountMeta::new(hop.amm_pc_vault, false),
+            // market
+            AccountMeta::new_readonly(hop.market_program, false),
+            AccountMeta::new(hop.market, false),
+            AccountMeta::new(hop.market_bids, false),
+            AccountMeta::new(hop.market_asks, false),
+            AccountMeta::new(hop.market_event_queue, false),
+            AccountMeta::new(hop.market_coin_vault, false),
+            AccountMeta::new(hop.market_pc_vault, false),
+            AccountMeta::new_readonly(hop.market_vault_signer, false),
+        ]);
+    }
+    // token-2022 transfer accounts, in route order
+    for (mint, token_program, hook_accounts) in transfers {
+        accounts.extend(transfer_checked_accounts(
+            mint,
+            token_program,
+            hook_accounts,
+        ));
+    }
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'deposit_single' instruction, `side` 0 deposits coin and 1 pc, for a pool created
+/// by `InitializeNoOrderbook` only.
+/// `mint` and `hook_accounts` are the transfer accounts of the deposited side,
+/// `other_mint` the mint of the other side.
+pub fn deposit_single(
+    amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    user_token_source: &Pubkey,
+    user_token_lp: &Pubkey,
+    user_owner: &Pubkey,
+    mint: &Pubkey,
+    hook_accounts: &[AccountMeta],
+    other_mint: &Pubkey,
+    amount_in: u64,
+    minimum_lp_amount: u64,
+    side: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::DepositSingle(DepositSingleInstruction {
+        amount_in,
+        minimum_lp_amount,
+        side,
+    })
+    .pack()?;
+
+    let mut accounts = vec![
+        // token programs, spl token or token-2022
+        AccountMeta::new_readonly(*token_program_coin, false),
+        AccountMeta::new_readonly(*token_program_pc, false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(*amm_lp_mint, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        // user
+        AccountMeta::new(*user_token_source, false),
+        AccountMeta::new(*user_token_lp, false),
+        AccountMeta::new_readonly(*user_owner, true),
+    ];
+    let (token_program, other_token_program) = if side == 0 {
+        (token_program_coin, token_program_pc)
+    } else {
+        (token_program_pc, token_program_coin)
+    };
+    // the hook program of a token-2022 other side is checked
+    if *other_token_program == spl_token_2022::id() {
+        accounts.push(AccountMeta::new_readonly(*other_mint, false));
+    }
+    // token-2022 transfer accounts of the deposited side
+    accounts.extend(transfer_checked_accounts(
+        mint,
+        token_program,
+        hook_accounts,
+    ));
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'withdraw_single' instruction, `side` 0 withdraws coin and 1 pc, for a pool created
+/// by `InitializeNoOrderbook` only.
+/// `mint` and `hook_accounts` are the transfer accounts of the withdrawn side,
+/// `other_mint` the mint of the other side.
+pub fn withdraw_single(
+    amm_program: &Pubkey,
+    token_program_coin: &Pubkey,
+    token_program_pc: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    user_token_lp: &Pubkey,
+    user_token_destination: &Pubkey,
+    user_owner: &Pubkey,
+    mint: &Pubkey,
+    hook_accounts: &[AccountMeta],
+    other_mint: &Pubkey,
+    amount: u64,
+    minimum_amount_out: u64,
+    side: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::WithdrawSingle(WithdrawSingleInstruction {
+        amount,
+        minimum_amount_out,
+        side,
+    })
+    .pack()?;
+
+    let mut accounts = vec![
+        // token programs, spl token or token-2022
+        AccountMeta::new_readonly(*token_program_coin, false),
+        AccountMeta::new_readonly(*token_program_pc, false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(*amm_lp_mint, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        // user
+        AccountMeta::new(*user_token_lp, false),
+        AccountMeta::new(*user_token_destination, false),
+        AccountMeta::new_readonly(*user_owner, true),
+    ];
+    let (token_program, other_token_program) = if side == 0 {
+        (token_program_coin, token_program_pc)
+    } else {
+        (token_program_pc, token_program_coin)
+    };
+    // the hook program of a token-2022 other side is checked
+    if *other_token_program == spl_token_2022::id() {
+        accounts.push(AccountMeta::new_readonly(*other_mint, false));
+    }
+    // token-2022 transfer accounts of the withdrawn side
+    accounts.extend(transfer_checked_accounts(
+        mint,
+        token_program,
+        hook_accounts,
+    ));
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'pause_on_hook_change' instruction.
+pub fn pause_on_hook_change(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    coin_mint: &Pubkey,
+    pc_mint: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::PauseOnHookChange.pack()?;
+    let accounts = vec![
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*coin_mint, false),
+        AccountMeta::new_readonly(*pc_mint, false),
+    ];
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'extend_amm' instruction.
+pub fn extend_amm(
+    amm_program: &Pubkey,
+    payer: &Pubkey,
+    amm_pool: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::ExtendAmm.pack()?;
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
@@ -37,6 +37,9 @@ pub enum LogType {
     Withdraw,
     SwapBaseIn,
     SwapBaseOut,
+    HookProgramChanged,
+    DepositSingle,
+    WithdrawSingle,
 }
 
 impl LogType {
@@ -47,6 +50,9 @@ impl LogType {
             2 => LogType::Withdraw,
             3 => LogType::SwapBaseIn,
             4 => LogType::SwapBaseOut,
+            5 => LogType::HookProgramChanged,
+            6 => LogType::DepositSingle,
+            7 => LogType::WithdrawSingle,
             _ => unreachable!(),
         }
     }
@@ -58,6 +64,9 @@ impl LogType {
             LogType::Withdraw => 2u8,
             LogType::SwapBaseIn => 3u8,
             LogType::SwapBaseOut => 4u8,
+            LogType::HookProgramChanged => 5u8,
+            LogType::DepositSingle => 6u8,
+            LogType::WithdrawSingle => 7u8,
         }
     }
 }
@@ -126,6 +135,15 @@ pub struct SwapBaseInLog {
     pub pool_pc: u64,
     // calc result
     pub out_amount: u64,
+    // token-2022 transfer fees
+    pub transfer_fee_in: u64,
+    pub transfer_fee_out: u64,
+    // shares of the swap fee
+    pub lp_fee: u64,
+    pub protocol_fee: u64,
+    pub referrer_fee: u64,
+    // compute units consumed by the hooked transfers
+    pub hook_compute_units: u64,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
@@ -142,6 +160,74 @@ pub struct SwapBaseOutLog {
     pub pool_pc: u64,
     // calc result
     pub deduct_in: u64,
+    // token-2022 transfer fees
+    pub transfer_fee_in: u64,
+    pub transfer_fee_out: u64,
+    // shares of the swap fee
+    pub lp_fee: u64,
+    pub protocol_fee: u64,
+    pub referrer_fee: u64,
+    // compute units consumed by the hooked transfers
+    pub hook_compute_units: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HookProgramChangedLog {
+    pub log_type: u8,
+    pub mint: Pubkey,
+    // hook program pinned at pool creation, default if the mint had no hook
+    pub pinned_hook_program: Pubkey,
+    // live hook program of the mint, default if the hook was removed
+    pub hook_program: Pubkey,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DepositSingleLog {
+    pub log_type: u8,
+    // input
+    pub amount_in: u64,
+    pub minimum_lp: u64,
+    pub side: u8,
+    // pool info
+    pub pool_coin: u64,
+    pub pool_pc: u64,
+    pub pool_lp: u64,
+    // calc result
+    pub swap_in: u64,
+    pub swap_out: u64,
+    pub deduct_coin: u64,
+    pub deduct_pc: u64,
+    pub mint_lp: u64,
+    // token-2022 transfer fee
+    pub transfer_fee_in: u64,
+    // shares of the swap fee
+    pub lp_fee: u64,
+    pub protocol_fee: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WithdrawSingleLog {
+    pub log_type: u8,
+    // input
+    pub withdraw_lp: u64,
+    pub minimum_out: u64,
+    pub side: u8,
+    // user info
+    pub user_lp: u64,
+    // pool info
+    pub pool_coin: u64,
+    pub pool_pc: u64,
+    pub pool_lp: u64,
+    // calc result
+    pub out_coin: u64,
+    pub out_pc: u64,
+    pub swap_out: u64,
+    pub out_amount: u64,
+    // token-2022 transfer fee
+    pub transfer_fee_out: u64,
+    // shares of the swap fee
+    pub lp_fee: u64,
+    pub protocol_fee: u64,
 }
 
 pub fn encode_ray_log<T: Serialize>(log: T) {
@@ -178,5 +264,17 @@ pub fn decode_ray_log(log: &str) {
             let log: SwapBaseOutLog = bincode::deserialize(&bytes).unwrap();
             println!("{:?}", log);
         }
+        LogType::HookProgramChanged => {
+            let log: HookProgramChangedLog = bincode::deserialize(&bytes).unwrap();
+            println!("{:?}", log);
+        }
+        LogType::DepositSingle => {
+            let log: DepositSingleLog = bincode::deserialize(&bytes).unwrap();
+            println!("{:?}", log);
+        }
+        LogType::WithdrawSingle => {
+            let log: WithdrawSingleLog = bincode::deserialize(&bytes).unwrap();
+            println!("{:?}", log);
+        }
     }
 }
@@ -454,6 +454,471 @@ impl Calculator {
     }
 }
 
+/// Bonding curve of a pool, see `AmmInfo::curve`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Curve {
+    /// x * y = k, `Calculator::swap_token_amount_base_in` and `swap_token_amount_base_out`
+    ConstantProduct,
+    /// amplified invariant for pegged pairs
+    StableSwap(StableSwap),
+}
+
+impl Curve {
+    pub fn swap_token_amount_base_in(
+        &self,
+        amount_in: U128,
+        total_pc_without_take_pnl: U128,
+        total_coin_without_take_pnl: U128,
+        swap_direction: SwapDirection,
+    ) -> Result<U128, AmmError> {
+        match self {
+            Curve::ConstantProduct => Ok(Calculator::swap_token_amount_base_in(
+                amount_in,
+                total_pc_without_take_pnl,
+                total_coin_without_take_pnl,
+                swap_direction,
+            )),
+            Curve::StableSwap(stable) => stable
+                .swap_token_amount_base_in(
+                    amount_in,
+                    total_pc_without_take_pnl,
+                    total_coin_without_take_pnl,
+                    swap_direction,
+                )
+                .ok_or(AmmError::CalculationExRateFailure),
+        }
+    }
+
+    pub fn swap_token_amount_base_out(
+        &self,
+        amount_out: U128,
+        total_pc_without_take_pnl: U128,
+        total_coin_without_take_pnl: U128,
+        swap_direction: SwapDirection,
+    ) -> Result<U128, AmmError> {
+        match self {
+            Curve::ConstantProduct => Ok(Calculator::swap_token_amount_base_out(
+                amount_out,
+                total_pc_without_take_pnl,
+                total_coin_without_take_pnl,
+                swap_direction,
+            )),
+            Curve::StableSwap(stable) => stable
+                .swap_token_amount_base_out(
+                    amount_out,
+                    total_pc_without_take_pnl,
+                    total_coin_without_take_pnl,
+                    swap_direction,
+                )
+                .ok_or(AmmError::CalculationExRateFailure),
+        }
+    }
+
+    /// Marginal prices of the pool totals as Q64.64, the pc amount of one coin unit and the
+    /// coin amount of one pc unit. Saturates at `u128::MAX`.
+    pub fn spot_prices_x64(
+        &self,
+        total_coin: u64,
+        total_pc: u64,
+    ) -> Result<(u128, u128), AmmError> {
+        if total_coin == 0 || total_pc == 0 {
+            return Err(AmmError::CalculationExRateFailure);
+        }
+        let saturate = |price: U256| {
+            if price > U256::from(u128::MAX) {
+                u128::MAX
+            } else {
+                price.as_u128()
+            }
+        };
+        let (coin_price, pc_price) = match self {
+            Curve::ConstantProduct => (
+                (U256::from(total_pc) << 64) / total_coin,
+                (U256::from(total_coin) << 64) / total_pc,
+            ),
+            Curve::StableSwap(stable) => stable
+                .spot_prices_x64(total_coin.into(), total_pc.into())
+                .ok_or(AmmError::CalculationExRateFailure)?,
+        };
+        Ok((saturate(coin_price), saturate(pc_price)))
+    }
+
+    /// lp amount of the first deposit, before the locked part is deducted
+    pub fn initial_liquidity(&self, coin_amount: u64, pc_amount: u64) -> Result<u64, AmmError> {
+        let liquidity = match self {
+            Curve::ConstantProduct => U128::from(pc_amount)
+                .checked_mul(coin_amount.into())
+                .ok_or(AmmError::CheckedMulOverflow)?
+                .integer_sqrt()
+                .as_u128(),
+            Curve::StableSwap(stable) => {
+                let (coin, pc) = stable
+                    .normalize(coin_amount.into(), pc_amount.into())
+                    .ok_or(AmmError::CheckedMulOverflow)?;
+                let d = stable
+                    .compute_d(coin, pc)
+                    .ok_or(AmmError::CalculationExRateFailure)?;
+                if d > U256::from(u128::MAX) {
+                    return Err(AmmError::ConversionFailure);
+                }
+                d.as_u128()
+            }
+        };
+        Calculator::to_u64(liquidity)
+    }
+
+    /// lp amount minted for adding `deposit_coin` and `deposit_pc` to the pool totals,
+    /// `share_lp_amount` is the proportional share of the deposit in `lp_amount`.
+    /// A StableSwap pool mints by the growth of its invariant D, capped at that share.
+    pub fn deposit_lp_amount(
+        &self,
+        deposit_coin: u64,
+        deposit_pc: u64,
+        total_coin: u64,
+        total_pc: u64,
+        lp_amount: u64,
+        share_lp_amount: u64,
+    ) -> Result<u64, AmmError> {
+        let stable = match self {
+            Curve::ConstantProduct => return Ok(share_lp_amount),
+            Curve::StableSwap(stable) => stable,
+        };
+        let (coin, pc) = stable
+            .normalize(total_coin.into(), total_pc.into())
+            .ok_or(AmmError::CheckedMulOverflow)?;
+        let (new_coin, new_pc) = stable
+            .normalize(
+                U128::from(total_coin) + U128::from(deposit_coin),
+                U128::from(total_pc) + U128::from(deposit_pc),
+            )
+            .ok_or(AmmError::CheckedMulOverflow)?;
+        let d0 = stable
+            .compute_d(coin, pc)
+            .ok_or(AmmError::CalculationExRateFailure)?;
+        let d1 = stable
+            .compute_d(new_coin, new_pc)
+            .ok_or(AmmError::CalculationExRateFailure)?;
+        if d0.is_zero() || d1 <= d0 {
+            return Ok(0);
+        }
+        // lp_amount * (d1 - d0) / d0
+        let lp = U256::from(lp_amount)
+            .checked_mul(d1 - d0)
+            .ok_or(AmmError::CheckedMulOverflow)?
+            / d0;
+        Ok(std::cmp::min(lp, U256::from(share_lp_amount)).as_u64())
+    }
+
+    /// Part of `amount_in` of one side that a single sided deposit swaps for the other side, so
+    /// that the rest and the swap output are in the ratio of the pool totals after the swap.
+    /// The swap pays `swap_fee_numerator / swap_fee_denominator` of its input as a swap does,
+    /// the whole input stays in the pool.
+    pub fn single_sided_swap_amount(
+        &self,
+        amount_in: u64,
+        total_pc_without_take_pnl: u64,
+        total_coin_without_take_pnl: u64,
+        swap_direction: SwapDirection,
+        swap_fee_numerator: u64,
+        swap_fee_denominator: u64,
+    ) -> Result<u64, AmmError> {
+        if swap_fee_numerator >= swap_fee_denominator {
+            return Err(AmmError::InvalidFee);
+        }
+        if let Curve::StableSwap(stable) = self {
+            return stable
+                .single_sided_swap_amount(
+                    amount_in,
+                    total_pc_without_take_pnl,
+                    total_coin_without_take_pnl,
+                    swap_direction,
+                    swap_fee_numerator,
+                    swap_fee_denominator,
+                )
+                .ok_or(AmmError::CalculationExRateFailure);
+        }
+        let total_in = match swap_direction {
+            SwapDirection::Coin2PC => total_coin_without_take_pnl,
+            SwapDirection::PC2Coin => total_pc_without_take_pnl,
+        };
+        // with r = 1 - fee, a swap of s out of x gives out / (y - out) = s * r / x, so
+        // (amount_in - s) / (x + s) = s * r / x
+        // => r * s^2 + (1 + r) * x * s - amount_in * x = 0
+        // => s = (sqrt(((1 + r) * x)^2 + 4 * r * amount_in * x) - (1 + r) * x) / (2 * r)
+        // multiplied through by the fee denominator
+        let r = U256::from(swap_fee_denominator - swap_fee_numerator);
+        let b = U256::from(total_in)
+            .checked_mul(r + U256::from(swap_fee_denominator))
+            .ok_or(AmmError::CheckedMulOverflow)?;
+        let discriminant = b
+            .checked_mul(b)
+            .and_then(|b_squared| {
+                r.checked_mul(4.into())?
+                    .checked_mul(amount_in.into())?
+                    .checked_mul(total_in.into())?
+                    .checked_mul(swap_fee_denominator.into())?
+                    .checked_add(b_squared)
+            })
+            .ok_or(AmmError::CheckedMulOverflow)?;
+        let swap_amount = (discriminant.integer_sqrt() - b) / (r * 2);
+        Ok(std::cmp::min(swap_amount, U256::from(amount_in)).as_u64())
+    }
+}
+
+/// StableSwap invariant of two coins, for amounts normalized to the same decimals:
+/// A * n^n * (x + y) + D = A * D * n^n + D^(n + 1) / (n^n * x * y), n = 2
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StableSwap {
+    /// amplification coefficient A
+    pub amp: u64,
+    /// scales coin amounts to the larger of the coin and pc decimals
+    pub coin_multiplier: u64,
+    /// scales pc amounts to the larger of the coin and pc decimals
+    pub pc_multiplier: u64,
+}
+
+impl StableSwap {
+    /// Newton's method stops after this many iterations without converging
+    const MAX_ITERATIONS: usize = 256;
+
+    /// coin and pc amounts at the same decimals
+    fn normalize(&self, coin: U128, pc: U128) -> Option<(U256, U256)> {
+        Some((
+            U256::from(coin.as_u128()).checked_mul(self.coin_multiplier.into())?,
+            U256::from(pc.as_u128()).checked_mul(self.pc_multiplier.into())?,
+        ))
+    }
+
+    /// A * n^n
+    fn amp_n_n(&self) -> U256 {
+        U256::from(self.amp) * 4
+    }
+
+    /// invariant D of the normalized amounts `x` and `y`
+    pub fn compute_d(&self, x: U256, y: U256) -> Option<U256> {
+        let sum = x.checked_add(y)?;
+        if sum.is_zero() {
+            return Some(U256::zero());
+        }
+        if x.is_zero() || y.is_zero() || self.amp == 0 {
+            return None;
+        }
+        let amp_n_n = self.amp_n_n();
+        let mut d = sum;
+        for _ in 0..Self::MAX_ITERATIONS {
+            // d_p = D^(n + 1) / (n^n * x * y)
+            let d_p = d
+                .checked_mul(d)?
+                .checked_div(x * 2)?
+                .checked_mul(d)?
+                .checked_div(y * 2)?;
+            let d_prev = d;
+            // D = (A * n^n * S + n * d_p) * D / ((A * n^n - 1) * D + (n + 1) * d_p)
+            let numerator = amp_n_n
+                .checked_mul(sum)?
+                .checked_add(d_p.checked_mul(2.into())?)?
+                .checked_mul(d)?;
+            let denominator = (amp_n_n - 1)
+                .checked_mul(d)?
+                .checked_add(d_p.checked_mul(3.into())?)?;
+            d = numerator.checked_div(denominator)?;
+            if d.max(d_prev) - d.min(d_prev) <= U256::one() {
+                return Some(d);
+            }
+        }
+        None
+    }
+
+    /// normalized amount of one side that keeps the invariant `d` with `x` on the other
+    pub fn compute_y(&self, x: U256, d: U256) -> Option<U256> {
+        if x.is_zero() || self.amp == 0 {
+            return None;
+        }
+        let amp_n_n = self.amp_n_n();
+        // y^2 + (x + D / (A * n^n) - D) * y = D^(n + 1) / (n^n * x * A * n^n)
+        let c = d
+            .checked_mul(d)?
+            .checked_div(x * 2)?
+            .checked_mul(d)?
+            .checked_div(amp_n_n * 2)?;
+        let b = x.checked_add(d / amp_n_n)?;
+        let mut y = d;
+        for _ in 0..Self::MAX_ITERATIONS {
+            let y_prev = y;
+            // y = (y^2 + c) / (2 * y + b - D)
+            y = y
+                .checked_mul(y)?
+                .checked_add(c)?
+                .checked_div((y * 2u64).checked_add(b)?.checked_sub(d)?)?;
+            if y.max(y_prev) - y.min(y_prev) <= U256::one() {
+                return Some(y);
+            }
+        }
+        None
+    }
+
+    /// Marginal prices -dy/dx and -dx/dy on the invariant at the pool totals, as Q64.64 of the
+    /// unnormalized amounts. With d_p = D^(n + 1) / (n^n * x * y) the price of x in y is
+    /// (A * n^n + d_p / x) / (A * n^n + d_p / y).
+    fn spot_prices_x64(&self, total_coin: U128, total_pc: U128) -> Option<(U256, U256)> {
+        let (x, y) = self.normalize(total_coin, total_pc)?;
+        let d = self.compute_d(x, y)?;
+        let d_p = d
+            .checked_mul(d)?
+            .checked_div(x * 2)?
+            .checked_mul(d)?
+            .checked_div(y * 2)?;
+        let amp_n_n = self.amp_n_n() << 64;
+        let coin_slope = amp_n_n.checked_add((d_p << 64) / x)?;
+        let pc_slope = amp_n_n.checked_add((d_p << 64) / y)?;
+        // one unnormalized coin unit is coin_multiplier normalized units
+        let coin_price = (coin_slope << 64)
+            .checked_mul(self.coin_multiplier.into())?
+            .checked_div(pc_slope.checked_mul(self.pc_multiplier.into())?)?;
+        let pc_price = (pc_slope << 64)
+            .checked_mul(self.pc_multiplier.into())?
+            .checked_div(coin_slope.checked_mul(self.coin_multiplier.into())?)?;
+        Some((coin_price, pc_price))
+    }
+
+    /// input side, output side and their multipliers of a swap
+    fn sides(
+        &self,
+        total_pc: U128,
+        total_coin: U128,
+        swap_direction: SwapDirection,
+    ) -> Option<(U256, U256, u64, u64)> {
+        let (coin, pc) = self.normalize(total_coin, total_pc)?;
+        Some(match swap_direction {
+            SwapDirection::Coin2PC => (coin, pc, self.coin_multiplier, self.pc_multiplier),
+            SwapDirection::PC2Coin => (pc, coin, self.pc_multiplier, self.coin_multiplier),
+        })
+    }
+
+    pub fn swap_token_amount_base_in(
+        &self,
+        amount_in: U128,
+        total_pc_without_take_pnl: U128,
+        total_coin_without_take_pnl: U128,
+        swap_direction: SwapDirection,
+    ) -> Option<U128> {
+        let (x, y, in_multiplier, out_multiplier) = self.sides(
+            total_pc_without_take_pnl,
+            total_coin_without_take_pnl,
+            swap_direction,
+        )?;
+        let d = self.compute_d(x, y)?;
+        let amount_out = self.amount_out(
+            x,
+            y,
+            d,
+            in_multiplier,
+            out_multiplier,
+            U256::from(amount_in.as_u128()),
+        )?;
+        Some(U128::from(amount_out.as_u128()))
+    }
+
+    /// amount out of a swap of `amount_in` against the normalized sides `x` and `y` of invariant `d`
+    fn amount_out(
+        &self,
+        x: U256,
+        y: U256,
+        d: U256,
+        in_multiplier: u64,
+        out_multiplier: u64,
+        amount_in: U256,
+    ) -> Option<U256> {
+        let new_x = amount_in
+            .checked_mul(in_multiplier.into())?
+            .checked_add(x)?;
+        let new_y = self.compute_y(new_x, d)?;
+        // one unit is kept back against the rounding of Newton's method
+        Some(y.checked_sub(new_y)?.saturating_sub(U256::one()) / out_multiplier)
+    }
+
+    /// See `Curve::single_sided_swap_amount`. Without a closed form, the largest swap whose
+    /// output is at most in the ratio of the rest is found by bisection, at a fixed invariant.
+    pub fn single_sided_swap_amount(
+        &self,
+        amount_in: u64,
+        total_pc_without_take_pnl: u64,
+        total_coin_without_take_pnl: u64,
+        swap_direction: SwapDirection,
+        swap_fee_numerator: u64,
+        swap_fee_denominator: u64,
+    ) -> Option<u64> {
+        let (x, y, in_multiplier, out_multiplier) = self.sides(
+            total_pc_without_take_pnl.into(),
+            total_coin_without_take_pnl.into(),
+            swap_direction,
+        )?;
+        let (total_in, total_out) = match swap_direction {
+            SwapDirection::Coin2PC => (total_coin_without_take_pnl, total_pc_without_take_pnl),
+            SwapDirection::PC2Coin => (total_pc_without_take_pnl, total_coin_without_take_pnl),
+        };
+        let d = self.compute_d(x, y)?;
+        // (amount_in - s) * (total_out - out) >= out * (total_in + s)
+        let keeps_ratio = |swap_amount: u64| -> Option<bool> {
+            let swap_fee = U256::from(swap_amount)
+                .checked_mul(swap_fee_numerator.into())?
+                .checked_add(U256::from(swap_fee_denominator - 1))?
+                / swap_fee_denominator;
+            let amount_out = self.amount_out(
+                x,
+                y,
+                d,
+                in_multiplier,
+                out_multiplier,
+                U256::from(swap_amount).checked_sub(swap_fee)?,
+            )?;
+            let rest = U256::from(amount_in - swap_amount)
+                .checked_mul(U256::from(total_out).checked_sub(amount_out)?)?;
+            Some(rest >= amount_out.checked_mul(U256::from(total_in) + swap_amount)?)
+        };
+        let (mut low, mut high) = (0u64, amount_in);
+        while low < high {
+            let middle = low + (high - low + 1) / 2;
+            if keeps_ratio(middle)? {
+                low = middle;
+            } else {
+                high = middle - 1;
+            }
+        }
+        Some(low)
+    }
+
+    pub fn swap_token_amount_base_out(
+        &self,
+        amount_out: U128,
+        total_pc_without_take_pnl: U128,
+        total_coin_without_take_pnl: U128,
+        swap_direction: SwapDirection,
+    ) -> Option<U128> {
+        let (x, y, in_multiplier, out_multiplier) = self.sides(
+            total_pc_without_take_pnl,
+            total_coin_without_take_pnl,
+            swap_direction,
+        )?;
+        let d = self.compute_d(x, y)?;
+        let new_y =
+            y.checked_sub(U256::from(amount_out.as_u128()).checked_mul(out_multiplier.into())?)?;
+        if new_y.is_zero() {
+            return None;
+        }
+        let new_x = self.compute_y(new_y, d)?;
+        // one unit is added against the rounding of Newton's method
+        let amount_in = new_x
+            .checked_sub(x)?
+            .checked_add(U256::from(in_multiplier))?
+            / in_multiplier;
+        if amount_in > U256::from(u128::MAX) {
+            return None;
+        }
+        Some(U128::from(amount_in.as_u128()))
+    }
+}
+
 /// The invariant calculator.
 pub struct InvariantToken {
     /// Token coin
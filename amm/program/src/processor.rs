@@ -3,19 +3,26 @@
 use crate::{
     error::AmmError,
     instruction::{
-        AdminCancelOrdersInstruction, AmmInstruction, ConfigArgs, DepositInstruction,
-        InitializeInstruction2, MonitorStepInstruction, SetParamsInstruction, SimulateInstruction,
-        SwapInstructionBaseIn, SwapInstructionBaseOut, WithdrawInstruction, WithdrawSrmInstruction,
+        AdminCancelOrdersInstruction, AmmInstruction, ConfigArgs, CreatePriceOracleInstruction,
+        DepositInstruction, DepositSingleInstruction, HookConfigArgs, InitializeInstruction2,
+        MonitorStepInstruction, SetParamsInstruction, SimulateInstruction, SwapInstructionBaseIn,
+        SwapInstructionBaseInV2, SwapInstructionBaseOut, SwapInstructionBaseOutV2,
+        SwapRouteInstruction, WithdrawInstruction, WithdrawSingleInstruction,
+        WithdrawSrmInstruction, WrapSides, ESCROW_ACCOUNT_LEN, MAX_SWAP_ROUTE_HOPS,
+        SWAP_ROUTE_HOP_ACCOUNT_LEN, WITHDRAW_ESCROW_COIN, WITHDRAW_ESCROW_PC, WRAP_ACCOUNT_LEN,
     },
     invokers::Invokers,
     math::{
-        Calculator, CheckedCeilDiv, InvariantPool, InvariantToken, RoundDirection, SwapDirection,
-        U128, U256,
+        Calculator, CheckedCeilDiv, Curve, InvariantPool, InvariantToken, RoundDirection,
+        SwapDirection, U128, U256,
     },
     state::{
-        AmmConfig, AmmInfo, AmmParams, AmmResetFlag, AmmState, AmmStatus, GetPoolData,
-        GetSwapBaseInData, GetSwapBaseOutData, Loadable, RunCrankData, SimulateParams,
-        TargetOrders, MAX_ORDER_LIMIT, TEN_THOUSAND,
+        AmmConfig, AmmExtension, AmmInfo, AmmParams, AmmResetFlag, AmmState, AmmStatus, AmpRamp,
+        EscrowClaim, FeeSplit, GetPoolData, GetSwapBaseInData, GetSwapBaseOutData, HookConfig,
+        HookDryRunData, HookDryRunSide, HookRegistration, Loadable, PriceOracle, RunCrankData,
+        SimulateParams, TargetOrders, TradeContext, AMM_EXTENDED_LEN, MAX_AMP, MAX_ORDER_LIMIT,
+        MAX_PRICE_OBSERVATIONS, MIN_AMP, POOL_FLAG_NO_ORDERBOOK, POOL_FLAG_PRICE_ORACLE,
+        POOL_FLAG_REGISTRY_HOOKS, TEN_THOUSAND,
     },
 };
 
@@ -27,12 +34,13 @@ use serum_dex::{
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     clock::Clock,
+    compute_units::sol_remaining_compute_units,
     entrypoint::ProgramResult,
+    instruction::AccountMeta,
     msg,
     program::{invoke, invoke_signed},
     // log::sol_log_compute_units,
     program_error::ProgramError,
-    program_option::COption,
     program_pack::Pack,
     pubkey::Pubkey,
     rent::Rent,
@@ -123,6 +131,20 @@ pub const PC_VAULT_ASSOCIATED_SEED: &'static [u8] = b"pc_vault_associated_seed";
 pub const LP_MINT_ASSOCIATED_SEED: &'static [u8] = b"lp_mint_associated_seed";
 /// Amm config seed
 pub const AMM_CONFIG_SEED: &'static [u8] = b"amm_config_account_seed";
+/// Transfer hook config seed
+pub const HOOK_CONFIG_SEED: &'static [u8] = b"hook_config_account_seed";
+/// Transfer hook registration seed
+pub const HOOK_REGISTRATION_SEED: &'static [u8] = b"hook_registration_account_seed";
+/// Suffix for coin escrow associated seed
+pub const COIN_ESCROW_ASSOCIATED_SEED: &'static [u8] = b"coin_escrow_associated_seed";
+/// Suffix for pc escrow associated seed
+pub const PC_ESCROW_ASSOCIATED_SEED: &'static [u8] = b"pc_escrow_associated_seed";
+/// Escrow claim seed
+pub const ESCROW_CLAIM_SEED: &'static [u8] = b"escrow_claim_account_seed";
+/// Trade context seed
+pub const TRADE_CONTEXT_SEED: &'static [u8] = b"trade_context_account_seed";
+/// Price oracle seed
+pub const PRICE_ORACLE_SEED: &'static [u8] = b"price_oracle_account_seed";
 
 pub fn get_associated_address_and_bump_seed(
     info_id: &Pubkey,
@@ -140,6 +162,118 @@ pub fn get_associated_address_and_bump_seed(
     )
 }
 
+/// Address of the escrow token account of one side of the pool, owned by $authority,
+/// `associated_seed` is `COIN_ESCROW_ASSOCIATED_SEED` or `PC_ESCROW_ASSOCIATED_SEED`
+pub fn escrow_token_address(program_id: &Pubkey, amm: &Pubkey, associated_seed: &[u8]) -> Pubkey {
+    get_associated_address_and_bump_seed(program_id, amm, associated_seed, program_id).0
+}
+
+/// Address of the `EscrowClaim` account of `owner` in the pool
+pub fn escrow_claim_address(program_id: &Pubkey, amm: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ESCROW_CLAIM_SEED, amm.as_ref(), owner.as_ref()],
+        program_id,
+    )
+}
+
+/// Address of the `TradeContext` account of a mint
+pub fn trade_context_address(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TRADE_CONTEXT_SEED, mint.as_ref()], program_id)
+}
+
+/// Address of the `PriceOracle` account of a pool
+pub fn price_oracle_address(program_id: &Pubkey, amm: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PRICE_ORACLE_SEED, amm.as_ref()], program_id)
+}
+
+/// Escrow accounts of a withdrawal with `escrow_sides` set, see `Withdraw`
+struct WithdrawEscrow<'a, 'b> {
+    system_program_info: &'a AccountInfo<'b>,
+    claim_info: &'a AccountInfo<'b>,
+    /// escrow token account of the coin side, if it is escrowed
+    coin_escrow_info: Option<&'a AccountInfo<'b>>,
+    /// escrow token account of the pc side, if it is escrowed
+    pc_escrow_info: Option<&'a AccountInfo<'b>>,
+}
+
+/// Token wrapper accounts of a side whose vault holds the wrapper mint of a Token-2022 mint,
+/// so that the user pays or receives the underlying mint, see `Processor::split_wrap_accounts`
+pub(crate) struct WrapAccounts<'a, 'b> {
+    wrapper_program_info: &'a AccountInfo<'b>,
+    wrapper_info: &'a AccountInfo<'b>,
+    underlying_mint_info: &'a AccountInfo<'b>,
+    /// the vault mint of the side
+    wrapper_mint_info: &'a AccountInfo<'b>,
+    escrow_info: &'a AccountInfo<'b>,
+    token_2022_program_info: &'a AccountInfo<'b>,
+    /// transfer hook accounts of the underlying mint, forwarded to the wrapper
+    hook_accounts: &'a [AccountInfo<'b>],
+}
+
+/// A pool of a `SwapRoute` with its swap, computed before any transfer of the route
+struct RouteHop<'a, 'b> {
+    /// the `SWAP_ROUTE_HOP_ACCOUNT_LEN` accounts of the pool
+    accounts: &'a [AccountInfo<'b>],
+    amm: RefMut<'a, AmmInfo>,
+    /// extension of the AMM Account, if the pool has one
+    amm_extension: Option<RefMut<'a, AmmExtension>>,
+    amm_authority_info: &'a AccountInfo<'b>,
+    swap_direction: SwapDirection,
+    enable_orderbook: bool,
+    /// orders of the pool on the orderbook the swap takes liquidity from
+    orders: Vec<LeafNode>,
+    total_coin_without_take_pnl: u64,
+    total_pc_without_take_pnl: u64,
+    input_token_program_info: &'a AccountInfo<'b>,
+    input_vault_info: &'a AccountInfo<'b>,
+    input_mint: Pubkey,
+    input_decimals: u8,
+    output_token_program_info: &'a AccountInfo<'b>,
+    output_vault_info: &'a AccountInfo<'b>,
+    output_mint: Pubkey,
+    output_decimals: u8,
+    /// balance of the output vault before the swap
+    output_vault_amount: u64,
+    /// amount transferred into the pool and its transfer fee
+    amount_in: u64,
+    transfer_fee_in: u64,
+    /// amount the pool swaps, net of the transfer fee
+    swap_amount_in: u64,
+    swap_fee: u64,
+    swap_amount_out: u64,
+}
+
+/// Serum market accounts of a swap on a pool with orderbook permission
+struct SwapOrderbook<'a, 'b> {
+    amm_open_orders_info: &'a AccountInfo<'b>,
+    market_program_info: &'a AccountInfo<'b>,
+    market_info: &'a AccountInfo<'b>,
+    market_bids_info: &'a AccountInfo<'b>,
+    market_asks_info: &'a AccountInfo<'b>,
+    market_event_queue_info: &'a AccountInfo<'b>,
+    market_coin_vault_info: &'a AccountInfo<'b>,
+    market_pc_vault_info: &'a AccountInfo<'b>,
+    market_vault_signer: &'a AccountInfo<'b>,
+}
+
+/// Accounts of a swap, as parsed from the layout of its instruction
+struct SwapAccounts<'a, 'b> {
+    coin_token_program_info: &'a AccountInfo<'b>,
+    pc_token_program_info: &'a AccountInfo<'b>,
+    amm_info: &'a AccountInfo<'b>,
+    amm_authority_info: &'a AccountInfo<'b>,
+    amm_coin_vault_info: &'a AccountInfo<'b>,
+    amm_pc_vault_info: &'a AccountInfo<'b>,
+    user_source_info: &'a AccountInfo<'b>,
+    user_destination_info: &'a AccountInfo<'b>,
+    user_source_owner: &'a AccountInfo<'b>,
+    /// market accounts, if the pool swaps against its orders on the orderbook
+    orderbook: Option<SwapOrderbook<'a, 'b>>,
+    /// transfer accounts of the Token-2022 sides, then the referrer accounts of a fee split
+    remaining_accounts: &'a [AccountInfo<'b>],
+    price_oracle_info: Option<&'a AccountInfo<'b>>,
+}
+
 /// Program state handler.
 pub struct Processor {}
 impl Processor {
@@ -179,6 +313,497 @@ impl Processor {
         }
     }
 
+    /// A pool with a price oracle takes it as the very last account, see `CreatePriceOracle`.
+    /// Splits it off `accounts`, whose AMM Account is at `amm_index`, and checks it.
+    /// Returns the remaining accounts and the oracle.
+    pub fn split_price_oracle<'a, 'b>(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+        amm_index: usize,
+    ) -> Result<(&'a [AccountInfo<'b>], Option<&'a AccountInfo<'b>>), ProgramError> {
+        let amm_info = accounts
+            .get(amm_index)
+            .ok_or(AmmError::WrongAccountsNumber)?;
+        if !AmmInfo::load_checked(amm_info, program_id)?.has_price_oracle() {
+            return Ok((accounts, None));
+        }
+        match accounts.split_last() {
+            Some((last, rest)) if rest.len() > amm_index => {
+                Self::check_price_oracle(program_id, amm_info.key, last)?;
+                Ok((rest, Some(last)))
+            }
+            _ => Err(AmmError::InvalidPriceOracle.into()),
+        }
+    }
+
+    /// A side whose vault holds the wrapper mint of a Token-2022 mint, as the market lists it,
+    /// can take the accounts of its token wrapper so that the user pays or receives the
+    /// underlying mint, see `Deposit`. The `wrap_sides` of the instruction data give the groups,
+    /// which come last before the optional price oracle, coin side first.
+    /// Returns the remaining accounts and the wrapper accounts of the coin and pc sides.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn split_wrap_accounts<'a, 'b>(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+        wrap_sides: &WrapSides,
+    ) -> Result<
+        (
+            &'a [AccountInfo<'b>],
+            Option<WrapAccounts<'a, 'b>>,
+            Option<WrapAccounts<'a, 'b>>,
+        ),
+        ProgramError,
+    > {
+        if *wrap_sides == WrapSides::default() {
+            return Ok((accounts, None, None));
+        }
+        // the amm account always follows the coin side token program
+        let amm_info = accounts.get(1).ok_or(AmmError::WrongAccountsNumber)?;
+        let amm = AmmInfo::load_checked(amm_info, program_id)?;
+        let (accounts, pc_wrap) = Self::split_wrap_group(
+            accounts,
+            wrap_sides.pc_hook_account_count,
+            &amm.pc_vault_mint,
+        )?;
+        let (accounts, coin_wrap) = Self::split_wrap_group(
+            accounts,
+            wrap_sides.coin_hook_account_count,
+            &amm.coin_vault_mint,
+        )?;
+        Ok((accounts, coin_wrap, pc_wrap))
+    }
+
+    /// Splits the token wrapper accounts of one side, whose vault holds `vault_mint`, off the
+    /// end of `accounts`: `WRAP_ACCOUNT_LEN` accounts, then `hook_account_count` hook accounts
+    /// of the underlying mint. A side that is not wrapped takes none.
+    fn split_wrap_group<'a, 'b>(
+        accounts: &'a [AccountInfo<'b>],
+        hook_account_count: Option<u8>,
+        vault_mint: &Pubkey,
+    ) -> Result<(&'a [AccountInfo<'b>], Option<WrapAccounts<'a, 'b>>), ProgramError> {
+        let hook_account_count = match hook_account_count {
+            Some(hook_account_count) => hook_account_count as usize,
+            None => return Ok((accounts, None)),
+        };
+        let group_len = WRAP_ACCOUNT_LEN + hook_account_count;
+        if accounts.len() < group_len {
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
+        let (accounts, group) = accounts.split_at(accounts.len() - group_len);
+        let (group, hook_accounts) = group.split_at(WRAP_ACCOUNT_LEN);
+        let wrap = WrapAccounts {
+            wrapper_program_info: &group[0],
+            wrapper_info: &group[1],
+            underlying_mint_info: &group[2],
+            wrapper_mint_info: &group[3],
+            escrow_info: &group[4],
+            token_2022_program_info: &group[5],
+            hook_accounts,
+        };
+        if *wrap.wrapper_program_info.key != token_wrapper::id() {
+            return Err(AmmError::InvalidWrapperAccounts.into());
+        }
+        let wrapper =
+            token_wrapper::state::Wrapper::load_checked(wrap.wrapper_info, &token_wrapper::id())
+                .map_err(|_| AmmError::InvalidWrapperAccounts)?;
+        if *wrap.underlying_mint_info.key != wrapper.underlying_mint
+            || *wrap.wrapper_mint_info.key != wrapper.wrapper_mint
+            || *wrap.escrow_info.key != wrapper.escrow
+            || *wrap.token_2022_program_info.key != spl_token_2022::id()
+            || wrapper.wrapper_mint != *vault_mint
+        {
+            return Err(AmmError::InvalidWrapperAccounts.into());
+        }
+        Ok((accounts, Some(wrap)))
+    }
+
+    /// The mint and token program a user account of one side holds: the underlying mint of a
+    /// wrapped side, whose transfer fee the wrapper withholds, else the vault mint
+    fn user_side_mint<'a, 'b>(
+        wrap: Option<&WrapAccounts<'a, 'b>>,
+        mint_info: Option<&'a AccountInfo<'b>>,
+        token_program_info: &'a AccountInfo<'b>,
+    ) -> (Option<&'a AccountInfo<'b>>, &'a Pubkey) {
+        match wrap {
+            Some(wrap) => (
+                Some(wrap.underlying_mint_info),
+                wrap.token_2022_program_info.key,
+            ),
+            None => (mint_info, token_program_info.key),
+        }
+    }
+
+    /// Wrap `amount` underlying tokens of `underlying_info` into `wrapper_token_info`, or unwrap
+    /// them back, through the token wrapper of `wrap`. A wrap mints what reached the escrow,
+    /// net of the transfer fee of the underlying mint. `owner_signature_seeds` is empty when
+    /// the owner of the source signed the outer transaction itself.
+    #[allow(clippy::too_many_arguments)]
+    fn invoke_wrapper<'a>(
+        wrap: &WrapAccounts<'_, 'a>,
+        unwrap: bool,
+        token_program_info: &AccountInfo<'a>,
+        underlying_info: &AccountInfo<'a>,
+        wrapper_token_info: &AccountInfo<'a>,
+        owner_info: &AccountInfo<'a>,
+        amount: u64,
+        owner_signature_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let hook_accounts = wrap
+            .hook_accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: *account.key,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect::<Vec<_>>();
+        let ix = if unwrap {
+            token_wrapper::instruction::unwrap(
+                wrap.wrapper_program_info.key,
+                wrap.underlying_mint_info.key,
+                underlying_info.key,
+                wrapper_token_info.key,
+                owner_info.key,
+                &hook_accounts,
+                amount,
+            )
+        } else {
+            token_wrapper::instruction::wrap(
+                wrap.wrapper_program_info.key,
+                wrap.underlying_mint_info.key,
+                underlying_info.key,
+                wrapper_token_info.key,
+                owner_info.key,
+                &hook_accounts,
+                amount,
+            )
+        };
+        let mut account_infos = vec![
+            wrap.wrapper_info.clone(),
+            wrap.underlying_mint_info.clone(),
+            wrap.wrapper_mint_info.clone(),
+            wrap.escrow_info.clone(),
+            underlying_info.clone(),
+            wrapper_token_info.clone(),
+            owner_info.clone(),
+            wrap.token_2022_program_info.clone(),
+            token_program_info.clone(),
+            wrap.wrapper_program_info.clone(),
+        ];
+        account_infos.extend_from_slice(wrap.hook_accounts);
+        let signer = [owner_signature_seeds];
+        let signers: &[&[&[u8]]] = if owner_signature_seeds.is_empty() {
+            &[]
+        } else {
+            &signer
+        };
+        invoke_signed(&ix, &account_infos, signers)
+    }
+
+    /// Checks `price_oracle_info` is the writable price oracle of the pool `amm`
+    fn check_price_oracle(
+        program_id: &Pubkey,
+        amm: &Pubkey,
+        price_oracle_info: &AccountInfo,
+    ) -> ProgramResult {
+        if !price_oracle_info.is_writable {
+            return Err(AmmError::InvalidPriceOracle.into());
+        }
+        PriceOracle::load_checked(price_oracle_info, program_id, amm)?;
+        Ok(())
+    }
+
+    /// Accumulate the spot prices of `total_coin` and `total_pc`, the pool totals after the
+    /// swap or deposit, into the `PriceAccumulator` of the pool and record it in the price
+    /// oracle of the pool, if it has one. Pools without `AmmExtension` keep no prices.
+    fn update_prices(
+        program_id: &Pubkey,
+        amm: &AmmInfo,
+        amm_extension: Option<&mut AmmExtension>,
+        price_oracle_info: Option<&AccountInfo>,
+        total_coin: u64,
+        total_pc: u64,
+    ) -> ProgramResult {
+        let amm_extension = match amm_extension {
+            Some(amm_extension) => amm_extension,
+            None => return Ok(()),
+        };
+        let now = Clock::get()?.unix_timestamp as u64;
+        let (coin_price_x64, pc_price_x64) = amm
+            .curve(Some(amm_extension), now)?
+            .spot_prices_x64(total_coin, total_pc)?;
+        amm_extension
+            .price
+            .update(now, coin_price_x64, pc_price_x64);
+        if let Some(price_oracle_info) = price_oracle_info {
+            let (mut price_oracle, mut observations) =
+                PriceOracle::load_mut_checked(price_oracle_info, program_id)?;
+            price_oracle.record(&mut observations, &amm_extension.price);
+        }
+        Ok(())
+    }
+
+    /// Pool totals after a swap of `amount_in`, net of the inbound transfer fee, for `amount_out`
+    fn totals_after_swap(
+        swap_direction: SwapDirection,
+        total_coin: u64,
+        total_pc: u64,
+        amount_in: u64,
+        amount_out: u64,
+    ) -> Result<(u64, u64), AmmError> {
+        let (input_total, output_total) = match swap_direction {
+            SwapDirection::Coin2PC => (total_coin, total_pc),
+            SwapDirection::PC2Coin => (total_pc, total_coin),
+        };
+        let input_total = input_total
+            .checked_add(amount_in)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        let output_total = output_total
+            .checked_sub(amount_out)
+            .ok_or(AmmError::CheckedSubOverflow)?;
+        Ok(match swap_direction {
+            SwapDirection::Coin2PC => (input_total, output_total),
+            SwapDirection::PC2Coin => (output_total, input_total),
+        })
+    }
+
+    /// Accrue the protocol share of a swap fee, charged in the input token, to the pnl the
+    /// pnl owner takes with `WithdrawPnl`. It leaves the pool totals right away.
+    fn take_protocol_fee(
+        amm: &mut AmmInfo,
+        swap_direction: SwapDirection,
+        protocol_fee: u64,
+    ) -> ProgramResult {
+        match swap_direction {
+            SwapDirection::Coin2PC => {
+                amm.state_data.need_take_pnl_coin = amm
+                    .state_data
+                    .need_take_pnl_coin
+                    .checked_add(protocol_fee)
+                    .ok_or(AmmError::CheckedAddOverflow)?;
+            }
+            SwapDirection::PC2Coin => {
+                amm.state_data.need_take_pnl_pc = amm
+                    .state_data
+                    .need_take_pnl_pc
+                    .checked_add(protocol_fee)
+                    .ok_or(AmmError::CheckedAddOverflow)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Accumulate a swap of `amount_in` for `amount_out` into the pool statistics, with the LP
+    /// share of its fee
+    fn accumulate_swap(
+        amm: &mut AmmInfo,
+        swap_direction: SwapDirection,
+        amount_in: u64,
+        amount_out: u64,
+        lp_fee: u64,
+    ) {
+        match swap_direction {
+            SwapDirection::Coin2PC => {
+                amm.state_data.swap_coin_in_amount = amm
+                    .state_data
+                    .swap_coin_in_amount
+                    .checked_add(amount_in.into())
+                    .unwrap();
+                amm.state_data.swap_pc_out_amount = amm
+                    .state_data
+                    .swap_pc_out_amount
+                    .checked_add(amount_out.into())
+                    .unwrap();
+                // charge coin as swap fee, the LP share
+                amm.state_data.swap_acc_coin_fee = amm
+                    .state_data
+                    .swap_acc_coin_fee
+                    .checked_add(lp_fee)
+                    .unwrap();
+            }
+            SwapDirection::PC2Coin => {
+                amm.state_data.swap_pc_in_amount = amm
+                    .state_data
+                    .swap_pc_in_amount
+                    .checked_add(amount_in.into())
+                    .unwrap();
+                amm.state_data.swap_coin_out_amount = amm
+                    .state_data
+                    .swap_coin_out_amount
+                    .checked_add(amount_out.into())
+                    .unwrap();
+                // charge pc as swap fee, the LP share
+                amm.state_data.swap_acc_pc_fee =
+                    amm.state_data.swap_acc_pc_fee.checked_add(lp_fee).unwrap();
+            }
+        }
+    }
+
+    /// Checks the token program of each side against the ones recorded in the pool
+    pub fn check_token_programs(
+        amm: &AmmInfo,
+        coin_token_program_info: &AccountInfo,
+        pc_token_program_info: &AccountInfo,
+    ) -> ProgramResult {
+        check_assert_eq!(
+            *coin_token_program_info.key,
+            amm.coin_token_program(),
+            "coin_token_program",
+            AmmError::InvalidSplTokenProgram
+        );
+        check_assert_eq!(
+            *pc_token_program_info.key,
+            amm.pc_token_program(),
+            "pc_token_program",
+            AmmError::InvalidSplTokenProgram
+        );
+        Ok(())
+    }
+
+    /// Pins the transfer hook programs of the pool mints, see `AmmInfo::pinned_hook_programs`
+    pub fn pin_hook_programs(
+        amm: &mut AmmInfo,
+        coin_mint_info: &AccountInfo,
+        pc_mint_info: &AccountInfo,
+    ) -> ProgramResult {
+        check_assert_eq!(
+            *coin_mint_info.key,
+            amm.coin_vault_mint,
+            "coin_mint",
+            AmmError::InvalidCoinMint
+        );
+        check_assert_eq!(
+            *pc_mint_info.key,
+            amm.pc_vault_mint,
+            "pc_mint",
+            AmmError::InvalidPCMint
+        );
+        let coin_hook_program = crate::token_utils::get_transfer_hook_program_id(
+            coin_mint_info,
+            &amm.coin_token_program(),
+        )?;
+        let pc_hook_program = crate::token_utils::get_transfer_hook_program_id(
+            pc_mint_info,
+            &amm.pc_token_program(),
+        )?;
+        amm.pin_hook_programs(coin_hook_program, pc_hook_program);
+        Ok(())
+    }
+
+    /// A hook authority can point a live mint at another transfer hook program, so the
+    /// hook programs of the pool mints are compared with the ones pinned at pool creation.
+    /// On a change the pool moves to `AmmStatus::HookProgramChanged`, which only allows
+    /// withdrawals. Returns whether a hook program changed, deposits and swaps fail on it
+    /// and the pause is committed by a withdrawal or `PauseOnHookChange`.
+    /// The mint of every Token-2022 side is required, a SPL Token mint has no hook.
+    pub fn check_hook_programs<'a>(
+        amm: &mut AmmInfo,
+        coin_mint_info: Option<&AccountInfo<'a>>,
+        pc_mint_info: Option<&AccountInfo<'a>>,
+    ) -> Result<bool, ProgramError> {
+        let (coin_hook_program, pc_hook_program) = match amm.pinned_hook_programs() {
+            Some(pinned) => pinned,
+            None => return Ok(false),
+        };
+        let mut changed = false;
+        for (mint_info, mint, token_program_id, pinned_hook_program) in [
+            (
+                coin_mint_info,
+                amm.coin_vault_mint,
+                amm.coin_token_program(),
+                coin_hook_program,
+            ),
+            (
+                pc_mint_info,
+                amm.pc_vault_mint,
+                amm.pc_token_program(),
+                pc_hook_program,
+            ),
+        ] {
+            if token_program_id != spl_token_2022::id() {
+                continue;
+            }
+            let mint_info = match mint_info.filter(|mint_info| *mint_info.key == mint) {
+                Some(mint_info) => mint_info,
+                None => return Err(AmmError::InvalidTransferHookAccounts.into()),
+            };
+            let hook_program =
+                crate::token_utils::get_transfer_hook_program_id(mint_info, &token_program_id)?
+                    .unwrap_or_default();
+            if hook_program != pinned_hook_program {
+                changed = true;
+                encode_ray_log(HookProgramChangedLog {
+                    log_type: LogType::HookProgramChanged.into_u8(),
+                    mint,
+                    pinned_hook_program,
+                    hook_program,
+                });
+            }
+        }
+        if changed {
+            amm.status = AmmStatus::HookProgramChanged.into_u64();
+        }
+        Ok(changed)
+    }
+
+    /// Transfer fee withheld when `amount` of one side is transferred.
+    /// Token-2022 transfers need the mint, so a side without one has no fee.
+    pub fn transfer_fee(
+        mint_info: Option<&AccountInfo>,
+        token_program_id: &Pubkey,
+        epoch: u64,
+        amount: u64,
+    ) -> Result<u64, ProgramError> {
+        match mint_info {
+            Some(mint_info) => Ok(crate::token_utils::get_transfer_fee(
+                mint_info,
+                token_program_id,
+                epoch,
+                amount,
+            )?),
+            None => Ok(0),
+        }
+    }
+
+    /// The coin and pc amounts a withdrawal delivers once the outbound Token-2022 transfer fees
+    /// are withheld, which is what the user's minimum amounts are held against
+    pub fn withdraw_received_amounts(
+        coin_mint_info: Option<&AccountInfo>,
+        coin_token_program_id: &Pubkey,
+        coin_amount: u64,
+        pc_mint_info: Option<&AccountInfo>,
+        pc_token_program_id: &Pubkey,
+        pc_amount: u64,
+    ) -> Result<(u64, u64), ProgramError> {
+        let epoch = Clock::get()?.epoch;
+        let coin_transfer_fee =
+            Self::transfer_fee(coin_mint_info, coin_token_program_id, epoch, coin_amount)?;
+        let pc_transfer_fee =
+            Self::transfer_fee(pc_mint_info, pc_token_program_id, epoch, pc_amount)?;
+        Ok((
+            coin_amount.saturating_sub(coin_transfer_fee),
+            pc_amount.saturating_sub(pc_transfer_fee),
+        ))
+    }
+
+    /// Transfer fee to add so that `post_fee_amount` of one side arrives in full, see `transfer_fee`
+    pub fn transfer_inverse_fee(
+        mint_info: Option<&AccountInfo>,
+        token_program_id: &Pubkey,
+        epoch: u64,
+        post_fee_amount: u64,
+    ) -> Result<u64, ProgramError> {
+        match mint_info {
+            Some(mint_info) => Ok(crate::token_utils::get_transfer_inverse_fee(
+                mint_info,
+                token_program_id,
+                epoch,
+                post_fee_amount,
+            )?),
+            None => Ok(0),
+        }
+    }
+
     pub fn load_serum_market_order<'a>(
         market_acc: &AccountInfo<'a>,
         open_orders_acc: &AccountInfo<'a>,
@@ -876,6 +1501,114 @@ impl Processor {
         Ok(())
     }
 
+    /// Checks the mints of a new pool: a mint with a transfer hook is only accepted if
+    /// the hook config of its hook program is in `remaining_accounts`, and extensions
+    /// that let the issuer drain the vaults need the admin to sign the pool creation.
+    /// Hooks activated through the registry are only accepted with `registry_hooks`.
+    /// Returns the extensions of the coin and pc mints.
+    fn check_pool_mints(
+        program_id: &Pubkey,
+        coin_mint_info: &AccountInfo,
+        coin_token_program_id: &Pubkey,
+        pc_mint_info: &AccountInfo,
+        pc_token_program_id: &Pubkey,
+        user_wallet_info: &AccountInfo,
+        remaining_accounts: &[AccountInfo],
+        registry_hooks: bool,
+    ) -> Result<(u64, u64), ProgramError> {
+        crate::whitelist::validate_transfer_hook(
+            program_id,
+            coin_mint_info,
+            coin_token_program_id,
+            remaining_accounts,
+            registry_hooks,
+        )?;
+        crate::whitelist::validate_transfer_hook(
+            program_id,
+            pc_mint_info,
+            pc_token_program_id,
+            remaining_accounts,
+            registry_hooks,
+        )?;
+        let admin_approved = *user_wallet_info.key == config_feature::amm_owner::id()
+            || remaining_accounts.iter().any(|account| {
+                *account.key == config_feature::amm_owner::id() && account.is_signer
+            });
+        let coin_mint_extensions = crate::token_utils::check_mint_extensions(
+            coin_mint_info,
+            coin_token_program_id,
+            admin_approved,
+        )?;
+        let pc_mint_extensions = crate::token_utils::check_mint_extensions(
+            pc_mint_info,
+            pc_token_program_id,
+            admin_approved,
+        )?;
+        Ok((coin_mint_extensions, pc_mint_extensions))
+    }
+
+    /// Charges the fee to create a pool, as set in the amm config
+    fn charge_create_pool_fee<'a>(
+        program_id: &Pubkey,
+        amm_config_info: &AccountInfo<'a>,
+        create_fee_destination_info: &AccountInfo<'a>,
+        user_wallet_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        coin_token_program_info: &AccountInfo<'a>,
+        pc_token_program_info: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        if *create_fee_destination_info.key != config_feature::create_pool_fee_address::id() {
+            return Err(AmmError::InvalidFee.into());
+        }
+        let amm_config = AmmConfig::load_checked(&amm_config_info, program_id)?;
+        // Charge the fee to create a pool
+        if amm_config.create_pool_fee != 0 {
+            invoke(
+                &system_instruction::transfer(
+                    user_wallet_info.key,
+                    create_fee_destination_info.key,
+                    amm_config.create_pool_fee,
+                ),
+                &[
+                    user_wallet_info.clone(),
+                    create_fee_destination_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+            // sync_native with the token program owning the fee destination
+            let token_program_info =
+                if create_fee_destination_info.owner == coin_token_program_info.key {
+                    coin_token_program_info
+                } else {
+                    pc_token_program_info
+                };
+            if *token_program_info.key == spl_token::id() {
+                invoke(
+                    &spl_token::instruction::sync_native(
+                        token_program_info.key,
+                        create_fee_destination_info.key,
+                    )?,
+                    &[
+                        token_program_info.clone(),
+                        create_fee_destination_info.clone(),
+                    ],
+                )?;
+            } else {
+                invoke(
+                    &spl_token_2022::instruction::sync_native(
+                        token_program_info.key,
+                        create_fee_destination_info.key,
+                    )?,
+                    &[
+                        token_program_info.clone(),
+                        create_fee_destination_info.clone(),
+                    ],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     /// Processes an [Initialize](enum.Instruction.html).
     pub fn process_initialize2(
         program_id: &Pubkey,
@@ -883,7 +1616,7 @@ impl Processor {
         init: InitializeInstruction2,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let token_program_info = next_account_info(account_info_iter)?;
+        let coin_token_program_info = next_account_info(account_info_iter)?;
         let ata_token_program_info = next_account_info(account_info_iter)?;
         let system_program_info = next_account_info(account_info_iter)?;
         let rent_sysvar_info = next_account_info(account_info_iter)?;
@@ -906,6 +1639,20 @@ impl Processor {
         let user_token_coin_info = next_account_info(account_info_iter)?;
         let user_token_pc_info = next_account_info(account_info_iter)?;
         let user_token_lp_info = next_account_info(account_info_iter)?;
+        // mints and transfer hook accounts of the Token-2022 sides,
+        // and the admin approving the mint extensions that need it
+        let remaining_accounts = account_info_iter.as_slice();
+        // a pc mint of the other token program takes it as the last account,
+        // so the accounts of an SPL Token pool keep their original order
+        let (remaining_accounts, pc_token_program_info) =
+            if amm_pc_mint_info.owner != coin_token_program_info.key {
+                match remaining_accounts.split_last() {
+                    Some((last, rest)) => (rest, last),
+                    None => return Err(AmmError::WrongAccountsNumber.into()),
+                }
+            } else {
+                (remaining_accounts, coin_token_program_info)
+            };
 
         let (pda, _) = Pubkey::find_program_address(&[&AMM_CONFIG_SEED], program_id);
         if pda != *amm_config_info.key || amm_config_info.owner != program_id {
@@ -916,12 +1663,32 @@ impl Processor {
         if !user_wallet_info.is_signer {
             return Err(AmmError::InvalidSignAccount.into());
         }
-        // Allow both SPL Token and Token-2022
-        let token_program = *token_program_info.key;
-        if token_program != spl_token::id() && token_program != spl_token_2022::id() {
-            return Err(AmmError::InvalidSplTokenProgram.into());
+        // the orderbook quotes the constant product curve
+        if init.amp != 0 {
+            return Err(AmmError::UnsupportedCurve.into());
         }
-        let spl_token_program_id = token_program_info.key;
+        // Each side may use SPL Token or Token-2022
+        for token_program_info in [coin_token_program_info, pc_token_program_info] {
+            let token_program = *token_program_info.key;
+            if token_program != spl_token::id() && token_program != spl_token_2022::id() {
+                return Err(AmmError::InvalidSplTokenProgram.into());
+            }
+        }
+        let coin_token_program_id = coin_token_program_info.key;
+        let pc_token_program_id = pc_token_program_info.key;
+        // the lp mint is created under the pc side token program
+        let lp_token_program_info = pc_token_program_info;
+        let lp_token_program_id = lp_token_program_info.key;
+        let (coin_mint_extensions, pc_mint_extensions) = Self::check_pool_mints(
+            program_id,
+            amm_coin_mint_info,
+            coin_token_program_id,
+            amm_pc_mint_info,
+            pc_token_program_id,
+            user_wallet_info,
+            remaining_accounts,
+            init.registry_hooks,
+        )?;
         check_assert_eq!(
             *ata_token_program_info.key,
             spl_associated_token_account::id(),
@@ -945,53 +1712,19 @@ impl Processor {
         if *amm_authority_info.key != expect_amm_authority || init.nonce != expect_nonce {
             return Err(AmmError::InvalidProgramAddress.into());
         }
-        if *create_fee_destination_info.key != config_feature::create_pool_fee_address::id() {
-            return Err(AmmError::InvalidFee.into());
-        }
-        let amm_config = AmmConfig::load_checked(&amm_config_info, program_id)?;
-        // Charge the fee to create a pool
-        if amm_config.create_pool_fee != 0 {
-            invoke(
-                &system_instruction::transfer(
-                    user_wallet_info.key,
-                    create_fee_destination_info.key,
-                    amm_config.create_pool_fee,
-                ),
-                &[
-                    user_wallet_info.clone(),
-                    create_fee_destination_info.clone(),
-                    system_program_info.clone(),
-                ],
-            )?;
-            // sync_native for either SPL Token or Token-2022
-            if *token_program_info.key == spl_token::id() {
-                invoke(
-                    &spl_token::instruction::sync_native(
-                        token_program_info.key,
-                        create_fee_destination_info.key,
-                    )?,
-                    &[
-                        token_program_info.clone(),
-                        create_fee_destination_info.clone(),
-                    ],
-                )?;
-            } else {
-                invoke(
-                    &spl_token_2022::instruction::sync_native(
-                        token_program_info.key,
-                        create_fee_destination_info.key,
-                    )?,
-                    &[
-                        token_program_info.clone(),
-                        create_fee_destination_info.clone(),
-                    ],
-                )?;
-            }
-        }
+        Self::charge_create_pool_fee(
+            program_id,
+            amm_config_info,
+            create_fee_destination_info,
+            user_wallet_info,
+            system_program_info,
+            coin_token_program_info,
+            pc_token_program_info,
+        )?;
 
         // unpack and check mints (support Token-2022 extensions)
-        let coin_mint = crate::token_utils::unpack_mint(&amm_coin_mint_info, spl_token_program_id)?;
-        let pc_mint = crate::token_utils::unpack_mint(&amm_pc_mint_info, spl_token_program_id)?;
+        let coin_mint = crate::token_utils::unpack_mint(&amm_coin_mint_info, coin_token_program_id)?;
+        let pc_mint = crate::token_utils::unpack_mint(&amm_pc_mint_info, pc_token_program_id)?;
 
         // create target_order account
         Self::generate_amm_associated_account(
@@ -1010,13 +1743,13 @@ impl Processor {
         let lp_decimals = coin_mint.decimals;
         Self::generate_amm_associated_spl_mint(
             program_id,
-            spl_token_program_id,
+            lp_token_program_id,
             market_info,
             amm_lp_mint_info,
             user_wallet_info,
             system_program_info,
             rent_sysvar_info,
-            token_program_info,
+            lp_token_program_info,
             amm_authority_info,
             LP_MINT_ASSOCIATED_SEED,
             lp_decimals,
@@ -1024,28 +1757,28 @@ impl Processor {
         // create coin vault account
         Self::generate_amm_associated_spl_token(
             program_id,
-            spl_token_program_id,
+            coin_token_program_id,
             market_info,
             amm_coin_vault_info,
             amm_coin_mint_info,
             user_wallet_info,
             system_program_info,
             rent_sysvar_info,
-            token_program_info,
+            coin_token_program_info,
             amm_authority_info,
             COIN_VAULT_ASSOCIATED_SEED,
         )?;
         // create pc vault account
         Self::generate_amm_associated_spl_token(
             program_id,
-            spl_token_program_id,
+            pc_token_program_id,
             market_info,
             amm_pc_vault_info,
             amm_pc_mint_info,
             user_wallet_info,
             system_program_info,
             rent_sysvar_info,
-            token_program_info,
+            pc_token_program_info,
             amm_authority_info,
             PC_VAULT_ASSOCIATED_SEED,
         )?;
@@ -1059,7 +1792,7 @@ impl Processor {
             system_program_info,
             rent_sysvar_info,
             AMM_ASSOCIATED_SEED,
-            size_of::<AmmInfo>(),
+            AMM_EXTENDED_LEN,
         )?;
 
         // create amm open order account
@@ -1091,25 +1824,67 @@ impl Processor {
             user_wallet_info.clone(),
             user_wallet_info.clone(),
             amm_lp_mint_info.clone(),
-            token_program_info.clone(),
+            lp_token_program_info.clone(),
             ata_token_program_info.clone(),
             system_program_info.clone(),
         )?;
 
+        // resolve the transfer hook accounts of both transfers, the vaults exist by now
+        let (_, coin_hook_config, coin_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                amm_coin_mint_info.key,
+                coin_token_program_id,
+                user_token_coin_info,
+                amm_coin_vault_info,
+                user_wallet_info,
+                init.init_coin_amount,
+                remaining_accounts,
+                init.registry_hooks,
+            )?;
+        let (_, pc_hook_config, pc_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                amm_pc_mint_info.key,
+                pc_token_program_id,
+                user_token_pc_info,
+                amm_pc_vault_info,
+                user_wallet_info,
+                init.init_pc_amount,
+                remaining_accounts,
+                init.registry_hooks,
+            )?;
+        // only the admin approving mint extensions may follow the transfer accounts
+        if remaining_accounts
+            .iter()
+            .any(|account| *account.key != config_feature::amm_owner::id())
+        {
+            return Err(AmmError::InvalidTransferHookAccounts.into());
+        }
         // transfer user tokens to vault
-        Invokers::token_transfer(
-            token_program_info.clone(),
+        crate::token_invokers::token_transfer_with_hook_budget(
+            coin_hook_config.as_ref(),
+            coin_token_program_info.clone(),
             user_token_coin_info.clone(),
+            Some(amm_coin_mint_info.clone()),
             amm_coin_vault_info.clone(),
             user_wallet_info.clone(),
+            coin_hook_accounts,
             init.init_coin_amount,
+            coin_mint.decimals,
+            &[],
         )?;
-        Invokers::token_transfer(
-            token_program_info.clone(),
+        crate::token_invokers::token_transfer_with_hook_budget(
+            pc_hook_config.as_ref(),
+            pc_token_program_info.clone(),
             user_token_pc_info.clone(),
+            Some(amm_pc_mint_info.clone()),
             amm_pc_vault_info.clone(),
             user_wallet_info.clone(),
+            pc_hook_accounts,
             init.init_pc_amount,
+            pc_mint.decimals,
+            &[],
         )?;
 
         // load AmmInfo
@@ -1120,7 +1895,7 @@ impl Processor {
 
         // unpack and check token_coin
         let amm_coin_vault =
-            crate::token_utils::unpack_token_account(&amm_coin_vault_info, spl_token_program_id)?;
+            crate::token_utils::unpack_token_account(&amm_coin_vault_info, coin_token_program_id)?;
         check_assert_eq!(
             amm_coin_vault.owner,
             *amm_authority_info.key,
@@ -1143,7 +1918,8 @@ impl Processor {
             AmmError::InvalidCoinMint
         );
         // unpack and check token_pc
-        let amm_pc_vault = crate::token_utils::unpack_token_account(&amm_pc_vault_info, spl_token_program_id)?;
+        let amm_pc_vault =
+            crate::token_utils::unpack_token_account(&amm_pc_vault_info, pc_token_program_id)?;
         check_assert_eq!(
             amm_pc_vault.owner,
             *amm_authority_info.key,
@@ -1188,11 +1964,11 @@ impl Processor {
             return Err(AmmError::InvalidMarket.into());
         }
 
-        let lp_mint = Self::unpack_mint(&amm_lp_mint_info, spl_token_program_id)?;
+        let lp_mint = crate::token_utils::unpack_mint(&amm_lp_mint_info, lp_token_program_id)?;
         if lp_mint.supply != 0 {
             return Err(AmmError::InvalidSupply.into());
         }
-        if COption::Some(*amm_authority_info.key) != lp_mint.mint_authority {
+        if Some(*amm_authority_info.key) != lp_mint.mint_authority {
             return Err(AmmError::InvalidOwner.into());
         }
         if lp_mint.freeze_authority.is_some() {
@@ -1212,14 +1988,13 @@ impl Processor {
 
         // liquidity is measured in terms of token_a's value since both sides of
         // the pool are equal
-        Invokers::token_mint_to(
-            token_program_info.clone(),
+        crate::token_invokers::token_mint_to(
+            lp_token_program_info.clone(),
             amm_lp_mint_info.clone(),
             user_token_lp_info.clone(),
             amm_authority_info.clone(),
-            AUTHORITY_AMM,
-            init.nonce,
             user_lp_amount,
+            &[AUTHORITY_AMM, &[init.nonce]],
         )?;
 
         amm.initialize(
@@ -1230,6 +2005,12 @@ impl Processor {
             market_state.coin_lot_size,
             market_state.pc_lot_size,
         )?;
+        amm.set_token_programs(coin_token_program_id, pc_token_program_id);
+        if init.registry_hooks {
+            amm.pool_flags |= POOL_FLAG_REGISTRY_HOOKS;
+        }
+        amm.state_data.coin_mint_extensions = coin_mint_extensions;
+        amm.state_data.pc_mint_extensions = pc_mint_extensions;
         encode_ray_log(InitLog {
             log_type: LogType::Init.into_u8(),
             time: init.open_time,
@@ -1263,6 +2044,7 @@ impl Processor {
         amm.coin_vault_mint = *amm_coin_mint_info.key;
         amm.pc_vault_mint = *amm_pc_mint_info.key;
         amm.lp_mint = *amm_lp_mint_info.key;
+        Self::pin_hook_programs(&mut amm, amm_coin_mint_info, amm_pc_mint_info)?;
         amm.open_orders = *amm_open_orders_info.key;
         amm.market = *market_info.key;
         amm.market_program = *market_program_info.key;
@@ -1279,107 +2061,501 @@ impl Processor {
         Ok(())
     }
 
-    /// Processes an [Deposit](enum.Instruction.html).
-    pub fn process_deposit(
+    /// Processes an [InitializeNoOrderbook](enum.Instruction.html).
+    pub fn process_initialize_no_orderbook(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        deposit: DepositInstruction,
+        init: InitializeInstruction2,
     ) -> ProgramResult {
-        const ACCOUNT_LEN: usize = 14;
-        let input_account_len = accounts.len();
-        if input_account_len != ACCOUNT_LEN && input_account_len != ACCOUNT_LEN + 1 {
-            return Err(AmmError::WrongAccountsNumber.into());
-        }
         let account_info_iter = &mut accounts.iter();
-        let token_program_info = next_account_info(account_info_iter)?;
-
+        let coin_token_program_info = next_account_info(account_info_iter)?;
+        let pc_token_program_info = next_account_info(account_info_iter)?;
+        let ata_token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_sysvar_info = next_account_info(account_info_iter)?;
         let amm_info = next_account_info(account_info_iter)?;
         let amm_authority_info = next_account_info(account_info_iter)?;
-        let amm_open_orders_info = next_account_info(account_info_iter)?;
-        let amm_target_orders_info = next_account_info(account_info_iter)?;
         let amm_lp_mint_info = next_account_info(account_info_iter)?;
+        let amm_coin_mint_info = next_account_info(account_info_iter)?;
+        let amm_pc_mint_info = next_account_info(account_info_iter)?;
         let amm_coin_vault_info = next_account_info(account_info_iter)?;
         let amm_pc_vault_info = next_account_info(account_info_iter)?;
+        let amm_config_info = next_account_info(account_info_iter)?;
+        let create_fee_destination_info = next_account_info(account_info_iter)?;
+        // derives the pool accounts, as the market does for an orderbook pool
+        let pool_seed_info = next_account_info(account_info_iter)?;
 
-        let market_info = next_account_info(account_info_iter)?;
+        let user_wallet_info = next_account_info(account_info_iter)?;
+        let user_token_coin_info = next_account_info(account_info_iter)?;
+        let user_token_pc_info = next_account_info(account_info_iter)?;
+        let user_token_lp_info = next_account_info(account_info_iter)?;
+        // mints and transfer hook accounts of the Token-2022 sides,
+        // and the admin approving the mint extensions that need it
+        let remaining_accounts = account_info_iter.as_slice();
 
-        let user_source_coin_info = next_account_info(account_info_iter)?;
-        let user_source_pc_info = next_account_info(account_info_iter)?;
-        let user_dest_lp_info = next_account_info(account_info_iter)?;
-        let source_owner_info = next_account_info(account_info_iter)?;
-        let market_event_queue_info = next_account_info(account_info_iter)?;
-        let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
-        if deposit.max_coin_amount == 0 || deposit.max_pc_amount == 0 {
-            encode_ray_log(DepositLog {
-                log_type: LogType::Deposit.into_u8(),
-                max_coin: deposit.max_coin_amount,
-                max_pc: deposit.max_pc_amount,
-                base: deposit.base_side,
-                pool_coin: 0,
-                pool_pc: 0,
-                pool_lp: 0,
-                calc_pnl_x: 0,
-                calc_pnl_y: 0,
-                deduct_coin: 0,
-                deduct_pc: 0,
-                mint_lp: 0,
-            });
-            return Err(AmmError::InvalidInput.into());
+        let (pda, _) = Pubkey::find_program_address(&[&AMM_CONFIG_SEED], program_id);
+        if pda != *amm_config_info.key || amm_config_info.owner != program_id {
+            return Err(AmmError::InvalidConfigAccount.into());
         }
-        if !source_owner_info.is_signer {
+
+        msg!(arrform!(LOG_SIZE, "initialize_no_orderbook: {:?}", init).as_str());
+        if !user_wallet_info.is_signer {
             return Err(AmmError::InvalidSignAccount.into());
         }
-
-        if !AmmStatus::from_u64(amm.status).deposit_permission() {
-            return Err(AmmError::InvalidStatus.into());
+        for token_program_info in [coin_token_program_info, pc_token_program_info] {
+            let token_program = *token_program_info.key;
+            if token_program != spl_token::id() && token_program != spl_token_2022::id() {
+                return Err(AmmError::InvalidSplTokenProgram.into());
+            }
         }
-        if *amm_authority_info.key
-            != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
-        {
+        let coin_token_program_id = coin_token_program_info.key;
+        let pc_token_program_id = pc_token_program_info.key;
+        // the lp mint is created under the pc side token program
+        let lp_token_program_info = pc_token_program_info;
+        let lp_token_program_id = lp_token_program_info.key;
+        let (coin_mint_extensions, pc_mint_extensions) = Self::check_pool_mints(
+            program_id,
+            amm_coin_mint_info,
+            coin_token_program_id,
+            amm_pc_mint_info,
+            pc_token_program_id,
+            user_wallet_info,
+            remaining_accounts,
+            init.registry_hooks,
+        )?;
+        check_assert_eq!(
+            *ata_token_program_info.key,
+            spl_associated_token_account::id(),
+            "spl_associated_token_account",
+            AmmError::InvalidSplTokenProgram
+        );
+        check_assert_eq!(
+            *system_program_info.key,
+            solana_program::system_program::id(),
+            "sys_program",
+            AmmError::InvalidSysProgramAddress
+        );
+        let (expect_amm_authority, expect_nonce) =
+            Pubkey::find_program_address(&[&AUTHORITY_AMM], program_id);
+        if *amm_authority_info.key != expect_amm_authority || init.nonce != expect_nonce {
             return Err(AmmError::InvalidProgramAddress.into());
         }
-        let enable_orderbook;
-        if AmmStatus::from_u64(amm.status).orderbook_permission() {
-            enable_orderbook = true;
-        } else {
-            enable_orderbook = false;
-        }
-        // Allow both SPL Token and Token-2022
-        let token_program = *token_program_info.key;
-        if token_program != spl_token::id() && token_program != spl_token_2022::id() {
-            return Err(AmmError::InvalidSplTokenProgram.into());
+        if *amm_coin_mint_info.key == *amm_pc_mint_info.key {
+            return Err(AmmError::InvalidPCMint.into());
         }
-        let spl_token_program_id = token_program_info.key;
-        // token_coin must be amm.coin_vault or token_source_coin must not be amm.coin_vault
-        if *amm_coin_vault_info.key != amm.coin_vault
-            || *user_source_coin_info.key == amm.coin_vault
+        Self::charge_create_pool_fee(
+            program_id,
+            amm_config_info,
+            create_fee_destination_info,
+            user_wallet_info,
+            system_program_info,
+            coin_token_program_info,
+            pc_token_program_info,
+        )?;
+
+        let coin_mint =
+            crate::token_utils::unpack_mint(&amm_coin_mint_info, coin_token_program_id)?;
+        let pc_mint = crate::token_utils::unpack_mint(&amm_pc_mint_info, pc_token_program_id)?;
+
+        // create lp mint account
+        let lp_decimals = coin_mint.decimals;
+        Self::generate_amm_associated_spl_mint(
+            program_id,
+            lp_token_program_id,
+            pool_seed_info,
+            amm_lp_mint_info,
+            user_wallet_info,
+            system_program_info,
+            rent_sysvar_info,
+            lp_token_program_info,
+            amm_authority_info,
+            LP_MINT_ASSOCIATED_SEED,
+            lp_decimals,
+        )?;
+        // create coin vault account
+        Self::generate_amm_associated_spl_token(
+            program_id,
+            coin_token_program_id,
+            pool_seed_info,
+            amm_coin_vault_info,
+            amm_coin_mint_info,
+            user_wallet_info,
+            system_program_info,
+            rent_sysvar_info,
+            coin_token_program_info,
+            amm_authority_info,
+            COIN_VAULT_ASSOCIATED_SEED,
+        )?;
+        // create pc vault account
+        Self::generate_amm_associated_spl_token(
+            program_id,
+            pc_token_program_id,
+            pool_seed_info,
+            amm_pc_vault_info,
+            amm_pc_mint_info,
+            user_wallet_info,
+            system_program_info,
+            rent_sysvar_info,
+            pc_token_program_info,
+            amm_authority_info,
+            PC_VAULT_ASSOCIATED_SEED,
+        )?;
+        // create amm account
+        Self::generate_amm_associated_account(
+            program_id,
+            program_id,
+            pool_seed_info,
+            amm_info,
+            user_wallet_info,
+            system_program_info,
+            rent_sysvar_info,
+            AMM_ASSOCIATED_SEED,
+            AMM_EXTENDED_LEN,
+        )?;
+
+        // create user ata lp token
+        Invokers::create_ata_spl_token(
+            user_token_lp_info.clone(),
+            user_wallet_info.clone(),
+            user_wallet_info.clone(),
+            amm_lp_mint_info.clone(),
+            lp_token_program_info.clone(),
+            ata_token_program_info.clone(),
+            system_program_info.clone(),
+        )?;
+
+        // resolve the transfer hook accounts of both transfers, the vaults exist by now
+        let (_, coin_hook_config, coin_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                amm_coin_mint_info.key,
+                coin_token_program_id,
+                user_token_coin_info,
+                amm_coin_vault_info,
+                user_wallet_info,
+                init.init_coin_amount,
+                remaining_accounts,
+                init.registry_hooks,
+            )?;
+        let (_, pc_hook_config, pc_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                amm_pc_mint_info.key,
+                pc_token_program_id,
+                user_token_pc_info,
+                amm_pc_vault_info,
+                user_wallet_info,
+                init.init_pc_amount,
+                remaining_accounts,
+                init.registry_hooks,
+            )?;
+        // only the admin approving mint extensions may follow the transfer accounts
+        if remaining_accounts
+            .iter()
+            .any(|account| *account.key != config_feature::amm_owner::id())
         {
-            return Err(AmmError::InvalidCoinVault.into());
+            return Err(AmmError::InvalidTransferHookAccounts.into());
         }
-        // token_pc must be amm.pc_vault or token_source_pc must not be amm.pc_vault
-        if *amm_pc_vault_info.key != amm.pc_vault || *user_source_pc_info.key == amm.pc_vault {
-            return Err(AmmError::InvalidPCVault.into());
+        // transfer user tokens to vault
+        crate::token_invokers::token_transfer_with_hook_budget(
+            coin_hook_config.as_ref(),
+            coin_token_program_info.clone(),
+            user_token_coin_info.clone(),
+            Some(amm_coin_mint_info.clone()),
+            amm_coin_vault_info.clone(),
+            user_wallet_info.clone(),
+            coin_hook_accounts,
+            init.init_coin_amount,
+            coin_mint.decimals,
+            &[],
+        )?;
+        crate::token_invokers::token_transfer_with_hook_budget(
+            pc_hook_config.as_ref(),
+            pc_token_program_info.clone(),
+            user_token_pc_info.clone(),
+            Some(amm_pc_mint_info.clone()),
+            amm_pc_vault_info.clone(),
+            user_wallet_info.clone(),
+            pc_hook_accounts,
+            init.init_pc_amount,
+            pc_mint.decimals,
+            &[],
+        )?;
+
+        // load AmmInfo
+        let (mut amm, mut amm_extension) = AmmInfo::load_mut_extended(&amm_info)?;
+        if amm.status != AmmStatus::Uninitialized.into_u64() {
+            return Err(AmmError::AlreadyInUse.into());
         }
+
+        // unpack and check token_coin
+        let amm_coin_vault =
+            crate::token_utils::unpack_token_account(&amm_coin_vault_info, coin_token_program_id)?;
         check_assert_eq!(
-            *amm_lp_mint_info.key,
-            amm.lp_mint,
-            "lp_mint",
-            AmmError::InvalidPoolMint
+            amm_coin_vault.owner,
+            *amm_authority_info.key,
+            "coin_vault_owner",
+            AmmError::InvalidOwner
         );
+        if amm_coin_vault.amount == 0 {
+            return Err(AmmError::InvalidSupply.into());
+        }
+        if amm_coin_vault.delegate.is_some() {
+            return Err(AmmError::InvalidDelegate.into());
+        }
+        if amm_coin_vault.close_authority.is_some() {
+            return Err(AmmError::InvalidCloseAuthority.into());
+        }
         check_assert_eq!(
-            *amm_target_orders_info.key,
-            amm.target_orders,
-            "target_orders",
-            AmmError::InvalidTargetOrders
+            *amm_coin_mint_info.key,
+            amm_coin_vault.mint,
+            "coin_mint",
+            AmmError::InvalidCoinMint
         );
-        let amm_coin_vault =
-            crate::token_utils::unpack_token_account(&amm_coin_vault_info, spl_token_program_id)?;
-        let amm_pc_vault = crate::token_utils::unpack_token_account(&amm_pc_vault_info, spl_token_program_id)?;
-        let user_source_coin =
-            crate::token_utils::unpack_token_account(&user_source_coin_info, spl_token_program_id)?;
-        let user_source_pc =
-            crate::token_utils::unpack_token_account(&user_source_pc_info, spl_token_program_id)?;
-        let mut target_orders =
+        // unpack and check token_pc
+        let amm_pc_vault =
+            crate::token_utils::unpack_token_account(&amm_pc_vault_info, pc_token_program_id)?;
+        check_assert_eq!(
+            amm_pc_vault.owner,
+            *amm_authority_info.key,
+            "pc_vault_owner",
+            AmmError::InvalidOwner
+        );
+        if amm_pc_vault.amount == 0 {
+            return Err(AmmError::InvalidSupply.into());
+        }
+        if amm_pc_vault.delegate.is_some() {
+            return Err(AmmError::InvalidDelegate.into());
+        }
+        if amm_pc_vault.close_authority.is_some() {
+            return Err(AmmError::InvalidCloseAuthority.into());
+        }
+        check_assert_eq!(
+            *amm_pc_mint_info.key,
+            amm_pc_vault.mint,
+            "pc_mint",
+            AmmError::InvalidPCMint
+        );
+
+        let lp_mint = crate::token_utils::unpack_mint(&amm_lp_mint_info, lp_token_program_id)?;
+        if lp_mint.supply != 0 {
+            return Err(AmmError::InvalidSupply.into());
+        }
+        if Some(*amm_authority_info.key) != lp_mint.mint_authority {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if lp_mint.freeze_authority.is_some() {
+            return Err(AmmError::InvalidFreezeAuthority.into());
+        }
+
+        // the curve is chosen at creation, a StableSwap pool starts without ramp and
+        // compares both sides at the decimals of their mints
+        let curve = if init.amp == 0 {
+            Curve::ConstantProduct
+        } else {
+            if init.amp < MIN_AMP || init.amp > MAX_AMP {
+                return Err(AmmError::InvalidAmp.into());
+            }
+            amm.coin_decimals = coin_mint.decimals.into();
+            amm.pc_decimals = pc_mint.decimals.into();
+            let amm_extension = amm_extension
+                .as_deref_mut()
+                .ok_or(AmmError::ExpectedAccount)?;
+            amm.set_amp_ramp(amm_extension, AmpRamp::fixed(init.amp));
+            amm.curve(Some(amm_extension), 0)?
+        };
+        let liquidity = curve.initial_liquidity(amm_coin_vault.amount, amm_pc_vault.amount)?;
+        let user_lp_amount = liquidity
+            .checked_sub((10u64).checked_pow(lp_mint.decimals.into()).unwrap())
+            .ok_or(AmmError::InitLpAmountTooLess)?;
+
+        crate::token_invokers::token_mint_to(
+            lp_token_program_info.clone(),
+            amm_lp_mint_info.clone(),
+            user_token_lp_info.clone(),
+            amm_authority_info.clone(),
+            user_lp_amount,
+            &[AUTHORITY_AMM, &[init.nonce]],
+        )?;
+
+        // there are no market lots, the smallest unit of each side is used
+        amm.initialize(
+            init.nonce,
+            init.open_time,
+            coin_mint.decimals,
+            pc_mint.decimals,
+            1,
+            1,
+        )?;
+        amm.set_token_programs(coin_token_program_id, pc_token_program_id);
+        amm.pool_flags |= POOL_FLAG_NO_ORDERBOOK;
+        if init.registry_hooks {
+            amm.pool_flags |= POOL_FLAG_REGISTRY_HOOKS;
+        }
+        amm.state_data.coin_mint_extensions = coin_mint_extensions;
+        amm.state_data.pc_mint_extensions = pc_mint_extensions;
+        encode_ray_log(InitLog {
+            log_type: LogType::Init.into_u8(),
+            time: init.open_time,
+            pc_decimals: amm.pc_decimals as u8,
+            coin_decimals: amm.coin_decimals as u8,
+            pc_lot_size: amm.pc_lot_size,
+            coin_lot_size: amm.coin_lot_size,
+            pc_amount: amm_pc_vault.amount,
+            coin_amount: amm_coin_vault.amount,
+            market: Pubkey::default(),
+        });
+
+        // market and open_orders stay default, target_orders too unless it keeps the
+        // amplification ramp of a StableSwap pool
+        amm.coin_vault = *amm_coin_vault_info.key;
+        amm.pc_vault = *amm_pc_vault_info.key;
+        amm.coin_vault_mint = *amm_coin_mint_info.key;
+        amm.pc_vault_mint = *amm_pc_mint_info.key;
+        amm.lp_mint = *amm_lp_mint_info.key;
+        Self::pin_hook_programs(&mut amm, amm_coin_mint_info, amm_pc_mint_info)?;
+        amm.amm_owner = config_feature::amm_owner::ID;
+        amm.lp_amount = liquidity;
+        amm.status = if init.open_time > (Clock::get()?.unix_timestamp as u64) {
+            AmmStatus::WaitingTrade.into_u64()
+        } else {
+            AmmStatus::SwapOnly.into_u64()
+        };
+        amm.reset_flag = AmmResetFlag::ResetYes.into_u64();
+
+        Ok(())
+    }
+
+    /// Processes an [Deposit](enum.Instruction.html).
+    pub fn process_deposit(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        deposit: DepositInstruction,
+    ) -> ProgramResult {
+        const ACCOUNT_LEN: usize = 14;
+        // a pool with a price oracle takes it as the very last account
+        let (accounts, price_oracle_info) = Self::split_price_oracle(program_id, accounts, 1)?;
+        // the token wrapper accounts of wrapped sides come before it
+        let (accounts, coin_wrap, pc_wrap) =
+            Self::split_wrap_accounts(program_id, accounts, &deposit.wrap_sides)?;
+        if accounts.len() < ACCOUNT_LEN {
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let coin_token_program_info = next_account_info(account_info_iter)?;
+
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_open_orders_info = next_account_info(account_info_iter)?;
+        let amm_target_orders_info = next_account_info(account_info_iter)?;
+        let amm_lp_mint_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+
+        let market_info = next_account_info(account_info_iter)?;
+
+        let user_source_coin_info = next_account_info(account_info_iter)?;
+        let user_source_pc_info = next_account_info(account_info_iter)?;
+        let user_dest_lp_info = next_account_info(account_info_iter)?;
+        let source_owner_info = next_account_info(account_info_iter)?;
+        let market_event_queue_info = next_account_info(account_info_iter)?;
+        // the pc side token program follows, then the mints and transfer hook accounts of the
+        // Token-2022 sides
+        let pc_token_program_info =
+            next_account_info(account_info_iter).unwrap_or(coin_token_program_info);
+        let transfer_accounts = account_info_iter.as_slice();
+        let (mut amm, mut amm_extension) =
+            AmmInfo::load_mut_checked_extended(&amm_info, program_id)?;
+        let coin_mint_info = transfer_accounts
+            .iter()
+            .find(|account| *account.key == amm.coin_vault_mint);
+        let pc_mint_info = transfer_accounts
+            .iter()
+            .find(|account| *account.key == amm.pc_vault_mint);
+        if deposit.max_coin_amount == 0 || deposit.max_pc_amount == 0 {
+            encode_ray_log(DepositLog {
+                log_type: LogType::Deposit.into_u8(),
+                max_coin: deposit.max_coin_amount,
+                max_pc: deposit.max_pc_amount,
+                base: deposit.base_side,
+                pool_coin: 0,
+                pool_pc: 0,
+                pool_lp: 0,
+                calc_pnl_x: 0,
+                calc_pnl_y: 0,
+                deduct_coin: 0,
+                deduct_pc: 0,
+                mint_lp: 0,
+            });
+            return Err(AmmError::InvalidInput.into());
+        }
+        if !source_owner_info.is_signer {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+
+        if !AmmStatus::from_u64(amm.status).deposit_permission() {
+            return Err(AmmError::InvalidStatus.into());
+        }
+        if Self::check_hook_programs(&mut amm, coin_mint_info, pc_mint_info)? {
+            msg!("deposit: transfer hook program changed, pool is withdraw only");
+            return Err(AmmError::HookProgramChanged.into());
+        }
+        if *amm_authority_info.key
+            != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
+        {
+            return Err(AmmError::InvalidProgramAddress.into());
+        }
+        let enable_orderbook;
+        if AmmStatus::from_u64(amm.status).orderbook_permission() {
+            enable_orderbook = true;
+        } else {
+            enable_orderbook = false;
+        }
+        Self::check_token_programs(&amm, coin_token_program_info, pc_token_program_info)?;
+        let coin_token_program_id = coin_token_program_info.key;
+        let pc_token_program_id = pc_token_program_info.key;
+        // token_coin must be amm.coin_vault or token_source_coin must not be amm.coin_vault
+        if *amm_coin_vault_info.key != amm.coin_vault
+            || *user_source_coin_info.key == amm.coin_vault
+        {
+            return Err(AmmError::InvalidCoinVault.into());
+        }
+        // token_pc must be amm.pc_vault or token_source_pc must not be amm.pc_vault
+        if *amm_pc_vault_info.key != amm.pc_vault || *user_source_pc_info.key == amm.pc_vault {
+            return Err(AmmError::InvalidPCVault.into());
+        }
+        check_assert_eq!(
+            *amm_lp_mint_info.key,
+            amm.lp_mint,
+            "lp_mint",
+            AmmError::InvalidPoolMint
+        );
+        check_assert_eq!(
+            *amm_target_orders_info.key,
+            amm.target_orders,
+            "target_orders",
+            AmmError::InvalidTargetOrders
+        );
+        let amm_coin_vault =
+            crate::token_utils::unpack_token_account(&amm_coin_vault_info, coin_token_program_id)?;
+        let amm_pc_vault =
+            crate::token_utils::unpack_token_account(&amm_pc_vault_info, pc_token_program_id)?;
+        // the user pays a wrapped side in its underlying mint
+        let (coin_user_mint_info, coin_user_token_program_id) =
+            Self::user_side_mint(coin_wrap.as_ref(), coin_mint_info, coin_token_program_info);
+        let (pc_user_mint_info, pc_user_token_program_id) =
+            Self::user_side_mint(pc_wrap.as_ref(), pc_mint_info, pc_token_program_info);
+        let user_source_coin = crate::token_utils::unpack_token_account(
+            &user_source_coin_info,
+            coin_user_token_program_id,
+        )?;
+        let user_source_pc = crate::token_utils::unpack_token_account(
+            &user_source_pc_info,
+            pc_user_token_program_id,
+        )?;
+        let user_coin_mint = coin_user_mint_info.map_or(amm.coin_vault_mint, |mint| *mint.key);
+        let user_pc_mint = pc_user_mint_info.map_or(amm.pc_vault_mint, |mint| *mint.key);
+        let mut target_orders =
             TargetOrders::load_mut_checked(&amm_target_orders_info, program_id, amm_info.key)?;
         // calc the remaining total_pc & total_coin
         let (mut total_pc_without_take_pnl, mut total_coin_without_take_pnl) = if enable_orderbook {
@@ -1402,13 +2578,14 @@ impl Processor {
                 &amm,
                 false,
             )?;
+            // the market lists the vault mints, the wrapper mint of a wrapped side
             if identity(market_state.coin_mint) != amm_coin_vault.mint.to_aligned_bytes()
-                || identity(market_state.coin_mint) != user_source_coin.mint.to_aligned_bytes()
+                || user_source_coin.mint != user_coin_mint
             {
                 return Err(AmmError::InvalidCoinMint.into());
             }
             if identity(market_state.pc_mint) != amm_pc_vault.mint.to_aligned_bytes()
-                || identity(market_state.pc_mint) != user_source_pc.mint.to_aligned_bytes()
+                || user_source_pc.mint != user_pc_mint
             {
                 return Err(AmmError::InvalidPCMint.into());
             }
@@ -1471,16 +2648,35 @@ impl Processor {
             });
             return Err(AmmError::NotAllowZeroLP.into());
         }
+        // deduct amounts are what arrives in the vaults, net of Token-2022 transfer fees
+        let epoch = Clock::get()?.epoch;
         let deduct_pc_amount;
         let deduct_coin_amount;
+        let coin_transfer_fee;
+        let pc_transfer_fee;
         let mint_lp_amount;
         if deposit.base_side == 0 {
             // base coin
+            coin_transfer_fee = Self::transfer_fee(
+                coin_user_mint_info,
+                coin_user_token_program_id,
+                epoch,
+                deposit.max_coin_amount,
+            )?;
+            deduct_coin_amount = deposit
+                .max_coin_amount
+                .checked_sub(coin_transfer_fee)
+                .ok_or(AmmError::InvalidInput)?;
             deduct_pc_amount = invariant
-                .exchange_coin_to_pc(deposit.max_coin_amount, RoundDirection::Ceiling)
+                .exchange_coin_to_pc(deduct_coin_amount, RoundDirection::Ceiling)
                 .ok_or(AmmError::CalculationExRateFailure)?;
-            deduct_coin_amount = deposit.max_coin_amount;
-            if deduct_pc_amount > deposit.max_pc_amount {
+            pc_transfer_fee = Self::transfer_inverse_fee(
+                pc_user_mint_info,
+                pc_user_token_program_id,
+                epoch,
+                deduct_pc_amount,
+            )?;
+            if deduct_pc_amount.saturating_add(pc_transfer_fee) > deposit.max_pc_amount {
                 encode_ray_log(DepositLog {
                     log_type: LogType::Deposit.into_u8(),
                     max_coin: deposit.max_coin_amount,
@@ -1527,11 +2723,26 @@ impl Processor {
                 .ok_or(AmmError::CalculationExRateFailure)?;
         } else {
             // base pc
+            pc_transfer_fee = Self::transfer_fee(
+                pc_user_mint_info,
+                pc_user_token_program_id,
+                epoch,
+                deposit.max_pc_amount,
+            )?;
+            deduct_pc_amount = deposit
+                .max_pc_amount
+                .checked_sub(pc_transfer_fee)
+                .ok_or(AmmError::InvalidInput)?;
             deduct_coin_amount = invariant
-                .exchange_pc_to_coin(deposit.max_pc_amount, RoundDirection::Ceiling)
+                .exchange_pc_to_coin(deduct_pc_amount, RoundDirection::Ceiling)
                 .ok_or(AmmError::CalculationExRateFailure)?;
-            deduct_pc_amount = deposit.max_pc_amount;
-            if deduct_coin_amount > deposit.max_coin_amount {
+            coin_transfer_fee = Self::transfer_inverse_fee(
+                coin_user_mint_info,
+                coin_user_token_program_id,
+                epoch,
+                deduct_coin_amount,
+            )?;
+            if deduct_coin_amount.saturating_add(coin_transfer_fee) > deposit.max_coin_amount {
                 encode_ray_log(DepositLog {
                     log_type: LogType::Deposit.into_u8(),
                     max_coin: deposit.max_coin_amount,
@@ -1593,7 +2804,15 @@ impl Processor {
             mint_lp: mint_lp_amount,
         });
 
-        if deduct_coin_amount > user_source_coin.amount || deduct_pc_amount > user_source_pc.amount
+        // the user sends the deduct amounts grossed up by the transfer fees
+        let transfer_coin_amount = deduct_coin_amount
+            .checked_add(coin_transfer_fee)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        let transfer_pc_amount = deduct_pc_amount
+            .checked_add(pc_transfer_fee)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        if transfer_coin_amount > user_source_coin.amount
+            || transfer_pc_amount > user_source_pc.amount
         {
             return Err(AmmError::InsufficientFunds.into());
         }
@@ -1601,28 +2820,97 @@ impl Processor {
             return Err(AmmError::InvalidInput.into());
         }
 
-        Invokers::token_transfer(
-            token_program_info.clone(),
-            user_source_coin_info.clone(),
-            amm_coin_vault_info.clone(),
-            source_owner_info.clone(),
-            deduct_coin_amount,
-        )?;
-        Invokers::token_transfer(
-            token_program_info.clone(),
-            user_source_pc_info.clone(),
-            amm_pc_vault_info.clone(),
-            source_owner_info.clone(),
-            deduct_pc_amount,
-        )?;
-        Invokers::token_mint_to(
-            token_program_info.clone(),
+        // resolve the transfer hook accounts of both transfers before any CPI, a wrapped side
+        // holds the wrapper mint, an Spl Token mint, in its vault and takes none
+        let (coin_mint_info, coin_hook_config, coin_hook_accounts, transfer_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                &amm.coin_vault_mint,
+                coin_token_program_id,
+                user_source_coin_info,
+                amm_coin_vault_info,
+                source_owner_info,
+                transfer_coin_amount,
+                transfer_accounts,
+                amm.accepts_registry_hooks(),
+            )?;
+        let (pc_mint_info, pc_hook_config, pc_hook_accounts, transfer_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                &amm.pc_vault_mint,
+                pc_token_program_id,
+                user_source_pc_info,
+                amm_pc_vault_info,
+                source_owner_info,
+                transfer_pc_amount,
+                transfer_accounts,
+                amm.accepts_registry_hooks(),
+            )?;
+        if !transfer_accounts.is_empty() {
+            return Err(AmmError::InvalidTransferHookAccounts.into());
+        }
+        amm.lock();
+        // a wrapped side is wrapped straight into the vault
+        match &coin_wrap {
+            Some(wrap) => Self::invoke_wrapper(
+                wrap,
+                false,
+                coin_token_program_info,
+                user_source_coin_info,
+                amm_coin_vault_info,
+                source_owner_info,
+                transfer_coin_amount,
+                &[],
+            )?,
+            None => {
+                crate::token_invokers::token_transfer_with_hook_budget(
+                    coin_hook_config.as_ref(),
+                    coin_token_program_info.clone(),
+                    user_source_coin_info.clone(),
+                    coin_mint_info.cloned(),
+                    amm_coin_vault_info.clone(),
+                    source_owner_info.clone(),
+                    coin_hook_accounts,
+                    transfer_coin_amount,
+                    amm.coin_decimals as u8,
+                    &[],
+                )?;
+            }
+        }
+        match &pc_wrap {
+            Some(wrap) => Self::invoke_wrapper(
+                wrap,
+                false,
+                pc_token_program_info,
+                user_source_pc_info,
+                amm_pc_vault_info,
+                source_owner_info,
+                transfer_pc_amount,
+                &[],
+            )?,
+            None => {
+                crate::token_invokers::token_transfer_with_hook_budget(
+                    pc_hook_config.as_ref(),
+                    pc_token_program_info.clone(),
+                    user_source_pc_info.clone(),
+                    pc_mint_info.cloned(),
+                    amm_pc_vault_info.clone(),
+                    source_owner_info.clone(),
+                    pc_hook_accounts,
+                    transfer_pc_amount,
+                    amm.pc_decimals as u8,
+                    &[],
+                )?;
+            }
+        }
+        // the lp mint shares the pc side token program
+        crate::token_invokers::token_mint_to(
+            pc_token_program_info.clone(),
             amm_lp_mint_info.clone(),
             user_dest_lp_info.clone(),
             amm_authority_info.clone(),
-            AUTHORITY_AMM,
-            amm.nonce as u8,
             mint_lp_amount,
+            &[AUTHORITY_AMM, &[amm.nonce as u8]],
         )?;
         amm.lp_amount = amm.lp_amount.checked_add(mint_lp_amount).unwrap();
 
@@ -1646,56 +2934,394 @@ impl Processor {
             .checked_sub(U128::from(delta_y))
             .unwrap()
             .as_u128();
+        Self::update_prices(
+            program_id,
+            &amm,
+            amm_extension.as_deref_mut(),
+            price_oracle_info,
+            total_coin_without_take_pnl
+                .checked_add(deduct_coin_amount)
+                .ok_or(AmmError::CheckedAddOverflow)?,
+            total_pc_without_take_pnl
+                .checked_add(deduct_pc_amount)
+                .ok_or(AmmError::CheckedAddOverflow)?,
+        )?;
         amm.recent_epoch = Clock::get()?.epoch;
+        amm.unlock();
         Ok(())
     }
 
-    pub fn process_withdrawpnl(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-        const ACCOUNT_LEN: usize = 17;
-        let input_account_len = accounts.len();
-        if input_account_len != ACCOUNT_LEN && input_account_len != ACCOUNT_LEN + 1 {
+    /// Processes a [DepositNoOrderbook](enum.Instruction.html).
+    pub fn process_deposit_no_orderbook(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        deposit: DepositInstruction,
+    ) -> ProgramResult {
+        const ACCOUNT_LEN: usize = 11;
+        // a pool with a price oracle takes it as the last account, after the transfer accounts
+        let (accounts, price_oracle_info) = Self::split_price_oracle(program_id, accounts, 2)?;
+        if accounts.len() < ACCOUNT_LEN {
             return Err(AmmError::WrongAccountsNumber.into());
         }
         let account_info_iter = &mut accounts.iter();
-        let token_program_info = next_account_info(account_info_iter)?;
+        let coin_token_program_info = next_account_info(account_info_iter)?;
+        let pc_token_program_info = next_account_info(account_info_iter)?;
 
         let amm_info = next_account_info(account_info_iter)?;
-        let amm_config_info = next_account_info(account_info_iter)?;
         let amm_authority_info = next_account_info(account_info_iter)?;
-        let amm_open_orders_info = next_account_info(account_info_iter)?;
+        let amm_lp_mint_info = next_account_info(account_info_iter)?;
         let amm_coin_vault_info = next_account_info(account_info_iter)?;
         let amm_pc_vault_info = next_account_info(account_info_iter)?;
-        let user_pnl_coin_info = next_account_info(account_info_iter)?;
-        let user_pnl_pc_info = next_account_info(account_info_iter)?;
-        let pnl_owner_info = next_account_info(account_info_iter)?;
-        let amm_target_orders_info = next_account_info(account_info_iter)?;
 
-        let market_program_info = next_account_info(account_info_iter)?;
-        let market_info = next_account_info(account_info_iter)?;
-        let market_event_queue_info = next_account_info(account_info_iter)?;
-        let _market_coin_vault_info = next_account_info(account_info_iter)?;
-        let _market_pc_vault_info = next_account_info(account_info_iter)?;
-        let _market_vault_signer = next_account_info(account_info_iter)?;
-        let mut _referrer_pc_wallet = None;
-        if input_account_len == ACCOUNT_LEN + 1 {
-            _referrer_pc_wallet = Some(next_account_info(account_info_iter)?);
-            let referrer_pc_token =
-                Self::unpack_token_account(&_referrer_pc_wallet.unwrap(), token_program_info.key)?;
-            check_assert_eq!(
-                referrer_pc_token.owner,
-                config_feature::referrer_pc_wallet::id(),
-                "referrer_pc_owner",
-                AmmError::InvalidOwner
-            );
+        let user_source_coin_info = next_account_info(account_info_iter)?;
+        let user_source_pc_info = next_account_info(account_info_iter)?;
+        let user_dest_lp_info = next_account_info(account_info_iter)?;
+        let source_owner_info = next_account_info(account_info_iter)?;
+        // mints and transfer hook accounts of the Token-2022 sides
+        let remaining_accounts = account_info_iter.as_slice();
+        let (mut amm, mut amm_extension) =
+            AmmInfo::load_mut_checked_extended(&amm_info, program_id)?;
+        if !amm.is_orderbook_free() {
+            return Err(AmmError::InvalidStatus.into());
+        }
+        if deposit.max_coin_amount == 0 || deposit.max_pc_amount == 0 {
+            return Err(AmmError::InvalidInput.into());
+        }
+        if !source_owner_info.is_signer {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        if !AmmStatus::from_u64(amm.status).deposit_permission() {
+            return Err(AmmError::InvalidStatus.into());
         }
-
-        let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
         if *amm_authority_info.key
             != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
         {
             return Err(AmmError::InvalidProgramAddress.into());
         }
-        if amm_info.owner != program_id {
+        Self::check_token_programs(&amm, coin_token_program_info, pc_token_program_info)?;
+        let coin_token_program_id = coin_token_program_info.key;
+        let pc_token_program_id = pc_token_program_info.key;
+        // token_coin must be amm.coin_vault or token_source_coin must not be amm.coin_vault
+        if *amm_coin_vault_info.key != amm.coin_vault
+            || *user_source_coin_info.key == amm.coin_vault
+        {
+            return Err(AmmError::InvalidCoinVault.into());
+        }
+        // token_pc must be amm.pc_vault or token_source_pc must not be amm.pc_vault
+        if *amm_pc_vault_info.key != amm.pc_vault || *user_source_pc_info.key == amm.pc_vault {
+            return Err(AmmError::InvalidPCVault.into());
+        }
+        check_assert_eq!(
+            *amm_lp_mint_info.key,
+            amm.lp_mint,
+            "lp_mint",
+            AmmError::InvalidPoolMint
+        );
+        let amm_coin_vault =
+            crate::token_utils::unpack_token_account(&amm_coin_vault_info, coin_token_program_id)?;
+        let amm_pc_vault =
+            crate::token_utils::unpack_token_account(&amm_pc_vault_info, pc_token_program_id)?;
+        let user_source_coin = crate::token_utils::unpack_token_account(
+            &user_source_coin_info,
+            coin_token_program_id,
+        )?;
+        let user_source_pc =
+            crate::token_utils::unpack_token_account(&user_source_pc_info, pc_token_program_id)?;
+        if user_source_coin.mint != amm.coin_vault_mint {
+            return Err(AmmError::InvalidCoinMint.into());
+        }
+        if user_source_pc.mint != amm.pc_vault_mint {
+            return Err(AmmError::InvalidPCMint.into());
+        }
+        // no pnl is taken, the swap fees stay in the vaults
+        let (total_pc, total_coin) = Calculator::calc_total_without_take_pnl_no_orderbook(
+            amm_pc_vault.amount,
+            amm_coin_vault.amount,
+            &amm,
+        )?;
+        let invariant = InvariantToken {
+            token_coin: total_coin,
+            token_pc: total_pc,
+        };
+        if amm.lp_amount == 0 {
+            return Err(AmmError::NotAllowZeroLP.into());
+        }
+        let coin_mint_info = remaining_accounts
+            .iter()
+            .find(|account| *account.key == amm.coin_vault_mint);
+        let pc_mint_info = remaining_accounts
+            .iter()
+            .find(|account| *account.key == amm.pc_vault_mint);
+        if Self::check_hook_programs(&mut amm, coin_mint_info, pc_mint_info)? {
+            msg!("deposit: transfer hook program changed, pool is withdraw only");
+            return Err(AmmError::HookProgramChanged.into());
+        }
+        // deduct amounts are what arrives in the vaults, net of Token-2022 transfer fees
+        let epoch = Clock::get()?.epoch;
+        let deduct_pc_amount;
+        let deduct_coin_amount;
+        let coin_transfer_fee;
+        let pc_transfer_fee;
+        let mint_lp_amount;
+        if deposit.base_side == 0 {
+            // base coin
+            coin_transfer_fee = Self::transfer_fee(
+                coin_mint_info,
+                coin_token_program_id,
+                epoch,
+                deposit.max_coin_amount,
+            )?;
+            deduct_coin_amount = deposit
+                .max_coin_amount
+                .checked_sub(coin_transfer_fee)
+                .ok_or(AmmError::InvalidInput)?;
+            deduct_pc_amount = invariant
+                .exchange_coin_to_pc(deduct_coin_amount, RoundDirection::Ceiling)
+                .ok_or(AmmError::CalculationExRateFailure)?;
+            pc_transfer_fee = Self::transfer_inverse_fee(
+                pc_mint_info,
+                pc_token_program_id,
+                epoch,
+                deduct_pc_amount,
+            )?;
+            if deduct_pc_amount.saturating_add(pc_transfer_fee) > deposit.max_pc_amount {
+                return Err(AmmError::ExceededSlippage.into());
+            }
+            // base coin, check other_amount_min if need
+            if let Some(other_amount_min) = deposit.other_amount_min {
+                if deduct_pc_amount < other_amount_min {
+                    return Err(AmmError::ExceededSlippage.into());
+                }
+            }
+            let invariant_coin = InvariantPool {
+                token_input: deduct_coin_amount,
+                token_total: total_coin,
+            };
+            mint_lp_amount = invariant_coin
+                .exchange_token_to_pool(amm.lp_amount, RoundDirection::Floor)
+                .ok_or(AmmError::CalculationExRateFailure)?;
+        } else {
+            // base pc
+            pc_transfer_fee = Self::transfer_fee(
+                pc_mint_info,
+                pc_token_program_id,
+                epoch,
+                deposit.max_pc_amount,
+            )?;
+            deduct_pc_amount = deposit
+                .max_pc_amount
+                .checked_sub(pc_transfer_fee)
+                .ok_or(AmmError::InvalidInput)?;
+            deduct_coin_amount = invariant
+                .exchange_pc_to_coin(deduct_pc_amount, RoundDirection::Ceiling)
+                .ok_or(AmmError::CalculationExRateFailure)?;
+            coin_transfer_fee = Self::transfer_inverse_fee(
+                coin_mint_info,
+                coin_token_program_id,
+                epoch,
+                deduct_coin_amount,
+            )?;
+            if deduct_coin_amount.saturating_add(coin_transfer_fee) > deposit.max_coin_amount {
+                return Err(AmmError::ExceededSlippage.into());
+            }
+            // base pc, check other_amount_min if need
+            if let Some(other_amount_min) = deposit.other_amount_min {
+                if deduct_coin_amount < other_amount_min {
+                    return Err(AmmError::ExceededSlippage.into());
+                }
+            }
+            let invariant_pc = InvariantPool {
+                token_input: deduct_pc_amount,
+                token_total: total_pc,
+            };
+            mint_lp_amount = invariant_pc
+                .exchange_token_to_pool(amm.lp_amount, RoundDirection::Floor)
+                .ok_or(AmmError::CalculationExRateFailure)?;
+        }
+        // a StableSwap pool mints by the growth of its invariant
+        let mint_lp_amount = amm
+            .curve(
+                amm_extension.as_deref(),
+                Clock::get()?.unix_timestamp as u64,
+            )?
+            .deposit_lp_amount(
+                deduct_coin_amount,
+                deduct_pc_amount,
+                total_coin,
+                total_pc,
+                amm.lp_amount,
+                mint_lp_amount,
+            )?;
+        encode_ray_log(DepositLog {
+            log_type: LogType::Deposit.into_u8(),
+            max_coin: deposit.max_coin_amount,
+            max_pc: deposit.max_pc_amount,
+            base: deposit.base_side,
+            pool_coin: total_coin,
+            pool_pc: total_pc,
+            pool_lp: amm.lp_amount,
+            calc_pnl_x: 0,
+            calc_pnl_y: 0,
+            deduct_coin: deduct_coin_amount,
+            deduct_pc: deduct_pc_amount,
+            mint_lp: mint_lp_amount,
+        });
+
+        // the user sends the deduct amounts grossed up by the transfer fees
+        let transfer_coin_amount = deduct_coin_amount
+            .checked_add(coin_transfer_fee)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        let transfer_pc_amount = deduct_pc_amount
+            .checked_add(pc_transfer_fee)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        if transfer_coin_amount > user_source_coin.amount
+            || transfer_pc_amount > user_source_pc.amount
+        {
+            return Err(AmmError::InsufficientFunds.into());
+        }
+        if mint_lp_amount == 0 || deduct_coin_amount == 0 || deduct_pc_amount == 0 {
+            return Err(AmmError::InvalidInput.into());
+        }
+
+        // resolve the transfer hook accounts of both transfers before any CPI
+        let (coin_mint_info, coin_hook_config, coin_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                &amm.coin_vault_mint,
+                coin_token_program_id,
+                user_source_coin_info,
+                amm_coin_vault_info,
+                source_owner_info,
+                transfer_coin_amount,
+                remaining_accounts,
+                amm.accepts_registry_hooks(),
+            )?;
+        let (pc_mint_info, pc_hook_config, pc_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                &amm.pc_vault_mint,
+                pc_token_program_id,
+                user_source_pc_info,
+                amm_pc_vault_info,
+                source_owner_info,
+                transfer_pc_amount,
+                remaining_accounts,
+                amm.accepts_registry_hooks(),
+            )?;
+        if !remaining_accounts.is_empty() {
+            return Err(AmmError::InvalidTransferHookAccounts.into());
+        }
+        amm.lock();
+        crate::token_invokers::token_transfer_with_hook_budget(
+            coin_hook_config.as_ref(),
+            coin_token_program_info.clone(),
+            user_source_coin_info.clone(),
+            coin_mint_info.cloned(),
+            amm_coin_vault_info.clone(),
+            source_owner_info.clone(),
+            coin_hook_accounts,
+            transfer_coin_amount,
+            amm.coin_decimals as u8,
+            &[],
+        )?;
+        crate::token_invokers::token_transfer_with_hook_budget(
+            pc_hook_config.as_ref(),
+            pc_token_program_info.clone(),
+            user_source_pc_info.clone(),
+            pc_mint_info.cloned(),
+            amm_pc_vault_info.clone(),
+            source_owner_info.clone(),
+            pc_hook_accounts,
+            transfer_pc_amount,
+            amm.pc_decimals as u8,
+            &[],
+        )?;
+        // the lp mint shares the pc side token program
+        crate::token_invokers::token_mint_to(
+            pc_token_program_info.clone(),
+            amm_lp_mint_info.clone(),
+            user_dest_lp_info.clone(),
+            amm_authority_info.clone(),
+            mint_lp_amount,
+            &[AUTHORITY_AMM, &[amm.nonce as u8]],
+        )?;
+        amm.lp_amount = amm.lp_amount.checked_add(mint_lp_amount).unwrap();
+        Self::update_prices(
+            program_id,
+            &amm,
+            amm_extension.as_deref_mut(),
+            price_oracle_info,
+            total_coin
+                .checked_add(deduct_coin_amount)
+                .ok_or(AmmError::CheckedAddOverflow)?,
+            total_pc
+                .checked_add(deduct_pc_amount)
+                .ok_or(AmmError::CheckedAddOverflow)?,
+        )?;
+        amm.recent_epoch = Clock::get()?.epoch;
+        amm.unlock();
+        Ok(())
+    }
+
+    pub fn process_withdrawpnl(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        const ACCOUNT_LEN: usize = 17;
+        let input_account_len = accounts.len();
+        if input_account_len < ACCOUNT_LEN {
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let coin_token_program_info = next_account_info(account_info_iter)?;
+
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_config_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_open_orders_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+        let user_pnl_coin_info = next_account_info(account_info_iter)?;
+        let user_pnl_pc_info = next_account_info(account_info_iter)?;
+        let pnl_owner_info = next_account_info(account_info_iter)?;
+        let amm_target_orders_info = next_account_info(account_info_iter)?;
+
+        let market_program_info = next_account_info(account_info_iter)?;
+        let market_info = next_account_info(account_info_iter)?;
+        let market_event_queue_info = next_account_info(account_info_iter)?;
+        let _market_coin_vault_info = next_account_info(account_info_iter)?;
+        let _market_pc_vault_info = next_account_info(account_info_iter)?;
+        let _market_vault_signer = next_account_info(account_info_iter)?;
+
+        let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
+        // a pool with a Token-2022 side takes the pc side token program next, then the transfer
+        // accounts of each Token-2022 side
+        let pc_token_program_info = if amm.has_token_2022_side() {
+            next_account_info(account_info_iter)?
+        } else {
+            if input_account_len != ACCOUNT_LEN && input_account_len != ACCOUNT_LEN + 1 {
+                return Err(AmmError::WrongAccountsNumber.into());
+            }
+            coin_token_program_info
+        };
+        let mut _referrer_pc_wallet = None;
+        if !amm.has_token_2022_side() && input_account_len == ACCOUNT_LEN + 1 {
+            _referrer_pc_wallet = Some(next_account_info(account_info_iter)?);
+            let referrer_pc_token = crate::token_utils::unpack_token_account(
+                &_referrer_pc_wallet.unwrap(),
+                pc_token_program_info.key,
+            )?;
+            check_assert_eq!(
+                referrer_pc_token.owner,
+                config_feature::referrer_pc_wallet::id(),
+                "referrer_pc_owner",
+                AmmError::InvalidOwner
+            );
+        }
+        let transfer_accounts = account_info_iter.as_slice();
+        if *amm_authority_info.key
+            != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
+        {
+            return Err(AmmError::InvalidProgramAddress.into());
+        }
+        if amm_info.owner != program_id {
             return Err(AmmError::InvalidOwner.into());
         }
         let enable_orderbook;
@@ -1722,12 +3348,35 @@ impl Processor {
             msg!(&format!("withdrawpnl: status {}", identity(amm.status)));
             return Err(AmmError::InvalidStatus.into());
         }
-        check_assert_eq!(
-            *market_info.key,
-            amm.market,
-            "market",
-            AmmError::InvalidMarket
-        );
+        // a pool without orderbook only has the protocol share of its swap fees to take,
+        // it passes the AMM Account in place of the target orders and market accounts
+        let orderbook_free = amm.is_orderbook_free();
+        if !orderbook_free {
+            check_assert_eq!(
+                *market_info.key,
+                amm.market,
+                "market",
+                AmmError::InvalidMarket
+            );
+            check_assert_eq!(
+                *market_program_info.key,
+                amm.market_program,
+                "market_program",
+                AmmError::InvalidMarketProgram
+            );
+            check_assert_eq!(
+                *amm_open_orders_info.key,
+                amm.open_orders,
+                "open_orders",
+                AmmError::InvalidOpenOrders
+            );
+            check_assert_eq!(
+                *amm_target_orders_info.key,
+                amm.target_orders,
+                "target_orders",
+                AmmError::InvalidTargetOrders
+            );
+        }
         check_assert_eq!(
             *amm_coin_vault_info.key,
             amm.coin_vault,
@@ -1740,38 +3389,26 @@ impl Processor {
             "pc_vault",
             AmmError::InvalidPCVault
         );
-        check_assert_eq!(
-            *token_program_info.key,
-            spl_token::id(),
-            "spl_token_program",
-            AmmError::InvalidSplTokenProgram
-        );
-        let spl_token_program_id = token_program_info.key;
-        check_assert_eq!(
-            *market_program_info.key,
-            amm.market_program,
-            "market_program",
-            AmmError::InvalidMarketProgram
-        );
-        check_assert_eq!(
-            *amm_open_orders_info.key,
-            amm.open_orders,
-            "open_orders",
-            AmmError::InvalidOpenOrders
-        );
-        check_assert_eq!(
-            *amm_target_orders_info.key,
-            amm.target_orders,
-            "target_orders",
-            AmmError::InvalidTargetOrders
-        );
+        Self::check_token_programs(&amm, coin_token_program_info, pc_token_program_info)?;
+        let coin_token_program_id = coin_token_program_info.key;
+        let pc_token_program_id = pc_token_program_info.key;
         let amm_coin_vault =
-            Self::unpack_token_account(&amm_coin_vault_info, spl_token_program_id)?;
-        let amm_pc_vault = Self::unpack_token_account(&amm_pc_vault_info, spl_token_program_id)?;
-        let user_pnl_coin = Self::unpack_token_account(&user_pnl_coin_info, spl_token_program_id)?;
-        let user_pnl_pc = Self::unpack_token_account(&user_pnl_pc_info, spl_token_program_id)?;
-        let mut target_orders =
-            TargetOrders::load_mut_checked(&amm_target_orders_info, program_id, amm_info.key)?;
+            crate::token_utils::unpack_token_account(&amm_coin_vault_info, coin_token_program_id)?;
+        let amm_pc_vault =
+            crate::token_utils::unpack_token_account(&amm_pc_vault_info, pc_token_program_id)?;
+        let user_pnl_coin =
+            crate::token_utils::unpack_token_account(&user_pnl_coin_info, coin_token_program_id)?;
+        let user_pnl_pc =
+            crate::token_utils::unpack_token_account(&user_pnl_pc_info, pc_token_program_id)?;
+        let mut target_orders = if orderbook_free {
+            None
+        } else {
+            Some(TargetOrders::load_mut_checked(
+                &amm_target_orders_info,
+                program_id,
+                amm_info.key,
+            )?)
+        };
         if amm_coin_vault.mint != amm.coin_vault_mint || user_pnl_coin.mint != amm.coin_vault_mint {
             return Err(AmmError::InvalidCoinMint.into());
         }
@@ -1856,50 +3493,91 @@ impl Processor {
         .as_str());
 
         // calc and update pnl
-        let (delta_x, delta_y) = Self::calc_take_pnl(
-            &target_orders,
-            &mut amm,
-            &mut total_pc_without_take_pnl,
-            &mut total_coin_without_take_pnl,
-            x1.as_u128().into(),
-            y1.as_u128().into(),
-        )?;
+        let (delta_x, delta_y) = match target_orders.as_ref() {
+            Some(target_orders) => Self::calc_take_pnl(
+                target_orders,
+                &mut amm,
+                &mut total_pc_without_take_pnl,
+                &mut total_coin_without_take_pnl,
+                x1.as_u128().into(),
+                y1.as_u128().into(),
+            )?,
+            None => (0, 0),
+        };
         msg!(arrform!(LOG_SIZE, "withdrawpnl total_pc:{}, total_pc:{}, delta_x:{}, delta_y:{}, need_take_coin:{}, need_take_pc:{}",total_pc_without_take_pnl, total_coin_without_take_pnl, delta_x, delta_y, identity(amm.state_data.need_take_pnl_coin), identity(amm.state_data.need_take_pnl_pc)).as_str());
 
         if amm.state_data.need_take_pnl_coin <= amm_coin_vault.amount
             && amm.state_data.need_take_pnl_pc <= amm_pc_vault.amount
         {
+            // resolve the transfer hook accounts of both transfers before any CPI
+            let (coin_mint_info, coin_hook_config, coin_hook_accounts, transfer_accounts) =
+                crate::whitelist::split_transfer_hook_accounts(
+                    program_id,
+                    &amm.coin_vault_mint,
+                    coin_token_program_id,
+                    amm_coin_vault_info,
+                    user_pnl_coin_info,
+                    amm_authority_info,
+                    amm.state_data.need_take_pnl_coin,
+                    transfer_accounts,
+                    amm.accepts_registry_hooks(),
+                )?;
+            let (pc_mint_info, pc_hook_config, pc_hook_accounts, transfer_accounts) =
+                crate::whitelist::split_transfer_hook_accounts(
+                    program_id,
+                    &amm.pc_vault_mint,
+                    pc_token_program_id,
+                    amm_pc_vault_info,
+                    user_pnl_pc_info,
+                    amm_authority_info,
+                    amm.state_data.need_take_pnl_pc,
+                    transfer_accounts,
+                    amm.accepts_registry_hooks(),
+                )?;
+            if !transfer_accounts.is_empty() {
+                return Err(AmmError::InvalidTransferHookAccounts.into());
+            }
             // coin & pc is enough, transfer directly
-            Invokers::token_transfer_with_authority(
-                token_program_info.clone(),
+            amm.lock();
+            crate::token_invokers::token_transfer_with_hook_budget(
+                coin_hook_config.as_ref(),
+                coin_token_program_info.clone(),
                 amm_coin_vault_info.clone(),
+                coin_mint_info.cloned(),
                 user_pnl_coin_info.clone(),
                 amm_authority_info.clone(),
-                AUTHORITY_AMM,
-                amm.nonce as u8,
+                coin_hook_accounts,
                 amm.state_data.need_take_pnl_coin,
+                amm.coin_decimals as u8,
+                &[AUTHORITY_AMM, &[amm.nonce as u8]],
             )?;
-            Invokers::token_transfer_with_authority(
-                token_program_info.clone(),
+            crate::token_invokers::token_transfer_with_hook_budget(
+                pc_hook_config.as_ref(),
+                pc_token_program_info.clone(),
                 amm_pc_vault_info.clone(),
+                pc_mint_info.cloned(),
                 user_pnl_pc_info.clone(),
                 amm_authority_info.clone(),
-                AUTHORITY_AMM,
-                amm.nonce as u8,
+                pc_hook_accounts,
                 amm.state_data.need_take_pnl_pc,
+                amm.pc_decimals as u8,
+                &[AUTHORITY_AMM, &[amm.nonce as u8]],
             )?;
             // clear need take pnl
             amm.state_data.need_take_pnl_coin = 0u64;
             amm.state_data.need_take_pnl_pc = 0u64;
             // update target_orders.calc_pnl_x & target_orders.calc_pnl_y
-            target_orders.calc_pnl_x = x1.checked_sub(U128::from(delta_x)).unwrap().as_u128();
-            target_orders.calc_pnl_y = y1.checked_sub(U128::from(delta_y)).unwrap().as_u128();
+            if let Some(target_orders) = target_orders.as_mut() {
+                target_orders.calc_pnl_x = x1.checked_sub(U128::from(delta_x)).unwrap().as_u128();
+                target_orders.calc_pnl_y = y1.checked_sub(U128::from(delta_y)).unwrap().as_u128();
+            }
         } else {
             // calc error
             return Err(AmmError::TakePnlError.into());
         }
         amm.recent_epoch = Clock::get()?.epoch;
 
+        amm.unlock();
         Ok(())
     }
 
@@ -1910,6 +3588,27 @@ impl Processor {
         withdraw: WithdrawInstruction,
     ) -> ProgramResult {
         const ACCOUNT_LEN: usize = 20;
+        // the token wrapper accounts of wrapped sides come last
+        let (accounts, coin_wrap, pc_wrap) =
+            Self::split_wrap_accounts(program_id, accounts, &withdraw.wrap_sides)?;
+        // a pool with a Token-2022 side, or a withdrawal that escrows or wraps a side, takes the
+        // referrer pc account and the pc side token program after the fixed accounts, then the
+        // escrow accounts and the transfer accounts of each Token-2022 side
+        let token_2022_layout = withdraw.escrow_sides != 0
+            || withdraw.wrap_sides != WrapSides::default()
+            || AmmInfo::load_checked(
+                accounts.get(1).ok_or(AmmError::WrongAccountsNumber)?,
+                program_id,
+            )?
+            .has_token_2022_side();
+        let (accounts, trailing_accounts) = if token_2022_layout {
+            if accounts.len() < ACCOUNT_LEN + 2 {
+                return Err(AmmError::WrongAccountsNumber.into());
+            }
+            accounts.split_at(ACCOUNT_LEN + 2)
+        } else {
+            (accounts, &[][..])
+        };
         let input_account_len = accounts.len();
         if input_account_len != ACCOUNT_LEN
             && input_account_len != ACCOUNT_LEN + 1
@@ -1918,8 +3617,17 @@ impl Processor {
         {
             return Err(AmmError::WrongAccountsNumber.into());
         }
+        let escrow_account_len = if withdraw.escrow_sides != 0 {
+            ESCROW_ACCOUNT_LEN
+        } else {
+            0
+        };
+        if trailing_accounts.len() < escrow_account_len {
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
+        let (escrow_accounts, transfer_accounts) = trailing_accounts.split_at(escrow_account_len);
         let account_info_iter = &mut accounts.iter();
-        let token_program_info = next_account_info(account_info_iter)?;
+        let coin_token_program_info = next_account_info(account_info_iter)?;
 
         let amm_info = next_account_info(account_info_iter)?;
         let amm_authority_info = next_account_info(account_info_iter)?;
@@ -1928,7 +3636,9 @@ impl Processor {
         let amm_lp_mint_info = next_account_info(account_info_iter)?;
         let amm_coin_vault_info = next_account_info(account_info_iter)?;
         let amm_pc_vault_info = next_account_info(account_info_iter)?;
-        if input_account_len == ACCOUNT_LEN + 2 || input_account_len == ACCOUNT_LEN + 3 {
+        if !token_2022_layout
+            && (input_account_len == ACCOUNT_LEN + 2 || input_account_len == ACCOUNT_LEN + 3)
+        {
             let _padding_account_info1 = next_account_info(account_info_iter)?;
             let _padding_account_info2 = next_account_info(account_info_iter)?;
         }
@@ -1949,20 +3659,32 @@ impl Processor {
         let market_asks_info = next_account_info(account_info_iter)?;
 
         let mut referrer_pc_wallet = None;
-        if input_account_len == ACCOUNT_LEN + 1 || input_account_len == ACCOUNT_LEN + 3 {
-            referrer_pc_wallet = Some(next_account_info(account_info_iter)?);
-            if *referrer_pc_wallet.unwrap().key != Pubkey::default() {
-                let referrer_pc_token = Self::unpack_token_account(
-                    &referrer_pc_wallet.unwrap(),
-                    token_program_info.key,
-                )?;
-                check_assert_eq!(
-                    referrer_pc_token.owner,
-                    config_feature::referrer_pc_wallet::id(),
-                    "referrer_pc_owner",
-                    AmmError::InvalidOwner
-                );
+        let pc_token_program_info = if token_2022_layout {
+            let referrer_pc_info = next_account_info(account_info_iter)?;
+            // the default pubkey stands for no referrer
+            if *referrer_pc_info.key != Pubkey::default() {
+                referrer_pc_wallet = Some(referrer_pc_info);
+            }
+            next_account_info(account_info_iter)?
+        } else {
+            if input_account_len == ACCOUNT_LEN + 1 || input_account_len == ACCOUNT_LEN + 3 {
+                referrer_pc_wallet = Some(next_account_info(account_info_iter)?);
             }
+            coin_token_program_info
+        };
+        if let Some(referrer_pc_info) =
+            referrer_pc_wallet.filter(|referrer_pc_info| *referrer_pc_info.key != Pubkey::default())
+        {
+            let referrer_pc_token = crate::token_utils::unpack_token_account(
+                referrer_pc_info,
+                pc_token_program_info.key,
+            )?;
+            check_assert_eq!(
+                referrer_pc_token.owner,
+                config_feature::referrer_pc_wallet::id(),
+                "referrer_pc_owner",
+                AmmError::InvalidOwner
+            );
         }
 
         if referrer_pc_wallet.is_none() {
@@ -1978,6 +3700,14 @@ impl Processor {
         if !AmmStatus::from_u64(amm.status).withdraw_permission() {
             return Err(AmmError::InvalidStatus.into());
         }
+        let coin_mint_info = transfer_accounts
+            .iter()
+            .find(|account| *account.key == amm.coin_vault_mint);
+        let pc_mint_info = transfer_accounts
+            .iter()
+            .find(|account| *account.key == amm.pc_vault_mint);
+        // a changed hook program pauses the pool, withdrawals go on
+        Self::check_hook_programs(&mut amm, coin_mint_info, pc_mint_info)?;
         if *amm_authority_info.key
             != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
         {
@@ -1989,13 +3719,25 @@ impl Processor {
         } else {
             enable_orderbook = false;
         }
-        check_assert_eq!(
-            *token_program_info.key,
-            spl_token::id(),
-            "spl_token_program",
-            AmmError::InvalidSplTokenProgram
-        );
-        let spl_token_program_id = token_program_info.key;
+        Self::check_token_programs(&amm, coin_token_program_info, pc_token_program_info)?;
+        let coin_token_program_id = coin_token_program_info.key;
+        let pc_token_program_id = pc_token_program_info.key;
+        let escrow = Self::load_withdraw_escrow(
+            program_id,
+            &amm,
+            amm_info.key,
+            withdraw.escrow_sides,
+            escrow_accounts,
+        )?;
+        // an escrowed side is transferred to its escrow token account instead of the user
+        let coin_destination_info = escrow
+            .as_ref()
+            .and_then(|escrow| escrow.coin_escrow_info)
+            .unwrap_or(user_dest_coin_info);
+        let pc_destination_info = escrow
+            .as_ref()
+            .and_then(|escrow| escrow.pc_escrow_info)
+            .unwrap_or(user_dest_pc_info);
         // token_coin must be amm.coin_vault or token_dest_coin must not be amm.coin_vault
         if *amm_coin_vault_info.key != amm.coin_vault || *user_dest_coin_info.key == amm.coin_vault
         {
@@ -2019,15 +3761,27 @@ impl Processor {
         );
 
         let amm_coin_vault =
-            Self::unpack_token_account(&amm_coin_vault_info, spl_token_program_id)?;
-        let amm_pc_vault = Self::unpack_token_account(&amm_pc_vault_info, spl_token_program_id)?;
-        let user_dest_coin =
-            Self::unpack_token_account(&user_dest_coin_info, spl_token_program_id)?;
-        let user_dest_pc = Self::unpack_token_account(&user_dest_pc_info, spl_token_program_id)?;
+            crate::token_utils::unpack_token_account(&amm_coin_vault_info, coin_token_program_id)?;
+        let amm_pc_vault =
+            crate::token_utils::unpack_token_account(&amm_pc_vault_info, pc_token_program_id)?;
+        // the user receives a wrapped side in its underlying mint
+        let (coin_user_mint_info, coin_user_token_program_id) =
+            Self::user_side_mint(coin_wrap.as_ref(), None, coin_token_program_info);
+        let (pc_user_mint_info, pc_user_token_program_id) =
+            Self::user_side_mint(pc_wrap.as_ref(), None, pc_token_program_info);
+        let user_dest_coin = crate::token_utils::unpack_token_account(
+            &user_dest_coin_info,
+            coin_user_token_program_id,
+        )?;
+        let user_dest_pc =
+            crate::token_utils::unpack_token_account(&user_dest_pc_info, pc_user_token_program_id)?;
+        let user_coin_mint = coin_user_mint_info.map_or(amm.coin_vault_mint, |mint| *mint.key);
+        let user_pc_mint = pc_user_mint_info.map_or(amm.pc_vault_mint, |mint| *mint.key);
 
-        let lp_mint = Self::unpack_mint(&amm_lp_mint_info, spl_token_program_id)?;
+        // the lp mint shares the pc side token program
+        let lp_mint = crate::token_utils::unpack_mint(&amm_lp_mint_info, pc_token_program_id)?;
         let user_source_lp =
-            Self::unpack_token_account(&user_source_lp_info, spl_token_program_id)?;
+            crate::token_utils::unpack_token_account(&user_source_lp_info, pc_token_program_id)?;
         if user_source_lp.mint != *amm_lp_mint_info.key {
             return Err(AmmError::InvalidTokenLP.into());
         }
@@ -2114,19 +3868,20 @@ impl Processor {
                 amm_coin_vault_info.clone(),
                 amm_pc_vault_info.clone(),
                 market_vault_signer.clone(),
-                token_program_info.clone(),
+                coin_token_program_info.clone(),
                 referrer_pc_wallet.clone(),
                 AUTHORITY_AMM,
                 amm.nonce as u8,
             )?;
 
+            // the market lists the vault mints, the wrapper mint of a wrapped side
             if identity(market_state.coin_mint) != amm_coin_vault.mint.to_aligned_bytes()
-                || identity(market_state.coin_mint) != user_dest_coin.mint.to_aligned_bytes()
+                || user_dest_coin.mint != user_coin_mint
             {
                 return Err(AmmError::InvalidCoinMint.into());
             }
             if identity(market_state.pc_mint) != amm_pc_vault.mint.to_aligned_bytes()
-                || identity(market_state.pc_mint) != user_dest_pc.mint.to_aligned_bytes()
+                || user_dest_pc.mint != user_pc_mint
             {
                 return Err(AmmError::InvalidPCMint.into());
             }
@@ -2201,37 +3956,127 @@ impl Processor {
         }
 
         if coin_amount < amm_coin_vault.amount && pc_amount < amm_pc_vault.amount {
+            // resolve the transfer hook accounts of both transfers before any CPI
+            let (coin_mint_info, coin_hook_config, coin_hook_accounts, transfer_accounts) =
+                crate::whitelist::split_transfer_hook_accounts(
+                    program_id,
+                    &amm.coin_vault_mint,
+                    coin_token_program_id,
+                    amm_coin_vault_info,
+                    coin_destination_info,
+                    amm_authority_info,
+                    coin_amount,
+                    transfer_accounts,
+                    amm.accepts_registry_hooks(),
+                )?;
+            let (pc_mint_info, pc_hook_config, pc_hook_accounts, transfer_accounts) =
+                crate::whitelist::split_transfer_hook_accounts(
+                    program_id,
+                    &amm.pc_vault_mint,
+                    pc_token_program_id,
+                    amm_pc_vault_info,
+                    pc_destination_info,
+                    amm_authority_info,
+                    pc_amount,
+                    transfer_accounts,
+                    amm.accepts_registry_hooks(),
+                )?;
+            if !transfer_accounts.is_empty() {
+                return Err(AmmError::InvalidTransferHookAccounts.into());
+            }
+            // the escrow of a wrapper withholds the transfer fee of its underlying mint
+            let (coin_fee_mint_info, coin_fee_token_program_id) =
+                Self::user_side_mint(coin_wrap.as_ref(), coin_mint_info, coin_token_program_info);
+            let (pc_fee_mint_info, pc_fee_token_program_id) =
+                Self::user_side_mint(pc_wrap.as_ref(), pc_mint_info, pc_token_program_info);
+            let (coin_received, pc_received) = Self::withdraw_received_amounts(
+                coin_fee_mint_info,
+                coin_fee_token_program_id,
+                coin_amount,
+                pc_fee_mint_info,
+                pc_fee_token_program_id,
+                pc_amount,
+            )?;
             if withdraw.min_coin_amount.is_some() && withdraw.min_pc_amount.is_some() {
-                if withdraw.min_coin_amount.unwrap() > coin_amount
-                    || withdraw.min_pc_amount.unwrap() > pc_amount
+                if withdraw.min_coin_amount.unwrap() > coin_received
+                    || withdraw.min_pc_amount.unwrap() > pc_received
                 {
                     return Err(AmmError::ExceededSlippage.into());
                 }
             }
-            Invokers::token_transfer_with_authority(
-                token_program_info.clone(),
-                amm_coin_vault_info.clone(),
-                user_dest_coin_info.clone(),
-                amm_authority_info.clone(),
-                AUTHORITY_AMM,
-                amm.nonce as u8,
-                coin_amount,
-            )?;
-            Invokers::token_transfer_with_authority(
-                token_program_info.clone(),
-                amm_pc_vault_info.clone(),
-                user_dest_pc_info.clone(),
-                amm_authority_info.clone(),
-                AUTHORITY_AMM,
-                amm.nonce as u8,
-                pc_amount,
-            )?;
-            Invokers::token_burn(
-                token_program_info.clone(),
+            if let Some(escrow) = &escrow {
+                Self::escrow_withdraw(
+                    program_id,
+                    escrow,
+                    amm_info,
+                    amm_authority_info,
+                    source_lp_owner_info,
+                    (coin_mint_info, coin_token_program_info, coin_received),
+                    (pc_mint_info, pc_token_program_info, pc_received),
+                )?;
+            }
+            amm.lock();
+            // a wrapped side is unwrapped straight from the vault, its wrapper mint is an
+            // Spl Token mint, so it is never escrowed
+            match &coin_wrap {
+                Some(wrap) => Self::invoke_wrapper(
+                    wrap,
+                    true,
+                    coin_token_program_info,
+                    coin_destination_info,
+                    amm_coin_vault_info,
+                    amm_authority_info,
+                    coin_amount,
+                    &[AUTHORITY_AMM, &[amm.nonce as u8]],
+                )?,
+                None => {
+                    crate::token_invokers::token_transfer_with_hook_budget(
+                        coin_hook_config.as_ref(),
+                        coin_token_program_info.clone(),
+                        amm_coin_vault_info.clone(),
+                        coin_mint_info.cloned(),
+                        coin_destination_info.clone(),
+                        amm_authority_info.clone(),
+                        coin_hook_accounts,
+                        coin_amount,
+                        amm.coin_decimals as u8,
+                        &[AUTHORITY_AMM, &[amm.nonce as u8]],
+                    )?;
+                }
+            }
+            match &pc_wrap {
+                Some(wrap) => Self::invoke_wrapper(
+                    wrap,
+                    true,
+                    pc_token_program_info,
+                    pc_destination_info,
+                    amm_pc_vault_info,
+                    amm_authority_info,
+                    pc_amount,
+                    &[AUTHORITY_AMM, &[amm.nonce as u8]],
+                )?,
+                None => {
+                    crate::token_invokers::token_transfer_with_hook_budget(
+                        pc_hook_config.as_ref(),
+                        pc_token_program_info.clone(),
+                        amm_pc_vault_info.clone(),
+                        pc_mint_info.cloned(),
+                        pc_destination_info.clone(),
+                        amm_authority_info.clone(),
+                        pc_hook_accounts,
+                        pc_amount,
+                        amm.pc_decimals as u8,
+                        &[AUTHORITY_AMM, &[amm.nonce as u8]],
+                    )?;
+                }
+            }
+            crate::token_invokers::token_burn(
+                pc_token_program_info.clone(),
                 user_source_lp_info.clone(),
                 amm_lp_mint_info.clone(),
                 source_lp_owner_info.clone(),
                 withdraw.amount,
+                &[],
             )?;
             amm.lp_amount = amm.lp_amount.checked_sub(withdraw.amount).unwrap();
         } else {
@@ -2261,848 +4106,1558 @@ impl Processor {
             .unwrap()
             .as_u128();
         amm.recent_epoch = Clock::get()?.epoch;
+        amm.unlock();
         Ok(())
     }
 
-    pub fn process_swap_base_in(
+    /// Checks the escrow accounts of a withdrawal with `escrow_sides` set, returns the escrow
+    /// token accounts of the escrowed sides
+    fn load_withdraw_escrow<'a, 'b>(
+        program_id: &Pubkey,
+        amm: &AmmInfo,
+        amm_key: &Pubkey,
+        escrow_sides: u8,
+        escrow_accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Option<WithdrawEscrow<'a, 'b>>, ProgramError> {
+        if escrow_sides == 0 {
+            return Ok(None);
+        }
+        if escrow_sides & !(WITHDRAW_ESCROW_COIN | WITHDRAW_ESCROW_PC) != 0 {
+            return Err(AmmError::InvalidInput.into());
+        }
+        let account_info_iter = &mut escrow_accounts.iter();
+        let system_program_info = next_account_info(account_info_iter)?;
+        let claim_info = next_account_info(account_info_iter)?;
+        let coin_escrow_info = next_account_info(account_info_iter)?;
+        let pc_escrow_info = next_account_info(account_info_iter)?;
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(AmmError::InvalidSysProgramAddress.into());
+        }
+        let coin_escrowed = escrow_sides & WITHDRAW_ESCROW_COIN != 0;
+        let pc_escrowed = escrow_sides & WITHDRAW_ESCROW_PC != 0;
+        // only a Token-2022 transfer can be refused by a transfer hook
+        if (coin_escrowed && amm.coin_token_program() != spl_token_2022::id())
+            || (pc_escrowed && amm.pc_token_program() != spl_token_2022::id())
+        {
+            msg!("Only a Token-2022 side can be escrowed");
+            return Err(AmmError::InvalidEscrowAccount.into());
+        }
+        if *coin_escrow_info.key
+            != escrow_token_address(program_id, amm_key, COIN_ESCROW_ASSOCIATED_SEED)
+            || *pc_escrow_info.key
+                != escrow_token_address(program_id, amm_key, PC_ESCROW_ASSOCIATED_SEED)
+        {
+            return Err(AmmError::InvalidEscrowAccount.into());
+        }
+        Ok(Some(WithdrawEscrow {
+            system_program_info,
+            claim_info,
+            coin_escrow_info: Some(coin_escrow_info).filter(|_| coin_escrowed),
+            pc_escrow_info: Some(pc_escrow_info).filter(|_| pc_escrowed),
+        }))
+    }
+
+    /// Credits the escrowed sides of a withdrawal to the claim of `owner_info`. Each side is
+    /// the mint, token program and amount that arrives net of the transfer fee. The escrow
+    /// token accounts and the claim are created on first use, paid by the owner.
+    fn escrow_withdraw<'a>(
+        program_id: &Pubkey,
+        escrow: &WithdrawEscrow<'_, 'a>,
+        amm_info: &AccountInfo<'a>,
+        amm_authority_info: &AccountInfo<'a>,
+        owner_info: &AccountInfo<'a>,
+        coin_side: (Option<&AccountInfo<'a>>, &AccountInfo<'a>, u64),
+        pc_side: (Option<&AccountInfo<'a>>, &AccountInfo<'a>, u64),
+    ) -> ProgramResult {
+        let mut escrowed_amounts = [0u64; 2];
+        for (index, escrow_info, associated_seed, (mint_info, token_program_info, amount)) in [
+            (
+                0,
+                escrow.coin_escrow_info,
+                COIN_ESCROW_ASSOCIATED_SEED,
+                coin_side,
+            ),
+            (1, escrow.pc_escrow_info, PC_ESCROW_ASSOCIATED_SEED, pc_side),
+        ] {
+            let escrow_info = match escrow_info {
+                Some(escrow_info) => escrow_info,
+                None => continue,
+            };
+            let mint_info = mint_info.ok_or(AmmError::InvalidEscrowAccount)?;
+            if escrow_info.owner == escrow.system_program_info.key {
+                Self::create_escrow_token_account(
+                    program_id,
+                    amm_info.key,
+                    associated_seed,
+                    escrow_info,
+                    mint_info,
+                    token_program_info,
+                    amm_authority_info,
+                    owner_info,
+                    escrow.system_program_info,
+                )?;
+            }
+            escrowed_amounts[index] = amount;
+        }
+        Self::credit_escrow_claim(
+            program_id,
+            amm_info.key,
+            escrow.claim_info,
+            owner_info,
+            escrow.system_program_info,
+            escrowed_amounts[0],
+            escrowed_amounts[1],
+        )
+    }
+
+    /// Creates the escrow token account of one side, a token account of the mint owned by
+    /// $authority at `escrow_token_address`
+    #[allow(clippy::too_many_arguments)]
+    fn create_escrow_token_account<'a>(
+        program_id: &Pubkey,
+        amm_key: &Pubkey,
+        associated_seed: &[u8],
+        escrow_info: &AccountInfo<'a>,
+        mint_info: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        amm_authority_info: &AccountInfo<'a>,
+        payer_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        let (_, bump_seed) =
+            get_associated_address_and_bump_seed(program_id, amm_key, associated_seed, program_id);
+        let escrow_signer_seeds: &[&[_]] = &[
+            &program_id.to_bytes(),
+            &amm_key.to_bytes(),
+            associated_seed,
+            &[bump_seed],
+        ];
+        let account_len =
+            crate::token_utils::get_account_len_for_mint(mint_info, token_program_info.key)?;
+        let required_lamports = Rent::get()?
+            .minimum_balance(account_len)
+            .max(1)
+            .saturating_sub(escrow_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(payer_info.key, escrow_info.key, required_lamports),
+                &[
+                    payer_info.clone(),
+                    escrow_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(escrow_info.key, account_len as u64),
+            &[escrow_info.clone(), system_program_info.clone()],
+            &[&escrow_signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(escrow_info.key, token_program_info.key),
+            &[escrow_info.clone(), system_program_info.clone()],
+            &[&escrow_signer_seeds],
+        )?;
+        invoke(
+            &spl_token_2022::instruction::initialize_account3(
+                token_program_info.key,
+                escrow_info.key,
+                mint_info.key,
+                amm_authority_info.key,
+            )?,
+            &[
+                escrow_info.clone(),
+                mint_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Adds the escrowed amounts to the `EscrowClaim` of `owner_info`, created on its first credit
+    fn credit_escrow_claim<'a>(
+        program_id: &Pubkey,
+        amm_key: &Pubkey,
+        claim_info: &AccountInfo<'a>,
+        owner_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        coin_amount: u64,
+        pc_amount: u64,
+    ) -> ProgramResult {
+        let (pda, bump_seed) = escrow_claim_address(program_id, amm_key, owner_info.key);
+        if pda != *claim_info.key {
+            return Err(AmmError::InvalidEscrowAccount.into());
+        }
+        if claim_info.owner == system_program_info.key {
+            let pda_signer_seeds: &[&[_]] = &[
+                ESCROW_CLAIM_SEED,
+                amm_key.as_ref(),
+                owner_info.key.as_ref(),
+                &[bump_seed],
+            ];
+            let data_size = size_of::<EscrowClaim>();
+            let required_lamports = Rent::get()?
+                .minimum_balance(data_size)
+                .saturating_sub(claim_info.lamports());
+            if required_lamports > 0 {
+                invoke(
+                    &system_instruction::transfer(
+                        owner_info.key,
+                        claim_info.key,
+                        required_lamports,
+                    ),
+                    &[
+                        owner_info.clone(),
+                        claim_info.clone(),
+                        system_program_info.clone(),
+                    ],
+                )?;
+            }
+            invoke_signed(
+                &system_instruction::allocate(claim_info.key, data_size as u64),
+                &[claim_info.clone(), system_program_info.clone()],
+                &[&pda_signer_seeds],
+            )?;
+            invoke_signed(
+                &system_instruction::assign(claim_info.key, &program_id),
+                &[claim_info.clone(), system_program_info.clone()],
+                &[&pda_signer_seeds],
+            )?;
+            *EscrowClaim::load_mut_checked(&claim_info, program_id)? = EscrowClaim {
+                amm: *amm_key,
+                owner: *owner_info.key,
+                bump: bump_seed as u64,
+                ..Default::default()
+            };
+        }
+        let mut claim = EscrowClaim::load_mut_checked(&claim_info, program_id)?;
+        claim.coin_amount = claim
+            .coin_amount
+            .checked_add(coin_amount)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        claim.pc_amount = claim
+            .pc_amount
+            .checked_add(pc_amount)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        msg!(
+            "Escrowed coin:{}, pc:{} for {}",
+            coin_amount,
+            pc_amount,
+            owner_info.key
+        );
+        Ok(())
+    }
+
+    /// Processes a [WithdrawNoOrderbook](enum.Instruction.html).
+    pub fn process_withdraw_no_orderbook(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        swap: SwapInstructionBaseIn,
+        withdraw: WithdrawInstruction,
     ) -> ProgramResult {
-        const ACCOUNT_LEN: usize = 17;
-        let input_account_len = accounts.len();
-        if input_account_len != ACCOUNT_LEN && input_account_len != ACCOUNT_LEN + 1 {
+        const ACCOUNT_LEN: usize = 11;
+        if accounts.len() < ACCOUNT_LEN {
             return Err(AmmError::WrongAccountsNumber.into());
         }
         let account_info_iter = &mut accounts.iter();
-        let token_program_info = next_account_info(account_info_iter)?;
+        let coin_token_program_info = next_account_info(account_info_iter)?;
+        let pc_token_program_info = next_account_info(account_info_iter)?;
 
         let amm_info = next_account_info(account_info_iter)?;
         let amm_authority_info = next_account_info(account_info_iter)?;
-        let amm_open_orders_info = next_account_info(account_info_iter)?;
-        if input_account_len == ACCOUNT_LEN + 1 {
-            let _amm_target_orders_info = next_account_info(account_info_iter)?;
-        }
+        let amm_lp_mint_info = next_account_info(account_info_iter)?;
         let amm_coin_vault_info = next_account_info(account_info_iter)?;
         let amm_pc_vault_info = next_account_info(account_info_iter)?;
 
-        let market_program_info = next_account_info(account_info_iter)?;
-
-        let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
-        let enable_orderbook;
-        if AmmStatus::from_u64(amm.status).orderbook_permission() {
-            enable_orderbook = true;
+        let user_source_lp_info = next_account_info(account_info_iter)?;
+        let user_dest_coin_info = next_account_info(account_info_iter)?;
+        let user_dest_pc_info = next_account_info(account_info_iter)?;
+        let source_lp_owner_info = next_account_info(account_info_iter)?;
+        // escrow accounts, then mints and transfer hook accounts of the Token-2022 sides
+        let remaining_accounts = account_info_iter.as_slice();
+        let escrow_account_len = if withdraw.escrow_sides != 0 {
+            ESCROW_ACCOUNT_LEN
         } else {
-            enable_orderbook = false;
-        }
-        let market_info = next_account_info(account_info_iter)?;
-        let market_bids_info = next_account_info(account_info_iter)?;
-        let market_asks_info = next_account_info(account_info_iter)?;
-        let market_event_queue_info = next_account_info(account_info_iter)?;
-        let market_coin_vault_info = next_account_info(account_info_iter)?;
-        let market_pc_vault_info = next_account_info(account_info_iter)?;
-        let market_vault_signer = next_account_info(account_info_iter)?;
-
-        let user_source_info = next_account_info(account_info_iter)?;
-        let user_destination_info = next_account_info(account_info_iter)?;
-        let user_source_owner = next_account_info(account_info_iter)?;
-        if !user_source_owner.is_signer {
+            0
+        };
+        let (escrow_accounts, remaining_accounts) =
+            remaining_accounts.split_at(escrow_account_len.min(remaining_accounts.len()));
+        if !source_lp_owner_info.is_signer {
             return Err(AmmError::InvalidSignAccount.into());
         }
-        check_assert_eq!(
-            *token_program_info.key,
-            spl_token::id(),
-            "spl_token_program",
-            AmmError::InvalidSplTokenProgram
-        );
-        let spl_token_program_id = token_program_info.key;
+        let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
+        if !amm.is_orderbook_free() {
+            return Err(AmmError::InvalidStatus.into());
+        }
+        if !AmmStatus::from_u64(amm.status).withdraw_permission() {
+            return Err(AmmError::InvalidStatus.into());
+        }
+        let coin_mint_info = remaining_accounts
+            .iter()
+            .find(|account| *account.key == amm.coin_vault_mint);
+        let pc_mint_info = remaining_accounts
+            .iter()
+            .find(|account| *account.key == amm.pc_vault_mint);
+        // a changed hook program pauses the pool, withdrawals go on
+        Self::check_hook_programs(&mut amm, coin_mint_info, pc_mint_info)?;
         if *amm_authority_info.key
             != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
         {
             return Err(AmmError::InvalidProgramAddress.into());
         }
+        Self::check_token_programs(&amm, coin_token_program_info, pc_token_program_info)?;
+        let coin_token_program_id = coin_token_program_info.key;
+        let pc_token_program_id = pc_token_program_info.key;
+        let escrow = Self::load_withdraw_escrow(
+            program_id,
+            &amm,
+            amm_info.key,
+            withdraw.escrow_sides,
+            escrow_accounts,
+        )?;
+        // an escrowed side is transferred to its escrow token account instead of the user
+        let coin_destination_info = escrow
+            .as_ref()
+            .and_then(|escrow| escrow.coin_escrow_info)
+            .unwrap_or(user_dest_coin_info);
+        let pc_destination_info = escrow
+            .as_ref()
+            .and_then(|escrow| escrow.pc_escrow_info)
+            .unwrap_or(user_dest_pc_info);
+        // token_coin must be amm.coin_vault or token_dest_coin must not be amm.coin_vault
+        if *amm_coin_vault_info.key != amm.coin_vault || *user_dest_coin_info.key == amm.coin_vault
+        {
+            return Err(AmmError::InvalidCoinVault.into());
+        }
+        // token_pc must be amm.pc_vault or token_dest_pc must not be amm.pc_vault
+        if *amm_pc_vault_info.key != amm.pc_vault || *user_dest_pc_info.key == amm.pc_vault {
+            return Err(AmmError::InvalidPCVault.into());
+        }
         check_assert_eq!(
-            *amm_coin_vault_info.key,
-            amm.coin_vault,
-            "coin_vault",
-            AmmError::InvalidCoinVault
-        );
-        check_assert_eq!(
-            *amm_pc_vault_info.key,
-            amm.pc_vault,
-            "pc_vault",
-            AmmError::InvalidPCVault
+            *amm_lp_mint_info.key,
+            amm.lp_mint,
+            "lp_mint",
+            AmmError::InvalidPoolMint
         );
 
-        if *user_source_info.key == amm.pc_vault || *user_source_info.key == amm.coin_vault {
-            return Err(AmmError::InvalidUserToken.into());
+        let amm_coin_vault =
+            crate::token_utils::unpack_token_account(&amm_coin_vault_info, coin_token_program_id)?;
+        let amm_pc_vault =
+            crate::token_utils::unpack_token_account(&amm_pc_vault_info, pc_token_program_id)?;
+        let user_dest_coin =
+            crate::token_utils::unpack_token_account(&user_dest_coin_info, coin_token_program_id)?;
+        let user_dest_pc =
+            crate::token_utils::unpack_token_account(&user_dest_pc_info, pc_token_program_id)?;
+        if user_dest_coin.mint != amm.coin_vault_mint {
+            return Err(AmmError::InvalidCoinMint.into());
         }
-        if *user_destination_info.key == amm.pc_vault
-            || *user_destination_info.key == amm.coin_vault
-        {
-            return Err(AmmError::InvalidUserToken.into());
+        if user_dest_pc.mint != amm.pc_vault_mint {
+            return Err(AmmError::InvalidPCMint.into());
         }
 
-        let amm_coin_vault =
-            Self::unpack_token_account(&amm_coin_vault_info, spl_token_program_id)?;
-        let amm_pc_vault = Self::unpack_token_account(&amm_pc_vault_info, spl_token_program_id)?;
-
-        let user_source = Self::unpack_token_account(&user_source_info, spl_token_program_id)?;
-        let user_destination =
-            Self::unpack_token_account(&user_destination_info, spl_token_program_id)?;
-
-        if !AmmStatus::from_u64(amm.status).swap_permission() {
-            msg!(&format!("swap_base_in: status {}", identity(amm.status)));
-            let clock = Clock::get()?;
-            if amm.status == AmmStatus::OrderBookOnly.into_u64()
-                && (clock.unix_timestamp as u64) >= amm.state_data.orderbook_to_init_time
-            {
-                amm.status = AmmStatus::Initialized.into_u64();
-                msg!("swap_base_in: OrderBook to Initialized");
-            } else {
-                return Err(AmmError::InvalidStatus.into());
-            }
-        } else if amm.status == AmmStatus::WaitingTrade.into_u64() {
-            let clock = Clock::get()?;
-            if (clock.unix_timestamp as u64) < amm.state_data.pool_open_time {
-                return Err(AmmError::InvalidStatus.into());
-            } else {
-                amm.status = AmmStatus::SwapOnly.into_u64();
-                msg!("swap_base_in: WaitingTrade to SwapOnly");
-            }
+        // the lp mint shares the pc side token program
+        let lp_mint = crate::token_utils::unpack_mint(&amm_lp_mint_info, pc_token_program_id)?;
+        let user_source_lp =
+            crate::token_utils::unpack_token_account(&user_source_lp_info, pc_token_program_id)?;
+        if user_source_lp.mint != *amm_lp_mint_info.key {
+            return Err(AmmError::InvalidTokenLP.into());
+        }
+        if withdraw.amount > user_source_lp.amount {
+            return Err(AmmError::InsufficientFunds.into());
+        }
+        if withdraw.amount > lp_mint.supply || withdraw.amount >= amm.lp_amount {
+            return Err(AmmError::NotAllowZeroLP.into());
         }
+        // no pnl is taken, the swap fees stay in the vaults
+        let (total_pc, total_coin) = Calculator::calc_total_without_take_pnl_no_orderbook(
+            amm_pc_vault.amount,
+            amm_coin_vault.amount,
+            &amm,
+        )?;
 
-        let total_pc_without_take_pnl;
-        let total_coin_without_take_pnl;
-        let mut bids: Vec<LeafNode> = Vec::new();
-        let mut asks: Vec<LeafNode> = Vec::new();
-        if enable_orderbook {
-            check_assert_eq!(
-                *amm_open_orders_info.key,
-                amm.open_orders,
-                "open_orders",
-                AmmError::InvalidOpenOrders
-            );
-            check_assert_eq!(
-                *market_program_info.key,
-                amm.market_program,
-                "market_program",
-                AmmError::InvalidMarketProgram
-            );
-            check_assert_eq!(
-                *market_info.key,
-                amm.market,
-                "market",
-                AmmError::InvalidMarket
-            );
-            let (market_state, open_orders) = Processor::load_serum_market_order(
-                market_info,
-                amm_open_orders_info,
+        // coin_amount / total_coin_amount = amount / lp_mint.supply => coin_amount = total_coin_amount * amount / pool_mint.supply
+        // sqrt(x * y) and the StableSwap D both scale linearly with the amounts, taking the
+        // same share of each side keeps the invariant per lp on either curve
+        let invariant = InvariantPool {
+            token_input: withdraw.amount,
+            token_total: amm.lp_amount,
+        };
+        let coin_amount = invariant
+            .exchange_pool_to_token(total_coin, RoundDirection::Floor)
+            .ok_or(AmmError::CalculationExRateFailure)?;
+        let pc_amount = invariant
+            .exchange_pool_to_token(total_pc, RoundDirection::Floor)
+            .ok_or(AmmError::CalculationExRateFailure)?;
+
+        encode_ray_log(WithdrawLog {
+            log_type: LogType::Withdraw.into_u8(),
+            withdraw_lp: withdraw.amount,
+            user_lp: user_source_lp.amount,
+            pool_coin: total_coin,
+            pool_pc: total_pc,
+            pool_lp: amm.lp_amount,
+            calc_pnl_x: 0,
+            calc_pnl_y: 0,
+            out_coin: coin_amount,
+            out_pc: pc_amount,
+        });
+        if withdraw.amount == 0 || coin_amount == 0 || pc_amount == 0 {
+            return Err(AmmError::InvalidInput.into());
+        }
+        if coin_amount >= amm_coin_vault.amount || pc_amount >= amm_pc_vault.amount {
+            return Err(AmmError::InsufficientFunds.into());
+        }
+
+        // resolve the transfer hook accounts of both transfers before any CPI
+        let (coin_mint_info, coin_hook_config, coin_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                &amm.coin_vault_mint,
+                coin_token_program_id,
+                amm_coin_vault_info,
+                coin_destination_info,
                 amm_authority_info,
-                &amm,
-                false,
+                coin_amount,
+                remaining_accounts,
+                amm.accepts_registry_hooks(),
             )?;
-            let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
-            let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
-            (bids, asks) = Self::get_amm_orders(&open_orders, bids_orders, asks_orders)?;
-            (total_pc_without_take_pnl, total_coin_without_take_pnl) =
-                Calculator::calc_total_without_take_pnl(
-                    amm_pc_vault.amount,
-                    amm_coin_vault.amount,
-                    &open_orders,
-                    &amm,
-                    &market_state,
-                    &market_event_queue_info,
-                    &amm_open_orders_info,
-                )?;
-        } else {
-            (total_pc_without_take_pnl, total_coin_without_take_pnl) =
-                Calculator::calc_total_without_take_pnl_no_orderbook(
-                    amm_pc_vault.amount,
-                    amm_coin_vault.amount,
-                    &amm,
-                )?;
+        let (pc_mint_info, pc_hook_config, pc_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                &amm.pc_vault_mint,
+                pc_token_program_id,
+                amm_pc_vault_info,
+                pc_destination_info,
+                amm_authority_info,
+                pc_amount,
+                remaining_accounts,
+                amm.accepts_registry_hooks(),
+            )?;
+        if !remaining_accounts.is_empty() {
+            return Err(AmmError::InvalidTransferHookAccounts.into());
+        }
+        let (coin_received, pc_received) = Self::withdraw_received_amounts(
+            coin_mint_info,
+            coin_token_program_id,
+            coin_amount,
+            pc_mint_info,
+            pc_token_program_id,
+            pc_amount,
+        )?;
+        if let (Some(min_coin_amount), Some(min_pc_amount)) =
+            (withdraw.min_coin_amount, withdraw.min_pc_amount)
+        {
+            if min_coin_amount > coin_received || min_pc_amount > pc_received {
+                return Err(AmmError::ExceededSlippage.into());
+            }
         }
+        if let Some(escrow) = &escrow {
+            Self::escrow_withdraw(
+                program_id,
+                escrow,
+                amm_info,
+                amm_authority_info,
+                source_lp_owner_info,
+                (coin_mint_info, coin_token_program_info, coin_received),
+                (pc_mint_info, pc_token_program_info, pc_received),
+            )?;
+        }
+        amm.lock();
+        crate::token_invokers::token_transfer_with_hook_budget(
+            coin_hook_config.as_ref(),
+            coin_token_program_info.clone(),
+            amm_coin_vault_info.clone(),
+            coin_mint_info.cloned(),
+            coin_destination_info.clone(),
+            amm_authority_info.clone(),
+            coin_hook_accounts,
+            coin_amount,
+            amm.coin_decimals as u8,
+            &[AUTHORITY_AMM, &[amm.nonce as u8]],
+        )?;
+        crate::token_invokers::token_transfer_with_hook_budget(
+            pc_hook_config.as_ref(),
+            pc_token_program_info.clone(),
+            amm_pc_vault_info.clone(),
+            pc_mint_info.cloned(),
+            pc_destination_info.clone(),
+            amm_authority_info.clone(),
+            pc_hook_accounts,
+            pc_amount,
+            amm.pc_decimals as u8,
+            &[AUTHORITY_AMM, &[amm.nonce as u8]],
+        )?;
+        crate::token_invokers::token_burn(
+            pc_token_program_info.clone(),
+            user_source_lp_info.clone(),
+            amm_lp_mint_info.clone(),
+            source_lp_owner_info.clone(),
+            withdraw.amount,
+            &[],
+        )?;
+        amm.lp_amount = amm.lp_amount.checked_sub(withdraw.amount).unwrap();
+        amm.recent_epoch = Clock::get()?.epoch;
+        amm.unlock();
+        Ok(())
+    }
 
-        let swap_direction;
-        if user_source.mint == amm_coin_vault.mint && user_destination.mint == amm_pc_vault.mint {
-            swap_direction = SwapDirection::Coin2PC
-        } else if user_source.mint == amm_pc_vault.mint
-            && user_destination.mint == amm_coin_vault.mint
+    /// Checks the pool trades and lets `permission` through, for the swap of a single sided
+    /// deposit or withdrawal
+    fn check_single_sided_status(amm: &AmmInfo, permission: bool) -> ProgramResult {
+        let status = AmmStatus::from_u64(amm.status);
+        if !permission || !status.swap_permission() {
+            return Err(AmmError::InvalidStatus.into());
+        }
+        if amm.status == AmmStatus::WaitingTrade.into_u64()
+            && (Clock::get()?.unix_timestamp as u64) < amm.state_data.pool_open_time
         {
-            swap_direction = SwapDirection::PC2Coin
+            return Err(AmmError::InvalidStatus.into());
+        }
+        Ok(())
+    }
+
+    /// A Token-2022 side leads the remaining accounts with its mint, such as the other side of a
+    /// single sided deposit or withdrawal, which is not transferred but whose hook program is
+    /// checked. Returns the mint and the remaining accounts.
+    fn split_other_mint<'a, 'b>(
+        mint: &Pubkey,
+        token_program_id: &Pubkey,
+        accounts: &'b [AccountInfo<'a>],
+    ) -> Result<(Option<&'b AccountInfo<'a>>, &'b [AccountInfo<'a>]), ProgramError> {
+        if *token_program_id != spl_token_2022::id() {
+            return Ok((None, accounts));
+        }
+        match accounts.split_first() {
+            Some((mint_info, rest)) if *mint_info.key == *mint => Ok((Some(mint_info), rest)),
+            _ => Err(AmmError::InvalidTransferHookAccounts.into()),
+        }
+    }
+
+    /// Processes a [DepositSingle](enum.Instruction.html).
+    pub fn process_deposit_single(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        deposit: DepositSingleInstruction,
+    ) -> ProgramResult {
+        const ACCOUNT_LEN: usize = 10;
+        // a pool with a price oracle takes it as the last account, after the transfer accounts
+        let (accounts, price_oracle_info) = Self::split_price_oracle(program_id, accounts, 2)?;
+        if accounts.len() < ACCOUNT_LEN {
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let coin_token_program_info = next_account_info(account_info_iter)?;
+        let pc_token_program_info = next_account_info(account_info_iter)?;
+
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_lp_mint_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+
+        let user_source_info = next_account_info(account_info_iter)?;
+        let user_dest_lp_info = next_account_info(account_info_iter)?;
+        let source_owner_info = next_account_info(account_info_iter)?;
+        // mint of the other side and transfer accounts of the deposited side
+        let remaining_accounts = account_info_iter.as_slice();
+        let (mut amm, mut amm_extension) =
+            AmmInfo::load_mut_checked_extended(&amm_info, program_id)?;
+        if !amm.is_orderbook_free() {
+            return Err(AmmError::InvalidStatus.into());
+        }
+        if deposit.amount_in == 0 || deposit.side > 1 {
+            return Err(AmmError::InvalidInput.into());
+        }
+        if !source_owner_info.is_signer {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        Self::check_single_sided_status(
+            &amm,
+            AmmStatus::from_u64(amm.status).deposit_permission(),
+        )?;
+        if *amm_authority_info.key
+            != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
+        {
+            return Err(AmmError::InvalidProgramAddress.into());
+        }
+        Self::check_token_programs(&amm, coin_token_program_info, pc_token_program_info)?;
+        if *amm_coin_vault_info.key != amm.coin_vault || *user_source_info.key == amm.coin_vault {
+            return Err(AmmError::InvalidCoinVault.into());
+        }
+        if *amm_pc_vault_info.key != amm.pc_vault || *user_source_info.key == amm.pc_vault {
+            return Err(AmmError::InvalidPCVault.into());
+        }
+        check_assert_eq!(
+            *amm_lp_mint_info.key,
+            amm.lp_mint,
+            "lp_mint",
+            AmmError::InvalidPoolMint
+        );
+        // the deposited side is swapped into the other one
+        let (
+            swap_direction,
+            source_mint,
+            other_mint,
+            source_token_program_info,
+            other_token_program,
+            source_vault_info,
+        ) = if deposit.side == 0 {
+            (
+                SwapDirection::Coin2PC,
+                amm.coin_vault_mint,
+                amm.pc_vault_mint,
+                coin_token_program_info,
+                amm.pc_token_program(),
+                amm_coin_vault_info,
+            )
         } else {
+            (
+                SwapDirection::PC2Coin,
+                amm.pc_vault_mint,
+                amm.coin_vault_mint,
+                pc_token_program_info,
+                amm.coin_token_program(),
+                amm_pc_vault_info,
+            )
+        };
+        let source_decimals = match swap_direction {
+            SwapDirection::Coin2PC => amm.coin_decimals,
+            SwapDirection::PC2Coin => amm.pc_decimals,
+        };
+        let amm_coin_vault = crate::token_utils::unpack_token_account(
+            &amm_coin_vault_info,
+            coin_token_program_info.key,
+        )?;
+        let amm_pc_vault = crate::token_utils::unpack_token_account(
+            &amm_pc_vault_info,
+            pc_token_program_info.key,
+        )?;
+        let user_source = crate::token_utils::unpack_token_account(
+            &user_source_info,
+            source_token_program_info.key,
+        )?;
+        if user_source.mint != source_mint {
             return Err(AmmError::InvalidUserToken.into());
         }
-        if user_source.amount < swap.amount_in {
-            encode_ray_log(SwapBaseInLog {
-                log_type: LogType::SwapBaseIn.into_u8(),
-                amount_in: swap.amount_in,
-                minimum_out: swap.minimum_amount_out,
-                direction: swap_direction as u64,
-                user_source: user_source.amount,
-                pool_coin: total_coin_without_take_pnl,
-                pool_pc: total_pc_without_take_pnl,
-                out_amount: 0,
-            });
+        if deposit.amount_in > user_source.amount {
             return Err(AmmError::InsufficientFunds.into());
         }
-        let swap_fee = U128::from(swap.amount_in)
+        // no pnl is taken, the swap fees stay in the vaults
+        let (total_pc, total_coin) = Calculator::calc_total_without_take_pnl_no_orderbook(
+            amm_pc_vault.amount,
+            amm_coin_vault.amount,
+            &amm,
+        )?;
+        if amm.lp_amount == 0 {
+            return Err(AmmError::NotAllowZeroLP.into());
+        }
+        let (other_mint_info, remaining_accounts) =
+            Self::split_other_mint(&other_mint, &other_token_program, remaining_accounts)?;
+        // resolve the transfer hook accounts of the deposit before any CPI
+        let (source_mint_info, source_hook_config, source_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                &source_mint,
+                source_token_program_info.key,
+                user_source_info,
+                source_vault_info,
+                source_owner_info,
+                deposit.amount_in,
+                remaining_accounts,
+                amm.accepts_registry_hooks(),
+            )?;
+        if !remaining_accounts.is_empty() {
+            return Err(AmmError::InvalidTransferHookAccounts.into());
+        }
+        let (coin_mint_info, pc_mint_info) = match swap_direction {
+            SwapDirection::Coin2PC => (source_mint_info, other_mint_info),
+            SwapDirection::PC2Coin => (other_mint_info, source_mint_info),
+        };
+        if Self::check_hook_programs(&mut amm, coin_mint_info, pc_mint_info)? {
+            msg!("deposit_single: transfer hook program changed, pool is withdraw only");
+            return Err(AmmError::HookProgramChanged.into());
+        }
+
+        // only the amount net of the inbound transfer fee arrives in the vault
+        let transfer_fee_in = Self::transfer_fee(
+            source_mint_info,
+            source_token_program_info.key,
+            Clock::get()?.epoch,
+            deposit.amount_in,
+        )?;
+        let amount_in = deposit
+            .amount_in
+            .checked_sub(transfer_fee_in)
+            .ok_or(AmmError::InvalidInput)?;
+        let curve = amm.curve(
+            amm_extension.as_deref(),
+            Clock::get()?.unix_timestamp as u64,
+        )?;
+        let swap_amount_in = curve.single_sided_swap_amount(
+            amount_in,
+            total_pc,
+            total_coin,
+            swap_direction,
+            amm.fees.swap_fee_numerator,
+            amm.fees.swap_fee_denominator,
+        )?;
+        let swap_fee = U128::from(swap_amount_in)
             .checked_mul(amm.fees.swap_fee_numerator.into())
             .unwrap()
             .checked_ceil_div(amm.fees.swap_fee_denominator.into())
             .unwrap()
-            .0;
-        let swap_in_after_deduct_fee = U128::from(swap.amount_in).checked_sub(swap_fee).unwrap();
-        let swap_amount_out = Calculator::swap_token_amount_base_in(
-            swap_in_after_deduct_fee,
-            total_pc_without_take_pnl.into(),
-            total_coin_without_take_pnl.into(),
+            .0
+            .as_u64();
+        let swap_amount_out = curve
+            .swap_token_amount_base_in(
+                (swap_amount_in - swap_fee).into(),
+                total_pc.into(),
+                total_coin.into(),
+                swap_direction,
+            )?
+            .as_u64();
+        // without a referrer the LPs keep its share
+        let (lp_fee, protocol_fee, _) = amm
+            .fee_split(amm_extension.as_deref())
+            .unwrap_or_default()
+            .shares(swap_fee, false);
+        // the swap output stays in the pool as the other side of the deposit
+        let (swap_total_coin, swap_total_pc) = Self::totals_after_swap(
             swap_direction,
-        )
-        .as_u64();
-        encode_ray_log(SwapBaseInLog {
-            log_type: LogType::SwapBaseIn.into_u8(),
-            amount_in: swap.amount_in,
-            minimum_out: swap.minimum_amount_out,
-            direction: swap_direction as u64,
-            user_source: user_source.amount,
-            pool_coin: total_coin_without_take_pnl,
-            pool_pc: total_pc_without_take_pnl,
-            out_amount: swap_amount_out,
-        });
-        if swap_amount_out < swap.minimum_amount_out {
-            return Err(AmmError::ExceededSlippage.into());
-        }
-        if swap_amount_out == 0 || swap.amount_in == 0 {
-            return Err(AmmError::InvalidInput.into());
-        }
-
-        match swap_direction {
-            SwapDirection::Coin2PC => {
-                if swap_amount_out >= total_pc_without_take_pnl {
-                    return Err(AmmError::InsufficientFunds.into());
-                }
-
-                if enable_orderbook {
-                    // coin -> pc, need cancel buy order
-                    if !bids.is_empty() {
-                        let mut amm_order_ids_vec = Vec::new();
-                        let mut order_ids = [0u64; 8];
-                        let mut count = 0;
-                        // fetch cancel order ids{
-                        for order in bids.into_iter() {
-                            order_ids[count] = order.client_order_id();
-                            count += 1;
-                            if count == 8 {
-                                amm_order_ids_vec.push(order_ids);
-                                order_ids = [0u64; 8];
-                                count = 0;
-                            }
-                        }
-                        if count != 0 {
-                            amm_order_ids_vec.push(order_ids);
-                        }
-                        for ids in amm_order_ids_vec.iter() {
-                            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
-                                market_program_info.clone(),
-                                market_info.clone(),
-                                market_bids_info.clone(),
-                                market_asks_info.clone(),
-                                amm_open_orders_info.clone(),
-                                amm_authority_info.clone(),
-                                market_event_queue_info.clone(),
-                                AUTHORITY_AMM,
-                                amm.nonce as u8,
-                                *ids,
-                            )?;
-                        }
-                    }
-
-                    if swap_amount_out > amm_pc_vault.amount {
-                        // need settle funds
-                        Invokers::invoke_dex_settle_funds(
-                            market_program_info.clone(),
-                            market_info.clone(),
-                            amm_open_orders_info.clone(),
-                            amm_authority_info.clone(),
-                            market_coin_vault_info.clone(),
-                            market_pc_vault_info.clone(),
-                            amm_coin_vault_info.clone(),
-                            amm_pc_vault_info.clone(),
-                            market_vault_signer.clone(),
-                            token_program_info.clone(),
-                            Some(&amm_pc_vault_info.clone()),
-                            AUTHORITY_AMM,
-                            amm.nonce as u8,
-                        )?;
-                    }
-                }
-                // deposit source coin to amm_coin_vault
-                Invokers::token_transfer(
-                    token_program_info.clone(),
-                    user_source_info.clone(),
-                    amm_coin_vault_info.clone(),
-                    user_source_owner.clone(),
-                    swap.amount_in,
-                )?;
-                // withdraw amm_pc_vault to destination pc
-                Invokers::token_transfer_with_authority(
-                    token_program_info.clone(),
-                    amm_pc_vault_info.clone(),
-                    user_destination_info.clone(),
-                    amm_authority_info.clone(),
-                    AUTHORITY_AMM,
-                    amm.nonce as u8,
-                    swap_amount_out,
-                )?;
-                // update state_data data
-                amm.state_data.swap_coin_in_amount = amm
-                    .state_data
-                    .swap_coin_in_amount
-                    .checked_add(swap.amount_in.into())
-                    .unwrap();
-                amm.state_data.swap_pc_out_amount = amm
-                    .state_data
-                    .swap_pc_out_amount
-                    .checked_add(swap_amount_out.into())
-                    .unwrap();
-                // charge coin as swap fee
-                amm.state_data.swap_acc_coin_fee = amm
-                    .state_data
-                    .swap_acc_coin_fee
-                    .checked_add(swap_fee.as_u64())
-                    .unwrap();
-            }
-            SwapDirection::PC2Coin => {
-                if swap_amount_out >= total_coin_without_take_pnl {
-                    return Err(AmmError::InsufficientFunds.into());
-                }
-
-                if enable_orderbook {
-                    // pc -> coin, need cancel sell order
-                    if !asks.is_empty() {
-                        let mut amm_order_ids_vec = Vec::new();
-                        let mut order_ids = [0u64; 8];
-                        let mut count = 0;
-                        // fetch cancel order ids{
-                        for order in asks.into_iter() {
-                            order_ids[count] = order.client_order_id();
-                            count += 1;
-                            if count == 8 {
-                                amm_order_ids_vec.push(order_ids);
-                                order_ids = [0u64; 8];
-                                count = 0;
-                            }
-                        }
-                        if count != 0 {
-                            amm_order_ids_vec.push(order_ids);
-                        }
-                        for ids in amm_order_ids_vec.iter() {
-                            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
-                                market_program_info.clone(),
-                                market_info.clone(),
-                                market_bids_info.clone(),
-                                market_asks_info.clone(),
-                                amm_open_orders_info.clone(),
-                                amm_authority_info.clone(),
-                                market_event_queue_info.clone(),
-                                AUTHORITY_AMM,
-                                amm.nonce as u8,
-                                *ids,
-                            )?;
-                        }
-                    }
-
-                    if swap_amount_out > amm_coin_vault.amount {
-                        Invokers::invoke_dex_settle_funds(
-                            market_program_info.clone(),
-                            market_info.clone(),
-                            amm_open_orders_info.clone(),
-                            amm_authority_info.clone(),
-                            market_coin_vault_info.clone(),
-                            market_pc_vault_info.clone(),
-                            amm_coin_vault_info.clone(),
-                            amm_pc_vault_info.clone(),
-                            market_vault_signer.clone(),
-                            token_program_info.clone(),
-                            Some(&amm_pc_vault_info.clone()),
-                            AUTHORITY_AMM,
-                            amm.nonce as u8,
-                        )?;
-                    }
-                }
-                // deposit source pc to amm_pc_vault
-                Invokers::token_transfer(
-                    token_program_info.clone(),
-                    user_source_info.clone(),
-                    amm_pc_vault_info.clone(),
-                    user_source_owner.clone(),
-                    swap.amount_in,
-                )?;
-                // withdraw amm_coin_vault to destination coin
-                Invokers::token_transfer_with_authority(
-                    token_program_info.clone(),
-                    amm_coin_vault_info.clone(),
-                    user_destination_info.clone(),
-                    amm_authority_info.clone(),
-                    AUTHORITY_AMM,
-                    amm.nonce as u8,
-                    swap_amount_out,
-                )?;
-                // update state_data data
-                amm.state_data.swap_pc_in_amount = amm
-                    .state_data
-                    .swap_pc_in_amount
-                    .checked_add(swap.amount_in.into())
-                    .unwrap();
-                amm.state_data.swap_coin_out_amount = amm
-                    .state_data
-                    .swap_coin_out_amount
-                    .checked_add(swap_amount_out.into())
-                    .unwrap();
-                // charge pc as swap fee
-                amm.state_data.swap_acc_pc_fee = amm
-                    .state_data
-                    .swap_acc_pc_fee
-                    .checked_add(swap_fee.as_u64())
-                    .unwrap();
+            total_coin,
+            total_pc,
+            swap_amount_in - protocol_fee,
+            swap_amount_out,
+        )?;
+        let (deduct_coin_amount, deduct_pc_amount) = match swap_direction {
+            SwapDirection::Coin2PC => (amount_in - swap_amount_in, swap_amount_out),
+            SwapDirection::PC2Coin => (swap_amount_out, amount_in - swap_amount_in),
+        };
+        // the scarcer side bounds the share, the rounding dust of the other stays with the LPs
+        let lp_amount = amm.lp_amount;
+        let share_of = |token_input: u64, token_total: u64| {
+            InvariantPool {
+                token_input,
+                token_total,
             }
+            .exchange_token_to_pool(lp_amount, RoundDirection::Floor)
+            .ok_or(AmmError::CalculationExRateFailure)
         };
-        amm.recent_epoch = Clock::get()?.epoch;
+        let share_lp_amount = std::cmp::min(
+            share_of(deduct_coin_amount, swap_total_coin)?,
+            share_of(deduct_pc_amount, swap_total_pc)?,
+        );
+        // a StableSwap pool mints by the growth of its invariant
+        let mint_lp_amount = curve.deposit_lp_amount(
+            deduct_coin_amount,
+            deduct_pc_amount,
+            swap_total_coin,
+            swap_total_pc,
+            lp_amount,
+            share_lp_amount,
+        )?;
+        encode_ray_log(DepositSingleLog {
+            log_type: LogType::DepositSingle.into_u8(),
+            amount_in: deposit.amount_in,
+            minimum_lp: deposit.minimum_lp_amount,
+            side: deposit.side,
+            pool_coin: total_coin,
+            pool_pc: total_pc,
+            pool_lp: lp_amount,
+            swap_in: swap_amount_in,
+            swap_out: swap_amount_out,
+            deduct_coin: deduct_coin_amount,
+            deduct_pc: deduct_pc_amount,
+            mint_lp: mint_lp_amount,
+            transfer_fee_in,
+            lp_fee,
+            protocol_fee,
+        });
+        if mint_lp_amount < deposit.minimum_lp_amount {
+            return Err(AmmError::ExceededSlippage.into());
+        }
+        if mint_lp_amount == 0 || swap_amount_out == 0 {
+            return Err(AmmError::InvalidInput.into());
+        }
 
+        amm.lock();
+        // a hook reading the trade context of the mint sees the swap of the deposit
+        let trade_context_sides = [(&source_mint, source_hook_accounts), (&other_mint, &[][..])];
+        Self::write_trade_contexts(
+            program_id,
+            &TradeContext {
+                amm: *amm_info.key,
+                active: 1,
+                direction: swap_direction as u64,
+                amount_in: swap_amount_in,
+                amount_out: swap_amount_out,
+                coin_reserve: total_coin,
+                pc_reserve: total_pc,
+                ..Default::default()
+            },
+            trade_context_sides,
+        )?;
+        crate::token_invokers::token_transfer_with_hook_budget(
+            source_hook_config.as_ref(),
+            source_token_program_info.clone(),
+            user_source_info.clone(),
+            source_mint_info.cloned(),
+            source_vault_info.clone(),
+            source_owner_info.clone(),
+            source_hook_accounts,
+            deposit.amount_in,
+            source_decimals as u8,
+            &[],
+        )?;
+        Self::write_trade_contexts(program_id, &TradeContext::default(), trade_context_sides)?;
+        // the lp mint shares the pc side token program
+        crate::token_invokers::token_mint_to(
+            pc_token_program_info.clone(),
+            amm_lp_mint_info.clone(),
+            user_dest_lp_info.clone(),
+            amm_authority_info.clone(),
+            mint_lp_amount,
+            &[AUTHORITY_AMM, &[amm.nonce as u8]],
+        )?;
+        amm.lp_amount = amm.lp_amount.checked_add(mint_lp_amount).unwrap();
+        Self::accumulate_swap(
+            &mut amm,
+            swap_direction,
+            swap_amount_in,
+            swap_amount_out,
+            lp_fee,
+        );
+        Self::take_protocol_fee(&mut amm, swap_direction, protocol_fee)?;
+        Self::update_prices(
+            program_id,
+            &amm,
+            amm_extension.as_deref_mut(),
+            price_oracle_info,
+            swap_total_coin
+                .checked_add(deduct_coin_amount)
+                .ok_or(AmmError::CheckedAddOverflow)?,
+            swap_total_pc
+                .checked_add(deduct_pc_amount)
+                .ok_or(AmmError::CheckedAddOverflow)?,
+        )?;
+        amm.recent_epoch = Clock::get()?.epoch;
+        amm.unlock();
         Ok(())
     }
 
-    pub fn process_swap_base_out(
+    /// Processes a [WithdrawSingle](enum.Instruction.html).
+    pub fn process_withdraw_single(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        swap: SwapInstructionBaseOut,
+        withdraw: WithdrawSingleInstruction,
     ) -> ProgramResult {
-        const SWAP_ACCOUNT_NUM: usize = 17;
-        let input_account_len = accounts.len();
-        if input_account_len != SWAP_ACCOUNT_NUM && input_account_len != SWAP_ACCOUNT_NUM + 1 {
+        const ACCOUNT_LEN: usize = 10;
+        // the swap moves the pool price, a pool with a price oracle takes it as the last account
+        let (accounts, price_oracle_info) = Self::split_price_oracle(program_id, accounts, 2)?;
+        if accounts.len() < ACCOUNT_LEN {
             return Err(AmmError::WrongAccountsNumber.into());
         }
         let account_info_iter = &mut accounts.iter();
-        let token_program_info = next_account_info(account_info_iter)?;
+        let coin_token_program_info = next_account_info(account_info_iter)?;
+        let pc_token_program_info = next_account_info(account_info_iter)?;
 
         let amm_info = next_account_info(account_info_iter)?;
         let amm_authority_info = next_account_info(account_info_iter)?;
-        let amm_open_orders_info = next_account_info(account_info_iter)?;
-        if input_account_len == SWAP_ACCOUNT_NUM + 1 {
-            let _amm_target_orders_info = next_account_info(account_info_iter)?;
-        }
+        let amm_lp_mint_info = next_account_info(account_info_iter)?;
         let amm_coin_vault_info = next_account_info(account_info_iter)?;
         let amm_pc_vault_info = next_account_info(account_info_iter)?;
 
-        let market_program_info = next_account_info(account_info_iter)?;
-
-        let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
-        let enable_orderbook;
-        if AmmStatus::from_u64(amm.status).orderbook_permission() {
-            enable_orderbook = true;
+        let user_source_lp_info = next_account_info(account_info_iter)?;
+        let user_dest_info = next_account_info(account_info_iter)?;
+        let source_lp_owner_info = next_account_info(account_info_iter)?;
+        // mint of the other side and transfer accounts of the withdrawn side
+        let remaining_accounts = account_info_iter.as_slice();
+        if !source_lp_owner_info.is_signer {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        let (mut amm, mut amm_extension) =
+            AmmInfo::load_mut_checked_extended(&amm_info, program_id)?;
+        if !amm.is_orderbook_free() {
+            return Err(AmmError::InvalidStatus.into());
+        }
+        if withdraw.amount == 0 || withdraw.side > 1 {
+            return Err(AmmError::InvalidInput.into());
+        }
+        Self::check_single_sided_status(
+            &amm,
+            AmmStatus::from_u64(amm.status).withdraw_permission(),
+        )?;
+        // the other side is swapped into the withdrawn one
+        let (
+            swap_direction,
+            dest_mint,
+            other_mint,
+            dest_token_program_info,
+            other_token_program,
+            dest_vault_info,
+        ) = if withdraw.side == 0 {
+            (
+                SwapDirection::PC2Coin,
+                amm.coin_vault_mint,
+                amm.pc_vault_mint,
+                coin_token_program_info,
+                amm.pc_token_program(),
+                amm_coin_vault_info,
+            )
         } else {
-            enable_orderbook = false;
+            (
+                SwapDirection::Coin2PC,
+                amm.pc_vault_mint,
+                amm.coin_vault_mint,
+                pc_token_program_info,
+                amm.coin_token_program(),
+                amm_pc_vault_info,
+            )
+        };
+        let dest_decimals = match swap_direction {
+            SwapDirection::PC2Coin => amm.coin_decimals,
+            SwapDirection::Coin2PC => amm.pc_decimals,
+        };
+        let (other_mint_info, remaining_accounts) =
+            Self::split_other_mint(&other_mint, &other_token_program, remaining_accounts)?;
+        // the transfer accounts of the withdrawn side start with its mint
+        let dest_mint_info = remaining_accounts
+            .first()
+            .filter(|account| *account.key == dest_mint);
+        let (coin_mint_info, pc_mint_info) = match swap_direction {
+            SwapDirection::PC2Coin => (dest_mint_info, other_mint_info),
+            SwapDirection::Coin2PC => (other_mint_info, dest_mint_info),
+        };
+        if Self::check_hook_programs(&mut amm, coin_mint_info, pc_mint_info)? {
+            msg!("withdraw_single: transfer hook program changed, pool is withdraw only");
+            return Err(AmmError::HookProgramChanged.into());
         }
-
-        let market_info = next_account_info(account_info_iter)?;
-        let market_bids_info = next_account_info(account_info_iter)?;
-        let market_asks_info = next_account_info(account_info_iter)?;
-        let market_event_queue_info = next_account_info(account_info_iter)?;
-        let market_coin_vault_info = next_account_info(account_info_iter)?;
-        let market_pc_vault_info = next_account_info(account_info_iter)?;
-        let market_vault_signer = next_account_info(account_info_iter)?;
-
-        let user_source_info = next_account_info(account_info_iter)?;
-        let user_destination_info = next_account_info(account_info_iter)?;
-        let user_source_owner = next_account_info(account_info_iter)?;
-        if !user_source_owner.is_signer {
-            return Err(AmmError::InvalidSignAccount.into());
+        if *amm_authority_info.key
+            != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
+        {
+            return Err(AmmError::InvalidProgramAddress.into());
+        }
+        Self::check_token_programs(&amm, coin_token_program_info, pc_token_program_info)?;
+        if *amm_coin_vault_info.key != amm.coin_vault || *user_dest_info.key == amm.coin_vault {
+            return Err(AmmError::InvalidCoinVault.into());
+        }
+        if *amm_pc_vault_info.key != amm.pc_vault || *user_dest_info.key == amm.pc_vault {
+            return Err(AmmError::InvalidPCVault.into());
         }
-
         check_assert_eq!(
-            *token_program_info.key,
-            spl_token::id(),
-            "spl_token_program",
-            AmmError::InvalidSplTokenProgram
-        );
-        let spl_token_program_id = token_program_info.key;
-        let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
-        check_assert_eq!(
-            *amm_authority_info.key,
-            authority,
-            "authority",
-            AmmError::InvalidProgramAddress
-        );
-        check_assert_eq!(
-            *amm_coin_vault_info.key,
-            amm.coin_vault,
-            "coin_vault",
-            AmmError::InvalidCoinVault
-        );
-        check_assert_eq!(
-            *amm_pc_vault_info.key,
-            amm.pc_vault,
-            "pc_vault",
-            AmmError::InvalidPCVault
+            *amm_lp_mint_info.key,
+            amm.lp_mint,
+            "lp_mint",
+            AmmError::InvalidPoolMint
         );
 
-        if *user_source_info.key == amm.pc_vault || *user_source_info.key == amm.coin_vault {
+        let amm_coin_vault = crate::token_utils::unpack_token_account(
+            &amm_coin_vault_info,
+            coin_token_program_info.key,
+        )?;
+        let amm_pc_vault = crate::token_utils::unpack_token_account(
+            &amm_pc_vault_info,
+            pc_token_program_info.key,
+        )?;
+        let user_dest =
+            crate::token_utils::unpack_token_account(&user_dest_info, dest_token_program_info.key)?;
+        if user_dest.mint != dest_mint {
             return Err(AmmError::InvalidUserToken.into());
         }
-        if *user_destination_info.key == amm.pc_vault
-            || *user_destination_info.key == amm.coin_vault
-        {
-            return Err(AmmError::InvalidUserToken.into());
+        // the lp mint shares the pc side token program
+        let lp_mint =
+            crate::token_utils::unpack_mint(&amm_lp_mint_info, pc_token_program_info.key)?;
+        let user_source_lp = crate::token_utils::unpack_token_account(
+            &user_source_lp_info,
+            pc_token_program_info.key,
+        )?;
+        if user_source_lp.mint != *amm_lp_mint_info.key {
+            return Err(AmmError::InvalidTokenLP.into());
+        }
+        if withdraw.amount > user_source_lp.amount {
+            return Err(AmmError::InsufficientFunds.into());
+        }
+        if withdraw.amount > lp_mint.supply || withdraw.amount >= amm.lp_amount {
+            return Err(AmmError::NotAllowZeroLP.into());
         }
+        // no pnl is taken, the swap fees stay in the vaults
+        let (total_pc, total_coin) = Calculator::calc_total_without_take_pnl_no_orderbook(
+            amm_pc_vault.amount,
+            amm_coin_vault.amount,
+            &amm,
+        )?;
 
-        let amm_coin_vault =
-            Self::unpack_token_account(&amm_coin_vault_info, spl_token_program_id)?;
-        let amm_pc_vault = Self::unpack_token_account(&amm_pc_vault_info, spl_token_program_id)?;
+        // the lp amount is worth its share of each side, as in `WithdrawNoOrderbook`
+        let invariant = InvariantPool {
+            token_input: withdraw.amount,
+            token_total: amm.lp_amount,
+        };
+        let coin_amount = invariant
+            .exchange_pool_to_token(total_coin, RoundDirection::Floor)
+            .ok_or(AmmError::CalculationExRateFailure)?;
+        let pc_amount = invariant
+            .exchange_pool_to_token(total_pc, RoundDirection::Floor)
+            .ok_or(AmmError::CalculationExRateFailure)?;
+        // the share of the other side is swapped against the pool left after the burn,
+        // it never leaves the vault
+        let (burn_total_coin, burn_total_pc) = (total_coin - coin_amount, total_pc - pc_amount);
+        let (swap_amount_in, dest_amount) = match swap_direction {
+            SwapDirection::PC2Coin => (pc_amount, coin_amount),
+            SwapDirection::Coin2PC => (coin_amount, pc_amount),
+        };
+        let swap_fee = U128::from(swap_amount_in)
+            .checked_mul(amm.fees.swap_fee_numerator.into())
+            .unwrap()
+            .checked_ceil_div(amm.fees.swap_fee_denominator.into())
+            .unwrap()
+            .0
+            .as_u64();
+        let swap_amount_out = amm
+            .curve(
+                amm_extension.as_deref(),
+                Clock::get()?.unix_timestamp as u64,
+            )?
+            .swap_token_amount_base_in(
+                (swap_amount_in - swap_fee).into(),
+                burn_total_pc.into(),
+                burn_total_coin.into(),
+                swap_direction,
+            )?
+            .as_u64();
+        // without a referrer the LPs keep its share
+        let (lp_fee, protocol_fee, _) = amm
+            .fee_split(amm_extension.as_deref())
+            .unwrap_or_default()
+            .shares(swap_fee, false);
+        let amount_out = dest_amount
+            .checked_add(swap_amount_out)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        // the outbound fee is withheld from what the user receives
+        let transfer_fee_out = Self::transfer_fee(
+            dest_mint_info,
+            dest_token_program_info.key,
+            Clock::get()?.epoch,
+            amount_out,
+        )?;
+        encode_ray_log(WithdrawSingleLog {
+            log_type: LogType::WithdrawSingle.into_u8(),
+            withdraw_lp: withdraw.amount,
+            minimum_out: withdraw.minimum_amount_out,
+            side: withdraw.side,
+            user_lp: user_source_lp.amount,
+            pool_coin: total_coin,
+            pool_pc: total_pc,
+            pool_lp: amm.lp_amount,
+            out_coin: coin_amount,
+            out_pc: pc_amount,
+            swap_out: swap_amount_out,
+            out_amount: amount_out,
+            transfer_fee_out,
+            lp_fee,
+            protocol_fee,
+        });
+        if amount_out.saturating_sub(transfer_fee_out) < withdraw.minimum_amount_out {
+            return Err(AmmError::ExceededSlippage.into());
+        }
+        if dest_amount == 0 || swap_amount_out == 0 {
+            return Err(AmmError::InvalidInput.into());
+        }
+        let dest_vault_amount = match swap_direction {
+            SwapDirection::PC2Coin => amm_coin_vault.amount,
+            SwapDirection::Coin2PC => amm_pc_vault.amount,
+        };
+        if amount_out >= dest_vault_amount {
+            return Err(AmmError::InsufficientFunds.into());
+        }
+
+        // resolve the transfer hook accounts of the withdrawal before any CPI
+        let (dest_mint_info, dest_hook_config, dest_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                &dest_mint,
+                dest_token_program_info.key,
+                dest_vault_info,
+                user_dest_info,
+                amm_authority_info,
+                amount_out,
+                remaining_accounts,
+                amm.accepts_registry_hooks(),
+            )?;
+        if !remaining_accounts.is_empty() {
+            return Err(AmmError::InvalidTransferHookAccounts.into());
+        }
+        amm.lock();
+        // a hook reading the trade context of the mint sees the swap of the withdrawal
+        let trade_context_sides = [(&other_mint, &[][..]), (&dest_mint, dest_hook_accounts)];
+        Self::write_trade_contexts(
+            program_id,
+            &TradeContext {
+                amm: *amm_info.key,
+                active: 1,
+                direction: swap_direction as u64,
+                amount_in: swap_amount_in,
+                amount_out: swap_amount_out,
+                coin_reserve: burn_total_coin,
+                pc_reserve: burn_total_pc,
+                ..Default::default()
+            },
+            trade_context_sides,
+        )?;
+        crate::token_invokers::token_transfer_with_hook_budget(
+            dest_hook_config.as_ref(),
+            dest_token_program_info.clone(),
+            dest_vault_info.clone(),
+            dest_mint_info.cloned(),
+            user_dest_info.clone(),
+            amm_authority_info.clone(),
+            dest_hook_accounts,
+            amount_out,
+            dest_decimals as u8,
+            &[AUTHORITY_AMM, &[amm.nonce as u8]],
+        )?;
+        Self::write_trade_contexts(program_id, &TradeContext::default(), trade_context_sides)?;
+        crate::token_invokers::token_burn(
+            pc_token_program_info.clone(),
+            user_source_lp_info.clone(),
+            amm_lp_mint_info.clone(),
+            source_lp_owner_info.clone(),
+            withdraw.amount,
+            &[],
+        )?;
+        amm.lp_amount = amm.lp_amount.checked_sub(withdraw.amount).unwrap();
+        Self::accumulate_swap(
+            &mut amm,
+            swap_direction,
+            swap_amount_in,
+            swap_amount_out,
+            lp_fee,
+        );
+        Self::take_protocol_fee(&mut amm, swap_direction, protocol_fee)?;
+        let (total_coin, total_pc) = Self::totals_after_swap(
+            swap_direction,
+            burn_total_coin,
+            burn_total_pc,
+            swap_amount_in - protocol_fee,
+            swap_amount_out,
+        )?;
+        Self::update_prices(
+            program_id,
+            &amm,
+            amm_extension.as_deref_mut(),
+            price_oracle_info,
+            total_coin,
+            total_pc,
+        )?;
+        amm.recent_epoch = Clock::get()?.epoch;
+        amm.unlock();
+        Ok(())
+    }
 
-        let user_source = Self::unpack_token_account(&user_source_info, spl_token_program_id)?;
-        let user_destination =
-            Self::unpack_token_account(&user_destination_info, spl_token_program_id)?;
+    /// Split the swap transfer hook accounts into the mint, hook config and hook accounts of the
+    /// user -> vault transfer and those of the vault -> user transfer, and the rest,
+    /// see `whitelist::split_transfer_hook_accounts`
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    fn split_swap_hook_accounts<'a, 'b>(
+        program_id: &Pubkey,
+        amm: &AmmInfo,
+        swap_direction: SwapDirection,
+        user_source_info: &AccountInfo<'a>,
+        user_destination_info: &AccountInfo<'a>,
+        user_source_owner: &AccountInfo<'a>,
+        amm_authority_info: &AccountInfo<'a>,
+        amm_coin_vault_info: &AccountInfo<'a>,
+        amm_pc_vault_info: &AccountInfo<'a>,
+        amount_in: u64,
+        amount_out: u64,
+        remaining_accounts: &'b [AccountInfo<'a>],
+    ) -> Result<
+        (
+            (
+                Option<&'b AccountInfo<'a>>,
+                Option<HookConfig>,
+                &'b [AccountInfo<'a>],
+            ),
+            (
+                Option<&'b AccountInfo<'a>>,
+                Option<HookConfig>,
+                &'b [AccountInfo<'a>],
+            ),
+            &'b [AccountInfo<'a>],
+        ),
+        ProgramError,
+    > {
+        let (source_side, destination_side) = match swap_direction {
+            SwapDirection::Coin2PC => (
+                (amm_coin_vault_info, amm.coin_vault_mint, amm.coin_token_program()),
+                (amm_pc_vault_info, amm.pc_vault_mint, amm.pc_token_program()),
+            ),
+            SwapDirection::PC2Coin => (
+                (amm_pc_vault_info, amm.pc_vault_mint, amm.pc_token_program()),
+                (amm_coin_vault_info, amm.coin_vault_mint, amm.coin_token_program()),
+            ),
+        };
+        let (source_mint_info, source_hook_config, source_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                &source_side.1,
+                &source_side.2,
+                user_source_info,
+                source_side.0,
+                user_source_owner,
+                amount_in,
+                remaining_accounts,
+                amm.accepts_registry_hooks(),
+            )?;
+        let (
+            destination_mint_info,
+            destination_hook_config,
+            destination_hook_accounts,
+            remaining_accounts,
+        ) = crate::whitelist::split_transfer_hook_accounts(
+            program_id,
+            &destination_side.1,
+            &destination_side.2,
+            destination_side.0,
+            user_destination_info,
+            amm_authority_info,
+            amount_out,
+            remaining_accounts,
+            amm.accepts_registry_hooks(),
+        )?;
+        Ok((
+            (source_mint_info, source_hook_config, source_hook_accounts),
+            (
+                destination_mint_info,
+                destination_hook_config,
+                destination_hook_accounts,
+            ),
+            remaining_accounts,
+        ))
+    }
 
-        if !AmmStatus::from_u64(amm.status).swap_permission() {
-            msg!(&format!("swap_base_out: status {}", identity(amm.status)));
-            let clock = Clock::get()?;
-            if amm.status == AmmStatus::OrderBookOnly.into_u64()
-                && (clock.unix_timestamp as u64) >= amm.state_data.orderbook_to_init_time
-            {
-                amm.status = AmmStatus::Initialized.into_u64();
-                msg!("swap_base_out: OrderBook to Initialized");
-            } else {
-                return Err(AmmError::InvalidStatus.into());
+    /// Split the optional referrer of a swap off the accounts left after its transfer hook
+    /// accounts: the referrer token account of the source mint, then the transfer accounts of
+    /// the vault -> referrer transfer of `referrer_fee`, as `whitelist::split_transfer_hook_accounts`.
+    /// Returns the referrer token account, mint, hook config and hook accounts, `None` without
+    /// a referrer. Only a pool with a fee split takes one.
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    fn split_referrer_accounts<'a, 'b>(
+        program_id: &Pubkey,
+        amm: &AmmInfo,
+        amm_extension: Option<&AmmExtension>,
+        source_mint: &Pubkey,
+        source_token_program: &Pubkey,
+        source_vault_info: &AccountInfo<'a>,
+        amm_authority_info: &AccountInfo<'a>,
+        referrer_fee: u64,
+        remaining_accounts: &'b [AccountInfo<'a>],
+    ) -> Result<
+        Option<(
+            &'b AccountInfo<'a>,
+            Option<&'b AccountInfo<'a>>,
+            Option<HookConfig>,
+            &'b [AccountInfo<'a>],
+        )>,
+        ProgramError,
+    > {
+        let (referrer_info, remaining_accounts) = match remaining_accounts.split_first() {
+            Some(split) => split,
+            None => return Ok(None),
+        };
+        if amm.fee_split(amm_extension).is_none() {
+            return Err(AmmError::InvalidTransferHookAccounts.into());
+        }
+        if *referrer_info.key == amm.coin_vault || *referrer_info.key == amm.pc_vault {
+            return Err(AmmError::InvalidReferrer.into());
+        }
+        let referrer =
+            crate::token_utils::unpack_token_account(referrer_info, source_token_program)?;
+        if referrer.mint != *source_mint {
+            return Err(AmmError::InvalidReferrer.into());
+        }
+        let (mint_info, hook_config, hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                source_mint,
+                source_token_program,
+                source_vault_info,
+                referrer_info,
+                amm_authority_info,
+                referrer_fee,
+                remaining_accounts,
+                amm.accepts_registry_hooks(),
+            )?;
+        if !remaining_accounts.is_empty() {
+            return Err(AmmError::InvalidTransferHookAccounts.into());
+        }
+        Ok(Some((referrer_info, mint_info, hook_config, hook_accounts)))
+    }
+
+    /// Write the swap into the trade context of each side's mint whose hook reads it, source
+    /// side first, or clear it with a default `trade`. The price of each mint is in the other token.
+    fn write_trade_contexts(
+        program_id: &Pubkey,
+        trade: &TradeContext,
+        sides: [(&Pubkey, &[AccountInfo]); 2],
+    ) -> ProgramResult {
+        for (index, (mint, hook_accounts)) in sides.into_iter().enumerate() {
+            let trade_context_info =
+                match crate::whitelist::find_trade_context(program_id, mint, hook_accounts) {
+                    Some(trade_context_info) => trade_context_info,
+                    None => continue,
+                };
+            if !trade_context_info.is_writable {
+                return Err(AmmError::InvalidTradeContext.into());
             }
-        } else if amm.status == AmmStatus::WaitingTrade.into_u64() {
-            let clock = Clock::get()?;
-            if (clock.unix_timestamp as u64) < amm.state_data.pool_open_time {
-                return Err(AmmError::InvalidStatus.into());
+            let (amount, other_amount) = if index == 0 {
+                (trade.amount_in, trade.amount_out)
             } else {
-                amm.status = AmmStatus::SwapOnly.into_u64();
-                msg!("swap_base_out: WaitingTrade to SwapOnly");
-            }
+                (trade.amount_out, trade.amount_in)
+            };
+            let price_x64 = if amount == 0 {
+                0
+            } else {
+                ((other_amount as u128) << 64) / amount as u128
+            };
+            let mut trade_context = TradeContext::load_mut_checked(trade_context_info, program_id)?;
+            let (mint, bump) = (trade_context.mint, trade_context.bump);
+            *trade_context = TradeContext {
+                mint,
+                bump,
+                price_x64,
+                ..*trade
+            };
         }
+        Ok(())
+    }
 
-        let total_pc_without_take_pnl;
-        let total_coin_without_take_pnl;
-        let mut bids: Vec<LeafNode> = Vec::new();
-        let mut asks: Vec<LeafNode> = Vec::new();
-        if enable_orderbook {
-            check_assert_eq!(
-                *amm_open_orders_info.key,
-                amm.open_orders,
-                "open_orders",
-                AmmError::InvalidOpenOrders
+    /// Lay the accounts of a `SwapBaseInV2` or `SwapBaseOutV2` out as those of a
+    /// `SwapBaseIn` or `SwapBaseOut`, whose processing they share: the mint and hook
+    /// accounts of each Token-2022 side, source side first, then the pc side token program.
+    fn swap_v2_accounts<'a>(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo<'a>],
+        coin_hook_account_count: u8,
+        pc_hook_account_count: u8,
+    ) -> Result<Vec<AccountInfo<'a>>, ProgramError> {
+        const ACCOUNT_LEN: usize = 20;
+        let hook_account_len = coin_hook_account_count as usize + pc_hook_account_count as usize;
+        if accounts.len() < ACCOUNT_LEN + hook_account_len {
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let coin_token_program_info = next_account_info(account_info_iter)?;
+        let pc_token_program_info = next_account_info(account_info_iter)?;
+
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_open_orders_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+        let amm_coin_mint_info = next_account_info(account_info_iter)?;
+        let amm_pc_mint_info = next_account_info(account_info_iter)?;
+
+        let market_program_info = next_account_info(account_info_iter)?;
+        let market_info = next_account_info(account_info_iter)?;
+        let market_bids_info = next_account_info(account_info_iter)?;
+        let market_asks_info = next_account_info(account_info_iter)?;
+        let market_event_queue_info = next_account_info(account_info_iter)?;
+        let market_coin_vault_info = next_account_info(account_info_iter)?;
+        let market_pc_vault_info = next_account_info(account_info_iter)?;
+        let market_vault_signer = next_account_info(account_info_iter)?;
+
+        let user_source_info = next_account_info(account_info_iter)?;
+        let user_destination_info = next_account_info(account_info_iter)?;
+        let user_source_owner = next_account_info(account_info_iter)?;
+        let (coin_hook_accounts, rest) = account_info_iter
+            .as_slice()
+            .split_at(coin_hook_account_count as usize);
+        let (pc_hook_accounts, rest) = rest.split_at(pc_hook_account_count as usize);
+        let referrer_accounts;
+        let price_oracle_accounts;
+
+        {
+            let amm = AmmInfo::load_checked(amm_info, program_id)?;
+            // the referrer accounts of a fee split follow the hook accounts, as those of a
+            // `SwapBaseIn`, and a pool with a price oracle takes it last
+            (referrer_accounts, price_oracle_accounts) = rest.split_at(
+                rest.len()
+                    .checked_sub(amm.has_price_oracle() as usize)
+                    .ok_or(AmmError::WrongAccountsNumber)?,
             );
             check_assert_eq!(
-                *market_program_info.key,
-                amm.market_program,
-                "market_program",
-                AmmError::InvalidMarketProgram
+                *amm_coin_mint_info.key,
+                amm.coin_vault_mint,
+                "coin_mint",
+                AmmError::InvalidCoinMint
             );
             check_assert_eq!(
-                *market_info.key,
-                amm.market,
-                "market",
-                AmmError::InvalidMarket
+                *amm_pc_mint_info.key,
+                amm.pc_vault_mint,
+                "pc_mint",
+                AmmError::InvalidPCMint
             );
-            let (market_state, open_orders) = Processor::load_serum_market_order(
-                market_info,
-                amm_open_orders_info,
-                amm_authority_info,
-                &amm,
-                false,
-            )?;
-            let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
-            let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
-            (bids, asks) = Self::get_amm_orders(&open_orders, bids_orders, asks_orders)?;
-            (total_pc_without_take_pnl, total_coin_without_take_pnl) =
-                Calculator::calc_total_without_take_pnl(
-                    amm_pc_vault.amount,
-                    amm_coin_vault.amount,
-                    &open_orders,
-                    &amm,
-                    &market_state,
-                    &market_event_queue_info,
-                    &amm_open_orders_info,
-                )?;
+        }
+        // a Token-2022 transfer takes its mint and hook accounts, an SPL Token transfer none
+        let transfer_accounts = |token_program_info: &AccountInfo<'a>,
+                                 mint_info: &AccountInfo<'a>,
+                                 hook_accounts: &[AccountInfo<'a>]|
+         -> Result<Vec<AccountInfo<'a>>, ProgramError> {
+            if *token_program_info.key != spl_token_2022::id() {
+                if !hook_accounts.is_empty() {
+                    return Err(AmmError::InvalidTransferHookAccounts.into());
+                }
+                return Ok(Vec::new());
+            }
+            let mut accounts = vec![mint_info.clone()];
+            accounts.extend_from_slice(hook_accounts);
+            Ok(accounts)
+        };
+        let coin_transfer_accounts = transfer_accounts(
+            coin_token_program_info,
+            amm_coin_mint_info,
+            coin_hook_accounts,
+        )?;
+        let pc_transfer_accounts =
+            transfer_accounts(pc_token_program_info, amm_pc_mint_info, pc_hook_accounts)?;
+        let user_source =
+            crate::token_utils::unpack_token_account(user_source_info, user_source_info.owner)?;
+
+        let mut swap_accounts = vec![
+            coin_token_program_info.clone(),
+            amm_info.clone(),
+            amm_authority_info.clone(),
+            amm_open_orders_info.clone(),
+            amm_coin_vault_info.clone(),
+            amm_pc_vault_info.clone(),
+            market_program_info.clone(),
+            market_info.clone(),
+            market_bids_info.clone(),
+            market_asks_info.clone(),
+            market_event_queue_info.clone(),
+            market_coin_vault_info.clone(),
+            market_pc_vault_info.clone(),
+            market_vault_signer.clone(),
+            user_source_info.clone(),
+            user_destination_info.clone(),
+            user_source_owner.clone(),
+        ];
+        if user_source.mint == *amm_coin_mint_info.key {
+            swap_accounts.extend(coin_transfer_accounts);
+            swap_accounts.extend(pc_transfer_accounts);
         } else {
-            (total_pc_without_take_pnl, total_coin_without_take_pnl) =
-                Calculator::calc_total_without_take_pnl_no_orderbook(
-                    amm_pc_vault.amount,
-                    amm_coin_vault.amount,
-                    &amm,
-                )?;
+            swap_accounts.extend(pc_transfer_accounts);
+            swap_accounts.extend(coin_transfer_accounts);
+        }
+        swap_accounts.extend_from_slice(referrer_accounts);
+        if pc_token_program_info.key != coin_token_program_info.key {
+            swap_accounts.push(pc_token_program_info.clone());
         }
+        swap_accounts.extend_from_slice(price_oracle_accounts);
+        Ok(swap_accounts)
+    }
 
-        let swap_direction;
-        if user_source.mint == amm_coin_vault.mint && user_destination.mint == amm_pc_vault.mint {
-            swap_direction = SwapDirection::Coin2PC
-        } else if user_source.mint == amm_pc_vault.mint
-            && user_destination.mint == amm_coin_vault.mint
-        {
-            swap_direction = SwapDirection::PC2Coin
-        } else {
-            return Err(AmmError::InvalidUserToken.into());
+    /// Parse the accounts of a `SwapBaseInNoOrderbook` or `SwapBaseOutNoOrderbook`, whose pool
+    /// has no orderbook permission and so takes no market accounts.
+    #[allow(clippy::type_complexity)]
+    fn swap_no_orderbook_accounts<'a, 'b>(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<
+        (
+            SwapAccounts<'a, 'b>,
+            RefMut<'a, AmmInfo>,
+            Option<RefMut<'a, AmmExtension>>,
+        ),
+        ProgramError,
+    > {
+        // a pool with a price oracle takes it as the last account, after the transfer accounts
+        let (accounts, price_oracle_info) = Self::split_price_oracle(program_id, accounts, 2)?;
+        const ACCOUNT_LEN: usize = 9;
+        if accounts.len() < ACCOUNT_LEN {
+            return Err(AmmError::WrongAccountsNumber.into());
         }
+        let account_info_iter = &mut accounts.iter();
+        let coin_token_program_info = next_account_info(account_info_iter)?;
+        let pc_token_program_info = next_account_info(account_info_iter)?;
 
-        let swap_in_before_add_fee = Calculator::swap_token_amount_base_out(
-            swap.amount_out.into(),
-            total_pc_without_take_pnl.into(),
-            total_coin_without_take_pnl.into(),
-            swap_direction,
-        );
-        // swap_in_after_add_fee * (1 - 0.0025) = swap_in_before_add_fee
-        // swap_in_after_add_fee = swap_in_before_add_fee / (1 - 0.0025)
-        let swap_in_after_add_fee = swap_in_before_add_fee
-            .checked_mul(amm.fees.swap_fee_denominator.into())
-            .unwrap()
-            .checked_ceil_div(
-                (amm.fees
-                    .swap_fee_denominator
-                    .checked_sub(amm.fees.swap_fee_numerator)
-                    .unwrap())
-                .into(),
-            )
-            .unwrap()
-            .0
-            .as_u64();
-        let swap_fee = swap_in_after_add_fee
-            .checked_sub(swap_in_before_add_fee.as_u64())
-            .unwrap();
-        encode_ray_log(SwapBaseOutLog {
-            log_type: LogType::SwapBaseOut.into_u8(),
-            max_in: swap.max_amount_in,
-            amount_out: swap.amount_out,
-            direction: swap_direction as u64,
-            user_source: user_source.amount,
-            pool_coin: total_coin_without_take_pnl,
-            pool_pc: total_pc_without_take_pnl,
-            deduct_in: swap_in_after_add_fee,
-        });
-        if user_source.amount < swap_in_after_add_fee {
-            return Err(AmmError::InsufficientFunds.into());
-        }
-        if swap.max_amount_in < swap_in_after_add_fee {
-            return Err(AmmError::ExceededSlippage.into());
-        }
-        if swap_in_after_add_fee == 0 || swap.amount_out == 0 {
-            return Err(AmmError::InvalidInput.into());
-        }
-
-        match swap_direction {
-            SwapDirection::Coin2PC => {
-                if swap.amount_out >= total_pc_without_take_pnl {
-                    return Err(AmmError::InsufficientFunds.into());
-                }
-
-                if enable_orderbook {
-                    // coin -> pc, need cancel buy order
-                    if !bids.is_empty() {
-                        let mut amm_order_ids_vec = Vec::new();
-                        let mut order_ids = [0u64; 8];
-                        let mut count = 0;
-                        // fetch cancel order ids
-                        for order in bids.into_iter() {
-                            order_ids[count] = order.client_order_id();
-                            count += 1;
-                            if count == 8 {
-                                amm_order_ids_vec.push(order_ids);
-                                order_ids = [0u64; 8];
-                                count = 0;
-                            }
-                        }
-                        if count != 0 {
-                            amm_order_ids_vec.push(order_ids);
-                        }
-                        for ids in amm_order_ids_vec.iter() {
-                            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
-                                market_program_info.clone(),
-                                market_info.clone(),
-                                market_bids_info.clone(),
-                                market_asks_info.clone(),
-                                amm_open_orders_info.clone(),
-                                amm_authority_info.clone(),
-                                market_event_queue_info.clone(),
-                                AUTHORITY_AMM,
-                                amm.nonce as u8,
-                                *ids,
-                            )?;
-                        }
-                    }
-                    if swap.amount_out > amm_pc_vault.amount {
-                        // need settle funds
-                        Invokers::invoke_dex_settle_funds(
-                            market_program_info.clone(),
-                            market_info.clone(),
-                            amm_open_orders_info.clone(),
-                            amm_authority_info.clone(),
-                            market_coin_vault_info.clone(),
-                            market_pc_vault_info.clone(),
-                            amm_coin_vault_info.clone(),
-                            amm_pc_vault_info.clone(),
-                            market_vault_signer.clone(),
-                            token_program_info.clone(),
-                            Some(&amm_pc_vault_info.clone()),
-                            AUTHORITY_AMM,
-                            amm.nonce as u8,
-                        )?;
-                    }
-                }
-                // deposit source coin to amm_coin_vault
-                Invokers::token_transfer(
-                    token_program_info.clone(),
-                    user_source_info.clone(),
-                    amm_coin_vault_info.clone(),
-                    user_source_owner.clone(),
-                    swap_in_after_add_fee,
-                )?;
-                // withdraw amm_pc_vault to destination pc
-                Invokers::token_transfer_with_authority(
-                    token_program_info.clone(),
-                    amm_pc_vault_info.clone(),
-                    user_destination_info.clone(),
-                    amm_authority_info.clone(),
-                    AUTHORITY_AMM,
-                    amm.nonce as u8,
-                    swap.amount_out,
-                )?;
-                // update state_data data
-                amm.state_data.swap_coin_in_amount = amm
-                    .state_data
-                    .swap_coin_in_amount
-                    .checked_add(swap_in_after_add_fee.into())
-                    .unwrap();
-                amm.state_data.swap_pc_out_amount = amm
-                    .state_data
-                    .swap_pc_out_amount
-                    .checked_add(Calculator::to_u128(swap.amount_out)?)
-                    .unwrap();
-                // charge coin as swap fee
-                amm.state_data.swap_acc_coin_fee = amm
-                    .state_data
-                    .swap_acc_coin_fee
-                    .checked_add(swap_fee)
-                    .unwrap();
-            }
-            SwapDirection::PC2Coin => {
-                if swap.amount_out >= total_coin_without_take_pnl {
-                    return Err(AmmError::InsufficientFunds.into());
-                }
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
 
-                if enable_orderbook {
-                    // pc -> coin, need cancel sell order
-                    if !asks.is_empty() {
-                        let mut amm_order_ids_vec = Vec::new();
-                        let mut order_ids = [0u64; 8];
-                        let mut count = 0;
-                        // fetch cancel order ids
-                        for order in asks.into_iter() {
-                            order_ids[count] = order.client_order_id();
-                            count += 1;
-                            if count == 8 {
-                                amm_order_ids_vec.push(order_ids);
-                                order_ids = [0u64; 8];
-                                count = 0;
-                            }
-                        }
-                        if count != 0 {
-                            amm_order_ids_vec.push(order_ids);
-                        }
-                        for ids in amm_order_ids_vec.iter() {
-                            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
-                                market_program_info.clone(),
-                                market_info.clone(),
-                                market_bids_info.clone(),
-                                market_asks_info.clone(),
-                                amm_open_orders_info.clone(),
-                                amm_authority_info.clone(),
-                                market_event_queue_info.clone(),
-                                AUTHORITY_AMM,
-                                amm.nonce as u8,
-                                *ids,
-                            )?;
-                        }
-                    }
-                    if swap.amount_out > amm_coin_vault.amount {
-                        Invokers::invoke_dex_settle_funds(
-                            market_program_info.clone(),
-                            market_info.clone(),
-                            amm_open_orders_info.clone(),
-                            amm_authority_info.clone(),
-                            market_asks_info.clone(),
-                            market_pc_vault_info.clone(),
-                            amm_coin_vault_info.clone(),
-                            amm_pc_vault_info.clone(),
-                            market_vault_signer.clone(),
-                            token_program_info.clone(),
-                            Some(&amm_pc_vault_info.clone()),
-                            AUTHORITY_AMM,
-                            amm.nonce as u8,
-                        )?;
-                    }
-                }
+        let user_source_info = next_account_info(account_info_iter)?;
+        let user_destination_info = next_account_info(account_info_iter)?;
+        let user_source_owner = next_account_info(account_info_iter)?;
+        // transfer hook extra accounts for Token-2022 mints
+        let remaining_accounts = account_info_iter.as_slice();
 
-                // deposit source pc to amm_pc_vault
-                Invokers::token_transfer(
-                    token_program_info.clone(),
-                    user_source_info.clone(),
-                    amm_pc_vault_info.clone(),
-                    user_source_owner.clone(),
-                    swap_in_after_add_fee,
-                )?;
-                // withdraw amm_coin_vault to destination coin
-                Invokers::token_transfer_with_authority(
-                    token_program_info.clone(),
-                    amm_coin_vault_info.clone(),
-                    user_destination_info.clone(),
-                    amm_authority_info.clone(),
-                    AUTHORITY_AMM,
-                    amm.nonce as u8,
-                    swap.amount_out,
-                )?;
-                // update state_data data
-                amm.state_data.swap_pc_in_amount = amm
-                    .state_data
-                    .swap_pc_in_amount
-                    .checked_add(swap_in_after_add_fee.into())
-                    .unwrap();
-                amm.state_data.swap_coin_out_amount = amm
-                    .state_data
-                    .swap_coin_out_amount
-                    .checked_add(swap.amount_out.into())
-                    .unwrap();
-                // charge pc as swap fee
-                amm.state_data.swap_acc_pc_fee = amm
-                    .state_data
-                    .swap_acc_pc_fee
-                    .checked_add(swap_fee)
-                    .unwrap();
-            }
+        let (amm, amm_extension) = AmmInfo::load_mut_checked_extended(amm_info, program_id)?;
+        if !amm.is_orderbook_free() || AmmStatus::from_u64(amm.status).orderbook_permission() {
+            return Err(AmmError::InvalidStatus.into());
+        }
+        let swap_accounts = SwapAccounts {
+            coin_token_program_info,
+            pc_token_program_info,
+            amm_info,
+            amm_authority_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            user_source_info,
+            user_destination_info,
+            user_source_owner,
+            orderbook: None,
+            remaining_accounts,
+            price_oracle_info,
         };
-        amm.recent_epoch = Clock::get()?.epoch;
+        Ok((swap_accounts, amm, amm_extension))
+    }
 
-        Ok(())
+    pub fn process_swap_base_in_no_orderbook(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        swap: SwapInstructionBaseIn,
+    ) -> ProgramResult {
+        let (swap_accounts, amm, amm_extension) =
+            Self::swap_no_orderbook_accounts(program_id, accounts)?;
+        Self::swap_base_in(program_id, swap_accounts, amm, amm_extension, swap)
     }
 
-    pub fn process_migrate_to_openbook(
+    pub fn process_swap_base_out_no_orderbook(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        swap: SwapInstructionBaseOut,
+    ) -> ProgramResult {
+        let (swap_accounts, amm, amm_extension) =
+            Self::swap_no_orderbook_accounts(program_id, accounts)?;
+        Self::swap_base_out(program_id, swap_accounts, amm, amm_extension, swap)
+    }
+
+    pub fn process_swap_base_in(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
+        swap: SwapInstructionBaseIn,
     ) -> ProgramResult {
+        // a pool with a price oracle takes it as the last account, after the pc side token program
+        let (accounts, price_oracle_info) = Self::split_price_oracle(program_id, accounts, 1)?;
+        const ACCOUNT_LEN: usize = 17;
+        let input_account_len = accounts.len();
+        if input_account_len < ACCOUNT_LEN {
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
         let account_info_iter = &mut accounts.iter();
-        let token_program_info = next_account_info(account_info_iter)?;
-        let system_program_info = next_account_info(account_info_iter)?;
-        let rent_sysvar_info = next_account_info(account_info_iter)?;
+        let coin_token_program_info = next_account_info(account_info_iter)?;
 
         let amm_info = next_account_info(account_info_iter)?;
         let amm_authority_info = next_account_info(account_info_iter)?;
         let amm_open_orders_info = next_account_info(account_info_iter)?;
+        let (amm, amm_extension) = AmmInfo::load_mut_checked_extended(amm_info, program_id)?;
+        // the optional target orders account is identified by key, since Token-2022
+        // transfer hook accounts may follow the fixed accounts.
+        if input_account_len > ACCOUNT_LEN && *accounts[4].key == amm.target_orders {
+            let _amm_target_orders_info = next_account_info(account_info_iter)?;
+        }
         let amm_coin_vault_info = next_account_info(account_info_iter)?;
         let amm_pc_vault_info = next_account_info(account_info_iter)?;
-        let amm_target_orders_info = next_account_info(account_info_iter)?;
 
         let market_program_info = next_account_info(account_info_iter)?;
         let market_info = next_account_info(account_info_iter)?;
@@ -3113,758 +5668,1413 @@ impl Processor {
         let market_pc_vault_info = next_account_info(account_info_iter)?;
         let market_vault_signer = next_account_info(account_info_iter)?;
 
-        let new_amm_open_orders_info = next_account_info(account_info_iter)?;
-        let new_market_program_info = next_account_info(account_info_iter)?;
-        let new_market_info = next_account_info(account_info_iter)?;
-        let admin_info = next_account_info(account_info_iter)?;
-        let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
-        if !admin_info.is_signer || *admin_info.key != config_feature::amm_owner::ID {
-            return Err(AmmError::InvalidSignAccount.into());
-        }
-        let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
-        check_assert_eq!(
-            *amm_authority_info.key,
-            authority,
-            "authority",
-            AmmError::InvalidProgramAddress
-        );
-        let spl_token_program_id = token_program_info.key;
-        check_assert_eq!(
-            *spl_token_program_id,
-            spl_token::id(),
-            "spl_token_program",
-            AmmError::InvalidSplTokenProgram
-        );
-        check_assert_eq!(
-            *system_program_info.key,
-            solana_program::system_program::id(),
-            "sys_program",
-            AmmError::InvalidSysProgramAddress
-        );
-        // old account check
-        check_assert_eq!(
-            *amm_coin_vault_info.key,
-            amm.coin_vault,
-            "coin_vault",
-            AmmError::InvalidCoinVault
-        );
-        check_assert_eq!(
-            *amm_pc_vault_info.key,
-            amm.pc_vault,
-            "pc_vault",
-            AmmError::InvalidPCVault
-        );
-        check_assert_eq!(
-            *amm_target_orders_info.key,
-            amm.target_orders,
-            "target_orders",
-            AmmError::InvalidPCVault
-        );
-        check_assert_eq!(
-            *market_info.key,
-            amm.market,
-            "market",
-            AmmError::InvalidMarket
-        );
-        check_assert_eq!(
-            *market_program_info.key,
-            amm.market_program,
-            "market_program",
-            AmmError::InvalidMarketProgram
-        );
-        check_assert_eq!(
-            *amm_open_orders_info.key,
-            amm.open_orders,
-            "open_orders",
-            AmmError::InvalidOpenOrders
-        );
-        let mut target =
-            TargetOrders::load_mut_checked(&amm_target_orders_info, program_id, amm_info.key)?;
-        let pnl_pc_amount = Calculator::restore_decimal(
-            target.calc_pnl_x.into(),
-            amm.pc_decimals,
-            amm.sys_decimal_value,
-        )
-        .as_u64();
-        let pnl_coin_amount = Calculator::restore_decimal(
-            target.calc_pnl_y.into(),
-            amm.coin_decimals,
-            amm.sys_decimal_value,
-        )
-        .as_u64();
-        // cancel amm orders in old market
-        Self::do_cancel_amm_orders(
-            &amm,
+        let user_source_info = next_account_info(account_info_iter)?;
+        let user_destination_info = next_account_info(account_info_iter)?;
+        let user_source_owner = next_account_info(account_info_iter)?;
+        // transfer hook extra accounts for Token-2022 mints
+        let remaining_accounts = account_info_iter.as_slice();
+        // pools whose sides use different token programs take the pc side token
+        // program as the last account, after the transfer hook accounts
+        let (remaining_accounts, pc_token_program_info) =
+            if amm.coin_token_program() != amm.pc_token_program() {
+                match remaining_accounts.split_last() {
+                    Some((last, rest)) => (rest, last),
+                    None => return Err(AmmError::WrongAccountsNumber.into()),
+                }
+            } else {
+                (remaining_accounts, coin_token_program_info)
+            };
+        let orderbook = if AmmStatus::from_u64(amm.status).orderbook_permission() {
+            Some(SwapOrderbook {
+                amm_open_orders_info,
+                market_program_info,
+                market_info,
+                market_bids_info,
+                market_asks_info,
+                market_event_queue_info,
+                market_coin_vault_info,
+                market_pc_vault_info,
+                market_vault_signer,
+            })
+        } else {
+            None
+        };
+        let swap_accounts = SwapAccounts {
+            coin_token_program_info,
+            pc_token_program_info,
+            amm_info,
             amm_authority_info,
-            amm_open_orders_info,
-            market_program_info,
-            market_info,
-            market_bids_info,
-            market_asks_info,
-            market_event_queue_info,
-            AUTHORITY_AMM,
-        )?;
-        // settle assets
-        Invokers::invoke_dex_settle_funds(
-            market_program_info.clone(),
-            market_info.clone(),
-            amm_open_orders_info.clone(),
-            amm_authority_info.clone(),
-            market_coin_vault_info.clone(),
-            market_pc_vault_info.clone(),
-            amm_coin_vault_info.clone(),
-            amm_pc_vault_info.clone(),
-            market_vault_signer.clone(),
-            token_program_info.clone(),
-            None,
-            AUTHORITY_AMM,
-            amm.nonce as u8,
-        )?;
-        let (market_state, open_orders) = Self::load_serum_market_order(
-            market_info,
-            amm_open_orders_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            user_source_info,
+            user_destination_info,
+            user_source_owner,
+            orderbook,
+            remaining_accounts,
+            price_oracle_info,
+        };
+        Self::swap_base_in(program_id, swap_accounts, amm, amm_extension, swap)
+    }
+
+    /// Swap of a `SwapBaseIn` or `SwapBaseInNoOrderbook`, on the accounts parsed from its layout
+    fn swap_base_in<'a, 'b>(
+        program_id: &Pubkey,
+        swap_accounts: SwapAccounts<'a, 'b>,
+        mut amm: RefMut<'a, AmmInfo>,
+        mut amm_extension: Option<RefMut<'a, AmmExtension>>,
+        swap: SwapInstructionBaseIn,
+    ) -> ProgramResult {
+        let SwapAccounts {
+            coin_token_program_info,
+            pc_token_program_info,
+            amm_info,
             amm_authority_info,
-            &amm,
-            false,
-        )?;
-        if identity(market_state.coin_mint) != amm.coin_vault_mint.to_aligned_bytes() {
-            return Err(AmmError::InvalidCoinMint.into());
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            user_source_info,
+            user_destination_info,
+            user_source_owner,
+            orderbook,
+            remaining_accounts,
+            price_oracle_info,
+        } = swap_accounts;
+        if !user_source_owner.is_signer {
+            return Err(AmmError::InvalidSignAccount.into());
         }
-        if identity(market_state.pc_mint) != amm.pc_vault_mint.to_aligned_bytes() {
-            return Err(AmmError::InvalidPCMint.into());
+        Self::check_token_programs(&amm, coin_token_program_info, pc_token_program_info)?;
+        // an SPL Token pool only takes the referrer token account of a fee split
+        if *coin_token_program_info.key == spl_token::id()
+            && *pc_token_program_info.key == spl_token::id()
+            && remaining_accounts.len() > amm.fee_split(amm_extension.as_deref()).is_some() as usize
+        {
+            return Err(AmmError::WrongAccountsNumber.into());
         }
-        // Partial filled without consumed assets exclude native total in open_orders will not be settled back to amm.
-        // It is necessary to check that all assets have been indeed settled back to amm
-        let (pc_total_in_serum, coin_total_in_serum) = Calculator::calc_exact_vault_in_serum(
-            &open_orders,
-            &market_state,
-            market_event_queue_info,
-            amm_open_orders_info,
-        )?;
-        if pc_total_in_serum != 0 || coin_total_in_serum != 0 {
-            // Invokers::invoke_dex_close_open_orders(
-            //     serum_dex_info.clone(),
-            //     amm_open_orders_info.clone(),
-            //     authority_info.clone(),
-            //     admin_info.clone(),
-            //     market_info.clone(),
-            //     AUTHORITY_AMM,
-            //     amm.nonce as u8,
-            // )?;
-            msg!(
-                "{}, {}, {}, {}, {:?}",
-                pc_total_in_serum,
-                coin_total_in_serum,
-                identity(open_orders.native_pc_total),
-                identity(open_orders.native_coin_total),
-                identity(open_orders.free_slot_bits)
-            );
-            return Err(AmmError::UnknownAmmError.into());
+        if *amm_authority_info.key
+            != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
+        {
+            return Err(AmmError::InvalidProgramAddress.into());
         }
-        // check new dex
         check_assert_eq!(
-            *new_market_program_info.key,
-            config_feature::openbook_program::id(),
-            "new_market_program",
-            AmmError::InvalidMarketProgram
+            *amm_coin_vault_info.key,
+            amm.coin_vault,
+            "coin_vault",
+            AmmError::InvalidCoinVault
         );
-        let new_market_pc_lot_size;
-        let new_market_coin_lot_size;
+        check_assert_eq!(
+            *amm_pc_vault_info.key,
+            amm.pc_vault,
+            "pc_vault",
+            AmmError::InvalidPCVault
+        );
+
+        if *user_source_info.key == amm.pc_vault || *user_source_info.key == amm.coin_vault {
+            return Err(AmmError::InvalidUserToken.into());
+        }
+        if *user_destination_info.key == amm.pc_vault
+            || *user_destination_info.key == amm.coin_vault
         {
-            let new_market_state = Market::load_checked(
-                new_market_info,
-                &config_feature::openbook_program::id(),
-                false,
-            )?;
-            new_market_pc_lot_size = new_market_state.pc_lot_size;
-            new_market_coin_lot_size = new_market_state.coin_lot_size;
-            if identity(new_market_state.coin_mint) != amm.coin_vault_mint.to_aligned_bytes() {
-                return Err(AmmError::InvalidCoinMint.into());
-            }
-            if identity(new_market_state.pc_mint) != amm.pc_vault_mint.to_aligned_bytes() {
-                return Err(AmmError::InvalidPCMint.into());
-            }
+            return Err(AmmError::InvalidUserToken.into());
         }
-        // create amm open order account
-        Self::generate_amm_associated_account(
-            program_id,
-            new_market_program_info.key,
-            new_market_info,
-            new_amm_open_orders_info,
-            admin_info,
-            system_program_info,
-            rent_sysvar_info,
-            OPEN_ORDER_ASSOCIATED_SEED,
-            size_of::<serum_dex::state::OpenOrders>() + 12,
+
+        let amm_coin_vault = crate::token_utils::unpack_token_account(
+            &amm_coin_vault_info,
+            coin_token_program_info.key,
         )?;
-        // init open orders account
-        Invokers::invoke_dex_init_open_orders(
-            new_market_program_info.clone(),
-            new_amm_open_orders_info.clone(),
-            amm_authority_info.clone(),
-            new_market_info.clone(),
-            rent_sysvar_info.clone(),
-            AUTHORITY_AMM,
-            amm.nonce as u8,
+        let amm_pc_vault = crate::token_utils::unpack_token_account(
+            &amm_pc_vault_info,
+            pc_token_program_info.key,
         )?;
-        // update pool
-        amm.market_program = *new_market_program_info.key;
-        amm.market = *new_market_info.key;
-        amm.open_orders = *new_amm_open_orders_info.key;
-        let nonce = amm.nonce as u8;
-        let pool_open_time = amm.state_data.pool_open_time;
-        let coin_decimals = amm.coin_decimals as u8;
-        let pc_decimals = amm.pc_decimals as u8;
-        amm.initialize(
-            nonce,
-            pool_open_time,
-            coin_decimals,
-            pc_decimals,
-            new_market_coin_lot_size,
-            new_market_pc_lot_size,
+
+        // user accounts are checked against the vault mints below, which pins their side
+        let user_source =
+            crate::token_utils::unpack_token_account(&user_source_info, user_source_info.owner)?;
+        let user_destination = crate::token_utils::unpack_token_account(
+            &user_destination_info,
+            user_destination_info.owner,
         )?;
-        amm.status = AmmStatus::WaitingTrade.into_u64();
-        amm.reset_flag = AmmResetFlag::ResetYes.into_u64();
 
-        if new_market_coin_lot_size != market_state.coin_lot_size
-            || new_market_pc_lot_size != market_state.pc_lot_size
-        {
-            // market lot size is different, calc_pnl_x & calc_pnl_x must update
-            target.calc_pnl_x = Calculator::normalize_decimal_v2(
-                pnl_pc_amount,
-                amm.pc_decimals,
-                amm.sys_decimal_value,
-            )
-            .as_u128();
-            target.calc_pnl_y = Calculator::normalize_decimal_v2(
-                pnl_coin_amount,
-                amm.coin_decimals,
-                amm.sys_decimal_value,
-            )
-            .as_u128();
+        if !AmmStatus::from_u64(amm.status).swap_permission() {
+            msg!(&format!("swap_base_in: status {}", identity(amm.status)));
+            let clock = Clock::get()?;
+            if amm.status == AmmStatus::OrderBookOnly.into_u64()
+                && (clock.unix_timestamp as u64) >= amm.state_data.orderbook_to_init_time
+            {
+                amm.status = AmmStatus::Initialized.into_u64();
+                msg!("swap_base_in: OrderBook to Initialized");
+            } else {
+                return Err(AmmError::InvalidStatus.into());
+            }
+        } else if amm.status == AmmStatus::WaitingTrade.into_u64() {
+            let clock = Clock::get()?;
+            if (clock.unix_timestamp as u64) < amm.state_data.pool_open_time {
+                return Err(AmmError::InvalidStatus.into());
+            } else {
+                amm.status = AmmStatus::SwapOnly.into_u64();
+                msg!("swap_base_in: WaitingTrade to SwapOnly");
+            }
+        }
+        let coin_mint_info = remaining_accounts
+            .iter()
+            .find(|account| *account.key == amm.coin_vault_mint);
+        let pc_mint_info = remaining_accounts
+            .iter()
+            .find(|account| *account.key == amm.pc_vault_mint);
+        if Self::check_hook_programs(&mut amm, coin_mint_info, pc_mint_info)? {
+            msg!("swap_base_in: transfer hook program changed, pool is withdraw only");
+            return Err(AmmError::HookProgramChanged.into());
         }
-        amm.recent_epoch = Clock::get()?.epoch;
-
-        Ok(())
-    }
-
-    /// withdraw_srm
-    pub fn process_withdraw_srm(
-        program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        withdrawsrm: WithdrawSrmInstruction,
-    ) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let token_program_info = next_account_info(account_info_iter)?;
 
-        let amm_info = next_account_info(account_info_iter)?;
-        let amm_owner_info = next_account_info(account_info_iter)?;
-        let amm_authority_info = next_account_info(account_info_iter)?;
-        let srm_token_info = next_account_info(account_info_iter)?;
-        let dest_srm_token_info = next_account_info(account_info_iter)?;
+        let total_pc_without_take_pnl;
+        let total_coin_without_take_pnl;
+        let mut bids: Vec<LeafNode> = Vec::new();
+        let mut asks: Vec<LeafNode> = Vec::new();
+        if let Some(orderbook) = &orderbook {
+            check_assert_eq!(
+                *orderbook.amm_open_orders_info.key,
+                amm.open_orders,
+                "open_orders",
+                AmmError::InvalidOpenOrders
+            );
+            check_assert_eq!(
+                *orderbook.market_program_info.key,
+                amm.market_program,
+                "market_program",
+                AmmError::InvalidMarketProgram
+            );
+            check_assert_eq!(
+                *orderbook.market_info.key,
+                amm.market,
+                "market",
+                AmmError::InvalidMarket
+            );
+            let (market_state, open_orders) = Processor::load_serum_market_order(
+                orderbook.market_info,
+                orderbook.amm_open_orders_info,
+                amm_authority_info,
+                &amm,
+                false,
+            )?;
+            let bids_orders = market_state.load_bids_checked(orderbook.market_bids_info)?;
+            let asks_orders = market_state.load_asks_checked(orderbook.market_asks_info)?;
+            (bids, asks) = Self::get_amm_orders(&open_orders, bids_orders, asks_orders)?;
+            (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+                Calculator::calc_total_without_take_pnl(
+                    amm_pc_vault.amount,
+                    amm_coin_vault.amount,
+                    &open_orders,
+                    &amm,
+                    &market_state,
+                    orderbook.market_event_queue_info,
+                    orderbook.amm_open_orders_info,
+                )?;
+        } else {
+            (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+                Calculator::calc_total_without_take_pnl_no_orderbook(
+                    amm_pc_vault.amount,
+                    amm_coin_vault.amount,
+                    &amm,
+                )?;
+        }
 
-        msg!("withdraw_srm: {}", withdrawsrm.amount);
-        let amm = AmmInfo::load_checked(&amm_info, program_id)?;
-        if amm.status == AmmStatus::Uninitialized.into_u64() {
-            msg!(&format!("withdraw_srm: status {}", identity(amm.status)));
-            return Err(AmmError::InvalidStatus.into());
+        let swap_direction;
+        if user_source.mint == amm_coin_vault.mint && user_destination.mint == amm_pc_vault.mint {
+            swap_direction = SwapDirection::Coin2PC
+        } else if user_source.mint == amm_pc_vault.mint
+            && user_destination.mint == amm_coin_vault.mint
+        {
+            swap_direction = SwapDirection::PC2Coin
+        } else {
+            return Err(AmmError::InvalidUserToken.into());
         }
-        if !amm_owner_info.is_signer || *amm_owner_info.key != config_feature::amm_owner::ID {
-            return Err(AmmError::InvalidSignAccount.into());
+        if user_source.amount < swap.amount_in {
+            encode_ray_log(SwapBaseInLog {
+                log_type: LogType::SwapBaseIn.into_u8(),
+                amount_in: swap.amount_in,
+                minimum_out: swap.minimum_amount_out,
+                direction: swap_direction as u64,
+                user_source: user_source.amount,
+                pool_coin: total_coin_without_take_pnl,
+                pool_pc: total_pc_without_take_pnl,
+                out_amount: 0,
+                transfer_fee_in: 0,
+                transfer_fee_out: 0,
+                hook_compute_units: 0,
+                ..Default::default()
+            });
+            return Err(AmmError::InsufficientFunds.into());
         }
-        // check_assert_eq!(
-        //     *amm_owner_info.key,
-        //     amm.amm_owner,
-        //     "amm_owner",
-        //     AmmError::InvalidOwner
-        // );
-        check_assert_eq!(
-            *token_program_info.key,
-            spl_token::id(),
-            "spl_token_program",
-            AmmError::InvalidSplTokenProgram
-        );
-        let spl_token_program_id = token_program_info.key;
-        let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
-        check_assert_eq!(
-            *amm_authority_info.key,
-            authority,
-            "authority",
-            AmmError::InvalidProgramAddress
-        );
-        let srm_token = Self::unpack_token_account(&srm_token_info, spl_token_program_id)?;
-        check_assert_eq!(
-            srm_token.owner,
-            *amm_authority_info.key,
-            "srm_token_owner",
-            AmmError::InvalidOwner
-        );
-        if srm_token.mint != srm_token::id() && srm_token.mint != msrm_token::id() {
-            return Err(AmmError::InvalidSrmMint.into());
+        let (source_mint, source_token_program, destination_mint, destination_token_program) =
+            match swap_direction {
+                SwapDirection::Coin2PC => (
+                    amm.coin_vault_mint,
+                    amm.coin_token_program(),
+                    amm.pc_vault_mint,
+                    amm.pc_token_program(),
+                ),
+                SwapDirection::PC2Coin => (
+                    amm.pc_vault_mint,
+                    amm.pc_token_program(),
+                    amm.coin_vault_mint,
+                    amm.coin_token_program(),
+                ),
+            };
+        // Token-2022 transfer fees, only the amount net of the inbound fee arrives in the vault
+        let epoch = Clock::get()?.epoch;
+        let transfer_fee_in = Self::transfer_fee(
+            remaining_accounts.iter().find(|account| *account.key == source_mint),
+            &source_token_program,
+            epoch,
+            swap.amount_in,
+        )?;
+        let swap_amount_in = swap
+            .amount_in
+            .checked_sub(transfer_fee_in)
+            .ok_or(AmmError::InvalidInput)?;
+        let swap_fee = U128::from(swap_amount_in)
+            .checked_mul(amm.fees.swap_fee_numerator.into())
+            .unwrap()
+            .checked_ceil_div(amm.fees.swap_fee_denominator.into())
+            .unwrap()
+            .0;
+        let swap_in_after_deduct_fee = U128::from(swap_amount_in).checked_sub(swap_fee).unwrap();
+        let swap_amount_out = amm
+            .curve(
+                amm_extension.as_deref(),
+                Clock::get()?.unix_timestamp as u64,
+            )?
+            .swap_token_amount_base_in(
+                swap_in_after_deduct_fee,
+                total_pc_without_take_pnl.into(),
+                total_coin_without_take_pnl.into(),
+                swap_direction,
+            )?
+            .as_u64();
+        // the outbound fee is withheld from what the user receives
+        let transfer_fee_out = Self::transfer_fee(
+            remaining_accounts.iter().find(|account| *account.key == destination_mint),
+            &destination_token_program,
+            epoch,
+            swap_amount_out,
+        )?;
+        // logged once the hooked transfers are metered, or when a check fails
+        let mut swap_log = SwapBaseInLog {
+            log_type: LogType::SwapBaseIn.into_u8(),
+            amount_in: swap.amount_in,
+            minimum_out: swap.minimum_amount_out,
+            direction: swap_direction as u64,
+            user_source: user_source.amount,
+            pool_coin: total_coin_without_take_pnl,
+            pool_pc: total_pc_without_take_pnl,
+            out_amount: swap_amount_out,
+            transfer_fee_in,
+            transfer_fee_out,
+            hook_compute_units: 0,
+            ..Default::default()
+        };
+        if swap_amount_out.saturating_sub(transfer_fee_out) < swap.minimum_amount_out {
+            encode_ray_log(swap_log);
+            return Err(AmmError::ExceededSlippage.into());
         }
-        let dest_srm_token =
-            Self::unpack_token_account(&dest_srm_token_info, spl_token_program_id)?;
-        check_assert_eq!(
-            srm_token.mint,
-            dest_srm_token.mint,
-            "srm_token_mint",
-            AmmError::InvalidInput
-        );
-        if withdrawsrm.amount > srm_token.amount {
+        if swap_amount_out == 0 || swap_amount_in == 0 {
+            encode_ray_log(swap_log);
+            return Err(AmmError::InvalidInput.into());
+        }
+        let pool_amount_out = match swap_direction {
+            SwapDirection::Coin2PC => total_pc_without_take_pnl,
+            SwapDirection::PC2Coin => total_coin_without_take_pnl,
+        };
+        if swap_amount_out >= pool_amount_out {
+            encode_ray_log(swap_log);
             return Err(AmmError::InsufficientFunds.into());
         }
 
-        Invokers::token_transfer_with_authority(
-            token_program_info.clone(),
-            srm_token_info.clone(),
-            dest_srm_token_info.clone(),
-            amm_authority_info.clone(),
-            AUTHORITY_AMM,
-            amm.nonce as u8,
-            withdrawsrm.amount,
+        // resolve the transfer hook accounts of both transfers before any CPI
+        let (
+            (source_mint_info, source_hook_config, source_hook_accounts),
+            (destination_mint_info, destination_hook_config, destination_hook_accounts),
+            remaining_accounts,
+        ) = Self::split_swap_hook_accounts(
+            program_id,
+            &amm,
+            swap_direction,
+            user_source_info,
+            user_destination_info,
+            user_source_owner,
+            amm_authority_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            swap.amount_in,
+            swap_amount_out,
+            remaining_accounts,
         )?;
-
-        Ok(())
-    }
-
-    fn simulate_pool_info(
-        program_id: &Pubkey,
-        accounts: &[AccountInfo],
-    ) -> Result<GetPoolData, ProgramError> {
-        const ACCOUNT_LEN: usize = 8;
-        let input_account_len = accounts.len();
-        let account_info_iter = &mut accounts.iter();
-        let amm_info = next_account_info(account_info_iter)?;
-        let amm_authority_info = next_account_info(account_info_iter)?;
-        let amm_open_orders_info = next_account_info(account_info_iter)?;
-        let amm_coin_vault_info = next_account_info(account_info_iter)?;
-        let amm_pc_vault_info = next_account_info(account_info_iter)?;
-        let amm_lp_mint_info = next_account_info(account_info_iter)?;
-        let market_info = next_account_info(account_info_iter)?;
-        let market_event_queue_info = next_account_info(account_info_iter)?;
-
-        let amm = AmmInfo::load_checked(&amm_info, program_id)?;
-        let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
-        Self::check_account_readonly(amm_info)?;
-        Self::check_account_readonly(amm_open_orders_info)?;
-        Self::check_account_readonly(amm_coin_vault_info)?;
-        Self::check_account_readonly(amm_pc_vault_info)?;
-        Self::check_account_readonly(amm_lp_mint_info)?;
-        Self::check_account_readonly(market_info)?;
-        Self::check_account_readonly(market_event_queue_info)?;
-        check_assert_eq!(
-            *amm_authority_info.key,
-            authority,
-            "authority",
-            AmmError::InvalidProgramAddress
-        );
-        check_assert_eq!(
-            *amm_coin_vault_info.key,
-            amm.coin_vault,
-            "coin_vault",
-            AmmError::InvalidCoinVault
-        );
-        check_assert_eq!(
-            *amm_pc_vault_info.key,
-            amm.pc_vault,
-            "pc_vault",
-            AmmError::InvalidPCVault
-        );
-        check_assert_eq!(
-            *amm_lp_mint_info.key,
-            amm.lp_mint,
-            "lp_mint",
-            AmmError::InvalidPoolMint
-        );
-        check_assert_eq!(
-            *amm_open_orders_info.key,
-            amm.open_orders,
-            "open_orders",
-            AmmError::InvalidOpenOrders
-        );
-        check_assert_eq!(
-            *market_info.key,
-            amm.market,
-            "market",
-            AmmError::InvalidMarket
-        );
-        let pnl_pc_amount;
-        let pnl_coin_amount;
-        if input_account_len == ACCOUNT_LEN + 1 {
-            let target_orders_info = next_account_info(account_info_iter)?;
-            check_assert_eq!(
-                *target_orders_info.key,
-                amm.target_orders,
-                "target_orders",
-                AmmError::InvalidTargetOrders
-            );
-            let target = TargetOrders::load_checked(&target_orders_info, program_id, amm_info.key)?;
-            pnl_pc_amount = Calculator::restore_decimal(
-                target.calc_pnl_x.into(),
-                amm.pc_decimals,
-                amm.sys_decimal_value,
-            )
-            .as_u64();
-            pnl_coin_amount = Calculator::restore_decimal(
-                target.calc_pnl_y.into(),
+        let (lp_fee, protocol_fee, referrer_fee) = amm
+            .fee_split(amm_extension.as_deref())
+            .unwrap_or_default()
+            .shares(swap_fee.as_u64(), !remaining_accounts.is_empty());
+        let (source_vault_info, source_token_program_info, source_decimals) = match swap_direction {
+            SwapDirection::Coin2PC => (
+                amm_coin_vault_info,
+                coin_token_program_info,
                 amm.coin_decimals,
-                amm.sys_decimal_value,
-            )
-            .as_u64();
-        } else {
-            pnl_pc_amount = 0;
-            pnl_coin_amount = 0;
-        }
-
-        let amm_coin_vault = Self::unpack_token_account(&amm_coin_vault_info, &spl_token::id())?;
-        let amm_pc_vault = Self::unpack_token_account(&amm_pc_vault_info, &spl_token::id())?;
-        let lp_mint = Self::unpack_mint(&amm_lp_mint_info, &spl_token::id())?;
-        let (market_state, open_orders) = Self::load_serum_market_order(
-            market_info,
-            amm_open_orders_info,
-            amm_authority_info,
+            ),
+            SwapDirection::PC2Coin => (amm_pc_vault_info, pc_token_program_info, amm.pc_decimals),
+        };
+        let referrer = Self::split_referrer_accounts(
+            program_id,
             &amm,
-            false,
+            amm_extension.as_deref(),
+            &source_mint,
+            &source_token_program,
+            source_vault_info,
+            amm_authority_info,
+            referrer_fee,
+            remaining_accounts,
         )?;
-        if identity(market_state.coin_mint) != amm_coin_vault.mint.to_aligned_bytes() {
-            return Err(AmmError::InvalidCoinMint.into());
-        }
-        if identity(market_state.pc_mint) != amm_pc_vault.mint.to_aligned_bytes() {
-            return Err(AmmError::InvalidPCMint.into());
-        }
-        let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
-            Calculator::calc_total_without_take_pnl(
-                amm_pc_vault.amount,
-                amm_coin_vault.amount,
-                &open_orders,
-                &amm,
-                &market_state,
-                &market_event_queue_info,
-                &amm_open_orders_info,
-            )?;
-        let pool_info_data = GetPoolData {
-            status: amm.status,
-            coin_decimals: amm.coin_decimals,
-            pc_decimals: amm.pc_decimals,
-            lp_decimals: lp_mint.decimals.into(),
-            pool_pc_amount: total_pc_without_take_pnl,
-            pool_coin_amount: total_coin_without_take_pnl,
-            pnl_pc_amount,
-            pnl_coin_amount,
-            pool_lp_supply: amm.lp_amount,
-            pool_open_time: amm.state_data.pool_open_time,
-            amm_id: amm_info.key.to_string(),
+        swap_log.lp_fee = lp_fee;
+        swap_log.protocol_fee = protocol_fee;
+        swap_log.referrer_fee = referrer_fee;
+
+        // compute units consumed by the hooked transfers, each checked against its hook's budget
+        let mut hook_compute_units = 0u64;
+        amm.lock();
+        // hooks reading the trade context of their mint see the swap during its transfers
+        let trade_context_sides = [
+            (&source_mint, source_hook_accounts),
+            (&destination_mint, destination_hook_accounts),
+        ];
+        Self::write_trade_contexts(
+            program_id,
+            &TradeContext {
+                amm: *amm_info.key,
+                active: 1,
+                direction: swap_direction as u64,
+                amount_in: swap_amount_in,
+                amount_out: swap_amount_out,
+                coin_reserve: total_coin_without_take_pnl,
+                pc_reserve: total_pc_without_take_pnl,
+                ..Default::default()
+            },
+            trade_context_sides,
+        )?;
+        match swap_direction {
+            SwapDirection::Coin2PC => {
+                if let Some(orderbook) = &orderbook {
+                    // coin -> pc, need cancel buy order
+                    if !bids.is_empty() {
+                        let mut amm_order_ids_vec = Vec::new();
+                        let mut order_ids = [0u64; 8];
+                        let mut count = 0;
+                        // fetch cancel order ids{
+                        for order in bids.into_iter() {
+                            order_ids[count] = order.client_order_id();
+                            count += 1;
+                            if count == 8 {
+                                amm_order_ids_vec.push(order_ids);
+                                order_ids = [0u64; 8];
+                                count = 0;
+                            }
+                        }
+                        if count != 0 {
+                            amm_order_ids_vec.push(order_ids);
+                        }
+                        for ids in amm_order_ids_vec.iter() {
+                            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
+                                orderbook.market_program_info.clone(),
+                                orderbook.market_info.clone(),
+                                orderbook.market_bids_info.clone(),
+                                orderbook.market_asks_info.clone(),
+                                orderbook.amm_open_orders_info.clone(),
+                                amm_authority_info.clone(),
+                                orderbook.market_event_queue_info.clone(),
+                                AUTHORITY_AMM,
+                                amm.nonce as u8,
+                                *ids,
+                            )?;
+                        }
+                    }
+
+                    if swap_amount_out > amm_pc_vault.amount {
+                        // need settle funds
+                        Invokers::invoke_dex_settle_funds(
+                            orderbook.market_program_info.clone(),
+                            orderbook.market_info.clone(),
+                            orderbook.amm_open_orders_info.clone(),
+                            amm_authority_info.clone(),
+                            orderbook.market_coin_vault_info.clone(),
+                            orderbook.market_pc_vault_info.clone(),
+                            amm_coin_vault_info.clone(),
+                            amm_pc_vault_info.clone(),
+                            orderbook.market_vault_signer.clone(),
+                            coin_token_program_info.clone(),
+                            Some(&amm_pc_vault_info.clone()),
+                            AUTHORITY_AMM,
+                            amm.nonce as u8,
+                        )?;
+                    }
+                }
+                // deposit source coin to amm_coin_vault
+                hook_compute_units += crate::token_invokers::token_transfer_with_hook_budget(
+                    source_hook_config.as_ref(),
+                    coin_token_program_info.clone(),
+                    user_source_info.clone(),
+                    source_mint_info.cloned(),
+                    amm_coin_vault_info.clone(),
+                    user_source_owner.clone(),
+                    source_hook_accounts,
+                    swap.amount_in,
+                    amm.coin_decimals as u8,
+                    &[],
+                )?;
+                // withdraw amm_pc_vault to destination pc
+                hook_compute_units += crate::token_invokers::token_transfer_with_hook_budget(
+                    destination_hook_config.as_ref(),
+                    pc_token_program_info.clone(),
+                    amm_pc_vault_info.clone(),
+                    destination_mint_info.cloned(),
+                    user_destination_info.clone(),
+                    amm_authority_info.clone(),
+                    destination_hook_accounts,
+                    swap_amount_out,
+                    amm.pc_decimals as u8,
+                    &[AUTHORITY_AMM, &[amm.nonce as u8]],
+                )?;
+                // update state_data data
+                amm.state_data.swap_coin_in_amount = amm
+                    .state_data
+                    .swap_coin_in_amount
+                    .checked_add(swap_amount_in.into())
+                    .unwrap();
+                amm.state_data.swap_pc_out_amount = amm
+                    .state_data
+                    .swap_pc_out_amount
+                    .checked_add(swap_amount_out.into())
+                    .unwrap();
+                // charge coin as swap fee, the LP share
+                amm.state_data.swap_acc_coin_fee = amm
+                    .state_data
+                    .swap_acc_coin_fee
+                    .checked_add(lp_fee)
+                    .unwrap();
+            }
+            SwapDirection::PC2Coin => {
+                if let Some(orderbook) = &orderbook {
+                    // pc -> coin, need cancel sell order
+                    if !asks.is_empty() {
+                        let mut amm_order_ids_vec = Vec::new();
+                        let mut order_ids = [0u64; 8];
+                        let mut count = 0;
+                        // fetch cancel order ids{
+                        for order in asks.into_iter() {
+                            order_ids[count] = order.client_order_id();
+                            count += 1;
+                            if count == 8 {
+                                amm_order_ids_vec.push(order_ids);
+                                order_ids = [0u64; 8];
+                                count = 0;
+                            }
+                        }
+                        if count != 0 {
+                            amm_order_ids_vec.push(order_ids);
+                        }
+                        for ids in amm_order_ids_vec.iter() {
+                            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
+                                orderbook.market_program_info.clone(),
+                                orderbook.market_info.clone(),
+                                orderbook.market_bids_info.clone(),
+                                orderbook.market_asks_info.clone(),
+                                orderbook.amm_open_orders_info.clone(),
+                                amm_authority_info.clone(),
+                                orderbook.market_event_queue_info.clone(),
+                                AUTHORITY_AMM,
+                                amm.nonce as u8,
+                                *ids,
+                            )?;
+                        }
+                    }
+
+                    if swap_amount_out > amm_coin_vault.amount {
+                        Invokers::invoke_dex_settle_funds(
+                            orderbook.market_program_info.clone(),
+                            orderbook.market_info.clone(),
+                            orderbook.amm_open_orders_info.clone(),
+                            amm_authority_info.clone(),
+                            orderbook.market_coin_vault_info.clone(),
+                            orderbook.market_pc_vault_info.clone(),
+                            amm_coin_vault_info.clone(),
+                            amm_pc_vault_info.clone(),
+                            orderbook.market_vault_signer.clone(),
+                            coin_token_program_info.clone(),
+                            Some(&amm_pc_vault_info.clone()),
+                            AUTHORITY_AMM,
+                            amm.nonce as u8,
+                        )?;
+                    }
+                }
+                // deposit source pc to amm_pc_vault
+                hook_compute_units += crate::token_invokers::token_transfer_with_hook_budget(
+                    source_hook_config.as_ref(),
+                    pc_token_program_info.clone(),
+                    user_source_info.clone(),
+                    source_mint_info.cloned(),
+                    amm_pc_vault_info.clone(),
+                    user_source_owner.clone(),
+                    source_hook_accounts,
+                    swap.amount_in,
+                    amm.pc_decimals as u8,
+                    &[],
+                )?;
+                // withdraw amm_coin_vault to destination coin
+                hook_compute_units += crate::token_invokers::token_transfer_with_hook_budget(
+                    destination_hook_config.as_ref(),
+                    coin_token_program_info.clone(),
+                    amm_coin_vault_info.clone(),
+                    destination_mint_info.cloned(),
+                    user_destination_info.clone(),
+                    amm_authority_info.clone(),
+                    destination_hook_accounts,
+                    swap_amount_out,
+                    amm.coin_decimals as u8,
+                    &[AUTHORITY_AMM, &[amm.nonce as u8]],
+                )?;
+                // update state_data data
+                amm.state_data.swap_pc_in_amount = amm
+                    .state_data
+                    .swap_pc_in_amount
+                    .checked_add(swap_amount_in.into())
+                    .unwrap();
+                amm.state_data.swap_coin_out_amount = amm
+                    .state_data
+                    .swap_coin_out_amount
+                    .checked_add(swap_amount_out.into())
+                    .unwrap();
+                // charge pc as swap fee, the LP share
+                amm.state_data.swap_acc_pc_fee =
+                    amm.state_data.swap_acc_pc_fee.checked_add(lp_fee).unwrap();
+            }
         };
-        return Ok(pool_info_data);
+        if let Some((
+            referrer_info,
+            referrer_mint_info,
+            referrer_hook_config,
+            referrer_hook_accounts,
+        )) = referrer
+        {
+            if referrer_fee != 0 {
+                // pay the referrer share of the swap fee out of the source vault
+                hook_compute_units += crate::token_invokers::token_transfer_with_hook_budget(
+                    referrer_hook_config.as_ref(),
+                    source_token_program_info.clone(),
+                    source_vault_info.clone(),
+                    referrer_mint_info.cloned(),
+                    referrer_info.clone(),
+                    amm_authority_info.clone(),
+                    referrer_hook_accounts,
+                    referrer_fee,
+                    source_decimals as u8,
+                    &[AUTHORITY_AMM, &[amm.nonce as u8]],
+                )?;
+            }
+        }
+        Self::take_protocol_fee(&mut amm, swap_direction, protocol_fee)?;
+        Self::write_trade_contexts(program_id, &TradeContext::default(), trade_context_sides)?;
+        let (total_coin, total_pc) = Self::totals_after_swap(
+            swap_direction,
+            total_coin_without_take_pnl,
+            total_pc_without_take_pnl,
+            swap_amount_in - protocol_fee - referrer_fee,
+            swap_amount_out,
+        )?;
+        Self::update_prices(
+            program_id,
+            &amm,
+            amm_extension.as_deref_mut(),
+            price_oracle_info,
+            total_coin,
+            total_pc,
+        )?;
+        swap_log.hook_compute_units = hook_compute_units;
+        encode_ray_log(swap_log);
+        amm.recent_epoch = Clock::get()?.epoch;
+
+        amm.unlock();
+        Ok(())
     }
 
-    fn simulate_swap_base_in(
+    pub fn process_swap_base_out(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        simulate: SimulateInstruction,
-    ) -> Result<GetSwapBaseInData, ProgramError> {
+        swap: SwapInstructionBaseOut,
+    ) -> ProgramResult {
+        // a pool with a price oracle takes it as the last account, after the pc side token program
+        let (accounts, price_oracle_info) = Self::split_price_oracle(program_id, accounts, 1)?;
+        const SWAP_ACCOUNT_NUM: usize = 17;
+        let input_account_len = accounts.len();
+        if input_account_len < SWAP_ACCOUNT_NUM {
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
         let account_info_iter = &mut accounts.iter();
+        let coin_token_program_info = next_account_info(account_info_iter)?;
 
         let amm_info = next_account_info(account_info_iter)?;
         let amm_authority_info = next_account_info(account_info_iter)?;
         let amm_open_orders_info = next_account_info(account_info_iter)?;
-        let amm_target_orders_info = next_account_info(account_info_iter)?;
+        let (amm, amm_extension) = AmmInfo::load_mut_checked_extended(amm_info, program_id)?;
+        // the optional target orders account is identified by key, since Token-2022
+        // transfer hook accounts may follow the fixed accounts.
+        if input_account_len > SWAP_ACCOUNT_NUM && *accounts[4].key == amm.target_orders {
+            let _amm_target_orders_info = next_account_info(account_info_iter)?;
+        }
         let amm_coin_vault_info = next_account_info(account_info_iter)?;
         let amm_pc_vault_info = next_account_info(account_info_iter)?;
-        let amm_lp_mint_info = next_account_info(account_info_iter)?;
 
         let market_program_info = next_account_info(account_info_iter)?;
+
+
         let market_info = next_account_info(account_info_iter)?;
+        let market_bids_info = next_account_info(account_info_iter)?;
+        let market_asks_info = next_account_info(account_info_iter)?;
         let market_event_queue_info = next_account_info(account_info_iter)?;
+        let market_coin_vault_info = next_account_info(account_info_iter)?;
+        let market_pc_vault_info = next_account_info(account_info_iter)?;
+        let market_vault_signer = next_account_info(account_info_iter)?;
 
         let user_source_info = next_account_info(account_info_iter)?;
         let user_destination_info = next_account_info(account_info_iter)?;
         let user_source_owner = next_account_info(account_info_iter)?;
+        // transfer hook extra accounts for Token-2022 mints
+        let remaining_accounts = account_info_iter.as_slice();
+
+        // pools whose sides use different token programs take the pc side token
+        // program as the last account, after the transfer hook accounts
+        let (remaining_accounts, pc_token_program_info) =
+            if amm.coin_token_program() != amm.pc_token_program() {
+                match remaining_accounts.split_last() {
+                    Some((last, rest)) => (rest, last),
+                    None => return Err(AmmError::WrongAccountsNumber.into()),
+                }
+            } else {
+                (remaining_accounts, coin_token_program_info)
+            };
+        let orderbook = if AmmStatus::from_u64(amm.status).orderbook_permission() {
+            Some(SwapOrderbook {
+                amm_open_orders_info,
+                market_program_info,
+                market_info,
+                market_bids_info,
+                market_asks_info,
+                market_event_queue_info,
+                market_coin_vault_info,
+                market_pc_vault_info,
+                market_vault_signer,
+            })
+        } else {
+            None
+        };
+        let swap_accounts = SwapAccounts {
+            coin_token_program_info,
+            pc_token_program_info,
+            amm_info,
+            amm_authority_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            user_source_info,
+            user_destination_info,
+            user_source_owner,
+            orderbook,
+            remaining_accounts,
+            price_oracle_info,
+        };
+        Self::swap_base_out(program_id, swap_accounts, amm, amm_extension, swap)
+    }
 
-        Self::check_account_readonly(amm_info)?;
-        Self::check_account_readonly(amm_open_orders_info)?;
-        Self::check_account_readonly(amm_target_orders_info)?;
-        Self::check_account_readonly(amm_coin_vault_info)?;
-        Self::check_account_readonly(amm_pc_vault_info)?;
-        Self::check_account_readonly(amm_lp_mint_info)?;
-        Self::check_account_readonly(market_info)?;
-        Self::check_account_readonly(market_event_queue_info)?;
-        Self::check_account_readonly(user_source_info)?;
-        Self::check_account_readonly(user_destination_info)?;
-        Self::check_account_readonly(user_source_owner)?;
-
-        let mut swap_base_in: GetSwapBaseInData = Default::default();
-        if let Some(swap) = simulate.swap_base_in_value {
-            swap_base_in.amount_in = swap.amount_in;
-
-            if !user_source_owner.is_signer {
-                return Err(AmmError::InvalidSignAccount.into());
-            }
-            let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+    /// Swap of a `SwapBaseOut` or `SwapBaseOutNoOrderbook`, on the accounts parsed from its layout
+    fn swap_base_out<'a, 'b>(
+        program_id: &Pubkey,
+        swap_accounts: SwapAccounts<'a, 'b>,
+        mut amm: RefMut<'a, AmmInfo>,
+        mut amm_extension: Option<RefMut<'a, AmmExtension>>,
+        swap: SwapInstructionBaseOut,
+    ) -> ProgramResult {
+        let SwapAccounts {
+            coin_token_program_info,
+            pc_token_program_info,
+            amm_info,
+            amm_authority_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            user_source_info,
+            user_destination_info,
+            user_source_owner,
+            orderbook,
+            remaining_accounts,
+            price_oracle_info,
+        } = swap_accounts;
+        if !user_source_owner.is_signer {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        Self::check_token_programs(&amm, coin_token_program_info, pc_token_program_info)?;
+        // an SPL Token pool only takes the referrer token account of a fee split
+        if *coin_token_program_info.key == spl_token::id()
+            && *pc_token_program_info.key == spl_token::id()
+            && remaining_accounts.len() > amm.fee_split(amm_extension.as_deref()).is_some() as usize
+        {
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
+        let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
+        check_assert_eq!(
+            *amm_authority_info.key,
+            authority,
+            "authority",
+            AmmError::InvalidProgramAddress
+        );
+        check_assert_eq!(
+            *amm_coin_vault_info.key,
+            amm.coin_vault,
+            "coin_vault",
+            AmmError::InvalidCoinVault
+        );
+        check_assert_eq!(
+            *amm_pc_vault_info.key,
+            amm.pc_vault,
+            "pc_vault",
+            AmmError::InvalidPCVault
+        );
 
-            if !AmmStatus::from_u64(amm.status).swap_permission() {
-                msg!("simulate_swap_base_in: status {}", identity(amm.status));
+        if *user_source_info.key == amm.pc_vault || *user_source_info.key == amm.coin_vault {
+            return Err(AmmError::InvalidUserToken.into());
+        }
+        if *user_destination_info.key == amm.pc_vault
+            || *user_destination_info.key == amm.coin_vault
+        {
+            return Err(AmmError::InvalidUserToken.into());
+        }
+
+        let amm_coin_vault = crate::token_utils::unpack_token_account(
+            &amm_coin_vault_info,
+            coin_token_program_info.key,
+        )?;
+        let amm_pc_vault = crate::token_utils::unpack_token_account(
+            &amm_pc_vault_info,
+            pc_token_program_info.key,
+        )?;
+
+        // user accounts are checked against the vault mints below, which pins their side
+        let user_source =
+            crate::token_utils::unpack_token_account(&user_source_info, user_source_info.owner)?;
+        let user_destination = crate::token_utils::unpack_token_account(
+            &user_destination_info,
+            user_destination_info.owner,
+        )?;
+
+        if !AmmStatus::from_u64(amm.status).swap_permission() {
+            msg!(&format!("swap_base_out: status {}", identity(amm.status)));
+            let clock = Clock::get()?;
+            if amm.status == AmmStatus::OrderBookOnly.into_u64()
+                && (clock.unix_timestamp as u64) >= amm.state_data.orderbook_to_init_time
+            {
+                amm.status = AmmStatus::Initialized.into_u64();
+                msg!("swap_base_out: OrderBook to Initialized");
+            } else {
                 return Err(AmmError::InvalidStatus.into());
             }
-            let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
-            check_assert_eq!(
-                *amm_authority_info.key,
-                authority,
-                "authority",
-                AmmError::InvalidProgramAddress
-            );
-            check_assert_eq!(
-                *amm_coin_vault_info.key,
-                amm.coin_vault,
-                "coin_vault",
-                AmmError::InvalidCoinVault
-            );
-            check_assert_eq!(
-                *amm_pc_vault_info.key,
-                amm.pc_vault,
-                "pc_vault",
-                AmmError::InvalidPCVault
-            );
+        } else if amm.status == AmmStatus::WaitingTrade.into_u64() {
+            let clock = Clock::get()?;
+            if (clock.unix_timestamp as u64) < amm.state_data.pool_open_time {
+                return Err(AmmError::InvalidStatus.into());
+            } else {
+                amm.status = AmmStatus::SwapOnly.into_u64();
+                msg!("swap_base_out: WaitingTrade to SwapOnly");
+            }
+        }
+        let coin_mint_info = remaining_accounts
+            .iter()
+            .find(|account| *account.key == amm.coin_vault_mint);
+        let pc_mint_info = remaining_accounts
+            .iter()
+            .find(|account| *account.key == amm.pc_vault_mint);
+        if Self::check_hook_programs(&mut amm, coin_mint_info, pc_mint_info)? {
+            msg!("swap_base_out: transfer hook program changed, pool is withdraw only");
+            return Err(AmmError::HookProgramChanged.into());
+        }
+
+        let total_pc_without_take_pnl;
+        let total_coin_without_take_pnl;
+        let mut bids: Vec<LeafNode> = Vec::new();
+        let mut asks: Vec<LeafNode> = Vec::new();
+        if let Some(orderbook) = &orderbook {
             check_assert_eq!(
-                *amm_open_orders_info.key,
+                *orderbook.amm_open_orders_info.key,
                 amm.open_orders,
                 "open_orders",
                 AmmError::InvalidOpenOrders
             );
             check_assert_eq!(
-                *amm_target_orders_info.key,
-                amm.target_orders,
-                "target_orders",
-                AmmError::InvalidTargetOrders
-            );
-            check_assert_eq!(
-                *amm_lp_mint_info.key,
-                amm.lp_mint,
-                "lp_mint",
-                AmmError::InvalidPoolMint
-            );
-            check_assert_eq!(
-                *market_program_info.key,
+                *orderbook.market_program_info.key,
                 amm.market_program,
                 "market_program",
                 AmmError::InvalidMarketProgram
             );
             check_assert_eq!(
-                *market_info.key,
+                *orderbook.market_info.key,
                 amm.market,
                 "market",
                 AmmError::InvalidMarket
             );
-
-            if *user_source_info.key == amm.pc_vault || *user_source_info.key == amm.coin_vault {
-                return Err(AmmError::InvalidInput.into());
-            }
-            if *user_destination_info.key == amm.pc_vault
-                || *user_destination_info.key == amm.coin_vault
-            {
-                return Err(AmmError::InvalidInput.into());
-            }
-
-            let (market_state, open_orders) = Self::load_serum_market_order(
-                market_info,
-                amm_open_orders_info,
+            let (market_state, open_orders) = Processor::load_serum_market_order(
+                orderbook.market_info,
+                orderbook.amm_open_orders_info,
                 amm_authority_info,
                 &amm,
                 false,
             )?;
-
-            let amm_coin_vault =
-                Self::unpack_token_account(&amm_coin_vault_info, &spl_token::id())?;
-            let amm_pc_vault = Self::unpack_token_account(&amm_pc_vault_info, &spl_token::id())?;
-            let lp_mint = Self::unpack_mint(&amm_lp_mint_info, &spl_token::id())?;
-
-            let user_source = Self::unpack_token_account(&user_source_info, &spl_token::id())?;
-            let user_destination =
-                Self::unpack_token_account(&user_destination_info, &spl_token::id())?;
-
-            // let target_orders = TargetOrders::load_mut_checked(&target_orders_info, program_id, amm_info.key)?;
-
-            let swap_direction;
-            if user_source.mint == amm_coin_vault.mint && user_destination.mint == amm_pc_vault.mint
-            {
-                swap_direction = SwapDirection::Coin2PC
-            } else if user_source.mint == amm_pc_vault.mint
-                && user_destination.mint == amm_coin_vault.mint
-            {
-                swap_direction = SwapDirection::PC2Coin
-            } else {
-                return Err(AmmError::InvalidInput.into());
-            }
-            let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+            let bids_orders = market_state.load_bids_checked(orderbook.market_bids_info)?;
+            let asks_orders = market_state.load_asks_checked(orderbook.market_asks_info)?;
+            (bids, asks) = Self::get_amm_orders(&open_orders, bids_orders, asks_orders)?;
+            (total_pc_without_take_pnl, total_coin_without_take_pnl) =
                 Calculator::calc_total_without_take_pnl(
                     amm_pc_vault.amount,
                     amm_coin_vault.amount,
                     &open_orders,
                     &amm,
                     &market_state,
-                    &market_event_queue_info,
-                    &amm_open_orders_info,
+                    orderbook.market_event_queue_info,
+                    orderbook.amm_open_orders_info,
                 )?;
-            swap_base_in.pool_data.status = amm.status;
-            swap_base_in.pool_data.coin_decimals = amm.coin_decimals;
-            swap_base_in.pool_data.pc_decimals = amm.pc_decimals;
-            swap_base_in.pool_data.lp_decimals = lp_mint.decimals.into();
-            swap_base_in.pool_data.pool_lp_supply = amm.lp_amount;
-            swap_base_in.pool_data.pool_open_time = amm.state_data.pool_open_time;
-            swap_base_in.pool_data.pool_pc_amount = total_pc_without_take_pnl;
-            swap_base_in.pool_data.pool_coin_amount = total_coin_without_take_pnl;
-            swap_base_in.pool_data.amm_id = amm_info.key.to_string();
+        } else {
+            (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+                Calculator::calc_total_without_take_pnl_no_orderbook(
+                    amm_pc_vault.amount,
+                    amm_coin_vault.amount,
+                    &amm,
+                )?;
+        }
 
-            let swap_fee = U128::from(swap.amount_in)
-                .checked_mul(amm.fees.swap_fee_numerator.into())
-                .unwrap()
-                .checked_ceil_div(amm.fees.swap_fee_denominator.into())
-                .unwrap()
-                .0;
-            let swap_in_after_deduct_fee =
-                U128::from(swap.amount_in).checked_sub(swap_fee).unwrap();
-            let swap_amount_out = Calculator::swap_token_amount_base_in(
-                swap_in_after_deduct_fee,
+        let swap_direction;
+        if user_source.mint == amm_coin_vault.mint && user_destination.mint == amm_pc_vault.mint {
+            swap_direction = SwapDirection::Coin2PC
+        } else if user_source.mint == amm_pc_vault.mint
+            && user_destination.mint == amm_coin_vault.mint
+        {
+            swap_direction = SwapDirection::PC2Coin
+        } else {
+            return Err(AmmError::InvalidUserToken.into());
+        }
+
+        let (source_mint, source_token_program, destination_mint, destination_token_program) =
+            match swap_direction {
+                SwapDirection::Coin2PC => (
+                    amm.coin_vault_mint,
+                    amm.coin_token_program(),
+                    amm.pc_vault_mint,
+                    amm.pc_token_program(),
+                ),
+                SwapDirection::PC2Coin => (
+                    amm.pc_vault_mint,
+                    amm.pc_token_program(),
+                    amm.coin_vault_mint,
+                    amm.coin_token_program(),
+                ),
+            };
+        // Token-2022 transfer fees, the vault sends amount_out grossed up by the outbound fee
+        let epoch = Clock::get()?.epoch;
+        let transfer_fee_out = Self::transfer_inverse_fee(
+            remaining_accounts.iter().find(|account| *account.key == destination_mint),
+            &destination_token_program,
+            epoch,
+            swap.amount_out,
+        )?;
+        let swap_amount_out = swap
+            .amount_out
+            .checked_add(transfer_fee_out)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        let swap_in_before_add_fee = amm
+            .curve(
+                amm_extension.as_deref(),
+                Clock::get()?.unix_timestamp as u64,
+            )?
+            .swap_token_amount_base_out(
+                swap_amount_out.into(),
                 total_pc_without_take_pnl.into(),
                 total_coin_without_take_pnl.into(),
                 swap_direction,
+            )?;
+        // swap_in_after_add_fee * (1 - 0.0025) = swap_in_before_add_fee
+        // swap_in_after_add_fee = swap_in_before_add_fee / (1 - 0.0025)
+        let swap_in_after_add_fee = swap_in_before_add_fee
+            .checked_mul(amm.fees.swap_fee_denominator.into())
+            .unwrap()
+            .checked_ceil_div(
+                (amm.fees
+                    .swap_fee_denominator
+                    .checked_sub(amm.fees.swap_fee_numerator)
+                    .unwrap())
+                .into(),
             )
+            .unwrap()
+            .0
             .as_u64();
-            swap_base_in.minimum_amount_out = swap_amount_out;
-            match swap_direction {
-                SwapDirection::Coin2PC => {
-                    // coin -> pc, need cancel buy order
-                    let token_pc_after_swap = total_pc_without_take_pnl
-                        .checked_sub(swap_amount_out)
-                        .unwrap();
-                    let token_coin_after_swap = total_coin_without_take_pnl
-                        .checked_add(swap.amount_in)
-                        .unwrap();
+        let swap_fee = swap_in_after_add_fee
+            .checked_sub(swap_in_before_add_fee.as_u64())
+            .unwrap();
+        // the user sends swap_in_after_add_fee grossed up by the inbound fee
+        let transfer_fee_in = Self::transfer_inverse_fee(
+            remaining_accounts.iter().find(|account| *account.key == source_mint),
+            &source_token_program,
+            epoch,
+            swap_in_after_add_fee,
+        )?;
+        let swap_amount_in = swap_in_after_add_fee
+            .checked_add(transfer_fee_in)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        // logged once the hooked transfers are metered, or when a check fails
+        let mut swap_log = SwapBaseOutLog {
+            log_type: LogType::SwapBaseOut.into_u8(),
+            max_in: swap.max_amount_in,
+            amount_out: swap.amount_out,
+            direction: swap_direction as u64,
+            user_source: user_source.amount,
+            pool_coin: total_coin_without_take_pnl,
+            pool_pc: total_pc_without_take_pnl,
+            deduct_in: swap_amount_in,
+            transfer_fee_in,
+            transfer_fee_out,
+            hook_compute_units: 0,
+            ..Default::default()
+        };
+        if user_source.amount < swap_amount_in {
+            encode_ray_log(swap_log);
+            return Err(AmmError::InsufficientFunds.into());
+        }
+        if swap.max_amount_in < swap_amount_in {
+            encode_ray_log(swap_log);
+            return Err(AmmError::ExceededSlippage.into());
+        }
+        if swap_in_after_add_fee == 0 || swap.amount_out == 0 {
+            encode_ray_log(swap_log);
+            return Err(AmmError::InvalidInput.into());
+        }
+        let pool_amount_out = match swap_direction {
+            SwapDirection::Coin2PC => total_pc_without_take_pnl,
+            SwapDirection::PC2Coin => total_coin_without_take_pnl,
+        };
+        if swap_amount_out >= pool_amount_out {
+            encode_ray_log(swap_log);
+            return Err(AmmError::InsufficientFunds.into());
+        }
 
-                    let swap_price_before = total_pc_without_take_pnl
-                        .checked_div(total_coin_without_take_pnl)
-                        .unwrap();
-                    let swap_price_after = token_pc_after_swap
-                        .checked_div(token_coin_after_swap)
-                        .unwrap();
-                    swap_base_in.price_impact =
-                        (swap_price_before.checked_sub(swap_price_after).unwrap())
-                            .checked_mul(1000000)
-                            .unwrap()
-                            .checked_div(swap_price_before)
-                            .unwrap();
-                }
-                SwapDirection::PC2Coin => {
-                    // pc -> coin, need cancel sell order
-                    let token_pc_after_swap = total_pc_without_take_pnl
-                        .checked_add(swap.amount_in)
-                        .unwrap();
-                    let token_coin_after_swap = total_coin_without_take_pnl
-                        .checked_sub(swap_amount_out)
-                        .unwrap();
-
-                    let swap_price_before = total_pc_without_take_pnl
-                        .checked_div(total_coin_without_take_pnl)
-                        .unwrap();
-                    let swap_price_after = token_pc_after_swap
-                        .checked_div(token_coin_after_swap)
-                        .unwrap();
-                    swap_base_in.price_impact =
-                        (swap_price_after.checked_sub(swap_price_before).unwrap())
-                            .checked_mul(1000000)
-                            .unwrap()
-                            .checked_div(swap_price_before)
-                            .unwrap();
+        // resolve the transfer hook accounts of both transfers before any CPI
+        let (
+            (source_mint_info, source_hook_config, source_hook_accounts),
+            (destination_mint_info, destination_hook_config, destination_hook_accounts),
+            remaining_accounts,
+        ) = Self::split_swap_hook_accounts(
+            program_id,
+            &amm,
+            swap_direction,
+            user_source_info,
+            user_destination_info,
+            user_source_owner,
+            amm_authority_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            swap_amount_in,
+            swap_amount_out,
+            remaining_accounts,
+        )?;
+        let (lp_fee, protocol_fee, referrer_fee) = amm
+            .fee_split(amm_extension.as_deref())
+            .unwrap_or_default()
+            .shares(swap_fee, !remaining_accounts.is_empty());
+        let (source_vault_info, source_token_program_info, source_decimals) = match swap_direction {
+            SwapDirection::Coin2PC => (
+                amm_coin_vault_info,
+                coin_token_program_info,
+                amm.coin_decimals,
+            ),
+            SwapDirection::PC2Coin => (amm_pc_vault_info, pc_token_program_info, amm.pc_decimals),
+        };
+        let referrer = Self::split_referrer_accounts(
+            program_id,
+            &amm,
+            amm_extension.as_deref(),
+            &source_mint,
+            &source_token_program,
+            source_vault_info,
+            amm_authority_info,
+            referrer_fee,
+            remaining_accounts,
+        )?;
+        swap_log.lp_fee = lp_fee;
+        swap_log.protocol_fee = protocol_fee;
+        swap_log.referrer_fee = referrer_fee;
+
+        // compute units consumed by the hooked transfers, each checked against its hook's budget
+        let mut hook_compute_units = 0u64;
+        amm.lock();
+        // hooks reading the trade context of their mint see the swap during its transfers
+        let trade_context_sides = [
+            (&source_mint, source_hook_accounts),
+            (&destination_mint, destination_hook_accounts),
+        ];
+        Self::write_trade_contexts(
+            program_id,
+            &TradeContext {
+                amm: *amm_info.key,
+                active: 1,
+                direction: swap_direction as u64,
+                amount_in: swap_in_after_add_fee,
+                amount_out: swap_amount_out,
+                coin_reserve: total_coin_without_take_pnl,
+                pc_reserve: total_pc_without_take_pnl,
+                ..Default::default()
+            },
+            trade_context_sides,
+        )?;
+        match swap_direction {
+            SwapDirection::Coin2PC => {
+                if let Some(orderbook) = &orderbook {
+                    // coin -> pc, need cancel buy order
+                    if !bids.is_empty() {
+                        let mut amm_order_ids_vec = Vec::new();
+                        let mut order_ids = [0u64; 8];
+                        let mut count = 0;
+                        // fetch cancel order ids
+                        for order in bids.into_iter() {
+                            order_ids[count] = order.client_order_id();
+                            count += 1;
+                            if count == 8 {
+                                amm_order_ids_vec.push(order_ids);
+                                order_ids = [0u64; 8];
+                                count = 0;
+                            }
+                        }
+                        if count != 0 {
+                            amm_order_ids_vec.push(order_ids);
+                        }
+                        for ids in amm_order_ids_vec.iter() {
+                            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
+                                orderbook.market_program_info.clone(),
+                                orderbook.market_info.clone(),
+                                orderbook.market_bids_info.clone(),
+                                orderbook.market_asks_info.clone(),
+                                orderbook.amm_open_orders_info.clone(),
+                                amm_authority_info.clone(),
+                                orderbook.market_event_queue_info.clone(),
+                                AUTHORITY_AMM,
+                                amm.nonce as u8,
+                                *ids,
+                            )?;
+                        }
+                    }
+                    if swap_amount_out > amm_pc_vault.amount {
+                        // need settle funds
+                        Invokers::invoke_dex_settle_funds(
+                            orderbook.market_program_info.clone(),
+                            orderbook.market_info.clone(),
+                            orderbook.amm_open_orders_info.clone(),
+                            amm_authority_info.clone(),
+                            orderbook.market_coin_vault_info.clone(),
+                            orderbook.market_pc_vault_info.clone(),
+                            amm_coin_vault_info.clone(),
+                            amm_pc_vault_info.clone(),
+                            orderbook.market_vault_signer.clone(),
+                            coin_token_program_info.clone(),
+                            Some(&amm_pc_vault_info.clone()),
+                            AUTHORITY_AMM,
+                            amm.nonce as u8,
+                        )?;
+                    }
+                }
+                // deposit source coin to amm_coin_vault
+                hook_compute_units += crate::token_invokers::token_transfer_with_hook_budget(
+                    source_hook_config.as_ref(),
+                    coin_token_program_info.clone(),
+                    user_source_info.clone(),
+                    source_mint_info.cloned(),
+                    amm_coin_vault_info.clone(),
+                    user_source_owner.clone(),
+                    source_hook_accounts,
+                    swap_amount_in,
+                    amm.coin_decimals as u8,
+                    &[],
+                )?;
+                // withdraw amm_pc_vault to destination pc
+                hook_compute_units += crate::token_invokers::token_transfer_with_hook_budget(
+                    destination_hook_config.as_ref(),
+                    pc_token_program_info.clone(),
+                    amm_pc_vault_info.clone(),
+                    destination_mint_info.cloned(),
+                    user_destination_info.clone(),
+                    amm_authority_info.clone(),
+                    destination_hook_accounts,
+                    swap_amount_out,
+                    amm.pc_decimals as u8,
+                    &[AUTHORITY_AMM, &[amm.nonce as u8]],
+                )?;
+                // update state_data data
+                amm.state_data.swap_coin_in_amount = amm
+                    .state_data
+                    .swap_coin_in_amount
+                    .checked_add(swap_in_after_add_fee.into())
+                    .unwrap();
+                amm.state_data.swap_pc_out_amount = amm
+                    .state_data
+                    .swap_pc_out_amount
+                    .checked_add(Calculator::to_u128(swap_amount_out)?)
+                    .unwrap();
+                // charge coin as swap fee, the LP share
+                amm.state_data.swap_acc_coin_fee = amm
+                    .state_data
+                    .swap_acc_coin_fee
+                    .checked_add(lp_fee)
+                    .unwrap();
+            }
+            SwapDirection::PC2Coin => {
+                if let Some(orderbook) = &orderbook {
+                    // pc -> coin, need cancel sell order
+                    if !asks.is_empty() {
+                        let mut amm_order_ids_vec = Vec::new();
+                        let mut order_ids = [0u64; 8];
+                        let mut count = 0;
+                        // fetch cancel order ids
+                        for order in asks.into_iter() {
+                            order_ids[count] = order.client_order_id();
+                            count += 1;
+                            if count == 8 {
+                                amm_order_ids_vec.push(order_ids);
+                                order_ids = [0u64; 8];
+                                count = 0;
+                            }
+                        }
+                        if count != 0 {
+                            amm_order_ids_vec.push(order_ids);
+                        }
+                        for ids in amm_order_ids_vec.iter() {
+                            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
+                                orderbook.market_program_info.clone(),
+                                orderbook.market_info.clone(),
+                                orderbook.market_bids_info.clone(),
+                                orderbook.market_asks_info.clone(),
+                                orderbook.amm_open_orders_info.clone(),
+                                amm_authority_info.clone(),
+                                orderbook.market_event_queue_info.clone(),
+                                AUTHORITY_AMM,
+                                amm.nonce as u8,
+                                *ids,
+                            )?;
+                        }
+                    }
+                    if swap_amount_out > amm_coin_vault.amount {
+                        Invokers::invoke_dex_settle_funds(
+                            orderbook.market_program_info.clone(),
+                            orderbook.market_info.clone(),
+                            orderbook.amm_open_orders_info.clone(),
+                            amm_authority_info.clone(),
+                            orderbook.market_asks_info.clone(),
+                            orderbook.market_pc_vault_info.clone(),
+                            amm_coin_vault_info.clone(),
+                            amm_pc_vault_info.clone(),
+                            orderbook.market_vault_signer.clone(),
+                            coin_token_program_info.clone(),
+                            Some(&amm_pc_vault_info.clone()),
+                            AUTHORITY_AMM,
+                            amm.nonce as u8,
+                        )?;
+                    }
                 }
+
+                // deposit source pc to amm_pc_vault
+                hook_compute_units += crate::token_invokers::token_transfer_with_hook_budget(
+                    source_hook_config.as_ref(),
+                    pc_token_program_info.clone(),
+                    user_source_info.clone(),
+                    source_mint_info.cloned(),
+                    amm_pc_vault_info.clone(),
+                    user_source_owner.clone(),
+                    source_hook_accounts,
+                    swap_amount_in,
+                    amm.pc_decimals as u8,
+                    &[],
+                )?;
+                // withdraw amm_coin_vault to destination coin
+                hook_compute_units += crate::token_invokers::token_transfer_with_hook_budget(
+                    destination_hook_config.as_ref(),
+                    coin_token_program_info.clone(),
+                    amm_coin_vault_info.clone(),
+                    destination_mint_info.cloned(),
+                    user_destination_info.clone(),
+                    amm_authority_info.clone(),
+                    destination_hook_accounts,
+                    swap_amount_out,
+                    amm.coin_decimals as u8,
+                    &[AUTHORITY_AMM, &[amm.nonce as u8]],
+                )?;
+                // update state_data data
+                amm.state_data.swap_pc_in_amount = amm
+                    .state_data
+                    .swap_pc_in_amount
+                    .checked_add(swap_in_after_add_fee.into())
+                    .unwrap();
+                amm.state_data.swap_coin_out_amount = amm
+                    .state_data
+                    .swap_coin_out_amount
+                    .checked_add(swap_amount_out.into())
+                    .unwrap();
+                // charge pc as swap fee, the LP share
+                amm.state_data.swap_acc_pc_fee =
+                    amm.state_data.swap_acc_pc_fee.checked_add(lp_fee).unwrap();
+            }
+        };
+        if let Some((
+            referrer_info,
+            referrer_mint_info,
+            referrer_hook_config,
+            referrer_hook_accounts,
+        )) = referrer
+        {
+            if referrer_fee != 0 {
+                // pay the referrer share of the swap fee out of the source vault
+                hook_compute_units += crate::token_invokers::token_transfer_with_hook_budget(
+                    referrer_hook_config.as_ref(),
+                    source_token_program_info.clone(),
+                    source_vault_info.clone(),
+                    referrer_mint_info.cloned(),
+                    referrer_info.clone(),
+                    amm_authority_info.clone(),
+                    referrer_hook_accounts,
+                    referrer_fee,
+                    source_decimals as u8,
+                    &[AUTHORITY_AMM, &[amm.nonce as u8]],
+                )?;
             }
         }
-        return Ok(swap_base_in);
+        Self::take_protocol_fee(&mut amm, swap_direction, protocol_fee)?;
+        Self::write_trade_contexts(program_id, &TradeContext::default(), trade_context_sides)?;
+        let (total_coin, total_pc) = Self::totals_after_swap(
+            swap_direction,
+            total_coin_without_take_pnl,
+            total_pc_without_take_pnl,
+            swap_in_after_add_fee - protocol_fee - referrer_fee,
+            swap_amount_out,
+        )?;
+        Self::update_prices(
+            program_id,
+            &amm,
+            amm_extension.as_deref_mut(),
+            price_oracle_info,
+            total_coin,
+            total_pc,
+        )?;
+        swap_log.hook_compute_units = hook_compute_units;
+        encode_ray_log(swap_log);
+        amm.recent_epoch = Clock::get()?.epoch;
+
+        amm.unlock();
+        Ok(())
     }
 
-    fn simulate_swap_base_out(
+    /// Check a pool of a `SwapRoute` and compute its swap of `amount` of `input_mint` transferred
+    /// into it. Fails with `HookProgramChanged` when a transfer hook program of the pool
+    /// changed, see `check_hook_programs`.
+    fn load_route_hop<'a, 'b>(
         program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        simulate: SimulateInstruction,
-    ) -> Result<GetSwapBaseOutData, ProgramError> {
-        let account_info_iter = &mut accounts.iter();
-
-        let amm_info = next_account_info(account_info_iter)?;
-        let amm_authority_info = next_account_info(account_info_iter)?;
-        let amm_open_orders_info = next_account_info(account_info_iter)?;
-        let amm_target_orders_info = next_account_info(account_info_iter)?;
-        let amm_coin_vault_info = next_account_info(account_info_iter)?;
-        let amm_pc_vault_info = next_account_info(account_info_iter)?;
-        let amm_lp_mint_info = next_account_info(account_info_iter)?;
-
-        let market_program_info = next_account_info(account_info_iter)?;
-        let market_info = next_account_info(account_info_iter)?;
-        let market_event_queue_info = next_account_info(account_info_iter)?;
-
-        let user_source_info = next_account_info(account_info_iter)?;
-        let user_destination_info = next_account_info(account_info_iter)?;
-        let user_source_owner = next_account_info(account_info_iter)?;
-
-        Self::check_account_readonly(amm_info)?;
-        Self::check_account_readonly(amm_open_orders_info)?;
-        Self::check_account_readonly(amm_target_orders_info)?;
-        Self::check_account_readonly(amm_coin_vault_info)?;
-        Self::check_account_readonly(amm_pc_vault_info)?;
-        Self::check_account_readonly(amm_lp_mint_info)?;
-        Self::check_account_readonly(market_info)?;
-        Self::check_account_readonly(market_event_queue_info)?;
-        Self::check_account_readonly(user_source_info)?;
-        Self::check_account_readonly(user_destination_info)?;
-        Self::check_account_readonly(user_source_owner)?;
-
-        let mut swap_base_out: GetSwapBaseOutData = Default::default();
-        if let Some(swap) = simulate.swap_base_out_value {
-            swap_base_out.amount_out = swap.amount_out;
+        accounts: &'a [AccountInfo<'b>],
+        transfer_accounts: &[AccountInfo<'b>],
+        user_accounts: &[AccountInfo<'b>],
+        input_mint: &Pubkey,
+        amount: u64,
+        clock: &Clock,
+    ) -> Result<RouteHop<'a, 'b>, ProgramError> {
+        let coin_token_program_info = &accounts[0];
+        let pc_token_program_info = &accounts[1];
+        let amm_info = &accounts[2];
+        let amm_authority_info = &accounts[3];
+        let amm_open_orders_info = &accounts[4];
+        let amm_coin_vault_info = &accounts[5];
+        let amm_pc_vault_info = &accounts[6];
+        let market_program_info = &accounts[7];
+        let market_info = &accounts[8];
+        let market_bids_info = &accounts[9];
+        let market_asks_info = &accounts[10];
+        let market_event_queue_info = &accounts[11];
+
+        let (mut amm, amm_extension) = AmmInfo::load_mut_checked_extended(amm_info, program_id)?;
+        Self::check_token_programs(&amm, coin_token_program_info, pc_token_program_info)?;
+        if *amm_authority_info.key
+            != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
+        {
+            return Err(AmmError::InvalidProgramAddress.into());
+        }
+        check_assert_eq!(
+            *amm_coin_vault_info.key,
+            amm.coin_vault,
+            "coin_vault",
+            AmmError::InvalidCoinVault
+        );
+        check_assert_eq!(
+            *amm_pc_vault_info.key,
+            amm.pc_vault,
+            "pc_vault",
+            AmmError::InvalidPCVault
+        );
+        if user_accounts
+            .iter()
+            .any(|account| *account.key == amm.coin_vault || *account.key == amm.pc_vault)
+        {
+            return Err(AmmError::InvalidUserToken.into());
+        }
+        let swap_direction = if *input_mint == amm.coin_vault_mint {
+            SwapDirection::Coin2PC
+        } else if *input_mint == amm.pc_vault_mint {
+            SwapDirection::PC2Coin
+        } else {
+            return Err(AmmError::InvalidUserToken.into());
+        };
 
-            if !user_source_owner.is_signer {
-                return Err(AmmError::InvalidSignAccount.into());
+        let enable_orderbook = AmmStatus::from_u64(amm.status).orderbook_permission();
+        if !AmmStatus::from_u64(amm.status).swap_permission() {
+            msg!(&format!("swap_route: status {}", identity(amm.status)));
+            if amm.status == AmmStatus::OrderBookOnly.into_u64()
+                && (clock.unix_timestamp as u64) >= amm.state_data.orderbook_to_init_time
+            {
+                amm.status = AmmStatus::Initialized.into_u64();
+                msg!("swap_route: OrderBook to Initialized");
+            } else {
+                return Err(AmmError::InvalidStatus.into());
             }
-            let amm = AmmInfo::load_checked(&amm_info, program_id)?;
-            if !AmmStatus::from_u64(amm.status).swap_permission() {
-                msg!("simulate_swap_base_out: status {}", identity(amm.status));
+        } else if amm.status == AmmStatus::WaitingTrade.into_u64() {
+            if (clock.unix_timestamp as u64) < amm.state_data.pool_open_time {
                 return Err(AmmError::InvalidStatus.into());
+            } else {
+                amm.status = AmmStatus::SwapOnly.into_u64();
+                msg!("swap_route: WaitingTrade to SwapOnly");
             }
-            let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
-            check_assert_eq!(
-                *amm_authority_info.key,
-                authority,
-                "authority",
-                AmmError::InvalidProgramAddress
-            );
-            check_assert_eq!(
-                *amm_coin_vault_info.key,
-                amm.coin_vault,
-                "coin_vault",
-                AmmError::InvalidCoinVault
-            );
-            check_assert_eq!(
-                *amm_pc_vault_info.key,
-                amm.pc_vault,
-                "pc_vault",
-                AmmError::InvalidPCVault
-            );
+        }
+        let coin_mint_info = transfer_accounts
+            .iter()
+            .find(|account| *account.key == amm.coin_vault_mint);
+        let pc_mint_info = transfer_accounts
+            .iter()
+            .find(|account| *account.key == amm.pc_vault_mint);
+        if Self::check_hook_programs(&mut amm, coin_mint_info, pc_mint_info)? {
+            msg!("swap_route: transfer hook program of {} changed", amm_info.key);
+            return Err(AmmError::HookProgramChanged.into());
+        }
+
+        let amm_coin_vault = crate::token_utils::unpack_token_account(
+            amm_coin_vault_info,
+            coin_token_program_info.key,
+        )?;
+        let amm_pc_vault =
+            crate::token_utils::unpack_token_account(amm_pc_vault_info, pc_token_program_info.key)?;
+        let mut orders: Vec<LeafNode> = Vec::new();
+        let (total_pc_without_take_pnl, total_coin_without_take_pnl) = if enable_orderbook {
             check_assert_eq!(
                 *amm_open_orders_info.key,
                 amm.open_orders,
                 "open_orders",
                 AmmError::InvalidOpenOrders
             );
-            check_assert_eq!(
-                *amm_target_orders_info.key,
-                amm.target_orders,
-                "target_orders",
-                AmmError::InvalidTargetOrders
-            );
-            check_assert_eq!(
-                *amm_lp_mint_info.key,
-                amm.lp_mint,
-                "lp_mint",
-                AmmError::InvalidPoolMint
-            );
             check_assert_eq!(
                 *market_program_info.key,
                 amm.market_program,
@@ -3877,252 +7087,1513 @@ impl Processor {
                 "market",
                 AmmError::InvalidMarket
             );
-
-            if *user_source_info.key == amm.pc_vault || *user_source_info.key == amm.coin_vault {
-                return Err(AmmError::InvalidInput.into());
-            }
-            if *user_destination_info.key == amm.pc_vault
-                || *user_destination_info.key == amm.coin_vault
-            {
-                return Err(AmmError::InvalidInput.into());
-            }
-
-            let (market_state, open_orders) = Self::load_serum_market_order(
+            let (market_state, open_orders) = Processor::load_serum_market_order(
                 market_info,
                 amm_open_orders_info,
                 amm_authority_info,
                 &amm,
                 false,
             )?;
+            let bids_orders = market_state.load_bids_checked(market_bids_info)?;
+            let asks_orders = market_state.load_asks_checked(market_asks_info)?;
+            let (bids, asks) = Self::get_amm_orders(&open_orders, bids_orders, asks_orders)?;
+            // coin -> pc cancels the buy orders, pc -> coin the sell orders
+            orders = match swap_direction {
+                SwapDirection::Coin2PC => bids,
+                SwapDirection::PC2Coin => asks,
+            };
+            Calculator::calc_total_without_take_pnl(
+                amm_pc_vault.amount,
+                amm_coin_vault.amount,
+                &open_orders,
+                &amm,
+                &market_state,
+                market_event_queue_info,
+                amm_open_orders_info,
+            )?
+        } else {
+            Calculator::calc_total_without_take_pnl_no_orderbook(
+                amm_pc_vault.amount,
+                amm_coin_vault.amount,
+                &amm,
+            )?
+        };
 
-            let amm_coin_vault =
-                Self::unpack_token_account(&amm_coin_vault_info, &spl_token::id())?;
-            let amm_pc_vault = Self::unpack_token_account(&amm_pc_vault_info, &spl_token::id())?;
-            let lp_mint = Self::unpack_mint(&amm_lp_mint_info, &spl_token::id())?;
-
-            let user_swap_source = Self::unpack_token_account(&user_source_info, &spl_token::id())?;
-            let user_swap_destination =
-                Self::unpack_token_account(&user_destination_info, &spl_token::id())?;
-
-            // let target_orders = TargetOrders::load_mut_checked(&target_orders_info, program_id, amm_info.key)?;
-
-            let swap_direction;
-            if user_swap_source.mint == amm_coin_vault.mint
-                && user_swap_destination.mint == amm_pc_vault.mint
-            {
-                swap_direction = SwapDirection::Coin2PC
-            } else if user_swap_source.mint == amm_pc_vault.mint
-                && user_swap_destination.mint == amm_coin_vault.mint
-            {
-                swap_direction = SwapDirection::PC2Coin
-            } else {
-                return Err(AmmError::InvalidInput.into());
-            }
-            let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
-                Calculator::calc_total_without_take_pnl(
-                    amm_pc_vault.amount,
-                    amm_coin_vault.amount,
-                    &open_orders,
-                    &amm,
-                    &market_state,
-                    &market_event_queue_info,
-                    &amm_open_orders_info,
-                )?;
-            swap_base_out.pool_data.status = amm.status;
-            swap_base_out.pool_data.coin_decimals = amm.coin_decimals;
-            swap_base_out.pool_data.pc_decimals = amm.pc_decimals;
-            swap_base_out.pool_data.lp_decimals = lp_mint.decimals.into();
-            swap_base_out.pool_data.pool_lp_supply = amm.lp_amount;
-            swap_base_out.pool_data.pool_open_time = amm.state_data.pool_open_time;
-            swap_base_out.pool_data.pool_pc_amount = total_pc_without_take_pnl;
-            swap_base_out.pool_data.pool_coin_amount = total_coin_without_take_pnl;
-            swap_base_out.pool_data.amm_id = amm_info.key.to_string();
-
-            let swap_in_before_add_fee = Calculator::swap_token_amount_base_out(
-                swap.amount_out.into(),
+        let coin_side = (
+            coin_token_program_info,
+            amm_coin_vault_info,
+            amm.coin_vault_mint,
+            amm.coin_decimals as u8,
+            amm_coin_vault.amount,
+        );
+        let pc_side = (
+            pc_token_program_info,
+            amm_pc_vault_info,
+            amm.pc_vault_mint,
+            amm.pc_decimals as u8,
+            amm_pc_vault.amount,
+        );
+        let (input_side, output_side, pool_amount_out) = match swap_direction {
+            SwapDirection::Coin2PC => (coin_side, pc_side, total_pc_without_take_pnl),
+            SwapDirection::PC2Coin => (pc_side, coin_side, total_coin_without_take_pnl),
+        };
+        // Token-2022 transfer fees, only the amount net of the inbound fee arrives in the vault
+        let transfer_fee_in = Self::transfer_fee(
+            transfer_accounts
+                .iter()
+                .find(|account| *account.key == input_side.2),
+            input_side.0.key,
+            clock.epoch,
+            amount,
+        )?;
+        let swap_amount_in = amount
+            .checked_sub(transfer_fee_in)
+            .ok_or(AmmError::InvalidInput)?;
+        let swap_fee = U128::from(swap_amount_in)
+            .checked_mul(amm.fees.swap_fee_numerator.into())
+            .unwrap()
+            .checked_ceil_div(amm.fees.swap_fee_denominator.into())
+            .unwrap()
+            .0;
+        let swap_in_after_deduct_fee = U128::from(swap_amount_in).checked_sub(swap_fee).unwrap();
+        let swap_amount_out = amm
+            .curve(
+                amm_extension.as_deref(),
+                Clock::get()?.unix_timestamp as u64,
+            )?
+            .swap_token_amount_base_in(
+                swap_in_after_deduct_fee,
                 total_pc_without_take_pnl.into(),
                 total_coin_without_take_pnl.into(),
                 swap_direction,
-            );
-
-            // swap_in_after_add_fee * (1 - 0.0025) = swap_in_before_add_fee
-            // swap_in_after_add_fee = swap_in_before_add_fee / (1 - 0.0025)
-            let swap_in_after_add_fee = swap_in_before_add_fee
-                .checked_mul(amm.fees.swap_fee_denominator.into())
-                .unwrap()
-                .checked_ceil_div(
-                    (amm.fees
-                        .swap_fee_denominator
-                        .checked_sub(amm.fees.swap_fee_numerator)
-                        .unwrap())
-                    .into(),
-                )
-                .unwrap()
-                .0
-                .as_u64();
-            swap_base_out.max_amount_in = swap_in_after_add_fee;
+            )?
+            .as_u64();
+        if swap_amount_out == 0 || swap_amount_in == 0 {
+            return Err(AmmError::InvalidInput.into());
+        }
+        if swap_amount_out >= pool_amount_out {
+            return Err(AmmError::InsufficientFunds.into());
+        }
 
-            match swap_direction {
-                SwapDirection::Coin2PC => {
-                    // coin -> pc, need cancel buy order
-                    let token_pc_after_swap = total_pc_without_take_pnl
-                        .checked_sub(swap.amount_out)
-                        .unwrap();
-                    let token_coin_after_swap = total_coin_without_take_pnl
-                        .checked_add(swap_in_after_add_fee)
-                        .unwrap();
+        Ok(RouteHop {
+            accounts,
+            amm,
+            amm_extension,
+            amm_authority_info,
+            swap_direction,
+            enable_orderbook,
+            orders,
+            total_coin_without_take_pnl,
+            total_pc_without_take_pnl,
+            input_token_program_info: input_side.0,
+            input_vault_info: input_side.1,
+            input_mint: input_side.2,
+            input_decimals: input_side.3,
+            output_token_program_info: output_side.0,
+            output_vault_info: output_side.1,
+            output_mint: output_side.2,
+            output_decimals: output_side.3,
+            output_vault_amount: output_side.4,
+            amount_in: amount,
+            transfer_fee_in,
+            swap_amount_in,
+            swap_fee: swap_fee.as_u64(),
+            swap_amount_out,
+        })
+    }
 
-                    let swap_price_before = total_pc_without_take_pnl
-                        .checked_div(total_coin_without_take_pnl)
-                        .unwrap();
-                    let swap_price_after = token_pc_after_swap
-                        .checked_div(token_coin_after_swap)
-                        .unwrap();
-                    swap_base_out.price_impact =
-                        (swap_price_before.checked_sub(swap_price_after).unwrap())
-                            .checked_mul(1000000)
-                            .unwrap()
-                            .checked_div(swap_price_before)
-                            .unwrap();
-                }
-                SwapDirection::PC2Coin => {
-                    // pc -> coin, need cancel sell order
-                    let token_pc_after_swap = total_pc_without_take_pnl
-                        .checked_add(swap_in_after_add_fee)
-                        .unwrap();
-                    let token_coin_after_swap = total_coin_without_take_pnl
-                        .checked_sub(swap.amount_out)
-                        .unwrap();
+    /// Processes a [SwapRoute](enum.Instruction.html).
+    pub fn process_swap_route(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        route: SwapRouteInstruction,
+    ) -> ProgramResult {
+        const ACCOUNT_LEN: usize = 3;
+        let hop_count = route.hop_count as usize;
+        if hop_count == 0 || hop_count > MAX_SWAP_ROUTE_HOPS {
+            return Err(AmmError::InvalidInput.into());
+        }
+        if accounts.len() < ACCOUNT_LEN + hop_count * SWAP_ROUTE_HOP_ACCOUNT_LEN {
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
+        let (user_accounts, rest) = accounts.split_at(ACCOUNT_LEN);
+        let (hop_accounts, transfer_accounts) =
+            rest.split_at(hop_count * SWAP_ROUTE_HOP_ACCOUNT_LEN);
+        // the pools with a price oracle take it after the transfer accounts, in route order
+        let mut price_oracle_count = 0;
+        for pool_accounts in hop_accounts.chunks(SWAP_ROUTE_HOP_ACCOUNT_LEN) {
+            if AmmInfo::load_checked(&pool_accounts[2], program_id)?.has_price_oracle() {
+                price_oracle_count += 1;
+            }
+        }
+        let (transfer_accounts, price_oracle_accounts) = transfer_accounts.split_at(
+            transfer_accounts
+                .len()
+                .checked_sub(price_oracle_count)
+                .ok_or(AmmError::InvalidPriceOracle)?,
+        );
+        let user_source_info = &user_accounts[0];
+        let user_destination_info = &user_accounts[1];
+        let user_source_owner = &user_accounts[2];
+        if !user_source_owner.is_signer {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        let user_source =
+            crate::token_utils::unpack_token_account(user_source_info, user_source_info.owner)?;
+        let user_destination = crate::token_utils::unpack_token_account(
+            user_destination_info,
+            user_destination_info.owner,
+        )?;
+        if user_source.amount < route.amount_in {
+            return Err(AmmError::InsufficientFunds.into());
+        }
 
-                    let swap_price_before = total_pc_without_take_pnl
-                        .checked_div(total_coin_without_take_pnl)
-                        .unwrap();
-                    let swap_price_after = token_pc_after_swap
-                        .checked_div(token_coin_after_swap)
-                        .unwrap();
-                    swap_base_out.price_impact =
-                        (swap_price_after.checked_sub(swap_price_before).unwrap())
-                            .checked_mul(1000000)
-                            .unwrap()
-                            .checked_div(swap_price_before)
-                            .unwrap();
+        let clock = Clock::get()?;
+        let mut hops: Vec<RouteHop> = Vec::with_capacity(hop_count);
+        // each pool swaps what the previous one delivers, the first one the user's amount_in
+        let (mut mint, mut amount) = (user_source.mint, route.amount_in);
+        for pool_accounts in hop_accounts.chunks(SWAP_ROUTE_HOP_ACCOUNT_LEN) {
+            if hops
+                .iter()
+                .any(|hop| hop.accounts[2].key == pool_accounts[2].key)
+            {
+                msg!("swap_route: pool {} is repeated", pool_accounts[2].key);
+                return Err(AmmError::InvalidInput.into());
+            }
+            let hop = Self::load_route_hop(
+                program_id,
+                pool_accounts,
+                transfer_accounts,
+                user_accounts,
+                &mint,
+                amount,
+                &clock,
+            )?;
+            (mint, amount) = (hop.output_mint, hop.swap_amount_out);
+            hops.push(hop);
+        }
+        if user_destination.mint != mint {
+            return Err(AmmError::InvalidUserToken.into());
+        }
+        let mut price_oracle_infos = price_oracle_accounts.iter();
+        let mut hop_price_oracles = Vec::with_capacity(hop_count);
+        for hop in hops.iter() {
+            let price_oracle_info = if hop.amm.has_price_oracle() {
+                let price_oracle_info = price_oracle_infos
+                    .next()
+                    .ok_or(AmmError::InvalidPriceOracle)?;
+                Self::check_price_oracle(program_id, hop.accounts[2].key, price_oracle_info)?;
+                Some(price_oracle_info)
+            } else {
+                None
+            };
+            hop_price_oracles.push(price_oracle_info);
+        }
+        // the outbound fee of the last transfer is withheld from what the user receives
+        let last_hop = &hops[hop_count - 1];
+        let transfer_fee_out = Self::transfer_fee(
+            transfer_accounts
+                .iter()
+                .find(|account| *account.key == mint),
+            last_hop.output_token_program_info.key,
+            clock.epoch,
+            amount,
+        )?;
+        // logged once the hooked transfers are metered, or when the slippage check fails
+        let mut swap_logs: Vec<SwapBaseInLog> = hops
+            .iter()
+            .enumerate()
+            .map(|(index, hop)| {
+                let last = index + 1 == hop_count;
+                // a route pays no referrer, the LPs keep the referrer share
+                let (lp_fee, protocol_fee, _) = hop
+                    .amm
+                    .fee_split(hop.amm_extension.as_deref())
+                    .unwrap_or_default()
+                    .shares(hop.swap_fee, false);
+                SwapBaseInLog {
+                    log_type: LogType::SwapBaseIn.into_u8(),
+                    amount_in: hop.amount_in,
+                    minimum_out: if last { route.minimum_amount_out } else { 0 },
+                    direction: hop.swap_direction as u64,
+                    user_source: if index == 0 {
+                        user_source.amount
+                    } else {
+                        hop.amount_in
+                    },
+                    pool_coin: hop.total_coin_without_take_pnl,
+                    pool_pc: hop.total_pc_without_take_pnl,
+                    out_amount: hop.swap_amount_out,
+                    transfer_fee_in: hop.transfer_fee_in,
+                    transfer_fee_out: if last { transfer_fee_out } else { 0 },
+                    hook_compute_units: 0,
+                    lp_fee,
+                    protocol_fee,
+                    referrer_fee: 0,
                 }
+            })
+            .collect();
+        if amount.saturating_sub(transfer_fee_out) < route.minimum_amount_out {
+            for swap_log in swap_logs {
+                encode_ray_log(swap_log);
             }
+            return Err(AmmError::ExceededSlippage.into());
         }
-        return Ok(swap_base_out);
-    }
-
-    fn simulate_run_crank(
-        program_id: &Pubkey,
-        accounts: &[AccountInfo],
-    ) -> Result<RunCrankData, ProgramError> {
-        let account_info_iter = &mut accounts.iter();
-        let token_program_info = next_account_info(account_info_iter)?;
-        let clock_info = next_account_info(account_info_iter)?;
 
-        let amm_info = next_account_info(account_info_iter)?;
-        let amm_authority_info = next_account_info(account_info_iter)?;
-        let amm_open_orders_info = next_account_info(account_info_iter)?;
-        let amm_target_orders_info = next_account_info(account_info_iter)?;
-        let amm_coin_vault_info = next_account_info(account_info_iter)?;
-        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+        // resolve the transfer hook accounts of every transfer before any CPI: the user into
+        // the first pool, each pool into the next and the last pool to the user
+        let mut transfers = Vec::with_capacity(hop_count + 1);
+        let mut remaining_accounts = transfer_accounts;
+        for index in 0..=hop_count {
+            let (source_info, authority_info, token_program_id, mint, amount, registry_hooks) =
+                match index.checked_sub(1).map(|from| &hops[from]) {
+                    None => (
+                        user_source_info,
+                        user_source_owner,
+                        *hops[0].input_token_program_info.key,
+                        hops[0].input_mint,
+                        route.amount_in,
+                        hops[0].amm.accepts_registry_hooks(),
+                    ),
+                    Some(from) => (
+                        from.output_vault_info,
+                        from.amm_authority_info,
+                        *from.output_token_program_info.key,
+                        from.output_mint,
+                        from.swap_amount_out,
+                        // a hook between two pools has to be accepted by both
+                        from.amm.accepts_registry_hooks()
+                            && hops
+                                .get(index)
+                                .map_or(true, |to| to.amm.accepts_registry_hooks()),
+                    ),
+                };
+            let destination_info = hops
+                .get(index)
+                .map_or(user_destination_info, |to| to.input_vault_info);
+            let (mint_info, hook_config, hook_accounts, rest) =
+                crate::whitelist::split_transfer_hook_accounts(
+                    program_id,
+                    &mint,
+                    &token_program_id,
+                    source_info,
+                    destination_info,
+                    authority_info,
+                    amount,
+                    remaining_accounts,
+                    registry_hooks,
+                )?;
+            transfers.push((mint_info, hook_config, hook_accounts));
+            remaining_accounts = rest;
+        }
+        if !remaining_accounts.is_empty() {
+            return Err(AmmError::InvalidTransferHookAccounts.into());
+        }
 
-        let market_program_info = next_account_info(account_info_iter)?;
-        let market_info = next_account_info(account_info_iter)?;
-        let market_bids_info = next_account_info(account_info_iter)?;
-        let market_asks_info = next_account_info(account_info_iter)?;
-        let market_event_queue_info = next_account_info(account_info_iter)?;
+        for hop in hops.iter_mut() {
+            hop.amm.lock();
+        }
+        // hooks reading the trade context of their mint see the pool the transfer goes into,
+        // the last pool for the transfer to the user
+        let trade_context_mints: Vec<(Pubkey, Pubkey)> = hops
+            .iter()
+            .map(|hop| (hop.input_mint, hop.output_mint))
+            .collect();
+        let trade_context_sides: Vec<[(&Pubkey, &[AccountInfo]); 2]> = trade_context_mints
+            .iter()
+            .enumerate()
+            .map(|(index, (input_mint, output_mint))| {
+                let output_hook_accounts = if index + 1 == hop_count {
+                    transfers[hop_count].2
+                } else {
+                    &[]
+                };
+                [
+                    (input_mint, transfers[index].2),
+                    (output_mint, output_hook_accounts),
+                ]
+            })
+            .collect();
+        for (hop, sides) in hops.iter().zip(trade_context_sides.iter()) {
+            Self::write_trade_contexts(
+                program_id,
+                &TradeContext {
+                    amm: *hop.accounts[2].key,
+                    active: 1,
+                    direction: hop.swap_direction as u64,
+                    amount_in: hop.swap_amount_in,
+                    amount_out: hop.swap_amount_out,
+                    coin_reserve: hop.total_coin_without_take_pnl,
+                    pc_reserve: hop.total_pc_without_take_pnl,
+                    ..Default::default()
+                },
+                *sides,
+            )?;
+        }
 
-        Self::check_account_readonly(amm_info)?;
-        Self::check_account_readonly(amm_open_orders_info)?;
-        Self::check_account_readonly(amm_target_orders_info)?;
-        Self::check_account_readonly(amm_coin_vault_info)?;
-        Self::check_account_readonly(amm_pc_vault_info)?;
-        Self::check_account_readonly(market_info)?;
-        Self::check_account_readonly(market_bids_info)?;
-        Self::check_account_readonly(market_asks_info)?;
-        Self::check_account_readonly(market_event_queue_info)?;
+        for hop in hops.iter().filter(|hop| hop.enable_orderbook) {
+            let market_program_info = &hop.accounts[7];
+            let market_info = &hop.accounts[8];
+            let market_bids_info = &hop.accounts[9];
+            let market_asks_info = &hop.accounts[10];
+            let market_event_queue_info = &hop.accounts[11];
+            let market_coin_vault_info = &hop.accounts[12];
+            let market_pc_vault_info = &hop.accounts[13];
+            let market_vault_signer = &hop.accounts[14];
+            for orders in hop.orders.chunks(8) {
+                let mut order_ids = [0u64; 8];
+                for (order_id, order) in order_ids.iter_mut().zip(orders) {
+                    *order_id = order.client_order_id();
+                }
+                Invokers::invoke_dex_cancel_orders_by_client_order_ids(
+                    market_program_info.clone(),
+                    market_info.clone(),
+                    market_bids_info.clone(),
+                    market_asks_info.clone(),
+                    hop.accounts[4].clone(),
+                    hop.amm_authority_info.clone(),
+                    market_event_queue_info.clone(),
+                    AUTHORITY_AMM,
+                    hop.amm.nonce as u8,
+                    order_ids,
+                )?;
+            }
+            if hop.swap_amount_out > hop.output_vault_amount {
+                // need settle funds
+                Invokers::invoke_dex_settle_funds(
+                    market_program_info.clone(),
+                    market_info.clone(),
+                    hop.accounts[4].clone(),
+                    hop.amm_authority_info.clone(),
+                    market_coin_vault_info.clone(),
+                    market_pc_vault_info.clone(),
+                    hop.accounts[5].clone(),
+                    hop.accounts[6].clone(),
+                    market_vault_signer.clone(),
+                    hop.accounts[0].clone(),
+                    Some(&hop.accounts[6].clone()),
+                    AUTHORITY_AMM,
+                    hop.amm.nonce as u8,
+                )?;
+            }
+        }
 
-        let amm = AmmInfo::load_checked(amm_info, program_id)?;
-        Self::check_accounts(
-            program_id,
+        for (index, (mint_info, hook_config, hook_accounts)) in transfers.iter().enumerate() {
+            let hook_compute_units = match index.checked_sub(1).map(|from| &hops[from]) {
+                // deposit the user's source into the first pool
+                None => crate::token_invokers::token_transfer_with_hook_budget(
+                    hook_config.as_ref(),
+                    hops[0].input_token_program_info.clone(),
+                    user_source_info.clone(),
+                    mint_info.cloned(),
+                    hops[0].input_vault_info.clone(),
+                    user_source_owner.clone(),
+                    hook_accounts,
+                    route.amount_in,
+                    hops[0].input_decimals,
+                    &[],
+                )?,
+                // withdraw the output of a pool into the next pool or to the user
+                Some(from) => crate::token_invokers::token_transfer_with_hook_budget(
+                    hook_config.as_ref(),
+                    from.output_token_program_info.clone(),
+                    from.output_vault_info.clone(),
+                    mint_info.cloned(),
+                    hops.get(index)
+                        .map_or(user_destination_info, |to| to.input_vault_info)
+                        .clone(),
+                    from.amm_authority_info.clone(),
+                    hook_accounts,
+                    from.swap_amount_out,
+                    from.output_decimals,
+                    &[AUTHORITY_AMM, &[from.amm.nonce as u8]],
+                )?,
+            };
+            swap_logs[index.min(hop_count - 1)].hook_compute_units += hook_compute_units;
+        }
+
+        for ((hop, sides), price_oracle_info) in hops
+            .iter_mut()
+            .zip(trade_context_sides)
+            .zip(hop_price_oracles)
+        {
+            let (swap_amount_in, swap_amount_out) = (hop.swap_amount_in, hop.swap_amount_out);
+            let (lp_fee, protocol_fee, _) = hop
+                .amm
+                .fee_split(hop.amm_extension.as_deref())
+                .unwrap_or_default()
+                .shares(hop.swap_fee, false);
+            let (total_coin, total_pc) = Self::totals_after_swap(
+                hop.swap_direction,
+                hop.total_coin_without_take_pnl,
+                hop.total_pc_without_take_pnl,
+                swap_amount_in - protocol_fee,
+                swap_amount_out,
+            )?;
+            let amm = &mut hop.amm;
+            Self::accumulate_swap(
+                amm,
+                hop.swap_direction,
+                swap_amount_in,
+                swap_amount_out,
+                lp_fee,
+            );
+            Self::take_protocol_fee(amm, hop.swap_direction, protocol_fee)?;
+            Self::write_trade_contexts(program_id, &TradeContext::default(), sides)?;
+            Self::update_prices(
+                program_id,
+                amm,
+                hop.amm_extension.as_deref_mut(),
+                price_oracle_info,
+                total_coin,
+                total_pc,
+            )?;
+            amm.recent_epoch = clock.epoch;
+            amm.unlock();
+        }
+        for swap_log in swap_logs {
+            encode_ray_log(swap_log);
+        }
+        Ok(())
+    }
+
+    pub fn process_migrate_to_openbook(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_sysvar_info = next_account_info(account_info_iter)?;
+
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_open_orders_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+        let amm_target_orders_info = next_account_info(account_info_iter)?;
+
+        let market_program_info = next_account_info(account_info_iter)?;
+        let market_info = next_account_info(account_info_iter)?;
+        let market_bids_info = next_account_info(account_info_iter)?;
+        let market_asks_info = next_account_info(account_info_iter)?;
+        let market_event_queue_info = next_account_info(account_info_iter)?;
+        let market_coin_vault_info = next_account_info(account_info_iter)?;
+        let market_pc_vault_info = next_account_info(account_info_iter)?;
+        let market_vault_signer = next_account_info(account_info_iter)?;
+
+        let new_amm_open_orders_info = next_account_info(account_info_iter)?;
+        let new_market_program_info = next_account_info(account_info_iter)?;
+        let new_market_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
+        if !admin_info.is_signer || *admin_info.key != config_feature::amm_owner::ID {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
+        check_assert_eq!(
+            *amm_authority_info.key,
+            authority,
+            "authority",
+            AmmError::InvalidProgramAddress
+        );
+        let spl_token_program_id = token_program_info.key;
+        check_assert_eq!(
+            *spl_token_program_id,
+            spl_token::id(),
+            "spl_token_program",
+            AmmError::InvalidSplTokenProgram
+        );
+        check_assert_eq!(
+            *system_program_info.key,
+            solana_program::system_program::id(),
+            "sys_program",
+            AmmError::InvalidSysProgramAddress
+        );
+        // old account check
+        check_assert_eq!(
+            *amm_coin_vault_info.key,
+            amm.coin_vault,
+            "coin_vault",
+            AmmError::InvalidCoinVault
+        );
+        check_assert_eq!(
+            *amm_pc_vault_info.key,
+            amm.pc_vault,
+            "pc_vault",
+            AmmError::InvalidPCVault
+        );
+        check_assert_eq!(
+            *amm_target_orders_info.key,
+            amm.target_orders,
+            "target_orders",
+            AmmError::InvalidPCVault
+        );
+        check_assert_eq!(
+            *market_info.key,
+            amm.market,
+            "market",
+            AmmError::InvalidMarket
+        );
+        check_assert_eq!(
+            *market_program_info.key,
+            amm.market_program,
+            "market_program",
+            AmmError::InvalidMarketProgram
+        );
+        check_assert_eq!(
+            *amm_open_orders_info.key,
+            amm.open_orders,
+            "open_orders",
+            AmmError::InvalidOpenOrders
+        );
+        let mut target =
+            TargetOrders::load_mut_checked(&amm_target_orders_info, program_id, amm_info.key)?;
+        let pnl_pc_amount = Calculator::restore_decimal(
+            target.calc_pnl_x.into(),
+            amm.pc_decimals,
+            amm.sys_decimal_value,
+        )
+        .as_u64();
+        let pnl_coin_amount = Calculator::restore_decimal(
+            target.calc_pnl_y.into(),
+            amm.coin_decimals,
+            amm.sys_decimal_value,
+        )
+        .as_u64();
+        // cancel amm orders in old market
+        Self::do_cancel_amm_orders(
             &amm,
-            amm_info,
-            token_program_info,
-            clock_info,
-            market_program_info,
             amm_authority_info,
-            market_info,
             amm_open_orders_info,
-            amm_coin_vault_info,
-            amm_pc_vault_info,
-            amm_target_orders_info,
-            None,
+            market_program_info,
+            market_info,
+            market_bids_info,
+            market_asks_info,
+            market_event_queue_info,
+            AUTHORITY_AMM,
+        )?;
+        // settle assets
+        Invokers::invoke_dex_settle_funds(
+            market_program_info.clone(),
+            market_info.clone(),
+            amm_open_orders_info.clone(),
+            amm_authority_info.clone(),
+            market_coin_vault_info.clone(),
+            market_pc_vault_info.clone(),
+            amm_coin_vault_info.clone(),
+            amm_pc_vault_info.clone(),
+            market_vault_signer.clone(),
+            token_program_info.clone(),
             None,
+            AUTHORITY_AMM,
+            amm.nonce as u8,
         )?;
-        let spl_token_program_id = token_program_info.key;
-        let amm_status = AmmStatus::from_u64(amm.status);
-        let amm_state = AmmState::from_u64(amm.state);
-        let mut run_crank_data = RunCrankData {
-            status: amm.status,
-            state: amm.state,
-            run_crank: false,
-        };
-
-        if amm.reset_flag == AmmResetFlag::ResetYes.into_u64() {
-            run_crank_data.run_crank = true;
-        } else if amm.order_num == 0 {
-            run_crank_data.run_crank = false;
-        } else {
-            match amm_status {
-                AmmStatus::Uninitialized
-                | AmmStatus::Disabled
-                | AmmStatus::WithdrawOnly
-                | AmmStatus::LiquidityOnly
-                | AmmStatus::SwapOnly
-                | AmmStatus::WaitingTrade => {
-                    run_crank_data.run_crank = false;
-                }
-                AmmStatus::Initialized | AmmStatus::OrderBookOnly => match amm_state {
-                    AmmState::IdleState => {
-                        let amm_coin_vault = Processor::unpack_token_account(
-                            &amm_coin_vault_info,
-                            spl_token_program_id,
-                        )?;
-                        let amm_pc_vault = Processor::unpack_token_account(
-                            &amm_pc_vault_info,
-                            spl_token_program_id,
-                        )?;
-                        let target = TargetOrders::load_checked(
-                            &amm_target_orders_info,
-                            program_id,
-                            amm_info.key,
-                        )?;
-                        let (market_state, open_orders) = Processor::load_serum_market_order(
-                            market_info,
-                            amm_open_orders_info,
-                            amm_authority_info,
-                            &amm,
-                            false,
-                        )?;
-                        let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
-                        let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
-                        let (bids, asks) =
-                            Self::get_amm_orders(&open_orders, bids_orders, asks_orders)?;
-                        let native_pc_total = open_orders.native_pc_total;
-                        let native_coin_total = open_orders.native_coin_total;
-                        msg!(&format!("simulate_run_crank pc_amount:{}, native_pc_total:{}, coin_amount:{}, native_coin_total:{}, need_take_pnl_pc:{}, need_take_pnl_coin:{}", amm_pc_vault.amount, native_pc_total, amm_coin_vault.amount, native_coin_total, identity(amm.state_data.need_take_pnl_pc), identity(amm.state_data.need_take_pnl_coin)));
-                        let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
-                            Calculator::calc_total_without_take_pnl(
-                                amm_pc_vault.amount,
-                                amm_coin_vault.amount,
-                                &open_orders,
-                                &amm,
-                                &market_state,
-                                &market_event_queue_info,
-                                &amm_open_orders_info,
-                            )?;
-                        let x = Calculator::normalize_decimal_v2(
+        let (market_state, open_orders) = Self::load_serum_market_order(
+            market_info,
+            amm_open_orders_info,
+            amm_authority_info,
+            &amm,
+            false,
+        )?;
+        if identity(market_state.coin_mint) != amm.coin_vault_mint.to_aligned_bytes() {
+            return Err(AmmError::InvalidCoinMint.into());
+        }
+        if identity(market_state.pc_mint) != amm.pc_vault_mint.to_aligned_bytes() {
+            return Err(AmmError::InvalidPCMint.into());
+        }
+        // Partial filled without consumed assets exclude native total in open_orders will not be settled back to amm.
+        // It is necessary to check that all assets have been indeed settled back to amm
+        let (pc_total_in_serum, coin_total_in_serum) = Calculator::calc_exact_vault_in_serum(
+            &open_orders,
+            &market_state,
+            market_event_queue_info,
+            amm_open_orders_info,
+        )?;
+        if pc_total_in_serum != 0 || coin_total_in_serum != 0 {
+            // Invokers::invoke_dex_close_open_orders(
+            //     serum_dex_info.clone(),
+            //     amm_open_orders_info.clone(),
+            //     authority_info.clone(),
+            //     admin_info.clone(),
+            //     market_info.clone(),
+            //     AUTHORITY_AMM,
+            //     amm.nonce as u8,
+            // )?;
+            msg!(
+                "{}, {}, {}, {}, {:?}",
+                pc_total_in_serum,
+                coin_total_in_serum,
+                identity(open_orders.native_pc_total),
+                identity(open_orders.native_coin_total),
+                identity(open_orders.free_slot_bits)
+            );
+            return Err(AmmError::UnknownAmmError.into());
+        }
+        // check new dex
+        check_assert_eq!(
+            *new_market_program_info.key,
+            config_feature::openbook_program::id(),
+            "new_market_program",
+            AmmError::InvalidMarketProgram
+        );
+        let new_market_pc_lot_size;
+        let new_market_coin_lot_size;
+        {
+            let new_market_state = Market::load_checked(
+                new_market_info,
+                &config_feature::openbook_program::id(),
+                false,
+            )?;
+            new_market_pc_lot_size = new_market_state.pc_lot_size;
+            new_market_coin_lot_size = new_market_state.coin_lot_size;
+            // a wrapped side stays on its wrapper mint, see `split_wrap_accounts`
+            if identity(new_market_state.coin_mint) != amm.coin_vault_mint.to_aligned_bytes() {
+                return Err(AmmError::InvalidCoinMint.into());
+            }
+            if identity(new_market_state.pc_mint) != amm.pc_vault_mint.to_aligned_bytes() {
+                return Err(AmmError::InvalidPCMint.into());
+            }
+        }
+        // create amm open order account
+        Self::generate_amm_associated_account(
+            program_id,
+            new_market_program_info.key,
+            new_market_info,
+            new_amm_open_orders_info,
+            admin_info,
+            system_program_info,
+            rent_sysvar_info,
+            OPEN_ORDER_ASSOCIATED_SEED,
+            size_of::<serum_dex::state::OpenOrders>() + 12,
+        )?;
+        // init open orders account
+        Invokers::invoke_dex_init_open_orders(
+            new_market_program_info.clone(),
+            new_amm_open_orders_info.clone(),
+            amm_authority_info.clone(),
+            new_market_info.clone(),
+            rent_sysvar_info.clone(),
+            AUTHORITY_AMM,
+            amm.nonce as u8,
+        )?;
+        // update pool
+        amm.market_program = *new_market_program_info.key;
+        amm.market = *new_market_info.key;
+        amm.open_orders = *new_amm_open_orders_info.key;
+        let nonce = amm.nonce as u8;
+        let pool_open_time = amm.state_data.pool_open_time;
+        let coin_decimals = amm.coin_decimals as u8;
+        let pc_decimals = amm.pc_decimals as u8;
+        amm.initialize(
+            nonce,
+            pool_open_time,
+            coin_decimals,
+            pc_decimals,
+            new_market_coin_lot_size,
+            new_market_pc_lot_size,
+        )?;
+        amm.status = AmmStatus::WaitingTrade.into_u64();
+        amm.reset_flag = AmmResetFlag::ResetYes.into_u64();
+
+        if new_market_coin_lot_size != market_state.coin_lot_size
+            || new_market_pc_lot_size != market_state.pc_lot_size
+        {
+            // market lot size is different, calc_pnl_x & calc_pnl_x must update
+            target.calc_pnl_x = Calculator::normalize_decimal_v2(
+                pnl_pc_amount,
+                amm.pc_decimals,
+                amm.sys_decimal_value,
+            )
+            .as_u128();
+            target.calc_pnl_y = Calculator::normalize_decimal_v2(
+                pnl_coin_amount,
+                amm.coin_decimals,
+                amm.sys_decimal_value,
+            )
+            .as_u128();
+        }
+        amm.recent_epoch = Clock::get()?.epoch;
+
+        Ok(())
+    }
+
+    /// withdraw_srm
+    pub fn process_withdraw_srm(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        withdrawsrm: WithdrawSrmInstruction,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_owner_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let srm_token_info = next_account_info(account_info_iter)?;
+        let dest_srm_token_info = next_account_info(account_info_iter)?;
+
+        msg!("withdraw_srm: {}", withdrawsrm.amount);
+        let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+        if amm.status == AmmStatus::Uninitialized.into_u64() {
+            msg!(&format!("withdraw_srm: status {}", identity(amm.status)));
+            return Err(AmmError::InvalidStatus.into());
+        }
+        if !amm_owner_info.is_signer || *amm_owner_info.key != config_feature::amm_owner::ID {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        // check_assert_eq!(
+        //     *amm_owner_info.key,
+        //     amm.amm_owner,
+        //     "amm_owner",
+        //     AmmError::InvalidOwner
+        // );
+        check_assert_eq!(
+            *token_program_info.key,
+            spl_token::id(),
+            "spl_token_program",
+            AmmError::InvalidSplTokenProgram
+        );
+        let spl_token_program_id = token_program_info.key;
+        let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
+        check_assert_eq!(
+            *amm_authority_info.key,
+            authority,
+            "authority",
+            AmmError::InvalidProgramAddress
+        );
+        let srm_token = Self::unpack_token_account(&srm_token_info, spl_token_program_id)?;
+        check_assert_eq!(
+            srm_token.owner,
+            *amm_authority_info.key,
+            "srm_token_owner",
+            AmmError::InvalidOwner
+        );
+        if srm_token.mint != srm_token::id() && srm_token.mint != msrm_token::id() {
+            return Err(AmmError::InvalidSrmMint.into());
+        }
+        let dest_srm_token =
+            Self::unpack_token_account(&dest_srm_token_info, spl_token_program_id)?;
+        check_assert_eq!(
+            srm_token.mint,
+            dest_srm_token.mint,
+            "srm_token_mint",
+            AmmError::InvalidInput
+        );
+        if withdrawsrm.amount > srm_token.amount {
+            return Err(AmmError::InsufficientFunds.into());
+        }
+
+        Invokers::token_transfer_with_authority(
+            token_program_info.clone(),
+            srm_token_info.clone(),
+            dest_srm_token_info.clone(),
+            amm_authority_info.clone(),
+            AUTHORITY_AMM,
+            amm.nonce as u8,
+            withdrawsrm.amount,
+        )?;
+
+        Ok(())
+    }
+
+    fn simulate_pool_info(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> Result<GetPoolData, ProgramError> {
+        const ACCOUNT_LEN: usize = 8;
+        let input_account_len = accounts.len();
+        let account_info_iter = &mut accounts.iter();
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_open_orders_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+        let amm_lp_mint_info = next_account_info(account_info_iter)?;
+        let market_info = next_account_info(account_info_iter)?;
+        let market_event_queue_info = next_account_info(account_info_iter)?;
+
+        let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+        let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
+        Self::check_account_readonly(amm_info)?;
+        Self::check_account_readonly(amm_open_orders_info)?;
+        Self::check_account_readonly(amm_coin_vault_info)?;
+        Self::check_account_readonly(amm_pc_vault_info)?;
+        Self::check_account_readonly(amm_lp_mint_info)?;
+        Self::check_account_readonly(market_info)?;
+        Self::check_account_readonly(market_event_queue_info)?;
+        check_assert_eq!(
+            *amm_authority_info.key,
+            authority,
+            "authority",
+            AmmError::InvalidProgramAddress
+        );
+        check_assert_eq!(
+            *amm_coin_vault_info.key,
+            amm.coin_vault,
+            "coin_vault",
+            AmmError::InvalidCoinVault
+        );
+        check_assert_eq!(
+            *amm_pc_vault_info.key,
+            amm.pc_vault,
+            "pc_vault",
+            AmmError::InvalidPCVault
+        );
+        check_assert_eq!(
+            *amm_lp_mint_info.key,
+            amm.lp_mint,
+            "lp_mint",
+            AmmError::InvalidPoolMint
+        );
+        check_assert_eq!(
+            *amm_open_orders_info.key,
+            amm.open_orders,
+            "open_orders",
+            AmmError::InvalidOpenOrders
+        );
+        check_assert_eq!(
+            *market_info.key,
+            amm.market,
+            "market",
+            AmmError::InvalidMarket
+        );
+        let pnl_pc_amount;
+        let pnl_coin_amount;
+        if input_account_len == ACCOUNT_LEN + 1 {
+            let target_orders_info = next_account_info(account_info_iter)?;
+            check_assert_eq!(
+                *target_orders_info.key,
+                amm.target_orders,
+                "target_orders",
+                AmmError::InvalidTargetOrders
+            );
+            let target = TargetOrders::load_checked(&target_orders_info, program_id, amm_info.key)?;
+            pnl_pc_amount = Calculator::restore_decimal(
+                target.calc_pnl_x.into(),
+                amm.pc_decimals,
+                amm.sys_decimal_value,
+            )
+            .as_u64();
+            pnl_coin_amount = Calculator::restore_decimal(
+                target.calc_pnl_y.into(),
+                amm.coin_decimals,
+                amm.sys_decimal_value,
+            )
+            .as_u64();
+        } else {
+            pnl_pc_amount = 0;
+            pnl_coin_amount = 0;
+        }
+
+        let amm_coin_vault = Self::unpack_token_account(&amm_coin_vault_info, &spl_token::id())?;
+        let amm_pc_vault = Self::unpack_token_account(&amm_pc_vault_info, &spl_token::id())?;
+        let lp_mint = Self::unpack_mint(&amm_lp_mint_info, &spl_token::id())?;
+        let (market_state, open_orders) = Self::load_serum_market_order(
+            market_info,
+            amm_open_orders_info,
+            amm_authority_info,
+            &amm,
+            false,
+        )?;
+        if identity(market_state.coin_mint) != amm_coin_vault.mint.to_aligned_bytes() {
+            return Err(AmmError::InvalidCoinMint.into());
+        }
+        if identity(market_state.pc_mint) != amm_pc_vault.mint.to_aligned_bytes() {
+            return Err(AmmError::InvalidPCMint.into());
+        }
+        let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+            Calculator::calc_total_without_take_pnl(
+                amm_pc_vault.amount,
+                amm_coin_vault.amount,
+                &open_orders,
+                &amm,
+                &market_state,
+                &market_event_queue_info,
+                &amm_open_orders_info,
+            )?;
+        let pool_info_data = GetPoolData {
+            status: amm.status,
+            coin_decimals: amm.coin_decimals,
+            pc_decimals: amm.pc_decimals,
+            lp_decimals: lp_mint.decimals.into(),
+            pool_pc_amount: total_pc_without_take_pnl,
+            pool_coin_amount: total_coin_without_take_pnl,
+            pnl_pc_amount,
+            pnl_coin_amount,
+            pool_lp_supply: amm.lp_amount,
+            pool_open_time: amm.state_data.pool_open_time,
+            amm_id: amm_info.key.to_string(),
+        };
+        return Ok(pool_info_data);
+    }
+
+    fn simulate_swap_base_in(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        simulate: SimulateInstruction,
+    ) -> Result<GetSwapBaseInData, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_open_orders_info = next_account_info(account_info_iter)?;
+        let amm_target_orders_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+        let amm_lp_mint_info = next_account_info(account_info_iter)?;
+
+        let market_program_info = next_account_info(account_info_iter)?;
+        let market_info = next_account_info(account_info_iter)?;
+        let market_event_queue_info = next_account_info(account_info_iter)?;
+
+        let user_source_info = next_account_info(account_info_iter)?;
+        let user_destination_info = next_account_info(account_info_iter)?;
+        let user_source_owner = next_account_info(account_info_iter)?;
+
+        Self::check_account_readonly(amm_info)?;
+        Self::check_account_readonly(amm_open_orders_info)?;
+        Self::check_account_readonly(amm_target_orders_info)?;
+        Self::check_account_readonly(amm_coin_vault_info)?;
+        Self::check_account_readonly(amm_pc_vault_info)?;
+        Self::check_account_readonly(amm_lp_mint_info)?;
+        Self::check_account_readonly(market_info)?;
+        Self::check_account_readonly(market_event_queue_info)?;
+        Self::check_account_readonly(user_source_info)?;
+        Self::check_account_readonly(user_destination_info)?;
+        Self::check_account_readonly(user_source_owner)?;
+
+        let mut swap_base_in: GetSwapBaseInData = Default::default();
+        if let Some(swap) = simulate.swap_base_in_value {
+            swap_base_in.amount_in = swap.amount_in;
+
+            if !user_source_owner.is_signer {
+                return Err(AmmError::InvalidSignAccount.into());
+            }
+            let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+
+            if !AmmStatus::from_u64(amm.status).swap_permission() {
+                msg!("simulate_swap_base_in: status {}", identity(amm.status));
+                return Err(AmmError::InvalidStatus.into());
+            }
+            let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
+            check_assert_eq!(
+                *amm_authority_info.key,
+                authority,
+                "authority",
+                AmmError::InvalidProgramAddress
+            );
+            check_assert_eq!(
+                *amm_coin_vault_info.key,
+                amm.coin_vault,
+                "coin_vault",
+                AmmError::InvalidCoinVault
+            );
+            check_assert_eq!(
+                *amm_pc_vault_info.key,
+                amm.pc_vault,
+                "pc_vault",
+                AmmError::InvalidPCVault
+            );
+            check_assert_eq!(
+                *amm_open_orders_info.key,
+                amm.open_orders,
+                "open_orders",
+                AmmError::InvalidOpenOrders
+            );
+            check_assert_eq!(
+                *amm_target_orders_info.key,
+                amm.target_orders,
+                "target_orders",
+                AmmError::InvalidTargetOrders
+            );
+            check_assert_eq!(
+                *amm_lp_mint_info.key,
+                amm.lp_mint,
+                "lp_mint",
+                AmmError::InvalidPoolMint
+            );
+            check_assert_eq!(
+                *market_program_info.key,
+                amm.market_program,
+                "market_program",
+                AmmError::InvalidMarketProgram
+            );
+            check_assert_eq!(
+                *market_info.key,
+                amm.market,
+                "market",
+                AmmError::InvalidMarket
+            );
+
+            if *user_source_info.key == amm.pc_vault || *user_source_info.key == amm.coin_vault {
+                return Err(AmmError::InvalidInput.into());
+            }
+            if *user_destination_info.key == amm.pc_vault
+                || *user_destination_info.key == amm.coin_vault
+            {
+                return Err(AmmError::InvalidInput.into());
+            }
+
+            let (market_state, open_orders) = Self::load_serum_market_order(
+                market_info,
+                amm_open_orders_info,
+                amm_authority_info,
+                &amm,
+                false,
+            )?;
+
+            let amm_coin_vault =
+                Self::unpack_token_account(&amm_coin_vault_info, &spl_token::id())?;
+            let amm_pc_vault = Self::unpack_token_account(&amm_pc_vault_info, &spl_token::id())?;
+            let lp_mint = Self::unpack_mint(&amm_lp_mint_info, &spl_token::id())?;
+
+            let user_source = Self::unpack_token_account(&user_source_info, &spl_token::id())?;
+            let user_destination =
+                Self::unpack_token_account(&user_destination_info, &spl_token::id())?;
+
+            // let target_orders = TargetOrders::load_mut_checked(&target_orders_info, program_id, amm_info.key)?;
+
+            let swap_direction;
+            if user_source.mint == amm_coin_vault.mint && user_destination.mint == amm_pc_vault.mint
+            {
+                swap_direction = SwapDirection::Coin2PC
+            } else if user_source.mint == amm_pc_vault.mint
+                && user_destination.mint == amm_coin_vault.mint
+            {
+                swap_direction = SwapDirection::PC2Coin
+            } else {
+                return Err(AmmError::InvalidInput.into());
+            }
+            let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+                Calculator::calc_total_without_take_pnl(
+                    amm_pc_vault.amount,
+                    amm_coin_vault.amount,
+                    &open_orders,
+                    &amm,
+                    &market_state,
+                    &market_event_queue_info,
+                    &amm_open_orders_info,
+                )?;
+            swap_base_in.pool_data.status = amm.status;
+            swap_base_in.pool_data.coin_decimals = amm.coin_decimals;
+            swap_base_in.pool_data.pc_decimals = amm.pc_decimals;
+            swap_base_in.pool_data.lp_decimals = lp_mint.decimals.into();
+            swap_base_in.pool_data.pool_lp_supply = amm.lp_amount;
+            swap_base_in.pool_data.pool_open_time = amm.state_data.pool_open_time;
+            swap_base_in.pool_data.pool_pc_amount = total_pc_without_take_pnl;
+            swap_base_in.pool_data.pool_coin_amount = total_coin_without_take_pnl;
+            swap_base_in.pool_data.amm_id = amm_info.key.to_string();
+
+            let swap_fee = U128::from(swap.amount_in)
+                .checked_mul(amm.fees.swap_fee_numerator.into())
+                .unwrap()
+                .checked_ceil_div(amm.fees.swap_fee_denominator.into())
+                .unwrap()
+                .0;
+            let swap_in_after_deduct_fee =
+                U128::from(swap.amount_in).checked_sub(swap_fee).unwrap();
+            let swap_amount_out = Calculator::swap_token_amount_base_in(
+                swap_in_after_deduct_fee,
+                total_pc_without_take_pnl.into(),
+                total_coin_without_take_pnl.into(),
+                swap_direction,
+            )
+            .as_u64();
+            swap_base_in.minimum_amount_out = swap_amount_out;
+            match swap_direction {
+                SwapDirection::Coin2PC => {
+                    // coin -> pc, need cancel buy order
+                    let token_pc_after_swap = total_pc_without_take_pnl
+                        .checked_sub(swap_amount_out)
+                        .unwrap();
+                    let token_coin_after_swap = total_coin_without_take_pnl
+                        .checked_add(swap.amount_in)
+                        .unwrap();
+
+                    let swap_price_before = total_pc_without_take_pnl
+                        .checked_div(total_coin_without_take_pnl)
+                        .unwrap();
+                    let swap_price_after = token_pc_after_swap
+                        .checked_div(token_coin_after_swap)
+                        .unwrap();
+                    swap_base_in.price_impact =
+                        (swap_price_before.checked_sub(swap_price_after).unwrap())
+                            .checked_mul(1000000)
+                            .unwrap()
+                            .checked_div(swap_price_before)
+                            .unwrap();
+                }
+                SwapDirection::PC2Coin => {
+                    // pc -> coin, need cancel sell order
+                    let token_pc_after_swap = total_pc_without_take_pnl
+                        .checked_add(swap.amount_in)
+                        .unwrap();
+                    let token_coin_after_swap = total_coin_without_take_pnl
+                        .checked_sub(swap_amount_out)
+                        .unwrap();
+
+                    let swap_price_before = total_pc_without_take_pnl
+                        .checked_div(total_coin_without_take_pnl)
+                        .unwrap();
+                    let swap_price_after = token_pc_after_swap
+                        .checked_div(token_coin_after_swap)
+                        .unwrap();
+                    swap_base_in.price_impact =
+                        (swap_price_after.checked_sub(swap_price_before).unwrap())
+                            .checked_mul(1000000)
+                            .unwrap()
+                            .checked_div(swap_price_before)
+                            .unwrap();
+                }
+            }
+        }
+        return Ok(swap_base_in);
+    }
+
+    fn simulate_swap_base_out(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        simulate: SimulateInstruction,
+    ) -> Result<GetSwapBaseOutData, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_open_orders_info = next_account_info(account_info_iter)?;
+        let amm_target_orders_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+        let amm_lp_mint_info = next_account_info(account_info_iter)?;
+
+        let market_program_info = next_account_info(account_info_iter)?;
+        let market_info = next_account_info(account_info_iter)?;
+        let market_event_queue_info = next_account_info(account_info_iter)?;
+
+        let user_source_info = next_account_info(account_info_iter)?;
+        let user_destination_info = next_account_info(account_info_iter)?;
+        let user_source_owner = next_account_info(account_info_iter)?;
+
+        Self::check_account_readonly(amm_info)?;
+        Self::check_account_readonly(amm_open_orders_info)?;
+        Self::check_account_readonly(amm_target_orders_info)?;
+        Self::check_account_readonly(amm_coin_vault_info)?;
+        Self::check_account_readonly(amm_pc_vault_info)?;
+        Self::check_account_readonly(amm_lp_mint_info)?;
+        Self::check_account_readonly(market_info)?;
+        Self::check_account_readonly(market_event_queue_info)?;
+        Self::check_account_readonly(user_source_info)?;
+        Self::check_account_readonly(user_destination_info)?;
+        Self::check_account_readonly(user_source_owner)?;
+
+        let mut swap_base_out: GetSwapBaseOutData = Default::default();
+        if let Some(swap) = simulate.swap_base_out_value {
+            swap_base_out.amount_out = swap.amount_out;
+
+            if !user_source_owner.is_signer {
+                return Err(AmmError::InvalidSignAccount.into());
+            }
+            let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+            if !AmmStatus::from_u64(amm.status).swap_permission() {
+                msg!("simulate_swap_base_out: status {}", identity(amm.status));
+                return Err(AmmError::InvalidStatus.into());
+            }
+            let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
+            check_assert_eq!(
+                *amm_authority_info.key,
+                authority,
+                "authority",
+                AmmError::InvalidProgramAddress
+            );
+            check_assert_eq!(
+                *amm_coin_vault_info.key,
+                amm.coin_vault,
+                "coin_vault",
+                AmmError::InvalidCoinVault
+            );
+            check_assert_eq!(
+                *amm_pc_vault_info.key,
+                amm.pc_vault,
+                "pc_vault",
+                AmmError::InvalidPCVault
+            );
+            check_assert_eq!(
+                *amm_open_orders_info.key,
+                amm.open_orders,
+                "open_orders",
+                AmmError::InvalidOpenOrders
+            );
+            check_assert_eq!(
+                *amm_target_orders_info.key,
+                amm.target_orders,
+                "target_orders",
+                AmmError::InvalidTargetOrders
+            );
+            check_assert_eq!(
+                *amm_lp_mint_info.key,
+                amm.lp_mint,
+                "lp_mint",
+                AmmError::InvalidPoolMint
+            );
+            check_assert_eq!(
+                *market_program_info.key,
+                amm.market_program,
+                "market_program",
+                AmmError::InvalidMarketProgram
+            );
+            check_assert_eq!(
+                *market_info.key,
+                amm.market,
+                "market",
+                AmmError::InvalidMarket
+            );
+
+            if *user_source_info.key == amm.pc_vault || *user_source_info.key == amm.coin_vault {
+                return Err(AmmError::InvalidInput.into());
+            }
+            if *user_destination_info.key == amm.pc_vault
+                || *user_destination_info.key == amm.coin_vault
+            {
+                return Err(AmmError::InvalidInput.into());
+            }
+
+            let (market_state, open_orders) = Self::load_serum_market_order(
+                market_info,
+                amm_open_orders_info,
+                amm_authority_info,
+                &amm,
+                false,
+            )?;
+
+            let amm_coin_vault =
+                Self::unpack_token_account(&amm_coin_vault_info, &spl_token::id())?;
+            let amm_pc_vault = Self::unpack_token_account(&amm_pc_vault_info, &spl_token::id())?;
+            let lp_mint = Self::unpack_mint(&amm_lp_mint_info, &spl_token::id())?;
+
+            let user_swap_source = Self::unpack_token_account(&user_source_info, &spl_token::id())?;
+            let user_swap_destination =
+                Self::unpack_token_account(&user_destination_info, &spl_token::id())?;
+
+            // let target_orders = TargetOrders::load_mut_checked(&target_orders_info, program_id, amm_info.key)?;
+
+            let swap_direction;
+            if user_swap_source.mint == amm_coin_vault.mint
+                && user_swap_destination.mint == amm_pc_vault.mint
+            {
+                swap_direction = SwapDirection::Coin2PC
+            } else if user_swap_source.mint == amm_pc_vault.mint
+                && user_swap_destination.mint == amm_coin_vault.mint
+            {
+                swap_direction = SwapDirection::PC2Coin
+            } else {
+                return Err(AmmError::InvalidInput.into());
+            }
+            let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+                Calculator::calc_total_without_take_pnl(
+                    amm_pc_vault.amount,
+                    amm_coin_vault.amount,
+                    &open_orders,
+                    &amm,
+                    &market_state,
+                    &market_event_queue_info,
+                    &amm_open_orders_info,
+                )?;
+            swap_base_out.pool_data.status = amm.status;
+            swap_base_out.pool_data.coin_decimals = amm.coin_decimals;
+            swap_base_out.pool_data.pc_decimals = amm.pc_decimals;
+            swap_base_out.pool_data.lp_decimals = lp_mint.decimals.into();
+            swap_base_out.pool_data.pool_lp_supply = amm.lp_amount;
+            swap_base_out.pool_data.pool_open_time = amm.state_data.pool_open_time;
+            swap_base_out.pool_data.pool_pc_amount = total_pc_without_take_pnl;
+            swap_base_out.pool_data.pool_coin_amount = total_coin_without_take_pnl;
+            swap_base_out.pool_data.amm_id = amm_info.key.to_string();
+
+            let swap_in_before_add_fee = Calculator::swap_token_amount_base_out(
+                swap.amount_out.into(),
+                total_pc_without_take_pnl.into(),
+                total_coin_without_take_pnl.into(),
+                swap_direction,
+            );
+
+            // swap_in_after_add_fee * (1 - 0.0025) = swap_in_before_add_fee
+            // swap_in_after_add_fee = swap_in_before_add_fee / (1 - 0.0025)
+            let swap_in_after_add_fee = swap_in_before_add_fee
+                .checked_mul(amm.fees.swap_fee_denominator.into())
+                .unwrap()
+                .checked_ceil_div(
+                    (amm.fees
+                        .swap_fee_denominator
+                        .checked_sub(amm.fees.swap_fee_numerator)
+                        .unwrap())
+                    .into(),
+                )
+                .unwrap()
+                .0
+                .as_u64();
+            swap_base_out.max_amount_in = swap_in_after_add_fee;
+
+            match swap_direction {
+                SwapDirection::Coin2PC => {
+                    // coin -> pc, need cancel buy order
+                    let token_pc_after_swap = total_pc_without_take_pnl
+                        .checked_sub(swap.amount_out)
+                        .unwrap();
+                    let token_coin_after_swap = total_coin_without_take_pnl
+                        .checked_add(swap_in_after_add_fee)
+                        .unwrap();
+
+                    let swap_price_before = total_pc_without_take_pnl
+                        .checked_div(total_coin_without_take_pnl)
+                        .unwrap();
+                    let swap_price_after = token_pc_after_swap
+                        .checked_div(token_coin_after_swap)
+                        .unwrap();
+                    swap_base_out.price_impact =
+                        (swap_price_before.checked_sub(swap_price_after).unwrap())
+                            .checked_mul(1000000)
+                            .unwrap()
+                            .checked_div(swap_price_before)
+                            .unwrap();
+                }
+                SwapDirection::PC2Coin => {
+                    // pc -> coin, need cancel sell order
+                    let token_pc_after_swap = total_pc_without_take_pnl
+                        .checked_add(swap_in_after_add_fee)
+                        .unwrap();
+                    let token_coin_after_swap = total_coin_without_take_pnl
+                        .checked_sub(swap.amount_out)
+                        .unwrap();
+
+                    let swap_price_before = total_pc_without_take_pnl
+                        .checked_div(total_coin_without_take_pnl)
+                        .unwrap();
+                    let swap_price_after = token_pc_after_swap
+                        .checked_div(token_coin_after_swap)
+                        .unwrap();
+                    swap_base_out.price_impact =
+                        (swap_price_after.checked_sub(swap_price_before).unwrap())
+                            .checked_mul(1000000)
+                            .unwrap()
+                            .checked_div(swap_price_before)
+                            .unwrap();
+                }
+            }
+        }
+        return Ok(swap_base_out);
+    }
+
+    fn simulate_run_crank(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> Result<RunCrankData, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let token_program_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_open_orders_info = next_account_info(account_info_iter)?;
+        let amm_target_orders_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+
+        let market_program_info = next_account_info(account_info_iter)?;
+        let market_info = next_account_info(account_info_iter)?;
+        let market_bids_info = next_account_info(account_info_iter)?;
+        let market_asks_info = next_account_info(account_info_iter)?;
+        let market_event_queue_info = next_account_info(account_info_iter)?;
+
+        Self::check_account_readonly(amm_info)?;
+        Self::check_account_readonly(amm_open_orders_info)?;
+        Self::check_account_readonly(amm_target_orders_info)?;
+        Self::check_account_readonly(amm_coin_vault_info)?;
+        Self::check_account_readonly(amm_pc_vault_info)?;
+        Self::check_account_readonly(market_info)?;
+        Self::check_account_readonly(market_bids_info)?;
+        Self::check_account_readonly(market_asks_info)?;
+        Self::check_account_readonly(market_event_queue_info)?;
+
+        let amm = AmmInfo::load_checked(amm_info, program_id)?;
+        Self::check_accounts(
+            program_id,
+            &amm,
+            amm_info,
+            token_program_info,
+            clock_info,
+            market_program_info,
+            amm_authority_info,
+            market_info,
+            amm_open_orders_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            amm_target_orders_info,
+            None,
+            None,
+        )?;
+        let spl_token_program_id = token_program_info.key;
+        let amm_status = AmmStatus::from_u64(amm.status);
+        let amm_state = AmmState::from_u64(amm.state);
+        let mut run_crank_data = RunCrankData {
+            status: amm.status,
+            state: amm.state,
+            run_crank: false,
+        };
+
+        if amm.reset_flag == AmmResetFlag::ResetYes.into_u64() {
+            run_crank_data.run_crank = true;
+        } else if amm.order_num == 0 {
+            run_crank_data.run_crank = false;
+        } else {
+            match amm_status {
+                AmmStatus::Uninitialized
+                | AmmStatus::Disabled
+                | AmmStatus::WithdrawOnly
+                | AmmStatus::LiquidityOnly
+                | AmmStatus::SwapOnly
+                | AmmStatus::WaitingTrade
+                | AmmStatus::HookProgramChanged => {
+                    run_crank_data.run_crank = false;
+                }
+                AmmStatus::Initialized | AmmStatus::OrderBookOnly => match amm_state {
+                    AmmState::IdleState => {
+                        let amm_coin_vault = Processor::unpack_token_account(
+                            &amm_coin_vault_info,
+                            spl_token_program_id,
+                        )?;
+                        let amm_pc_vault = Processor::unpack_token_account(
+                            &amm_pc_vault_info,
+                            spl_token_program_id,
+                        )?;
+                        let target = TargetOrders::load_checked(
+                            &amm_target_orders_info,
+                            program_id,
+                            amm_info.key,
+                        )?;
+                        let (market_state, open_orders) = Processor::load_serum_market_order(
+                            market_info,
+                            amm_open_orders_info,
+                            amm_authority_info,
+                            &amm,
+                            false,
+                        )?;
+                        let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
+                        let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
+                        let (bids, asks) =
+                            Self::get_amm_orders(&open_orders, bids_orders, asks_orders)?;
+                        let native_pc_total = open_orders.native_pc_total;
+                        let native_coin_total = open_orders.native_coin_total;
+                        msg!(&format!("simulate_run_crank pc_amount:{}, native_pc_total:{}, coin_amount:{}, native_coin_total:{}, need_take_pnl_pc:{}, need_take_pnl_coin:{}", amm_pc_vault.amount, native_pc_total, amm_coin_vault.amount, native_coin_total, identity(amm.state_data.need_take_pnl_pc), identity(amm.state_data.need_take_pnl_coin)));
+                        let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+                            Calculator::calc_total_without_take_pnl(
+                                amm_pc_vault.amount,
+                                amm_coin_vault.amount,
+                                &open_orders,
+                                &amm,
+                                &market_state,
+                                &market_event_queue_info,
+                                &amm_open_orders_info,
+                            )?;
+                        let x = Calculator::normalize_decimal_v2(
                             total_pc_without_take_pnl,
                             amm.pc_decimals,
                             amm.sys_decimal_value,
@@ -4280,10 +8751,311 @@ impl Processor {
             }
         }
 
-        return Ok(run_crank_data);
+        return Ok(run_crank_data);
+    }
+
+    /// simulate_info
+    /// One transfer of a hook dry run, `side` is logged before the transfer so a rejecting
+    /// hook, which fails the simulation, is known from the logs
+    #[allow(clippy::too_many_arguments)]
+    fn dry_run_transfer<'a>(
+        side: &mut HookDryRunSide,
+        hook_config: Option<&HookConfig>,
+        token_program_info: &AccountInfo<'a>,
+        source_info: &AccountInfo<'a>,
+        mint_info: Option<&AccountInfo<'a>>,
+        destination_info: &AccountInfo<'a>,
+        authority_info: &AccountInfo<'a>,
+        hook_accounts: &[AccountInfo<'a>],
+        amount: u64,
+        decimals: u8,
+        authority_signature_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        side.hook_program = hook_config
+            .map(|hook_config| {
+                let hook_program = hook_config.hook_program;
+                hook_program.to_string()
+            })
+            .unwrap_or_default();
+        side.extra_accounts = hook_accounts
+            .iter()
+            .map(|account| account.key.to_string())
+            .collect();
+        msg!("HookDryRunSide: {}", side.to_json());
+        let remaining_before = sol_remaining_compute_units();
+        crate::token_invokers::token_transfer_with_hook(
+            token_program_info.clone(),
+            source_info.clone(),
+            mint_info.cloned(),
+            destination_info.clone(),
+            authority_info.clone(),
+            hook_accounts,
+            amount,
+            decimals,
+            authority_signature_seeds,
+        )?;
+        side.compute_units = remaining_before.saturating_sub(sol_remaining_compute_units());
+        side.within_budget = match hook_config {
+            Some(hook_config) => {
+                side.compute_units
+                    <= hook_config
+                        .max_compute_units
+                        .saturating_add(crate::whitelist::HOOKED_TRANSFER_COMPUTE_UNITS)
+            }
+            None => true,
+        };
+        Ok(())
+    }
+
+    /// Performs the hooked transfers of a swap for `SimulateParams::HookDryRunInfo`,
+    /// the caller reverts them
+    fn simulate_hook_dry_run(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        simulate: SimulateInstruction,
+    ) -> Result<HookDryRunData, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        let coin_token_program_info = next_account_info(account_info_iter)?;
+        let pc_token_program_info = next_account_info(account_info_iter)?;
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+        let user_source_info = next_account_info(account_info_iter)?;
+        let user_destination_info = next_account_info(account_info_iter)?;
+        let user_source_owner = next_account_info(account_info_iter)?;
+        let remaining_accounts = account_info_iter.as_slice();
+
+        Self::check_account_readonly(amm_info)?;
+        let swap = simulate.swap_base_in_value.ok_or(AmmError::InvalidInput)?;
+        if !user_source_owner.is_signer {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+        if !AmmStatus::from_u64(amm.status).swap_permission() {
+            msg!("simulate_hook_dry_run: status {}", identity(amm.status));
+            return Err(AmmError::InvalidStatus.into());
+        }
+        // a pool with an orderbook takes the accounts its reserves are read from
+        let orderbook_account_len = if amm.is_orderbook_free() { 0 } else { 3 };
+        if remaining_accounts.len() < orderbook_account_len {
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
+        let (orderbook_accounts, remaining_accounts) =
+            remaining_accounts.split_at(orderbook_account_len);
+        // the pool is only read, a changed hook program fails the dry run as it fails a swap
+        let coin_mint_info = remaining_accounts
+            .iter()
+            .find(|account| *account.key == amm.coin_vault_mint);
+        let pc_mint_info = remaining_accounts
+            .iter()
+            .find(|account| *account.key == amm.pc_vault_mint);
+        if Self::check_hook_programs(&mut { *amm }, coin_mint_info, pc_mint_info)? {
+            msg!("simulate_hook_dry_run: transfer hook program changed, pool is withdraw only");
+            return Err(AmmError::HookProgramChanged.into());
+        }
+        check_assert_eq!(
+            *coin_token_program_info.key,
+            amm.coin_token_program(),
+            "coin_token_program",
+            AmmError::InvalidSplTokenProgram
+        );
+        check_assert_eq!(
+            *pc_token_program_info.key,
+            amm.pc_token_program(),
+            "pc_token_program",
+            AmmError::InvalidSplTokenProgram
+        );
+        let authority = Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
+        check_assert_eq!(
+            *amm_authority_info.key,
+            authority,
+            "authority",
+            AmmError::InvalidProgramAddress
+        );
+        check_assert_eq!(
+            *amm_coin_vault_info.key,
+            amm.coin_vault,
+            "coin_vault",
+            AmmError::InvalidCoinVault
+        );
+        check_assert_eq!(
+            *amm_pc_vault_info.key,
+            amm.pc_vault,
+            "pc_vault",
+            AmmError::InvalidPCVault
+        );
+        if *user_source_info.key == amm.pc_vault || *user_source_info.key == amm.coin_vault {
+            return Err(AmmError::InvalidUserToken.into());
+        }
+        if *user_destination_info.key == amm.pc_vault
+            || *user_destination_info.key == amm.coin_vault
+        {
+            return Err(AmmError::InvalidUserToken.into());
+        }
+        let user_source =
+            crate::token_utils::unpack_token_account(&user_source_info, user_source_info.owner)?;
+        let user_destination = crate::token_utils::unpack_token_account(
+            &user_destination_info,
+            user_destination_info.owner,
+        )?;
+        let swap_direction = if user_source.mint == amm.coin_vault_mint
+            && user_destination.mint == amm.pc_vault_mint
+        {
+            SwapDirection::Coin2PC
+        } else if user_source.mint == amm.pc_vault_mint
+            && user_destination.mint == amm.coin_vault_mint
+        {
+            SwapDirection::PC2Coin
+        } else {
+            return Err(AmmError::InvalidUserToken.into());
+        };
+
+        let (
+            (source_mint_info, source_hook_config, source_hook_accounts),
+            (destination_mint_info, destination_hook_config, destination_hook_accounts),
+            remaining_accounts,
+        ) = Self::split_swap_hook_accounts(
+            program_id,
+            &amm,
+            swap_direction,
+            user_source_info,
+            user_destination_info,
+            user_source_owner,
+            amm_authority_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            swap.amount_in,
+            swap.minimum_amount_out,
+            remaining_accounts,
+        )?;
+        if !remaining_accounts.is_empty() {
+            return Err(AmmError::InvalidTransferHookAccounts.into());
+        }
+        let (
+            (source_token_program_info, source_vault_info, source_decimals),
+            (destination_token_program_info, destination_vault_info, destination_decimals),
+        ) = match swap_direction {
+            SwapDirection::Coin2PC => (
+                (
+                    coin_token_program_info,
+                    amm_coin_vault_info,
+                    amm.coin_decimals,
+                ),
+                (pc_token_program_info, amm_pc_vault_info, amm.pc_decimals),
+            ),
+            SwapDirection::PC2Coin => (
+                (pc_token_program_info, amm_pc_vault_info, amm.pc_decimals),
+                (
+                    coin_token_program_info,
+                    amm_coin_vault_info,
+                    amm.coin_decimals,
+                ),
+            ),
+        };
+
+        let mut hook_dry_run = HookDryRunData {
+            amm_id: amm_info.key.to_string(),
+            amount_in: swap.amount_in,
+            amount_out: swap.minimum_amount_out,
+            ..Default::default()
+        };
+        hook_dry_run.source.mint = user_source.mint.to_string();
+        hook_dry_run.destination.mint = user_destination.mint.to_string();
+        // hooks reading the trade context see the swap, with the reserves the swap would see
+        let amm_coin_vault = crate::token_utils::unpack_token_account(
+            &amm_coin_vault_info,
+            coin_token_program_info.key,
+        )?;
+        let amm_pc_vault = crate::token_utils::unpack_token_account(
+            &amm_pc_vault_info,
+            pc_token_program_info.key,
+        )?;
+        let (pc_reserve, coin_reserve) = match orderbook_accounts {
+            [amm_open_orders_info, market_info, market_event_queue_info]
+                if AmmStatus::from_u64(amm.status).orderbook_permission() =>
+            {
+                check_assert_eq!(
+                    *amm_open_orders_info.key,
+                    amm.open_orders,
+                    "open_orders",
+                    AmmError::InvalidOpenOrders
+                );
+                check_assert_eq!(
+                    *market_info.key,
+                    amm.market,
+                    "market",
+                    AmmError::InvalidMarket
+                );
+                let (market_state, open_orders) = Self::load_serum_market_order(
+                    market_info,
+                    amm_open_orders_info,
+                    amm_authority_info,
+                    &amm,
+                    false,
+                )?;
+                Calculator::calc_total_without_take_pnl(
+                    amm_pc_vault.amount,
+                    amm_coin_vault.amount,
+                    &open_orders,
+                    &amm,
+                    &market_state,
+                    market_event_queue_info,
+                    amm_open_orders_info,
+                )?
+            }
+            _ => Calculator::calc_total_without_take_pnl_no_orderbook(
+                amm_pc_vault.amount,
+                amm_coin_vault.amount,
+                &amm,
+            )?,
+        };
+        Self::write_trade_contexts(
+            program_id,
+            &TradeContext {
+                amm: *amm_info.key,
+                active: 1,
+                direction: swap_direction as u64,
+                amount_in: swap.amount_in,
+                amount_out: swap.minimum_amount_out,
+                coin_reserve,
+                pc_reserve,
+                ..Default::default()
+            },
+            [
+                (&user_source.mint, source_hook_accounts),
+                (&user_destination.mint, destination_hook_accounts),
+            ],
+        )?;
+        Self::dry_run_transfer(
+            &mut hook_dry_run.source,
+            source_hook_config.as_ref(),
+            source_token_program_info,
+            user_source_info,
+            source_mint_info,
+            source_vault_info,
+            user_source_owner,
+            source_hook_accounts,
+            swap.amount_in,
+            source_decimals as u8,
+            &[],
+        )?;
+        Self::dry_run_transfer(
+            &mut hook_dry_run.destination,
+            destination_hook_config.as_ref(),
+            destination_token_program_info,
+            destination_vault_info,
+            destination_mint_info,
+            user_destination_info,
+            amm_authority_info,
+            destination_hook_accounts,
+            swap.minimum_amount_out,
+            destination_decimals as u8,
+            &[AUTHORITY_AMM, &[amm.nonce as u8]],
+        )?;
+        Ok(hook_dry_run)
     }
 
-    /// simulate_info
     pub fn process_simulate_info(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -4309,6 +9081,12 @@ impl Processor {
                     Self::simulate_swap_base_out(program_id, accounts, simulate).unwrap();
                 msg!("GetSwapBaseOutData: {}", swap_base_out_data.to_json());
             }
+            SimulateParams::HookDryRunInfo => {
+                let hook_dry_run = Self::simulate_hook_dry_run(program_id, accounts, simulate)?;
+                msg!("HookDryRunData: {}", hook_dry_run.to_json());
+                // the transfers only ran to exercise the hooks
+                return Err(AmmError::HookDryRunReverted.into());
+            }
         }
         return Ok(());
     }
@@ -5017,473 +9795,905 @@ impl Processor {
                     .unwrap();
             }
         }
-        if target.place_orders_cur == amm.order_num {
-            // save placed_x & placed_y
-            target.placed_x = x.as_u128();
-            target.placed_y = y.as_u128();
-        }
-        if target.place_orders_cur >= amm.order_num {
-            amm.state = AmmState::PurgeOrderState.into_u64();
-            return Ok(());
+        if target.place_orders_cur == amm.order_num {
+            // save placed_x & placed_y
+            target.placed_x = x.as_u128();
+            target.placed_y = y.as_u128();
+        }
+        if target.place_orders_cur >= amm.order_num {
+            amm.state = AmmState::PurgeOrderState.into_u64();
+            return Ok(());
+        }
+        Ok(())
+    }
+
+    pub fn do_purge_orders(args: account_parser::PurgeOrderArgs) -> ProgramResult {
+        let account_parser::PurgeOrderArgs {
+            program_id: _,
+            limit: _,
+            market_program_info,
+            market_info,
+            amm_open_orders_info,
+            amm_authority_info,
+            market_event_queue_info,
+            market_bids_info,
+            market_asks_info,
+            amm,
+            target,
+            bids,
+            asks,
+        } = args;
+
+        if bids.len() <= target.valid_buy_order_num as usize
+            && asks.len() <= target.valid_sell_order_num as usize
+        {
+            // no order to purge
+            amm.state = AmmState::IdleState.into_u64();
+        } else {
+            let mut cancel_client_order_ids = Vec::new();
+            for i in target.valid_buy_order_num as usize..bids.len() {
+                cancel_client_order_ids.push(bids[i].client_order_id());
+            }
+            for i in target.valid_sell_order_num as usize..asks.len() {
+                cancel_client_order_ids.push(asks[i].client_order_id());
+            }
+
+            let mut order_ids_vec = Vec::new();
+            let mut order_ids = [0u64; 8];
+            let mut count = 0;
+            for i in 0..cancel_client_order_ids.len() {
+                order_ids[count] = cancel_client_order_ids[i];
+                count += 1;
+                if count == 8 {
+                    order_ids_vec.push(order_ids);
+                    order_ids = [0u64; 8];
+                    count = 0;
+                }
+            }
+            if count != 0 {
+                order_ids_vec.push(order_ids);
+            }
+            for ids in order_ids_vec.iter() {
+                Invokers::invoke_dex_cancel_orders_by_client_order_ids(
+                    market_program_info.clone(),
+                    market_info.clone(),
+                    market_bids_info.clone(),
+                    market_asks_info.clone(),
+                    amm_open_orders_info.clone(),
+                    amm_authority_info.clone(),
+                    market_event_queue_info.clone(),
+                    AUTHORITY_AMM,
+                    amm.nonce as u8,
+                    *ids,
+                )?;
+            }
+        }
+        msg!("do_purge to_state {}", identity(amm.state));
+        Ok(())
+    }
+
+    pub fn do_cancel_all_orders_state(args: account_parser::CancelAllOrdersArgs) -> ProgramResult {
+        let account_parser::CancelAllOrdersArgs {
+            program_id: _,
+            limit: _,
+            market_program_info,
+            market_info,
+            amm_open_orders_info,
+            amm_authority_info,
+            market_event_queue_info,
+            market_coin_vault_info,
+            market_pc_vault_info,
+            market_bids_info,
+            market_asks_info,
+            market_vault_signer,
+            token_program_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            referrer_pc_account,
+            amm,
+            open_orders,
+            target,
+            bids,
+            asks,
+        } = args;
+
+        msg!(arrform!(
+            LOG_SIZE,
+            "do_cancel_all o_bits:{:x}",
+            identity(open_orders.free_slot_bits)
+        )
+        .as_str());
+        // to decide whether all orders are really canceled
+        if open_orders.free_slot_bits.count_zeros() > 100 && bids.is_empty() && asks.is_empty() {
+            msg!(
+                "do_cancel_all to state :{}, count_zeros:{}",
+                identity(amm.state),
+                open_orders.free_slot_bits.count_zeros()
+            );
+            return Ok(());
+        } else {
+            let mut amm_order_ids_vec = Vec::new();
+            let mut order_ids = [0u64; 8];
+            let mut count = 0;
+            for i in 0..std::cmp::max(bids.len(), asks.len()) {
+                if i < bids.len() {
+                    order_ids[count] = bids[i].client_order_id();
+                    count += 1;
+                }
+                if i < asks.len() {
+                    order_ids[count] = asks[i].client_order_id();
+                    count += 1;
+                }
+                if count == 8 {
+                    amm_order_ids_vec.push(order_ids);
+                    order_ids = [0u64; 8];
+                    count = 0;
+                }
+            }
+            if count != 0 {
+                amm_order_ids_vec.push(order_ids);
+            }
+            for ids in amm_order_ids_vec.iter() {
+                Invokers::invoke_dex_cancel_orders_by_client_order_ids(
+                    market_program_info.clone(),
+                    market_info.clone(),
+                    market_bids_info.clone(),
+                    market_asks_info.clone(),
+                    amm_open_orders_info.clone(),
+                    amm_authority_info.clone(),
+                    market_event_queue_info.clone(),
+                    AUTHORITY_AMM,
+                    amm.nonce as u8,
+                    *ids,
+                )?;
+            }
+            if open_orders.native_coin_total != 0 || open_orders.native_pc_total != 0 {
+                Invokers::invoke_dex_settle_funds(
+                    market_program_info.clone(),
+                    market_info.clone(),
+                    amm_open_orders_info.clone(),
+                    amm_authority_info.clone(),
+                    market_coin_vault_info.clone(),
+                    market_pc_vault_info.clone(),
+                    amm_coin_vault_info.clone(),
+                    amm_pc_vault_info.clone(),
+                    market_vault_signer.clone(),
+                    token_program_info.clone(),
+                    referrer_pc_account,
+                    AUTHORITY_AMM,
+                    amm.nonce as u8,
+                )?;
+            }
+            if amm.reset_flag == AmmResetFlag::ResetYes.into_u64() {
+                amm.reset_flag = AmmResetFlag::ResetNo.into_u64();
+            }
+            amm.state = AmmState::IdleState.into_u64();
+            target.plan_orders_cur = 0;
+            target.place_orders_cur = 0;
+        }
+        Ok(())
+    }
+
+    /// Sets the status of a pool, see `AmmParams::Status`. Reviving a pool paused by a hook
+    /// change accepts the live hook programs, so the mints follow the other accounts then.
+    fn set_status<'a, 'b: 'a>(
+        amm: &mut AmmInfo,
+        value: u64,
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    ) -> ProgramResult {
+        if amm.status == AmmStatus::HookProgramChanged.into_u64()
+            && value != AmmStatus::HookProgramChanged.into_u64()
+        {
+            let coin_mint_info = next_account_info(account_info_iter)?;
+            let pc_mint_info = next_account_info(account_info_iter)?;
+            Self::pin_hook_programs(amm, coin_mint_info, pc_mint_info)?;
         }
+        amm.status = value;
         Ok(())
     }
 
-    pub fn do_purge_orders(args: account_parser::PurgeOrderArgs) -> ProgramResult {
-        let account_parser::PurgeOrderArgs {
-            program_id: _,
-            limit: _,
+    /// Sets the split of the swap fee of a pool, see `AmmParams::FeeSplit`. A pool created
+    /// before the `AmmExtension` gets it with `ExtendAmm` first.
+    fn set_fee_split(
+        amm: &mut AmmInfo,
+        amm_extension: Option<&mut AmmExtension>,
+        fee_split: Option<FeeSplit>,
+    ) -> ProgramResult {
+        let fee_split = fee_split.ok_or(AmmError::InvalidInput)?;
+        fee_split.validate()?;
+        let amm_extension = amm_extension.ok_or(AmmError::ExpectedAccount)?;
+        amm.set_fee_split(amm_extension, fee_split);
+        msg!(arrform!(
+            LOG_SIZE,
+            "fee_split: protocol {}, referrer {}",
+            fee_split.protocol_share,
+            fee_split.referrer_share
+        )
+        .as_str());
+        Ok(())
+    }
+
+    pub fn process_set_params(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        setparams: SetParamsInstruction,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_open_orders_info = next_account_info(account_info_iter)?;
+        let amm_target_orders_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+
+        let market_program_info = next_account_info(account_info_iter)?;
+        let market_info = next_account_info(account_info_iter)?;
+        let market_coin_vault_info = next_account_info(account_info_iter)?;
+        let market_pc_vault_info = next_account_info(account_info_iter)?;
+        let market_vault_signer = next_account_info(account_info_iter)?;
+        let market_event_q_info = next_account_info(account_info_iter)?;
+        let market_bids_info = next_account_info(account_info_iter)?;
+        let market_asks_info = next_account_info(account_info_iter)?;
+
+        let amm_owner_info = next_account_info(account_info_iter)?;
+
+        if *token_program_info.key != spl_token::ID {
+            return Err(AmmError::InvalidSplTokenProgram.into());
+        }
+        let (mut amm, mut amm_extension) =
+            AmmInfo::load_mut_checked_extended(&amm_info, program_id)?;
+        if *amm_authority_info.key
+            != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
+        {
+            return Err(AmmError::InvalidProgramAddress.into());
+        }
+        if amm_info.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if !amm_owner_info.is_signer || *amm_owner_info.key != config_feature::amm_owner::ID {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        // a pool without orderbook has no orders to cancel, only its status, curve and fee
+        // split are set here
+        if amm.is_orderbook_free() {
+            match AmmParams::from_u64(setparams.param as u64) {
+                AmmParams::Status => {
+                    let value = setparams.value.ok_or(AmmError::InvalidInput)?;
+                    // the orderbook statuses would plan orders on a pool without market
+                    if !AmmStatus::valid_status(value)
+                        || AmmStatus::from_u64(value).orderbook_permission()
+                    {
+                        return Err(AmmError::InvalidParamsSet.into());
+                    }
+                    Self::set_status(&mut amm, value, account_info_iter)?;
+                }
+                AmmParams::FeeSplit => Self::set_fee_split(
+                    &mut amm,
+                    amm_extension.as_deref_mut(),
+                    setparams.fee_split,
+                )?,
+                AmmParams::RampAmp => {
+                    let ramp_amp = setparams.ramp_amp.ok_or(AmmError::InvalidParamsSet)?;
+                    let amp_ramp = amm
+                        .amp_ramp(amm_extension.as_deref())?
+                        .ok_or(AmmError::InvalidParamsSet)?;
+                    let now = Clock::get()?.unix_timestamp as u64;
+                    let amp_ramp = amp_ramp.ramp(now, ramp_amp)?;
+                    let amm_extension = amm_extension
+                        .as_deref_mut()
+                        .ok_or(AmmError::ExpectedAccount)?;
+                    amm.set_amp_ramp(amm_extension, amp_ramp);
+                    msg!(arrform!(
+                        LOG_SIZE,
+                        "ramp_amp: {} to {} at {}",
+                        { amp_ramp.initial_amp },
+                        { amp_ramp.target_amp },
+                        { amp_ramp.stop_ramp_ts }
+                    )
+                    .as_str());
+                }
+                _ => return Err(AmmError::InvalidParamsSet.into()),
+            }
+            amm.recent_epoch = Clock::get()?.epoch;
+            return Ok(());
+        }
+        // the orders of the pool quote the constant product curve, it has no amplification
+        if matches!(
+            AmmParams::from_u64(setparams.param as u64),
+            AmmParams::RampAmp
+        ) {
+            return Err(AmmError::UnsupportedCurve.into());
+        }
+        if *market_program_info.key != amm.market_program {
+            return Err(AmmError::InvalidMarketProgram.into());
+        }
+        if *market_info.key != amm.market {
+            return Err(AmmError::InvalidMarket.into());
+        }
+        if *amm_open_orders_info.key != amm.open_orders {
+            return Err(AmmError::InvalidOpenOrders.into());
+        }
+        if amm.coin_vault != *amm_coin_vault_info.key {
+            return Err(AmmError::InvalidCoinVault.into());
+        }
+        if amm.pc_vault != *amm_pc_vault_info.key {
+            return Err(AmmError::InvalidPCVault.into());
+        }
+        if amm.target_orders != *amm_target_orders_info.key {
+            return Err(AmmError::InvalidTargetOrders.into());
+        }
+        // cancel amm orders in openbook
+        Self::do_cancel_amm_orders(
+            &amm,
+            amm_authority_info,
+            amm_open_orders_info,
             market_program_info,
             market_info,
-            amm_open_orders_info,
-            amm_authority_info,
-            market_event_queue_info,
             market_bids_info,
             market_asks_info,
-            amm,
-            target,
-            bids,
-            asks,
-        } = args;
+            market_event_q_info,
+            AUTHORITY_AMM,
+        )?;
+        Invokers::invoke_dex_settle_funds(
+            market_program_info.clone(),
+            market_info.clone(),
+            amm_open_orders_info.clone(),
+            amm_authority_info.clone(),
+            market_coin_vault_info.clone(),
+            market_pc_vault_info.clone(),
+            amm_coin_vault_info.clone(),
+            amm_pc_vault_info.clone(),
+            market_vault_signer.clone(),
+            token_program_info.clone(),
+            None,
+            AUTHORITY_AMM,
+            amm.nonce as u8,
+        )?;
 
-        if bids.len() <= target.valid_buy_order_num as usize
-            && asks.len() <= target.valid_sell_order_num as usize
-        {
-            // no order to purge
-            amm.state = AmmState::IdleState.into_u64();
-        } else {
-            let mut cancel_client_order_ids = Vec::new();
-            for i in target.valid_buy_order_num as usize..bids.len() {
-                cancel_client_order_ids.push(bids[i].client_order_id());
+        let param = setparams.param;
+        let mut set_valid = false;
+        match AmmParams::from_u64(param as u64) {
+            AmmParams::Status => {
+                {
+                    let (market_state, open_orders) = Processor::load_serum_market_order(
+                        market_info,
+                        amm_open_orders_info,
+                        amm_authority_info,
+                        &amm,
+                        false,
+                    )?;
+                    let (shared_pc, shared_coin) = Calculator::calc_exact_vault_in_serum(
+                        &open_orders,
+                        &market_state,
+                        market_event_q_info,
+                        amm_open_orders_info,
+                    )
+                    .unwrap();
+                    if shared_pc != 0 || shared_coin != 0 {
+                        msg!("shared_pc:{}, shared_coin:{}", shared_pc, shared_coin);
+                        return Err(AmmError::InvalidInput.into());
+                    }
+                }
+                let value = match setparams.value {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                if AmmStatus::valid_status(value) {
+                    Self::set_status(&mut amm, value, account_info_iter)?;
+                    set_valid = true;
+                }
+            }
+            AmmParams::State => {
+                let value = match setparams.value {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                if AmmState::valid_state(value) {
+                    amm.state = value as u64;
+                    set_valid = true;
+                }
+            }
+            AmmParams::OrderNum => {
+                let value = match setparams.value {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                if value > MAX_ORDER_LIMIT as u64 {
+                    return Err(AmmError::InvalidInput.into());
+                }
+                amm.order_num = value as u64;
+                set_valid = true;
+            }
+            AmmParams::Depth => {
+                let value = match setparams.value {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                if value > 0 && value < 100 {
+                    amm.depth = value as u64;
+                    set_valid = true;
+                }
+            }
+            AmmParams::AmountWave => {
+                let value = match setparams.value {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                amm.amount_wave = value;
+                set_valid = true;
+            }
+            AmmParams::MinPriceMultiplier => {
+                let value = match setparams.value {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                if value < amm.max_price_multiplier {
+                    amm.min_price_multiplier = value;
+                    set_valid = true;
+                }
+            }
+            AmmParams::MaxPriceMultiplier => {
+                let value = match setparams.value {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                if value > amm.max_price_multiplier {
+                    amm.max_price_multiplier = value;
+                    set_valid = true;
+                }
+            }
+            AmmParams::VolMaxCutRatio => {
+                let value = match setparams.value {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                if value <= TEN_THOUSAND {
+                    amm.vol_max_cut_ratio = value;
+                    set_valid = true;
+                }
+            }
+            AmmParams::Seperate => {
+                let value = match setparams.value {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                if value <= TEN_THOUSAND {
+                    amm.fees.min_separate_numerator = value;
+                    set_valid = true;
+                }
             }
-            for i in target.valid_sell_order_num as usize..asks.len() {
-                cancel_client_order_ids.push(asks[i].client_order_id());
+            AmmParams::Fees => {
+                let fees = match setparams.fees {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                fees.validate()?;
+                amm.fees = fees;
+                set_valid = true;
             }
-
-            let mut order_ids_vec = Vec::new();
-            let mut order_ids = [0u64; 8];
-            let mut count = 0;
-            for i in 0..cancel_client_order_ids.len() {
-                order_ids[count] = cancel_client_order_ids[i];
-                count += 1;
-                if count == 8 {
-                    order_ids_vec.push(order_ids);
-                    order_ids = [0u64; 8];
-                    count = 0;
-                }
+            AmmParams::FeeSplit => {
+                Self::set_fee_split(&mut amm, amm_extension.as_deref_mut(), setparams.fee_split)?;
+                set_valid = true;
             }
-            if count != 0 {
-                order_ids_vec.push(order_ids);
+            AmmParams::AmmOwner => {
+                let new_pubkey = match setparams.new_pubkey {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                amm.amm_owner = new_pubkey;
+                set_valid = true;
             }
-            for ids in order_ids_vec.iter() {
-                Invokers::invoke_dex_cancel_orders_by_client_order_ids(
-                    market_program_info.clone(),
-                    market_info.clone(),
-                    market_bids_info.clone(),
-                    market_asks_info.clone(),
-                    amm_open_orders_info.clone(),
-                    amm_authority_info.clone(),
-                    market_event_queue_info.clone(),
-                    AUTHORITY_AMM,
-                    amm.nonce as u8,
-                    *ids,
+            AmmParams::SetOpenTime => {
+                let value = match setparams.value {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                amm.state_data.pool_open_time = value as u64;
+                set_valid = true;
+            }
+            AmmParams::LastOrderDistance => {
+                let mut target = TargetOrders::load_mut_checked(
+                    &amm_target_orders_info,
+                    program_id,
+                    amm_info.key,
                 )?;
+                let distance = match setparams.last_order_distance {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                target.last_order_numerator = distance.last_order_numerator;
+                target.last_order_denominator = distance.last_order_denominator;
+                set_valid = true;
             }
-        }
-        msg!("do_purge to_state {}", identity(amm.state));
-        Ok(())
-    }
-
-    pub fn do_cancel_all_orders_state(args: account_parser::CancelAllOrdersArgs) -> ProgramResult {
-        let account_parser::CancelAllOrdersArgs {
-            program_id: _,
-            limit: _,
-            market_program_info,
-            market_info,
-            amm_open_orders_info,
-            amm_authority_info,
-            market_event_queue_info,
-            market_coin_vault_info,
-            market_pc_vault_info,
-            market_bids_info,
-            market_asks_info,
-            market_vault_signer,
-            token_program_info,
-            amm_coin_vault_info,
-            amm_pc_vault_info,
-            referrer_pc_account,
-            amm,
-            open_orders,
-            target,
-            bids,
-            asks,
-        } = args;
-
-        msg!(arrform!(
-            LOG_SIZE,
-            "do_cancel_all o_bits:{:x}",
-            identity(open_orders.free_slot_bits)
-        )
-        .as_str());
-        // to decide whether all orders are really canceled
-        if open_orders.free_slot_bits.count_zeros() > 100 && bids.is_empty() && asks.is_empty() {
-            msg!(
-                "do_cancel_all to state :{}, count_zeros:{}",
-                identity(amm.state),
-                open_orders.free_slot_bits.count_zeros()
-            );
-            return Ok(());
-        } else {
-            let mut amm_order_ids_vec = Vec::new();
-            let mut order_ids = [0u64; 8];
-            let mut count = 0;
-            for i in 0..std::cmp::max(bids.len(), asks.len()) {
-                if i < bids.len() {
-                    order_ids[count] = bids[i].client_order_id();
-                    count += 1;
-                }
-                if i < asks.len() {
-                    order_ids[count] = asks[i].client_order_id();
-                    count += 1;
-                }
-                if count == 8 {
-                    amm_order_ids_vec.push(order_ids);
-                    order_ids = [0u64; 8];
-                    count = 0;
-                }
+            AmmParams::InitOrderDepth => {
+                amm.order_num = 7u64;
+                amm.depth = 3u64;
+                set_valid = true;
             }
-            if count != 0 {
-                amm_order_ids_vec.push(order_ids);
+            AmmParams::SetSwitchTime => {
+                let value = match setparams.value {
+                    Some(a) => a,
+                    None => return Err(AmmError::InvalidInput.into()),
+                };
+                amm.state_data.orderbook_to_init_time = value as u64;
+                set_valid = true;
             }
-            for ids in amm_order_ids_vec.iter() {
-                Invokers::invoke_dex_cancel_orders_by_client_order_ids(
-                    market_program_info.clone(),
-                    market_info.clone(),
-                    market_bids_info.clone(),
-                    market_asks_info.clone(),
-                    amm_open_orders_info.clone(),
-                    amm_authority_info.clone(),
-                    market_event_queue_info.clone(),
-                    AUTHORITY_AMM,
-                    amm.nonce as u8,
-                    *ids,
-                )?;
+            AmmParams::ClearOpenTime => {
+                amm.state_data.pool_open_time = 0;
+                amm.state_data.orderbook_to_init_time = 0;
+                set_valid = true;
             }
-            if open_orders.native_coin_total != 0 || open_orders.native_pc_total != 0 {
-                Invokers::invoke_dex_settle_funds(
-                    market_program_info.clone(),
-                    market_info.clone(),
-                    amm_open_orders_info.clone(),
-                    amm_authority_info.clone(),
-                    market_coin_vault_info.clone(),
-                    market_pc_vault_info.clone(),
-                    amm_coin_vault_info.clone(),
-                    amm_pc_vault_info.clone(),
-                    market_vault_signer.clone(),
-                    token_program_info.clone(),
-                    referrer_pc_account,
-                    AUTHORITY_AMM,
-                    amm.nonce as u8,
+            AmmParams::UpdateOpenOrder => {
+                let new_open_orders_info = next_account_info(account_info_iter)?;
+                amm.open_orders = *new_open_orders_info.key;
+                let (_market_state, _open_orders) = Processor::load_serum_market_order(
+                    market_info,
+                    new_open_orders_info,
+                    amm_authority_info,
+                    &amm,
+                    false,
                 )?;
+                set_valid = true;
             }
-            if amm.reset_flag == AmmResetFlag::ResetYes.into_u64() {
-                amm.reset_flag = AmmResetFlag::ResetNo.into_u64();
+            _ => {
+                return Err(AmmError::InvalidInput.into());
             }
-            amm.state = AmmState::IdleState.into_u64();
-            target.plan_orders_cur = 0;
-            target.place_orders_cur = 0;
         }
+        if set_valid {
+            amm.state = AmmState::CancelAllOrdersState.into_u64();
+            amm.reset_flag = AmmResetFlag::ResetYes.into_u64();
+        } else {
+            return Err(AmmError::InvalidParamsSet.into());
+        }
+        amm.recent_epoch = Clock::get()?.epoch;
         Ok(())
     }
 
-    pub fn process_set_params(
+    pub fn process_monitor_step(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        setparams: SetParamsInstruction,
+        monitor: MonitorStepInstruction,
     ) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let token_program_info = next_account_info(account_info_iter)?;
-
-        let amm_info = next_account_info(account_info_iter)?;
-        let amm_authority_info = next_account_info(account_info_iter)?;
-        let amm_open_orders_info = next_account_info(account_info_iter)?;
-        let amm_target_orders_info = next_account_info(account_info_iter)?;
-        let amm_coin_vault_info = next_account_info(account_info_iter)?;
-        let amm_pc_vault_info = next_account_info(account_info_iter)?;
-
-        let market_program_info = next_account_info(account_info_iter)?;
-        let market_info = next_account_info(account_info_iter)?;
-        let market_coin_vault_info = next_account_info(account_info_iter)?;
-        let market_pc_vault_info = next_account_info(account_info_iter)?;
-        let market_vault_signer = next_account_info(account_info_iter)?;
-        let market_event_q_info = next_account_info(account_info_iter)?;
-        let market_bids_info = next_account_info(account_info_iter)?;
-        let market_asks_info = next_account_info(account_info_iter)?;
-
-        let amm_owner_info = next_account_info(account_info_iter)?;
-
-        if *token_program_info.key != spl_token::ID {
-            return Err(AmmError::InvalidSplTokenProgram.into());
-        }
-        let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
-        if *amm_authority_info.key
-            != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
+        const MIN_ACCOUNTS: usize = 18;
+        let input_account_len = accounts.len();
+        if input_account_len != MIN_ACCOUNTS
+            && input_account_len != MIN_ACCOUNTS + 1
+            && input_account_len != MIN_ACCOUNTS + 2
         {
-            return Err(AmmError::InvalidProgramAddress.into());
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
+        let (fixed_accounts, extra_token_accounts) = array_refs![accounts, MIN_ACCOUNTS; .. ;];
+        let [token_program_info, rent_info, clock_info, amm_info, amm_authority_info, amm_open_orders_info, amm_target_orders_info, amm_coin_vault_info, amm_pc_vault_info, market_program_info, market_info, market_coin_vault_info, market_pc_vault_info, market_vault_signer, market_request_queue_info, market_event_queue_info, market_bids_info, market_asks_info] =
+            fixed_accounts;
+        let mut srm_token_info = None;
+        let mut referrer_pc_info = None;
+        if input_account_len == MIN_ACCOUNTS + 1 {
+            if let [srm_token_acc] = extra_token_accounts {
+                srm_token_info = Some(srm_token_acc);
+            }
+        } else if input_account_len == MIN_ACCOUNTS + 2 {
+            if let [srm_token_acc, referrer_pc_acc] = extra_token_accounts {
+                srm_token_info = Some(srm_token_acc);
+                referrer_pc_info = Some(referrer_pc_acc);
+            }
         }
+
+        let mut amm = AmmInfo::load_mut_checked(amm_info, program_id)?;
+        Self::check_accounts(
+            program_id,
+            &amm,
+            amm_info,
+            token_program_info,
+            clock_info,
+            market_program_info,
+            amm_authority_info,
+            market_info,
+            amm_open_orders_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            amm_target_orders_info,
+            srm_token_info,
+            referrer_pc_info,
+        )?;
+        check_assert_eq!(
+            *market_program_info.key,
+            config_feature::openbook_program::id(),
+            "market_program",
+            AmmError::InvalidMarketProgram
+        );
         if amm_info.owner != program_id {
             return Err(AmmError::InvalidOwner.into());
         }
-        if !amm_owner_info.is_signer || *amm_owner_info.key != config_feature::amm_owner::ID {
-            return Err(AmmError::InvalidSignAccount.into());
-        }
-        if *market_program_info.key != amm.market_program {
-            return Err(AmmError::InvalidMarketProgram.into());
-        }
-        if *market_info.key != amm.market {
-            return Err(AmmError::InvalidMarket.into());
-        }
-        if *amm_open_orders_info.key != amm.open_orders {
-            return Err(AmmError::InvalidOpenOrders.into());
-        }
-        if amm.coin_vault != *amm_coin_vault_info.key {
-            return Err(AmmError::InvalidCoinVault.into());
-        }
-        if amm.pc_vault != *amm_pc_vault_info.key {
-            return Err(AmmError::InvalidPCVault.into());
+        if amm.min_size == 0 {
+            return Err(AmmError::MarketLotSizeIsTooLarge.into());
         }
-        if amm.target_orders != *amm_target_orders_info.key {
-            return Err(AmmError::InvalidTargetOrders.into());
+        let amm_status = AmmStatus::from_u64(amm.status);
+        let amm_state = AmmState::from_u64(amm.state);
+        let spl_token_program_id = token_program_info.key;
+
+        if amm.reset_flag == AmmResetFlag::ResetYes.into_u64() {
+            msg!("monitor_step: ResetYes");
+            let cancel_all_orders_accounts: &[AccountInfo] = &[
+                amm_info.clone(),
+                market_program_info.clone(),
+                market_info.clone(),
+                amm_open_orders_info.clone(),
+                amm_authority_info.clone(),
+                market_event_queue_info.clone(),
+                market_coin_vault_info.clone(),
+                market_pc_vault_info.clone(),
+                market_bids_info.clone(),
+                market_asks_info.clone(),
+                market_vault_signer.clone(),
+                token_program_info.clone(),
+                amm_coin_vault_info.clone(),
+                amm_pc_vault_info.clone(),
+                amm_target_orders_info.clone(),
+            ];
+            account_parser::CancelAllOrdersArgs::with_parsed_args(
+                program_id,
+                monitor.cancel_order_limit,
+                &mut amm,
+                cancel_all_orders_accounts,
+                referrer_pc_info,
+                Self::do_cancel_all_orders_state,
+            )
+            .unwrap();
+        } else if amm.order_num == 0 {
+            msg!(arrform!(
+                LOG_SIZE,
+                "monitor_step Status:{}, order_num:{}",
+                identity(amm.status),
+                identity(amm.order_num)
+            )
+            .as_str());
+        } else {
+            match amm_status {
+                AmmStatus::Uninitialized
+                | AmmStatus::Disabled
+                | AmmStatus::WithdrawOnly
+                | AmmStatus::LiquidityOnly
+                | AmmStatus::SwapOnly
+                | AmmStatus::WaitingTrade
+                | AmmStatus::HookProgramChanged => {
+                    msg!("monitor_step: AmmStatus:{}", identity(amm.status));
+                    return Err(AmmError::InvalidStatus.into());
+                }
+                AmmStatus::Initialized | AmmStatus::OrderBookOnly => match amm_state {
+                    AmmState::IdleState => {
+                        msg!("monitor_step IdleState:{}", identity(amm.state));
+                        let idle_accounts: &[AccountInfo] = &[
+                            amm_info.clone(),
+                            market_program_info.clone(),
+                            market_info.clone(),
+                            market_bids_info.clone(),
+                            market_asks_info.clone(),
+                            market_event_queue_info.clone(),
+                            amm_authority_info.clone(),
+                            amm_open_orders_info.clone(),
+                            amm_coin_vault_info.clone(),
+                            amm_pc_vault_info.clone(),
+                            amm_target_orders_info.clone(),
+                        ];
+                        account_parser::IdleArgs::with_parsed_args(
+                            program_id,
+                            spl_token_program_id,
+                            &mut amm,
+                            idle_accounts,
+                            Self::do_idle_state,
+                        )
+                        .unwrap();
+                    }
+                    AmmState::CancelAllOrdersState => {
+                        msg!("monitor_step CancelAllOrdersState:{}", identity(amm.state));
+                        let cancel_all_orders_accounts: &[AccountInfo] = &[
+                            amm_info.clone(),
+                            market_program_info.clone(),
+                            market_info.clone(),
+                            amm_open_orders_info.clone(),
+                            amm_authority_info.clone(),
+                            market_event_queue_info.clone(),
+                            market_coin_vault_info.clone(),
+                            market_pc_vault_info.clone(),
+                            market_bids_info.clone(),
+                            market_asks_info.clone(),
+                            market_vault_signer.clone(),
+                            token_program_info.clone(),
+                            amm_coin_vault_info.clone(),
+                            amm_pc_vault_info.clone(),
+                            amm_target_orders_info.clone(),
+                        ];
+                        account_parser::CancelAllOrdersArgs::with_parsed_args(
+                            program_id,
+                            monitor.cancel_order_limit,
+                            &mut amm,
+                            cancel_all_orders_accounts,
+                            referrer_pc_info,
+                            Self::do_cancel_all_orders_state,
+                        )
+                        .unwrap();
+                    }
+                    AmmState::PlanOrdersState => {
+                        msg!("monitor_step PlanOrdersState:{}", identity(amm.state));
+                        let plan_buy_accounts: &[AccountInfo] = &[
+                            amm_info.clone(),
+                            market_info.clone(),
+                            market_event_queue_info.clone(),
+                            amm_authority_info.clone(),
+                            amm_open_orders_info.clone(),
+                            amm_coin_vault_info.clone(),
+                            amm_pc_vault_info.clone(),
+                            amm_target_orders_info.clone(),
+                        ];
+                        account_parser::PlanOrderBookArgs::with_parsed_args(
+                            program_id,
+                            spl_token_program_id,
+                            monitor.plan_order_limit,
+                            &mut amm,
+                            plan_buy_accounts,
+                            Self::do_plan_orderbook,
+                        )
+                        .unwrap();
+                    }
+                    AmmState::CancelOrderState => {
+                        msg!("monitor_step CancelOrderState:{}", identity(amm.state));
+                        let cancel_order_accounts: &[AccountInfo] = &[
+                            amm_info.clone(),
+                            market_program_info.clone(),
+                            market_info.clone(),
+                            amm_open_orders_info.clone(),
+                            amm_authority_info.clone(),
+                            market_event_queue_info.clone(),
+                            market_bids_info.clone(),
+                            market_asks_info.clone(),
+                            amm_coin_vault_info.clone(),
+                            amm_pc_vault_info.clone(),
+                            amm_target_orders_info.clone(),
+                        ];
+                        account_parser::CancelOrderArgs::with_parsed_args(
+                            program_id,
+                            spl_token_program_id,
+                            monitor.cancel_order_limit,
+                            &mut amm,
+                            cancel_order_accounts,
+                            Self::do_cancel_order,
+                        )
+                        .unwrap();
+                    }
+                    AmmState::PlaceOrdersState => {
+                        msg!("monitor_step PlaceOrdersState:{}", identity(amm.state));
+                        let place_order_accounts: &[AccountInfo] = &[
+                            amm_info.clone(),
+                            amm_authority_info.clone(),
+                            amm_open_orders_info.clone(),
+                            market_program_info.clone(),
+                            market_info.clone(),
+                            market_request_queue_info.clone(),
+                            amm_coin_vault_info.clone(),
+                            amm_pc_vault_info.clone(),
+                            market_coin_vault_info.clone(),
+                            market_pc_vault_info.clone(),
+                            token_program_info.clone(),
+                            rent_info.clone(),
+                            market_event_queue_info.clone(),
+                            market_bids_info.clone(),
+                            market_asks_info.clone(),
+                            amm_target_orders_info.clone(),
+                            clock_info.clone(),
+                        ];
+                        account_parser::PlaceOrdersArgs::with_parsed_args(
+                            program_id,
+                            spl_token_program_id,
+                            monitor.place_order_limit,
+                            &mut amm,
+                            place_order_accounts,
+                            srm_token_info,
+                            Self::do_place_orders,
+                        )
+                        .unwrap();
+                    }
+                    AmmState::PurgeOrderState => {
+                        msg!("monitor_step PurgeOrderState:{}", identity(amm.state));
+                        let purge_orders_accounts: &[AccountInfo] = &[
+                            amm_info.clone(),
+                            market_program_info.clone(),
+                            market_info.clone(),
+                            amm_open_orders_info.clone(),
+                            amm_authority_info.clone(),
+                            market_event_queue_info.clone(),
+                            market_bids_info.clone(),
+                            market_asks_info.clone(),
+                            amm_target_orders_info.clone(),
+                        ];
+                        account_parser::PurgeOrderArgs::with_parsed_args(
+                            program_id,
+                            spl_token_program_id,
+                            monitor.cancel_order_limit,
+                            &mut amm,
+                            purge_orders_accounts,
+                            Self::do_purge_orders,
+                        )
+                        .unwrap();
+                    }
+                    _ => {
+                        msg!("monitor_step InvalidState:{}", identity(amm.state));
+                    }
+                },
+            }
         }
-        // cancel amm orders in openbook
-        Self::do_cancel_amm_orders(
-            &amm,
-            amm_authority_info,
-            amm_open_orders_info,
-            market_program_info,
+        msg!("monitor_step end");
+        Ok(())
+    }
+
+    fn do_cancel_amm_orders<'a>(
+        amm: &AmmInfo,
+        amm_authority_info: &AccountInfo<'a>,
+        amm_open_orders_info: &AccountInfo<'a>,
+        market_program_info: &AccountInfo<'a>,
+        market_info: &AccountInfo<'a>,
+        market_bids_info: &AccountInfo<'a>,
+        market_asks_info: &AccountInfo<'a>,
+        market_event_queue_info: &AccountInfo<'a>,
+        amm_seed: &[u8],
+    ) -> ProgramResult {
+        let (market_state, open_orders) = Processor::load_serum_market_order(
             market_info,
-            market_bids_info,
-            market_asks_info,
-            market_event_q_info,
-            AUTHORITY_AMM,
-        )?;
-        Invokers::invoke_dex_settle_funds(
-            market_program_info.clone(),
-            market_info.clone(),
-            amm_open_orders_info.clone(),
-            amm_authority_info.clone(),
-            market_coin_vault_info.clone(),
-            market_pc_vault_info.clone(),
-            amm_coin_vault_info.clone(),
-            amm_pc_vault_info.clone(),
-            market_vault_signer.clone(),
-            token_program_info.clone(),
-            None,
-            AUTHORITY_AMM,
-            amm.nonce as u8,
+            amm_open_orders_info,
+            amm_authority_info,
+            amm,
+            false,
         )?;
+        let bids_orders = market_state.load_bids_checked(market_bids_info)?;
+        let asks_orders = market_state.load_asks_checked(market_asks_info)?;
+        let (bids, asks) = Processor::get_amm_orders(&open_orders, bids_orders, asks_orders)?;
 
-        let param = setparams.param;
-        let mut set_valid = false;
-        match AmmParams::from_u64(param as u64) {
-            AmmParams::Status => {
-                {
-                    let (market_state, open_orders) = Processor::load_serum_market_order(
-                        market_info,
-                        amm_open_orders_info,
-                        amm_authority_info,
-                        &amm,
-                        false,
-                    )?;
-                    let (shared_pc, shared_coin) = Calculator::calc_exact_vault_in_serum(
-                        &open_orders,
-                        &market_state,
-                        market_event_q_info,
-                        amm_open_orders_info,
-                    )
-                    .unwrap();
-                    if shared_pc != 0 || shared_coin != 0 {
-                        msg!("shared_pc:{}, shared_coin:{}", shared_pc, shared_coin);
-                        return Err(AmmError::InvalidInput.into());
-                    }
-                }
-                let value = match setparams.value {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                if AmmStatus::valid_status(value) {
-                    amm.status = value as u64;
-                    set_valid = true;
-                }
-            }
-            AmmParams::State => {
-                let value = match setparams.value {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                if AmmState::valid_state(value) {
-                    amm.state = value as u64;
-                    set_valid = true;
-                }
-            }
-            AmmParams::OrderNum => {
-                let value = match setparams.value {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                if value > MAX_ORDER_LIMIT as u64 {
-                    return Err(AmmError::InvalidInput.into());
-                }
-                amm.order_num = value as u64;
-                set_valid = true;
-            }
-            AmmParams::Depth => {
-                let value = match setparams.value {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                if value > 0 && value < 100 {
-                    amm.depth = value as u64;
-                    set_valid = true;
-                }
-            }
-            AmmParams::AmountWave => {
-                let value = match setparams.value {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                amm.amount_wave = value;
-                set_valid = true;
-            }
-            AmmParams::MinPriceMultiplier => {
-                let value = match setparams.value {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                if value < amm.max_price_multiplier {
-                    amm.min_price_multiplier = value;
-                    set_valid = true;
-                }
-            }
-            AmmParams::MaxPriceMultiplier => {
-                let value = match setparams.value {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                if value > amm.max_price_multiplier {
-                    amm.max_price_multiplier = value;
-                    set_valid = true;
-                }
-            }
-            AmmParams::VolMaxCutRatio => {
-                let value = match setparams.value {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                if value <= TEN_THOUSAND {
-                    amm.vol_max_cut_ratio = value;
-                    set_valid = true;
-                }
-            }
-            AmmParams::Seperate => {
-                let value = match setparams.value {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                if value <= TEN_THOUSAND {
-                    amm.fees.min_separate_numerator = value;
-                    set_valid = true;
-                }
-            }
-            AmmParams::Fees => {
-                let fees = match setparams.fees {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                fees.validate()?;
-                amm.fees = fees;
-                set_valid = true;
-            }
-            AmmParams::AmmOwner => {
-                let new_pubkey = match setparams.new_pubkey {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                amm.amm_owner = new_pubkey;
-                set_valid = true;
-            }
-            AmmParams::SetOpenTime => {
-                let value = match setparams.value {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                amm.state_data.pool_open_time = value as u64;
-                set_valid = true;
-            }
-            AmmParams::LastOrderDistance => {
-                let mut target = TargetOrders::load_mut_checked(
-                    &amm_target_orders_info,
-                    program_id,
-                    amm_info.key,
-                )?;
-                let distance = match setparams.last_order_distance {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                target.last_order_numerator = distance.last_order_numerator;
-                target.last_order_denominator = distance.last_order_denominator;
-                set_valid = true;
-            }
-            AmmParams::InitOrderDepth => {
-                amm.order_num = 7u64;
-                amm.depth = 3u64;
-                set_valid = true;
-            }
-            AmmParams::SetSwitchTime => {
-                let value = match setparams.value {
-                    Some(a) => a,
-                    None => return Err(AmmError::InvalidInput.into()),
-                };
-                amm.state_data.orderbook_to_init_time = value as u64;
-                set_valid = true;
-            }
-            AmmParams::ClearOpenTime => {
-                amm.state_data.pool_open_time = 0;
-                amm.state_data.orderbook_to_init_time = 0;
-                set_valid = true;
+        let mut amm_order_ids_vec = Vec::new();
+        let mut order_ids = [0u64; 8];
+        let mut count = 0;
+        for i in 0..std::cmp::max(bids.len(), asks.len()) {
+            if i < bids.len() {
+                order_ids[count] = bids[i].client_order_id();
+                count += 1;
             }
-            AmmParams::UpdateOpenOrder => {
-                let new_open_orders_info = next_account_info(account_info_iter)?;
-                amm.open_orders = *new_open_orders_info.key;
-                let (_market_state, _open_orders) = Processor::load_serum_market_order(
-                    market_info,
-                    new_open_orders_info,
-                    amm_authority_info,
-                    &amm,
-                    false,
-                )?;
-                set_valid = true;
+            if i < asks.len() {
+                order_ids[count] = asks[i].client_order_id();
+                count += 1;
             }
-            _ => {
-                return Err(AmmError::InvalidInput.into());
+            if count == 8 {
+                amm_order_ids_vec.push(order_ids);
+                order_ids = [0u64; 8];
+                count = 0;
             }
         }
-        if set_valid {
-            amm.state = AmmState::CancelAllOrdersState.into_u64();
-            amm.reset_flag = AmmResetFlag::ResetYes.into_u64();
-        } else {
-            return Err(AmmError::InvalidParamsSet.into());
+        if count != 0 {
+            amm_order_ids_vec.push(order_ids);
         }
-        amm.recent_epoch = Clock::get()?.epoch;
+        for ids in amm_order_ids_vec.iter() {
+            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
+                market_program_info.clone(),
+                market_info.clone(),
+                market_bids_info.clone(),
+                market_asks_info.clone(),
+                amm_open_orders_info.clone(),
+                amm_authority_info.clone(),
+                market_event_queue_info.clone(),
+                amm_seed,
+                amm.nonce as u8,
+                *ids,
+            )?;
+        }
+
         Ok(())
     }
 
-    pub fn process_monitor_step(
+    pub fn process_admin_cancel_orders(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        monitor: MonitorStepInstruction,
+        _cancel: AdminCancelOrdersInstruction,
     ) -> ProgramResult {
-        const MIN_ACCOUNTS: usize = 18;
+        const MIN_ACCOUNTS: usize = 17;
         let input_account_len = accounts.len();
         if input_account_len != MIN_ACCOUNTS
             && input_account_len != MIN_ACCOUNTS + 1
@@ -5491,378 +10701,728 @@ impl Processor {
         {
             return Err(AmmError::WrongAccountsNumber.into());
         }
-        let (fixed_accounts, extra_token_accounts) = array_refs![accounts, MIN_ACCOUNTS; .. ;];
-        let [token_program_info, rent_info, clock_info, amm_info, amm_authority_info, amm_open_orders_info, amm_target_orders_info, amm_coin_vault_info, amm_pc_vault_info, market_program_info, market_info, market_coin_vault_info, market_pc_vault_info, market_vault_signer, market_request_queue_info, market_event_queue_info, market_bids_info, market_asks_info] =
-            fixed_accounts;
-        let mut srm_token_info = None;
-        let mut referrer_pc_info = None;
+
+        let account_info_iter = &mut accounts.iter();
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let amm_open_orders_info = next_account_info(account_info_iter)?;
+        let amm_target_orders_info = next_account_info(account_info_iter)?;
+        let amm_coin_vault_info = next_account_info(account_info_iter)?;
+        let amm_pc_vault_info = next_account_info(account_info_iter)?;
+        let amm_owner_info = next_account_info(account_info_iter)?;
+        let amm_config_info = next_account_info(account_info_iter)?;
+
+        let market_program_info = next_account_info(account_info_iter)?;
+        let market_info = next_account_info(account_info_iter)?;
+        let market_coin_vault_info = next_account_info(account_info_iter)?;
+        let market_pc_vault_info = next_account_info(account_info_iter)?;
+        let market_vault_signer_info = next_account_info(account_info_iter)?;
+        let market_event_queue_info = next_account_info(account_info_iter)?;
+        let market_bids_info = next_account_info(account_info_iter)?;
+        let market_asks_info = next_account_info(account_info_iter)?;
+
+        let mut srm_token_account = None;
+        let mut referrer_pc_wallet = None;
         if input_account_len == MIN_ACCOUNTS + 1 {
-            if let [srm_token_acc] = extra_token_accounts {
-                srm_token_info = Some(srm_token_acc);
+            let srm_token_info = next_account_info(account_info_iter)?;
+            srm_token_account = Some(srm_token_info);
+        } else if input_account_len == MIN_ACCOUNTS + 2 {
+            let srm_token_info = next_account_info(account_info_iter)?;
+            let referrer_pc_info = next_account_info(account_info_iter)?;
+            srm_token_account = Some(srm_token_info);
+            referrer_pc_wallet = Some(referrer_pc_info);
+        }
+
+        if *token_program_info.key != spl_token::ID {
+            return Err(AmmError::InvalidSplTokenProgram.into());
+        }
+        let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+        if *amm_authority_info.key
+            != Self::authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?
+        {
+            return Err(AmmError::InvalidProgramAddress.into());
+        }
+        if amm_info.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        let (pda, _) = Pubkey::find_program_address(&[&AMM_CONFIG_SEED], program_id);
+        if pda != *amm_config_info.key {
+            return Err(AmmError::InvalidConfigAccount.into());
+        }
+        let amm_config = AmmConfig::load_checked(&amm_config_info, program_id)?;
+        if !amm_owner_info.is_signer
+            || (*amm_owner_info.key != config_feature::amm_owner::ID
+                && *amm_owner_info.key != amm_config.cancel_owner)
+        {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+
+        if amm.status == AmmStatus::Uninitialized.into_u64() {
+            return Err(AmmError::InvalidStatus.into());
+        }
+        if *market_program_info.key != amm.market_program {
+            return Err(AmmError::InvalidMarketProgram.into());
+        }
+        if *market_info.key != amm.market {
+            return Err(AmmError::InvalidMarket.into());
+        }
+        if *amm_open_orders_info.key != amm.open_orders {
+            return Err(AmmError::InvalidOpenOrders.into());
+        }
+        if amm.coin_vault != *amm_coin_vault_info.key {
+            return Err(AmmError::InvalidCoinVault.into());
+        }
+        if amm.pc_vault != *amm_pc_vault_info.key {
+            return Err(AmmError::InvalidPCVault.into());
+        }
+        if amm.target_orders != *amm_target_orders_info.key {
+            return Err(AmmError::InvalidTargetOrders.into());
+        }
+        if let Some(srm_token_info) = srm_token_account {
+            let srm_token = Self::unpack_token_account(&srm_token_info, &spl_token::id())?;
+            if *amm_authority_info.key != srm_token.owner {
+                return Err(AmmError::InvalidOwner.into());
+            }
+            if srm_token.mint != srm_token::ID && srm_token.mint != msrm_token::ID {
+                return Err(AmmError::InvalidSrmMint.into());
+            }
+        }
+        if let Some(referrer_pc_info) = referrer_pc_wallet {
+            let referrer_pc_token =
+                Self::unpack_token_account(&referrer_pc_info, &spl_token::id())?;
+            if referrer_pc_token.owner != config_feature::referrer_pc_wallet::id() {
+                return Err(AmmError::InvalidOwner.into());
+            }
+            if referrer_pc_token.mint != amm.pc_vault_mint {
+                return Err(AmmError::InvalidReferPCMint.into());
+            }
+        }
+        Self::do_cancel_amm_orders(
+            &amm,
+            amm_authority_info,
+            amm_open_orders_info,
+            market_program_info,
+            market_info,
+            market_bids_info,
+            market_asks_info,
+            market_event_queue_info,
+            AUTHORITY_AMM,
+        )?;
+        let (open_order_coin_free, open_order_pc_free) = {
+            let (_market_state, open_orders) = Processor::load_serum_market_order(
+                market_info,
+                amm_open_orders_info,
+                amm_authority_info,
+                &amm,
+                true,
+            )?;
+            (open_orders.native_coin_free, open_orders.native_pc_free)
+        };
+        if open_order_coin_free == 0 && open_order_pc_free == 0 {
+            return Ok(());
+        }
+        Invokers::invoke_dex_settle_funds(
+            market_program_info.clone(),
+            market_info.clone(),
+            amm_open_orders_info.clone(),
+            amm_authority_info.clone(),
+            market_coin_vault_info.clone(),
+            market_pc_vault_info.clone(),
+            amm_coin_vault_info.clone(),
+            amm_pc_vault_info.clone(),
+            market_vault_signer_info.clone(),
+            token_program_info.clone(),
+            referrer_pc_wallet,
+            AUTHORITY_AMM,
+            amm.nonce as u8,
+        )?;
+        Ok(())
+    }
+
+    /// Processes `process_create_config` instruction.
+    pub fn process_create_config(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let amm_config_info = next_account_info(account_info_iter)?;
+        let pnl_owner_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_sysvar_info = next_account_info(account_info_iter)?;
+
+        if !admin_info.is_signer || config_feature::amm_owner::id() != *admin_info.key {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(AmmError::InvalidSysProgramAddress.into());
+        }
+
+        let (pda, bump_seed) = Pubkey::find_program_address(&[&&AMM_CONFIG_SEED], program_id);
+        if pda != *amm_config_info.key {
+            return Err(AmmError::InvalidConfigAccount.into());
+        }
+        if amm_config_info.owner != system_program_info.key {
+            return Err(AmmError::RepeatCreateConfigAccount.into());
+        }
+        let pda_signer_seeds: &[&[_]] = &[&AMM_CONFIG_SEED, &[bump_seed]];
+        let rent = &Rent::from_account_info(rent_sysvar_info)?;
+        let data_size = size_of::<AmmConfig>();
+        let required_lamports = rent
+            .minimum_balance(data_size)
+            .max(1)
+            .saturating_sub(amm_config_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    admin_info.key,
+                    amm_config_info.key,
+                    required_lamports,
+                ),
+                &[
+                    admin_info.clone(),
+                    amm_config_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(amm_config_info.key, data_size as u64),
+            &[amm_config_info.clone(), system_program_info.clone()],
+            &[&pda_signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(amm_config_info.key, &program_id),
+            &[amm_config_info.clone(), system_program_info.clone()],
+            &[&pda_signer_seeds],
+        )?;
+
+        let mut amm_config = AmmConfig::load_mut_checked(&amm_config_info, program_id)?;
+        amm_config.pnl_owner = *pnl_owner_info.key;
+        amm_config.create_pool_fee = 0;
+
+        Ok(())
+    }
+
+    /// Processes `process_update_config` instruction.
+    pub fn process_update_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        config_args: ConfigArgs,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let amm_config_info = next_account_info(account_info_iter)?;
+        if !admin_info.is_signer || config_feature::amm_owner::id() != *admin_info.key {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        let (pda, _) = Pubkey::find_program_address(&[&AMM_CONFIG_SEED], program_id);
+        if pda != *amm_config_info.key || amm_config_info.owner != program_id {
+            return Err(AmmError::InvalidConfigAccount.into());
+        }
+
+        let mut amm_config = AmmConfig::load_mut_checked(&amm_config_info, program_id)?;
+        match config_args.param {
+            0 => {
+                let pnl_owner = config_args.owner.unwrap();
+                if pnl_owner == Pubkey::default() {
+                    return Err(AmmError::InvalidInput.into());
+                }
+                amm_config.pnl_owner = pnl_owner;
+            }
+            1 => {
+                let cancel_owner = config_args.owner.unwrap();
+                if cancel_owner == Pubkey::default() {
+                    return Err(AmmError::InvalidInput.into());
+                }
+                amm_config.cancel_owner = cancel_owner;
+            }
+            2 => {
+                let create_pool_fee = config_args.create_pool_fee.unwrap();
+                amm_config.create_pool_fee = create_pool_fee;
+            }
+            3 => {
+                let reviewer = config_args.owner.unwrap();
+                if reviewer == Pubkey::default() || amm_config.is_hook_reviewer(&reviewer) {
+                    return Err(AmmError::InvalidInput.into());
+                }
+                let mut hook_reviewers = amm_config.hook_reviewers;
+                let slot = hook_reviewers
+                    .iter_mut()
+                    .find(|slot| **slot == Pubkey::default())
+                    .ok_or(AmmError::InvalidInput)?;
+                *slot = reviewer;
+                amm_config.hook_reviewers = hook_reviewers;
+            }
+            4 => {
+                let reviewer = config_args.owner.unwrap();
+                let mut hook_reviewers = amm_config.hook_reviewers;
+                let slot = hook_reviewers
+                    .iter_mut()
+                    .find(|slot| **slot == reviewer)
+                    .ok_or(AmmError::InvalidInput)?;
+                *slot = Pubkey::default();
+                amm_config.hook_reviewers = hook_reviewers;
+            }
+            5 => {
+                amm_config.hook_bond = config_args.value.unwrap();
             }
-        } else if input_account_len == MIN_ACCOUNTS + 2 {
-            if let [srm_token_acc, referrer_pc_acc] = extra_token_accounts {
-                srm_token_info = Some(srm_token_acc);
-                referrer_pc_info = Some(referrer_pc_acc);
+            6 => {
+                amm_config.hook_challenge_period = config_args.value.unwrap();
+            }
+            _ => {
+                return Err(AmmError::InvalidInput.into());
             }
         }
 
-        let mut amm = AmmInfo::load_mut_checked(amm_info, program_id)?;
-        Self::check_accounts(
-            program_id,
-            &amm,
-            amm_info,
-            token_program_info,
-            clock_info,
-            market_program_info,
-            amm_authority_info,
-            market_info,
-            amm_open_orders_info,
-            amm_coin_vault_info,
-            amm_pc_vault_info,
-            amm_target_orders_info,
-            srm_token_info,
-            referrer_pc_info,
-        )?;
-        check_assert_eq!(
-            *market_program_info.key,
-            config_feature::openbook_program::id(),
-            "market_program",
-            AmmError::InvalidMarketProgram
-        );
-        if amm_info.owner != program_id {
-            return Err(AmmError::InvalidOwner.into());
-        }
-        if amm.min_size == 0 {
-            return Err(AmmError::MarketLotSizeIsTooLarge.into());
+        return Ok(());
+    }
+
+    /// Checks the ProgramData Account of a hook program, an upgradeable hook program is
+    /// approved as deployed, with no upgrade authority or the approved one.
+    /// Returns the ProgramData address, default for a non-upgradeable loader, and the
+    /// slot the program was deployed at.
+    fn check_hook_program_data(
+        hook_program_info: &AccountInfo,
+        hook_program_data_info: &AccountInfo,
+        approved_upgrade_authority: Option<Pubkey>,
+    ) -> Result<(Pubkey, u64), ProgramError> {
+        let program_data = crate::whitelist::get_hook_program_data_address(hook_program_info)?;
+        let mut program_data_slot = 0;
+        if program_data != Pubkey::default() {
+            check_assert_eq!(
+                *hook_program_data_info.key,
+                program_data,
+                "hook_program_data",
+                AmmError::UnverifiedHookProgram
+            );
+            let (slot, upgrade_authority) =
+                crate::whitelist::get_hook_program_data(hook_program_data_info)?;
+            if upgrade_authority.is_some() && upgrade_authority != approved_upgrade_authority {
+                msg!(
+                    "Transfer hook program {} is upgradeable",
+                    hook_program_info.key
+                );
+                return Err(AmmError::UnverifiedHookProgram.into());
+            }
+            program_data_slot = slot;
         }
-        let amm_status = AmmStatus::from_u64(amm.status);
-        let amm_state = AmmState::from_u64(amm.state);
-        let spl_token_program_id = token_program_info.key;
+        Ok((program_data, program_data_slot))
+    }
 
-        if amm.reset_flag == AmmResetFlag::ResetYes.into_u64() {
-            msg!("monitor_step: ResetYes");
-            let cancel_all_orders_accounts: &[AccountInfo] = &[
-                amm_info.clone(),
-                market_program_info.clone(),
-                market_info.clone(),
-                amm_open_orders_info.clone(),
-                amm_authority_info.clone(),
-                market_event_queue_info.clone(),
-                market_coin_vault_info.clone(),
-                market_pc_vault_info.clone(),
-                market_bids_info.clone(),
-                market_asks_info.clone(),
-                market_vault_signer.clone(),
-                token_program_info.clone(),
-                amm_coin_vault_info.clone(),
-                amm_pc_vault_info.clone(),
-                amm_target_orders_info.clone(),
-            ];
-            account_parser::CancelAllOrdersArgs::with_parsed_args(
-                program_id,
-                monitor.cancel_order_limit,
-                &mut amm,
-                cancel_all_orders_accounts,
-                referrer_pc_info,
-                Self::do_cancel_all_orders_state,
-            )
-            .unwrap();
-        } else if amm.order_num == 0 {
-            msg!(arrform!(
-                LOG_SIZE,
-                "monitor_step Status:{}, order_num:{}",
-                identity(amm.status),
-                identity(amm.order_num)
-            )
-            .as_str());
-        } else {
-            match amm_status {
-                AmmStatus::Uninitialized
-                | AmmStatus::Disabled
-                | AmmStatus::WithdrawOnly
-                | AmmStatus::LiquidityOnly
-                | AmmStatus::SwapOnly
-                | AmmStatus::WaitingTrade => {
-                    msg!("monitor_step: AmmStatus:{}", identity(amm.status));
-                    return Err(AmmError::InvalidStatus.into());
-                }
-                AmmStatus::Initialized | AmmStatus::OrderBookOnly => match amm_state {
-                    AmmState::IdleState => {
-                        msg!("monitor_step IdleState:{}", identity(amm.state));
-                        let idle_accounts: &[AccountInfo] = &[
-                            amm_info.clone(),
-                            market_program_info.clone(),
-                            market_info.clone(),
-                            market_bids_info.clone(),
-                            market_asks_info.clone(),
-                            market_event_queue_info.clone(),
-                            amm_authority_info.clone(),
-                            amm_open_orders_info.clone(),
-                            amm_coin_vault_info.clone(),
-                            amm_pc_vault_info.clone(),
-                            amm_target_orders_info.clone(),
-                        ];
-                        account_parser::IdleArgs::with_parsed_args(
-                            program_id,
-                            spl_token_program_id,
-                            &mut amm,
-                            idle_accounts,
-                            Self::do_idle_state,
-                        )
-                        .unwrap();
-                    }
-                    AmmState::CancelAllOrdersState => {
-                        msg!("monitor_step CancelAllOrdersState:{}", identity(amm.state));
-                        let cancel_all_orders_accounts: &[AccountInfo] = &[
-                            amm_info.clone(),
-                            market_program_info.clone(),
-                            market_info.clone(),
-                            amm_open_orders_info.clone(),
-                            amm_authority_info.clone(),
-                            market_event_queue_info.clone(),
-                            market_coin_vault_info.clone(),
-                            market_pc_vault_info.clone(),
-                            market_bids_info.clone(),
-                            market_asks_info.clone(),
-                            market_vault_signer.clone(),
-                            token_program_info.clone(),
-                            amm_coin_vault_info.clone(),
-                            amm_pc_vault_info.clone(),
-                            amm_target_orders_info.clone(),
-                        ];
-                        account_parser::CancelAllOrdersArgs::with_parsed_args(
-                            program_id,
-                            monitor.cancel_order_limit,
-                            &mut amm,
-                            cancel_all_orders_accounts,
-                            referrer_pc_info,
-                            Self::do_cancel_all_orders_state,
-                        )
-                        .unwrap();
-                    }
-                    AmmState::PlanOrdersState => {
-                        msg!("monitor_step PlanOrdersState:{}", identity(amm.state));
-                        let plan_buy_accounts: &[AccountInfo] = &[
-                            amm_info.clone(),
-                            market_info.clone(),
-                            market_event_queue_info.clone(),
-                            amm_authority_info.clone(),
-                            amm_open_orders_info.clone(),
-                            amm_coin_vault_info.clone(),
-                            amm_pc_vault_info.clone(),
-                            amm_target_orders_info.clone(),
-                        ];
-                        account_parser::PlanOrderBookArgs::with_parsed_args(
-                            program_id,
-                            spl_token_program_id,
-                            monitor.plan_order_limit,
-                            &mut amm,
-                            plan_buy_accounts,
-                            Self::do_plan_orderbook,
-                        )
-                        .unwrap();
-                    }
-                    AmmState::CancelOrderState => {
-                        msg!("monitor_step CancelOrderState:{}", identity(amm.state));
-                        let cancel_order_accounts: &[AccountInfo] = &[
-                            amm_info.clone(),
-                            market_program_info.clone(),
-                            market_info.clone(),
-                            amm_open_orders_info.clone(),
-                            amm_authority_info.clone(),
-                            market_event_queue_info.clone(),
-                            market_bids_info.clone(),
-                            market_asks_info.clone(),
-                            amm_coin_vault_info.clone(),
-                            amm_pc_vault_info.clone(),
-                            amm_target_orders_info.clone(),
-                        ];
-                        account_parser::CancelOrderArgs::with_parsed_args(
-                            program_id,
-                            spl_token_program_id,
-                            monitor.cancel_order_limit,
-                            &mut amm,
-                            cancel_order_accounts,
-                            Self::do_cancel_order,
-                        )
-                        .unwrap();
-                    }
-                    AmmState::PlaceOrdersState => {
-                        msg!("monitor_step PlaceOrdersState:{}", identity(amm.state));
-                        let place_order_accounts: &[AccountInfo] = &[
-                            amm_info.clone(),
-                            amm_authority_info.clone(),
-                            amm_open_orders_info.clone(),
-                            market_program_info.clone(),
-                            market_info.clone(),
-                            market_request_queue_info.clone(),
-                            amm_coin_vault_info.clone(),
-                            amm_pc_vault_info.clone(),
-                            market_coin_vault_info.clone(),
-                            market_pc_vault_info.clone(),
-                            token_program_info.clone(),
-                            rent_info.clone(),
-                            market_event_queue_info.clone(),
-                            market_bids_info.clone(),
-                            market_asks_info.clone(),
-                            amm_target_orders_info.clone(),
-                            clock_info.clone(),
-                        ];
-                        account_parser::PlaceOrdersArgs::with_parsed_args(
-                            program_id,
-                            spl_token_program_id,
-                            monitor.place_order_limit,
-                            &mut amm,
-                            place_order_accounts,
-                            srm_token_info,
-                            Self::do_place_orders,
-                        )
-                        .unwrap();
-                    }
-                    AmmState::PurgeOrderState => {
-                        msg!("monitor_step PurgeOrderState:{}", identity(amm.state));
-                        let purge_orders_accounts: &[AccountInfo] = &[
-                            amm_info.clone(),
-                            market_program_info.clone(),
-                            market_info.clone(),
-                            amm_open_orders_info.clone(),
-                            amm_authority_info.clone(),
-                            market_event_queue_info.clone(),
-                            market_bids_info.clone(),
-                            market_asks_info.clone(),
-                            amm_target_orders_info.clone(),
-                        ];
-                        account_parser::PurgeOrderArgs::with_parsed_args(
-                            program_id,
-                            spl_token_program_id,
-                            monitor.cancel_order_limit,
-                            &mut amm,
-                            purge_orders_accounts,
-                            Self::do_purge_orders,
-                        )
-                        .unwrap();
-                    }
-                    _ => {
-                        msg!("monitor_step InvalidState:{}", identity(amm.state));
-                    }
-                },
+    /// Processes `process_add_transfer_hook` instruction.
+    pub fn process_add_transfer_hook(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        hook_config_args: HookConfigArgs,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let hook_config_info = next_account_info(account_info_iter)?;
+        let hook_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_sysvar_info = next_account_info(account_info_iter)?;
+        let hook_program_data_info = next_account_info(account_info_iter)?;
+
+        if !admin_info.is_signer || config_feature::amm_owner::id() != *admin_info.key {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(AmmError::InvalidSysProgramAddress.into());
+        }
+        if !hook_program_info.executable {
+            return Err(AmmError::UnauthorizedTransferHook.into());
+        }
+        // an upgradeable hook program keeps no upgrade authority or the one approved by the admin
+        let (program_data, program_data_slot) = Self::check_hook_program_data(
+            hook_program_info,
+            hook_program_data_info,
+            hook_config_args.upgrade_authority,
+        )?;
+
+        let (pda, bump_seed) =
+            crate::whitelist::hook_config_address(program_id, hook_program_info.key);
+        if pda != *hook_config_info.key {
+            return Err(AmmError::InvalidConfigAccount.into());
+        }
+        if hook_config_info.owner == system_program_info.key {
+            let pda_signer_seeds: &[&[_]] =
+                &[HOOK_CONFIG_SEED, hook_program_info.key.as_ref(), &[bump_seed]];
+            let rent = &Rent::from_account_info(rent_sysvar_info)?;
+            let data_size = size_of::<HookConfig>();
+            let required_lamports = rent
+                .minimum_balance(data_size)
+                .max(1)
+                .saturating_sub(hook_config_info.lamports());
+            if required_lamports > 0 {
+                invoke(
+                    &system_instruction::transfer(
+                        admin_info.key,
+                        hook_config_info.key,
+                        required_lamports,
+                    ),
+                    &[
+                        admin_info.clone(),
+                        hook_config_info.clone(),
+                        system_program_info.clone(),
+                    ],
+                )?;
             }
+            invoke_signed(
+                &system_instruction::allocate(hook_config_info.key, data_size as u64),
+                &[hook_config_info.clone(), system_program_info.clone()],
+                &[&pda_signer_seeds],
+            )?;
+            invoke_signed(
+                &system_instruction::assign(hook_config_info.key, &program_id),
+                &[hook_config_info.clone(), system_program_info.clone()],
+                &[&pda_signer_seeds],
+            )?;
         }
-        msg!("monitor_step end");
+
+        let mut hook_config = HookConfig::load_mut_checked(&hook_config_info, program_id)?;
+        hook_config.hook_program = *hook_program_info.key;
+        hook_config.max_compute_units = hook_config_args.max_compute_units;
+        hook_config.is_reentrant_safe = hook_config_args.is_reentrant_safe as u64;
+        hook_config.expected_accounts_hash =
+            hook_config_args.expected_accounts_hash.unwrap_or_default();
+        hook_config.bump = bump_seed as u64;
+        hook_config.upgrade_authority = hook_config_args.upgrade_authority.unwrap_or_default();
+        hook_config.program_data = program_data;
+        hook_config.program_data_slot = program_data_slot;
+        // the admin takes over a hook activated through the registry
+        hook_config.registry_approved = 0;
+        if !crate::whitelist::is_hook_safe(&hook_config) {
+            return Err(AmmError::InvalidParamsSet.into());
+        }
+        msg!("Transfer hook program {} whitelisted", hook_program_info.key);
+
         Ok(())
     }
 
-    fn do_cancel_amm_orders<'a>(
-        amm: &AmmInfo,
-        amm_authority_info: &AccountInfo<'a>,
-        amm_open_orders_info: &AccountInfo<'a>,
-        market_program_info: &AccountInfo<'a>,
-        market_info: &AccountInfo<'a>,
-        market_bids_info: &AccountInfo<'a>,
-        market_asks_info: &AccountInfo<'a>,
-        market_event_queue_info: &AccountInfo<'a>,
-        amm_seed: &[u8],
+    /// Processes `process_remove_transfer_hook` instruction.
+    pub fn process_remove_transfer_hook(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
     ) -> ProgramResult {
-        let (market_state, open_orders) = Processor::load_serum_market_order(
-            market_info,
-            amm_open_orders_info,
-            amm_authority_info,
-            amm,
-            false,
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?;
+        let hook_config_info = next_account_info(account_info_iter)?;
+
+        if !admin_info.is_signer || config_feature::amm_owner::id() != *admin_info.key {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        let hook_program = HookConfig::load_checked(&hook_config_info, program_id)?.hook_program;
+        let (pda, _) = crate::whitelist::hook_config_address(program_id, &hook_program);
+        if pda != *hook_config_info.key {
+            return Err(AmmError::InvalidConfigAccount.into());
+        }
+
+        // zeroed data is rejected by `HookConfig::load_checked` until the account is closed
+        *HookConfig::load_mut_checked(&hook_config_info, program_id)? = HookConfig::default();
+        let lamports = hook_config_info.lamports();
+        **hook_config_info.lamports.borrow_mut() = 0;
+        **admin_info.lamports.borrow_mut() = admin_info
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        msg!("Transfer hook program {} removed", hook_program);
+
+        Ok(())
+    }
+
+    /// Processes `process_propose_transfer_hook` instruction.
+    pub fn process_propose_transfer_hook(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        hook_config_args: HookConfigArgs,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let proposer_info = next_account_info(account_info_iter)?;
+        let amm_config_info = next_account_info(account_info_iter)?;
+        let hook_registration_info = next_account_info(account_info_iter)?;
+        let hook_program_info = next_account_info(account_info_iter)?;
+        let hook_program_data_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !proposer_info.is_signer {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(AmmError::InvalidSysProgramAddress.into());
+        }
+        let (pda, _) = Pubkey::find_program_address(&[&AMM_CONFIG_SEED], program_id);
+        if pda != *amm_config_info.key {
+            return Err(AmmError::InvalidConfigAccount.into());
+        }
+        let amm_config = AmmConfig::load_checked(&amm_config_info, program_id)?;
+        let bond = amm_config.hook_bond;
+        if bond == 0 {
+            msg!("Transfer hook proposals are disabled");
+            return Err(AmmError::InvalidStatus.into());
+        }
+        let challenge_end = (Clock::get()?.unix_timestamp as u64)
+            .checked_add(amm_config.hook_challenge_period)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        if !hook_program_info.executable {
+            return Err(AmmError::UnauthorizedTransferHook.into());
+        }
+        // re-entrancy is the admin's call, see `HookRegistration::hook_config`
+        if hook_config_args.is_reentrant_safe {
+            msg!("Only the admin marks a transfer hook re-entrant safe");
+            return Err(AmmError::InvalidParamsSet.into());
+        }
+        let (program_data, program_data_slot) = Self::check_hook_program_data(
+            hook_program_info,
+            hook_program_data_info,
+            hook_config_args.upgrade_authority,
         )?;
-        let bids_orders = market_state.load_bids_checked(market_bids_info)?;
-        let asks_orders = market_state.load_asks_checked(market_asks_info)?;
-        let (bids, asks) = Processor::get_amm_orders(&open_orders, bids_orders, asks_orders)?;
 
-        let mut amm_order_ids_vec = Vec::new();
-        let mut order_ids = [0u64; 8];
-        let mut count = 0;
-        for i in 0..std::cmp::max(bids.len(), asks.len()) {
-            if i < bids.len() {
-                order_ids[count] = bids[i].client_order_id();
-                count += 1;
-            }
-            if i < asks.len() {
-                order_ids[count] = asks[i].client_order_id();
-                count += 1;
-            }
-            if count == 8 {
-                amm_order_ids_vec.push(order_ids);
-                order_ids = [0u64; 8];
-                count = 0;
-            }
+        let (pda, bump_seed) =
+            crate::whitelist::hook_registration_address(program_id, hook_program_info.key);
+        if pda != *hook_registration_info.key {
+            return Err(AmmError::InvalidConfigAccount.into());
         }
-        if count != 0 {
-            amm_order_ids_vec.push(order_ids);
+        if hook_registration_info.owner != system_program_info.key {
+            return Err(AmmError::AlreadyInUse.into());
         }
-        for ids in amm_order_ids_vec.iter() {
-            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
-                market_program_info.clone(),
-                market_info.clone(),
-                market_bids_info.clone(),
-                market_asks_info.clone(),
-                amm_open_orders_info.clone(),
-                amm_authority_info.clone(),
-                market_event_queue_info.clone(),
-                amm_seed,
-                amm.nonce as u8,
-                *ids,
+        let registration = HookRegistration {
+            proposer: *proposer_info.key,
+            hook_program: *hook_program_info.key,
+            bond,
+            challenge_end,
+            max_compute_units: hook_config_args.max_compute_units,
+            expected_accounts_hash: hook_config_args.expected_accounts_hash.unwrap_or_default(),
+            upgrade_authority: hook_config_args.upgrade_authority.unwrap_or_default(),
+            program_data,
+            program_data_slot,
+            bump: bump_seed as u64,
+            padding: [0u64; 5],
+        };
+        if !crate::whitelist::is_hook_safe(&registration.hook_config()) {
+            return Err(AmmError::InvalidParamsSet.into());
+        }
+
+        // the registration account holds the bond on top of its rent
+        let pda_signer_seeds: &[&[_]] = &[
+            HOOK_REGISTRATION_SEED,
+            hook_program_info.key.as_ref(),
+            &[bump_seed],
+        ];
+        let data_size = size_of::<HookRegistration>();
+        let required_lamports = Rent::get()?
+            .minimum_balance(data_size)
+            .checked_add(bond)
+            .ok_or(AmmError::CheckedAddOverflow)?
+            .saturating_sub(hook_registration_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    proposer_info.key,
+                    hook_registration_info.key,
+                    required_lamports,
+                ),
+                &[
+                    proposer_info.clone(),
+                    hook_registration_info.clone(),
+                    system_program_info.clone(),
+                ],
             )?;
         }
+        invoke_signed(
+            &system_instruction::allocate(hook_registration_info.key, data_size as u64),
+            &[hook_registration_info.clone(), system_program_info.clone()],
+            &[&pda_signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(hook_registration_info.key, &program_id),
+            &[hook_registration_info.clone(), system_program_info.clone()],
+            &[&pda_signer_seeds],
+        )?;
+        *HookRegistration::load_mut_checked(&hook_registration_info, program_id)? = registration;
+        msg!(
+            "Transfer hook program {} proposed, challenge period ends at {}",
+            hook_program_info.key,
+            challenge_end
+        );
 
         Ok(())
     }
 
-    pub fn process_admin_cancel_orders(
+    /// Closes a hook registration, its lamports are moved to `destination_info`
+    fn close_hook_registration(
+        program_id: &Pubkey,
+        hook_registration_info: &AccountInfo,
+        destination_info: &AccountInfo,
+    ) -> ProgramResult {
+        *HookRegistration::load_mut_checked(&hook_registration_info, program_id)? =
+            HookRegistration::default();
+        let lamports = hook_registration_info.lamports();
+        **hook_registration_info.lamports.borrow_mut() = 0;
+        **destination_info.lamports.borrow_mut() = destination_info
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        Ok(())
+    }
+
+    /// Processes `process_veto_transfer_hook` instruction.
+    pub fn process_veto_transfer_hook(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        _cancel: AdminCancelOrdersInstruction,
     ) -> ProgramResult {
-        const MIN_ACCOUNTS: usize = 17;
-        let input_account_len = accounts.len();
-        if input_account_len != MIN_ACCOUNTS
-            && input_account_len != MIN_ACCOUNTS + 1
-            && input_account_len != MIN_ACCOUNTS + 2
-        {
-            return Err(AmmError::WrongAccountsNumber.into());
+        let account_info_iter = &mut accounts.iter();
+        let reviewer_info = next_account_info(account_info_iter)?;
+        let amm_config_info = next_account_info(account_info_iter)?;
+        let hook_registration_info = next_account_info(account_info_iter)?;
+        let create_fee_destination_info = next_account_info(account_info_iter)?;
+
+        let (pda, _) = Pubkey::find_program_address(&[&AMM_CONFIG_SEED], program_id);
+        if pda != *amm_config_info.key {
+            return Err(AmmError::InvalidConfigAccount.into());
+        }
+        let amm_config = AmmConfig::load_checked(&amm_config_info, program_id)?;
+        if !reviewer_info.is_signer || !amm_config.is_hook_reviewer(reviewer_info.key) {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        if *create_fee_destination_info.key != config_feature::create_pool_fee_address::id() {
+            return Err(AmmError::InvalidFee.into());
+        }
+        let registration = *HookRegistration::load_checked(&hook_registration_info, program_id)?;
+        let (pda, _) =
+            crate::whitelist::hook_registration_address(program_id, &registration.hook_program);
+        if pda != *hook_registration_info.key {
+            return Err(AmmError::InvalidConfigAccount.into());
+        }
+        if Clock::get()?.unix_timestamp as u64 >= registration.challenge_end {
+            return Err(AmmError::HookChallengePeriod.into());
         }
 
-        let account_info_iter = &mut accounts.iter();
-        let token_program_info = next_account_info(account_info_iter)?;
+        // the whole registration account is slashed, rent included
+        Self::close_hook_registration(
+            program_id,
+            hook_registration_info,
+            create_fee_destination_info,
+        )?;
+        let hook_program = registration.hook_program;
+        let bond = registration.bond;
+        msg!(
+            "Transfer hook program {} vetoed, bond of {} slashed",
+            hook_program,
+            bond
+        );
 
-        let amm_info = next_account_info(account_info_iter)?;
-        let amm_authority_info = next_account_info(account_info_iter)?;
-        let amm_open_orders_info = next_account_info(account_info_iter)?;
-        let amm_target_orders_info = next_account_info(account_info_iter)?;
-        let amm_coin_vault_info = next_account_info(account_info_iter)?;
-        let amm_pc_vault_info = next_account_info(account_info_iter)?;
-        let amm_owner_info = next_account_info(account_info_iter)?;
-        let amm_config_info = next_account_info(account_info_iter)?;
+        Ok(())
+    }
 
-        let market_program_info = next_account_info(account_info_iter)?;
-        let market_info = next_account_info(account_info_iter)?;
-        let market_coin_vault_info = next_account_info(account_info_iter)?;
-        let market_pc_vault_info = next_account_info(account_info_iter)?;
-        let market_vault_signer_info = next_account_info(account_info_iter)?;
-        let market_event_queue_info = next_account_info(account_info_iter)?;
-        let market_bids_info = next_account_info(account_info_iter)?;
-        let market_asks_info = next_account_info(account_info_iter)?;
+    /// Processes `process_activate_transfer_hook` instruction.
+    pub fn process_activate_transfer_hook(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let hook_registration_info = next_account_info(account_info_iter)?;
+        let proposer_info = next_account_info(account_info_iter)?;
+        let hook_config_info = next_account_info(account_info_iter)?;
+        let hook_program_data_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
 
-        let mut srm_token_account = None;
-        let mut referrer_pc_wallet = None;
-        if input_account_len == MIN_ACCOUNTS + 1 {
-            let srm_token_info = next_account_info(account_info_iter)?;
-            srm_token_account = Some(srm_token_info);
-        } else if input_account_len == MIN_ACCOUNTS + 2 {
-            let srm_token_info = next_account_info(account_info_iter)?;
-            let referrer_pc_info = next_account_info(account_info_iter)?;
-            srm_token_account = Some(srm_token_info);
-            referrer_pc_wallet = Some(referrer_pc_info);
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(AmmError::InvalidSysProgramAddress.into());
+        }
+        let registration = *HookRegistration::load_checked(&hook_registration_info, program_id)?;
+        let hook_program = registration.hook_program;
+        let (pda, _) = crate::whitelist::hook_registration_address(program_id, &hook_program);
+        if pda != *hook_registration_info.key {
+            return Err(AmmError::InvalidConfigAccount.into());
+        }
+        check_assert_eq!(
+            *proposer_info.key,
+            registration.proposer,
+            "proposer",
+            AmmError::InvalidOwner
+        );
+        if (Clock::get()?.unix_timestamp as u64) < registration.challenge_end {
+            return Err(AmmError::HookChallengePeriod.into());
+        }
+        let (pda, bump_seed) = crate::whitelist::hook_config_address(program_id, &hook_program);
+        if pda != *hook_config_info.key {
+            return Err(AmmError::InvalidConfigAccount.into());
         }
 
-        if *token_program_info.key != spl_token::ID {
-            return Err(AmmError::InvalidSplTokenProgram.into());
+        let mut hook_config = registration.hook_config();
+        hook_config.bump = bump_seed as u64;
+        // a hook redeployed since its proposal is not the reviewed code, the bond is
+        // returned without activating it
+        let reviewed = match crate::whitelist::validate_hook_program_data(
+            &hook_config,
+            std::slice::from_ref(hook_program_data_info),
+        ) {
+            Ok(()) => true,
+            Err(err) if err == AmmError::UnverifiedHookProgram.into() => false,
+            Err(err) => return Err(err),
+        };
+        if !reviewed {
+            msg!("Transfer hook program {} is not activated", hook_program);
+        } else if hook_config_info.owner == program_id {
+            // whitelisted by the admin during the challenge period
+            msg!(
+                "Transfer hook program {} is already whitelisted",
+                hook_program
+            );
+        } else {
+            let pda_signer_seeds: &[&[_]] =
+                &[HOOK_CONFIG_SEED, hook_program.as_ref(), &[bump_seed]];
+            let data_size = size_of::<HookConfig>();
+            invoke_signed(
+                &system_instruction::allocate(hook_config_info.key, data_size as u64),
+                &[hook_config_info.clone(), system_program_info.clone()],
+                &[&pda_signer_seeds],
+            )?;
+            invoke_signed(
+                &system_instruction::assign(hook_config_info.key, &program_id),
+                &[hook_config_info.clone(), system_program_info.clone()],
+                &[&pda_signer_seeds],
+            )?;
+            // the hook config rent is paid from the registration account, moved after the
+            // CPIs that don't take the registration account
+            let required_lamports = Rent::get()?
+                .minimum_balance(data_size)
+                .max(1)
+                .saturating_sub(hook_config_info.lamports());
+            **hook_registration_info.lamports.borrow_mut() = hook_registration_info
+                .lamports()
+                .checked_sub(required_lamports)
+                .ok_or(AmmError::InsufficientFunds)?;
+            **hook_config_info.lamports.borrow_mut() = hook_config_info
+                .lamports()
+                .checked_add(required_lamports)
+                .ok_or(AmmError::CheckedAddOverflow)?;
+            *HookConfig::load_mut_checked(&hook_config_info, program_id)? = hook_config;
+            msg!("Transfer hook program {} activated", hook_program);
+        }
+        Self::close_hook_registration(program_id, hook_registration_info, proposer_info)?;
+
+        Ok(())
+    }
+
+    /// Processes a [ClaimEscrowed](enum.Instruction.html).
+    pub fn process_claim_escrowed(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let coin_token_program_info = next_account_info(account_info_iter)?;
+        let pc_token_program_info = next_account_info(account_info_iter)?;
+        let amm_info = next_account_info(account_info_iter)?;
+        let amm_authority_info = next_account_info(account_info_iter)?;
+        let claim_info = next_account_info(account_info_iter)?;
+        let coin_escrow_info = next_account_info(account_info_iter)?;
+        let pc_escrow_info = next_account_info(account_info_iter)?;
+        let user_dest_coin_info = next_account_info(account_info_iter)?;
+        let user_dest_pc_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        // mints and transfer hook accounts of the Token-2022 sides
+        let remaining_accounts = account_info_iter.as_slice();
+
+        if !owner_info.is_signer {
+            return Err(AmmError::InvalidSignAccount.into());
         }
         let amm = AmmInfo::load_checked(&amm_info, program_id)?;
         if *amm_authority_info.key
@@ -5870,111 +11430,171 @@ impl Processor {
         {
             return Err(AmmError::InvalidProgramAddress.into());
         }
-        if amm_info.owner != program_id {
-            return Err(AmmError::InvalidOwner.into());
-        }
-        let (pda, _) = Pubkey::find_program_address(&[&AMM_CONFIG_SEED], program_id);
-        if pda != *amm_config_info.key {
-            return Err(AmmError::InvalidConfigAccount.into());
-        }
-        let amm_config = AmmConfig::load_checked(&amm_config_info, program_id)?;
-        if !amm_owner_info.is_signer
-            || (*amm_owner_info.key != config_feature::amm_owner::ID
-                && *amm_owner_info.key != amm_config.cancel_owner)
+        Self::check_token_programs(&amm, coin_token_program_info, pc_token_program_info)?;
+        let (pda, _) = escrow_claim_address(program_id, amm_info.key, owner_info.key);
+        if pda != *claim_info.key
+            || *coin_escrow_info.key
+                != escrow_token_address(program_id, amm_info.key, COIN_ESCROW_ASSOCIATED_SEED)
+            || *pc_escrow_info.key
+                != escrow_token_address(program_id, amm_info.key, PC_ESCROW_ASSOCIATED_SEED)
         {
-            return Err(AmmError::InvalidSignAccount.into());
+            return Err(AmmError::InvalidEscrowAccount.into());
         }
+        let (coin_amount, pc_amount) = {
+            let claim = EscrowClaim::load_checked(&claim_info, program_id)?;
+            (claim.coin_amount, claim.pc_amount)
+        };
 
-        if amm.status == AmmStatus::Uninitialized.into_u64() {
-            return Err(AmmError::InvalidStatus.into());
-        }
-        if *market_program_info.key != amm.market_program {
-            return Err(AmmError::InvalidMarketProgram.into());
-        }
-        if *market_info.key != amm.market {
-            return Err(AmmError::InvalidMarket.into());
+        // resolve the transfer hook accounts of both transfers before any CPI
+        let (coin_mint_info, coin_hook_config, coin_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                &amm.coin_vault_mint,
+                coin_token_program_info.key,
+                coin_escrow_info,
+                user_dest_coin_info,
+                amm_authority_info,
+                coin_amount,
+                remaining_accounts,
+                amm.accepts_registry_hooks(),
+            )?;
+        let (pc_mint_info, pc_hook_config, pc_hook_accounts, remaining_accounts) =
+            crate::whitelist::split_transfer_hook_accounts(
+                program_id,
+                &amm.pc_vault_mint,
+                pc_token_program_info.key,
+                pc_escrow_info,
+                user_dest_pc_info,
+                amm_authority_info,
+                pc_amount,
+                remaining_accounts,
+                amm.accepts_registry_hooks(),
+            )?;
+        if !remaining_accounts.is_empty() {
+            return Err(AmmError::InvalidTransferHookAccounts.into());
+        }
+        if coin_amount > 0 {
+            crate::token_invokers::token_transfer_with_hook_budget(
+                coin_hook_config.as_ref(),
+                coin_token_program_info.clone(),
+                coin_escrow_info.clone(),
+                coin_mint_info.cloned(),
+                user_dest_coin_info.clone(),
+                amm_authority_info.clone(),
+                coin_hook_accounts,
+                coin_amount,
+                amm.coin_decimals as u8,
+                &[AUTHORITY_AMM, &[amm.nonce as u8]],
+            )?;
         }
-        if *amm_open_orders_info.key != amm.open_orders {
-            return Err(AmmError::InvalidOpenOrders.into());
+        if pc_amount > 0 {
+            crate::token_invokers::token_transfer_with_hook_budget(
+                pc_hook_config.as_ref(),
+                pc_token_program_info.clone(),
+                pc_escrow_info.clone(),
+                pc_mint_info.cloned(),
+                user_dest_pc_info.clone(),
+                amm_authority_info.clone(),
+                pc_hook_accounts,
+                pc_amount,
+                amm.pc_decimals as u8,
+                &[AUTHORITY_AMM, &[amm.nonce as u8]],
+            )?;
         }
-        if amm.coin_vault != *amm_coin_vault_info.key {
-            return Err(AmmError::InvalidCoinVault.into());
+
+        // everything is delivered, the claim is closed to its owner
+        *EscrowClaim::load_mut_checked(&claim_info, program_id)? = EscrowClaim::default();
+        let lamports = claim_info.lamports();
+        **claim_info.lamports.borrow_mut() = 0;
+        **owner_info.lamports.borrow_mut() = owner_info
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        msg!(
+            "Claimed escrowed coin:{}, pc:{} for {}",
+            coin_amount,
+            pc_amount,
+            owner_info.key
+        );
+        Ok(())
+    }
+
+    /// Processes a [CreateTradeContext](enum.Instruction.html).
+    pub fn process_create_trade_context(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer_info = next_account_info(account_info_iter)?;
+        let trade_context_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !payer_info.is_signer {
+            return Err(AmmError::InvalidSignAccount.into());
         }
-        if amm.pc_vault != *amm_pc_vault_info.key {
-            return Err(AmmError::InvalidPCVault.into());
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(AmmError::InvalidSysProgramAddress.into());
         }
-        if amm.target_orders != *amm_target_orders_info.key {
-            return Err(AmmError::InvalidTargetOrders.into());
+        // only a Token-2022 mint can have a transfer hook reading the context
+        if *mint_info.owner != spl_token_2022::id() {
+            return Err(AmmError::InvalidSplTokenProgram.into());
         }
-        if let Some(srm_token_info) = srm_token_account {
-            let srm_token = Self::unpack_token_account(&srm_token_info, &spl_token::id())?;
-            if *amm_authority_info.key != srm_token.owner {
-                return Err(AmmError::InvalidOwner.into());
-            }
-            if srm_token.mint != srm_token::ID && srm_token.mint != msrm_token::ID {
-                return Err(AmmError::InvalidSrmMint.into());
-            }
+        let (pda, bump_seed) = trade_context_address(program_id, mint_info.key);
+        if pda != *trade_context_info.key {
+            return Err(AmmError::InvalidTradeContext.into());
         }
-        if let Some(referrer_pc_info) = referrer_pc_wallet {
-            let referrer_pc_token =
-                Self::unpack_token_account(&referrer_pc_info, &spl_token::id())?;
-            if referrer_pc_token.owner != config_feature::referrer_pc_wallet::id() {
-                return Err(AmmError::InvalidOwner.into());
-            }
-            if referrer_pc_token.mint != amm.pc_vault_mint {
-                return Err(AmmError::InvalidReferPCMint.into());
-            }
+        if trade_context_info.owner != system_program_info.key {
+            return Err(AmmError::AlreadyInUse.into());
         }
-        Self::do_cancel_amm_orders(
-            &amm,
-            amm_authority_info,
-            amm_open_orders_info,
-            market_program_info,
-            market_info,
-            market_bids_info,
-            market_asks_info,
-            market_event_queue_info,
-            AUTHORITY_AMM,
-        )?;
-        let (open_order_coin_free, open_order_pc_free) = {
-            let (_market_state, open_orders) = Processor::load_serum_market_order(
-                market_info,
-                amm_open_orders_info,
-                amm_authority_info,
-                &amm,
-                true,
+        let pda_signer_seeds: &[&[_]] = &[TRADE_CONTEXT_SEED, mint_info.key.as_ref(), &[bump_seed]];
+        let data_size = size_of::<TradeContext>();
+        let required_lamports = Rent::get()?
+            .minimum_balance(data_size)
+            .saturating_sub(trade_context_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    payer_info.key,
+                    trade_context_info.key,
+                    required_lamports,
+                ),
+                &[
+                    payer_info.clone(),
+                    trade_context_info.clone(),
+                    system_program_info.clone(),
+                ],
             )?;
-            (open_orders.native_coin_free, open_orders.native_pc_free)
-        };
-        if open_order_coin_free == 0 && open_order_pc_free == 0 {
-            return Ok(());
         }
-        Invokers::invoke_dex_settle_funds(
-            market_program_info.clone(),
-            market_info.clone(),
-            amm_open_orders_info.clone(),
-            amm_authority_info.clone(),
-            market_coin_vault_info.clone(),
-            market_pc_vault_info.clone(),
-            amm_coin_vault_info.clone(),
-            amm_pc_vault_info.clone(),
-            market_vault_signer_info.clone(),
-            token_program_info.clone(),
-            referrer_pc_wallet,
-            AUTHORITY_AMM,
-            amm.nonce as u8,
+        invoke_signed(
+            &system_instruction::allocate(trade_context_info.key, data_size as u64),
+            &[trade_context_info.clone(), system_program_info.clone()],
+            &[&pda_signer_seeds],
         )?;
+        invoke_signed(
+            &system_instruction::assign(trade_context_info.key, &program_id),
+            &[trade_context_info.clone(), system_program_info.clone()],
+            &[&pda_signer_seeds],
+        )?;
+        *TradeContext::load_mut_checked(&trade_context_info, program_id)? = TradeContext {
+            mint: *mint_info.key,
+            bump: bump_seed as u64,
+            ..Default::default()
+        };
         Ok(())
     }
 
-    /// Processes `process_create_config` instruction.
-    pub fn process_create_config(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    /// Processes a [CreatePriceOracle](enum.Instruction.html).
+    pub fn process_create_price_oracle(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        create: CreatePriceOracleInstruction,
+    ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let admin_info = next_account_info(account_info_iter)?;
-        let amm_config_info = next_account_info(account_info_iter)?;
-        let pnl_owner_info = next_account_info(account_info_iter)?;
+        let amm_info = next_account_info(account_info_iter)?;
+        let price_oracle_info = next_account_info(account_info_iter)?;
         let system_program_info = next_account_info(account_info_iter)?;
-        let rent_sysvar_info = next_account_info(account_info_iter)?;
 
         if !admin_info.is_signer || config_feature::amm_owner::id() != *admin_info.key {
             return Err(AmmError::InvalidSignAccount.into());
@@ -5982,96 +11602,142 @@ impl Processor {
         if *system_program_info.key != solana_program::system_program::id() {
             return Err(AmmError::InvalidSysProgramAddress.into());
         }
-
-        let (pda, bump_seed) = Pubkey::find_program_address(&[&&AMM_CONFIG_SEED], program_id);
-        if pda != *amm_config_info.key {
-            return Err(AmmError::InvalidConfigAccount.into());
+        if create.observation_len == 0 || create.observation_len > MAX_PRICE_OBSERVATIONS {
+            return Err(AmmError::InvalidInput.into());
         }
-        if amm_config_info.owner != system_program_info.key {
-            return Err(AmmError::RepeatCreateConfigAccount.into());
+        let (mut amm, amm_extension) = AmmInfo::load_mut_checked_extended(&amm_info, program_id)?;
+        if amm.has_price_oracle() {
+            return Err(AmmError::AlreadyInUse.into());
         }
-        let pda_signer_seeds: &[&[_]] = &[&AMM_CONFIG_SEED, &[bump_seed]];
-        let rent = &Rent::from_account_info(rent_sysvar_info)?;
-        let data_size = size_of::<AmmConfig>();
-        let required_lamports = rent
+        // the oracle records the accumulator of the `AmmExtension`, older pools get it
+        // with `ExtendAmm` first
+        if amm_extension.is_none() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let (pda, bump_seed) = price_oracle_address(program_id, amm_info.key);
+        if pda != *price_oracle_info.key {
+            return Err(AmmError::InvalidPriceOracle.into());
+        }
+        if price_oracle_info.owner != system_program_info.key {
+            return Err(AmmError::AlreadyInUse.into());
+        }
+        let pda_signer_seeds: &[&[_]] = &[PRICE_ORACLE_SEED, amm_info.key.as_ref(), &[bump_seed]];
+        let data_size = PriceOracle::space(create.observation_len);
+        let required_lamports = Rent::get()?
             .minimum_balance(data_size)
-            .max(1)
-            .saturating_sub(amm_config_info.lamports());
+            .saturating_sub(price_oracle_info.lamports());
         if required_lamports > 0 {
             invoke(
                 &system_instruction::transfer(
                     admin_info.key,
-                    amm_config_info.key,
+                    price_oracle_info.key,
                     required_lamports,
                 ),
                 &[
                     admin_info.clone(),
-                    amm_config_info.clone(),
+                    price_oracle_info.clone(),
                     system_program_info.clone(),
                 ],
             )?;
         }
         invoke_signed(
-            &system_instruction::allocate(amm_config_info.key, data_size as u64),
-            &[amm_config_info.clone(), system_program_info.clone()],
+            &system_instruction::allocate(price_oracle_info.key, data_size as u64),
+            &[price_oracle_info.clone(), system_program_info.clone()],
             &[&pda_signer_seeds],
         )?;
         invoke_signed(
-            &system_instruction::assign(amm_config_info.key, &program_id),
-            &[amm_config_info.clone(), system_program_info.clone()],
+            &system_instruction::assign(price_oracle_info.key, &program_id),
+            &[price_oracle_info.clone(), system_program_info.clone()],
             &[&pda_signer_seeds],
         )?;
-
-        let mut amm_config = AmmConfig::load_mut_checked(&amm_config_info, program_id)?;
-        amm_config.pnl_owner = *pnl_owner_info.key;
-        amm_config.create_pool_fee = 0;
-
+        PriceOracle::initialize(
+            price_oracle_info,
+            program_id,
+            amm_info.key,
+            create.observation_len,
+            bump_seed,
+        )?;
+        // swaps and deposits take the oracle from now on
+        amm.pool_flags |= POOL_FLAG_PRICE_ORACLE;
+        msg!(
+            "price oracle {} of {} keeps {} observations",
+            price_oracle_info.key,
+            amm_info.key,
+            create.observation_len
+        );
         Ok(())
     }
 
-    /// Processes `process_update_config` instruction.
-    pub fn process_update_config(
+    /// Processes a [PauseOnHookChange](enum.Instruction.html).
+    pub fn process_pause_on_hook_change(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        config_args: ConfigArgs,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let admin_info = next_account_info(account_info_iter)?;
-        let amm_config_info = next_account_info(account_info_iter)?;
-        if !admin_info.is_signer || config_feature::amm_owner::id() != *admin_info.key {
-            return Err(AmmError::InvalidSignAccount.into());
+        let amm_info = next_account_info(account_info_iter)?;
+        let coin_mint_info = next_account_info(account_info_iter)?;
+        let pc_mint_info = next_account_info(account_info_iter)?;
+
+        let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
+        check_assert_eq!(
+            *coin_mint_info.key,
+            amm.coin_vault_mint,
+            "coin_mint",
+            AmmError::InvalidCoinMint
+        );
+        check_assert_eq!(
+            *pc_mint_info.key,
+            amm.pc_vault_mint,
+            "pc_mint",
+            AmmError::InvalidPCMint
+        );
+        // a pool the admin already closed to deposits and swaps keeps its status
+        let status = AmmStatus::from_u64(amm.status);
+        if !status.deposit_permission() && !status.swap_permission() {
+            return Err(AmmError::InvalidStatus.into());
         }
-        let (pda, _) = Pubkey::find_program_address(&[&AMM_CONFIG_SEED], program_id);
-        if pda != *amm_config_info.key || amm_config_info.owner != program_id {
-            return Err(AmmError::InvalidConfigAccount.into());
+        if Self::check_hook_programs(&mut amm, Some(coin_mint_info), Some(pc_mint_info))? {
+            msg!("pause_on_hook_change: {} is withdraw only", amm_info.key);
+        } else {
+            msg!("pause_on_hook_change: transfer hook programs unchanged");
         }
+        Ok(())
+    }
 
-        let mut amm_config = AmmConfig::load_mut_checked(&amm_config_info, program_id)?;
-        match config_args.param {
-            0 => {
-                let pnl_owner = config_args.owner.unwrap();
-                if pnl_owner == Pubkey::default() {
-                    return Err(AmmError::InvalidInput.into());
-                }
-                amm_config.pnl_owner = pnl_owner;
-            }
-            1 => {
-                let cancel_owner = config_args.owner.unwrap();
-                if cancel_owner == Pubkey::default() {
-                    return Err(AmmError::InvalidInput.into());
-                }
-                amm_config.cancel_owner = cancel_owner;
-            }
-            2 => {
-                let create_pool_fee = config_args.create_pool_fee.unwrap();
-                amm_config.create_pool_fee = create_pool_fee;
-            }
-            _ => {
-                return Err(AmmError::InvalidInput.into());
-            }
-        }
+    /// Processes an [ExtendAmm](enum.Instruction.html).
+    pub fn process_extend_amm(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer_info = next_account_info(account_info_iter)?;
+        let amm_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
 
-        return Ok(());
+        if !payer_info.is_signer {
+            return Err(AmmError::InvalidSignAccount.into());
+        }
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(AmmError::InvalidSysProgramAddress.into());
+        }
+        AmmInfo::load_checked(amm_info, program_id)?;
+        if amm_info.data_len() == AMM_EXTENDED_LEN {
+            return Err(AmmError::AlreadyInUse.into());
+        }
+        let required_lamports = Rent::get()?
+            .minimum_balance(AMM_EXTENDED_LEN)
+            .saturating_sub(amm_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(payer_info.key, amm_info.key, required_lamports),
+                &[
+                    payer_info.clone(),
+                    amm_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        // the extension starts zeroed, the pool accumulates its prices from its next trade
+        amm_info.realloc(AMM_EXTENDED_LEN, true)?;
+        msg!("extend_amm: {} is {} bytes", amm_info.key, AMM_EXTENDED_LEN);
+        Ok(())
     }
 
     /// Processes an [Instruction](enum.Instruction.html).
@@ -6124,6 +11790,88 @@ impl Processor {
             AmmInstruction::UpdateConfigAccount(config_args) => {
                 Self::process_update_config(program_id, accounts, config_args)
             }
+            AmmInstruction::AddTransferHook(hook_config_args) => {
+                Self::process_add_transfer_hook(program_id, accounts, hook_config_args)
+            }
+            AmmInstruction::RemoveTransferHook => {
+                Self::process_remove_transfer_hook(program_id, accounts)
+            }
+            AmmInstruction::SwapBaseInV2(swap) => {
+                let accounts = Self::swap_v2_accounts(
+                    program_id,
+                    accounts,
+                    swap.coin_hook_account_count,
+                    swap.pc_hook_account_count,
+                )?;
+                Self::process_swap_base_in(
+                    program_id,
+                    &accounts,
+                    SwapInstructionBaseIn {
+                        amount_in: swap.amount_in,
+                        minimum_amount_out: swap.minimum_amount_out,
+                    },
+                )
+            }
+            AmmInstruction::SwapBaseOutV2(swap) => {
+                let accounts = Self::swap_v2_accounts(
+                    program_id,
+                    accounts,
+                    swap.coin_hook_account_count,
+                    swap.pc_hook_account_count,
+                )?;
+                Self::process_swap_base_out(
+                    program_id,
+                    &accounts,
+                    SwapInstructionBaseOut {
+                        max_amount_in: swap.max_amount_in,
+                        amount_out: swap.amount_out,
+                    },
+                )
+            }
+            AmmInstruction::InitializeNoOrderbook(init) => {
+                Self::process_initialize_no_orderbook(program_id, accounts, init)
+            }
+            AmmInstruction::DepositNoOrderbook(deposit) => {
+                Self::process_deposit_no_orderbook(program_id, accounts, deposit)
+            }
+            AmmInstruction::WithdrawNoOrderbook(withdraw) => {
+                Self::process_withdraw_no_orderbook(program_id, accounts, withdraw)
+            }
+            AmmInstruction::SwapBaseInNoOrderbook(swap) => {
+                Self::process_swap_base_in_no_orderbook(program_id, accounts, swap)
+            }
+            AmmInstruction::SwapBaseOutNoOrderbook(swap) => {
+                Self::process_swap_base_out_no_orderbook(program_id, accounts, swap)
+            }
+            AmmInstruction::ProposeTransferHook(hook_config_args) => {
+                Self::process_propose_transfer_hook(program_id, accounts, hook_config_args)
+            }
+            AmmInstruction::VetoTransferHook => {
+                Self::process_veto_transfer_hook(program_id, accounts)
+            }
+            AmmInstruction::ActivateTransferHook => {
+                Self::process_activate_transfer_hook(program_id, accounts)
+            }
+            AmmInstruction::ClaimEscrowed => Self::process_claim_escrowed(program_id, accounts),
+            AmmInstruction::CreateTradeContext => {
+                Self::process_create_trade_context(program_id, accounts)
+            }
+            AmmInstruction::SwapRoute(route) => {
+                Self::process_swap_route(program_id, accounts, route)
+            }
+            AmmInstruction::CreatePriceOracle(create) => {
+                Self::process_create_price_oracle(program_id, accounts, create)
+            }
+            AmmInstruction::DepositSingle(deposit) => {
+                Self::process_deposit_single(program_id, accounts, deposit)
+            }
+            AmmInstruction::WithdrawSingle(withdraw) => {
+                Self::process_withdraw_single(program_id, accounts, withdraw)
+            }
+            AmmInstruction::PauseOnHookChange => {
+                Self::process_pause_on_hook_change(program_id, accounts)
+            }
+            AmmInstruction::ExtendAmm => Self::process_extend_amm(program_id, accounts),
         }
     }
 }
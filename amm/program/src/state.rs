@@ -1,6 +1,10 @@
 //! State transition types
 
-use crate::{error::AmmError, math::Calculator};
+use crate::{
+    error::AmmError,
+    math::{Calculator, Curve, StableSwap, U256},
+    processor::{PRICE_ORACLE_SEED, TRADE_CONTEXT_SEED},
+};
 use serum_dex::state::ToAlignedBytes;
 use solana_program::{
     account_info::AccountInfo,
@@ -10,7 +14,7 @@ use solana_program::{
 };
 
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
-use bytemuck::{from_bytes, from_bytes_mut, Pod, Zeroable};
+use bytemuck::{cast_slice, cast_slice_mut, from_bytes, from_bytes_mut, Pod, Zeroable};
 use safe_transmute::{self, trivial::TriviallyTransmutable};
 use serde::{Deserialize, Serialize};
 use std::{
@@ -235,6 +239,9 @@ pub enum AmmStatus {
     SwapOnly = 6u64,
     // pool status after created and will auto update to SwapOnly during swap after open_time
     WaitingTrade = 7u64,
+    // the transfer hook program of a pool mint changed since the pool was created,
+    // pool only can remove liquidity until the admin revives it
+    HookProgramChanged = 8u64,
 }
 impl AmmStatus {
     pub fn from_u64(status: u64) -> Self {
@@ -247,6 +254,7 @@ impl AmmStatus {
             5u64 => AmmStatus::OrderBookOnly,
             6u64 => AmmStatus::SwapOnly,
             7u64 => AmmStatus::WaitingTrade,
+            8u64 => AmmStatus::HookProgramChanged,
             _ => unreachable!(),
         }
     }
@@ -261,11 +269,12 @@ impl AmmStatus {
             AmmStatus::OrderBookOnly => 5u64,
             AmmStatus::SwapOnly => 6u64,
             AmmStatus::WaitingTrade => 7u64,
+            AmmStatus::HookProgramChanged => 8u64,
         }
     }
     pub fn valid_status(status: u64) -> bool {
         match status {
-            1u64 | 2u64 | 3u64 | 4u64 | 5u64 | 6u64 | 7u64 => return true,
+            1u64 | 2u64 | 3u64 | 4u64 | 5u64 | 6u64 | 7u64 | 8u64 => return true,
             _ => return false,
         }
     }
@@ -280,6 +289,7 @@ impl AmmStatus {
             AmmStatus::OrderBookOnly => true,
             AmmStatus::SwapOnly => true,
             AmmStatus::WaitingTrade => true,
+            AmmStatus::HookProgramChanged => false,
         }
     }
 
@@ -293,6 +303,7 @@ impl AmmStatus {
             AmmStatus::OrderBookOnly => true,
             AmmStatus::SwapOnly => true,
             AmmStatus::WaitingTrade => true,
+            AmmStatus::HookProgramChanged => true,
         }
     }
 
@@ -306,6 +317,7 @@ impl AmmStatus {
             AmmStatus::OrderBookOnly => false,
             AmmStatus::SwapOnly => true,
             AmmStatus::WaitingTrade => true,
+            AmmStatus::HookProgramChanged => false,
         }
     }
 
@@ -319,6 +331,7 @@ impl AmmStatus {
             AmmStatus::OrderBookOnly => true,
             AmmStatus::SwapOnly => false,
             AmmStatus::WaitingTrade => false,
+            AmmStatus::HookProgramChanged => false,
         }
     }
 }
@@ -388,6 +401,8 @@ pub enum AmmParams {
     ClearOpenTime = 15u64,
     Seperate = 16u64,
     UpdateOpenOrder = 17u64,
+    RampAmp = 18u64,
+    FeeSplit = 19u64,
 }
 impl AmmParams {
     pub fn from_u64(state: u64) -> Self {
@@ -410,6 +425,8 @@ impl AmmParams {
             15u64 => AmmParams::ClearOpenTime,
             16u64 => AmmParams::Seperate,
             17u64 => AmmParams::UpdateOpenOrder,
+            18u64 => AmmParams::RampAmp,
+            19u64 => AmmParams::FeeSplit,
             _ => unreachable!(),
         }
     }
@@ -434,6 +451,8 @@ impl AmmParams {
             AmmParams::ClearOpenTime => 15u64,
             AmmParams::Seperate => 16u64,
             AmmParams::UpdateOpenOrder => 17u64,
+            AmmParams::RampAmp => 18u64,
+            AmmParams::FeeSplit => 19u64,
         }
     }
 }
@@ -593,8 +612,10 @@ pub struct StateData {
     pub total_pnl_coin: u64,
     /// ido pool open time
     pub pool_open_time: u64,
-    /// padding for future updates
-    pub padding: [u64; 2],
+    /// Token-2022 extensions of the coin mint, bit n is set for `ExtensionType` n
+    pub coin_mint_extensions: u64,
+    /// Token-2022 extensions of the pc mint, bit n is set for `ExtensionType` n
+    pub pc_mint_extensions: u64,
     /// switch from orderbookonly to init
     pub orderbook_to_init_time: u64,
 
@@ -620,7 +641,7 @@ impl StateData {
         self.total_pnl_pc = 0u64;
         self.total_pnl_coin = 0u64;
         self.pool_open_time = open_time;
-        self.padding = Zeroable::zeroed();
+        // mint extensions are kept, migrate_to_openbook reinitializes an existing pool
         self.orderbook_to_init_time = 0u64;
         self.swap_coin_in_amount = 0u128;
         self.swap_pc_out_amount = 0u128;
@@ -695,7 +716,8 @@ pub struct AmmInfo {
     pub market_program: Pubkey,
     /// target_orders key
     pub target_orders: Pubkey,
-    /// padding
+    /// transfer hook programs of the coin and pc mints pinned at pool creation,
+    /// see `AmmInfo::pinned_hook_programs`
     pub padding1: [u64; 8],
     /// amm owner key
     pub amm_owner: Pubkey,
@@ -705,10 +727,96 @@ pub struct AmmInfo {
     pub client_order_id: u64,
     /// recent epoch
     pub recent_epoch: u64,
+    /// pool flags, see POOL_FLAG_*
+    pub pool_flags: u64,
+}
+unsafe impl Zeroable for AmmInfo {}
+unsafe impl Pod for AmmInfo {}
+unsafe impl TriviallyTransmutable for AmmInfo {}
+/// an extended AMM Account keeps its `AmmExtension` after the `AmmInfo`
+impl Loadable for AmmInfo {
+    fn load_mut<'a>(account: &'a AccountInfo) -> Result<RefMut<'a, Self>, ProgramError> {
+        if account.data_len() < size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        Ok(RefMut::map(account.try_borrow_mut_data()?, |data| {
+            from_bytes_mut(&mut data[..size_of::<Self>()])
+        }))
+    }
+    fn load<'a>(account: &'a AccountInfo) -> Result<Ref<'a, Self>, ProgramError> {
+        if account.data_len() < size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        Ok(Ref::map(account.try_borrow_data()?, |data| {
+            from_bytes(&data[..size_of::<Self>()])
+        }))
+    }
+}
+
+/// size of an AMM Account with its `AmmExtension`
+pub const AMM_EXTENDED_LEN: usize = size_of::<AmmInfo>() + size_of::<AmmExtension>();
+
+/// Pool state that does not fit the `AmmInfo` layout, kept right after it in the AMM Account.
+/// Pools are created with it, see `AMM_EXTENDED_LEN`.
+#[cfg_attr(feature = "client", derive(Debug))]
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct AmmExtension {
+    /// amplification ramp of a StableSwap pool, see `AmmInfo::amp_ramp`
+    pub amp_ramp: AmpRamp,
+    /// cumulative prices of the pool, updated by every swap and deposit
+    pub price: PriceAccumulator,
+    /// split of the swap fee, see `AmmInfo::fee_split`
+    pub fee_split: FeeSplit,
     /// padding
-    pub padding2: u64,
+    pub padding: [u64; 14],
+}
+impl_loadable!(AmmExtension);
+
+impl AmmExtension {
+    /// Decode the extension from the data of an AMM Account, `None` for an AMM Account
+    /// created before it. For clients, the program reads it with `AmmInfo::load_checked_extended`.
+    pub fn unpack(data: &[u8]) -> Option<&Self> {
+        if data.len() != AMM_EXTENDED_LEN {
+            return None;
+        }
+        Some(from_bytes(&data[size_of::<AmmInfo>()..]))
+    }
+}
+
+/// coin vault and coin mint are owned by the Token-2022 program
+pub const POOL_FLAG_COIN_TOKEN_2022: u64 = 1 << 0;
+/// pc vault, pc mint and lp mint are owned by the Token-2022 program
+pub const POOL_FLAG_PC_TOKEN_2022: u64 = 1 << 1;
+/// pool created by `InitializeNoOrderbook`, without market, open orders and target orders
+pub const POOL_FLAG_NO_ORDERBOOK: u64 = 1 << 2;
+/// transfer hook programs of the mints are pinned in `padding1`, pools created
+/// before pinning have none
+pub const POOL_FLAG_HOOKS_PINNED: u64 = 1 << 3;
+/// set while a swap, deposit or withdraw moves the vault balances, a transfer hook
+/// re-entering the program finds the pool locked
+pub const POOL_FLAG_LOCKED: u64 = 1 << 4;
+/// the pool accepts transfer hooks activated through the bonded registry besides
+/// the hooks whitelisted by the admin
+pub const POOL_FLAG_REGISTRY_HOOKS: u64 = 1 << 5;
+/// the pool trades on the StableSwap curve, its amplification ramp is kept in the
+/// `AmmExtension` of its AMM Account
+pub const POOL_FLAG_STABLE_SWAP: u64 = 1 << 6;
+/// the pool has a `PriceOracle`, which every swap and deposit of the pool records its
+/// cumulative prices in
+pub const POOL_FLAG_PRICE_ORACLE: u64 = 1 << 7;
+/// the swap fee is split between the LPs, the protocol and the referrer, the split is
+/// kept in the `AmmExtension` of its AMM Account
+pub const POOL_FLAG_FEE_SPLIT: u64 = 1 << 8;
+
+/// bonding curve of a pool
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CurveType {
+    /// x * y = k
+    ConstantProduct,
+    /// amplified invariant for pegged pairs, pools without orderbook only
+    StableSwap,
 }
-impl_loadable!(AmmInfo);
 
 impl AmmInfo {
     /// Helper function to get the more efficient packed size of the struct
@@ -718,16 +826,14 @@ impl AmmInfo {
         account: &'a AccountInfo,
         program_id: &Pubkey,
     ) -> Result<RefMut<'a, Self>, ProgramError> {
-        if account.owner != program_id {
-            return Err(AmmError::InvalidAmmAccountOwner.into());
-        }
-        if account.data_len() != size_of::<Self>() {
-            return Err(AmmError::ExpectedAccount.into());
-        }
+        Self::check_account(account, program_id)?;
         let data = Self::load_mut(account)?;
         if data.status == AmmStatus::Uninitialized as u64 {
             return Err(AmmError::InvalidStatus.into());
         }
+        if data.is_locked() {
+            return Err(AmmError::PoolLocked.into());
+        }
         Ok(data)
     }
 
@@ -737,19 +843,84 @@ impl AmmInfo {
         account: &'a AccountInfo,
         program_id: &Pubkey,
     ) -> Result<Ref<'a, Self>, ProgramError> {
-        if account.owner != program_id {
-            return Err(AmmError::InvalidAmmAccountOwner.into());
-        }
-        if account.data_len() != size_of::<Self>() {
-            return Err(AmmError::ExpectedAccount.into());
-        }
+        Self::check_account(account, program_id)?;
         let data = Self::load(account)?;
         if data.status == AmmStatus::Uninitialized as u64 {
             return Err(AmmError::InvalidStatus.into());
         }
+        if data.is_locked() {
+            return Err(AmmError::PoolLocked.into());
+        }
         Ok(data)
     }
 
+    /// load_mut_checked, with the `AmmExtension` of an extended AMM Account
+    #[inline]
+    pub fn load_mut_checked_extended<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<(RefMut<'a, Self>, Option<RefMut<'a, AmmExtension>>), ProgramError> {
+        Self::check_account(account, program_id)?;
+        let (data, extension) = Self::load_mut_extended(account)?;
+        if data.status == AmmStatus::Uninitialized as u64 {
+            return Err(AmmError::InvalidStatus.into());
+        }
+        if data.is_locked() {
+            return Err(AmmError::PoolLocked.into());
+        }
+        Ok((data, extension))
+    }
+
+    /// load_checked, with the `AmmExtension` of an extended AMM Account. Other programs read
+    /// the cumulative prices of a pool with it, see `PriceOracle::observe`.
+    #[inline]
+    pub fn load_checked_extended<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<(Ref<'a, Self>, Option<Ref<'a, AmmExtension>>), ProgramError> {
+        if !Self::check_account(account, program_id)? {
+            return Ok((Self::load_checked(account, program_id)?, None));
+        }
+        let (data, extension): (Ref<Self>, Ref<AmmExtension>) =
+            Ref::map_split(account.try_borrow_data()?, |data| {
+                let (data, extension) = data.split_at(size_of::<Self>());
+                (from_bytes(data), from_bytes(extension))
+            });
+        if data.status == AmmStatus::Uninitialized as u64 {
+            return Err(AmmError::InvalidStatus.into());
+        }
+        if data.is_locked() {
+            return Err(AmmError::PoolLocked.into());
+        }
+        Ok((data, Some(extension)))
+    }
+
+    /// load_mut, with the `AmmExtension` of an extended AMM Account
+    pub fn load_mut_extended<'a>(
+        account: &'a AccountInfo,
+    ) -> Result<(RefMut<'a, Self>, Option<RefMut<'a, AmmExtension>>), ProgramError> {
+        if account.data_len() != AMM_EXTENDED_LEN {
+            return Ok((Self::load_mut(account)?, None));
+        }
+        let (data, extension) = RefMut::map_split(account.try_borrow_mut_data()?, |data| {
+            let (data, extension) = data.split_at_mut(size_of::<Self>());
+            (from_bytes_mut(data), from_bytes_mut(extension))
+        });
+        Ok((data, Some(extension)))
+    }
+
+    /// Checks the owner and the size of an AMM Account, returns whether it is extended
+    fn check_account(account: &AccountInfo, program_id: &Pubkey) -> Result<bool, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidAmmAccountOwner.into());
+        }
+        match account.data_len() {
+            len if len == size_of::<Self>() => Ok(false),
+            AMM_EXTENDED_LEN => Ok(true),
+            _ => Err(AmmError::ExpectedAccount.into()),
+        }
+    }
+
     pub fn initialize(
         &mut self,
         nonce: u8,
@@ -838,13 +1009,181 @@ impl AmmInfo {
         self.min_price_multiplier = 1;
         self.max_price_multiplier = 1000000000;
         self.client_order_id = 0;
-        self.padding1 = Zeroable::zeroed();
         self.recent_epoch = get_recent_epoch().unwrap();
-        self.padding2 = Zeroable::zeroed();
+        // pool_flags and the hook programs pinned in padding1 are kept,
+        // migrate_to_openbook reinitializes an existing pool
 
         Ok(())
     }
 
+    /// see `POOL_FLAG_LOCKED`, a locked pool fails to load
+    pub fn is_locked(&self) -> bool {
+        self.pool_flags & POOL_FLAG_LOCKED != 0
+    }
+
+    /// set before the first CPI of an instruction that moves the vault balances
+    pub fn lock(&mut self) {
+        self.pool_flags |= POOL_FLAG_LOCKED;
+    }
+
+    /// cleared once the instruction is done, a failed instruction reverts the lock
+    pub fn unlock(&mut self) {
+        self.pool_flags &= !POOL_FLAG_LOCKED;
+    }
+
+    /// see `POOL_FLAG_REGISTRY_HOOKS`
+    pub fn accepts_registry_hooks(&self) -> bool {
+        self.pool_flags & POOL_FLAG_REGISTRY_HOOKS != 0
+    }
+
+    /// pool without an OpenBook market, see `POOL_FLAG_NO_ORDERBOOK`
+    pub fn is_orderbook_free(&self) -> bool {
+        self.pool_flags & POOL_FLAG_NO_ORDERBOOK != 0
+    }
+
+    /// see `POOL_FLAG_PRICE_ORACLE`
+    pub fn has_price_oracle(&self) -> bool {
+        self.pool_flags & POOL_FLAG_PRICE_ORACLE != 0
+    }
+
+    /// split of the swap fee, `None` if the LPs keep all of it. The split is kept in the
+    /// `AmmExtension` of the pool, which a pool with a split has.
+    pub fn fee_split(&self, extension: Option<&AmmExtension>) -> Option<FeeSplit> {
+        if self.pool_flags & POOL_FLAG_FEE_SPLIT == 0 {
+            return None;
+        }
+        extension.map(|extension| extension.fee_split)
+    }
+
+    pub fn set_fee_split(&mut self, extension: &mut AmmExtension, split: FeeSplit) {
+        extension.fee_split = split;
+        self.pool_flags |= POOL_FLAG_FEE_SPLIT;
+    }
+
+    /// see `POOL_FLAG_STABLE_SWAP`
+    pub fn curve_type(&self) -> CurveType {
+        if self.pool_flags & POOL_FLAG_STABLE_SWAP != 0 {
+            CurveType::StableSwap
+        } else {
+            CurveType::ConstantProduct
+        }
+    }
+
+    /// amplification ramp of a StableSwap pool, `None` for the constant product curve.
+    /// The ramp is kept in the `AmmExtension` of the pool, which a StableSwap pool has.
+    pub fn amp_ramp(&self, extension: Option<&AmmExtension>) -> Result<Option<AmpRamp>, AmmError> {
+        if self.curve_type() != CurveType::StableSwap {
+            return Ok(None);
+        }
+        // only `InitializeNoOrderbook` creates a StableSwap pool, the orders of a pool with an
+        // orderbook quote the constant product curve
+        if !self.is_orderbook_free() {
+            return Err(AmmError::UnsupportedCurve);
+        }
+        let extension = extension.ok_or(AmmError::ExpectedAccount)?;
+        Ok(Some(extension.amp_ramp))
+    }
+
+    /// switches a pool without orderbook to the StableSwap curve or updates its ramp
+    pub fn set_amp_ramp(&mut self, extension: &mut AmmExtension, ramp: AmpRamp) {
+        extension.amp_ramp = ramp;
+        self.pool_flags |= POOL_FLAG_STABLE_SWAP;
+    }
+
+    /// bonding curve of the pool at unix timestamp `now`
+    pub fn curve(&self, extension: Option<&AmmExtension>, now: u64) -> Result<Curve, AmmError> {
+        let ramp = match self.amp_ramp(extension)? {
+            Some(ramp) => ramp,
+            None => return Ok(Curve::ConstantProduct),
+        };
+        // amounts of both sides are compared at the larger of the two decimals
+        let decimals = self.coin_decimals.max(self.pc_decimals);
+        let multiplier = |side_decimals: u64| {
+            10u64
+                .checked_pow((decimals - side_decimals) as u32)
+                .ok_or(AmmError::InvalidAmp)
+        };
+        Ok(Curve::StableSwap(StableSwap {
+            amp: ramp.amp(now),
+            coin_multiplier: multiplier(self.coin_decimals)?,
+            pc_multiplier: multiplier(self.pc_decimals)?,
+        }))
+    }
+
+    /// transfer hook programs of the coin and pc mints pinned at pool creation,
+    /// `Pubkey::default()` for a mint without hook, `None` if the pool has no pins
+    pub fn pinned_hook_programs(&self) -> Option<(Pubkey, Pubkey)> {
+        if self.pool_flags & POOL_FLAG_HOOKS_PINNED == 0 {
+            return None;
+        }
+        let padding1 = self.padding1;
+        let coin_hook_program: [u8; 32] =
+            bytemuck::cast([padding1[0], padding1[1], padding1[2], padding1[3]]);
+        let pc_hook_program: [u8; 32] =
+            bytemuck::cast([padding1[4], padding1[5], padding1[6], padding1[7]]);
+        Some((
+            Pubkey::new_from_array(coin_hook_program),
+            Pubkey::new_from_array(pc_hook_program),
+        ))
+    }
+
+    pub fn pin_hook_programs(
+        &mut self,
+        coin_hook_program: Option<Pubkey>,
+        pc_hook_program: Option<Pubkey>,
+    ) {
+        let coin_hook_program: [u64; 4] =
+            bytemuck::cast(coin_hook_program.unwrap_or_default().to_bytes());
+        let pc_hook_program: [u64; 4] =
+            bytemuck::cast(pc_hook_program.unwrap_or_default().to_bytes());
+        let mut padding1 = [0u64; 8];
+        padding1[..4].copy_from_slice(&coin_hook_program);
+        padding1[4..].copy_from_slice(&pc_hook_program);
+        self.padding1 = padding1;
+        self.pool_flags |= POOL_FLAG_HOOKS_PINNED;
+    }
+
+    /// token program of the coin vault and coin mint
+    pub fn coin_token_program(&self) -> Pubkey {
+        if self.pool_flags & POOL_FLAG_COIN_TOKEN_2022 != 0 {
+            spl_token_2022::id()
+        } else {
+            spl_token::id()
+        }
+    }
+
+    /// token program of the pc vault and pc mint
+    pub fn pc_token_program(&self) -> Pubkey {
+        if self.pool_flags & POOL_FLAG_PC_TOKEN_2022 != 0 {
+            spl_token_2022::id()
+        } else {
+            spl_token::id()
+        }
+    }
+
+    /// either vault is a Token-2022 account, the pool then takes the trailing accounts of
+    /// `Withdraw` and `WithdrawPnl` at fixed positions
+    pub fn has_token_2022_side(&self) -> bool {
+        self.pool_flags & (POOL_FLAG_COIN_TOKEN_2022 | POOL_FLAG_PC_TOKEN_2022) != 0
+    }
+
+    /// the lp mint is created under the pc side token program
+    pub fn lp_token_program(&self) -> Pubkey {
+        self.pc_token_program()
+    }
+
+    pub fn set_token_programs(&mut self, coin_token_program: &Pubkey, pc_token_program: &Pubkey) {
+        let mut pool_flags =
+            self.pool_flags & !(POOL_FLAG_COIN_TOKEN_2022 | POOL_FLAG_PC_TOKEN_2022);
+        if *coin_token_program == spl_token_2022::id() {
+            pool_flags |= POOL_FLAG_COIN_TOKEN_2022;
+        }
+        if *pc_token_program == spl_token_2022::id() {
+            pool_flags |= POOL_FLAG_PC_TOKEN_2022;
+        }
+        self.pool_flags = pool_flags;
+    }
+
     pub fn incr_client_order_id(&mut self) -> u64 {
         self.client_order_id = self.client_order_id.wrapping_add(1);
         if self.client_order_id == 0 {
@@ -862,8 +1201,14 @@ pub struct AmmConfig {
     pub pnl_owner: Pubkey,
     /// admin amm order owner
     pub cancel_owner: Pubkey,
+    /// lamports a proposer bonds to register a transfer hook, 0 disables proposals
+    pub hook_bond: u64,
+    /// seconds the reviewers may veto a proposed transfer hook
+    pub hook_challenge_period: u64,
+    /// reviewers allowed to veto proposed transfer hooks, default for an empty slot
+    pub hook_reviewers: [Pubkey; 4],
     /// pending
-    pub pending_1: [u64; 28],
+    pub pending_1: [u64; 10],
     /// pending
     pub pending_2: [u64; 31],
     /// init amm pool fee amount
@@ -904,6 +1249,607 @@ impl AmmConfig {
         let data = Self::load(account)?;
         Ok(data)
     }
+
+    pub fn is_hook_reviewer(&self, reviewer: &Pubkey) -> bool {
+        let hook_reviewers = self.hook_reviewers;
+        *reviewer != Pubkey::default() && hook_reviewers.contains(reviewer)
+    }
+}
+
+/// Whitelisted transfer hook program, one account per hook program derived from
+/// `find_program_address(&[HOOK_CONFIG_SEED, hook_program])`
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HookConfig {
+    /// transfer hook program key
+    pub hook_program: Pubkey,
+    /// max compute units the hook may consume per transfer
+    pub max_compute_units: u64,
+    /// 1 if the hook is safe to be called re-entrantly
+    pub is_reentrant_safe: u64,
+    /// hash of the extra accounts the hook expects, all zero if not pinned
+    pub expected_accounts_hash: [u8; 32],
+    /// bump seed of the account address
+    pub bump: u64,
+    /// upgrade authority the hook program may keep, default if it must be immutable
+    pub upgrade_authority: Pubkey,
+    /// ProgramData account of a hook program of the upgradeable loader, default for
+    /// a program of a non-upgradeable loader
+    pub program_data: Pubkey,
+    /// slot the hook program was last deployed at, a redeploy invalidates the approval
+    pub program_data_slot: u64,
+    /// 1 if the hook was activated through the bonded registry instead of the admin,
+    /// only pools with `POOL_FLAG_REGISTRY_HOOKS` accept it
+    pub registry_approved: u64,
+    /// padding
+    pub padding: [u64; 6],
+}
+impl_loadable!(HookConfig);
+
+impl HookConfig {
+    /// load_mut_checked
+    #[inline]
+    pub fn load_mut_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<RefMut<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Self::load_mut(account)?;
+        Ok(data)
+    }
+
+    /// load_checked, also rejects a removed hook config
+    #[inline]
+    pub fn load_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Ref<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Self::load(account)?;
+        if data.hook_program == Pubkey::default() {
+            return Err(AmmError::UnauthorizedTransferHook.into());
+        }
+        Ok(data)
+    }
+
+    pub fn is_reentrant_safe(&self) -> bool {
+        self.is_reentrant_safe != 0
+    }
+
+    pub fn is_registry_approved(&self) -> bool {
+        self.registry_approved != 0
+    }
+
+    pub fn expected_accounts_hash(&self) -> Option<[u8; 32]> {
+        if self.expected_accounts_hash == [0u8; 32] {
+            None
+        } else {
+            Some(self.expected_accounts_hash)
+        }
+    }
+}
+
+/// Transfer hook proposed through the bonded registry, one account per hook program
+/// derived from `find_program_address(&[HOOK_REGISTRATION_SEED, hook_program])`.
+/// The account holds the bond until the hook is vetoed or activated.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HookRegistration {
+    /// account that posted the bond, gets it back on activation
+    pub proposer: Pubkey,
+    /// transfer hook program key
+    pub hook_program: Pubkey,
+    /// lamports bonded by the proposer
+    pub bond: u64,
+    /// unix timestamp the challenge period ends at
+    pub challenge_end: u64,
+    /// max compute units the hook may consume per transfer
+    pub max_compute_units: u64,
+    /// hash of the extra accounts the hook expects, all zero if not pinned
+    pub expected_accounts_hash: [u8; 32],
+    /// upgrade authority the hook program may keep, default if it must be immutable
+    pub upgrade_authority: Pubkey,
+    /// ProgramData account of the hook program, default for a non-upgradeable loader
+    pub program_data: Pubkey,
+    /// slot the hook program was deployed at when proposed
+    pub program_data_slot: u64,
+    /// bump seed of the account address
+    pub bump: u64,
+    /// padding
+    pub padding: [u64; 5],
+}
+impl_loadable!(HookRegistration);
+
+impl HookRegistration {
+    /// load_mut_checked
+    #[inline]
+    pub fn load_mut_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<RefMut<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Self::load_mut(account)?;
+        Ok(data)
+    }
+
+    /// load_checked
+    #[inline]
+    pub fn load_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Ref<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Self::load(account)?;
+        Ok(data)
+    }
+
+    /// hook config the registration activates into. A registry hook is never handed this
+    /// program to call back into, only the admin marks a hook re-entrant safe with
+    /// `AddTransferHook`, which takes the hook over.
+    pub fn hook_config(&self) -> HookConfig {
+        HookConfig {
+            hook_program: self.hook_program,
+            max_compute_units: self.max_compute_units,
+            is_reentrant_safe: 0,
+            expected_accounts_hash: self.expected_accounts_hash,
+            bump: 0,
+            upgrade_authority: self.upgrade_authority,
+            program_data: self.program_data,
+            program_data_slot: self.program_data_slot,
+            registry_approved: 1,
+            padding: [0u64; 6],
+        }
+    }
+}
+
+/// Withdrawal outputs escrowed for an owner whose transfer hook refuses delivery, one account
+/// per pool and owner derived from `find_program_address(&[ESCROW_CLAIM_SEED, amm, owner])`.
+/// The tokens sit in the escrow token account of each side until `ClaimEscrowed` delivers them.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EscrowClaim {
+    /// pool the amounts were withdrawn from
+    pub amm: Pubkey,
+    /// owner the amounts are delivered to
+    pub owner: Pubkey,
+    /// coin amount held for the owner in the coin escrow token account
+    pub coin_amount: u64,
+    /// pc amount held for the owner in the pc escrow token account
+    pub pc_amount: u64,
+    /// bump seed of the account address
+    pub bump: u64,
+    /// padding
+    pub padding: [u64; 4],
+}
+impl_loadable!(EscrowClaim);
+
+impl EscrowClaim {
+    /// load_mut_checked
+    #[inline]
+    pub fn load_mut_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<RefMut<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Self::load_mut(account)?;
+        Ok(data)
+    }
+
+    /// load_checked
+    #[inline]
+    pub fn load_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Ref<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Self::load(account)?;
+        Ok(data)
+    }
+
+    /// nothing is left to claim
+    pub fn is_empty(&self) -> bool {
+        self.coin_amount == 0 && self.pc_amount == 0
+    }
+}
+
+/// Swap a hooked mint is transferred for, written by the AMM before the hooked transfers of
+/// a swap and cleared once they are done. One account per mint derived from
+/// `find_program_address(&[TRADE_CONTEXT_SEED, mint])`, created by `CreateTradeContext`.
+/// A hook lists it in its ExtraAccountMetaList to read it, see `TradeContext::load_checked`.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TradeContext {
+    /// mint the context is written for
+    pub mint: Pubkey,
+    /// pool of the swap
+    pub amm: Pubkey,
+    /// 1 while the swap transfers run, 0 otherwise
+    pub active: u64,
+    /// `SwapDirection` of the swap, 1 pc to coin, 2 coin to pc
+    pub direction: u64,
+    /// amount the pool receives, net of the inbound transfer fee
+    pub amount_in: u64,
+    /// amount the pool sends, before the outbound transfer fee
+    pub amount_out: u64,
+    /// coin reserve of the pool before the swap
+    pub coin_reserve: u64,
+    /// pc reserve of the pool before the swap
+    pub pc_reserve: u64,
+    /// execution price of `mint` in the other token of the pool, as a Q64.64
+    pub price_x64: u128,
+    /// bump seed of the account address
+    pub bump: u64,
+    /// padding
+    pub padding: [u64; 4],
+}
+impl_loadable!(TradeContext);
+
+impl TradeContext {
+    /// load_mut_checked
+    #[inline]
+    pub fn load_mut_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<RefMut<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Self::load_mut(account)?;
+        Ok(data)
+    }
+
+    /// load_checked, also rejects an account that is not the trade context of `mint`
+    #[inline]
+    pub fn load_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+        mint: &Pubkey,
+    ) -> Result<Ref<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let context = Self::load(account)?;
+        if context.mint != *mint {
+            return Err(AmmError::InvalidTradeContext.into());
+        }
+        // the stored bump pins the account to its seeds
+        let address = Pubkey::create_program_address(
+            &[TRADE_CONTEXT_SEED, mint.as_ref(), &[context.bump as u8]],
+            program_id,
+        )
+        .map_err(|_| AmmError::InvalidTradeContext)?;
+        if address != *account.key {
+            return Err(AmmError::InvalidTradeContext.into());
+        }
+        Ok(context)
+    }
+
+    /// a swap of the mint is running
+    pub fn is_active(&self) -> bool {
+        self.active != 0
+    }
+}
+
+/// most observations a `PriceOracle` keeps, its account stays within the size a program can
+/// allocate in one instruction
+pub const MAX_PRICE_OBSERVATIONS: u16 = 250;
+
+/// Time-weighted average price accumulator of a pool, kept in its `AmmExtension`. Every swap
+/// and deposit of the pool accumulates the spot price the pool held since the previous one,
+/// so a price moved within a transaction weighs nothing until it stays.
+/// Prices are Q64.64, `coin_price_x64` is the pc amount of one coin unit.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PriceAccumulator {
+    /// unix timestamp of the last update, 0 before the first one
+    pub last_update_ts: u64,
+    /// coin price in pc after the last update
+    pub coin_price_x64: u128,
+    /// pc price in coin after the last update
+    pub pc_price_x64: u128,
+    /// sum of `coin_price_x64` times the seconds it held, wrapping
+    pub coin_price_cumulative: u128,
+    /// sum of `pc_price_x64` times the seconds it held, wrapping
+    pub pc_price_cumulative: u128,
+}
+unsafe impl Zeroable for PriceAccumulator {}
+unsafe impl Pod for PriceAccumulator {}
+
+impl PriceAccumulator {
+    /// Accumulate the prices held since the last update up to `now` and keep the spot prices
+    /// after the swap or deposit
+    pub fn update(&mut self, now: u64, coin_price_x64: u128, pc_price_x64: u128) {
+        let now = now.max(self.last_update_ts);
+        if self.last_update_ts != 0 {
+            (self.coin_price_cumulative, self.pc_price_cumulative) = self.cumulative_at(now);
+        }
+        self.last_update_ts = now;
+        self.coin_price_x64 = coin_price_x64;
+        self.pc_price_x64 = pc_price_x64;
+    }
+
+    /// cumulative coin and pc prices at `timestamp`, extrapolated from the last update
+    pub fn cumulative_at(&self, timestamp: u64) -> (u128, u128) {
+        let elapsed = timestamp.saturating_sub(self.last_update_ts) as u128;
+        (
+            self.coin_price_cumulative
+                .wrapping_add(self.coin_price_x64.wrapping_mul(elapsed)),
+            self.pc_price_cumulative
+                .wrapping_add(self.pc_price_x64.wrapping_mul(elapsed)),
+        )
+    }
+
+    /// the cumulative prices at the last update
+    fn observation(&self) -> Observation {
+        Observation {
+            timestamp: self.last_update_ts,
+            coin_price_cumulative: self.coin_price_cumulative,
+            pc_price_cumulative: self.pc_price_cumulative,
+        }
+    }
+}
+
+/// Ring buffer of `observation_len` `Observation`s of the `PriceAccumulator` of a pool, one
+/// account per pool derived from `find_program_address(&[PRICE_ORACLE_SEED, amm])`, created
+/// by `CreatePriceOracle`. Every swap and deposit of the pool records the accumulator in it.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PriceOracle {
+    /// pool the prices are of
+    pub amm: Pubkey,
+    /// observations the ring buffer holds
+    pub observation_len: u64,
+    /// observations written, at most `observation_len`
+    pub observation_count: u64,
+    /// index of the latest observation
+    pub observation_index: u64,
+    /// bump seed of the account address
+    pub bump: u64,
+    /// padding
+    pub padding: [u64; 4],
+}
+impl_loadable!(PriceOracle);
+
+/// Cumulative prices of a `PriceAccumulator` at the first update of a second
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Observation {
+    /// unix timestamp of the update
+    pub timestamp: u64,
+    /// `PriceAccumulator::coin_price_cumulative` at `timestamp`
+    pub coin_price_cumulative: u128,
+    /// `PriceAccumulator::pc_price_cumulative` at `timestamp`
+    pub pc_price_cumulative: u128,
+}
+impl_loadable!(Observation);
+
+impl Observation {
+    /// cumulative prices at `timestamp` between `self` and the next observation `next`,
+    /// the price held in between is constant
+    fn interpolate(&self, next: &Observation, timestamp: u64) -> (u128, u128) {
+        let (start, end) = (self.timestamp, next.timestamp);
+        let interpolate = |from: u128, to: u128| {
+            let delta = U256::from(to.wrapping_sub(from)) * (timestamp - start) / (end - start);
+            from.wrapping_add(delta.as_u128())
+        };
+        (
+            interpolate(self.coin_price_cumulative, next.coin_price_cumulative),
+            interpolate(self.pc_price_cumulative, next.pc_price_cumulative),
+        )
+    }
+}
+
+impl PriceOracle {
+    /// account size of an oracle keeping `observation_len` observations
+    pub fn space(observation_len: u16) -> usize {
+        size_of::<Self>() + observation_len as usize * size_of::<Observation>()
+    }
+
+    /// Decode the data of an oracle account into its header and its observations, in ring
+    /// buffer order. For clients, the program reads the account with `load_checked`.
+    pub fn unpack(data: &[u8]) -> Result<(&Self, &[Observation]), ProgramError> {
+        if data.len() < size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let (header, observations) = data.split_at(size_of::<Self>());
+        let oracle: &Self = from_bytes(header);
+        if observations.len() != oracle.observation_len as usize * size_of::<Observation>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        Ok((oracle, cast_slice(observations)))
+    }
+
+    /// Write the header of a newly allocated oracle account of `amm`
+    pub fn initialize(
+        account: &AccountInfo,
+        program_id: &Pubkey,
+        amm: &Pubkey,
+        observation_len: u16,
+        bump: u8,
+    ) -> Result<(), ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != Self::space(observation_len) {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let mut data = account.try_borrow_mut_data()?;
+        *from_bytes_mut::<Self>(&mut data[..size_of::<Self>()]) = PriceOracle {
+            amm: *amm,
+            observation_len: observation_len as u64,
+            bump: bump as u64,
+            ..Default::default()
+        };
+        Ok(())
+    }
+
+    /// load_mut_checked
+    #[inline]
+    pub fn load_mut_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<(RefMut<'a, Self>, RefMut<'a, [Observation]>), ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        Self::unpack(&account.try_borrow_data()?)?;
+        Ok(RefMut::map_split(account.try_borrow_mut_data()?, |data| {
+            let (header, observations) = data.split_at_mut(size_of::<Self>());
+            (from_bytes_mut(header), cast_slice_mut(observations))
+        }))
+    }
+
+    /// load_checked, also rejects an account that is not the oracle of `amm`. Other programs
+    /// read the prices of a pool with it, the `PriceAccumulator` of the pool from
+    /// `AmmInfo::load_checked_extended` and `observe` or `twap`.
+    #[inline]
+    pub fn load_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+        amm: &Pubkey,
+    ) -> Result<(Ref<'a, Self>, Ref<'a, [Observation]>), ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        let bump = {
+            let data = account.try_borrow_data()?;
+            let (oracle, _) = Self::unpack(&data)?;
+            if oracle.amm != *amm {
+                return Err(AmmError::InvalidPriceOracle.into());
+            }
+            oracle.bump
+        };
+        // the stored bump pins the account to its seeds
+        let address = Pubkey::create_program_address(
+            &[PRICE_ORACLE_SEED, amm.as_ref(), &[bump as u8]],
+            program_id,
+        )
+        .map_err(|_| AmmError::InvalidPriceOracle)?;
+        if address != *account.key {
+            return Err(AmmError::InvalidPriceOracle.into());
+        }
+        Ok(Ref::map_split(account.try_borrow_data()?, |data| {
+            let (header, observations) = data.split_at(size_of::<Self>());
+            (from_bytes(header), cast_slice(observations))
+        }))
+    }
+
+    /// Write an observation of the accumulator of the pool if it was updated after the
+    /// latest observation
+    pub fn record(&mut self, observations: &mut [Observation], price: &PriceAccumulator) {
+        if self.observation_count != 0
+            && observations[self.observation_index as usize].timestamp >= price.last_update_ts
+        {
+            return;
+        }
+        let index = if self.observation_count == 0 {
+            0
+        } else {
+            (self.observation_index + 1) % self.observation_len
+        };
+        observations[index as usize] = price.observation();
+        self.observation_index = index;
+        self.observation_count = std::cmp::min(self.observation_count + 1, self.observation_len);
+    }
+
+    /// Cumulative coin and pc prices `seconds_ago` before `now`, extrapolated from the last
+    /// update of the accumulator `price` of the pool or interpolated between the observations
+    /// around it. On chain `now` is the clock's `unix_timestamp`.
+    pub fn observe(
+        &self,
+        observations: &[Observation],
+        price: &PriceAccumulator,
+        now: u64,
+        seconds_ago: u64,
+    ) -> Result<(u128, u128), AmmError> {
+        if price.last_update_ts == 0 {
+            return Err(AmmError::ObservationUnavailable);
+        }
+        let target = now
+            .checked_sub(seconds_ago)
+            .ok_or(AmmError::ObservationUnavailable)?;
+        if target >= price.last_update_ts {
+            return Ok(price.cumulative_at(target));
+        }
+        // from the oldest observation to the last update of the accumulator
+        let (len, count) = (
+            self.observation_len as usize,
+            self.observation_count as usize,
+        );
+        let oldest = if count == len {
+            (self.observation_index as usize + 1) % len
+        } else {
+            0
+        };
+        let mut previous: Option<Observation> = None;
+        for observation in (0..count)
+            .map(|index| observations[(oldest + index) % len])
+            .chain(std::iter::once(price.observation()))
+        {
+            if observation.timestamp > target {
+                return previous
+                    .map(|previous| previous.interpolate(&observation, target))
+                    .ok_or(AmmError::ObservationUnavailable);
+            }
+            previous = Some(observation);
+        }
+        Err(AmmError::ObservationUnavailable)
+    }
+
+    /// Time-weighted average coin and pc prices over the `seconds` before `now`
+    pub fn twap(
+        &self,
+        observations: &[Observation],
+        price: &PriceAccumulator,
+        now: u64,
+        seconds: u64,
+    ) -> Result<(u128, u128), AmmError> {
+        if seconds == 0 {
+            return Err(AmmError::InvalidInput);
+        }
+        let (coin_end, pc_end) = self.observe(observations, price, now, 0)?;
+        let (coin_start, pc_start) = self.observe(observations, price, now, seconds)?;
+        Ok((
+            coin_end.wrapping_sub(coin_start) / seconds as u128,
+            pc_end.wrapping_sub(pc_start) / seconds as u128,
+        ))
+    }
 }
 
 #[repr(C)]
@@ -913,6 +1859,129 @@ pub struct LastOrderDistance {
     pub last_order_denominator: u64,
 }
 
+/// smallest amplification coefficient of a StableSwap pool
+pub const MIN_AMP: u64 = 1;
+/// largest amplification coefficient of a StableSwap pool
+pub const MAX_AMP: u64 = 1_000_000;
+/// a ramp at most multiplies or divides the current amplification coefficient by this
+pub const MAX_AMP_CHANGE: u64 = 10;
+/// shortest ramp in seconds, so that liquidity providers can react to it
+pub const MIN_RAMP_DURATION: u64 = 86400;
+
+/// Amplification coefficient of a StableSwap pool moving linearly from `initial_amp`
+/// to `target_amp` between two unix timestamps
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AmpRamp {
+    pub initial_amp: u64,
+    pub target_amp: u64,
+    pub start_ramp_ts: u64,
+    pub stop_ramp_ts: u64,
+}
+unsafe impl Zeroable for AmpRamp {}
+unsafe impl Pod for AmpRamp {}
+
+impl AmpRamp {
+    /// a fixed amplification coefficient
+    pub fn fixed(amp: u64) -> Self {
+        AmpRamp {
+            initial_amp: amp,
+            target_amp: amp,
+            start_ramp_ts: 0,
+            stop_ramp_ts: 0,
+        }
+    }
+
+    /// amplification coefficient at unix timestamp `now`
+    pub fn amp(&self, now: u64) -> u64 {
+        if now >= self.stop_ramp_ts || self.stop_ramp_ts <= self.start_ramp_ts {
+            return self.target_amp;
+        }
+        if now <= self.start_ramp_ts {
+            return self.initial_amp;
+        }
+        let elapsed = (now - self.start_ramp_ts) as u128;
+        let duration = (self.stop_ramp_ts - self.start_ramp_ts) as u128;
+        if self.target_amp > self.initial_amp {
+            let change = (self.target_amp - self.initial_amp) as u128 * elapsed / duration;
+            self.initial_amp + change as u64
+        } else {
+            let change = (self.initial_amp - self.target_amp) as u128 * elapsed / duration;
+            self.initial_amp - change as u64
+        }
+    }
+
+    /// ramp from the amplification coefficient at `now` to `ramp.target_amp`, reached at
+    /// `ramp.stop_ramp_ts`. A stop timestamp not in the future stops the ramp at the
+    /// current amplification coefficient.
+    pub fn ramp(&self, now: u64, ramp: RampAmp) -> Result<AmpRamp, AmmError> {
+        let current_amp = self.amp(now);
+        if ramp.stop_ramp_ts <= now {
+            return Ok(AmpRamp::fixed(current_amp));
+        }
+        if ramp.target_amp < MIN_AMP
+            || ramp.target_amp > MAX_AMP
+            || ramp.stop_ramp_ts < now.saturating_add(MIN_RAMP_DURATION)
+            || ramp.target_amp.saturating_mul(MAX_AMP_CHANGE) < current_amp
+            || ramp.target_amp > current_amp.saturating_mul(MAX_AMP_CHANGE)
+        {
+            return Err(AmmError::InvalidAmp);
+        }
+        Ok(AmpRamp {
+            initial_amp: current_amp,
+            target_amp: ramp.target_amp,
+            start_ramp_ts: now,
+            stop_ramp_ts: ramp.stop_ramp_ts,
+        })
+    }
+}
+
+/// `SetParams` of `AmmParams::RampAmp`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RampAmp {
+    pub target_amp: u64,
+    pub stop_ramp_ts: u64,
+}
+
+/// Split of the swap fee, in parts of `TEN_THOUSAND` of the fee, the LPs keep the rest.
+/// `SetParams` of `AmmParams::FeeSplit`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeSplit {
+    /// accrues to `need_take_pnl_coin` or `need_take_pnl_pc`, withdrawn by `WithdrawPnl`
+    pub protocol_share: u32,
+    /// paid to the referrer token account of a swap, kept by the LPs without one
+    pub referrer_share: u32,
+}
+unsafe impl Zeroable for FeeSplit {}
+unsafe impl Pod for FeeSplit {}
+
+impl FeeSplit {
+    pub fn validate(&self) -> Result<(), AmmError> {
+        if self.protocol_share as u64 + self.referrer_share as u64 > TEN_THOUSAND {
+            return Err(AmmError::InvalidFee);
+        }
+        Ok(())
+    }
+
+    /// LP, protocol and referrer shares of `swap_fee`, rounded in favour of the LPs
+    pub fn shares(&self, swap_fee: u64, referrer: bool) -> (u64, u64, u64) {
+        let share = |part: u32| (swap_fee as u128 * part as u128 / TEN_THOUSAND as u128) as u64;
+        let protocol_fee = share(self.protocol_share);
+        let referrer_fee = if referrer {
+            share(self.referrer_share)
+        } else {
+            0
+        };
+        (
+            swap_fee - protocol_fee - referrer_fee,
+            protocol_fee,
+            referrer_fee,
+        )
+    }
+}
+
 /// For simulateTransaction to get instruction data
 #[cfg_attr(feature = "client", derive(Debug))]
 #[derive(Copy, Clone)]
@@ -922,6 +1991,8 @@ pub enum SimulateParams {
     SwapBaseInInfo = 1u64,
     SwapBaseOutInfo = 2u64,
     RunCrankInfo = 3u64,
+    /// performs the hooked transfers of a swap and reverts, see `HookDryRunData`
+    HookDryRunInfo = 4u64,
 }
 impl SimulateParams {
     pub fn from_u64(flag: u64) -> Self {
@@ -930,6 +2001,7 @@ impl SimulateParams {
             1u64 => SimulateParams::SwapBaseInInfo,
             2u64 => SimulateParams::SwapBaseOutInfo,
             3u64 => SimulateParams::RunCrankInfo,
+            4u64 => SimulateParams::HookDryRunInfo,
             _ => unreachable!(),
         }
     }
@@ -940,6 +2012,7 @@ impl SimulateParams {
             SimulateParams::SwapBaseInInfo => 1u64,
             SimulateParams::SwapBaseOutInfo => 2u64,
             SimulateParams::RunCrankInfo => 3u64,
+            SimulateParams::HookDryRunInfo => 4u64,
         }
     }
 }
@@ -1015,6 +2088,52 @@ impl GetSwapBaseOutData {
     }
 }
 
+/// One transfer of a hook dry run, logged as `HookDryRunSide` before the transfer.
+/// A failed transfer aborts the whole simulation, so a side has no status of its own: a hook
+/// that rejects the transfer shows up as the error of the simulation, and the last side logged
+/// is the one rejected.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HookDryRunSide {
+    pub mint: String,
+    /// transfer hook program of the mint, empty without a hook
+    pub hook_program: String,
+    /// compute units the transfer consumed, the hook included
+    pub compute_units: u64,
+    /// the transfer stayed within the compute units registered for the hook
+    pub within_budget: bool,
+    /// accounts Token-2022 forwards to the hook: the resolved extra accounts,
+    /// the hook program and the ExtraAccountMetaList Account
+    pub extra_accounts: Vec<String>,
+}
+impl HookDryRunSide {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+    pub fn from_json(data: &str) -> Self {
+        serde_json::from_str(data).unwrap()
+    }
+}
+
+/// Logged as `HookDryRunData` once both transfers of a hook dry run succeeded
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HookDryRunData {
+    pub amm_id: String,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    /// user -> vault transfer
+    pub source: HookDryRunSide,
+    /// vault -> user transfer
+    pub destination: HookDryRunSide,
+}
+impl HookDryRunData {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+    pub fn from_json(data: &str) -> Self {
+        serde_json::from_str(data).unwrap()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1052,7 +2171,8 @@ mod test {
         let total_pnl_pc: u64 = 0x12f3456789abcde0;
         let total_pnl_coin: u64 = 0x1f23456789abcde0;
         let pool_open_time: u64 = 0x123456789abcedf0;
-        let padding: [u64; 2] = [0x123456789abecdf0, 0x123456789aebcdf0];
+        let coin_mint_extensions: u64 = 0x123456789abecdf0;
+        let pc_mint_extensions: u64 = 0x123456789aebcdf0;
         let orderbook_to_init_time: u64 = 0x123456789eabcdf0;
         let swap_coin_in_amount: u128 = 0x11002233445566778899aabbccddeeff;
         let swap_pc_out_amount: u128 = 0x11220033445566778899aabbccddeeff;
@@ -1083,7 +2203,7 @@ mod test {
         let lp_amount: u64 = 0x123456e789abcdf0;
         let client_order_id: u64 = 0x12345e6789abcdf0;
         let recent_epoch: u64 = 0x1234e56789abcdf0;
-        let padding2: u64 = 0x123e456789abcdf0;
+        let pool_flags: u64 = 0x123e456789abcdf0;
 
         // serialize original data
         let mut pool_data = [0u8; 752];
@@ -1148,9 +2268,9 @@ mod test {
         offset += 8;
         pool_data[offset..offset + 8].copy_from_slice(&pool_open_time.to_le_bytes());
         offset += 8;
-        pool_data[offset..offset + 8].copy_from_slice(&padding[0].to_le_bytes());
+        pool_data[offset..offset + 8].copy_from_slice(&coin_mint_extensions.to_le_bytes());
         offset += 8;
-        pool_data[offset..offset + 8].copy_from_slice(&padding[1].to_le_bytes());
+        pool_data[offset..offset + 8].copy_from_slice(&pc_mint_extensions.to_le_bytes());
         offset += 8;
         pool_data[offset..offset + 8].copy_from_slice(&orderbook_to_init_time.to_le_bytes());
         offset += 8;
@@ -1195,7 +2315,7 @@ mod test {
         offset += 8;
         pool_data[offset..offset + 8].copy_from_slice(&recent_epoch.to_le_bytes());
         offset += 8;
-        pool_data[offset..offset + 8].copy_from_slice(&padding2.to_le_bytes());
+        pool_data[offset..offset + 8].copy_from_slice(&pool_flags.to_le_bytes());
         offset += 8;
 
         // len check
@@ -1264,10 +2384,10 @@ mod test {
         assert_eq!(total_pnl_coin, unpack_total_pnl_coin);
         let unpack_pool_open_time = unpack_data.state_data.pool_open_time;
         assert_eq!(pool_open_time, unpack_pool_open_time);
-        for i in 0..2 {
-            let unpack_padding = unpack_data.state_data.padding[i];
-            assert_eq!(padding[i], unpack_padding);
-        }
+        let unpack_coin_mint_extensions = unpack_data.state_data.coin_mint_extensions;
+        assert_eq!(coin_mint_extensions, unpack_coin_mint_extensions);
+        let unpack_pc_mint_extensions = unpack_data.state_data.pc_mint_extensions;
+        assert_eq!(pc_mint_extensions, unpack_pc_mint_extensions);
         let unpack_orderbook_to_init_time = unpack_data.state_data.orderbook_to_init_time;
         assert_eq!(orderbook_to_init_time, unpack_orderbook_to_init_time);
         let unpack_swap_coin_in_amount = unpack_data.state_data.swap_coin_in_amount;
@@ -1312,8 +2432,161 @@ mod test {
         assert_eq!(client_order_id, unpack_client_order_id);
         let unpack_recent_epoch = unpack_data.recent_epoch;
         assert_eq!(recent_epoch, unpack_recent_epoch);
-        let unpack_padding2 = unpack_data.padding2;
-        assert_eq!(padding2, unpack_padding2);
+        let unpack_pool_flags = unpack_data.pool_flags;
+        assert_eq!(pool_flags, unpack_pool_flags);
+    }
+
+    #[test]
+    fn test_amm_extension_layout() {
+        assert_eq!(size_of::<AmmInfo>(), 752);
+        assert_eq!(size_of::<AmmExtension>(), 4 * 8 + 24 * 8);
+        assert_eq!(AMM_EXTENDED_LEN, 752 + 224);
+
+        let initial_amp: u64 = 0x123456789abcdef0;
+        let target_amp: u64 = 0x123456789abcde0f;
+        let start_ramp_ts: u64 = 0x123456789abcd0ef;
+        let stop_ramp_ts: u64 = 0x123456789abc0def;
+        let mut data = [0u8; AMM_EXTENDED_LEN];
+        let mut offset = size_of::<AmmInfo>();
+        for value in [initial_amp, target_amp, start_ramp_ts, stop_ramp_ts] {
+            data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+            offset += 8;
+        }
+
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut lamports = 0;
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        {
+            let (_, extension) = AmmInfo::load_mut_extended(&account).unwrap();
+            let unpack_amp_ramp = extension.unwrap().amp_ramp;
+            assert_eq!(
+                unpack_amp_ramp,
+                AmpRamp {
+                    initial_amp,
+                    target_amp,
+                    start_ramp_ts,
+                    stop_ramp_ts,
+                }
+            );
+        }
+        assert!(AmmInfo::check_account(&account, &owner).unwrap());
+
+        // an AMM Account created before the extension loads without it
+        let mut data = [0u8; 752];
+        let mut lamports = 0;
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        let (_, extension) = AmmInfo::load_mut_extended(&account).unwrap();
+        assert!(extension.is_none());
+        assert!(!AmmInfo::check_account(&account, &owner).unwrap());
+    }
+
+    #[test]
+    fn test_amm_info_token_programs() {
+        let mut amm = AmmInfo::zeroed();
+        assert_eq!(amm.coin_token_program(), spl_token::id());
+        assert_eq!(amm.pc_token_program(), spl_token::id());
+
+        amm.pool_flags = 1 << 63;
+        amm.set_token_programs(&spl_token_2022::id(), &spl_token::id());
+        assert_eq!(amm.coin_token_program(), spl_token_2022::id());
+        assert_eq!(amm.pc_token_program(), spl_token::id());
+        assert_eq!(amm.lp_token_program(), spl_token::id());
+
+        amm.set_token_programs(&spl_token::id(), &spl_token_2022::id());
+        assert_eq!(amm.coin_token_program(), spl_token::id());
+        assert_eq!(amm.pc_token_program(), spl_token_2022::id());
+        assert_eq!(amm.lp_token_program(), spl_token_2022::id());
+        // other flags are kept
+        assert_eq!(amm.pool_flags & (1 << 63), 1 << 63);
+    }
+
+    #[test]
+    fn test_amm_info_lock() {
+        let mut amm = AmmInfo::zeroed();
+        amm.pool_flags = POOL_FLAG_NO_ORDERBOOK;
+        assert!(!amm.is_locked());
+        amm.lock();
+        assert!(amm.is_locked());
+        amm.unlock();
+        assert!(!amm.is_locked());
+        // other flags are kept
+        assert!(amm.is_orderbook_free());
+    }
+
+    #[test]
+    fn test_amm_info_pinned_hook_programs() {
+        let mut amm = AmmInfo::zeroed();
+        assert_eq!(amm.pinned_hook_programs(), None);
+
+        let hook_program = Pubkey::new_unique();
+        amm.pin_hook_programs(None, Some(hook_program));
+        assert_eq!(
+            amm.pinned_hook_programs(),
+            Some((Pubkey::default(), hook_program))
+        );
+        amm.pin_hook_programs(Some(hook_program), None);
+        assert_eq!(
+            amm.pinned_hook_programs(),
+            Some((hook_program, Pubkey::default()))
+        );
+        assert!(amm.pool_flags & POOL_FLAG_HOOKS_PINNED != 0);
+
+        let status = AmmStatus::HookProgramChanged;
+        assert_eq!(AmmStatus::from_u64(status.into_u64()).into_u64(), 8);
+        assert!(AmmStatus::valid_status(8));
+        assert!(status.withdraw_permission());
+        assert!(!status.deposit_permission());
+        assert!(!status.swap_permission());
+        assert!(!status.orderbook_permission());
+    }
+
+    #[test]
+    fn test_hook_registry_state() {
+        // the registry fields take the place of pending words
+        assert_eq!(size_of::<AmmConfig>(), 2 * 32 + 59 * 8 + 8);
+        let reviewer = Pubkey::new_unique();
+        let mut amm_config = AmmConfig::zeroed();
+        assert!(!amm_config.is_hook_reviewer(&Pubkey::default()));
+        amm_config.hook_reviewers[1] = reviewer;
+        assert!(amm_config.is_hook_reviewer(&reviewer));
+        assert!(!amm_config.is_hook_reviewer(&Pubkey::new_unique()));
+
+        let registration = HookRegistration {
+            hook_program: Pubkey::new_unique(),
+            max_compute_units: 10000,
+            program_data_slot: 100,
+            ..Default::default()
+        };
+        let hook_config = registration.hook_config();
+        assert!(hook_config.is_registry_approved());
+        assert_eq!(hook_config.hook_program, registration.hook_program);
+        assert_eq!({ hook_config.program_data_slot }, 100);
+        assert!(!hook_config.is_reentrant_safe());
+        assert!(!HookConfig::default().is_registry_approved());
+
+        let mut amm = AmmInfo::zeroed();
+        assert!(!amm.accepts_registry_hooks());
+        amm.pool_flags |= POOL_FLAG_REGISTRY_HOOKS;
+        assert!(amm.accepts_registry_hooks());
     }
 
     #[test]
@@ -5,24 +5,42 @@
 
 use solana_program::{
     account_info::AccountInfo,
+    compute_units::sol_remaining_compute_units,
+    instruction::AccountMeta,
     program::invoke_signed,
     program_error::ProgramError,
     pubkey::Pubkey,
     msg,
 };
-use spl_token_2022::extension::BaseStateWithExtensions;
-use crate::error::AmmError;
+use crate::{
+    error::AmmError,
+    state::HookConfig,
+    whitelist::HOOKED_TRANSFER_COMPUTE_UNITS,
+};
 
 /// Token transfer with automatic hook handling
+///
+/// Token-2022 transfers use `transfer_checked`, which needs the `mint` and its `decimals`;
+/// the mint of a Spl Token transfer may be omitted.
+/// `authority_signature_seeds` is empty when the authority signed the outer
+/// transaction itself, e.g. a user moving tokens into a pool vault.
 pub fn token_transfer_with_hook<'a>(
     token_program: AccountInfo<'a>,
     source: AccountInfo<'a>,
+    mint: Option<AccountInfo<'a>>,
     destination: AccountInfo<'a>,
     authority: AccountInfo<'a>,
     remaining_accounts: &[AccountInfo<'a>],
     amount: u64,
+    decimals: u8,
     authority_signature_seeds: &[&[u8]],
 ) -> Result<(), ProgramError> {
+    let signer = [authority_signature_seeds];
+    let signers: &[&[&[u8]]] = if authority_signature_seeds.is_empty() {
+        &[]
+    } else {
+        &signer
+    };
     if *token_program.key == spl_token::id() {
         // Standard SPL Token transfer
         let ix = spl_token::instruction::transfer(
@@ -37,22 +55,33 @@ pub fn token_transfer_with_hook<'a>(
         invoke_signed(
             &ix,
             &[source, destination, authority, token_program],
-            &[authority_signature_seeds],
+            signers,
         )
     } else if *token_program.key == spl_token_2022::id() {
-        // Token-2022 transfer with potential hooks (unchecked)
-        let ix = spl_token_2022::instruction::transfer(
+        let mint = mint.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        // Token-2022 transfer with potential hooks and fees
+        let mut ix = spl_token_2022::instruction::transfer_checked(
             token_program.key,
             source.key,
+            mint.key,
             destination.key,
             authority.key,
             &[],
             amount,
+            decimals,
         )?;
+        // Token-2022 forwards the accounts after the authority to the hook program
+        ix.accounts
+            .extend(remaining_accounts.iter().map(|account| AccountMeta {
+                pubkey: *account.key,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            }));
 
         // Prepare accounts including remaining accounts for hooks
         let mut accounts = vec![
             source.clone(),
+            mint.clone(),
             destination.clone(),
             authority.clone(),
             token_program.clone(),
@@ -60,12 +89,86 @@ pub fn token_transfer_with_hook<'a>(
         // Add remaining accounts for transfer hooks
         accounts.extend_from_slice(remaining_accounts);
 
-        invoke_signed(&ix, &accounts, &[authority_signature_seeds])
+        invoke_signed(&ix, &accounts, signers)
     } else {
         Err(ProgramError::InvalidArgument)
     }
 }
 
+/// `token_transfer_with_hook` metering a hooked transfer against the compute units
+/// registered in the `HookConfig` of its hook program, plus `HOOKED_TRANSFER_COMPUTE_UNITS`.
+/// The remaining compute is checked before the transfer, so a transaction without enough
+/// budget fails with `HookComputeBudgetExceeded` rather than running out of compute in the hook.
+///
+/// Returns the compute units the transfer consumed, 0 without a hook.
+pub fn token_transfer_with_hook_budget<'a>(
+    hook_config: Option<&HookConfig>,
+    token_program: AccountInfo<'a>,
+    source: AccountInfo<'a>,
+    mint: Option<AccountInfo<'a>>,
+    destination: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    remaining_accounts: &[AccountInfo<'a>],
+    amount: u64,
+    decimals: u8,
+    authority_signature_seeds: &[&[u8]],
+) -> Result<u64, ProgramError> {
+    let hook_config = match hook_config {
+        Some(hook_config) => hook_config,
+        None => {
+            token_transfer_with_hook(
+                token_program,
+                source,
+                mint,
+                destination,
+                authority,
+                remaining_accounts,
+                amount,
+                decimals,
+                authority_signature_seeds,
+            )?;
+            return Ok(0);
+        }
+    };
+    // HookConfig is packed, its fields are copied out before use
+    let hook_program = hook_config.hook_program;
+    let budget = hook_config
+        .max_compute_units
+        .saturating_add(HOOKED_TRANSFER_COMPUTE_UNITS);
+    let remaining_before = sol_remaining_compute_units();
+    if remaining_before < budget {
+        msg!(
+            "Transfer hook {} needs {} compute units, {} remaining",
+            hook_program,
+            budget,
+            remaining_before
+        );
+        return Err(AmmError::HookComputeBudgetExceeded.into());
+    }
+    token_transfer_with_hook(
+        token_program,
+        source,
+        mint,
+        destination,
+        authority,
+        remaining_accounts,
+        amount,
+        decimals,
+        authority_signature_seeds,
+    )?;
+    let consumed = remaining_before.saturating_sub(sol_remaining_compute_units());
+    if consumed > budget {
+        msg!(
+            "Transfer hook {} consumed {} compute units, budget {}",
+            hook_program,
+            consumed,
+            budget
+        );
+        return Err(AmmError::HookComputeBudgetExceeded.into());
+    }
+    Ok(consumed)
+}
+
 /// Token mint_to with program selection
 pub fn token_mint_to<'a>(
     token_program: AccountInfo<'a>,
@@ -111,6 +214,9 @@ pub fn token_mint_to<'a>(
 }
 
 /// Token burn with program selection
+///
+/// `authority_signature_seeds` is empty when the owner signed the outer
+/// transaction itself, e.g. a user burning lp tokens on withdraw.
 pub fn token_burn<'a>(
     token_program: AccountInfo<'a>,
     burn_account: AccountInfo<'a>,
@@ -119,6 +225,12 @@ pub fn token_burn<'a>(
     burn_amount: u64,
     authority_signature_seeds: &[&[u8]],
 ) -> Result<(), ProgramError> {
+    let signer = [authority_signature_seeds];
+    let signers: &[&[&[u8]]] = if authority_signature_seeds.is_empty() {
+        &[]
+    } else {
+        &signer
+    };
     if *token_program.key == spl_token::id() {
         let ix = spl_token::instruction::burn(
             token_program.key,
@@ -132,7 +244,7 @@ pub fn token_burn<'a>(
         invoke_signed(
             &ix,
             &[burn_account, mint, owner, token_program],
-            &[authority_signature_seeds],
+            signers,
         )
     } else if *token_program.key == spl_token_2022::id() {
         let ix = spl_token_2022::instruction::burn(
@@ -147,7 +259,7 @@ pub fn token_burn<'a>(
         invoke_signed(
             &ix,
             &[burn_account, mint, owner, token_program],
-            &[authority_signature_seeds],
+            signers,
         )
     } else {
         Err(ProgramError::InvalidArgument)
@@ -287,32 +399,28 @@ pub fn create_associated_token_account<'a>(
     )
 }
 
-/// Validate transfer hook accounts for Token-2022
+/// Validate transfer hook accounts for Token-2022 exactly against the accounts
+/// resolved by `whitelist::get_required_hook_accounts`: order, writability and signer flags
 pub fn validate_transfer_hook_accounts(
-    mint_info: &AccountInfo,
-    token_program_id: &Pubkey,
-    remaining_accounts: &[AccountInfo],
+    expected_accounts: &[AccountMeta],
+    provided_accounts: &[AccountInfo],
 ) -> Result<(), ProgramError> {
-    if *token_program_id != spl_token_2022::id() {
-        return Ok(());
+    if expected_accounts.len() != provided_accounts.len() {
+        msg!(
+            "Transfer hook expects {} accounts, {} provided",
+            expected_accounts.len(),
+            provided_accounts.len()
+        );
+        return Err(AmmError::InvalidTransferHookAccounts.into());
     }
-
-    // Check if mint has transfer hooks
-    let mint_data = mint_info.data.borrow();
-    let mint = spl_token_2022::extension::StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)
-        .map_err(|_| AmmError::ExpectedMint)?;
-
-    if let Ok(transfer_hook) = mint.get_extension::<spl_token_2022::extension::transfer_hook::TransferHook>() {
-        let hook_program: Option<Pubkey> = transfer_hook.program_id.into();
-        msg!("Transfer hook program: {:?}", hook_program);
-        
-        // Basic validation that we have the minimum required accounts
-        // The exact validation would depend on the specific hook implementation
-        if remaining_accounts.is_empty() {
-            msg!("Transfer hook enabled but no additional accounts provided");
+    for (expected, provided) in expected_accounts.iter().zip(provided_accounts.iter()) {
+        if expected.pubkey != *provided.key
+            || expected.is_writable != provided.is_writable
+            || expected.is_signer != provided.is_signer
+        {
+            msg!("Transfer hook account mismatch, expected {}", expected.pubkey);
             return Err(AmmError::InvalidTransferHookAccounts.into());
         }
     }
-
     Ok(())
-}
\ No newline at end of file
+}
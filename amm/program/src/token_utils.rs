@@ -5,12 +5,17 @@
 
 use solana_program::{
     account_info::AccountInfo,
+    msg,
     program_pack::Pack,
     pubkey::Pubkey,
 };
 use spl_token::state as spl_token_state;
 use spl_token_2022::{
-    extension::{StateWithExtensions, ExtensionType, BaseStateWithExtensions},
+    extension::{
+        default_account_state::DefaultAccountState,
+        transfer_fee::{TransferFeeConfig, MAX_FEE_BASIS_POINTS},
+        StateWithExtensions, ExtensionType, BaseStateWithExtensions,
+    },
     state as spl_token_2022_state,
 };
 use crate::error::AmmError;
@@ -177,6 +182,138 @@ pub fn get_transfer_hook_program_id(
     }
 }
 
+/// Get the transfer fee withheld by a Token-2022 mint when `amount` is transferred in `epoch`
+pub fn get_transfer_fee(
+    mint_info: &AccountInfo,
+    token_program_id: &Pubkey,
+    epoch: u64,
+    amount: u64,
+) -> Result<u64, AmmError> {
+    if *token_program_id != spl_token_2022::id() {
+        return Ok(0);
+    }
+
+    let mint_data = mint_info.data.borrow();
+    let mint = StateWithExtensions::<spl_token_2022_state::Mint>::unpack(&mint_data)
+        .map_err(|_| AmmError::ExpectedMint)?;
+
+    match mint.get_extension::<TransferFeeConfig>() {
+        Ok(transfer_fee_config) => transfer_fee_config
+            .calculate_epoch_fee(epoch, amount)
+            .ok_or(AmmError::CalculationExRateFailure),
+        Err(_) => Ok(0),
+    }
+}
+
+/// Get the transfer fee to add to `post_fee_amount` so that it arrives in full
+/// when transferred in `epoch`
+pub fn get_transfer_inverse_fee(
+    mint_info: &AccountInfo,
+    token_program_id: &Pubkey,
+    epoch: u64,
+    post_fee_amount: u64,
+) -> Result<u64, AmmError> {
+    if *token_program_id != spl_token_2022::id() || post_fee_amount == 0 {
+        return Ok(0);
+    }
+
+    let mint_data = mint_info.data.borrow();
+    let mint = StateWithExtensions::<spl_token_2022_state::Mint>::unpack(&mint_data)
+        .map_err(|_| AmmError::ExpectedMint)?;
+
+    match mint.get_extension::<TransferFeeConfig>() {
+        Ok(transfer_fee_config) => {
+            let epoch_fee = transfer_fee_config.get_epoch_fee(epoch);
+            if u16::from(epoch_fee.transfer_fee_basis_points) == MAX_FEE_BASIS_POINTS {
+                // every transferred token is a fee up to the maximum
+                Ok(u64::from(epoch_fee.maximum_fee))
+            } else {
+                transfer_fee_config
+                    .calculate_inverse_epoch_fee(epoch, post_fee_amount)
+                    .ok_or(AmmError::CalculationExRateFailure)
+            }
+        }
+        Err(_) => Ok(0),
+    }
+}
+
+/// How pools treat a Token-2022 mint extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtensionPolicy {
+    /// Safe for the pool vaults
+    Allowed,
+    /// Lets the issuer drain or close the pool vaults, the pool creation must be approved by the admin
+    AdminApproved,
+    /// Bricks the pool vaults or hides their balances
+    Forbidden,
+}
+
+/// Classify a Token-2022 mint extension. Extensions unknown to this program,
+/// e.g. a pausable configuration, are forbidden.
+pub fn extension_policy(extension_type: ExtensionType) -> ExtensionPolicy {
+    match extension_type {
+        ExtensionType::TransferFeeConfig
+        | ExtensionType::InterestBearingConfig
+        | ExtensionType::TransferHook
+        | ExtensionType::MetadataPointer
+        | ExtensionType::TokenMetadata
+        | ExtensionType::GroupPointer
+        | ExtensionType::TokenGroup
+        | ExtensionType::GroupMemberPointer
+        | ExtensionType::TokenGroupMember => ExtensionPolicy::Allowed,
+        // only a frozen default state is forbidden, see `check_mint_extensions`
+        ExtensionType::DefaultAccountState => ExtensionPolicy::Allowed,
+        ExtensionType::PermanentDelegate | ExtensionType::MintCloseAuthority => {
+            ExtensionPolicy::AdminApproved
+        }
+        _ => ExtensionPolicy::Forbidden,
+    }
+}
+
+/// Check the extensions of a mint against the pool extension policy.
+/// Returns the set of extensions of the mint, bit n is set for `ExtensionType` n.
+pub fn check_mint_extensions(
+    mint_info: &AccountInfo,
+    token_program_id: &Pubkey,
+    admin_approved: bool,
+) -> Result<u64, AmmError> {
+    if *token_program_id != spl_token_2022::id() {
+        return Ok(0);
+    }
+
+    let mint_data = mint_info.data.borrow();
+    let mint = StateWithExtensions::<spl_token_2022_state::Mint>::unpack(&mint_data)
+        .map_err(|_| AmmError::ExpectedMint)?;
+
+    let mut extensions = 0u64;
+    for extension_type in mint
+        .get_extension_types()
+        .map_err(|_| AmmError::ExpectedMint)?
+    {
+        let mut policy = extension_policy(extension_type);
+        if extension_type == ExtensionType::DefaultAccountState {
+            let default_account_state = mint
+                .get_extension::<DefaultAccountState>()
+                .map_err(|_| AmmError::ExpectedMint)?;
+            if default_account_state.state == spl_token_2022_state::AccountState::Frozen as u8 {
+                policy = ExtensionPolicy::Forbidden;
+            }
+        }
+        match policy {
+            ExtensionPolicy::Allowed => {}
+            ExtensionPolicy::AdminApproved if admin_approved => {}
+            _ => {
+                msg!("Mint extension {:?} is {:?}", extension_type, policy);
+                return Err(AmmError::ForbiddenMintExtension);
+            }
+        }
+        extensions |= 1u64
+            .checked_shl(extension_type as u16 as u32)
+            .unwrap_or_default();
+    }
+    Ok(extensions)
+}
+
 /// Get the account length for a mint with extensions
 pub fn get_mint_len_for_token_2022(extension_types: &[ExtensionType]) -> usize {
     ExtensionType::try_calculate_account_len::<spl_token_2022_state::Mint>(extension_types)
@@ -189,6 +326,26 @@ pub fn get_account_len_for_token_2022(extension_types: &[ExtensionType]) -> usiz
         .unwrap_or(spl_token_2022_state::Account::LEN)
 }
 
+/// Get the length of a token account of the mint, with the account extensions its
+/// mint extensions require
+pub fn get_account_len_for_mint(
+    mint_info: &AccountInfo,
+    token_program_id: &Pubkey,
+) -> Result<usize, AmmError> {
+    if *token_program_id != spl_token_2022::id() {
+        return Ok(spl_token_state::Account::LEN);
+    }
+    let mint_data = mint_info.data.borrow();
+    let mint = StateWithExtensions::<spl_token_2022_state::Mint>::unpack(&mint_data)
+        .map_err(|_| AmmError::ExpectedMint)?;
+    let mint_extensions = mint
+        .get_extension_types()
+        .map_err(|_| AmmError::ExpectedMint)?;
+    Ok(get_account_len_for_token_2022(
+        &ExtensionType::get_required_init_account_extensions(&mint_extensions),
+    ))
+}
+
 /// Check if two token programs are compatible for a swap operation
 pub fn are_programs_compatible(program_a: &Pubkey, program_b: &Pubkey) -> bool {
     // 両方が SPL Token または Token-2022 のいずれかである場合のみ互換とみなす
@@ -1,160 +1,464 @@
 //! Transfer Hook Whitelist Management
-//! 
+//!
 //! This module handles the whitelist of approved transfer hook programs
-//! that are allowed to be used with this AMM.
+//! that are allowed to be used with this AMM. Each approved hook program has
+//! a `HookConfig` account owned by this program, added and removed by the admin,
+//! or activated through the bonded registry for pools that accept registry hooks.
 
 use solana_program::{
     account_info::AccountInfo,
+    bpf_loader, bpf_loader_deprecated,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    hash::hash,
+    instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     pubkey::Pubkey,
     msg,
 };
-use std::collections::HashMap;
-use crate::{error::AmmError, state::AmmConfig};
-
-/// Configuration for a whitelisted transfer hook
-#[derive(Clone, Debug)]
-pub struct HookConfig {
-    /// Whether this hook is safe for re-entrant calls
-    pub is_reentrant_safe: bool,
-    /// Maximum compute units this hook is expected to consume
-    pub max_compute_units: u32,
-    /// Expected hash of additional accounts for validation
-    pub expected_accounts_hash: Option<[u8; 32]>,
+use spl_tlv_account_resolution::state::ExtraAccountMetaList;
+use spl_transfer_hook_interface::{
+    get_extra_account_metas_address,
+    instruction::{ExecuteInstruction, TransferHookInstruction},
+};
+use crate::{
+    error::AmmError,
+    processor::{trade_context_address, HOOK_CONFIG_SEED, HOOK_REGISTRATION_SEED},
+    state::{HookConfig, TradeContext},
+};
+
+/// Max compute units a whitelisted hook may declare
+pub const MAX_HOOK_COMPUTE_UNITS: u64 = 50000;
+/// Compute units of a Token-2022 `transfer_checked` and its CPI into the hook program,
+/// allowed on top of the hook's own `HookConfig::max_compute_units`
+pub const HOOKED_TRANSFER_COMPUTE_UNITS: u64 = 20000;
+
+/// Hook programs approved by default in devnet builds with their max compute units, only while
+/// they can not be redeployed: their ProgramData Account, passed after the hook config address,
+/// must have no upgrade authority, see `immutable_hook_program_data`. A `HookConfig` added for
+/// the program takes precedence, otherwise its unused address still takes the hook config slot.
+#[cfg(feature = "devnet")]
+pub const DEFAULT_HOOK_PROGRAMS: [(Pubkey, u64); 1] = [
+    // allowlist-hook, the reference KYC allowlist transfer hook
+    (
+        solana_program::pubkey!("FR6LsTDVFePtFJ9fetKRGpz63Mi59yxWNzJ2EDU5yFQ1"),
+        20000,
+    ),
+];
+#[cfg(not(feature = "devnet"))]
+pub const DEFAULT_HOOK_PROGRAMS: [(Pubkey, u64); 0] = [];
+
+/// Address of the `HookConfig` account of a hook program
+pub fn hook_config_address(program_id: &Pubkey, hook_program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[HOOK_CONFIG_SEED, hook_program_id.as_ref()], program_id)
 }
 
-/// Default whitelist of known safe transfer hooks
-pub fn get_default_whitelist() -> HashMap<Pubkey, HookConfig> {
-    let whitelist = HashMap::new();
-    
-    // Example: Add some well-known safe transfer hooks
-    // These would be actual program IDs of audited hooks in production
-    
-    // Placeholder for example hook (would be replaced with real program IDs)
-    // whitelist.insert(
-    //     Pubkey::from_str("ExampleHookProgramId11111111111111111111111").unwrap(),
-    //     HookConfig {
-    //         is_reentrant_safe: true,
-    //         max_compute_units: 10000,
-    //         expected_accounts_hash: None,
-    //     }
-    // );
-    
-    whitelist
+/// Address of the `HookRegistration` account of a proposed hook program
+pub fn hook_registration_address(program_id: &Pubkey, hook_program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[HOOK_REGISTRATION_SEED, hook_program_id.as_ref()],
+        program_id,
+    )
 }
 
-/// Validate that a transfer hook program is whitelisted
-pub fn validate_transfer_hook(
+/// Find the `HookConfig` of a hook program among `accounts`
+pub fn find_hook_config(
+    program_id: &Pubkey,
     hook_program_id: &Pubkey,
-    config_info: &AccountInfo,
-) -> Result<(), ProgramError> {
-    // Load AMM config to get the current whitelist
-    let _config = AmmConfig::load_checked(config_info, config_info.key)?;
-    
-    // For now, we'll use a simple approach where we check against default whitelist
-    // In a full implementation, the whitelist would be stored in the config account
-    let whitelist = get_default_whitelist();
-    
-    if whitelist.contains_key(hook_program_id) {
-        msg!("Transfer hook program {} is whitelisted", hook_program_id);
-        Ok(())
-    } else {
-        msg!("Transfer hook program {} is NOT whitelisted", hook_program_id);
-        Err(AmmError::UnauthorizedTransferHook.into())
+    accounts: &[AccountInfo],
+) -> Result<HookConfig, ProgramError> {
+    for account in accounts.iter().filter(|account| account.owner == program_id) {
+        let hook_config = match HookConfig::load_checked(account, program_id) {
+            Ok(hook_config) => *hook_config,
+            Err(_) => continue,
+        };
+        if hook_config.hook_program != *hook_program_id {
+            continue;
+        }
+        // the stored bump pins the account to its seeds
+        let address = Pubkey::create_program_address(
+            &[
+                HOOK_CONFIG_SEED,
+                hook_program_id.as_ref(),
+                &[hook_config.bump as u8],
+            ],
+            program_id,
+        )
+        .map_err(|_| AmmError::UnauthorizedTransferHook)?;
+        if address == *account.key {
+            return Ok(hook_config);
+        }
     }
+    if let Some(hook_config) = default_hook_config(program_id, hook_program_id, accounts) {
+        return Ok(hook_config);
+    }
+    msg!("Transfer hook program {} is NOT whitelisted", hook_program_id);
+    Err(AmmError::UnauthorizedTransferHook.into())
 }
 
-/// Check if a hook configuration is safe for use
-pub fn is_hook_safe(hook_config: &HookConfig) -> bool {
-    // Basic safety checks
-    hook_config.max_compute_units <= 50000 // Reasonable CU limit
+/// `HookConfig` of a hook program of `DEFAULT_HOOK_PROGRAMS`, whose uninitialized hook config
+/// account and ProgramData account must be among `accounts`. The config pins the ProgramData
+/// at its last deployed slot like one added by the admin.
+pub fn default_hook_config(
+    program_id: &Pubkey,
+    hook_program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> Option<HookConfig> {
+    let (_, max_compute_units) = DEFAULT_HOOK_PROGRAMS
+        .iter()
+        .find(|(default_hook_program, _)| default_hook_program == hook_program_id)?;
+    let (address, bump) = hook_config_address(program_id, hook_program_id);
+    accounts
+        .iter()
+        .find(|account| *account.key == address && account.data_is_empty())?;
+    let (program_data, program_data_slot) =
+        match immutable_hook_program_data(hook_program_id, accounts) {
+            Ok(program_data) => program_data,
+            Err(_) => {
+                msg!(
+                    "Default transfer hook program {} is not immutable",
+                    hook_program_id
+                );
+                return None;
+            }
+        };
+    Some(HookConfig {
+        hook_program: *hook_program_id,
+        max_compute_units: *max_compute_units,
+        bump: bump as u64,
+        program_data,
+        program_data_slot,
+        ..Default::default()
+    })
 }
 
-/// Validate additional accounts required by transfer hooks
-pub fn validate_hook_accounts(
+/// ProgramData address and last deployed slot of a hook program of the upgradeable loader
+/// that can not be redeployed. Its ProgramData Account must be in `accounts` and have no
+/// upgrade authority.
+pub fn immutable_hook_program_data(
     hook_program_id: &Pubkey,
-    provided_accounts: &[AccountInfo],
-    _expected_config: &HookConfig,
+    accounts: &[AccountInfo],
+) -> Result<(Pubkey, u64), ProgramError> {
+    let program_data = hook_program_data_address(hook_program_id);
+    let program_data_info = accounts
+        .iter()
+        .find(|account| *account.key == program_data)
+        .ok_or(AmmError::InvalidTransferHookAccounts)?;
+    match get_hook_program_data(program_data_info)? {
+        (slot, None) => Ok((program_data, slot)),
+        (_, Some(_)) => Err(AmmError::UnverifiedHookProgram.into()),
+    }
+}
+
+/// Validate that the transfer hook of a mint, if any, is whitelisted.
+/// The `HookConfig` account of the hook program must be among `accounts`.
+/// A hook activated through the registry is only accepted with `registry_hooks`.
+pub fn validate_transfer_hook(
+    program_id: &Pubkey,
+    mint_info: &AccountInfo,
+    token_program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    registry_hooks: bool,
+) -> Result<Option<HookConfig>, ProgramError> {
+    let hook_program_id =
+        match crate::token_utils::get_transfer_hook_program_id(mint_info, token_program_id)? {
+            Some(hook_program_id) => hook_program_id,
+            None => return Ok(None),
+        };
+    let hook_config = find_hook_config(program_id, &hook_program_id, accounts)?;
+    if hook_config.is_registry_approved() && !registry_hooks {
+        msg!(
+            "Transfer hook program {} is only approved by the registry",
+            hook_program_id
+        );
+        return Err(AmmError::UnauthorizedTransferHook.into());
+    }
+    validate_hook_program_data(&hook_config, accounts)?;
+    Ok(Some(hook_config))
+}
+
+/// Address of the ProgramData account of a program of the upgradeable loader
+pub fn hook_program_data_address(hook_program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[hook_program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+/// ProgramData account of a hook program, default for a program of a non-upgradeable
+/// loader, whose code can not change
+pub fn get_hook_program_data_address(
+    hook_program_info: &AccountInfo,
+) -> Result<Pubkey, ProgramError> {
+    if *hook_program_info.owner == bpf_loader::id()
+        || *hook_program_info.owner == bpf_loader_deprecated::id()
+    {
+        return Ok(Pubkey::default());
+    }
+    if *hook_program_info.owner != bpf_loader_upgradeable::id() {
+        msg!(
+            "Transfer hook program {} has an unknown loader",
+            hook_program_info.key
+        );
+        return Err(AmmError::UnverifiedHookProgram.into());
+    }
+    match bincode::deserialize(&hook_program_info.try_borrow_data()?) {
+        Ok(UpgradeableLoaderState::Program {
+            programdata_address,
+        }) => Ok(programdata_address),
+        _ => Err(AmmError::UnverifiedHookProgram.into()),
+    }
+}
+
+/// Last deployed slot and upgrade authority of a hook program, read from its ProgramData account
+pub fn get_hook_program_data(
+    program_data_info: &AccountInfo,
+) -> Result<(u64, Option<Pubkey>), ProgramError> {
+    if *program_data_info.owner != bpf_loader_upgradeable::id() {
+        return Err(AmmError::UnverifiedHookProgram.into());
+    }
+    let data = program_data_info.try_borrow_data()?;
+    let metadata = data
+        .get(..UpgradeableLoaderState::size_of_programdata_metadata())
+        .ok_or(AmmError::UnverifiedHookProgram)?;
+    match bincode::deserialize(metadata) {
+        Ok(UpgradeableLoaderState::ProgramData {
+            slot,
+            upgrade_authority_address,
+        }) => Ok((slot, upgrade_authority_address)),
+        _ => Err(AmmError::UnverifiedHookProgram.into()),
+    }
+}
+
+/// A hook program of the upgradeable loader stays approved while it has no upgrade
+/// authority or the one of its hook config, and is not redeployed.
+/// Its ProgramData account must be in `accounts`.
+pub fn validate_hook_program_data(
+    hook_config: &HookConfig,
+    accounts: &[AccountInfo],
 ) -> Result<(), ProgramError> {
-    // This is a simplified validation
-    // In a real implementation, you would:
-    // 1. Verify the exact accounts required by the hook
-    // 2. Check account ownership, writability, and signer status
-    // 3. Validate account data if necessary
-    
-    if provided_accounts.is_empty() {
-        msg!("No additional accounts provided for hook {}", hook_program_id);
-        return Err(AmmError::InvalidTransferHookAccounts.into());
+    let program_data = hook_config.program_data;
+    if program_data == Pubkey::default() {
+        return Ok(());
     }
-    
-    // Check if we have the hook program in the accounts
-    let has_hook_program = provided_accounts
+    let program_data_info = accounts
         .iter()
-        .any(|acc| acc.key == hook_program_id);
-    
-    if !has_hook_program {
-        msg!("Hook program {} not found in provided accounts", hook_program_id);
-        return Err(AmmError::InvalidTransferHookAccounts.into());
+        .find(|account| *account.key == program_data)
+        .ok_or(AmmError::InvalidTransferHookAccounts)?;
+    let (slot, upgrade_authority) = get_hook_program_data(program_data_info)?;
+    if slot != hook_config.program_data_slot {
+        let hook_program = hook_config.hook_program;
+        msg!(
+            "Transfer hook program {} was redeployed at slot {}",
+            hook_program,
+            slot
+        );
+        return Err(AmmError::UnverifiedHookProgram.into());
+    }
+    if let Some(upgrade_authority) = upgrade_authority {
+        if upgrade_authority != hook_config.upgrade_authority {
+            msg!(
+                "Transfer hook upgrade authority {} is not approved",
+                upgrade_authority
+            );
+            return Err(AmmError::UnverifiedHookProgram.into());
+        }
     }
-    
-    msg!("Hook accounts validation passed for {}", hook_program_id);
     Ok(())
 }
 
-/// Get required additional accounts for a transfer hook
-/// This would typically involve calling the hook's "get_extra_account_metas" function
-pub fn get_required_hook_accounts(
-    hook_program_id: &Pubkey,
-    _mint: &Pubkey,
-    _source: &Pubkey,
-    _destination: &Pubkey,
-) -> Result<Vec<Pubkey>, ProgramError> {
-    // This is a placeholder implementation
-    // In a real scenario, you would:
-    // 1. Call the hook's get_extra_account_metas function
-    // 2. Parse the returned account metas
-    // 3. Return the list of required accounts
-    
-    msg!("Getting required accounts for hook {}", hook_program_id);
-    
-    // For now, return empty vector as placeholder
-    Ok(vec![])
+/// Split the accounts of one hooked Token-2022 transfer off the front of `accounts`.
+/// This is the transfer account group the instructions take for each of their Token-2022
+/// transfers, the hook accounts resolved for that transfer's source, destination and authority.
+///
+///   0. `[]` Mint of the transferred token
+///   1. `[]` Hook config Account of the whitelisted transfer hook program, if the mint has a hook
+///   2. `[]` ProgramData Account of the hook program, if it belongs to the upgradeable loader
+///   3..N. Extra accounts resolved from the hook's ExtraAccountMetaList, in order
+///   N+1. `[]` Transfer hook program id
+///   N+2. `[]` ExtraAccountMetaList validation Account of the mint
+///
+/// Returns the mint, the hook config of a hooked mint, the accounts Token-2022 forwards
+/// to the hook (3..N+2) and the rest.
+/// The mint is required by `transfer_checked`, an SPL Token transfer takes no group.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn split_transfer_hook_accounts<'a, 'b>(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    token_program_id: &Pubkey,
+    source_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    amount: u64,
+    accounts: &'b [AccountInfo<'a>],
+    registry_hooks: bool,
+) -> Result<
+    (
+        Option<&'b AccountInfo<'a>>,
+        Option<HookConfig>,
+        &'b [AccountInfo<'a>],
+        &'b [AccountInfo<'a>],
+    ),
+    ProgramError,
+> {
+    if *token_program_id != spl_token_2022::id() {
+        return Ok((None, None, &[], accounts));
+    }
+    let (mint_info, rest) = match accounts.split_first() {
+        Some((mint_info, rest)) if mint_info.key == mint => (mint_info, rest),
+        _ => {
+            msg!("Mint {} is required for a Token-2022 transfer", mint);
+            return Err(AmmError::WrongAccountsNumber.into());
+        }
+    };
+    if mint_info.owner != token_program_id {
+        return Err(AmmError::InvalidTransferHookAccounts.into());
+    }
+    let hook_config_accounts = &rest[..rest.len().min(2)];
+    let hook_config = match validate_transfer_hook(
+        program_id,
+        mint_info,
+        token_program_id,
+        hook_config_accounts,
+        registry_hooks,
+    )? {
+        Some(hook_config) => hook_config,
+        None => return Ok((Some(mint_info), None, &[], rest)),
+    };
+    let rest = if hook_config.program_data == Pubkey::default() {
+        &rest[1..]
+    } else {
+        &rest[2..]
+    };
+
+    let expected_accounts = get_required_hook_accounts(
+        &hook_config.hook_program,
+        mint_info,
+        source_info,
+        destination_info,
+        authority_info,
+        amount,
+        rest,
+    )?;
+    if rest.len() < expected_accounts.len() {
+        return Err(AmmError::InvalidTransferHookAccounts.into());
+    }
+    let (hook_accounts, rest) = rest.split_at(expected_accounts.len());
+    // a hook reads the trade context of its mint, this program writes it before the transfer
+    let mut provided_accounts = expected_accounts.clone();
+    for (expected, provided) in provided_accounts.iter_mut().zip(hook_accounts.iter()) {
+        if provided.is_writable
+            && !expected.is_writable
+            && expected.pubkey == *provided.key
+            && *provided.key == trade_context_address(program_id, mint).0
+        {
+            expected.is_writable = true;
+        }
+    }
+    crate::token_invokers::validate_transfer_hook_accounts(&provided_accounts, hook_accounts)?;
+    // a hook that is not re-entrant safe is not handed this program to call back into
+    if !hook_config.is_reentrant_safe()
+        && hook_accounts
+            .iter()
+            .any(|account| account.key == program_id)
+    {
+        let hook_program = hook_config.hook_program;
+        msg!("Transfer hook {} is not re-entrant safe", hook_program);
+        return Err(AmmError::InvalidTransferHookAccounts.into());
+    }
+    if let Some(expected_accounts_hash) = hook_config.expected_accounts_hash() {
+        // the hook program and validation account are fixed by the mint, only the extras are pinned
+        let extra_accounts = &expected_accounts[..expected_accounts.len() - 2];
+        if hook_accounts_hash(extra_accounts) != expected_accounts_hash {
+            msg!("Transfer hook accounts do not match the pinned hash");
+            return Err(AmmError::InvalidTransferHookAccounts.into());
+        }
+    }
+    Ok((Some(mint_info), Some(hook_config), hook_accounts, rest))
+}
+
+/// Find the `TradeContext` account of a mint among the hook accounts of its transfer
+pub fn find_trade_context<'a, 'b>(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    accounts: &'b [AccountInfo<'a>],
+) -> Option<&'b AccountInfo<'a>> {
+    accounts
+        .iter()
+        .filter(|account| account.owner == program_id)
+        .find(|account| TradeContext::load_checked(account, program_id, mint).is_ok())
+}
+
+/// Hash of a list of hook extra accounts, as pinned in `HookConfig::expected_accounts_hash`
+pub fn hook_accounts_hash(accounts: &[AccountMeta]) -> [u8; 32] {
+    let mut data = Vec::with_capacity(accounts.len() * 34);
+    for account in accounts {
+        data.extend_from_slice(account.pubkey.as_ref());
+        data.push(account.is_signer as u8);
+        data.push(account.is_writable as u8);
+    }
+    hash(&data).to_bytes()
+}
+
+/// Check if a hook configuration is safe for use, the hook program itself is checked
+/// by `validate_hook_program_data`
+pub fn is_hook_safe(hook_config: &HookConfig) -> bool {
+    // Basic safety checks
+    hook_config.max_compute_units <= MAX_HOOK_COMPUTE_UNITS
 }
 
-/// Update the transfer hook whitelist (admin function)
-pub fn update_whitelist(
-    config_info: &AccountInfo,
-    admin: &AccountInfo,
+/// Get the accounts Token-2022 forwards to a transfer hook program, in order:
+/// the extra accounts resolved from the mint's ExtraAccountMetaList, the hook
+/// program and the ExtraAccountMetaList validation account.
+/// The validation account and every resolved account must be in `accounts`.
+pub fn get_required_hook_accounts<'a>(
     hook_program_id: &Pubkey,
-    hook_config: HookConfig,
-    add: bool, // true to add, false to remove
-) -> Result<(), ProgramError> {
-    // Verify admin authority
-    if !admin.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Load config
-    let config = AmmConfig::load_checked(config_info, config_info.key)?;
-    
-    // Verify admin has permission to modify whitelist
-    if config.pnl_owner != *admin.key {
-        return Err(AmmError::InvalidOwner.into());
-    }
-    
-    // In a real implementation, we would modify the whitelist stored in config
-    // For now, just log the operation
-    if add {
-        msg!("Adding hook {} to whitelist", hook_program_id);
-        if !is_hook_safe(&hook_config) {
-            return Err(AmmError::InvalidParamsSet.into());
-        }
-    } else {
-        msg!("Removing hook {} from whitelist", hook_program_id);
+    mint_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    amount: u64,
+    accounts: &[AccountInfo<'a>],
+) -> Result<Vec<AccountMeta>, ProgramError> {
+    let validation_address = get_extra_account_metas_address(mint_info.key, hook_program_id);
+    let validation_info = accounts
+        .iter()
+        .find(|account| *account.key == validation_address)
+        .ok_or(AmmError::InvalidTransferHookAccounts)?;
+    if validation_info.owner != hook_program_id {
+        msg!("Transfer hook validation account {} is not owned by the hook", validation_address);
+        return Err(AmmError::InvalidTransferHookAccounts.into());
     }
-    
-    // TODO: Actually update the config data with the new whitelist
-    // This would require extending the AmmConfig structure
-    
-    Ok(())
-}
\ No newline at end of file
+
+    // the execute instruction the hook receives, seeds may refer to its accounts and data
+    let mut execute_instruction = Instruction {
+        program_id: *hook_program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*source_info.key, false),
+            AccountMeta::new_readonly(*mint_info.key, false),
+            AccountMeta::new_readonly(*destination_info.key, false),
+            AccountMeta::new_readonly(*authority_info.key, false),
+            AccountMeta::new_readonly(validation_address, false),
+        ],
+        data: TransferHookInstruction::Execute { amount }.pack(),
+    };
+    let mut execute_account_infos = vec![
+        source_info.clone(),
+        mint_info.clone(),
+        destination_info.clone(),
+        authority_info.clone(),
+        validation_info.clone(),
+    ];
+    let execute_account_len = execute_instruction.accounts.len();
+    ExtraAccountMetaList::add_to_cpi_instruction::<ExecuteInstruction>(
+        &mut execute_instruction,
+        &mut execute_account_infos,
+        &validation_info.try_borrow_data()?,
+        accounts,
+    )
+    .map_err(|err| {
+        msg!("Failed to resolve transfer hook accounts: {:?}", err);
+        AmmError::InvalidTransferHookAccounts
+    })?;
+
+    let mut required_accounts = execute_instruction.accounts.split_off(execute_account_len);
+    required_accounts.push(AccountMeta::new_readonly(*hook_program_id, false));
+    required_accounts.push(AccountMeta::new_readonly(validation_address, false));
+    Ok(required_accounts)
+}
@@ -0,0 +1,870 @@
+//! Runs the AMM instructions in solana-program-test on pools of Token-2022 mints with a
+//! transfer fee, the coin side of a pool with a whitelisted transfer hook and the pc side
+//! without one.
+
+use raydium_amm::{
+    error::AmmError,
+    instruction::{self, SwapRouteHop, WITHDRAW_ESCROW_COIN, WITHDRAW_ESCROW_PC},
+    processor::{
+        config_feature, escrow_token_address, get_associated_address_and_bump_seed, Processor,
+        AMM_ASSOCIATED_SEED, AMM_CONFIG_SEED, AUTHORITY_AMM, COIN_ESCROW_ASSOCIATED_SEED,
+        COIN_VAULT_ASSOCIATED_SEED, LP_MINT_ASSOCIATED_SEED, PC_ESCROW_ASSOCIATED_SEED,
+        PC_VAULT_ASSOCIATED_SEED,
+    },
+    state::{AmmConfig, AmmInfo, AmmStatus, FeeSplit, HookConfig},
+    whitelist::{hook_config_address, MAX_HOOK_COMPUTE_UNITS},
+};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    compute_budget::ComputeBudgetInstruction,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id, instruction::create_associated_token_account,
+};
+use spl_tlv_account_resolution::state::ExtraAccountMetaList;
+use spl_token_2022::{
+    extension::{
+        transfer_fee::instruction::initialize_transfer_fee_config, transfer_hook, ExtensionType,
+        StateWithExtensions,
+    },
+    state::Mint,
+};
+use spl_transfer_hook_interface::{
+    get_extra_account_metas_address, instruction::ExecuteInstruction,
+};
+use std::mem::size_of;
+
+const DECIMALS: u8 = 6;
+const TRANSFER_FEE_BPS: u16 = 100;
+const USER_AMOUNT: u64 = 1_000_000_000_000;
+const INIT_AMOUNT: u64 = 1_000_000_000;
+
+/// A transfer hook that accepts every transfer, whitelisted with a `HookConfig`
+fn process_hook(_program_id: &Pubkey, _accounts: &[AccountInfo], _input: &[u8]) -> ProgramResult {
+    Ok(())
+}
+
+/// A Token-2022 mint and the payer's token account holding `USER_AMOUNT` of it
+struct TestMint {
+    key: Pubkey,
+    user_account: Pubkey,
+    hook_accounts: Vec<AccountMeta>,
+}
+
+/// A pool created by `InitializeNoOrderbook` from the payer's token accounts
+struct TestPool {
+    amm: Pubkey,
+    authority: Pubkey,
+    lp_mint: Pubkey,
+    coin_vault: Pubkey,
+    pc_vault: Pubkey,
+    user_lp: Pubkey,
+}
+
+struct TestContext {
+    context: ProgramTestContext,
+    hook_program: Pubkey,
+    pnl_owner: Keypair,
+}
+
+fn program_account(data: &[u8], owner: Pubkey) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data: data.to_vec(),
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// The AMM with its config, whose pnl owner can sign, and a whitelisted transfer hook
+async fn setup() -> TestContext {
+    let program_id = raydium_amm::id();
+    let mut program_test =
+        ProgramTest::new("raydium_amm", program_id, processor!(Processor::process));
+    let hook_program = Pubkey::new_unique();
+    program_test.add_program("test_hook", hook_program, processor!(process_hook));
+
+    let pnl_owner = Keypair::new();
+    let amm_config = AmmConfig {
+        pnl_owner: pnl_owner.pubkey(),
+        create_pool_fee: 0,
+        ..Default::default()
+    };
+    program_test.add_account(
+        Pubkey::find_program_address(&[AMM_CONFIG_SEED], &program_id).0,
+        program_account(bytemuck::bytes_of(&amm_config), program_id),
+    );
+    let (hook_config_key, bump) = hook_config_address(&program_id, &hook_program);
+    let hook_config = HookConfig {
+        hook_program,
+        max_compute_units: MAX_HOOK_COMPUTE_UNITS,
+        bump: bump as u64,
+        ..Default::default()
+    };
+    program_test.add_account(
+        hook_config_key,
+        program_account(bytemuck::bytes_of(&hook_config), program_id),
+    );
+
+    TestContext {
+        context: program_test.start_with_context().await,
+        hook_program,
+        pnl_owner,
+    }
+}
+
+async fn process(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let mut all_instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(1_400_000)];
+    all_instructions.extend_from_slice(instructions);
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        &all_instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|err| err.unwrap())
+}
+
+fn assert_amm_error(result: Result<(), TransactionError>, error: AmmError) {
+    match result {
+        Err(TransactionError::InstructionError(_, InstructionError::Custom(code))) => {
+            assert_eq!(code, error.clone() as u32)
+        }
+        result => panic!("expected {:?}, got {:?}", error, result),
+    }
+}
+
+async fn token_balance(context: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    let account = context
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .unwrap();
+    StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+        .unwrap()
+        .base
+        .amount
+}
+
+async fn amm_info(context: &mut ProgramTestContext, amm: &Pubkey) -> AmmInfo {
+    let account = context
+        .banks_client
+        .get_account(*amm)
+        .await
+        .unwrap()
+        .unwrap();
+    *bytemuck::from_bytes::<AmmInfo>(&account.data[..size_of::<AmmInfo>()])
+}
+
+/// Transfer fee Token-2022 charges on `amount`
+fn transfer_fee(amount: u64) -> u64 {
+    (amount as u128 * TRANSFER_FEE_BPS as u128).div_ceil(10_000) as u64
+}
+
+/// Token account of `mint` for `wallet`, created by the payer
+async fn create_token_account(
+    context: &mut ProgramTestContext,
+    wallet: &Pubkey,
+    mint: &Pubkey,
+) -> Pubkey {
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[create_associated_token_account(
+            &payer,
+            wallet,
+            mint,
+            &spl_token_2022::id(),
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+    get_associated_token_address_with_program_id(wallet, mint, &spl_token_2022::id())
+}
+
+/// Transfer fee mint, with the transfer hook when `hooked`, the payer is its authority
+async fn create_mint(test: &mut TestContext, hooked: bool) -> TestMint {
+    let context = &mut test.context;
+    let mint = Keypair::new();
+    let authority = context.payer.pubkey();
+    let mut extensions = vec![ExtensionType::TransferFeeConfig];
+    if hooked {
+        extensions.push(ExtensionType::TransferHook);
+    }
+    let space = ExtensionType::try_calculate_account_len::<Mint>(&extensions).unwrap();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let mut instructions = vec![
+        system_instruction::create_account(
+            &authority,
+            &mint.pubkey(),
+            rent.minimum_balance(space),
+            space as u64,
+            &spl_token_2022::id(),
+        ),
+        initialize_transfer_fee_config(
+            &spl_token_2022::id(),
+            &mint.pubkey(),
+            Some(&authority),
+            Some(&authority),
+            TRANSFER_FEE_BPS,
+            u64::MAX,
+        )
+        .unwrap(),
+    ];
+    let mut hook_accounts = Vec::new();
+    if hooked {
+        instructions.push(
+            transfer_hook::instruction::initialize(
+                &spl_token_2022::id(),
+                &mint.pubkey(),
+                Some(authority),
+                Some(test.hook_program),
+            )
+            .unwrap(),
+        );
+        // the hook takes no extra accounts
+        let validation = get_extra_account_metas_address(&mint.pubkey(), &test.hook_program);
+        let mut data = vec![0u8; ExtraAccountMetaList::size_of(0).unwrap()];
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &[]).unwrap();
+        context.set_account(
+            &validation,
+            &program_account(&data, test.hook_program).into(),
+        );
+        hook_accounts = vec![
+            AccountMeta::new_readonly(
+                hook_config_address(&raydium_amm::id(), &test.hook_program).0,
+                false,
+            ),
+            AccountMeta::new_readonly(test.hook_program, false),
+            AccountMeta::new_readonly(validation, false),
+        ];
+    }
+    instructions.push(
+        spl_token_2022::instruction::initialize_mint2(
+            &spl_token_2022::id(),
+            &mint.pubkey(),
+            &authority,
+            None,
+            DECIMALS,
+        )
+        .unwrap(),
+    );
+    process(context, &instructions, &[&mint]).await.unwrap();
+
+    let user_account = create_token_account(context, &authority, &mint.pubkey()).await;
+    process(
+        context,
+        &[spl_token_2022::instruction::mint_to(
+            &spl_token_2022::id(),
+            &mint.pubkey(),
+            &user_account,
+            &authority,
+            &[],
+            USER_AMOUNT,
+        )
+        .unwrap()],
+        &[],
+    )
+    .await
+    .unwrap();
+    TestMint {
+        key: mint.pubkey(),
+        user_account,
+        hook_accounts,
+    }
+}
+
+async fn create_pool(context: &mut ProgramTestContext, coin: &TestMint, pc: &TestMint) -> TestPool {
+    let program_id = raydium_amm::id();
+    let pool_seed = Pubkey::new_unique();
+    let associated = |seed: &[u8]| {
+        get_associated_address_and_bump_seed(&program_id, &pool_seed, seed, &program_id).0
+    };
+    let (authority, nonce) = Pubkey::find_program_address(&[AUTHORITY_AMM], &program_id);
+    let user = context.payer.pubkey();
+    let pool = TestPool {
+        amm: associated(AMM_ASSOCIATED_SEED),
+        authority,
+        lp_mint: associated(LP_MINT_ASSOCIATED_SEED),
+        coin_vault: associated(COIN_VAULT_ASSOCIATED_SEED),
+        pc_vault: associated(PC_VAULT_ASSOCIATED_SEED),
+        // the lp mint is created under the pc side token program
+        user_lp: get_associated_token_address_with_program_id(
+            &user,
+            &associated(LP_MINT_ASSOCIATED_SEED),
+            &spl_token_2022::id(),
+        ),
+    };
+    let initialize = instruction::initialize_no_orderbook(
+        &program_id,
+        &pool.amm,
+        &pool.authority,
+        &pool.lp_mint,
+        &coin.key,
+        &pc.key,
+        &pool.coin_vault,
+        &pool.pc_vault,
+        &Pubkey::find_program_address(&[AMM_CONFIG_SEED], &program_id).0,
+        &config_feature::create_pool_fee_address::id(),
+        &pool_seed,
+        &user,
+        &coin.user_account,
+        &pc.user_account,
+        &pool.user_lp,
+        &spl_token_2022::id(),
+        &spl_token_2022::id(),
+        &coin.hook_accounts,
+        &pc.hook_accounts,
+        nonce,
+        0,
+        INIT_AMOUNT,
+        INIT_AMOUNT,
+        false,
+        0,
+    )
+    .unwrap();
+    process(context, &[initialize], &[]).await.unwrap();
+
+    // only the amounts net of the transfer fees arrive in the vaults
+    let net_amount = INIT_AMOUNT - transfer_fee(INIT_AMOUNT);
+    assert_eq!(token_balance(context, &pool.coin_vault).await, net_amount);
+    assert_eq!(token_balance(context, &pool.pc_vault).await, net_amount);
+    assert!(token_balance(context, &pool.user_lp).await > 0);
+    pool
+}
+
+fn withdraw(
+    pool: &TestPool,
+    coin: &TestMint,
+    pc: &TestMint,
+    user: &Pubkey,
+    amount: u64,
+    escrow_sides: u8,
+) -> Instruction {
+    instruction::withdraw_no_orderbook(
+        &raydium_amm::id(),
+        &spl_token_2022::id(),
+        &spl_token_2022::id(),
+        &pool.amm,
+        &pool.authority,
+        &pool.lp_mint,
+        &pool.coin_vault,
+        &pool.pc_vault,
+        &pool.user_lp,
+        &coin.user_account,
+        &pc.user_account,
+        user,
+        &coin.key,
+        &coin.hook_accounts,
+        &pc.key,
+        &pc.hook_accounts,
+        amount,
+        None,
+        None,
+        escrow_sides,
+    )
+    .unwrap()
+}
+
+fn swap_base_in(
+    pool: &TestPool,
+    source: &TestMint,
+    destination: &TestMint,
+    user: &Pubkey,
+    amount_in: u64,
+) -> Instruction {
+    let program_id = raydium_amm::id();
+    instruction::swap_base_in_no_orderbook(
+        &program_id,
+        &spl_token_2022::id(),
+        &spl_token_2022::id(),
+        &pool.amm,
+        &pool.authority,
+        &pool.coin_vault,
+        &pool.pc_vault,
+        &source.user_account,
+        &destination.user_account,
+        user,
+        &source.key,
+        &spl_token_2022::id(),
+        &source.hook_accounts,
+        &destination.key,
+        &spl_token_2022::id(),
+        &destination.hook_accounts,
+        amount_in,
+        1,
+    )
+    .unwrap()
+}
+
+/// A pool without orderbook takes its AMM Account in place of the open orders and market
+fn route_hop(pool: &TestPool) -> SwapRouteHop {
+    SwapRouteHop {
+        token_program_coin: spl_token_2022::id(),
+        token_program_pc: spl_token_2022::id(),
+        amm_pool: pool.amm,
+        amm_authority: pool.authority,
+        amm_open_orders: pool.amm,
+        amm_coin_vault: pool.coin_vault,
+        amm_pc_vault: pool.pc_vault,
+        market_program: pool.amm,
+        market: pool.amm,
+        market_bids: pool.amm,
+        market_asks: pool.amm,
+        market_event_queue: pool.amm,
+        market_coin_vault: pool.amm,
+        market_pc_vault: pool.amm,
+        market_vault_signer: pool.amm,
+    }
+}
+
+/// Test DepositSingle and WithdrawSingle through the hooked and the plain side
+#[tokio::test]
+async fn test_deposit_single_withdraw_single() {
+    let mut test = setup().await;
+    let coin = create_mint(&mut test, true).await;
+    let pc = create_mint(&mut test, false).await;
+    let context = &mut test.context;
+    let pool = create_pool(context, &coin, &pc).await;
+    let program_id = raydium_amm::id();
+    let user = context.payer.pubkey();
+
+    for (side, source, destination, amount_in) in [
+        (0u8, &coin, &pc, 10_000_000u64),
+        (1u8, &pc, &coin, 20_000_000u64),
+    ] {
+        let (source_vault, destination_vault) = if side == 0 {
+            (pool.coin_vault, pool.pc_vault)
+        } else {
+            (pool.pc_vault, pool.coin_vault)
+        };
+        let lp_before = token_balance(context, &pool.user_lp).await;
+        let source_vault_before = token_balance(context, &source_vault).await;
+        let deposit = instruction::deposit_single(
+            &program_id,
+            &spl_token_2022::id(),
+            &spl_token_2022::id(),
+            &pool.amm,
+            &pool.authority,
+            &pool.lp_mint,
+            &pool.coin_vault,
+            &pool.pc_vault,
+            &source.user_account,
+            &pool.user_lp,
+            &user,
+            &source.key,
+            &source.hook_accounts,
+            &destination.key,
+            amount_in,
+            1,
+            side,
+        )
+        .unwrap();
+        process(context, &[deposit], &[]).await.unwrap();
+        assert_eq!(
+            token_balance(context, &source_vault).await - source_vault_before,
+            amount_in - transfer_fee(amount_in)
+        );
+        let lp_amount = token_balance(context, &pool.user_lp).await - lp_before;
+        assert!(lp_amount > 0);
+
+        // the LP tokens minted are withdrawn on the other side
+        let user_before = token_balance(context, &destination.user_account).await;
+        let destination_vault_before = token_balance(context, &destination_vault).await;
+        let withdraw = instruction::withdraw_single(
+            &program_id,
+            &spl_token_2022::id(),
+            &spl_token_2022::id(),
+            &pool.amm,
+            &pool.authority,
+            &pool.lp_mint,
+            &pool.coin_vault,
+            &pool.pc_vault,
+            &pool.user_lp,
+            &destination.user_account,
+            &user,
+            &destination.key,
+            &destination.hook_accounts,
+            &source.key,
+            lp_amount,
+            1,
+            1 - side,
+        )
+        .unwrap();
+        process(context, &[withdraw], &[]).await.unwrap();
+        assert_eq!(token_balance(context, &pool.user_lp).await, lp_before);
+        let amount_out =
+            destination_vault_before - token_balance(context, &destination_vault).await;
+        assert!(amount_out > 0);
+        assert_eq!(
+            token_balance(context, &destination.user_account).await - user_before,
+            amount_out - transfer_fee(amount_out)
+        );
+    }
+}
+
+/// Test a withdrawal into the escrow and its claim
+#[tokio::test]
+async fn test_escrowed_withdraw() {
+    let mut test = setup().await;
+    let coin = create_mint(&mut test, true).await;
+    let pc = create_mint(&mut test, false).await;
+    let context = &mut test.context;
+    let pool = create_pool(context, &coin, &pc).await;
+    let program_id = raydium_amm::id();
+    let user = context.payer.pubkey();
+
+    let coin_before = token_balance(context, &coin.user_account).await;
+    let pc_before = token_balance(context, &pc.user_account).await;
+    let coin_vault_before = token_balance(context, &pool.coin_vault).await;
+    let pc_vault_before = token_balance(context, &pool.pc_vault).await;
+    let lp_amount = token_balance(context, &pool.user_lp).await / 2;
+    process(
+        context,
+        &[withdraw(
+            &pool,
+            &coin,
+            &pc,
+            &user,
+            lp_amount,
+            WITHDRAW_ESCROW_COIN | WITHDRAW_ESCROW_PC,
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+
+    // both sides wait in the escrow, the user received nothing yet
+    let coin_escrow = escrow_token_address(&program_id, &pool.amm, COIN_ESCROW_ASSOCIATED_SEED);
+    let pc_escrow = escrow_token_address(&program_id, &pool.amm, PC_ESCROW_ASSOCIATED_SEED);
+    let coin_out = coin_vault_before - token_balance(context, &pool.coin_vault).await;
+    let pc_out = pc_vault_before - token_balance(context, &pool.pc_vault).await;
+    assert!(coin_out > 0 && pc_out > 0);
+    let coin_escrowed = token_balance(context, &coin_escrow).await;
+    let pc_escrowed = token_balance(context, &pc_escrow).await;
+    assert_eq!(coin_escrowed, coin_out - transfer_fee(coin_out));
+    assert_eq!(pc_escrowed, pc_out - transfer_fee(pc_out));
+    assert_eq!(
+        token_balance(context, &coin.user_account).await,
+        coin_before
+    );
+    assert_eq!(token_balance(context, &pc.user_account).await, pc_before);
+
+    let claim = instruction::claim_escrowed(
+        &program_id,
+        &spl_token_2022::id(),
+        &spl_token_2022::id(),
+        &pool.amm,
+        &pool.authority,
+        &coin.user_account,
+        &pc.user_account,
+        &user,
+        &coin.key,
+        &coin.hook_accounts,
+        &pc.key,
+        &pc.hook_accounts,
+    )
+    .unwrap();
+    process(context, &[claim], &[]).await.unwrap();
+    assert_eq!(token_balance(context, &coin_escrow).await, 0);
+    assert_eq!(token_balance(context, &pc_escrow).await, 0);
+    assert_eq!(
+        token_balance(context, &coin.user_account).await - coin_before,
+        coin_escrowed - transfer_fee(coin_escrowed)
+    );
+    assert_eq!(
+        token_balance(context, &pc.user_account).await - pc_before,
+        pc_escrowed - transfer_fee(pc_escrowed)
+    );
+}
+
+/// Test SwapRoute through two pools, the intermediate hooked mint goes from vault to vault
+#[tokio::test]
+async fn test_swap_route() {
+    let mut test = setup().await;
+    let first = create_mint(&mut test, false).await;
+    let middle = create_mint(&mut test, true).await;
+    let last = create_mint(&mut test, false).await;
+    let context = &mut test.context;
+    let first_pool = create_pool(context, &first, &middle).await;
+    let last_pool = create_pool(context, &middle, &last).await;
+    let user = context.payer.pubkey();
+
+    let first_before = token_balance(context, &first.user_account).await;
+    let middle_before = token_balance(context, &middle.user_account).await;
+    let last_before = token_balance(context, &last.user_account).await;
+    let first_pool_middle_before = token_balance(context, &first_pool.pc_vault).await;
+    let last_pool_middle_before = token_balance(context, &last_pool.coin_vault).await;
+    let amount_in = 10_000_000;
+    let transfers: [(Pubkey, Pubkey, &[AccountMeta]); 3] = [
+        (
+            first.key,
+            spl_token_2022::id(),
+            first.hook_accounts.as_slice(),
+        ),
+        (
+            middle.key,
+            spl_token_2022::id(),
+            middle.hook_accounts.as_slice(),
+        ),
+        (
+            last.key,
+            spl_token_2022::id(),
+            last.hook_accounts.as_slice(),
+        ),
+    ];
+    let route = instruction::swap_route(
+        &raydium_amm::id(),
+        &[route_hop(&first_pool), route_hop(&last_pool)],
+        &first.user_account,
+        &last.user_account,
+        &user,
+        &transfers,
+        amount_in,
+        1,
+    )
+    .unwrap();
+    process(context, &[route], &[]).await.unwrap();
+
+    assert_eq!(
+        first_before - token_balance(context, &first.user_account).await,
+        amount_in
+    );
+    assert!(token_balance(context, &last.user_account).await > last_before);
+    // the user never holds the intermediate mint
+    assert_eq!(
+        token_balance(context, &middle.user_account).await,
+        middle_before
+    );
+    let middle_out = first_pool_middle_before - token_balance(context, &first_pool.pc_vault).await;
+    assert!(middle_out > 0);
+    assert_eq!(
+        token_balance(context, &last_pool.coin_vault).await - last_pool_middle_before,
+        middle_out - transfer_fee(middle_out)
+    );
+}
+
+/// Test the fee split of a swap with a referrer and the protocol share taken by WithdrawPnl
+#[tokio::test]
+async fn test_fee_split() {
+    let mut test = setup().await;
+    let coin = create_mint(&mut test, true).await;
+    let pc = create_mint(&mut test, false).await;
+    let context = &mut test.context;
+    let pool = create_pool(context, &coin, &pc).await;
+    let program_id = raydium_amm::id();
+    let user = context.payer.pubkey();
+
+    // SetParams needs the admin, the split is written into the extension of the pool directly
+    let mut amm_account = context
+        .banks_client
+        .get_account(pool.amm)
+        .await
+        .unwrap()
+        .unwrap();
+    let fee_split = FeeSplit {
+        protocol_share: 5000,
+        referrer_share: 2000,
+    };
+    let (amm, amm_extension) = amm_account.data.split_at_mut(size_of::<AmmInfo>());
+    bytemuck::from_bytes_mut::<AmmInfo>(amm)
+        .set_fee_split(bytemuck::from_bytes_mut(amm_extension), fee_split);
+    context.set_account(&pool.amm, &amm_account.into());
+
+    let referrer = Pubkey::new_unique();
+    let referrer_account = create_token_account(context, &referrer, &pc.key).await;
+    let amm = amm_info(context, &pool.amm).await;
+    let fees = amm.fees;
+    let amount_in = 10_000_000;
+    let swap_amount_in = amount_in - transfer_fee(amount_in);
+    let swap_fee = (swap_amount_in as u128 * fees.swap_fee_numerator as u128)
+        .div_ceil(fees.swap_fee_denominator as u128) as u64;
+    let (_, protocol_fee, referrer_fee) = fee_split.shares(swap_fee, true);
+    assert!(protocol_fee > 0 && referrer_fee > 0);
+
+    // the referrer is paid from the pc vault, the pc mint has no hook
+    let mut swap = swap_base_in(&pool, &pc, &coin, &user, amount_in);
+    swap.accounts
+        .push(AccountMeta::new(referrer_account, false));
+    swap.accounts.push(AccountMeta::new_readonly(pc.key, false));
+    process(context, &[swap], &[]).await.unwrap();
+    assert_eq!(
+        token_balance(context, &referrer_account).await,
+        referrer_fee - transfer_fee(referrer_fee)
+    );
+    let amm = amm_info(context, &pool.amm).await;
+    assert_eq!({ amm.state_data.need_take_pnl_pc }, protocol_fee);
+    assert_eq!({ amm.state_data.need_take_pnl_coin }, 0);
+
+    // the pnl owner of the config takes the protocol share, the AMM Account stands in for
+    // the open orders, target orders and market accounts of a pool without orderbook
+    let pnl_owner = test.pnl_owner.pubkey();
+    let pnl_coin = create_token_account(context, &pnl_owner, &coin.key).await;
+    let pnl_pc = create_token_account(context, &pnl_owner, &pc.key).await;
+    let withdraw_pnl = instruction::withdrawpnl_checked(
+        &program_id,
+        &pool.amm,
+        &Pubkey::find_program_address(&[AMM_CONFIG_SEED], &program_id).0,
+        &pool.authority,
+        &pool.amm,
+        &pool.coin_vault,
+        &pool.pc_vault,
+        &pnl_coin,
+        &pnl_pc,
+        &pnl_owner,
+        &pool.amm,
+        &pool.amm,
+        &pool.amm,
+        &pool.amm,
+        &pool.amm,
+        &pool.amm,
+        &pool.amm,
+        &coin.key,
+        &pc.key,
+        &spl_token_2022::id(),
+        &spl_token_2022::id(),
+        &coin.hook_accounts,
+        &pc.hook_accounts,
+    )
+    .unwrap();
+    process(context, &[withdraw_pnl], &[&test.pnl_owner])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(context, &pnl_pc).await,
+        protocol_fee - transfer_fee(protocol_fee)
+    );
+    let amm = amm_info(context, &pool.amm).await;
+    assert_eq!({ amm.state_data.need_take_pnl_pc }, 0);
+}
+
+/// Test that a changed transfer hook program stops swaps until PauseOnHookChange makes
+/// the pool withdraw only
+#[tokio::test]
+async fn test_pause_on_hook_change() {
+    let mut test = setup().await;
+    let mut coin = create_mint(&mut test, true).await;
+    let pc = create_mint(&mut test, false).await;
+    let context = &mut test.context;
+    let pool = create_pool(context, &coin, &pc).await;
+    let program_id = raydium_amm::id();
+    let user = context.payer.pubkey();
+
+    process(
+        context,
+        &[swap_base_in(&pool, &coin, &pc, &user, 10_000_000)],
+        &[],
+    )
+    .await
+    .unwrap();
+
+    // the hook authority removes the hook pinned by the pool
+    process(
+        context,
+        &[
+            transfer_hook::instruction::update(&spl_token_2022::id(), &coin.key, &user, &[], None)
+                .unwrap(),
+        ],
+        &[],
+    )
+    .await
+    .unwrap();
+    coin.hook_accounts.clear();
+    assert_amm_error(
+        process(
+            context,
+            &[swap_base_in(&pool, &coin, &pc, &user, 20_000_000)],
+            &[],
+        )
+        .await,
+        AmmError::HookProgramChanged,
+    );
+    // a deposit of the plain side alone still checks the hook of the coin mint
+    let mut deposit = instruction::deposit_single(
+        &program_id,
+        &spl_token_2022::id(),
+        &spl_token_2022::id(),
+        &pool.amm,
+        &pool.authority,
+        &pool.lp_mint,
+        &pool.coin_vault,
+        &pool.pc_vault,
+        &pc.user_account,
+        &pool.user_lp,
+        &user,
+        &pc.key,
+        &pc.hook_accounts,
+        &coin.key,
+        10_000_000,
+        1,
+        1,
+    )
+    .unwrap();
+    assert_amm_error(
+        process(context, &[deposit.clone()], &[]).await,
+        AmmError::HookProgramChanged,
+    );
+    // and fails without it
+    deposit.accounts.remove(10);
+    assert_amm_error(
+        process(context, &[deposit], &[]).await,
+        AmmError::InvalidTransferHookAccounts,
+    );
+    // the failed instructions leave the status as it was
+    let amm = amm_info(context, &pool.amm).await;
+    assert_eq!({ amm.status }, AmmStatus::SwapOnly.into_u64());
+
+    let pause =
+        instruction::pause_on_hook_change(&program_id, &pool.amm, &coin.key, &pc.key).unwrap();
+    process(context, &[pause], &[]).await.unwrap();
+    let amm = amm_info(context, &pool.amm).await;
+    assert_eq!({ amm.status }, AmmStatus::HookProgramChanged.into_u64());
+    assert_amm_error(
+        process(
+            context,
+            &[swap_base_in(&pool, &pc, &coin, &user, 30_000_000)],
+            &[],
+        )
+        .await,
+        AmmError::InvalidStatus,
+    );
+
+    // the LPs still withdraw both sides
+    let coin_before = token_balance(context, &coin.user_account).await;
+    let pc_before = token_balance(context, &pc.user_account).await;
+    let lp_amount = token_balance(context, &pool.user_lp).await;
+    process(
+        context,
+        &[withdraw(&pool, &coin, &pc, &user, lp_amount, 0)],
+        &[],
+    )
+    .await
+    .unwrap();
+    assert_eq!(token_balance(context, &pool.user_lp).await, 0);
+    assert!(token_balance(context, &coin.user_account).await > coin_before);
+    assert!(token_balance(context, &pc.user_account).await > pc_before);
+}
@@ -0,0 +1,93 @@
+//! Error types
+
+use num_derive::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    msg,
+    program_error::{PrintProgramError, ProgramError},
+};
+use thiserror::Error;
+
+/// Errors that may be returned by the token wrapper program.
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum WrapperError {
+    // 0
+    /// The wrapper account is already initialized.
+    #[error("AlreadyInUse")]
+    AlreadyInUse,
+    /// The program address provided doesn't match value generated by the program.
+    #[error("InvalidProgramAddress")]
+    InvalidProgramAddress,
+    /// The token program provided is not the expected one.
+    #[error("InvalidTokenProgram")]
+    InvalidTokenProgram,
+    /// The underlying mint provided doesn't match the one in the wrapper.
+    #[error("InvalidUnderlyingMint")]
+    InvalidUnderlyingMint,
+    /// The wrapper mint provided doesn't match the one in the wrapper.
+    #[error("InvalidWrapperMint")]
+    InvalidWrapperMint,
+
+    // 5
+    /// The escrow provided doesn't match the one in the wrapper.
+    #[error("InvalidEscrow")]
+    InvalidEscrow,
+    /// The owner of the user token accounts must sign.
+    #[error("InvalidSignAccount")]
+    InvalidSignAccount,
+    /// The amount is zero or nothing reached the escrow.
+    #[error("InvalidInput")]
+    InvalidInput,
+    /// The underlying mint has an extension that lets its issuer move the escrowed tokens.
+    #[error("Mint extension is forbidden for a wrapped mint")]
+    ForbiddenMintExtension,
+    /// The wrapper supply would exceed the escrowed amount.
+    #[error("Wrapper supply is not backed by the escrow")]
+    InsufficientBacking,
+
+    // 10
+    /// The underlying mint can freeze the escrow, through its freeze authority or a
+    /// frozen default account state.
+    #[error("Underlying mint can freeze the escrow")]
+    FreezableMint,
+}
+
+impl From<WrapperError> for ProgramError {
+    fn from(e: WrapperError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+impl<T> DecodeError<T> for WrapperError {
+    fn type_of() -> &'static str {
+        "Wrapper Error"
+    }
+}
+
+impl PrintProgramError for WrapperError {
+    fn print<E>(&self)
+    where
+        E: 'static
+            + std::error::Error
+            + DecodeError<E>
+            + PrintProgramError
+            + num_traits::FromPrimitive,
+    {
+        match self {
+            WrapperError::AlreadyInUse => msg!("Error: AlreadyInUse"),
+            WrapperError::InvalidProgramAddress => msg!("Error: InvalidProgramAddress"),
+            WrapperError::InvalidTokenProgram => msg!("Error: InvalidTokenProgram"),
+            WrapperError::InvalidUnderlyingMint => msg!("Error: InvalidUnderlyingMint"),
+            WrapperError::InvalidWrapperMint => msg!("Error: InvalidWrapperMint"),
+            WrapperError::InvalidEscrow => msg!("Error: InvalidEscrow"),
+            WrapperError::InvalidSignAccount => msg!("Error: Invalid Sign Account"),
+            WrapperError::InvalidInput => msg!("Error: InvalidInput"),
+            WrapperError::ForbiddenMintExtension => {
+                msg!("Error: Mint extension is forbidden for a wrapped mint")
+            }
+            WrapperError::InsufficientBacking => {
+                msg!("Error: Wrapper supply is not backed by the escrow")
+            }
+            WrapperError::FreezableMint => msg!("Error: Underlying mint can freeze the escrow"),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! Instruction types
+
+#![allow(clippy::too_many_arguments)]
+
+use crate::state::{escrow_address, wrapper_address, wrapper_mint_address};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+};
+use std::convert::TryInto;
+
+/// Instructions supported by the token wrapper program.
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum WrapperInstruction {
+    ///   Creates the wrapper of a Token-2022 mint: the `Wrapper` account, the SPL Token
+    ///   wrapper mint with the decimals of the underlying mint and the escrow.
+    ///
+    ///   0. `[writable, signer]` Payer Account
+    ///   1. `[writable]` Wrapper Account, derived from `find_program_address(&[WRAPPER_SEED, underlying_mint])`
+    ///   2. `[]` Underlying Token-2022 mint Account
+    ///   3. `[writable]` Wrapper mint Account, derived from `find_program_address(&[WRAPPER_MINT_SEED, underlying_mint])`
+    ///   4. `[writable]` Escrow Account, derived from `find_program_address(&[ESCROW_SEED, underlying_mint])`
+    ///   5. `[]` Spl Token-2022 program id
+    ///   6. `[]` Spl Token program id
+    ///   7. `[]` Sys program id
+    InitializeWrapper,
+
+    ///   Moves `amount` underlying tokens into the escrow and mints the wrapper tokens
+    ///   that arrived, net of the Token-2022 transfer fee. The transfer runs the
+    ///   transfer hook of the underlying mint.
+    ///
+    ///   0. `[]` Wrapper Account
+    ///   1. `[]` Underlying Token-2022 mint Account
+    ///   2. `[writable]` Wrapper mint Account
+    ///   3. `[writable]` Escrow Account
+    ///   4. `[writable]` User underlying token Account
+    ///   5. `[writable]` User wrapper token Account
+    ///   6. `[signer]` User wallet Account, owner of both user token Accounts
+    ///   7. `[]` Spl Token-2022 program id
+    ///   8. `[]` Spl Token program id
+    ///   9..N. `[]` Transfer hook accounts of the underlying mint, if it has a hook: the extra
+    ///          accounts resolved from the ExtraAccountMetaList, the hook program id and the
+    ///          ExtraAccountMetaList Account
+    Wrap {
+        /// underlying tokens sent to the escrow
+        amount: u64,
+    },
+
+    ///   Burns `amount` wrapper tokens and releases as many underlying tokens from the
+    ///   escrow, the user receives them net of the Token-2022 transfer fee. The transfer
+    ///   runs the transfer hook of the underlying mint.
+    ///
+    ///   Accounts as `Wrap`
+    Unwrap {
+        /// wrapper tokens burned
+        amount: u64,
+    },
+}
+
+impl WrapperInstruction {
+    /// Unpacks a byte buffer into a [WrapperInstruction](enum.WrapperInstruction.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        Ok(match tag {
+            0 => Self::InitializeWrapper,
+            1 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::Wrap { amount }
+            }
+            2 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::Unwrap { amount }
+            }
+            _ => return Err(ProgramError::InvalidInstructionData),
+        })
+    }
+
+    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+        if input.len() >= 8 {
+            let (amount, rest) = input.split_at(8);
+            let amount = amount
+                .get(..8)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u64::from_le_bytes)
+                .ok_or(ProgramError::InvalidInstructionData)?;
+            Ok((amount, rest))
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    /// Packs a [WrapperInstruction](enum.WrapperInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(9);
+        match *self {
+            Self::InitializeWrapper => {
+                buf.push(0);
+            }
+            Self::Wrap { amount } => {
+                buf.push(1);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::Unwrap { amount } => {
+                buf.push(2);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+        }
+        buf
+    }
+}
+
+/// Creates an 'initialize_wrapper' instruction.
+pub fn initialize_wrapper(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    underlying_mint: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(wrapper_address(program_id, underlying_mint).0, false),
+        AccountMeta::new_readonly(*underlying_mint, false),
+        AccountMeta::new(wrapper_mint_address(program_id, underlying_mint).0, false),
+        AccountMeta::new(escrow_address(program_id, underlying_mint).0, false),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: WrapperInstruction::InitializeWrapper.pack(),
+    }
+}
+
+/// Creates a 'wrap' instruction, `hook_accounts` as resolved for the transfer
+/// from the user to the escrow.
+pub fn wrap(
+    program_id: &Pubkey,
+    underlying_mint: &Pubkey,
+    user_underlying_token: &Pubkey,
+    user_wrapper_token: &Pubkey,
+    user_owner: &Pubkey,
+    hook_accounts: &[AccountMeta],
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: wrapper_accounts(
+            program_id,
+            underlying_mint,
+            user_underlying_token,
+            user_wrapper_token,
+            user_owner,
+            hook_accounts,
+        ),
+        data: WrapperInstruction::Wrap { amount }.pack(),
+    }
+}
+
+/// Creates an 'unwrap' instruction, `hook_accounts` as resolved for the transfer
+/// from the escrow to the user.
+pub fn unwrap(
+    program_id: &Pubkey,
+    underlying_mint: &Pubkey,
+    user_underlying_token: &Pubkey,
+    user_wrapper_token: &Pubkey,
+    user_owner: &Pubkey,
+    hook_accounts: &[AccountMeta],
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: wrapper_accounts(
+            program_id,
+            underlying_mint,
+            user_underlying_token,
+            user_wrapper_token,
+            user_owner,
+            hook_accounts,
+        ),
+        data: WrapperInstruction::Unwrap { amount }.pack(),
+    }
+}
+
+fn wrapper_accounts(
+    program_id: &Pubkey,
+    underlying_mint: &Pubkey,
+    user_underlying_token: &Pubkey,
+    user_wrapper_token: &Pubkey,
+    user_owner: &Pubkey,
+    hook_accounts: &[AccountMeta],
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(wrapper_address(program_id, underlying_mint).0, false),
+        AccountMeta::new_readonly(*underlying_mint, false),
+        AccountMeta::new(wrapper_mint_address(program_id, underlying_mint).0, false),
+        AccountMeta::new(escrow_address(program_id, underlying_mint).0, false),
+        AccountMeta::new(*user_underlying_token, false),
+        AccountMeta::new(*user_wrapper_token, false),
+        AccountMeta::new_readonly(*user_owner, true),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    accounts.extend_from_slice(hook_accounts);
+    accounts
+}
@@ -0,0 +1,17 @@
+//! 1:1 SPL Token wrapper of a Token-2022 mint, so an OpenBook market can list
+//! a Token-2022 asset the DEX can't hold, such as a mint with a transfer hook.
+//! The Token-2022 asset is escrowed by the program and the wrapper mint supply
+//! never exceeds the escrow balance.
+
+mod entrypoint;
+pub mod error;
+pub mod instruction;
+pub mod processor;
+pub mod state;
+#[cfg(test)]
+mod tests;
+
+// Export current solana-sdk types for downstream users who may also be building with a different solana-sdk version
+pub use solana_program;
+
+solana_program::declare_id!("CjsYdnufdpSCi92TE3WfNiEDmR6KjJ58aHoyQLn3vK8p");
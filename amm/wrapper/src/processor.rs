@@ -0,0 +1,393 @@
+//! Program state processor
+
+use crate::{
+    error::WrapperError,
+    instruction::WrapperInstruction,
+    state::{
+        escrow_address, wrapper_address, wrapper_mint_address, Wrapper, ESCROW_SEED,
+        WRAPPER_MINT_SEED, WRAPPER_SEED,
+    },
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
+};
+use spl_token_2022::{
+    extension::{
+        default_account_state::DefaultAccountState, BaseStateWithExtensions, ExtensionType,
+        StateWithExtensions,
+    },
+    state::{Account as Token2022Account, AccountState, Mint as Token2022Mint},
+};
+
+/// Extensions of an underlying mint that let its issuer move or lock the escrowed
+/// tokens, which would leave the wrapper supply unbacked
+const FORBIDDEN_MINT_EXTENSIONS: [ExtensionType; 2] = [
+    ExtensionType::PermanentDelegate,
+    ExtensionType::NonTransferable,
+];
+
+/// Program state handler.
+pub struct Processor {}
+impl Processor {
+    /// Create a program derived account owned by `owner`, also when someone sent
+    /// lamports to its address beforehand
+    fn create_pda_account<'a>(
+        payer_info: &AccountInfo<'a>,
+        new_account_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        owner: &Pubkey,
+        data_size: usize,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        if new_account_info.owner != system_program_info.key || new_account_info.data_len() != 0 {
+            return Err(WrapperError::AlreadyInUse.into());
+        }
+        let required_lamports = Rent::get()?
+            .minimum_balance(data_size)
+            .max(1)
+            .saturating_sub(new_account_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(
+                    payer_info.key,
+                    new_account_info.key,
+                    required_lamports,
+                ),
+                &[
+                    payer_info.clone(),
+                    new_account_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(new_account_info.key, data_size as u64),
+            &[new_account_info.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(new_account_info.key, owner),
+            &[new_account_info.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        )
+    }
+
+    /// Escrowed amount, as credited to the escrow net of Token-2022 transfer fees
+    fn escrow_amount(escrow_info: &AccountInfo) -> Result<u64, ProgramError> {
+        let data = escrow_info.try_borrow_data()?;
+        Ok(StateWithExtensions::<Token2022Account>::unpack(&data)?
+            .base
+            .amount)
+    }
+
+    /// The wrapper supply must never exceed what the escrow holds
+    fn check_backing(escrow_info: &AccountInfo, wrapper_mint_info: &AccountInfo) -> ProgramResult {
+        let escrowed = Self::escrow_amount(escrow_info)?;
+        let supply = spl_token::state::Mint::unpack(&wrapper_mint_info.try_borrow_data()?)?.supply;
+        if supply > escrowed {
+            msg!("wrapper supply {} exceeds escrow {}", supply, escrowed);
+            return Err(WrapperError::InsufficientBacking.into());
+        }
+        Ok(())
+    }
+
+    /// Check the underlying mint can neither move nor lock the escrowed tokens and
+    /// return its decimals
+    pub fn check_underlying_mint(data: &[u8]) -> Result<u8, ProgramError> {
+        let mint = StateWithExtensions::<Token2022Mint>::unpack(data)?;
+        // a frozen escrow can't release the underlying tokens on unwrap
+        if mint.base.freeze_authority.is_some() {
+            msg!("underlying mint has a freeze authority");
+            return Err(WrapperError::FreezableMint.into());
+        }
+        // an extension this Token-2022 version can't decode, like Pausable, is refused
+        // since it may halt the escrow transfers
+        let extensions = mint
+            .get_extension_types()
+            .map_err(|_| WrapperError::ForbiddenMintExtension)?;
+        if let Some(extension) = FORBIDDEN_MINT_EXTENSIONS
+            .iter()
+            .find(|extension| extensions.contains(extension))
+        {
+            msg!("underlying mint has forbidden extension {:?}", extension);
+            return Err(WrapperError::ForbiddenMintExtension.into());
+        }
+        if let Ok(default_state) = mint.get_extension::<DefaultAccountState>() {
+            if default_state.state == AccountState::Frozen as u8 {
+                msg!("underlying mint freezes new accounts by default");
+                return Err(WrapperError::FreezableMint.into());
+            }
+        }
+        Ok(mint.base.decimals)
+    }
+
+    /// Processes an [InitializeWrapper](enum.WrapperInstruction.html).
+    pub fn process_initialize_wrapper(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer_info = next_account_info(account_info_iter)?;
+        let wrapper_info = next_account_info(account_info_iter)?;
+        let underlying_mint_info = next_account_info(account_info_iter)?;
+        let wrapper_mint_info = next_account_info(account_info_iter)?;
+        let escrow_info = next_account_info(account_info_iter)?;
+        let token_2022_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !payer_info.is_signer {
+            return Err(WrapperError::InvalidSignAccount.into());
+        }
+        if *token_2022_program_info.key != spl_token_2022::id()
+            || *token_program_info.key != spl_token::id()
+        {
+            return Err(WrapperError::InvalidTokenProgram.into());
+        }
+        if *system_program_info.key != solana_program::system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // only Token-2022 mints are wrapped, an SPL Token mint can be listed as is
+        if underlying_mint_info.owner != token_2022_program_info.key {
+            return Err(WrapperError::InvalidUnderlyingMint.into());
+        }
+        let decimals = Self::check_underlying_mint(&underlying_mint_info.try_borrow_data()?)?;
+
+        let underlying_mint = underlying_mint_info.key;
+        let (expect_wrapper, bump) = wrapper_address(program_id, underlying_mint);
+        let (expect_wrapper_mint, wrapper_mint_bump) =
+            wrapper_mint_address(program_id, underlying_mint);
+        let (expect_escrow, escrow_bump) = escrow_address(program_id, underlying_mint);
+        if *wrapper_info.key != expect_wrapper
+            || *wrapper_mint_info.key != expect_wrapper_mint
+            || *escrow_info.key != expect_escrow
+        {
+            return Err(WrapperError::InvalidProgramAddress.into());
+        }
+
+        // wrapper account
+        Self::create_pda_account(
+            payer_info,
+            wrapper_info,
+            system_program_info,
+            program_id,
+            Wrapper::LEN,
+            &[WRAPPER_SEED, underlying_mint.as_ref(), &[bump]],
+        )?;
+        // SPL Token wrapper mint, minted by the wrapper account only and never frozen
+        Self::create_pda_account(
+            payer_info,
+            wrapper_mint_info,
+            system_program_info,
+            token_program_info.key,
+            spl_token::state::Mint::LEN,
+            &[
+                WRAPPER_MINT_SEED,
+                underlying_mint.as_ref(),
+                &[wrapper_mint_bump],
+            ],
+        )?;
+        invoke(
+            &spl_token::instruction::initialize_mint2(
+                token_program_info.key,
+                wrapper_mint_info.key,
+                wrapper_info.key,
+                None,
+                decimals,
+            )?,
+            &[wrapper_mint_info.clone(), token_program_info.clone()],
+        )?;
+        // Token-2022 escrow, with the account extensions the underlying mint requires
+        let escrow_len = {
+            let data = underlying_mint_info.try_borrow_data()?;
+            let mint = StateWithExtensions::<Token2022Mint>::unpack(&data)?;
+            let account_extensions =
+                ExtensionType::get_required_init_account_extensions(&mint.get_extension_types()?);
+            ExtensionType::try_calculate_account_len::<Token2022Account>(&account_extensions)?
+        };
+        Self::create_pda_account(
+            payer_info,
+            escrow_info,
+            system_program_info,
+            token_2022_program_info.key,
+            escrow_len,
+            &[ESCROW_SEED, underlying_mint.as_ref(), &[escrow_bump]],
+        )?;
+        invoke(
+            &spl_token_2022::instruction::initialize_account3(
+                token_2022_program_info.key,
+                escrow_info.key,
+                underlying_mint_info.key,
+                wrapper_info.key,
+            )?,
+            &[
+                escrow_info.clone(),
+                underlying_mint_info.clone(),
+                token_2022_program_info.clone(),
+            ],
+        )?;
+
+        let mut wrapper = Wrapper::load_mut_checked(wrapper_info, program_id)?;
+        wrapper.underlying_mint = *underlying_mint;
+        wrapper.wrapper_mint = *wrapper_mint_info.key;
+        wrapper.escrow = *escrow_info.key;
+        wrapper.decimals = decimals as u64;
+        wrapper.bump = bump as u64;
+        msg!(
+            "wrapper mint {} for {}",
+            wrapper_mint_info.key,
+            underlying_mint
+        );
+        Ok(())
+    }
+
+    /// Processes a [Wrap](enum.WrapperInstruction.html) or an
+    /// [Unwrap](enum.WrapperInstruction.html).
+    pub fn process_wrap(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        unwrap: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let wrapper_info = next_account_info(account_info_iter)?;
+        let underlying_mint_info = next_account_info(account_info_iter)?;
+        let wrapper_mint_info = next_account_info(account_info_iter)?;
+        let escrow_info = next_account_info(account_info_iter)?;
+        let user_underlying_info = next_account_info(account_info_iter)?;
+        let user_wrapper_info = next_account_info(account_info_iter)?;
+        let user_owner_info = next_account_info(account_info_iter)?;
+        let token_2022_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        // transfer hook accounts of the underlying mint
+        let hook_accounts = account_info_iter.as_slice();
+
+        if !user_owner_info.is_signer {
+            return Err(WrapperError::InvalidSignAccount.into());
+        }
+        if amount == 0 {
+            return Err(WrapperError::InvalidInput.into());
+        }
+        if *token_2022_program_info.key != spl_token_2022::id()
+            || *token_program_info.key != spl_token::id()
+        {
+            return Err(WrapperError::InvalidTokenProgram.into());
+        }
+        let wrapper = *Wrapper::load_checked(wrapper_info, program_id)?;
+        if *underlying_mint_info.key != wrapper.underlying_mint {
+            return Err(WrapperError::InvalidUnderlyingMint.into());
+        }
+        if *wrapper_mint_info.key != wrapper.wrapper_mint {
+            return Err(WrapperError::InvalidWrapperMint.into());
+        }
+        if *escrow_info.key != wrapper.escrow {
+            return Err(WrapperError::InvalidEscrow.into());
+        }
+        let underlying_mint = wrapper.underlying_mint;
+        let wrapper_seeds: &[&[u8]] = &[
+            WRAPPER_SEED,
+            underlying_mint.as_ref(),
+            &[wrapper.bump as u8],
+        ];
+        let decimals = wrapper.decimals as u8;
+
+        if unwrap {
+            // burn first, so the escrow releases no more than the supply it backed
+            invoke(
+                &spl_token::instruction::burn_checked(
+                    token_program_info.key,
+                    user_wrapper_info.key,
+                    wrapper_mint_info.key,
+                    user_owner_info.key,
+                    &[],
+                    amount,
+                    decimals,
+                )?,
+                &[
+                    user_wrapper_info.clone(),
+                    wrapper_mint_info.clone(),
+                    user_owner_info.clone(),
+                    token_program_info.clone(),
+                ],
+            )?;
+            // transfer_checked resolves and calls the transfer hook from `hook_accounts`
+            spl_token_2022::onchain::invoke_transfer_checked(
+                token_2022_program_info.key,
+                escrow_info.clone(),
+                underlying_mint_info.clone(),
+                user_underlying_info.clone(),
+                wrapper_info.clone(),
+                hook_accounts,
+                amount,
+                decimals,
+                &[wrapper_seeds],
+            )?;
+            msg!("unwrap {}", amount);
+        } else {
+            let escrowed_before = Self::escrow_amount(escrow_info)?;
+            spl_token_2022::onchain::invoke_transfer_checked(
+                token_2022_program_info.key,
+                user_underlying_info.clone(),
+                underlying_mint_info.clone(),
+                escrow_info.clone(),
+                user_owner_info.clone(),
+                hook_accounts,
+                amount,
+                decimals,
+                &[],
+            )?;
+            // only what reached the escrow is wrapped, the transfer fee is withheld
+            let received = Self::escrow_amount(escrow_info)?
+                .checked_sub(escrowed_before)
+                .ok_or(WrapperError::InsufficientBacking)?;
+            if received == 0 {
+                return Err(WrapperError::InvalidInput.into());
+            }
+            invoke_signed(
+                &spl_token::instruction::mint_to_checked(
+                    token_program_info.key,
+                    wrapper_mint_info.key,
+                    user_wrapper_info.key,
+                    wrapper_info.key,
+                    &[],
+                    received,
+                    decimals,
+                )?,
+                &[
+                    wrapper_mint_info.clone(),
+                    user_wrapper_info.clone(),
+                    wrapper_info.clone(),
+                    token_program_info.clone(),
+                ],
+                &[wrapper_seeds],
+            )?;
+            msg!("wrap {}, received {}", amount, received);
+        }
+        Self::check_backing(escrow_info, wrapper_mint_info)
+    }
+
+    /// Processes an [Instruction](enum.Instruction.html).
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+        let instruction = WrapperInstruction::unpack(input)?;
+        match instruction {
+            WrapperInstruction::InitializeWrapper => {
+                Self::process_initialize_wrapper(program_id, accounts)
+            }
+            WrapperInstruction::Wrap { amount } => {
+                Self::process_wrap(program_id, accounts, amount, false)
+            }
+            WrapperInstruction::Unwrap { amount } => {
+                Self::process_wrap(program_id, accounts, amount, true)
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! State transition types
+
+use crate::error::WrapperError;
+use bytemuck::{from_bytes, from_bytes_mut, Pod, Zeroable};
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+use std::{
+    cell::{Ref, RefMut},
+    mem::size_of,
+};
+
+/// Seed of the `Wrapper` account, which is also the mint authority of the wrapper
+/// mint and the owner of the escrow
+pub const WRAPPER_SEED: &[u8] = b"wrapper";
+/// Seed of the SPL Token wrapper mint
+pub const WRAPPER_MINT_SEED: &[u8] = b"wrapper_mint";
+/// Seed of the Token-2022 escrow of the underlying tokens
+pub const ESCROW_SEED: &[u8] = b"escrow";
+
+/// Address of the `Wrapper` account of an underlying mint
+pub fn wrapper_address(program_id: &Pubkey, underlying_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[WRAPPER_SEED, underlying_mint.as_ref()], program_id)
+}
+
+/// Address of the SPL Token wrapper mint of an underlying mint
+pub fn wrapper_mint_address(program_id: &Pubkey, underlying_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[WRAPPER_MINT_SEED, underlying_mint.as_ref()], program_id)
+}
+
+/// Address of the escrow of an underlying mint
+pub fn escrow_address(program_id: &Pubkey, underlying_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ESCROW_SEED, underlying_mint.as_ref()], program_id)
+}
+
+/// Links a Token-2022 mint to its SPL Token wrapper mint, one account per underlying
+/// mint derived from `find_program_address(&[WRAPPER_SEED, underlying_mint])`
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Wrapper {
+    /// Token-2022 mint escrowed by the wrapper
+    pub underlying_mint: Pubkey,
+    /// SPL Token mint issued 1:1 against the escrow
+    pub wrapper_mint: Pubkey,
+    /// Token-2022 account holding the underlying tokens, owned by the `Wrapper` account
+    pub escrow: Pubkey,
+    /// decimals of both mints
+    pub decimals: u64,
+    /// bump seed of the account address
+    pub bump: u64,
+    /// padding
+    pub padding: [u64; 8],
+}
+unsafe impl Zeroable for Wrapper {}
+unsafe impl Pod for Wrapper {}
+
+impl Wrapper {
+    pub const LEN: usize = size_of::<Self>();
+
+    /// load_mut_checked
+    #[inline]
+    pub fn load_mut_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<RefMut<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if account.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(RefMut::map(account.try_borrow_mut_data()?, |data| {
+            from_bytes_mut(data)
+        }))
+    }
+
+    /// load_checked, also rejects an uninitialized wrapper
+    #[inline]
+    pub fn load_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Ref<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if account.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let wrapper: Ref<Self> = Ref::map(account.try_borrow_data()?, |data| from_bytes(data));
+        if wrapper.underlying_mint == Pubkey::default() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        // the stored bump pins the account to its seeds
+        let underlying_mint = wrapper.underlying_mint;
+        let address = Pubkey::create_program_address(
+            &[
+                WRAPPER_SEED,
+                underlying_mint.as_ref(),
+                &[wrapper.bump as u8],
+            ],
+            program_id,
+        )
+        .map_err(|_| WrapperError::InvalidProgramAddress)?;
+        if address != *account.key {
+            return Err(WrapperError::InvalidProgramAddress.into());
+        }
+        Ok(wrapper)
+    }
+}
@@ -0,0 +1,185 @@
+#[cfg(test)]
+mod tests {
+    use solana_program::{
+        program_error::ProgramError, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+    };
+    use spl_token_2022::{
+        extension::{
+            default_account_state::DefaultAccountState, mint_close_authority::MintCloseAuthority,
+            permanent_delegate::PermanentDelegate, transfer_fee::TransferFeeConfig,
+            BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+        },
+        state::{AccountState, Mint},
+    };
+
+    use crate::{
+        error::WrapperError,
+        instruction::{self, WrapperInstruction},
+        processor::Processor,
+        state::{escrow_address, wrapper_address, wrapper_mint_address, Wrapper},
+    };
+
+    fn mint_data(
+        extensions: &[ExtensionType],
+        freeze_authority: COption<Pubkey>,
+        init: impl FnOnce(&mut StateWithExtensionsMut<Mint>),
+    ) -> Vec<u8> {
+        let mut data = if extensions.is_empty() {
+            vec![0u8; Mint::LEN]
+        } else {
+            vec![0u8; ExtensionType::try_calculate_account_len::<Mint>(extensions).unwrap()]
+        };
+        let mut mint = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        init(&mut mint);
+        mint.base = Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            supply: 0,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority,
+        };
+        mint.pack_base();
+        mint.init_account_type().unwrap();
+        data
+    }
+
+    /// Test instruction data round trip
+    #[test]
+    fn test_instruction_pack_unpack() {
+        for instruction in [
+            WrapperInstruction::InitializeWrapper,
+            WrapperInstruction::Wrap { amount: 1_000 },
+            WrapperInstruction::Unwrap { amount: u64::MAX },
+        ] {
+            let data = instruction.pack();
+            assert_eq!(WrapperInstruction::unpack(&data).unwrap(), instruction);
+        }
+        assert!(WrapperInstruction::unpack(&[]).is_err());
+        assert!(WrapperInstruction::unpack(&[1, 0, 0]).is_err());
+        assert!(WrapperInstruction::unpack(&[3]).is_err());
+    }
+
+    /// Test the wrapper accounts are distinct per underlying mint
+    #[test]
+    fn test_wrapper_addresses() {
+        let program_id = Pubkey::new_unique();
+        let underlying_mint = Pubkey::new_unique();
+        let other_mint = Pubkey::new_unique();
+
+        let wrapper = wrapper_address(&program_id, &underlying_mint).0;
+        let wrapper_mint = wrapper_mint_address(&program_id, &underlying_mint).0;
+        let escrow = escrow_address(&program_id, &underlying_mint).0;
+        assert_ne!(wrapper, wrapper_mint);
+        assert_ne!(wrapper, escrow);
+        assert_ne!(wrapper_mint, escrow);
+        assert_ne!(wrapper, wrapper_address(&program_id, &other_mint).0);
+        assert_eq!(Wrapper::LEN, 32 * 3 + 8 * 10);
+    }
+
+    /// Test the underlying mint can't freeze, pause or move the escrowed tokens
+    #[test]
+    fn test_check_underlying_mint() {
+        let forbidden: ProgramError = WrapperError::ForbiddenMintExtension.into();
+        let freezable: ProgramError = WrapperError::FreezableMint.into();
+
+        let plain = mint_data(&[], COption::None, |_| {});
+        assert_eq!(Processor::check_underlying_mint(&plain).unwrap(), 6);
+        let fee = mint_data(&[ExtensionType::TransferFeeConfig], COption::None, |mint| {
+            mint.init_extension::<TransferFeeConfig>(true).unwrap();
+        });
+        assert_eq!(Processor::check_underlying_mint(&fee).unwrap(), 6);
+
+        let with_freeze_authority = mint_data(&[], COption::Some(Pubkey::new_unique()), |_| {});
+        assert_eq!(
+            Processor::check_underlying_mint(&with_freeze_authority).unwrap_err(),
+            freezable
+        );
+
+        let default_state = |state: AccountState| {
+            mint_data(
+                &[ExtensionType::DefaultAccountState],
+                COption::None,
+                |mint| {
+                    mint.init_extension::<DefaultAccountState>(true)
+                        .unwrap()
+                        .state = state as u8;
+                },
+            )
+        };
+        assert_eq!(
+            Processor::check_underlying_mint(&default_state(AccountState::Frozen)).unwrap_err(),
+            freezable
+        );
+        assert_eq!(
+            Processor::check_underlying_mint(&default_state(AccountState::Initialized)).unwrap(),
+            6
+        );
+
+        let permanent_delegate =
+            mint_data(&[ExtensionType::PermanentDelegate], COption::None, |mint| {
+                mint.init_extension::<PermanentDelegate>(true).unwrap();
+            });
+        assert_eq!(
+            Processor::check_underlying_mint(&permanent_delegate).unwrap_err(),
+            forbidden
+        );
+
+        // Pausable (type 26) is unknown to this Token-2022 version
+        let mut pausable = mint_data(
+            &[ExtensionType::MintCloseAuthority],
+            COption::None,
+            |mint| {
+                mint.init_extension::<MintCloseAuthority>(true).unwrap();
+            },
+        );
+        let tlv_start = spl_token_2022::state::Account::LEN + 1;
+        pausable[tlv_start..tlv_start + 2].copy_from_slice(&26u16.to_le_bytes());
+        assert_eq!(
+            Processor::check_underlying_mint(&pausable).unwrap_err(),
+            forbidden
+        );
+    }
+
+    /// Test wrap and unwrap take the hook accounts after the fixed accounts
+    #[test]
+    fn test_wrap_accounts() {
+        let program_id = Pubkey::new_unique();
+        let underlying_mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let hook_accounts = vec![
+            solana_program::instruction::AccountMeta::new_readonly(Pubkey::new_unique(), false),
+            solana_program::instruction::AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        ];
+
+        let init = instruction::initialize_wrapper(&program_id, &owner, &underlying_mint);
+        assert_eq!(init.accounts.len(), 8);
+
+        let wrap = instruction::wrap(
+            &program_id,
+            &underlying_mint,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &owner,
+            &hook_accounts,
+            10,
+        );
+        assert_eq!(wrap.accounts.len(), 9 + hook_accounts.len());
+        assert_eq!(wrap.accounts[9..], hook_accounts[..]);
+        assert!(wrap.accounts[6].is_signer);
+
+        let unwrap = instruction::unwrap(
+            &program_id,
+            &underlying_mint,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &owner,
+            &[],
+            10,
+        );
+        assert_eq!(unwrap.accounts.len(), 9);
+        assert_eq!(
+            unwrap.data,
+            WrapperInstruction::Unwrap { amount: 10 }.pack()
+        );
+    }
+}
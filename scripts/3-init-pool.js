@@ -70,10 +70,11 @@ async function main() {
   const userCoin = asPk(getEnv('USER_COIN_ACCOUNT'));
   const userPc = asPk(getEnv('USER_PC_ACCOUNT'));
 
-  // Token program must be single and consistent. For Token-2022, both coin/pc must be 2022.
-  const tokenProgram = asPk(getEnv('COIN_TOKEN_PROGRAM', TOKEN_PROGRAM_ID.toBase58()));
-  
-  const userLp = getAssociatedTokenAddressSync(lpMint, payer.publicKey);
+  // Each side may use SPL Token or Token-2022. The lp mint follows the pc side program.
+  const coinTokenProgram = asPk(getEnv('COIN_TOKEN_PROGRAM', TOKEN_PROGRAM_ID.toBase58()));
+  const pcTokenProgram = asPk(getEnv('PC_TOKEN_PROGRAM', coinTokenProgram.toBase58()));
+
+  const userLp = getAssociatedTokenAddressSync(lpMint, payer.publicKey, false, pcTokenProgram);
 
   const initPc = BigInt(getEnv('INIT_PC', '1000000'));
   const initCoin = BigInt(getEnv('INIT_COIN', '1000000'));
@@ -82,7 +83,8 @@ async function main() {
   const data = packInitialize2({ nonce, openTime, initPcAmount: initPc, initCoinAmount: initCoin });
 
   const keys = [
-    meta(tokenProgram, false, false), // Token program (single)
+    meta(coinTokenProgram, false, false), // Coin side token program
+    meta(pcTokenProgram, false, false), // Pc side token program
     meta(ASSOCIATED_TOKEN_PROGRAM_ID, false, false),
     meta(SystemProgram.programId, false, false),
     meta(SYSVAR_RENT_PUBKEY, false, false),
@@ -50,10 +50,11 @@ async function main() {
 
   const userCoin = asPk(getEnv('USER_COIN_ACCOUNT'));
   const userPc = asPk(getEnv('USER_PC_ACCOUNT'));
-  // Use single token program for both coin and pc (Token-2022 allowed)
-  const tokenProgram = asPk(getEnv('COIN_TOKEN_PROGRAM', TOKEN_PROGRAM_ID.toBase58()));
-  
-  const userLp = getAssociatedTokenAddressSync(lpMint, payer.publicKey);
+  // Each side may use SPL Token or Token-2022. The lp mint follows the pc side program.
+  const coinTokenProgram = asPk(getEnv('COIN_TOKEN_PROGRAM', TOKEN_PROGRAM_ID.toBase58()));
+  const pcTokenProgram = asPk(getEnv('PC_TOKEN_PROGRAM', coinTokenProgram.toBase58()));
+
+  const userLp = getAssociatedTokenAddressSync(lpMint, payer.publicKey, false, pcTokenProgram);
 
   const maxCoin = BigInt(getEnv('DEPOSIT_MAX_COIN', '1000'));
   const maxPc = BigInt(getEnv('DEPOSIT_MAX_PC', '1000'));
@@ -64,7 +65,7 @@ async function main() {
   const data = packDeposit({ maxCoin, maxPc, baseSide, otherAmountMin });
 
   const keys = [
-    meta(tokenProgram, false, false), // Token program
+    meta(coinTokenProgram, false, false), // Coin side token program
     meta(ammPool, true, false),
     meta(authority, false, false),
     meta(openOrders, false, false),
@@ -78,6 +79,7 @@ async function main() {
     meta(userLp, true, false),
     meta(payer.publicKey, false, true),
     meta(marketEventQ, false, false),
+    meta(pcTokenProgram, false, false), // Pc side token program
   ];
 
   const instruction = ix(keys, programId, data);
@@ -46,16 +46,16 @@ async function main() {
   const userSource = asPk(getEnv('SWAP_SOURCE_ATA'));
   const userDest = asPk(getEnv('SWAP_DEST_ATA'));
   
-  // Get token programs (default to TOKEN_PROGRAM_ID for backwards compatibility)
-  const sourceTokenProgram = asPk(getEnv('SOURCE_TOKEN_PROGRAM', TOKEN_PROGRAM_ID.toBase58()));
-  const destTokenProgram = asPk(getEnv('DEST_TOKEN_PROGRAM', TOKEN_PROGRAM_ID.toBase58()));
+  // Token program of each pool side (default to TOKEN_PROGRAM_ID for backwards compatibility)
+  const coinTokenProgram = asPk(getEnv('COIN_TOKEN_PROGRAM', TOKEN_PROGRAM_ID.toBase58()));
+  const pcTokenProgram = asPk(getEnv('PC_TOKEN_PROGRAM', coinTokenProgram.toBase58()));
 
   const amountIn = BigInt(getEnv('SWAP_AMOUNT_IN', '100'));
   const minimumOut = BigInt(getEnv('SWAP_MINIMUM_OUT', '1'));
   const data = packSwapBaseIn({ amountIn, minimumOut });
 
   const keys = [
-    meta(sourceTokenProgram, false, false), // Source token program
+    meta(coinTokenProgram, false, false), // Coin side token program
     meta(ammPool, true, false),
     meta(authority, false, false),
     meta(openOrders, true, false),
@@ -75,14 +75,14 @@ async function main() {
     meta(payer.publicKey, false, true),
   ];
   
-  // Add destination token program if different from source
-  if (!destTokenProgram.equals(sourceTokenProgram)) {
-    keys.push(meta(destTokenProgram, false, false));
-  }
-  
   // TODO: Add remaining accounts for transfer hooks if needed
   // This would require reading the mint extensions and building the proper account list
 
+  // Pc side token program goes last when it differs from the coin side
+  if (!pcTokenProgram.equals(coinTokenProgram)) {
+    keys.push(meta(pcTokenProgram, false, false));
+  }
+
   const instruction = ix(keys, programId, data);
   const tx = new Transaction().add(instruction);
   const sig = await connection.sendTransaction(tx, [payer], { skipPreflight: true });